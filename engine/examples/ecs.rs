@@ -24,7 +24,7 @@ fn main() {
 	let entity = entity_manager.create_entity();
 	renderable_components.add_defer(entity, Renderable);
 	transform_components.add_defer(entity, Transform3D::new());
-	mesh_components.add_defer(entity, Mesh { geometry_handle: geo_handle, material: Material::Normal });
+	mesh_components.add_defer(entity, Mesh::new(geo_handle, Material::Normal));
 
 	let (extent_width, extent_height) = render_system.get_swapchain_extent();
 	let mut camera = Camera::new(extent_width as f32 / extent_height as f32, 75.0, 0.1, 50.0);
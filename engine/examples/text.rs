@@ -28,9 +28,9 @@ fn main() {
 	mesh_node.transform.translate_z(2.0);
 	let mesh_handle = scene.graph.add(mesh_node);
 
-	let font = Font::new("game/res/roboto.ttf", 32);
+	let font = Font::new(renderer.vfs(), "game/res/roboto.ttf", 32);
 	let font_handle = scene.fonts.add(font);
-	renderer.submit_fonts(&mut scene.fonts);
+	renderer.submit_fonts(&mut scene.fonts).unwrap();
 
 	let mut text = Text::new(font_handle, String::from("This is some text!"));
 	text.transform.position.set(50.0, 80.0);
@@ -0,0 +1,177 @@
+use crate::math::{Quaternion, Vector3};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Keyframe {
+	// Seconds from the start of the clip
+	pub time: f32,
+	pub position: Vector3,
+	pub orientation: Quaternion
+}
+
+// The keyframes of one joint sorted by time, the joint is an index into the animated entity's list of joints
+pub struct AnimationTrack {
+	pub joint: usize,
+	pub keyframes: Vec<Keyframe>
+}
+
+impl AnimationTrack {
+	pub fn new(joint: usize, keyframes: Vec<Keyframe>) -> Self {
+		assert!(!keyframes.is_empty(), "Cannot create an animation track without any keyframes");
+		assert!(keyframes.windows(2).all(|pair| pair[0].time <= pair[1].time), "Animation track keyframes must be sorted by time");
+
+		Self {
+			joint,
+			keyframes
+		}
+	}
+
+	// The joint's local position and orientation at the time, held at the first and last keyframes outside of them
+	pub fn sample(&self, time: f32) -> (Vector3, Quaternion) {
+		let next_index = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+
+		if next_index == 0 {
+			let first = &self.keyframes[0];
+			return (first.position, first.orientation);
+		}
+
+		let previous = &self.keyframes[next_index - 1];
+
+		let Some(next) = self.keyframes.get(next_index) else {
+			return (previous.position, previous.orientation);
+		};
+
+		let t = (time - previous.time) / (next.time - previous.time);
+		let mut orientation = previous.orientation;
+		orientation.slerp(&next.orientation, t);
+
+		(previous.position + (next.position - previous.position) * t, orientation)
+	}
+
+	// The pose with the root motion taken out, which is where the joint starts. Flattened root motion only takes out the
+	// movement along the ground and the turning about the y axis.
+	pub fn sample_without_root_motion(&self, time: f32, flatten: bool) -> (Vector3, Quaternion) {
+		let first = &self.keyframes[0];
+
+		if !flatten {
+			return (first.position, first.orientation);
+		}
+
+		let (position, orientation) = self.sample(time);
+		let mut heading = yaw(&orientation);
+		heading.conjigate();
+
+		(Vector3::new(first.position.x, position.y, first.position.z), yaw(&first.orientation) * heading * orientation)
+	}
+
+	// How far the joint moved and turned from the previous time to the time, in the space of the way it faced at the previous
+	// time. A time before the previous time wrapped around the end of a looping clip of the duration.
+	pub fn root_motion(&self, previous_time: f32, time: f32, duration: f32, flatten: bool) -> (Vector3, Quaternion) {
+		if time < previous_time {
+			let (to_end, end_turn) = self.root_motion(previous_time, duration, duration, flatten);
+			let (mut from_start, start_turn) = self.root_motion(0.0, time, duration, flatten);
+			from_start.apply_quaternion(&end_turn);
+			return (to_end + from_start, end_turn * start_turn);
+		}
+
+		let heading_at = |time: f32| {
+			let (position, orientation) = self.sample(time);
+			(position, if flatten { yaw(&orientation) } else { orientation })
+		};
+
+		let (previous_position, mut previous_heading) = heading_at(previous_time);
+		let (position, heading) = heading_at(time);
+		previous_heading.conjigate();
+
+		let mut moved = position - previous_position;
+
+		if flatten {
+			moved.y = 0.0;
+		}
+
+		moved.apply_quaternion(&previous_heading);
+		(moved, previous_heading * heading)
+	}
+}
+
+// The turn of the orientation about the y axis
+fn yaw(orientation: &Quaternion) -> Quaternion {
+	let mut yaw = Quaternion::new(0.0, orientation.y, 0.0, orientation.w);
+	yaw.normalize();
+	yaw
+}
+
+// Keyframed positions and orientations of a hierarchy of joints, such as the limbs of a character made of separate meshes.
+// Clips are kept in a pool and played by animation controllers. Scale isn't animated.
+pub struct AnimationClip {
+	pub duration: f32,
+	pub tracks: Vec<AnimationTrack>
+}
+
+impl AnimationClip {
+	pub fn new(duration: f32, tracks: Vec<AnimationTrack>) -> Self {
+		assert!(duration > 0.0, "Animation clips must have a duration");
+
+		Self {
+			duration,
+			tracks
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+	use crate::math::{assert_approx_eq, quaternion};
+
+	#[test]
+	fn samples_between_keyframes() {
+		let mut turned = quaternion::ZERO;
+		turned.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 1.0);
+
+		let track = AnimationTrack::new(0, vec![
+			Keyframe { time: 0.0, position: Vector3::new(0.0, 0.0, 0.0), orientation: quaternion::ZERO },
+			Keyframe { time: 2.0, position: Vector3::new(4.0, 0.0, 0.0), orientation: turned }
+		]);
+
+		let (position, orientation) = track.sample(1.0);
+		assert_approx_eq(&position, &Vector3::new(2.0, 0.0, 0.0), 1e-6);
+
+		let mut half_turned = quaternion::ZERO;
+		half_turned.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5);
+		assert_approx_eq(&orientation, &half_turned, 1e-5);
+
+		// Held outside of the keyframes
+		assert_eq!(track.sample(-1.0).0, Vector3::new(0.0, 0.0, 0.0));
+		assert_eq!(track.sample(3.0).0, Vector3::new(4.0, 0.0, 0.0));
+	}
+
+	#[test]
+	fn extracts_root_motion() {
+		// Walks forward 2 units with a bob and turns a quarter to the left over a second
+		let mut turned = quaternion::ZERO;
+		turned.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), FRAC_PI_2);
+
+		let track = AnimationTrack::new(0, vec![
+			Keyframe { time: 0.0, position: Vector3::new(0.0, 1.0, 0.0), orientation: quaternion::ZERO },
+			Keyframe { time: 1.0, position: Vector3::new(0.0, 1.5, 2.0), orientation: turned }
+		]);
+
+		let (moved, turn) = track.root_motion(0.0, 0.5, 1.0, true);
+		assert_approx_eq(&moved, &Vector3::new(0.0, 0.0, 1.0), 1e-5);
+
+		let mut eighth_turn = quaternion::ZERO;
+		eighth_turn.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), FRAC_PI_4);
+		assert_approx_eq(&turn, &eighth_turn, 1e-5);
+
+		// Wrapping around the end of the loop adds both parts
+		let (moved, turn) = track.root_motion(0.5, 0.0, 1.0, true);
+		assert_approx_eq(&turn, &eighth_turn, 1e-5);
+		assert!((moved.length() - 1.0).abs() < 0.1);
+
+		// The bob is kept on the joint but not the walk or the turn
+		let (position, orientation) = track.sample_without_root_motion(0.5, true);
+		assert_approx_eq(&position, &Vector3::new(0.0, 1.25, 0.0), 1e-5);
+		assert_approx_eq(&orientation, &quaternion::ZERO, 1e-5);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,276 @@
+use std::{sync::{Arc, Condvar, Mutex, mpsc}, thread::{self, JoinHandle}};
+use crate::{Geometry3D, geometry3d::Topology, pool::{Handle, Pool}};
+
+// What a loader produces on a worker thread, it's put into the geometry on the main thread
+pub struct GeometryData {
+	pub indices: Vec<u16>,
+	pub attributes: Vec<f32>,
+	pub topology: Topology
+}
+
+impl GeometryData {
+	pub fn new(indices: Vec<u16>, attributes: Vec<f32>, topology: Topology) -> Self {
+		Self { indices, attributes, topology }
+	}
+}
+
+type Loader = Box<dyn FnOnce() -> Result<GeometryData, String> + Send>;
+
+struct Job {
+	handle: Handle,
+	priority: i32,
+	sequence: u64,
+	load: Loader
+}
+
+struct Queue {
+	jobs: Vec<Job>,
+	shutdown: bool
+}
+
+// Loads geometry on background threads so big levels can stream in without stalling the render loop. Requesting a
+// geometry adds a placeholder box to the pool straight away so meshes can use the handle, the placeholder is replaced in
+// place once the real geometry is loaded so nothing referencing the handle needs to change. Jobs with a higher priority are
+// started first and jobs with the same priority in the order they were requested.
+pub struct AssetStreamer {
+	queue: Arc<(Mutex<Queue>, Condvar)>,
+	results: mpsc::Receiver<(Handle, Result<GeometryData, String>)>,
+	workers: Vec<JoinHandle<()>>,
+	next_sequence: u64,
+	requested_count: usize,
+	finished_count: usize
+}
+
+impl AssetStreamer {
+	pub fn new(worker_count: usize) -> Self {
+		assert!(worker_count > 0, "An asset streamer needs at least 1 worker thread");
+
+		let queue = Arc::new((Mutex::new(Queue { jobs: Vec::new(), shutdown: false }), Condvar::new()));
+		let (sender, results) = mpsc::channel();
+
+		let workers = (0..worker_count).map(|index| {
+			let queue = Arc::clone(&queue);
+			let sender = sender.clone();
+
+			thread::Builder::new()
+				.name(format!("asset streamer {}", index))
+				.spawn(move || Self::work(&queue, &sender))
+				.unwrap()
+		}).collect();
+
+		Self {
+			queue,
+			results,
+			workers,
+			next_sequence: 0,
+			requested_count: 0,
+			finished_count: 0
+		}
+	}
+
+	fn work(queue: &(Mutex<Queue>, Condvar), sender: &mpsc::Sender<(Handle, Result<GeometryData, String>)>) {
+		let (mutex, condvar) = queue;
+
+		loop {
+			let job = {
+				let mut queue = mutex.lock().unwrap();
+
+				while queue.jobs.is_empty() && !queue.shutdown {
+					queue = condvar.wait(queue).unwrap();
+				}
+
+				if queue.shutdown {
+					return;
+				}
+
+				let next = (0..queue.jobs.len())
+					.max_by_key(|index| (queue.jobs[*index].priority, std::cmp::Reverse(queue.jobs[*index].sequence)))
+					.unwrap();
+
+				queue.jobs.remove(next)
+			};
+
+			// The streamer being dropped closes the channel, there's nobody to tell
+			if sender.send((job.handle, (job.load)())).is_err() {
+				return;
+			}
+		}
+	}
+
+	// The loader runs on a worker thread, it could read a file through a Vfs and parse it
+	pub fn request_geometry(&mut self, geometries: &mut Pool<Geometry3D>, priority: i32, load: impl FnOnce() -> Result<GeometryData, String> + Send + 'static) -> Handle {
+		let handle = geometries.add(Geometry3D::create_box());
+
+		// Progress starts over for a new batch once the last one is done
+		if self.finished_count == self.requested_count {
+			self.requested_count = 0;
+			self.finished_count = 0;
+		}
+
+		self.requested_count += 1;
+		let sequence = self.next_sequence;
+		self.next_sequence += 1;
+
+		let (mutex, condvar) = &*self.queue;
+		mutex.lock().unwrap().jobs.push(Job { handle, priority, sequence, load: Box::new(load) });
+		condvar.notify_one();
+
+		handle
+	}
+
+	// Only affects jobs which haven't started, such as raising the priority of things which came into view
+	pub fn set_priority(&self, handle: Handle, priority: i32) {
+		let mut queue = self.queue.0.lock().unwrap();
+
+		if let Some(job) = queue.jobs.iter_mut().find(|job| job.handle == handle) {
+			job.priority = priority;
+		}
+	}
+
+	// Drops a job which hasn't started, the placeholder stays in the pool. Returns whether the job was dropped.
+	pub fn cancel(&mut self, handle: Handle) -> bool {
+		let mut queue = self.queue.0.lock().unwrap();
+		let job_count = queue.jobs.len();
+		queue.jobs.retain(|job| job.handle != handle);
+
+		let cancelled = queue.jobs.len() < job_count;
+
+		if cancelled {
+			self.requested_count -= 1;
+		}
+
+		cancelled
+	}
+
+	// Puts the geometry which has finished loading into the pool, call it once a frame. Geometry which failed to load keeps
+	// its placeholder. Returns the handles which finished.
+	pub fn update(&mut self, geometries: &mut Pool<Geometry3D>) -> Vec<Handle> {
+		let mut finished = Vec::new();
+
+		for (handle, result) in self.results.try_iter() {
+			self.finished_count += 1;
+			finished.push(handle);
+
+			match (result, geometries.try_borrow_mut(handle)) {
+				(Ok(data), Some(geometry)) => geometry.set(data.indices, data.attributes, data.topology),
+				(Err(error), _) => println!("Could not load geometry: {}", error),
+				// The placeholder was removed while it was loading
+				(Ok(_), None) => ()
+			}
+		}
+
+		finished
+	}
+
+	// From 0 to 1 for the batch of requests made since everything was last loaded, for a loading screen
+	pub fn progress(&self) -> f32 {
+		if self.requested_count == 0 {
+			1.0
+		}
+		else {
+			self.finished_count as f32 / self.requested_count as f32
+		}
+	}
+
+	pub fn is_loading(&self) -> bool {
+		self.finished_count < self.requested_count
+	}
+}
+
+impl Drop for AssetStreamer {
+	// Jobs which haven't started are dropped, the ones in progress are waited for
+	fn drop(&mut self) {
+		let (mutex, condvar) = &*self.queue;
+		mutex.lock().unwrap().shutdown = true;
+		condvar.notify_all();
+
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::{Duration, Instant};
+
+	fn triangle(size: f32) -> GeometryData {
+		GeometryData::new(vec![0, 1, 2], vec![
+			0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+			size, 0.0, 0.0, 0.0, 0.0, 1.0,
+			0.0, size, 0.0, 0.0, 0.0, 1.0
+		], Topology::Triangle)
+	}
+
+	fn wait_until_loaded(streamer: &mut AssetStreamer, geometries: &mut Pool<Geometry3D>) {
+		let start = Instant::now();
+
+		while streamer.is_loading() {
+			streamer.update(geometries);
+			assert!(start.elapsed() < Duration::from_secs(10), "Loading took too long");
+			thread::sleep(Duration::from_millis(1));
+		}
+	}
+
+	#[test]
+	fn placeholders_are_replaced() {
+		let mut streamer = AssetStreamer::new(2);
+		let mut geometries = Pool::new();
+
+		let handle = streamer.request_geometry(&mut geometries, 0, || Ok(triangle(5.0)));
+		let failed_handle = streamer.request_geometry(&mut geometries, 0, || Err(String::from("missing file")));
+		assert_eq!(geometries.borrow(handle).indices().len(), 36);
+		assert!(streamer.progress() < 1.0);
+
+		wait_until_loaded(&mut streamer, &mut geometries);
+		assert_eq!(streamer.progress(), 1.0);
+		assert_eq!(geometries.borrow(handle).indices(), &[0, 1, 2]);
+		assert_eq!(geometries.borrow(handle).bounding_box().max.x, 5.0);
+		assert_eq!(geometries.borrow(failed_handle).indices().len(), 36);
+
+		// A new batch starts its progress over
+		streamer.request_geometry(&mut geometries, 0, || Ok(triangle(1.0)));
+		assert_eq!(streamer.progress(), 0.0);
+		wait_until_loaded(&mut streamer, &mut geometries);
+	}
+
+	#[test]
+	fn higher_priorities_load_first() {
+		let mut streamer = AssetStreamer::new(1);
+		let mut geometries = Pool::new();
+		let order = Arc::new(Mutex::new(Vec::new()));
+
+		// Hold the only worker until everything is queued
+		let (release, gate) = mpsc::channel::<()>();
+		let (started, wait_for_start) = mpsc::channel::<()>();
+		streamer.request_geometry(&mut geometries, 0, move || {
+			started.send(()).unwrap();
+			gate.recv().unwrap();
+			Ok(triangle(1.0))
+		});
+
+		wait_for_start.recv().unwrap();
+
+		let mut request = |priority: i32, name: &'static str| {
+			let order = Arc::clone(&order);
+			streamer.request_geometry(&mut geometries, priority, move || {
+				order.lock().unwrap().push(name);
+				Ok(triangle(1.0))
+			})
+		};
+
+		request(0, "low");
+		let raised = request(0, "raised");
+		request(5, "high");
+		request(5, "high later");
+		let cancelled = request(10, "cancelled");
+
+		streamer.set_priority(raised, 7);
+		assert!(streamer.cancel(cancelled));
+		release.send(()).unwrap();
+
+		wait_until_loaded(&mut streamer, &mut geometries);
+		assert_eq!(*order.lock().unwrap(), vec!["raised", "high", "high later", "low"]);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,220 @@
+// A run of the top edge of everything packed so far, the runs cover the width of the atlas from left to right
+struct SkylineNode {
+	x: usize,
+	y: usize,
+	width: usize
+}
+
+// Packs rectangles into an atlas for glyphs, sprite sheets and icons. Each rectangle goes as low as it fits on the skyline, the
+// top edge of everything packed so far, which wastes little space when the rectangles are packed tallest first. Rectangles
+// can keep being inserted into a packer, such as glyphs generated while the game runs.
+pub struct AtlasPacker {
+	width: usize,
+	height: usize,
+	skyline: Vec<SkylineNode>,
+	used_area: usize
+}
+
+impl AtlasPacker {
+	pub fn new(width: usize, height: usize) -> Self {
+		assert!(width > 0 && height > 0, "Cannot create an empty atlas");
+
+		Self {
+			width,
+			height,
+			skyline: vec![SkylineNode { x: 0, y: 0, width }],
+			used_area: 0
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	// The fraction of the atlas taken up by rectangles
+	pub fn occupancy(&self) -> f32 {
+		self.used_area as f32 / (self.width * self.height) as f32
+	}
+
+	// Where the top left corner of the rectangle went, None when it doesn't fit anywhere
+	pub fn insert(&mut self, width: usize, height: usize) -> Option<(usize, usize)> {
+		assert!(width > 0 && height > 0, "Cannot pack an empty rectangle");
+
+		// The lowest place, leftmost among the lowest
+		let mut best: Option<(usize, usize)> = None;
+
+		for index in 0..self.skyline.len() {
+			if let Some(y) = self.fit(index, width, height) {
+				match best {
+					Some((_, best_y)) if best_y <= y => (),
+					_ => best = Some((index, y))
+				}
+			}
+		}
+
+		let (index, y) = best?;
+		let x = self.skyline[index].x;
+		self.skyline.insert(index, SkylineNode { x, y: y + height, width });
+
+		// Cut away the parts of the skyline the rectangle now covers
+		let right = x + width;
+
+		while let Some(node) = self.skyline.get_mut(index + 1) {
+			if node.x >= right {
+				break;
+			}
+
+			let overlap = right - node.x;
+
+			if overlap < node.width {
+				node.x += overlap;
+				node.width -= overlap;
+				break;
+			}
+
+			self.skyline.remove(index + 1);
+		}
+
+		// Join neighbors at the same height so there are fewer places to try
+		let mut i = 0;
+
+		while i + 1 < self.skyline.len() {
+			if self.skyline[i].y == self.skyline[i + 1].y {
+				self.skyline[i].width += self.skyline[i + 1].width;
+				self.skyline.remove(i + 1);
+			}
+			else {
+				i += 1;
+			}
+		}
+
+		self.used_area += width * height;
+		Some((x, y))
+	}
+
+	// The height the rectangle would sit at with its left edge at the start of the skyline node
+	fn fit(&self, index: usize, width: usize, height: usize) -> Option<usize> {
+		if self.skyline[index].x + width > self.width {
+			return None;
+		}
+
+		let mut y = 0;
+		let mut remaining_width = width;
+
+		for node in &self.skyline[index..] {
+			if remaining_width == 0 {
+				break;
+			}
+
+			y = y.max(node.y);
+			remaining_width = remaining_width.saturating_sub(node.width);
+		}
+
+		if y + height > self.height { None } else { Some(y) }
+	}
+
+	// Packs rectangles which are all known up front into a power of two sized atlas, starting from the smallest one they could
+	// fit in and doubling the shorter side until they do. Returns the width and height of the part of the atlas that was used
+	// and the top left corner of each rectangle.
+	pub fn pack(sizes: &[(usize, usize)]) -> (usize, usize, Vec<(usize, usize)>) {
+		if sizes.is_empty() {
+			return (0, 0, vec![]);
+		}
+
+		// Tallest first then widest first
+		let mut order: Vec<usize> = (0..sizes.len()).collect();
+		order.sort_unstable_by_key(|i| (usize::MAX - sizes[*i].1, usize::MAX - sizes[*i].0));
+
+		let area: usize = sizes.iter().map(|(width, height)| width * height).sum();
+		let side = ((area as f64).sqrt().ceil() as usize).next_power_of_two();
+		let mut width = side.max(sizes.iter().map(|size| size.0).max().unwrap().next_power_of_two());
+		let mut height = side.max(sizes.iter().map(|size| size.1).max().unwrap().next_power_of_two());
+
+		loop {
+			let mut packer = Self::new(width, height);
+			let mut positions = vec![(0, 0); sizes.len()];
+
+			let packed = order.iter().all(|i| match packer.insert(sizes[*i].0, sizes[*i].1) {
+				Some(position) => {
+					positions[*i] = position;
+					true
+				},
+				None => false
+			});
+
+			if packed {
+				let used_width = positions.iter().zip(sizes).map(|((x, _), (width, _))| x + width).max().unwrap();
+				let used_height = positions.iter().zip(sizes).map(|((_, y), (_, height))| y + height).max().unwrap();
+				return (used_width, used_height, positions);
+			}
+
+			if width <= height {
+				width *= 2;
+			}
+			else {
+				height *= 2;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_no_overlaps(sizes: &[(usize, usize)], positions: &[(usize, usize)], width: usize, height: usize) {
+		for (i, (&(ax, ay), &(aw, ah))) in positions.iter().zip(sizes).enumerate() {
+			assert!(ax + aw <= width && ay + ah <= height, "Rectangle {} is outside the atlas", i);
+
+			for (j, (&(bx, by), &(bw, bh))) in positions.iter().zip(sizes).enumerate().skip(i + 1) {
+				let overlaps = ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah;
+				assert!(!overlaps, "Rectangles {} and {} overlap", i, j);
+			}
+		}
+	}
+
+	#[test]
+	fn fills_an_atlas_with_squares() {
+		let mut packer = AtlasPacker::new(32, 32);
+		let mut sizes = vec![];
+		let mut positions = vec![];
+
+		for _ in 0..16 {
+			positions.push(packer.insert(8, 8).unwrap());
+			sizes.push((8, 8));
+		}
+
+		assert_no_overlaps(&sizes, &positions, 32, 32);
+		assert_eq!(packer.occupancy(), 1.0);
+		assert_eq!(packer.insert(1, 1), None);
+	}
+
+	#[test]
+	fn packs_mixed_sizes_tightly() {
+		// Sizes like the glyphs of a font
+		let mut state: u32 = 0x2545_f491;
+		let mut next = |max: u32| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			(state % max) as usize + 2
+		};
+
+		let sizes: Vec<(usize, usize)> = (0..300).map(|_| (next(20), next(28))).collect();
+		let (width, height, positions) = AtlasPacker::pack(&sizes);
+		assert_no_overlaps(&sizes, &positions, width, height);
+
+		let area: usize = sizes.iter().map(|(width, height)| width * height).sum();
+		let efficiency = area as f32 / (width * height) as f32;
+		assert!(efficiency > 0.75, "Only {:.0}% of the atlas is used", efficiency * 100.0);
+
+		// A rectangle wider than the estimate widens the atlas and the smaller one fits next to it
+		let (width, height, positions) = AtlasPacker::pack(&[(100, 2), (3, 3)]);
+		assert_no_overlaps(&[(100, 2), (3, 3)], &positions, width, height);
+		assert_eq!((width, height), (103, 3));
+	}
+}
\ No newline at end of file
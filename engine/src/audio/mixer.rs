@@ -0,0 +1,330 @@
+use std::time::Duration;
+
+// Everything is mixed as interleaved left and right samples
+pub const CHANNEL_COUNT: usize = 2;
+
+// Once a track reaches the end of the region it jumps back to the start, so an intro before the region only plays once
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LoopRegion {
+	pub start: Duration,
+	// The end of the track when there isn't one
+	pub end: Option<Duration>
+}
+
+// Produces stereo frames at the mixer's sample rate
+pub trait SampleSource: Send {
+	// Fills the start of the samples with interleaved frames and returns how many samples were written. Fewer than were asked
+	// for means the source has ended.
+	fn read(&mut self, samples: &mut [f32]) -> usize;
+
+	// Moves to the frame so the next read starts there
+	fn seek(&mut self, frame: u64);
+}
+
+// A linear ramp of the gain over a number of frames
+struct Fade {
+	from: f32,
+	to: f32,
+	frame_count: u64,
+	elapsed_frame_count: u64
+}
+
+impl Fade {
+	fn constant(gain: f32) -> Self {
+		Self {
+			from: gain,
+			to: gain,
+			frame_count: 0,
+			elapsed_frame_count: 0
+		}
+	}
+
+	fn gain(&self) -> f32 {
+		if self.elapsed_frame_count >= self.frame_count {
+			self.to
+		}
+		else {
+			self.from + (self.to - self.from) * self.elapsed_frame_count as f32 / self.frame_count as f32
+		}
+	}
+
+	fn is_silent(&self) -> bool {
+		self.to == 0.0 && self.elapsed_frame_count >= self.frame_count
+	}
+}
+
+struct Track {
+	source: Box<dyn SampleSource>,
+	// The loop region's start and end in frames
+	loop_frames: Option<(u64, Option<u64>)>,
+	position: u64,
+	fade: Fade,
+	ended: bool
+}
+
+impl Track {
+	// Reads up to the end of the loop region before jumping back to its start. A track whose region has nothing in it ends
+	// rather than seeking forever.
+	fn read(&mut self, samples: &mut [f32]) -> usize {
+		let mut written = 0;
+		let mut read_since_seek = true;
+
+		while written < samples.len() {
+			let remaining = samples.len() - written;
+			let available = match self.loop_frames {
+				Some((_, Some(end))) => remaining.min(end.saturating_sub(self.position) as usize * CHANNEL_COUNT),
+				_ => remaining
+			};
+
+			let read = self.source.read(&mut samples[written..written + available]);
+			written += read;
+			self.position += (read / CHANNEL_COUNT) as u64;
+			read_since_seek |= read > 0;
+
+			if written == samples.len() {
+				break;
+			}
+
+			match self.loop_frames {
+				Some((start, _)) if read_since_seek => {
+					self.source.seek(start);
+					self.position = start;
+					read_since_seek = false;
+				},
+				_ => {
+					self.ended = true;
+					break;
+				}
+			}
+		}
+
+		written
+	}
+}
+
+// Mixes music tracks together, fading between them when a new one is played. Tracks that have faded out or ended are
+// dropped.
+pub struct Mixer {
+	sample_rate: u32,
+	volume: f32,
+	tracks: Vec<Track>,
+	scratch: Vec<f32>
+}
+
+impl Mixer {
+	pub fn new(sample_rate: u32) -> Self {
+		Self {
+			sample_rate,
+			volume: 1.0,
+			tracks: vec![],
+			scratch: vec![]
+		}
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate
+	}
+
+	fn frame_count(&self, duration: Duration) -> u64 {
+		(duration.as_secs_f64() * self.sample_rate as f64).round() as u64
+	}
+
+	// The source fades in over the crossfade while whatever was playing fades out
+	pub fn play(&mut self, source: Box<dyn SampleSource>, loop_region: Option<LoopRegion>, crossfade: Duration) {
+		let crossfade_frame_count = self.frame_count(crossfade);
+		self.fade_out(crossfade_frame_count);
+
+		let loop_frames = loop_region.map(|region| (self.frame_count(region.start), region.end.map(|end| self.frame_count(end))));
+		let fade = if crossfade_frame_count == 0 {
+			Fade::constant(1.0)
+		}
+		else {
+			Fade {
+				from: 0.0,
+				to: 1.0,
+				frame_count: crossfade_frame_count,
+				elapsed_frame_count: 0
+			}
+		};
+
+		self.tracks.push(Track {
+			source,
+			loop_frames,
+			position: 0,
+			fade,
+			ended: false
+		});
+	}
+
+	pub fn stop(&mut self, fade: Duration) {
+		let fade_frame_count = self.frame_count(fade);
+		self.fade_out(fade_frame_count);
+	}
+
+	// Tracks already fading start from the gain they're at
+	fn fade_out(&mut self, frame_count: u64) {
+		if frame_count == 0 {
+			self.tracks.clear();
+			return;
+		}
+
+		for track in &mut self.tracks {
+			track.fade = Fade {
+				from: track.fade.gain(),
+				to: 0.0,
+				frame_count,
+				elapsed_frame_count: 0
+			};
+		}
+	}
+
+	pub fn set_volume(&mut self, volume: f32) {
+		self.volume = volume;
+	}
+
+	pub fn is_playing(&self) -> bool {
+		!self.tracks.is_empty()
+	}
+
+	// Fills the interleaved samples with the tracks' frames, silence is written when nothing is playing
+	pub fn mix(&mut self, samples: &mut [f32]) {
+		samples.fill(0.0);
+		self.scratch.resize(samples.len(), 0.0);
+
+		for track in &mut self.tracks {
+			let read = track.read(&mut self.scratch[..samples.len()]);
+
+			for (frame, track_frame) in samples[..read].chunks_exact_mut(CHANNEL_COUNT).zip(self.scratch.chunks_exact(CHANNEL_COUNT)) {
+				let gain = track.fade.gain() * self.volume;
+
+				for (sample, track_sample) in frame.iter_mut().zip(track_frame) {
+					*sample += track_sample * gain;
+				}
+
+				track.fade.elapsed_frame_count += 1;
+			}
+		}
+
+		self.tracks.retain(|track| !track.ended && !track.fade.is_silent());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Each frame's samples are its index in the source
+	struct Ramp {
+		frame_count: u64,
+		position: u64
+	}
+
+	impl SampleSource for Ramp {
+		fn read(&mut self, samples: &mut [f32]) -> usize {
+			let mut written = 0;
+
+			for frame in samples.chunks_exact_mut(CHANNEL_COUNT) {
+				if self.position == self.frame_count {
+					break;
+				}
+
+				frame.fill(self.position as f32);
+				self.position += 1;
+				written += CHANNEL_COUNT;
+			}
+
+			written
+		}
+
+		fn seek(&mut self, frame: u64) {
+			self.position = frame.min(self.frame_count);
+		}
+	}
+
+	struct Constant(f32);
+
+	impl SampleSource for Constant {
+		fn read(&mut self, samples: &mut [f32]) -> usize {
+			samples.fill(self.0);
+			samples.len()
+		}
+
+		fn seek(&mut self, _frame: u64) {}
+	}
+
+	fn ramp(frame_count: u64) -> Box<dyn SampleSource> {
+		Box::new(Ramp { frame_count, position: 0 })
+	}
+
+	// The mixers run at ten frames a second so a frame is 100 milliseconds
+	fn milliseconds(milliseconds: u64) -> Duration {
+		Duration::from_millis(milliseconds)
+	}
+
+	// The left channel of the next frames
+	fn mix_left(mixer: &mut Mixer, frame_count: usize) -> Vec<f32> {
+		let mut samples = vec![0.0; frame_count * CHANNEL_COUNT];
+		mixer.mix(&mut samples);
+		samples.iter().step_by(CHANNEL_COUNT).copied().collect()
+	}
+
+	#[test]
+	fn track_ends_without_a_loop_region() {
+		let mut mixer = Mixer::new(10);
+		mixer.play(ramp(3), None, Duration::ZERO);
+
+		assert_eq!(mix_left(&mut mixer, 5), vec![0.0, 1.0, 2.0, 0.0, 0.0]);
+		assert!(!mixer.is_playing());
+	}
+
+	#[test]
+	fn loop_region_repeats_after_the_intro() {
+		let mut mixer = Mixer::new(10);
+		let loop_region = LoopRegion { start: milliseconds(200), end: Some(milliseconds(500)) };
+		mixer.play(ramp(10), Some(loop_region), Duration::ZERO);
+
+		assert_eq!(mix_left(&mut mixer, 8), vec![0.0, 1.0, 2.0, 3.0, 4.0, 2.0, 3.0, 4.0]);
+		assert_eq!(mix_left(&mut mixer, 3), vec![2.0, 3.0, 4.0]);
+		assert!(mixer.is_playing());
+	}
+
+	#[test]
+	fn loop_region_without_an_end_repeats_to_the_end_of_the_track() {
+		let mut mixer = Mixer::new(10);
+		let loop_region = LoopRegion { start: milliseconds(100), end: None };
+		mixer.play(ramp(4), Some(loop_region), Duration::ZERO);
+
+		assert_eq!(mix_left(&mut mixer, 8), vec![0.0, 1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0]);
+	}
+
+	#[test]
+	fn empty_loop_region_ends_the_track() {
+		let mut mixer = Mixer::new(10);
+		let loop_region = LoopRegion { start: milliseconds(500), end: None };
+		mixer.play(ramp(3), Some(loop_region), Duration::ZERO);
+
+		assert_eq!(mix_left(&mut mixer, 4), vec![0.0, 1.0, 2.0, 0.0]);
+		assert!(!mixer.is_playing());
+	}
+
+	#[test]
+	fn crossfade_ramps_between_tracks() {
+		let mut mixer = Mixer::new(10);
+		mixer.play(Box::new(Constant(1.0)), None, Duration::ZERO);
+		mixer.play(Box::new(Constant(10.0)), None, milliseconds(400));
+
+		assert_eq!(mix_left(&mut mixer, 5), vec![1.0, 3.25, 5.5, 7.75, 10.0]);
+		assert_eq!(mixer.tracks.len(), 1);
+	}
+
+	#[test]
+	fn stop_fades_out_and_drops_the_track() {
+		let mut mixer = Mixer::new(10);
+		mixer.play(Box::new(Constant(1.0)), None, Duration::ZERO);
+		mixer.set_volume(0.5);
+		mixer.stop(milliseconds(200));
+
+		assert_eq!(mix_left(&mut mixer, 3), vec![0.5, 0.25, 0.0]);
+		assert!(!mixer.is_playing());
+	}
+}
\ No newline at end of file
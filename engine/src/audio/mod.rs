@@ -0,0 +1,7 @@
+pub mod mixer;
+pub use mixer::{Mixer, SampleSource, LoopRegion, CHANNEL_COUNT};
+
+#[cfg(feature = "audio")]
+pub mod music_stream;
+#[cfg(feature = "audio")]
+pub use music_stream::{MusicStream, MusicStreamError};
\ No newline at end of file
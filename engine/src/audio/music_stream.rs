@@ -0,0 +1,185 @@
+use std::{collections::VecDeque, fmt::{self, Display}, fs::File, io, path::Path};
+use symphonia::core::{
+	audio::SampleBuffer,
+	codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions},
+	errors::Error,
+	formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+	io::MediaSourceStream,
+	meta::MetadataOptions,
+	probe::Hint,
+	units::Time
+};
+use super::{SampleSource, CHANNEL_COUNT};
+
+#[derive(Debug)]
+pub enum MusicStreamError {
+	Io(io::Error),
+	Decode(Error),
+	NoAudioTrack
+}
+
+impl Display for MusicStreamError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(error) => write!(f, "Failed to open the track: {}", error),
+			Self::Decode(error) => write!(f, "Failed to read the track: {}", error),
+			Self::NoAudioTrack => write!(f, "The file has no audio track")
+		}
+	}
+}
+
+impl From<io::Error> for MusicStreamError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl From<Error> for MusicStreamError {
+	fn from(error: Error) -> Self {
+		Self::Decode(error)
+	}
+}
+
+// A compressed track which is decoded a packet at a time as it's played instead of all at once, the file itself is read in
+// chunks as the packets are needed. The decoded frames are linearly resampled to the output's sample rate.
+pub struct MusicStream {
+	format: Box<dyn FormatReader>,
+	decoder: Box<dyn Decoder>,
+	track_id: u32,
+	output_sample_rate: u32,
+	// How far through the decoded frames each output frame moves
+	step: f64,
+	frames: VecDeque<[f32; 2]>,
+	// Between the first two decoded frames
+	position: f64,
+	// The decoded frames before the one a seek asked for which are thrown away
+	skip_frame_count: u64,
+	ended: bool
+}
+
+impl MusicStream {
+	// The container is guessed from the extension, OGG Vorbis and MP3 are supported
+	pub fn open(path: impl AsRef<Path>, output_sample_rate: u32) -> Result<Self, MusicStreamError> {
+		let path = path.as_ref();
+		let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+
+		let mut hint = Hint::new();
+
+		if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+			hint.with_extension(extension);
+		}
+
+		let format = symphonia::default::get_probe().format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())?.format;
+		let track = format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL).ok_or(MusicStreamError::NoAudioTrack)?;
+		let sample_rate = track.codec_params.sample_rate.ok_or(MusicStreamError::NoAudioTrack)?;
+		let decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+		let track_id = track.id;
+
+		Ok(Self {
+			format,
+			decoder,
+			track_id,
+			output_sample_rate,
+			step: sample_rate as f64 / output_sample_rate as f64,
+			frames: VecDeque::new(),
+			position: 0.0,
+			skip_frame_count: 0,
+			ended: false
+		})
+	}
+
+	// Appends the next packet's frames, false once there are none left. The end of the file or an unreadable one ends the
+	// track but a corrupt packet is only skipped.
+	fn decode_packet(&mut self) -> bool {
+		loop {
+			let packet = match self.format.next_packet() {
+				Ok(packet) => packet,
+				Err(_) => return false
+			};
+
+			if packet.track_id() != self.track_id {
+				continue;
+			}
+
+			let decoded = match self.decoder.decode(&packet) {
+				Ok(decoded) => decoded,
+				Err(Error::DecodeError(_)) => continue,
+				Err(_) => return false
+			};
+
+			let spec = *decoded.spec();
+			let channel_count = spec.channels.count();
+			let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+			buffer.copy_interleaved_ref(decoded);
+
+			for frame in buffer.samples().chunks_exact(channel_count) {
+				if self.skip_frame_count > 0 {
+					self.skip_frame_count -= 1;
+					continue;
+				}
+
+				// Mono plays on both sides and the channels past the first two are dropped
+				self.frames.push_back([frame[0], frame[channel_count.min(2) - 1]]);
+			}
+
+			return true;
+		}
+	}
+
+	// Decodes until there are at least as many frames as asked for or the track ends
+	fn fill(&mut self, frame_count: usize) -> bool {
+		while self.frames.len() < frame_count && !self.ended {
+			self.ended = !self.decode_packet();
+		}
+
+		self.frames.len() >= frame_count
+	}
+}
+
+impl SampleSource for MusicStream {
+	fn read(&mut self, samples: &mut [f32]) -> usize {
+		let mut written = 0;
+
+		for frame in samples.chunks_exact_mut(CHANNEL_COUNT) {
+			while self.position >= 1.0 && self.fill(2) {
+				self.frames.pop_front();
+				self.position -= 1.0;
+			}
+
+			// Past the last frame
+			if self.position >= 1.0 || !self.fill(1) {
+				break;
+			}
+
+			let current = self.frames[0];
+			let next = if self.fill(2) { self.frames[1] } else { current };
+			let t = self.position as f32;
+
+			for (channel, sample) in frame.iter_mut().enumerate() {
+				*sample = current[channel] + (next[channel] - current[channel]) * t;
+			}
+
+			self.position += self.step;
+			written += CHANNEL_COUNT;
+		}
+
+		written
+	}
+
+	fn seek(&mut self, frame: u64) {
+		let time = Time::from(frame as f64 / self.output_sample_rate as f64);
+		self.frames.clear();
+		self.position = 0.0;
+		self.skip_frame_count = 0;
+		self.ended = false;
+
+		match self.format.seek(SeekMode::Accurate, SeekTo::Time { time, track_id: Some(self.track_id) }) {
+			Ok(seeked_to) => {
+				// An accurate seek lands on the packet holding the frame so the ones before it in the packet are skipped
+				self.skip_frame_count = seeked_to.required_ts.saturating_sub(seeked_to.actual_ts);
+				self.decoder.reset();
+			},
+			Err(_) => self.ended = true
+		}
+	}
+}
\ No newline at end of file
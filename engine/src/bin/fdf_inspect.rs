@@ -0,0 +1,296 @@
+// Checks a generated font file and prints what's in it, for telling whether text which renders wrong has bad data or the
+// renderer is at fault. Run it with cargo run -p engine --bin fdf_inspect -- <file.fnt> [--glyphs] [--preview <file.ppm>]
+// [--text <string>].
+
+use std::{env, fs, process};
+use engine::font::{Font, FntContents, Glyph};
+
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog 0123456789";
+const PREVIEW_MARGIN: usize = 4;
+
+struct Options {
+	fnt_path: String,
+	print_glyphs: bool,
+	preview_path: Option<String>,
+	text: Option<String>
+}
+
+fn main() {
+	let options = parse_args().unwrap_or_else(|message| {
+		eprintln!("{}", message);
+		eprintln!("Usage: fdf_inspect <file.fnt> [--glyphs] [--preview <file.ppm>] [--text <string>]");
+		process::exit(2);
+	});
+
+	let bytes = fs::read(&options.fnt_path).unwrap_or_else(|e| {
+		eprintln!("Cannot read {}: {}", options.fnt_path, e);
+		process::exit(1);
+	});
+
+	let fnt = Font::parse_fnt(&bytes).unwrap_or_else(|e| {
+		eprintln!("{} is invalid: {}", options.fnt_path, e);
+		process::exit(1);
+	});
+
+	print_summary(&options.fnt_path, &fnt, bytes.len());
+
+	if options.print_glyphs {
+		print_glyphs(&fnt.glyphs);
+	}
+
+	if let Some(preview_path) = &options.preview_path {
+		let text = options.text.clone().unwrap_or_else(|| default_text(&fnt));
+		let (width, height, pixels) = render_preview(&fnt, &text);
+		let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+		data.extend_from_slice(&pixels);
+
+		fs::write(preview_path, data).unwrap_or_else(|e| {
+			eprintln!("Cannot write {}: {}", preview_path, e);
+			process::exit(1);
+		});
+
+		println!("Wrote a {}x{} preview to {}", width, height, preview_path);
+	}
+
+	let problems = find_problems(&fnt);
+
+	if problems.is_empty() {
+		println!("No problems found");
+	}
+	else {
+		for problem in &problems {
+			println!("Problem: {}", problem);
+		}
+
+		process::exit(1);
+	}
+}
+
+fn parse_args() -> Result<Options, String> {
+	let mut args = env::args().skip(1);
+	let mut fnt_path = None;
+	let mut print_glyphs = false;
+	let mut preview_path = None;
+	let mut text = None;
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--glyphs" => print_glyphs = true,
+			"--preview" => preview_path = Some(args.next().ok_or("--preview needs a file path")?),
+			"--text" => text = Some(args.next().ok_or("--text needs a string")?),
+			_ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+			_ if fnt_path.is_none() => fnt_path = Some(arg),
+			_ => return Err(format!("Unexpected argument {}", arg))
+		}
+	}
+
+	Ok(Options {
+		fnt_path: fnt_path.ok_or("No font file given")?,
+		print_glyphs,
+		preview_path,
+		text
+	})
+}
+
+fn print_summary(fnt_path: &str, fnt: &FntContents, file_size: usize) {
+	println!("{} ({} bytes)", fnt_path, file_size);
+	println!("Atlas: {}x{} {}", fnt.atlas_width, fnt.atlas_height, if fnt.color { "color" } else { "coverage" });
+
+	let used_area: f32 = fnt.glyphs.iter().map(|g| g.width * g.height).sum();
+	let atlas_area = (fnt.atlas_width * fnt.atlas_height) as f32;
+	println!("Atlas use: {:.1}%", used_area / atlas_area * 100.0);
+	println!("Space advance: {}", fnt.space_advance);
+	println!("Glyphs: {}", fnt.glyphs.len());
+	println!("Coverage: {}", coverage_ranges(&fnt.glyphs));
+
+	// The default character set of coverage fonts
+	if !fnt.color {
+		let missing: String = (33..127u32)
+			.filter(|char_code| !fnt.glyphs.iter().any(|g| g.char_code == *char_code))
+			.filter_map(char::from_u32)
+			.collect();
+
+		if !missing.is_empty() {
+			println!("Missing printable ASCII: {}", missing);
+		}
+	}
+
+	let ascent = fnt.glyphs.iter().map(|g| -g.bearing_y).fold(0.0, f32::max);
+	let descent = fnt.glyphs.iter().map(|g| g.bearing_y + g.height).fold(0.0, f32::max);
+	println!("Ascent: {}, descent: {}", ascent, descent);
+
+	match fnt.source_hash {
+		Some(source_hash) => println!("Source hash: {:016x}", source_hash),
+		None => println!("Source hash: none, the file is regenerated the next time the font is loaded")
+	}
+}
+
+// Runs of consecutive character codes, such as U+0021-U+007E
+fn coverage_ranges(glyphs: &[Glyph]) -> String {
+	let mut char_codes: Vec<u32> = glyphs.iter().map(|g| g.char_code).collect();
+	char_codes.sort_unstable();
+	char_codes.dedup();
+
+	let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+	for char_code in char_codes {
+		match ranges.last_mut() {
+			Some((_, last)) if *last + 1 == char_code => *last = char_code,
+			_ => ranges.push((char_code, char_code))
+		}
+	}
+
+	let ranges: Vec<String> = ranges.iter().map(|(first, last)| {
+		if first == last {
+			format!("U+{:04X}", first)
+		}
+		else {
+			format!("U+{:04X}-U+{:04X}", first, last)
+		}
+	}).collect();
+
+	if ranges.is_empty() { String::from("none") } else { ranges.join(", ") }
+}
+
+fn print_glyphs(glyphs: &[Glyph]) {
+	println!("{:>10} {:>4} {:>14} {:>14} {:>14} {:>8}", "code", "char", "atlas x, y", "width, height", "bearing x, y", "advance");
+
+	for g in glyphs {
+		let c = char::from_u32(g.char_code).filter(|c| !c.is_control()).unwrap_or(' ');
+
+		println!(
+			"{:>10} {:>4} {:>14} {:>14} {:>14} {:>8}",
+			format!("U+{:04X}", g.char_code),
+			c,
+			format!("{}, {}", g.position_x, g.position_y),
+			format!("{}, {}", g.width, g.height),
+			format!("{}, {}", g.bearing_x, g.bearing_y),
+			g.advance
+		);
+	}
+}
+
+// What the parser lets through but would still render wrong
+fn find_problems(fnt: &FntContents) -> Vec<String> {
+	let mut problems = Vec::new();
+
+	// Glyphs are found with a binary search
+	for pair in fnt.glyphs.windows(2) {
+		if pair[0].char_code == pair[1].char_code {
+			problems.push(format!("U+{:04X} has more than one glyph", pair[0].char_code));
+		}
+		else if pair[0].char_code > pair[1].char_code {
+			problems.push(format!("U+{:04X} comes before U+{:04X}, glyphs must be sorted", pair[0].char_code, pair[1].char_code));
+		}
+	}
+
+	for (i, a) in fnt.glyphs.iter().enumerate() {
+		if a.advance < 0.0 {
+			problems.push(format!("U+{:04X} has a negative advance", a.char_code));
+		}
+
+		for b in &fnt.glyphs[i + 1..] {
+			let overlaps = a.position_x < b.position_x + b.width && b.position_x < a.position_x + a.width
+				&& a.position_y < b.position_y + b.height && b.position_y < a.position_y + a.height;
+
+			if overlaps {
+				problems.push(format!("U+{:04X} and U+{:04X} overlap in the atlas", a.char_code, b.char_code));
+			}
+		}
+	}
+
+	// Glyphs are padded with a transparent texel, anything drawn there bleeds into neighboring glyphs
+	for g in &fnt.glyphs {
+		if g.width > 0.0 && g.height > 0.0 && !border_is_transparent(fnt, g) {
+			problems.push(format!("U+{:04X} has no transparent border", g.char_code));
+		}
+	}
+
+	if fnt.space_advance <= 0.0 {
+		problems.push(String::from("The space advance isn't positive"));
+	}
+
+	problems
+}
+
+fn border_is_transparent(fnt: &FntContents, g: &Glyph) -> bool {
+	let (x0, y0) = (g.position_x as usize, g.position_y as usize);
+	let (x1, y1) = (x0 + g.width as usize - 1, y0 + g.height as usize - 1);
+
+	(x0..=x1).all(|x| coverage(fnt, x, y0) == 0 && coverage(fnt, x, y1) == 0)
+		&& (y0..=y1).all(|y| coverage(fnt, x0, y) == 0 && coverage(fnt, x1, y) == 0)
+}
+
+// The coverage or alpha of the texel
+fn coverage(fnt: &FntContents, x: usize, y: usize) -> u8 {
+	let texel_size = Font::texel_size(fnt.color);
+	fnt.atlas[(y * fnt.atlas_width + x) * texel_size + texel_size - 1]
+}
+
+// Every glyph in the file for color fonts since they usually only have a few
+fn default_text(fnt: &FntContents) -> String {
+	if fnt.color {
+		fnt.glyphs.iter().filter_map(|g| char::from_u32(g.char_code)).collect()
+	}
+	else {
+		String::from(SAMPLE_TEXT)
+	}
+}
+
+// The atlas with the text laid out underneath using the glyph metrics, as RGB on black. Characters without a glyph are
+// skipped like the renderer does.
+fn render_preview(fnt: &FntContents, text: &str) -> (usize, usize, Vec<u8>) {
+	let ascent = fnt.glyphs.iter().map(|g| -g.bearing_y).fold(0.0, f32::max);
+	let descent = fnt.glyphs.iter().map(|g| g.bearing_y + g.height).fold(0.0, f32::max);
+
+	let find = |c: char| fnt.glyphs.iter().find(|g| g.char_code == c as u32);
+	let text_width: f32 = text.chars().map(|c| if c == ' ' { fnt.space_advance } else { find(c).map_or(0.0, |g| g.advance) }).sum();
+
+	let width = fnt.atlas_width.max(text_width.ceil() as usize) + PREVIEW_MARGIN * 2;
+	let text_top = fnt.atlas_height + PREVIEW_MARGIN * 2;
+	let height = text_top + (ascent + descent).ceil() as usize + PREVIEW_MARGIN;
+	let mut pixels = vec![0u8; width * height * 3];
+	let texel_size = Font::texel_size(fnt.color);
+
+	let mut blit = |source_x: usize, source_y: usize, source_width: usize, source_height: usize, x: isize, y: isize| {
+		for row in 0..source_height {
+			for col in 0..source_width {
+				let (px, py) = (x + col as isize, y + row as isize);
+
+				if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+					continue;
+				}
+
+				let texel = ((source_y + row) * fnt.atlas_width + source_x + col) * texel_size;
+				let pixel = (py as usize * width + px as usize) * 3;
+
+				// Color atlases are premultiplied so their color is already over black
+				for channel in 0..3 {
+					let value = if fnt.color { fnt.atlas[texel + channel] } else { fnt.atlas[texel] };
+					pixels[pixel + channel] = pixels[pixel + channel].saturating_add(value);
+				}
+			}
+		}
+	};
+
+	blit(0, 0, fnt.atlas_width, fnt.atlas_height, PREVIEW_MARGIN as isize, PREVIEW_MARGIN as isize);
+
+	let baseline = text_top as f32 + ascent;
+	let mut pen = PREVIEW_MARGIN as f32;
+
+	for c in text.chars() {
+		if c == ' ' {
+			pen += fnt.space_advance;
+			continue;
+		}
+
+		if let Some(g) = find(c) {
+			let x = (pen + g.bearing_x).round() as isize;
+			let y = (baseline + g.bearing_y).round() as isize;
+			blit(g.position_x as usize, g.position_y as usize, g.width as usize, g.height as usize, x, y);
+			pen += g.advance;
+		}
+	}
+
+	(width, height, pixels)
+}
\ No newline at end of file
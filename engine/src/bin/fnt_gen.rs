@@ -0,0 +1,36 @@
+// Generates the font files listed in a font config, skipping the ones already generated from the same inputs so it can run as
+// a build step every time. Run it with cargo run -p engine --bin fnt_gen -- <fonts.toml>
+
+use std::{env, process};
+use engine::FontConfig;
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+
+	if args.len() != 1 {
+		eprintln!("Usage: fnt_gen <fonts.toml>");
+		process::exit(2);
+	}
+
+	let config = FontConfig::load(&args[0]).unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		process::exit(1);
+	});
+
+	let mut failed = false;
+
+	for output in &config.outputs {
+		match output.generate() {
+			Ok(true) => println!("Generated {} at {}", output.name, output.fnt_path),
+			Ok(false) => println!("{} is up to date", output.name),
+			Err(e) => {
+				eprintln!("Cannot generate {} from {}: {}", output.name, output.font_path, e);
+				failed = true;
+			}
+		}
+	}
+
+	if failed {
+		process::exit(1);
+	}
+}
\ No newline at end of file
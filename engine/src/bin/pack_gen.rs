@@ -0,0 +1,33 @@
+// Packs directories into the pack release builds load their assets from, each directory's files are put under its prefix. Run
+// it after the shaders are compiled and the fonts are generated with
+// cargo run -p engine --bin pack_gen -- data.pak target/shaders shaders target/fonts fonts game/res res
+
+use std::{env, process};
+use engine::vfs::PackWriter;
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+
+	if args.len() < 3 || args.len().is_multiple_of(2) {
+		eprintln!("Usage: pack_gen <output.pak> <directory> <prefix> [<directory> <prefix>]...");
+		process::exit(2);
+	}
+
+	let mut writer = PackWriter::new();
+
+	for pair in args[1..].chunks(2) {
+		let (directory, prefix) = (&pair[0], &pair[1]);
+
+		writer.add_directory(directory, prefix).unwrap_or_else(|e| {
+			eprintln!("Cannot read {}: {}", directory, e);
+			process::exit(1);
+		});
+	}
+
+	writer.write(&args[0]).unwrap_or_else(|e| {
+		eprintln!("Cannot write {}: {}", args[0], e);
+		process::exit(1);
+	});
+
+	println!("Wrote {}", args[0]);
+}
\ No newline at end of file
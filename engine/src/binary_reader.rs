@@ -0,0 +1,101 @@
+use std::{fmt, convert::TryInto};
+
+#[derive(Debug, PartialEq)]
+pub enum BinaryReadError {
+	UnexpectedEnd { position: usize, length: usize, size: usize }
+}
+
+impl fmt::Display for BinaryReadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::UnexpectedEnd { position, length, size } => write!(f, "Cannot read {} bytes at offset {}, the data is only {} bytes long", length, position, size)
+		}
+	}
+}
+
+impl std::error::Error for BinaryReadError {}
+
+// Reads little endian values from a byte slice, every read checks the bounds and returns an error instead of panicking so
+// truncated or corrupted files can be rejected
+pub struct BinaryReader<'a> {
+	bytes: &'a [u8],
+	position: usize
+}
+
+impl<'a> BinaryReader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		Self {
+			bytes,
+			position: 0
+		}
+	}
+
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	pub fn remaining(&self) -> usize {
+		self.bytes.len() - self.position
+	}
+
+	pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], BinaryReadError> {
+		if length > self.remaining() {
+			return Err(BinaryReadError::UnexpectedEnd { position: self.position, length, size: self.bytes.len() });
+		}
+
+		let bytes = &self.bytes[self.position..self.position + length];
+		self.position += length;
+		Ok(bytes)
+	}
+
+	pub fn skip(&mut self, length: usize) -> Result<(), BinaryReadError> {
+		self.read_bytes(length).map(|_| ())
+	}
+
+	pub fn read_u32(&mut self) -> Result<u32, BinaryReadError> {
+		Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+	}
+
+	pub fn read_f32(&mut self) -> Result<f32, BinaryReadError> {
+		Ok(f32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+	}
+
+	pub fn read_u64(&mut self) -> Result<u64, BinaryReadError> {
+		Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_little_endian_values() {
+		let mut bytes = vec![];
+		bytes.extend_from_slice(&7u32.to_le_bytes());
+		bytes.extend_from_slice(&1.5f32.to_le_bytes());
+		bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+		bytes.extend_from_slice(&[1, 2, 3]);
+
+		let mut reader = BinaryReader::new(&bytes);
+		assert_eq!(reader.read_u32(), Ok(7));
+		assert_eq!(reader.read_f32(), Ok(1.5));
+		assert_eq!(reader.read_u64(), Ok(u64::MAX));
+		assert_eq!(reader.remaining(), 3);
+		assert_eq!(reader.read_bytes(3), Ok(&[1u8, 2, 3][..]));
+		assert_eq!(reader.remaining(), 0);
+	}
+
+	#[test]
+	fn reading_past_the_end_is_an_error() {
+		let bytes = [1, 2, 3];
+		let mut reader = BinaryReader::new(&bytes);
+
+		assert_eq!(reader.read_u32(), Err(BinaryReadError::UnexpectedEnd { position: 0, length: 4, size: 3 }));
+		assert_eq!(reader.position(), 0);
+		assert!(reader.skip(2).is_ok());
+		assert!(reader.skip(2).is_err());
+		assert!(reader.read_bytes(usize::MAX).is_err());
+		assert_eq!(reader.read_bytes(1), Ok(&[3u8][..]));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,259 @@
+use crate::{Geometry3D, geometry3d::Topology, math::{Box3, Matrix4, Ray, Vector3}};
+
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+struct Node {
+	bounds: Box3,
+	// A leaf's range of triangles, or the index of the right child for an inner node where the left child follows it
+	start: usize,
+	count: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+	// In units of the ray's direction
+	pub distance: f32,
+	// The index of the triangle in the geometry, so indices[triangle * 3..triangle * 3 + 3] are its vertices
+	pub triangle: usize
+}
+
+// A bounding volume hierarchy over the triangles of a geometry for ray casts which need to hit the actual surface rather
+// than the bounding box. It's built once per geometry, each instance can keep a copy refit to its global matrix which is
+// much cheaper than building it again.
+pub struct Bvh {
+	nodes: Vec<Node>,
+	// The triangles in geometry space and after the last refit, in the order the leaves reference them
+	local_triangles: Vec<[Vector3; 3]>,
+	triangles: Vec<[Vector3; 3]>,
+	// The index in the geometry of each triangle
+	triangle_indices: Vec<usize>
+}
+
+impl Bvh {
+	// Line geometry has no triangles so nothing hits it
+	pub fn new(geometry: &Geometry3D) -> Self {
+		let triangles = match geometry.topology() {
+			Topology::Triangle => {
+				let attributes = geometry.attributes();
+				let position = |index: u16| {
+					let offset = index as usize * 6;
+					Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2])
+				};
+
+				geometry.indices().chunks_exact(3)
+					.map(|triangle| [position(triangle[0]), position(triangle[1]), position(triangle[2])])
+					.collect()
+			},
+			Topology::Line => Vec::new()
+		};
+
+		Self::from_triangles(triangles)
+	}
+
+	pub fn from_triangles(triangles: Vec<[Vector3; 3]>) -> Self {
+		let mut triangle_indices: Vec<usize> = (0..triangles.len()).collect();
+		let centroids: Vec<Vector3> = triangles.iter().map(|[a, b, c]| (a + b + c) / 3.0).collect();
+		let mut nodes = Vec::new();
+
+		if !triangles.is_empty() {
+			Self::build(&mut nodes, &mut triangle_indices, &triangles, &centroids, 0, triangles.len());
+		}
+
+		let local_triangles: Vec<[Vector3; 3]> = triangle_indices.iter().map(|index| triangles[*index]).collect();
+
+		Self {
+			nodes,
+			triangles: local_triangles.clone(),
+			local_triangles,
+			triangle_indices
+		}
+	}
+
+	// Splits the triangles at the median centroid along the longest axis of the centroids' bounds, parents always come
+	// before their children
+	fn build(nodes: &mut Vec<Node>, triangle_indices: &mut [usize], triangles: &[[Vector3; 3]], centroids: &[Vector3], start: usize, count: usize) -> usize {
+		let node_index = nodes.len();
+		let range = &mut triangle_indices[start..start + count];
+		let bounds = triangles_bounds(range.iter().map(|index| &triangles[*index]));
+		nodes.push(Node { bounds, start, count });
+
+		if count <= MAX_LEAF_TRIANGLES {
+			return node_index;
+		}
+
+		let centroid_bounds = Box3::from_points(&range.iter().map(|index| centroids[*index]).collect::<Vec<_>>());
+		let extent = centroid_bounds.max - centroid_bounds.min;
+		let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+
+		let half = count / 2;
+		range.select_nth_unstable_by(half, |a, b| {
+			centroids[*a].get_from_index(axis).total_cmp(&centroids[*b].get_from_index(axis))
+		});
+
+		Self::build(nodes, triangle_indices, triangles, centroids, start, half);
+		let right = Self::build(nodes, triangle_indices, triangles, centroids, start + half, count - half);
+
+		nodes[node_index].start = right;
+		nodes[node_index].count = 0;
+		node_index
+	}
+
+	pub fn triangle_count(&self) -> usize {
+		self.triangles.len()
+	}
+
+	pub fn bounds(&self) -> Option<&Box3> {
+		self.nodes.first().map(|node| &node.bounds)
+	}
+
+	// Moves the triangles into the space of the matrix, such as an instance's global matrix, and updates the bounds without
+	// changing the hierarchy. The tree gets looser the more the matrix rotates or skews it.
+	pub fn refit(&mut self, matrix: &Matrix4) {
+		for (triangle, local_triangle) in self.triangles.iter_mut().zip(&self.local_triangles) {
+			for (vertex, local_vertex) in triangle.iter_mut().zip(local_triangle) {
+				*vertex = *local_vertex;
+				vertex.apply_matrix4(matrix);
+			}
+		}
+
+		for node_index in (0..self.nodes.len()).rev() {
+			let node = &self.nodes[node_index];
+
+			let bounds = if node.count > 0 {
+				triangles_bounds(self.triangles[node.start..node.start + node.count].iter())
+			}
+			else {
+				let mut bounds = self.nodes[node_index + 1].bounds;
+				bounds.union(&self.nodes[node.start].bounds);
+				bounds
+			};
+
+			self.nodes[node_index].bounds = bounds;
+		}
+	}
+
+	// The closest hit
+	pub fn intersect_ray(&self, ray: &Ray) -> Option<RayHit> {
+		self.traverse(ray, f32::INFINITY, false)
+	}
+
+	// Stops at the first hit within the max distance, for line of sight checks
+	pub fn intersects_ray(&self, ray: &Ray, max_distance: f32) -> bool {
+		self.traverse(ray, max_distance, true).is_some()
+	}
+
+	// Skips nodes further than the closest hit so far
+	fn traverse(&self, ray: &Ray, mut max_distance: f32, first_hit: bool) -> Option<RayHit> {
+		let mut closest = None;
+
+		if self.nodes.is_empty() {
+			return closest;
+		}
+
+		let mut stack = vec![0];
+
+		while let Some(node_index) = stack.pop() {
+			let node = &self.nodes[node_index];
+
+			match ray.intersect_box(&node.bounds) {
+				Some(distance) if distance <= max_distance => (),
+				_ => continue
+			}
+
+			if node.count == 0 {
+				stack.push(node.start);
+				stack.push(node_index + 1);
+				continue;
+			}
+
+			for index in node.start..node.start + node.count {
+				let [a, b, c] = &self.triangles[index];
+
+				match ray.intersect_triangle(a, b, c) {
+					Some(distance) if distance <= max_distance => {
+						max_distance = distance;
+						closest = Some(RayHit { distance, triangle: self.triangle_indices[index] });
+
+						if first_hit {
+							return closest;
+						}
+					},
+					_ => ()
+				}
+			}
+		}
+
+		closest
+	}
+}
+
+fn triangles_bounds<'a>(triangles: impl Iterator<Item = &'a [Vector3; 3]>) -> Box3 {
+	let mut bounds = Box3::new(Vector3::from_scalar(f32::INFINITY), Vector3::from_scalar(f32::NEG_INFINITY));
+
+	for triangle in triangles {
+		for vertex in triangle {
+			bounds.expand_by_point(vertex);
+		}
+	}
+
+	bounds
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{vector3, Quaternion};
+
+	// A row of unit squares along x facing up, two triangles each
+	fn row_of_squares(count: usize) -> Vec<[Vector3; 3]> {
+		(0..count).flat_map(|square| {
+			let x = square as f32;
+			let (a, b, c, d) = (Vector3::new(x, 0.0, 0.0), Vector3::new(x + 1.0, 0.0, 0.0), Vector3::new(x + 1.0, 0.0, 1.0), Vector3::new(x, 0.0, 1.0));
+			vec![[a, b, c], [a, c, d]]
+		}).collect()
+	}
+
+	#[test]
+	fn intersect_ray() {
+		let bvh = Bvh::from_triangles(row_of_squares(50));
+		assert_eq!(bvh.triangle_count(), 100);
+
+		let hit = bvh.intersect_ray(&Ray::new(Vector3::new(20.75, 5.0, 0.25), -vector3::UNIT_Y)).unwrap();
+		assert_eq!(hit, RayHit { distance: 5.0, triangle: 40 });
+
+		assert_eq!(bvh.intersect_ray(&Ray::new(Vector3::new(60.0, 5.0, 0.5), -vector3::UNIT_Y)), None);
+		assert_eq!(bvh.intersect_ray(&Ray::new(Vector3::new(20.0, 5.0, 0.5), vector3::UNIT_Y)), None);
+	}
+
+	#[test]
+	fn closest_hit_of_many() {
+		// Two layers, the ray starts below both and goes up
+		let mut triangles = row_of_squares(20);
+		triangles.extend(row_of_squares(20).iter().map(|triangle| triangle.map(|vertex| vertex + Vector3::new(0.0, 2.0, 0.0))));
+		let bvh = Bvh::from_triangles(triangles);
+
+		let ray = Ray::new(Vector3::new(5.5, -1.0, 0.75), vector3::UNIT_Y);
+		assert_eq!(bvh.intersect_ray(&ray).unwrap().distance, 1.0);
+		assert!(bvh.intersects_ray(&ray, 1.5));
+		assert!(!bvh.intersects_ray(&ray, 0.5));
+	}
+
+	#[test]
+	fn geometry_and_refit() {
+		let mut bvh = Bvh::new(&Geometry3D::create_box());
+		let ray = Ray::new(Vector3::new(0.25, 0.25, -10.0), vector3::UNIT_Z);
+		assert!((bvh.intersect_ray(&ray).unwrap().distance - 9.0).abs() < 1e-5);
+
+		let mut orientation = Quaternion::default();
+		orientation.set_from_axis_angle(&vector3::UNIT_Y, std::f32::consts::FRAC_PI_4);
+		let mut matrix = Matrix4::default();
+		matrix.compose(&Vector3::new(0.0, 0.0, 3.0), &orientation, &Vector3::from_scalar(2.0));
+		bvh.refit(&matrix);
+
+		// Scaled by 2 and turned so an edge faces the ray
+		let ray = Ray::new(Vector3::new(0.0, 0.0, -10.0), vector3::UNIT_Z);
+		assert!((bvh.intersect_ray(&ray).unwrap().distance - (13.0 - 8.0f32.sqrt())).abs() < 1e-4);
+		assert!((bvh.bounds().unwrap().max.x - 8.0f32.sqrt()).abs() < 1e-4);
+		assert_eq!(Bvh::new(&Geometry3D::create_box_helper(&crate::math::box3::DEFAULT_SQUARE)).intersect_ray(&ray), None);
+	}
+}
\ No newline at end of file
@@ -1,8 +1,11 @@
-use crate::{component::Transform3D, math::{matrix4, Matrix4}};
+use crate::{component::{Transform3D, ALL_LAYERS_MASK}, math::{matrix4, Matrix4, Plane, Ray, Vector2, Vector3, Vector4}};
 
 pub struct Camera {
 	pub projection_matrix: Matrix4,
-	pub transform: Transform3D
+	pub transform: Transform3D,
+	pub layer_mask: u32,
+	// Keeps the image at this aspect ratio with bars filling the rest of the window instead of stretching it to the window
+	pub fixed_aspect_ratio: Option<f32>
 }
 
 impl Camera {
@@ -12,12 +15,243 @@ impl Camera {
 
 		Self {
 			projection_matrix,
-			transform: Transform3D::new()
+			transform: Transform3D::new(),
+			layer_mask: ALL_LAYERS_MASK,
+			fixed_aspect_ratio: None
 		}
 	}
 
+	pub fn new_orthographic(width: f32, height: f32, near: f32, far: f32) -> Self {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_orthographic(width, height, near, far);
+
+		Self {
+			projection_matrix,
+			transform: Transform3D::new(),
+			layer_mask: ALL_LAYERS_MASK,
+			fixed_aspect_ratio: None
+		}
+	}
+
+	// The aspect ratio to make the projection with when rendering to an image of this size
+	pub fn aspect_ratio(&self, width: u32, height: u32) -> f32 {
+		self.fixed_aspect_ratio.unwrap_or(width as f32 / height as f32)
+	}
+
+	// The x, y, width and height of the region of an image of this size the camera renders to
+	pub fn viewport(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+		letterbox_viewport(self.fixed_aspect_ratio, width, height)
+	}
+
+	// Meshes and text are only rendered by this camera if their layer mask shares a bit with the camera's
+	pub fn renders(&self, layer_mask: u32) -> bool {
+		self.layer_mask & layer_mask != 0
+	}
+
 	pub fn update(&mut self) {
 		self.transform.update_local_matrix();
 		self.transform.global_matrix = self.transform.local_matrix;
 	}
+
+	// The ray from the near plane through the cursor for picking and dragging things in the world. The cursor position is in
+	// framebuffer pixels of an image of this size, the letterbox bars are outside of the view.
+	pub fn cursor_ray(&self, cursor_position: &Vector2, width: u32, height: u32) -> Ray {
+		let (x, y, viewport_width, viewport_height) = self.viewport(width, height);
+		let clip_x = (cursor_position.x - x as f32) / viewport_width as f32 * 2.0 - 1.0;
+		let clip_y = (cursor_position.y - y as f32) / viewport_height as f32 * 2.0 - 1.0;
+
+		let mut inverse_projection_matrix = self.projection_matrix;
+		inverse_projection_matrix.invert();
+
+		let unproject = |depth: f32| {
+			let point = inverse_projection_matrix * Vector4::new(clip_x, clip_y, depth, 1.0);
+			let mut point = Vector3::new(point.x / point.w, point.y / point.w, point.z / point.w);
+			point.apply_matrix4(&self.transform.global_matrix);
+			point
+		};
+
+		let near = unproject(0.0);
+		let mut direction = unproject(1.0) - near;
+		direction.normalize();
+		Ray::new(near, direction)
+	}
+
+	// The camera seen in a mirror lying on the plane, for rendering planar reflections of mirrors and water. Its near plane
+	// is tilted onto the mirror so nothing behind the mirror ends up in the reflection. The reflection is mirrored so
+	// triangles wind the other way when rendered with it.
+	pub fn reflected(&self, plane: &Plane) -> Self {
+		// The mirror faces the camera
+		let mut plane = *plane;
+		plane.normalize();
+
+		if plane.distance_to_point(&self.transform.global_matrix.extract_position()) < 0.0 {
+			plane = Plane::new(-plane.normal, -plane.constant);
+		}
+
+		let global_matrix = reflection_matrix(&plane) * self.transform.global_matrix;
+		let mut transform = Transform3D::new();
+		let (position, orientation, scale) = global_matrix.decompose();
+		transform.position = position;
+		transform.orientation = orientation;
+		transform.scale = scale;
+		transform.update_local_matrix();
+		transform.global_matrix = transform.local_matrix;
+
+		// Planes are taken into view space by the inverse transpose of the view matrix which is the transpose of the global
+		let mut plane_matrix = transform.global_matrix;
+		plane_matrix.transpose();
+		let view_plane = plane_matrix * Vector4::new(plane.normal.x, plane.normal.y, plane.normal.z, plane.constant);
+
+		Self {
+			projection_matrix: oblique_projection_matrix(&self.projection_matrix, &view_plane),
+			transform,
+			layer_mask: self.layer_mask,
+			fixed_aspect_ratio: self.fixed_aspect_ratio
+		}
+	}
+}
+
+// Centers the largest region with the aspect ratio in the image, leaving bars on the sides of a wider image or above and below
+// a taller one
+pub(crate) fn letterbox_viewport(fixed_aspect_ratio: Option<f32>, width: u32, height: u32) -> (u32, u32, u32, u32) {
+	let aspect_ratio = match fixed_aspect_ratio {
+		Some(aspect_ratio) => aspect_ratio,
+		None => return (0, 0, width, height)
+	};
+
+	if width as f32 / height as f32 > aspect_ratio {
+		let viewport_width = ((height as f32 * aspect_ratio).round() as u32).clamp(1, width);
+		((width - viewport_width) / 2, 0, viewport_width, height)
+	}
+	else {
+		let viewport_height = ((width as f32 / aspect_ratio).round() as u32).clamp(1, height);
+		(0, (height - viewport_height) / 2, width, viewport_height)
+	}
+}
+
+// Mirrors points across the plane, its normal must be unit length
+fn reflection_matrix(plane: &Plane) -> Matrix4 {
+	let n = &plane.normal;
+	let d = plane.constant;
+
+	Matrix4::new([
+		[1.0 - 2.0 * n.x * n.x, -2.0 * n.x * n.y, -2.0 * n.x * n.z, -2.0 * d * n.x],
+		[-2.0 * n.y * n.x, 1.0 - 2.0 * n.y * n.y, -2.0 * n.y * n.z, -2.0 * d * n.y],
+		[-2.0 * n.z * n.x, -2.0 * n.z * n.y, 1.0 - 2.0 * n.z * n.z, -2.0 * d * n.z],
+		[0.0, 0.0, 0.0, 1.0]])
+}
+
+// Replaces the near plane of the projection with the view space clip plane, keeping the side it faces. The depth row is
+// scaled so the far plane still passes through the far corner of the view volume on the clip plane's side, which keeps as
+// much depth precision as possible (Lengyel, "Oblique View Frustum Depth Projection and Clipping").
+fn oblique_projection_matrix(projection_matrix: &Matrix4, clip_plane: &Vector4) -> Matrix4 {
+	let mut inverse_projection_matrix = *projection_matrix;
+	inverse_projection_matrix.invert();
+
+	// The clip plane in clip space tells which corner is farthest on its side
+	let mut inverse_transpose_projection_matrix = inverse_projection_matrix;
+	inverse_transpose_projection_matrix.transpose();
+	let clip_space_plane = inverse_transpose_projection_matrix * clip_plane;
+
+	let corner = inverse_projection_matrix * Vector4::new(clip_space_plane.x.signum(), clip_space_plane.y.signum(), 1.0, 1.0);
+	let scale = 1.0 / (clip_plane.x * corner.x + clip_plane.y * corner.y + clip_plane.z * corner.z + clip_plane.w * corner.w);
+
+	let mut oblique_projection_matrix = *projection_matrix;
+	oblique_projection_matrix.elements[2] = [clip_plane.x * scale, clip_plane.y * scale, clip_plane.z * scale, clip_plane.w * scale];
+	oblique_projection_matrix
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::Vector3;
+
+	fn camera_above_water() -> Camera {
+		let mut camera = Camera::new(16.0 / 9.0, 75.0, 0.1, 100.0);
+		camera.transform.position.set(1.0, 3.0, -4.0);
+		camera.transform.rotate_x(-0.4);
+		camera.update();
+		camera
+	}
+
+	fn water() -> Plane {
+		Plane::new(Vector3::new(0.0, 1.0, 0.0), -1.0)
+	}
+
+	fn view_projection_matrix(camera: &Camera) -> Matrix4 {
+		let mut view_matrix = camera.transform.global_matrix;
+		view_matrix.invert();
+		camera.projection_matrix * view_matrix
+	}
+
+	#[test]
+	fn reflected_camera_sees_the_mirrored_scene() {
+		let camera = camera_above_water();
+		let reflected_camera = camera.reflected(&water());
+		assert!((reflected_camera.transform.position.y - -1.0).abs() < 1e-5);
+
+		// Apart from the depth the reflected camera sees a point where the camera sees its mirror image
+		let point = Vector3::new(2.0, 2.5, 6.0);
+		let mirrored_point = Vector3::new(2.0, -0.5, 6.0);
+		let reflected = view_projection_matrix(&reflected_camera) * point.expand(1.0);
+		let expected = view_projection_matrix(&camera) * mirrored_point.expand(1.0);
+		assert!((reflected.x - expected.x).abs() < 1e-4 && (reflected.y - expected.y).abs() < 1e-4 && (reflected.w - expected.w).abs() < 1e-4);
+	}
+
+	#[test]
+	fn reflected_camera_clips_at_the_mirror() {
+		let reflected_camera = camera_above_water().reflected(&water());
+		let view_projection_matrix = view_projection_matrix(&reflected_camera);
+		let depth = |point: Vector3| view_projection_matrix * point.expand(1.0);
+
+		// On the mirror the depth is at the near plane, above it in front of the near plane and below it behind
+		assert!(depth(Vector3::new(1.0, 1.0, 5.0)).z.abs() < 1e-4);
+		assert!(depth(Vector3::new(1.0, 2.0, 5.0)).z > 0.0);
+		assert!(depth(Vector3::new(1.0, 0.0, 5.0)).z < 0.0);
+
+		// The far plane is still behind everything in view
+		let far_point = depth(Vector3::new(1.0, 1.5, 40.0));
+		assert!(far_point.z < far_point.w);
+	}
+
+	#[test]
+	fn letterbox() {
+		let mut camera = Camera::new(1.0, 75.0, 0.1, 100.0);
+		assert_eq!(camera.viewport(800, 600), (0, 0, 800, 600));
+		assert_eq!(camera.aspect_ratio(800, 600), 800.0 / 600.0);
+
+		// Pillarboxed in a wider window and letterboxed in a taller one
+		camera.fixed_aspect_ratio = Some(16.0 / 9.0);
+		assert_eq!(camera.viewport(2560, 1080), (320, 0, 1920, 1080));
+		assert_eq!(camera.viewport(1600, 1200), (0, 150, 1600, 900));
+		assert_eq!(camera.viewport(1920, 1080), (0, 0, 1920, 1080));
+		assert_eq!(camera.aspect_ratio(800, 600), 16.0 / 9.0);
+	}
+
+	#[test]
+	fn cursor_ray_passes_through_what_is_under_the_cursor() {
+		let mut camera = camera_above_water();
+		camera.fixed_aspect_ratio = Some(16.0 / 9.0);
+		let (width, height) = (1600, 1200);
+		let (x, y, viewport_width, viewport_height) = camera.viewport(width, height);
+
+		for point in [Vector3::new(2.0, 2.5, 6.0), Vector3::new(-3.0, 0.0, 10.0)] {
+			let clip = view_projection_matrix(&camera) * point.expand(1.0);
+			let cursor_position = Vector2::new(
+				x as f32 + (clip.x / clip.w + 1.0) / 2.0 * viewport_width as f32,
+				y as f32 + (clip.y / clip.w + 1.0) / 2.0 * viewport_height as f32);
+
+			let ray = camera.cursor_ray(&cursor_position, width, height);
+			let distance = (point - ray.origin).dot(&ray.direction);
+			assert!(distance > 0.0);
+			assert!(ray.at(distance).distance(&point) < 1e-3);
+		}
+	}
+
+	#[test]
+	fn mirror_faces_the_camera() {
+		let camera = camera_above_water();
+		let flipped_water = Plane::new(Vector3::new(0.0, -2.0, 0.0), 2.0);
+		assert_eq!(camera.reflected(&flipped_water).projection_matrix, camera.reflected(&water()).projection_matrix);
+	}
 }
\ No newline at end of file
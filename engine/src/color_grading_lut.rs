@@ -0,0 +1,242 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+
+#[derive(Debug)]
+pub enum LutError {
+	Io { path: PathBuf, error: io::Error },
+	Parse { line: usize, reason: String },
+	MissingSize,
+	InvalidSize { size: usize },
+	EntryCount { expected: usize, found: usize }
+}
+
+impl fmt::Display for LutError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read LUT {}: {}", path.display(), error),
+			Self::Parse { line, reason } => write!(f, "LUT line {} is invalid: {}", line, reason),
+			Self::MissingSize => write!(f, "LUT is missing LUT_3D_SIZE"),
+			Self::InvalidSize { size } => write!(f, "LUT size {} is not supported, it must be at least 2", size),
+			Self::EntryCount { expected, found } => write!(f, "LUT should have {} entries but has {}", expected, found)
+		}
+	}
+}
+
+impl Error for LutError {}
+
+pub struct ColorGradingLut {
+	pub size: usize,
+	pub data: Vec<[f32; 3]>
+}
+
+impl ColorGradingLut {
+	pub fn identity(size: usize) -> Self {
+		assert!(size >= 2, "LUT size must be at least 2");
+
+		let max = (size - 1) as f32;
+		let mut data = Vec::with_capacity(size * size * size);
+
+		for b in 0..size {
+			for g in 0..size {
+				for r in 0..size {
+					data.push([r as f32 / max, g as f32 / max, b as f32 / max]);
+				}
+			}
+		}
+
+		Self { size, data }
+	}
+
+	pub fn from_cube_file(path: impl AsRef<Path>) -> Result<Self, LutError> {
+		let path = path.as_ref();
+		let contents = fs::read_to_string(path).map_err(|error| LutError::Io { path: path.to_path_buf(), error })?;
+		Self::from_cube(&contents)
+	}
+
+	// Reads the Adobe cube format. Keywords other than the size and domain, like the LUT_3D_INPUT_RANGE Resolve writes, are
+	// skipped unless they change how the entries are read.
+	pub fn from_cube(contents: &str) -> Result<Self, LutError> {
+		let mut size = None;
+		let mut domain_min = [0.0, 0.0, 0.0];
+		let mut domain_max = [1.0, 1.0, 1.0];
+		let mut data = Vec::new();
+
+		for (index, line) in contents.lines().enumerate() {
+			let error = |reason: String| LutError::Parse { line: index + 1, reason };
+			let line = line.trim();
+
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut words = line.split_whitespace();
+			let keyword = words.next().unwrap();
+
+			match keyword {
+				"LUT_1D_SIZE" => return Err(error(String::from("1D LUTs are not supported"))),
+				"LUT_3D_SIZE" => {
+					let value = words.next().and_then(|w| w.parse::<usize>().ok()).ok_or_else(|| error(String::from("LUT_3D_SIZE needs a whole number")))?;
+
+					if value < 2 {
+						return Err(LutError::InvalidSize { size: value });
+					}
+
+					size = Some(value);
+				},
+				"DOMAIN_MIN" => domain_min = Self::parse_triple(words).ok_or_else(|| error(String::from("DOMAIN_MIN needs 3 numbers")))?,
+				"DOMAIN_MAX" => domain_max = Self::parse_triple(words).ok_or_else(|| error(String::from("DOMAIN_MAX needs 3 numbers")))?,
+				"LUT_3D_INPUT_RANGE" => {
+					let range = Self::parse_numbers::<2>(words).ok_or_else(|| error(String::from("LUT_3D_INPUT_RANGE needs 2 numbers")))?;
+					domain_min = [range[0]; 3];
+					domain_max = [range[1]; 3];
+				},
+				_ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => (),
+				_ => data.push(Self::parse_triple(line.split_whitespace()).ok_or_else(|| error(format!("{} is not 3 numbers", line)))?)
+			}
+		}
+
+		let size = size.ok_or(LutError::MissingSize)?;
+		let expected = size.checked_mul(size).and_then(|square| square.checked_mul(size)).ok_or(LutError::InvalidSize { size })?;
+
+		if data.len() != expected {
+			return Err(LutError::EntryCount { expected, found: data.len() });
+		}
+
+		// Normalize the entries into the 0 to 1 range
+		for entry in &mut data {
+			for i in 0..3 {
+				entry[i] = (entry[i] - domain_min[i]) / (domain_max[i] - domain_min[i]);
+			}
+		}
+
+		Ok(Self { size, data })
+	}
+
+	// The strip is a size * size wide and size tall RGBA8 image made of size square slices, one for each blue value
+	pub fn from_strip(size: usize, pixels: &[u8]) -> Self {
+		let width = size * size;
+		assert_eq!(pixels.len(), width * size * 4, "Strip LUT of size {} should be {}x{} RGBA pixels", size, width, size);
+
+		let mut data = Vec::with_capacity(size * size * size);
+
+		for b in 0..size {
+			for g in 0..size {
+				for r in 0..size {
+					let pixel_index = (g * width + b * size + r) * 4;
+					let pixel = &pixels[pixel_index..pixel_index + 3];
+					data.push([pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[2] as f32 / 255.0]);
+				}
+			}
+		}
+
+		Self { size, data }
+	}
+
+	pub(crate) fn to_rgba8(&self) -> Vec<u8> {
+		let mut pixels = Vec::with_capacity(self.data.len() * 4);
+
+		for entry in &self.data {
+			for component in entry {
+				pixels.push((component.clamp(0.0, 1.0) * 255.0).round() as u8);
+			}
+
+			pixels.push(255);
+		}
+
+		pixels
+	}
+
+	fn parse_triple<'a>(words: impl Iterator<Item = &'a str>) -> Option<[f32; 3]> {
+		Self::parse_numbers(words)
+	}
+
+	// Extra numbers after the first N are an error
+	fn parse_numbers<'a, const N: usize>(mut words: impl Iterator<Item = &'a str>) -> Option<[f32; N]> {
+		let mut numbers = [0.0; N];
+
+		for value in &mut numbers {
+			*value = words.next()?.parse::<f32>().ok()?;
+		}
+
+		if words.next().is_some() {
+			return None;
+		}
+
+		Some(numbers)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn identity() {
+		let lut = ColorGradingLut::identity(2);
+		assert_eq!(lut.data.len(), 8);
+		assert_eq!(lut.data[1], [1.0, 0.0, 0.0]);
+		assert_eq!(lut.data[2], [0.0, 1.0, 0.0]);
+		assert_eq!(lut.data[4], [0.0, 0.0, 1.0]);
+	}
+
+	#[test]
+	fn from_cube() {
+		let contents = "
+			# Comment
+			TITLE \"Test\"
+			LUT_3D_SIZE 2
+			DOMAIN_MIN 0 0 0
+			DOMAIN_MAX 2 2 2
+			0 0 0
+			2 0 0
+			0 2 0
+			2 2 0
+			0 0 2
+			2 0 2
+			0 2 2
+			2 2 2";
+
+		let lut = ColorGradingLut::from_cube(contents).unwrap();
+		assert_eq!(lut.size, 2);
+		assert_eq!(lut.data, ColorGradingLut::identity(2).data);
+	}
+
+	#[test]
+	fn from_cube_input_range() {
+		let mut contents = String::from("LUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE 0 2\nLUT_1D_INPUT_RANGE 0 1\nUNKNOWN_KEYWORD 1\n");
+
+		for entry in ColorGradingLut::identity(2).data {
+			contents += &format!("{} {} {}\n", entry[0] * 2.0, entry[1] * 2.0, entry[2] * 2.0);
+		}
+
+		assert_eq!(ColorGradingLut::from_cube(&contents).unwrap().data, ColorGradingLut::identity(2).data);
+	}
+
+	#[test]
+	fn from_cube_errors() {
+		assert!(matches!(ColorGradingLut::from_cube("0 0 0"), Err(LutError::MissingSize)));
+		assert!(matches!(ColorGradingLut::from_cube("LUT_3D_SIZE 1\n0 0 0"), Err(LutError::InvalidSize { size: 1 })));
+		assert!(matches!(ColorGradingLut::from_cube("LUT_3D_SIZE 99999999999"), Err(LutError::InvalidSize { size: 99999999999 })));
+		assert!(matches!(ColorGradingLut::from_cube("LUT_3D_SIZE 2\n0 0 0"), Err(LutError::EntryCount { expected: 8, found: 1 })));
+		assert!(matches!(ColorGradingLut::from_cube("LUT_3D_SIZE 2\n0 0 x"), Err(LutError::Parse { line: 2, .. })));
+		assert!(matches!(ColorGradingLut::from_cube("LUT_1D_SIZE 2"), Err(LutError::Parse { line: 1, .. })));
+		assert!(matches!(ColorGradingLut::from_cube_file("missing.cube"), Err(LutError::Io { .. })));
+	}
+
+	#[test]
+	fn from_strip() {
+		let mut pixels = vec![0; 4 * 2 * 4];
+
+		// Red at r = 1, g = 0, b = 1 which is the second pixel of the second slice
+		pixels[3 * 4] = 255;
+
+		let lut = ColorGradingLut::from_strip(2, &pixels);
+		assert_eq!(lut.data[5], [1.0, 0.0, 0.0]);
+		assert_eq!(lut.data[1], [0.0, 0.0, 0.0]);
+	}
+
+	#[test]
+	fn to_rgba8() {
+		let lut = ColorGradingLut { size: 1, data: vec![[1.5, 0.5, -1.0]] };
+		assert_eq!(lut.to_rgba8(), vec![255, 128, 0, 255]);
+	}
+}
\ No newline at end of file
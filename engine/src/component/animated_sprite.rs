@@ -0,0 +1,129 @@
+use std::time::Duration;
+use crate::pool::Handle;
+
+#[derive(Copy, Clone)]
+pub struct SpriteFrame {
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32
+}
+
+// A quad showing one frame of an atlas texture at a time, drawn in pixels by the render system with the entity's 2D transform
+pub struct AnimatedSprite {
+	// The atlas in the texture pool, the sprite isn't drawn until it's submitted to the render system
+	pub texture: Handle,
+	pub atlas_width: f32,
+	pub atlas_height: f32,
+	pub frames: Vec<SpriteFrame>,
+	pub frames_per_second: f32,
+	pub looping: bool,
+	pub playing: bool,
+	pub(crate) frame_index: usize,
+	pub(crate) frame_duration: Duration,
+	pub(crate) indices: Vec<u16>,
+	pub(crate) attributes: Vec<f32>
+}
+
+impl AnimatedSprite {
+	pub fn new(texture: Handle, atlas_width: f32, atlas_height: f32, frames: Vec<SpriteFrame>, frames_per_second: f32) -> Self {
+		assert!(!frames.is_empty(), "Cannot create animated sprite without any frames");
+
+		let mut sprite = Self {
+			texture,
+			atlas_width,
+			atlas_height,
+			frames,
+			frames_per_second,
+			looping: true,
+			playing: true,
+			frame_index: 0,
+			frame_duration: Duration::new(0, 0),
+			indices: vec![0, 1, 2, 0, 2, 3],
+			attributes: Vec::with_capacity(16)
+		};
+
+		sprite.generate_attributes();
+		sprite
+	}
+
+	pub fn from_grid(texture: Handle, atlas_width: f32, atlas_height: f32, columns: usize, rows: usize, frame_count: usize, frames_per_second: f32) -> Self {
+		assert!(frame_count <= columns * rows, "Cannot create {} frames from a {}x{} grid", frame_count, columns, rows);
+
+		let width = atlas_width / columns as f32;
+		let height = atlas_height / rows as f32;
+
+		let frames = (0..frame_count).map(|i| SpriteFrame {
+			x: (i % columns) as f32 * width,
+			y: (i / columns) as f32 * height,
+			width,
+			height
+		}).collect();
+
+		Self::new(texture, atlas_width, atlas_height, frames, frames_per_second)
+	}
+
+	pub fn frame_index(&self) -> usize {
+		self.frame_index
+	}
+
+	pub fn set_frame_index(&mut self, frame_index: usize) {
+		assert!(frame_index < self.frames.len(), "Frame index {} out of range", frame_index);
+
+		self.frame_index = frame_index;
+		self.frame_duration = Duration::new(0, 0);
+		self.generate_attributes();
+	}
+
+	pub fn uv_rect(&self) -> (f32, f32, f32, f32) {
+		let frame = &self.frames[self.frame_index];
+
+		(frame.x / self.atlas_width, frame.y / self.atlas_height, (frame.x + frame.width) / self.atlas_width, (frame.y + frame.height) / self.atlas_height)
+	}
+
+	pub fn indices(&self) -> &[u16] {
+		&self.indices
+	}
+
+	pub fn attributes(&self) -> &[f32] {
+		&self.attributes
+	}
+
+	pub(crate) fn generate_attributes(&mut self) {
+		let frame = self.frames[self.frame_index];
+		let (u_min, v_min, u_max, v_max) = self.uv_rect();
+
+		self.attributes.clear();
+		self.attributes.extend_from_slice(&[
+			0.0, 0.0, u_min, v_min,
+			frame.width, 0.0, u_max, v_min,
+			frame.width, frame.height, u_max, v_max,
+			0.0, frame.height, u_min, v_max
+		]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{pool::Pool, Texture};
+
+	#[test]
+	fn generates_uvs_from_grid() {
+		let texture = Pool::new().add(Texture::from_color([255; 4]));
+		let mut sprite = AnimatedSprite::from_grid(texture, 64.0, 32.0, 4, 2, 6, 12.0);
+		assert_eq!(sprite.frames.len(), 6);
+		assert_eq!(sprite.uv_rect(), (0.0, 0.0, 0.25, 0.5));
+
+		// The fifth frame starts the second row
+		sprite.set_frame_index(5);
+		assert_eq!(sprite.uv_rect(), (0.25, 0.5, 0.5, 1.0));
+		assert_eq!(sprite.attributes(), &[
+			0.0, 0.0, 0.25, 0.5,
+			16.0, 0.0, 0.5, 0.5,
+			16.0, 16.0, 0.5, 1.0,
+			0.0, 16.0, 0.25, 1.0
+		]);
+		assert_eq!(sprite.indices(), &[0, 1, 2, 0, 2, 3]);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,369 @@
+use crate::{AnimationClip, Entity, math::{Quaternion, Vector3, quaternion, vector3}, pool::{Handle, Pool}};
+
+// What a state plays
+pub enum Motion {
+	Clip(Handle),
+	// Blends between the clips by where the float parameter falls between their thresholds, such as idle at a speed of 0,
+	// walk at 2 and run at 6. The clips play in step so they should cycle the same way, such as starting on the same foot.
+	Blend1D { parameter: String, clips: Vec<(f32, Handle)> }
+}
+
+pub struct AnimationState {
+	pub name: String,
+	pub motion: Motion,
+	pub speed: f32,
+	// Holds the last pose once it ends otherwise
+	pub looping: bool
+}
+
+impl AnimationState {
+	pub fn new(name: &str, motion: Motion) -> Self {
+		if let Motion::Blend1D { clips, .. } = &motion {
+			assert!(!clips.is_empty(), "Blend state {} needs at least one clip", name);
+			assert!(clips.windows(2).all(|pair| pair[0].0 <= pair[1].0), "Blend state {} clips must be sorted by threshold", name);
+		}
+
+		Self {
+			name: name.to_string(),
+			motion,
+			speed: 1.0,
+			looping: true
+		}
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AnimationParameter {
+	Float(f32),
+	Bool(bool),
+	// Stays set until a transition it lets through is taken
+	Trigger(bool)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AnimationCondition {
+	Greater(String, f32),
+	Less(String, f32),
+	Bool(String, bool),
+	Trigger(String)
+}
+
+pub struct AnimationTransition {
+	// None to transition from any other state
+	pub from: Option<usize>,
+	pub to: usize,
+	// Seconds to cross fade from one state to the other
+	pub blend_duration: f32,
+	// All of them have to hold
+	pub conditions: Vec<AnimationCondition>,
+	// How far through the from state from 0 to 1 it has to be before the transition can be taken, such as waiting for a
+	// jump's landing to finish
+	pub exit_time: Option<f32>
+}
+
+impl AnimationTransition {
+	pub fn new(from: Option<usize>, to: usize, blend_duration: f32, conditions: Vec<AnimationCondition>) -> Self {
+		Self {
+			from,
+			to,
+			blend_duration,
+			conditions,
+			exit_time: None
+		}
+	}
+}
+
+// Moves the controller's entity by how the root joint moves in the clips instead of moving the root joint, so the feet of a
+// walk cycle stay planted on the ground. The root joint is held where it starts in each clip, it has to be under the entity.
+// Clips should start at the origin facing +z.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RootMotion {
+	// The index of the root joint in the controller's joints
+	pub joint: usize,
+	// Only moves the entity along the ground and turns it about the y axis, the root joint keeps its height, lean and roll
+	// from the clip. Otherwise the entity follows the root joint entirely, such as when climbing.
+	pub flatten: bool
+}
+
+impl RootMotion {
+	pub fn new(joint: usize) -> Self {
+		Self {
+			joint,
+			flatten: true
+		}
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct PlayingState {
+	state: usize,
+	// How far through the state from 0 to 1 and how far it was the update before
+	phase: f32,
+	previous_phase: f32
+}
+
+// A clip to sample with the time in seconds to sample it at, the time it was sampled at the update before and its weight
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) struct ClipSample {
+	pub handle: Handle,
+	pub previous_time: f32,
+	pub time: f32,
+	pub weight: f32
+}
+
+// Plays animation clips on the joints, which are the entity itself and the entities under it which the clips' tracks animate.
+// The first state added is the one it starts in. Each update the first transition whose conditions hold is taken and the
+// state it leaves is faded out over the transition's blend duration.
+pub struct AnimationController {
+	pub joints: Vec<Entity>,
+	pub root_motion: Option<RootMotion>,
+	pub(crate) root_motion_delta: (Vector3, Quaternion),
+	states: Vec<AnimationState>,
+	transitions: Vec<AnimationTransition>,
+	parameters: Vec<(String, AnimationParameter)>,
+	current: PlayingState,
+	previous: Option<PlayingState>,
+	blend_elapsed: f32,
+	blend_duration: f32
+}
+
+impl AnimationController {
+	pub fn new(joints: Vec<Entity>) -> Self {
+		Self {
+			joints,
+			root_motion: None,
+			root_motion_delta: (vector3::ZERO, quaternion::ZERO),
+			states: vec![],
+			transitions: vec![],
+			parameters: vec![],
+			current: PlayingState { state: 0, phase: 0.0, previous_phase: 0.0 },
+			previous: None,
+			blend_elapsed: 0.0,
+			blend_duration: 0.0
+		}
+	}
+
+	// Returns the state's index which transitions refer to it by
+	pub fn add_state(&mut self, state: AnimationState) -> usize {
+		self.states.push(state);
+		self.states.len() - 1
+	}
+
+	pub fn add_transition(&mut self, transition: AnimationTransition) {
+		assert!(transition.to < self.states.len(), "Animation transition goes to state {} which doesn't exist", transition.to);
+		self.transitions.push(transition);
+	}
+
+	pub fn state_index(&self, name: &str) -> Option<usize> {
+		self.states.iter().position(|state| state.name == name)
+	}
+
+	pub fn current_state(&self) -> &AnimationState {
+		&self.states[self.current.state]
+	}
+
+	// How far the root motion moved and turned the entity in the last update, in the entity's space
+	pub fn root_motion_delta(&self) -> (Vector3, Quaternion) {
+		self.root_motion_delta
+	}
+
+	// Whether a state is still fading out
+	pub fn blending(&self) -> bool {
+		self.previous.is_some()
+	}
+
+	// Jumps to the state without any of the transitions
+	pub fn play(&mut self, state: usize, blend_duration: f32) {
+		assert!(state < self.states.len(), "Cannot play animation state {} which doesn't exist", state);
+		self.previous = (blend_duration > 0.0).then_some(self.current);
+		self.current = PlayingState { state, phase: 0.0, previous_phase: 0.0 };
+		self.blend_elapsed = 0.0;
+		self.blend_duration = blend_duration;
+	}
+
+	pub fn set_float(&mut self, name: &str, value: f32) {
+		self.set_parameter(name, AnimationParameter::Float(value));
+	}
+
+	pub fn set_bool(&mut self, name: &str, value: bool) {
+		self.set_parameter(name, AnimationParameter::Bool(value));
+	}
+
+	pub fn set_trigger(&mut self, name: &str) {
+		self.set_parameter(name, AnimationParameter::Trigger(true));
+	}
+
+	pub fn parameter(&self, name: &str) -> Option<AnimationParameter> {
+		self.parameters.iter().find(|(set_name, _)| set_name == name).map(|(_, parameter)| *parameter)
+	}
+
+	fn set_parameter(&mut self, name: &str, parameter: AnimationParameter) {
+		match self.parameters.iter_mut().find(|(set_name, _)| set_name == name) {
+			Some(entry) => entry.1 = parameter,
+			None => self.parameters.push((name.to_string(), parameter))
+		}
+	}
+
+	fn float(&self, name: &str) -> f32 {
+		match self.parameter(name) {
+			Some(AnimationParameter::Float(value)) => value,
+			_ => 0.0
+		}
+	}
+
+	fn holds(&self, condition: &AnimationCondition) -> bool {
+		match condition {
+			AnimationCondition::Greater(name, value) => matches!(self.parameter(name), Some(AnimationParameter::Float(set)) if set > *value),
+			AnimationCondition::Less(name, value) => matches!(self.parameter(name), Some(AnimationParameter::Float(set)) if set < *value),
+			AnimationCondition::Bool(name, value) => self.parameter(name) == Some(AnimationParameter::Bool(*value)),
+			AnimationCondition::Trigger(name) => self.parameter(name) == Some(AnimationParameter::Trigger(true))
+		}
+	}
+
+	// Takes a transition if one applies and moves the playing states forward
+	pub(crate) fn advance(&mut self, delta_time: f32, clips: &Pool<AnimationClip>) {
+		assert!(!self.states.is_empty(), "Animation controller has no states");
+
+		let transition = self.transitions.iter().find(|transition| {
+			let from = transition.from.map_or(transition.to != self.current.state, |from| from == self.current.state);
+			from && transition.exit_time.is_none_or(|exit_time| self.current.phase >= exit_time) && transition.conditions.iter().all(|condition| self.holds(condition))
+		});
+
+		if let Some(transition) = transition {
+			let (to, blend_duration) = (transition.to, transition.blend_duration);
+
+			for condition in transition.conditions.clone() {
+				if let AnimationCondition::Trigger(name) = condition {
+					self.set_parameter(&name, AnimationParameter::Trigger(false));
+				}
+			}
+
+			self.play(to, blend_duration);
+		}
+
+		self.current = self.advanced(self.current, delta_time, clips);
+		self.previous = self.previous.map(|previous| self.advanced(previous, delta_time, clips));
+		self.blend_elapsed += delta_time;
+
+		if self.blend_elapsed >= self.blend_duration {
+			self.previous = None;
+		}
+	}
+
+	fn advanced(&self, playing_state: PlayingState, delta_time: f32, clips: &Pool<AnimationClip>) -> PlayingState {
+		let state = &self.states[playing_state.state];
+		let duration: f32 = self.motion_clips(&state.motion).iter().map(|(handle, weight)| clips.borrow(*handle).duration * weight).sum();
+		let phase = playing_state.phase + delta_time * state.speed / duration;
+
+		PlayingState {
+			state: playing_state.state,
+			phase: if state.looping { phase.rem_euclid(1.0) } else { phase.clamp(0.0, 1.0) },
+			previous_phase: playing_state.phase
+		}
+	}
+
+	// The clips of the motion and how much of it each makes up
+	fn motion_clips(&self, motion: &Motion) -> Vec<(Handle, f32)> {
+		match motion {
+			Motion::Clip(handle) => vec![(*handle, 1.0)],
+			Motion::Blend1D { parameter, clips } => {
+				let value = self.float(parameter);
+				let next_index = clips.partition_point(|(threshold, _)| *threshold <= value);
+
+				if next_index == 0 {
+					return vec![(clips[0].1, 1.0)];
+				}
+
+				let (previous_threshold, previous_handle) = clips[next_index - 1];
+
+				match clips.get(next_index) {
+					Some((next_threshold, next_handle)) => {
+						let t = (value - previous_threshold) / (next_threshold - previous_threshold);
+						vec![(previous_handle, 1.0 - t), (*next_handle, t)]
+					},
+					None => vec![(previous_handle, 1.0)]
+				}
+			}
+		}
+	}
+
+	// The weights of the clips add up to 1
+	pub(crate) fn clip_samples(&self, clips: &Pool<AnimationClip>) -> Vec<ClipSample> {
+		let current_weight = match self.previous {
+			Some(_) => (self.blend_elapsed / self.blend_duration).min(1.0),
+			None => 1.0
+		};
+
+		let mut samples = vec![];
+		let playing_states = [Some((self.current, current_weight)), self.previous.map(|previous| (previous, 1.0 - current_weight))];
+
+		for &(playing_state, state_weight) in playing_states.iter().flatten() {
+			for (handle, weight) in self.motion_clips(&self.states[playing_state.state].motion) {
+				if weight > 0.0 {
+					let duration = clips.borrow(handle).duration;
+
+					samples.push(ClipSample {
+						handle,
+						previous_time: playing_state.previous_phase * duration,
+						time: playing_state.phase * duration,
+						weight: weight * state_weight
+					});
+				}
+			}
+		}
+
+		samples
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transitions_on_parameters() {
+		let mut clips = Pool::new();
+		let idle = clips.add(AnimationClip::new(2.0, vec![]));
+		let walk = clips.add(AnimationClip::new(1.0, vec![]));
+		let run = clips.add(AnimationClip::new(0.5, vec![]));
+		let jump = clips.add(AnimationClip::new(1.0, vec![]));
+
+		let mut controller = AnimationController::new(vec![]);
+		let locomotion = controller.add_state(AnimationState::new("locomotion", Motion::Blend1D {
+			parameter: String::from("speed"),
+			clips: vec![(0.0, idle), (2.0, walk), (6.0, run)]
+		}));
+
+		let mut jump_state = AnimationState::new("jump", Motion::Clip(jump));
+		jump_state.looping = false;
+		let jumping = controller.add_state(jump_state);
+
+		controller.add_transition(AnimationTransition::new(None, jumping, 0.2, vec![AnimationCondition::Trigger(String::from("jump"))]));
+		let mut land = AnimationTransition::new(Some(jumping), locomotion, 0.2, vec![]);
+		land.exit_time = Some(1.0);
+		controller.add_transition(land);
+
+		// Half way between walking and running
+		controller.set_float("speed", 4.0);
+		controller.advance(0.1, &clips);
+		assert_eq!(controller.clip_samples(&clips).iter().map(|sample| (sample.handle, sample.weight)).collect::<Vec<_>>(), [(walk, 0.5), (run, 0.5)]);
+
+		// The trigger is used up by the transition and the jump cross fades in
+		controller.set_trigger("jump");
+		controller.advance(0.1, &clips);
+		assert_eq!(controller.current_state().name, "jump");
+		assert_eq!(controller.parameter("jump"), Some(AnimationParameter::Trigger(false)));
+		let samples = controller.clip_samples(&clips);
+		assert_eq!(samples[0].handle, jump);
+		assert!((samples[0].weight - 0.5).abs() < 1e-5);
+		assert!(controller.blending());
+
+		// Lands once the jump finishes
+		controller.advance(0.5, &clips);
+		assert!(!controller.blending());
+		controller.advance(0.5, &clips);
+		assert_eq!(controller.current_state().name, "jump");
+		controller.advance(0.1, &clips);
+		assert_eq!(controller.current_state().name, "locomotion");
+	}
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+use crate::math::Vector3;
+use super::ColorVertex;
+
+const SEGMENT_COUNT: usize = 16;
+// How far above the ground the decal is lifted so it doesn't fight with the ground's depth
+const GROUND_OFFSET: f32 = 0.01;
+
+// A cheap stand in for a real shadow, a dark disc on whatever the blob shadow system finds below the entity. It fades out
+// the higher the entity is above the ground.
+pub struct BlobShadow {
+	pub radius: f32,
+	// How dark the center is when the entity is on the ground, the disc fades out towards its edge
+	pub opacity: f32,
+	// How far below the entity the ground is looked for, the shadow has faded out completely by then
+	pub max_distance: f32,
+	pub(crate) ground: Option<BlobShadowGround>
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlobShadowGround {
+	pub position: Vector3,
+	pub normal: Vector3,
+	// How far the ground is below the entity
+	pub distance: f32
+}
+
+impl BlobShadow {
+	pub fn new(radius: f32) -> Self {
+		Self {
+			radius,
+			opacity: 0.6,
+			max_distance: 5.0,
+			ground: None
+		}
+	}
+
+	// Where the shadow was last placed, none when there was no ground within the max distance
+	pub fn ground(&self) -> Option<&BlobShadowGround> {
+		self.ground.as_ref()
+	}
+
+	// A fan of triangles lying on the ground, 3 vertices per segment
+	pub fn decal(&self, vertices: &mut Vec<ColorVertex>) {
+		let Some(ground) = &self.ground else {
+			return;
+		};
+
+		let opacity = self.opacity * (1.0 - ground.distance / self.max_distance).clamp(0.0, 1.0);
+
+		if opacity <= 0.0 {
+			return;
+		}
+
+		let mut tangent = if ground.normal.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+		tangent.cross(&ground.normal);
+		tangent.normalize();
+
+		let mut bitangent = ground.normal;
+		bitangent.cross(&tangent);
+
+		let center = ground.position + ground.normal * GROUND_OFFSET;
+		let vertex = |position: Vector3, alpha: f32| ColorVertex { position: [position.x, position.y, position.z], color: [0.0, 0.0, 0.0, alpha] };
+
+		let rim: Vec<ColorVertex> = (0..=SEGMENT_COUNT)
+			.map(|segment| {
+				let angle = segment as f32 / SEGMENT_COUNT as f32 * 2.0 * PI;
+				vertex(center + (tangent * angle.cos() + bitangent * angle.sin()) * self.radius, 0.0)
+			})
+			.collect();
+
+		for pair in rim.windows(2) {
+			vertices.extend_from_slice(&[vertex(center, opacity), pair[0], pair[1]]);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decal_fades_with_height() {
+		let mut blob_shadow = BlobShadow::new(0.5);
+		let mut vertices = vec![];
+		blob_shadow.decal(&mut vertices);
+		assert!(vertices.is_empty());
+
+		blob_shadow.ground = Some(BlobShadowGround { position: Vector3::new(1.0, 2.0, 3.0), normal: Vector3::new(0.0, 1.0, 0.0), distance: 2.5 });
+		blob_shadow.decal(&mut vertices);
+		assert_eq!(vertices.len(), SEGMENT_COUNT * 3);
+
+		// Lies flat just above the ground with the rim at the radius
+		for vertex in &vertices {
+			assert!((vertex.position[1] - 2.0 - GROUND_OFFSET).abs() < 1e-6);
+			let distance = Vector3::new(vertex.position[0] - 1.0, 0.0, vertex.position[2] - 3.0).length();
+			assert!(distance < 1e-6 && vertex.color[3] == 0.3 || (distance - 0.5).abs() < 1e-5 && vertex.color[3] == 0.0);
+		}
+
+		vertices.clear();
+		blob_shadow.ground.as_mut().unwrap().distance = 5.0;
+		blob_shadow.decal(&mut vertices);
+		assert!(vertices.is_empty());
+	}
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+use crate::math::Vector2;
+
+#[derive(Copy, Clone)]
+pub enum Shape2D {
+	Aabb { half_extents: Vector2 },
+	Circle { radius: f32 }
+}
+
+#[derive(Copy, Clone)]
+pub struct Collider2D {
+	pub shape: Shape2D,
+	pub offset: Vector2,
+	pub is_static: bool
+}
+
+impl Collider2D {
+	pub fn new(shape: Shape2D) -> Self {
+		Self {
+			shape,
+			offset: Vector2::new(0.0, 0.0),
+			is_static: false
+		}
+	}
+
+	pub fn new_static(shape: Shape2D) -> Self {
+		Self {
+			shape,
+			offset: Vector2::new(0.0, 0.0),
+			is_static: true
+		}
+	}
+
+	pub(crate) fn scaled_shape(&self, scale: &Vector2) -> Shape2D {
+		match self.shape {
+			Shape2D::Aabb { half_extents } => Shape2D::Aabb { half_extents: Vector2::new(half_extents.x * scale.x.abs(), half_extents.y * scale.y.abs()) },
+			Shape2D::Circle { radius } => Shape2D::Circle { radius: radius * scale.x.abs().max(scale.y.abs()) }
+		}
+	}
+}
+
+impl Shape2D {
+	pub fn contains_point(&self, position: &Vector2, point: &Vector2) -> bool {
+		let delta = point - position;
+
+		match *self {
+			Shape2D::Aabb { half_extents } => delta.x.abs() <= half_extents.x && delta.y.abs() <= half_extents.y,
+			Shape2D::Circle { radius } => delta.length_sq() <= radius * radius
+		}
+	}
+
+	// Returns the normal pointing from self towards other and the penetration depth along it
+	pub fn overlap(&self, position: &Vector2, other: &Shape2D, other_position: &Vector2) -> Option<(Vector2, f32)> {
+		let delta = other_position - position;
+
+		match (*self, *other) {
+			(Shape2D::Aabb { half_extents: a }, Shape2D::Aabb { half_extents: b }) => {
+				let overlap_x = a.x + b.x - delta.x.abs();
+				let overlap_y = a.y + b.y - delta.y.abs();
+
+				if overlap_x <= 0.0 || overlap_y <= 0.0 {
+					None
+				}
+				else if overlap_x < overlap_y {
+					Some((Vector2::new(delta.x.signum(), 0.0), overlap_x))
+				}
+				else {
+					Some((Vector2::new(0.0, delta.y.signum()), overlap_y))
+				}
+			},
+			(Shape2D::Circle { radius: a }, Shape2D::Circle { radius: b }) => {
+				let distance = delta.length();
+				let depth = a + b - distance;
+
+				if depth <= 0.0 {
+					None
+				}
+				else if distance == 0.0 {
+					Some((Vector2::new(0.0, 1.0), depth))
+				}
+				else {
+					Some((delta / distance, depth))
+				}
+			},
+			(Shape2D::Aabb { half_extents }, Shape2D::Circle { radius }) => Self::aabb_circle_overlap(&half_extents, &delta, radius),
+			(Shape2D::Circle { radius }, Shape2D::Aabb { half_extents }) => {
+				Self::aabb_circle_overlap(&half_extents, &-delta, radius).map(|(normal, depth)| (-normal, depth))
+			}
+		}
+	}
+
+	fn aabb_circle_overlap(half_extents: &Vector2, circle_delta: &Vector2, radius: f32) -> Option<(Vector2, f32)> {
+		let closest = Vector2::new(
+			circle_delta.x.clamp(-half_extents.x, half_extents.x),
+			circle_delta.y.clamp(-half_extents.y, half_extents.y));
+
+		// The circle's center is inside the box so push it out along the shallowest axis
+		if closest == *circle_delta {
+			let overlap_x = half_extents.x - circle_delta.x.abs();
+			let overlap_y = half_extents.y - circle_delta.y.abs();
+
+			return if overlap_x < overlap_y {
+				Some((Vector2::new(if circle_delta.x < 0.0 { -1.0 } else { 1.0 }, 0.0), overlap_x + radius))
+			}
+			else {
+				Some((Vector2::new(0.0, if circle_delta.y < 0.0 { -1.0 } else { 1.0 }), overlap_y + radius))
+			};
+		}
+
+		let mut normal = circle_delta - closest;
+		let distance = normal.length();
+
+		if distance >= radius {
+			return None;
+		}
+
+		normal /= distance;
+		Some((normal, radius - distance))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn contains_point() {
+		let aabb = Shape2D::Aabb { half_extents: Vector2::new(1.0, 2.0) };
+		assert!(aabb.contains_point(&Vector2::new(1.0, 1.0), &Vector2::new(1.5, 2.5)));
+		assert!(!aabb.contains_point(&Vector2::new(1.0, 1.0), &Vector2::new(2.5, 2.5)));
+
+		let circle = Shape2D::Circle { radius: 1.0 };
+		assert!(circle.contains_point(&Vector2::new(1.0, 1.0), &Vector2::new(1.5, 1.5)));
+		assert!(!circle.contains_point(&Vector2::new(1.0, 1.0), &Vector2::new(2.0, 2.0)));
+	}
+
+	#[test]
+	fn overlap_aabb_aabb() {
+		let a = Shape2D::Aabb { half_extents: Vector2::new(1.0, 1.0) };
+		let b = Shape2D::Aabb { half_extents: Vector2::new(1.0, 1.0) };
+		assert_eq!(a.overlap(&Vector2::new(0.0, 0.0), &b, &Vector2::new(1.5, 0.5)), Some((Vector2::new(1.0, 0.0), 0.5)));
+		assert_eq!(a.overlap(&Vector2::new(0.0, 0.0), &b, &Vector2::new(0.5, -1.5)), Some((Vector2::new(0.0, -1.0), 0.5)));
+		assert_eq!(a.overlap(&Vector2::new(0.0, 0.0), &b, &Vector2::new(3.0, 0.0)), None);
+	}
+
+	#[test]
+	fn overlap_circle_circle() {
+		let a = Shape2D::Circle { radius: 1.0 };
+		let b = Shape2D::Circle { radius: 2.0 };
+		assert_eq!(a.overlap(&Vector2::new(0.0, 0.0), &b, &Vector2::new(0.0, 2.0)), Some((Vector2::new(0.0, 1.0), 1.0)));
+		assert_eq!(a.overlap(&Vector2::new(0.0, 0.0), &b, &Vector2::new(3.0, 0.0)), None);
+	}
+
+	#[test]
+	fn overlap_aabb_circle() {
+		let aabb = Shape2D::Aabb { half_extents: Vector2::new(1.0, 1.0) };
+		let circle = Shape2D::Circle { radius: 1.0 };
+		assert_eq!(aabb.overlap(&Vector2::new(0.0, 0.0), &circle, &Vector2::new(1.5, 0.0)), Some((Vector2::new(1.0, 0.0), 0.5)));
+		assert_eq!(circle.overlap(&Vector2::new(1.5, 0.0), &aabb, &Vector2::new(0.0, 0.0)), Some((Vector2::new(-1.0, 0.0), 0.5)));
+		assert_eq!(aabb.overlap(&Vector2::new(0.0, 0.0), &circle, &Vector2::new(0.5, 0.0)), Some((Vector2::new(1.0, 0.0), 1.5)));
+		assert_eq!(aabb.overlap(&Vector2::new(0.0, 0.0), &circle, &Vector2::new(2.0, 2.0)), None);
+	}
+}
\ No newline at end of file
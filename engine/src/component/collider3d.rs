@@ -0,0 +1,224 @@
+use crate::math::{Vector3, vector3};
+
+// Alternating between the closest points on each shape converges on the closest pair for shapes without a closed form
+const CLOSEST_POINTS_MAX_ITERATIONS: usize = 32;
+const CLOSEST_POINTS_TOLERANCE: f32 = 1e-6;
+
+// The shapes are axis aligned like the 2D ones, the capsule and cylinder stand along the y axis. The half height is from the
+// center to the end of the capsule's segment, not counting the round caps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape3D {
+	Sphere { radius: f32 },
+	Box { half_extents: Vector3 },
+	Capsule { half_height: f32, radius: f32 },
+	Cylinder { half_height: f32, radius: f32 }
+}
+
+#[derive(Copy, Clone)]
+pub struct Collider3D {
+	pub shape: Shape3D,
+	pub offset: Vector3,
+	pub is_static: bool
+}
+
+impl Collider3D {
+	pub fn new(shape: Shape3D) -> Self {
+		Self {
+			shape,
+			offset: vector3::ZERO,
+			is_static: false
+		}
+	}
+
+	pub fn new_static(shape: Shape3D) -> Self {
+		Self {
+			shape,
+			offset: vector3::ZERO,
+			is_static: true
+		}
+	}
+}
+
+impl Shape3D {
+	pub fn contains_point(&self, position: &Vector3, point: &Vector3) -> bool {
+		self.closest_point(position, point).distance(point) <= CLOSEST_POINTS_TOLERANCE
+	}
+
+	// The shapes are solid so a point inside is its own closest point
+	pub fn closest_point(&self, position: &Vector3, point: &Vector3) -> Vector3 {
+		let delta = point - position;
+
+		match *self {
+			Shape3D::Sphere { radius } => position + clamp_length(&delta, radius),
+			Shape3D::Box { half_extents } => Vector3::new(
+				position.x + delta.x.clamp(-half_extents.x, half_extents.x),
+				position.y + delta.y.clamp(-half_extents.y, half_extents.y),
+				position.z + delta.z.clamp(-half_extents.z, half_extents.z)),
+			Shape3D::Capsule { half_height, radius } => {
+				let center = Vector3::new(position.x, position.y + delta.y.clamp(-half_height, half_height), position.z);
+				center + clamp_length(&(point - center), radius)
+			},
+			Shape3D::Cylinder { half_height, radius } => {
+				let radial = clamp_length(&Vector3::new(delta.x, 0.0, delta.z), radius);
+				Vector3::new(position.x + radial.x, position.y + delta.y.clamp(-half_height, half_height), position.z + radial.z)
+			}
+		}
+	}
+
+	// The point on each shape closest to the other, when the shapes overlap both are the same point inside both shapes
+	pub fn closest_points(&self, position: &Vector3, other: &Shape3D, other_position: &Vector3) -> (Vector3, Vector3) {
+		// Spheres and capsules are a point or segment grown by a radius which has an exact answer
+		if let (Some((start, end, radius)), Some((other_start, other_end, other_radius))) = (self.core(position), other.core(other_position)) {
+			let (a, b) = closest_points_on_segments(&start, &end, &other_start, &other_end);
+			let delta = b - a;
+			let distance = delta.length();
+
+			if distance <= radius + other_radius {
+				let point = if distance > 0.0 { a + delta * (radius / (radius + other_radius)) } else { a };
+				return (point, point);
+			}
+
+			let direction = delta / distance;
+			return (a + direction * radius, b - direction * other_radius);
+		}
+
+		let mut b = other.closest_point(other_position, position);
+		let mut a = self.closest_point(position, &b);
+
+		for _ in 0..CLOSEST_POINTS_MAX_ITERATIONS {
+			let next_b = other.closest_point(other_position, &a);
+			let next_a = self.closest_point(position, &next_b);
+			let converged = (next_b - b).length_sq() < CLOSEST_POINTS_TOLERANCE * CLOSEST_POINTS_TOLERANCE;
+			a = next_a;
+			b = next_b;
+
+			if converged {
+				break;
+			}
+		}
+
+		(a, b)
+	}
+
+	// Zero when the shapes overlap
+	pub fn distance(&self, position: &Vector3, other: &Shape3D, other_position: &Vector3) -> f32 {
+		let (a, b) = self.closest_points(position, other, other_position);
+		a.distance(&b)
+	}
+
+	// The segment and radius of the shapes which are made by growing one
+	fn core(&self, position: &Vector3) -> Option<(Vector3, Vector3, f32)> {
+		match *self {
+			Shape3D::Sphere { radius } => Some((*position, *position, radius)),
+			Shape3D::Capsule { half_height, radius } => {
+				let offset = Vector3::new(0.0, half_height, 0.0);
+				Some((position - offset, position + offset, radius))
+			},
+			_ => None
+		}
+	}
+}
+
+fn clamp_length(vector: &Vector3, max_length: f32) -> Vector3 {
+	let length = vector.length();
+
+	if length > max_length {
+		vector * (max_length / length)
+	}
+	else {
+		*vector
+	}
+}
+
+// The closest points between segments p1 q1 and p2 q2, from Real-Time Collision Detection by Christer Ericson
+fn closest_points_on_segments(p1: &Vector3, q1: &Vector3, p2: &Vector3, q2: &Vector3) -> (Vector3, Vector3) {
+	let d1 = q1 - p1;
+	let d2 = q2 - p2;
+	let r = p1 - p2;
+	let a = d1.dot(&d1);
+	let e = d2.dot(&d2);
+	let f = d2.dot(&r);
+
+	let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+		(0.0, 0.0)
+	}
+	else if a <= f32::EPSILON {
+		(0.0, (f / e).clamp(0.0, 1.0))
+	}
+	else {
+		let c = d1.dot(&r);
+
+		if e <= f32::EPSILON {
+			((-c / a).clamp(0.0, 1.0), 0.0)
+		}
+		else {
+			let b = d1.dot(&d2);
+			let denominator = a * e - b * b;
+
+			// Parallel segments have no single closest pair so any s works
+			let s = if denominator != 0.0 { ((b * f - c * e) / denominator).clamp(0.0, 1.0) } else { 0.0 };
+			let t = (b * s + f) / e;
+
+			if t < 0.0 {
+				((-c / a).clamp(0.0, 1.0), 0.0)
+			}
+			else if t > 1.0 {
+				(((b - c) / a).clamp(0.0, 1.0), 1.0)
+			}
+			else {
+				(s, t)
+			}
+		}
+	};
+
+	(p1 + d1 * s, p2 + d2 * t)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::assert_approx_eq;
+
+	#[test]
+	fn closest_point() {
+		let position = Vector3::new(1.0, 1.0, 1.0);
+		let capsule = Shape3D::Capsule { half_height: 1.0, radius: 0.5 };
+		assert_approx_eq(&capsule.closest_point(&position, &Vector3::new(3.0, 1.5, 1.0)), &Vector3::new(1.5, 1.5, 1.0), 1e-6);
+		assert_approx_eq(&capsule.closest_point(&position, &Vector3::new(1.0, 5.0, 1.0)), &Vector3::new(1.0, 2.5, 1.0), 1e-6);
+		assert!(capsule.contains_point(&position, &Vector3::new(1.0, 2.4, 1.0)));
+		assert!(!capsule.contains_point(&position, &Vector3::new(1.4, 2.4, 1.0)));
+
+		// The cylinder has flat ends unlike the capsule
+		let cylinder = Shape3D::Cylinder { half_height: 1.0, radius: 0.5 };
+		assert_approx_eq(&cylinder.closest_point(&position, &Vector3::new(3.0, 5.0, 1.0)), &Vector3::new(1.5, 2.0, 1.0), 1e-6);
+		assert!(cylinder.contains_point(&position, &Vector3::new(1.4, 1.9, 1.0)));
+		assert!(!cylinder.contains_point(&position, &Vector3::new(1.4, 1.0, 1.4)));
+	}
+
+	#[test]
+	fn capsule_distances() {
+		let capsule = Shape3D::Capsule { half_height: 1.0, radius: 0.5 };
+		let sphere = Shape3D::Sphere { radius: 1.0 };
+
+		// Side by side, end to end and overlapping
+		assert!((capsule.distance(&vector3::ZERO, &capsule, &Vector3::new(3.0, 0.5, 0.0)) - 2.0).abs() < 1e-5);
+		assert!((capsule.distance(&vector3::ZERO, &sphere, &Vector3::new(0.0, 4.0, 0.0)) - 1.5).abs() < 1e-5);
+		assert_eq!(capsule.distance(&vector3::ZERO, &sphere, &Vector3::new(1.0, 0.5, 0.0)), 0.0);
+
+		let (a, b) = capsule.closest_points(&vector3::ZERO, &sphere, &Vector3::new(2.0, -3.0, 0.0));
+		assert_approx_eq(&a, &Vector3::new(0.5f32.sqrt() / 2.0, -1.0 - 0.5f32.sqrt() / 2.0, 0.0), 1e-5);
+		assert_approx_eq(&b, &Vector3::new(2.0 - 0.5f32.sqrt(), -3.0 + 0.5f32.sqrt(), 0.0), 1e-5);
+	}
+
+	#[test]
+	fn iterative_distances() {
+		let cylinder = Shape3D::Cylinder { half_height: 1.0, radius: 1.0 };
+		let box3 = Shape3D::Box { half_extents: Vector3::new(1.0, 1.0, 1.0) };
+		let sphere = Shape3D::Sphere { radius: 0.5 };
+
+		assert!((box3.distance(&vector3::ZERO, &box3, &Vector3::new(3.0, 0.5, 0.0)) - 1.0).abs() < 1e-5);
+		assert!((cylinder.distance(&vector3::ZERO, &sphere, &Vector3::new(0.0, 3.0, 0.0)) - 1.5).abs() < 1e-5);
+		assert!((cylinder.distance(&vector3::ZERO, &box3, &Vector3::new(3.0, 0.0, 3.0)) - (18.0f32.sqrt() - 1.0 - 2.0f32.sqrt())).abs() < 1e-3);
+		assert!(cylinder.distance(&vector3::ZERO, &box3, &Vector3::new(1.5, 1.5, 0.0)) < 1e-5);
+	}
+}
\ No newline at end of file
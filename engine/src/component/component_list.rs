@@ -1,5 +1,8 @@
 use crate::{EntityManager, Entity, entity_manager::MAX_ENTITY_COUNT};
 
+// Components are iterated in the order they were added, removing one moves the last component into its place unless it's
+// removed with remove_ordered. The order only depends on the adds and removes so the same calls always give the same order.
+
 pub struct ComponentList<T> {
 	components: Vec<(Entity, T)>,
 	entity_to_index_map: [Option<usize>; MAX_ENTITY_COUNT]
@@ -21,15 +24,35 @@ impl<T> ComponentList<T> {
 		entity_manager.increment_component_count(entity.index)
 	}
 
+	// Keeps the components in insertion order except for the last one which takes the removed one's place
 	pub fn remove(&mut self, entity_manager: &mut EntityManager, entity: &Entity) {
+		let component_index = self.take_index(entity_manager, entity);
+		self.components.swap_remove(component_index);
+
+		if let Some((swapped_entity, _)) = self.components.get(component_index) {
+			self.entity_to_index_map[swapped_entity.index] = Some(component_index);
+		}
+	}
+
+	// Keeps the remaining components in insertion order by shifting the ones after the removed one down which is linear in
+	// the number of components, use this when something depends on the order
+	pub fn remove_ordered(&mut self, entity_manager: &mut EntityManager, entity: &Entity) {
+		let component_index = self.take_index(entity_manager, entity);
+		self.components.remove(component_index);
+
+		for (shifted_index, (shifted_entity, _)) in self.components.iter().enumerate().skip(component_index) {
+			self.entity_to_index_map[shifted_entity.index] = Some(shifted_index);
+		}
+	}
+
+	fn take_index(&mut self, entity_manager: &mut EntityManager, entity: &Entity) -> usize {
 		let component_index_option = self.entity_to_index_map[entity.index];
 		assert!(component_index_option.is_some(), "Cannot remove component from entity {} because it does not have this component type", entity);
 		let component_index = component_index_option.unwrap();
+		assert_eq!(entity.generation, self.components[component_index].0.generation, "Cannot remove component from entity {} because it's generation does not match", entity);
 		self.entity_to_index_map[entity.index] = None;
-		self.components.swap_remove(component_index);
-		let (swapped_entity, _) = self.components[component_index];
-		self.entity_to_index_map[swapped_entity.index] = Some(component_index);
 		entity_manager.decrement_component_count(entity.index);
+		component_index
 	}
 
 	pub fn borrow(&self, entity: &Entity) -> &T {
@@ -73,4 +96,57 @@ impl<T> ComponentList<T> {
 	pub fn iter(&self) -> impl Iterator<Item = &(Entity, T)> {
 		self.components.iter()
 	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Entity, &mut T)> {
+		self.components.iter_mut().map(|(entity, component)| (&*entity, component))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn list(count: u32) -> (EntityManager, ComponentList<u32>, Vec<Entity>) {
+		let mut entity_manager = EntityManager::new();
+		let mut list = ComponentList::new();
+		let mut entities = vec![];
+
+		for component in 0..count {
+			let entity = entity_manager.create();
+			list.add(&mut entity_manager, entity, component);
+			entities.push(entity);
+		}
+
+		(entity_manager, list, entities)
+	}
+
+	fn order(list: &ComponentList<u32>) -> Vec<u32> {
+		list.iter().map(|(_, component)| *component).collect()
+	}
+
+	#[test]
+	fn remove_moves_the_last_component() {
+		let (mut entity_manager, mut list, entities) = list(4);
+		list.remove(&mut entity_manager, &entities[1]);
+		assert_eq!(order(&list), [0, 3, 2]);
+		assert_eq!(*list.borrow(&entities[3]), 3);
+
+		// Removing the last component leaves the others where they are
+		list.remove(&mut entity_manager, &entities[2]);
+		assert_eq!(order(&list), [0, 3]);
+	}
+
+	#[test]
+	fn remove_ordered_keeps_insertion_order() {
+		let (mut entity_manager, mut list, entities) = list(4);
+		list.remove_ordered(&mut entity_manager, &entities[1]);
+		assert_eq!(order(&list), [0, 2, 3]);
+		assert_eq!(*list.borrow(&entities[2]), 2);
+		assert_eq!(*list.borrow(&entities[3]), 3);
+
+		list.remove_ordered(&mut entity_manager, &entities[3]);
+		let entity = entity_manager.create();
+		list.add(&mut entity_manager, entity, 4);
+		assert_eq!(order(&list), [0, 2, 4]);
+	}
 }
\ No newline at end of file
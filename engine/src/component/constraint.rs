@@ -0,0 +1,157 @@
+use crate::{Entity, math::{Matrix4, Quaternion, Vector3, quaternion, vector3}};
+use super::Transform3D;
+
+// Simulated in steps no longer than this so stiff springs stay stable when a frame takes long
+const MAX_FOLLOW_STEP: f32 = 1.0 / 120.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ConstraintKind {
+	// Turns the entity's +z towards the target, which is the way cameras look, with the up vector kept up
+	LookAt { up: Vector3 },
+	// The offset is in the target's space so it turns with the target
+	CopyPosition { offset: Vector3 },
+	// The offset is applied in the target's space before its orientation
+	CopyRotation { offset: Quaternion },
+	// Pulled towards the target's position plus the offset in the target's space by a spring, the stiffness is how hard it's
+	// pulled and the damping how quickly it settles. A damping of twice the square root of the stiffness settles without
+	// overshooting.
+	Follow { offset: Vector3, stiffness: f32, damping: f32 }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Constraint {
+	pub target: Entity,
+	pub kind: ConstraintKind,
+	velocity: Vector3
+}
+
+impl Constraint {
+	pub fn new(target: Entity, kind: ConstraintKind) -> Self {
+		Self {
+			target,
+			kind,
+			velocity: vector3::ZERO
+		}
+	}
+
+	pub fn look_at(target: Entity) -> Self {
+		Self::new(target, ConstraintKind::LookAt { up: vector3::UNIT_Y })
+	}
+
+	pub fn copy_position(target: Entity, offset: Vector3) -> Self {
+		Self::new(target, ConstraintKind::CopyPosition { offset })
+	}
+
+	pub fn copy_rotation(target: Entity) -> Self {
+		Self::new(target, ConstraintKind::CopyRotation { offset: quaternion::ZERO })
+	}
+
+	pub fn follow(target: Entity, offset: Vector3, stiffness: f32) -> Self {
+		Self::new(target, ConstraintKind::Follow { offset, stiffness, damping: 2.0 * stiffness.sqrt() })
+	}
+
+	// Moves the world position and orientation towards what the constraint wants
+	fn evaluate(&mut self, position: &mut Vector3, orientation: &mut Quaternion, target_global_matrix: &Matrix4, delta_time: f32) {
+		let (target_position, target_orientation, _) = target_global_matrix.decompose();
+
+		match self.kind {
+			ConstraintKind::LookAt { up } => {
+				if let Some(look_orientation) = look_orientation(&(target_position - *position), &up) {
+					*orientation = look_orientation;
+				}
+			},
+			ConstraintKind::CopyPosition { offset } => {
+				let mut offset = offset;
+				offset.apply_quaternion(&target_orientation);
+				*position = target_position + offset;
+			},
+			ConstraintKind::CopyRotation { offset } => {
+				*orientation = target_orientation * offset;
+			},
+			ConstraintKind::Follow { offset, stiffness, damping } => {
+				let mut offset = offset;
+				offset.apply_quaternion(&target_orientation);
+				let goal = target_position + offset;
+
+				let step_count = (delta_time / MAX_FOLLOW_STEP).ceil().max(1.0);
+				let step = delta_time / step_count;
+
+				for _ in 0..step_count as usize {
+					let acceleration = (goal - *position) * stiffness - self.velocity * damping;
+					self.velocity += acceleration * step;
+					*position += self.velocity * step;
+				}
+			}
+		}
+	}
+}
+
+// Constraints evaluated in order after the entity's transform and its targets' have been updated, so a later constraint sees
+// where the earlier ones put the entity. Only the position and orientation are changed, they replace what the entity had.
+#[derive(Clone, PartialEq)]
+pub struct Constraints {
+	pub constraints: Vec<Constraint>
+}
+
+impl Constraints {
+	pub fn new(constraints: Vec<Constraint>) -> Self {
+		Self {
+			constraints
+		}
+	}
+
+	// The target global matrices are in the same order as the constraints, a constraint whose target is gone is skipped. Also
+	// works for transforms which aren't components such as the camera's, the transform's matrices aren't updated.
+	pub fn apply(&mut self, transform: &mut Transform3D, parent_global_matrix: Option<&Matrix4>, target_global_matrices: &[Option<Matrix4>], delta_time: f32) {
+		assert!(target_global_matrices.len() == self.constraints.len(), "There must be a target global matrix for each constraint");
+
+		let (mut position, mut orientation, _) = transform.global_matrix.decompose();
+
+		for (constraint, target_global_matrix) in self.constraints.iter_mut().zip(target_global_matrices) {
+			if let Some(target_global_matrix) = target_global_matrix {
+				constraint.evaluate(&mut position, &mut orientation, target_global_matrix, delta_time);
+			}
+		}
+
+		// Back into the parent's space
+		if let Some(parent_global_matrix) = parent_global_matrix {
+			let mut inverse_parent_global_matrix = *parent_global_matrix;
+			inverse_parent_global_matrix.invert();
+			position.apply_matrix4(&inverse_parent_global_matrix);
+
+			let (_, mut parent_orientation, _) = parent_global_matrix.decompose();
+			parent_orientation.conjigate();
+			orientation = parent_orientation * orientation;
+		}
+
+		transform.position = position;
+		transform.orientation = orientation;
+	}
+}
+
+// None when the direction is zero or along the up vector
+fn look_orientation(direction: &Vector3, up: &Vector3) -> Option<Quaternion> {
+	let mut z = *direction;
+	z.normalize();
+	let mut x = *up;
+	x.cross(&z);
+
+	if x.length_sq() < 1e-12 {
+		return None;
+	}
+
+	x.normalize();
+	let mut y = z;
+	y.cross(&x);
+
+	let rotation = Matrix4::new([
+		[x.x, y.x, z.x, 0.0],
+		[x.y, y.y, z.y, 0.0],
+		[x.z, y.z, z.z, 0.0],
+		[0.0, 0.0, 0.0, 1.0]
+	]);
+
+	let mut orientation = Quaternion::default();
+	orientation.set_from_rotation_matrix(&rotation);
+	Some(orientation)
+}
\ No newline at end of file
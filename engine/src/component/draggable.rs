@@ -0,0 +1,57 @@
+use crate::math::Vector3;
+
+// An entity with a HitArea2D which can be picked up with the cursor and dropped on a DropTarget. The groups are a bit mask of
+// which drop targets accept it.
+#[derive(Copy, Clone)]
+pub struct Draggable {
+	pub groups: u32,
+	// Goes back to where it was picked up when it isn't dropped on a drop target
+	pub return_on_miss: bool
+}
+
+impl Draggable {
+	pub fn new(groups: u32) -> Self {
+		Self {
+			groups,
+			return_on_miss: true
+		}
+	}
+}
+
+// An entity with a HitArea2D which accepts draggables in any of its groups
+#[derive(Copy, Clone)]
+pub struct DropTarget {
+	pub groups: u32
+}
+
+impl DropTarget {
+	pub fn new(groups: u32) -> Self {
+		Self {
+			groups
+		}
+	}
+}
+
+// What a Draggable3D moves along, in world space through the point it was grabbed at
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DragConstraint {
+	Plane { normal: Vector3 },
+	Axis { direction: Vector3 }
+}
+
+// An entity with a mesh which can be grabbed with the cursor and moved along a plane or axis, such as a piece of furniture in
+// an editor or a block in a puzzle. With a snap it moves in steps of that size from where it was grabbed.
+#[derive(Copy, Clone)]
+pub struct Draggable3D {
+	pub constraint: DragConstraint,
+	pub snap: Option<f32>
+}
+
+impl Draggable3D {
+	pub fn new(constraint: DragConstraint) -> Self {
+		Self {
+			constraint,
+			snap: None
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+use crate::math::Vector2;
+
+// A rectangle in the space of the entity's Transform2D which the cursor can hit. When areas overlap the one with the higher
+// order is on top.
+#[derive(Copy, Clone)]
+pub struct HitArea2D {
+	pub min: Vector2,
+	pub max: Vector2,
+	pub order: i32,
+	// The min and max corners in framebuffer pixels outside of which the area can't be hit
+	pub clip_rect: Option<(Vector2, Vector2)>
+}
+
+impl HitArea2D {
+	pub fn new(min: Vector2, max: Vector2) -> Self {
+		Self {
+			min,
+			max,
+			order: 0,
+			clip_rect: None
+		}
+	}
+
+	pub fn contains_point(&self, point: &Vector2) -> bool {
+		point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+	}
+}
\ No newline at end of file
@@ -0,0 +1,200 @@
+use crate::{Entity, math::{Vector3, vector3}};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum IkSolver {
+	// Exact for chains of three joints such as a hip, knee and ankle or a shoulder, elbow and wrist
+	TwoBone,
+	// Any number of joints, iterates until the tip is within the tolerance of the target
+	Fabrik { iterations: u32, tolerance: f32 }
+}
+
+impl IkSolver {
+	// Moves the joint positions so the last one reaches for the target while keeping the distances between them and the first
+	// one in place. A target out of reach straightens the chain towards it.
+	pub fn solve(&self, positions: &mut [Vector3], target: &Vector3, pole: Option<&Vector3>) {
+		match *self {
+			Self::TwoBone => solve_two_bone(positions, target, pole),
+			Self::Fabrik { iterations, tolerance } => {
+				solve_fabrik(positions, target, iterations, tolerance);
+
+				if let Some(pole) = pole {
+					for index in 1..positions.len() - 1 {
+						let bend = bend_direction(&positions[index - 1], &positions[index + 1], pole);
+
+						if let Some((foot, radius)) = foot_on_line(&positions[index - 1], &positions[index + 1], &positions[index]) {
+							positions[index] = foot + bend * radius;
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+// Rotates the joints, from the root to the tip and each a child of the one before, so the tip reaches for the target. The
+// chain bends towards the pole when there is one, such as a point in front of the knee, otherwise it keeps bending the way
+// it already does. The root stays in place and the tip turns with the last bone.
+pub struct IkChain {
+	pub joints: Vec<Entity>,
+	pub target: Entity,
+	pub pole: Option<Entity>,
+	pub solver: IkSolver
+}
+
+impl IkChain {
+	pub fn new(joints: Vec<Entity>, target: Entity, solver: IkSolver) -> Self {
+		assert!(joints.len() >= 2, "An IK chain needs at least two joints");
+		assert!(solver != IkSolver::TwoBone || joints.len() == 3, "A two bone IK chain needs exactly three joints");
+
+		Self {
+			joints,
+			target,
+			pole: None,
+			solver
+		}
+	}
+
+	pub fn two_bone(joints: [Entity; 3], target: Entity) -> Self {
+		Self::new(joints.to_vec(), target, IkSolver::TwoBone)
+	}
+
+	pub fn fabrik(joints: Vec<Entity>, target: Entity) -> Self {
+		Self::new(joints, target, IkSolver::Fabrik { iterations: 10, tolerance: 1e-3 })
+	}
+}
+
+fn solve_two_bone(positions: &mut [Vector3], target: &Vector3, pole: Option<&Vector3>) {
+	let root = positions[0];
+	let upper_length = positions[0].distance(&positions[1]);
+	let lower_length = positions[1].distance(&positions[2]);
+
+	let mut direction = target - root;
+
+	if direction.length_sq() == 0.0 {
+		return;
+	}
+
+	let distance = direction.length().clamp((upper_length - lower_length).abs().max(1e-6), upper_length + lower_length);
+	direction.normalize();
+
+	let bend = bend_direction(&root, target, pole.unwrap_or(&positions[1]));
+
+	// Where the middle joint's circle of reach crosses the end joint's
+	let along = (upper_length * upper_length - lower_length * lower_length + distance * distance) / (2.0 * distance);
+	let across = (upper_length * upper_length - along * along).max(0.0).sqrt();
+
+	positions[1] = root + direction * along + bend * across;
+	positions[2] = root + direction * distance;
+}
+
+fn solve_fabrik(positions: &mut [Vector3], target: &Vector3, iterations: u32, tolerance: f32) {
+	let root = positions[0];
+	let lengths: Vec<f32> = positions.windows(2).map(|pair| pair[0].distance(&pair[1])).collect();
+	let last = positions.len() - 1;
+
+	if root.distance(target) >= lengths.iter().sum() {
+		let mut direction = target - root;
+		direction.normalize();
+
+		for (index, length) in lengths.iter().enumerate() {
+			positions[index + 1] = positions[index] + direction * *length;
+		}
+
+		return;
+	}
+
+	for _ in 0..iterations {
+		if positions[last].distance(target) <= tolerance {
+			break;
+		}
+
+		// Drag the tip onto the target and each joint after it, then the root back into place and each joint after that
+		positions[last] = *target;
+
+		for index in (0..last).rev() {
+			let mut direction = positions[index] - positions[index + 1];
+			direction.normalize();
+			positions[index] = positions[index + 1] + direction * lengths[index];
+		}
+
+		positions[0] = root;
+
+		for index in 0..last {
+			let mut direction = positions[index + 1] - positions[index];
+			direction.normalize();
+			positions[index + 1] = positions[index] + direction * lengths[index];
+		}
+	}
+}
+
+// The closest point on the line from start to end to the point and how far the point is from it, none when the line has no
+// length
+fn foot_on_line(start: &Vector3, end: &Vector3, point: &Vector3) -> Option<(Vector3, f32)> {
+	let mut axis = end - start;
+
+	if axis.length_sq() == 0.0 {
+		return None;
+	}
+
+	axis.normalize();
+	let foot = start + axis * (point - start).dot(&axis);
+	Some((foot, point.distance(&foot)))
+}
+
+// The direction from the line from start to end towards the bend point, perpendicular to the line
+fn bend_direction(start: &Vector3, end: &Vector3, bend_point: &Vector3) -> Vector3 {
+	let mut axis = end - start;
+	axis.normalize();
+
+	let mut bend = bend_point - start;
+	bend -= axis * bend.dot(&axis);
+
+	// Straight chains and poles on the line bend any way perpendicular to it
+	if bend.length_sq() < 1e-12 {
+		bend = if axis.y.abs() < 0.9 { vector3::UNIT_Y } else { vector3::UNIT_X };
+		bend -= axis * bend.dot(&axis);
+	}
+
+	bend.normalize();
+	bend
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_lengths(positions: &[Vector3], lengths: &[f32]) {
+		for (pair, length) in positions.windows(2).zip(lengths) {
+			assert!((pair[0].distance(&pair[1]) - length).abs() < 1e-3, "{:?} should be {} apart", pair, length);
+		}
+	}
+
+	#[test]
+	fn two_bone_bends_towards_pole() {
+		let mut positions = [vector3::ZERO, Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, -2.0, 0.0)];
+		let target = Vector3::new(0.0, -1.0, 0.5);
+		IkSolver::TwoBone.solve(&mut positions, &target, Some(&Vector3::new(0.0, -1.0, 5.0)));
+
+		assert!(positions[2].distance(&target) < 1e-5);
+		assert!(positions[1].z > 0.5);
+		assert_lengths(&positions, &[1.0, 1.0]);
+
+		// Out of reach
+		let target = Vector3::new(5.0, 0.0, 0.0);
+		IkSolver::TwoBone.solve(&mut positions, &target, None);
+		assert!(positions[2].distance(&Vector3::new(2.0, 0.0, 0.0)) < 1e-5);
+	}
+
+	#[test]
+	fn fabrik_reaches_targets() {
+		let mut positions: Vec<Vector3> = (0..5).map(|index| Vector3::new(0.0, index as f32, 0.0)).collect();
+		let solver = IkSolver::Fabrik { iterations: 20, tolerance: 1e-4 };
+		let target = Vector3::new(2.0, 2.0, 0.0);
+		solver.solve(&mut positions, &target, Some(&Vector3::new(0.0, 0.0, 3.0)));
+
+		assert!(positions[4].distance(&target) < 1e-3);
+		assert_eq!(positions[0], vector3::ZERO);
+		assert_lengths(&positions, &[1.0; 4]);
+		assert!(positions[1..4].iter().all(|position| position.z > 0.0));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+use crate::math::Color;
+
+// Editable single line text, the entity's text component holds the string. The caret and the selection highlight are quads
+// in the text's space generated by the input field system from the glyph advances, the render system draws them under the
+// text with the entity's 2D transform.
+pub struct InputField {
+	pub caret_color: Color,
+	pub selection_color: Color,
+	pub(crate) caret_index: usize,
+	pub(crate) selection_anchor: Option<usize>,
+	pub(crate) caret_quad: Option<[f32; 16]>,
+	pub(crate) selection_quad: Option<[f32; 16]>
+}
+
+impl InputField {
+	pub fn new(caret_color: Color, selection_color: Color) -> Self {
+		Self {
+			caret_color,
+			selection_color,
+			caret_index: 0,
+			selection_anchor: None,
+			caret_quad: None,
+			selection_quad: None
+		}
+	}
+
+	pub fn caret_index(&self) -> usize {
+		self.caret_index
+	}
+
+	pub fn selection(&self) -> Option<(usize, usize)> {
+		let anchor = self.selection_anchor?;
+
+		if anchor < self.caret_index {
+			Some((anchor, self.caret_index))
+		}
+		else if anchor > self.caret_index {
+			Some((self.caret_index, anchor))
+		}
+		else {
+			None
+		}
+	}
+
+	// The corners of the caret as position and texture position pairs while it's shown
+	pub fn caret_quad(&self) -> Option<&[f32]> {
+		self.caret_quad.as_ref().map(|quad| &quad[..])
+	}
+
+	// The corners of the selection highlight as position and texture position pairs while the field is focused with a selection
+	pub fn selection_quad(&self) -> Option<&[f32]> {
+		self.selection_quad.as_ref().map(|quad| &quad[..])
+	}
+}
\ No newline at end of file
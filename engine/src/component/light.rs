@@ -1,17 +1,41 @@
-use crate::math::Vector3;
+use crate::math::Color;
 
 pub enum Light {
 	PointLight(PointLight),
 	AmbientLight(AmbientLight)
 }
 
+// How a point light's intensity falls off with distance, nothing beyond the light's range is lit
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Falloff {
+	// Full intensity up to the range
+	None,
+	// Fades linearly to zero at the range
+	Linear,
+	// Physically based inverse square falloff smoothly windowed to zero at the range
+	InverseSquare
+}
+
 pub struct PointLight {
-	pub color: Vector3,
-	pub intensity: f32
+	pub color: Color,
+	pub intensity: f32,
+	pub range: f32,
+	pub falloff: Falloff
+}
+
+impl PointLight {
+	pub fn new(color: Color, intensity: f32) -> Self {
+		Self {
+			color,
+			intensity,
+			range: f32::INFINITY,
+			falloff: Falloff::None
+		}
+	}
 }
 
 pub struct AmbientLight {
-	pub color: Vector3,
+	pub color: Color,
 	pub intensity: f32
 }
 
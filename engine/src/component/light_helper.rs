@@ -0,0 +1,5 @@
+use crate::Entity;
+
+pub struct LightHelper {
+	pub helper_entity: Entity
+}
\ No newline at end of file
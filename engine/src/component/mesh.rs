@@ -1,14 +1,42 @@
 use crate::pool::Handle;
+use super::{RenderLayer, ALL_LAYERS_MASK};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Material {
 	Line,
 	Basic,
 	Normal,
-	Lambert
+	Lambert,
+	// Samples the lightmap submitted to the render system with the second UV set instead of lighting in real time
+	Lightmapped,
+	// Distorts the scene behind it with its normals for glass and heat haze, it can't be in the opaque layer since it samples
+	// a copy of what's drawn before it
+	Refractive,
+	// Shows the scene mirrored across the render system's reflection plane for mirrors and water, the plane has to be set
+	// while it's drawn
+	Reflective,
+	// Lambert lit and double sided, its vertices sway in the render system's wind by the masks in the geometry's colors
+	Foliage
 }
 
 pub struct Mesh {
 	pub geometry_handle: Handle,
-	pub material: Material
+	pub material: Material,
+	pub layer: RenderLayer,
+	pub layer_mask: u32,
+	// Rasterized into the render system's occlusion culling depth buffer to hide the instances behind it, use it for big
+	// simple geometry like walls
+	pub occluder: bool
+}
+
+impl Mesh {
+	pub fn new(geometry_handle: Handle, material: Material) -> Self {
+		Self {
+			geometry_handle,
+			material,
+			layer: RenderLayer::Opaque,
+			layer_mask: ALL_LAYERS_MASK,
+			occluder: false
+		}
+	}
 }
\ No newline at end of file
@@ -1,5 +1,19 @@
-use crate::Entity;
-
+// Which debug lines the MeshBoundsHelperSystem draws for the entity's mesh
 pub struct MeshBoundsHelper {
-	pub bounds_entity: Entity
+	// The bounding box in world space
+	pub bounds: bool,
+	// A short line along each vertex normal
+	pub normals: bool,
+	// The edges of the triangles, or the lines of line meshes
+	pub wireframe: bool
+}
+
+impl MeshBoundsHelper {
+	pub fn new() -> Self {
+		Self {
+			bounds: true,
+			normals: false,
+			wireframe: false
+		}
+	}
 }
\ No newline at end of file
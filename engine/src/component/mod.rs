@@ -2,13 +2,13 @@ pub mod component_list;
 pub use component_list::ComponentList;
 
 pub mod multi_component_list;
-pub use multi_component_list::MultiComponentList;
+pub use multi_component_list::{MultiComponentList, ComponentHandle, MeshAsset};
 
 pub mod transform3d;
 pub use transform3d::Transform3D;
 
 pub mod transform3d_component_list;
-pub use transform3d_component_list::Transform3DComponentList;
+pub use transform3d_component_list::{Transform3DComponentList, ReparentMode};
 
 pub mod transform2d;
 pub use transform2d::Transform2D;
@@ -17,7 +17,10 @@ pub mod transform2d_component_list;
 pub use transform2d_component_list::Transform2DComponentList;
 
 pub mod light;
-pub use light::Light;
+pub use light::{Light, Falloff};
+
+pub mod render_layer;
+pub use render_layer::{RenderLayer, ALL_LAYERS_MASK};
 
 pub mod mesh;
 pub use mesh::Mesh;
@@ -25,8 +28,64 @@ pub use mesh::Mesh;
 pub mod mesh_bounds_helper;
 pub use mesh_bounds_helper::MeshBoundsHelper;
 
+pub mod light_helper;
+pub use light_helper::LightHelper;
+
 pub mod text;
-pub use text::Text;
+pub use text::{Text, TextGlow, TextPositioning, TextReveal};
 
 pub mod text_component_list;
-pub use text_component_list::TextComponentList;
\ No newline at end of file
+pub use text_component_list::TextComponentList;
+
+pub mod input_field;
+pub use input_field::InputField;
+
+pub mod animated_sprite;
+pub use animated_sprite::{AnimatedSprite, SpriteFrame};
+
+pub mod tilemap;
+pub use tilemap::Tilemap;
+
+pub mod collider2d;
+pub use collider2d::{Collider2D, Shape2D};
+
+pub mod hit_area2d;
+pub use hit_area2d::HitArea2D;
+
+pub mod scroll_view;
+pub use scroll_view::ScrollView;
+
+pub mod draggable;
+pub use draggable::{Draggable, DropTarget, Draggable3D, DragConstraint};
+
+pub mod collider3d;
+pub use collider3d::{Collider3D, Shape3D};
+
+pub mod path_follower;
+pub use path_follower::PathFollower;
+
+pub mod constraint;
+pub use constraint::{Constraint, ConstraintKind, Constraints};
+
+pub mod ik_chain;
+pub use ik_chain::{IkChain, IkSolver};
+
+pub mod animation_controller;
+pub use animation_controller::{AnimationController, AnimationState, AnimationTransition, AnimationParameter, AnimationCondition, Motion, RootMotion};
+
+pub mod socket;
+pub use socket::{Socket, Sockets, Attachment};
+
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "scripting")]
+pub use script::Script;
+
+pub mod trail;
+pub use trail::{Trail, TrailBlend, ColorVertex};
+
+pub mod blob_shadow;
+pub use blob_shadow::BlobShadow;
+
+pub mod user_data;
+pub use user_data::{UserData, UserValue};
\ No newline at end of file
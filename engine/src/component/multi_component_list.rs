@@ -1,36 +1,127 @@
-use crate::{EntityManager, Entity, entity_manager::MAX_ENTITY_COUNT};
+use crate::{EntityManager, Entity, entity_manager::MAX_ENTITY_COUNT, pool::Handle};
+use super::{Mesh, mesh::Material};
 
+// Stays valid until its component is removed, unlike the component's index which changes when another one is removed
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ComponentHandle {
+	index: usize,
+	generation: u32
+}
+
+struct HandleRecord {
+	generation: u32,
+	component_index: Option<usize>
+}
+
+// Each component is shared by any number of entities, for meshes the component is the geometry and material drawn and
+// every entity assigned to it is an instance. Component indices are returned by add and are invalidated when a component is
+// removed, keep a handle from handle to refer to a component for longer. Components and the entities of each are iterated
+// in the order they were added or assigned, removing or unassigning one moves the last into its place unless the ordered
+// variant is used.
 pub struct MultiComponentList<T> {
 	components: Vec<(Vec<Entity>, T)>,
-	entity_to_index_map: [Option<(usize, usize)>; MAX_ENTITY_COUNT]
+	entity_to_index_map: [Option<(usize, usize)>; MAX_ENTITY_COUNT],
+	// The handle record of each component
+	handle_indices: Vec<usize>,
+	handle_records: Vec<HandleRecord>,
+	vacant_handle_indices: Vec<usize>
 }
 
 impl<T> MultiComponentList<T> {
 	pub fn new() -> Self {
 		Self {
 			components: Vec::new(),
-			entity_to_index_map: [None; MAX_ENTITY_COUNT]
+			entity_to_index_map: [None; MAX_ENTITY_COUNT],
+			handle_indices: Vec::new(),
+			handle_records: Vec::new(),
+			vacant_handle_indices: Vec::new()
 		}
 	}
 
 	pub fn add(&mut self, component: T) -> usize {
+		let component_index = self.components.len();
 		self.components.push((Vec::new(), component));
-		self.components.len() - 1
+
+		let handle_index = match self.vacant_handle_indices.pop() {
+			Some(handle_index) => {
+				let record = &mut self.handle_records[handle_index];
+				record.generation += 1;
+				record.component_index = Some(component_index);
+				handle_index
+			},
+			None => {
+				self.handle_records.push(HandleRecord { generation: 1, component_index: Some(component_index) });
+				self.handle_records.len() - 1
+			}
+		};
+
+		self.handle_indices.push(handle_index);
+		component_index
 	}
 
 	pub fn remove(&mut self, entity_manager: &mut EntityManager, component_index: usize) {
+		self.unassign_all(entity_manager, component_index);
+		self.components.swap_remove(component_index);
+		let handle_index = self.handle_indices.swap_remove(component_index);
+		self.release_handle(handle_index);
+
+		if let Some((swapped_entities, _)) = self.components.get(component_index) {
+			for (iter_index, swapped_entity) in swapped_entities.iter().enumerate() {
+				self.entity_to_index_map[swapped_entity.index] = Some((component_index, iter_index));
+			}
+
+			self.handle_records[self.handle_indices[component_index]].component_index = Some(component_index);
+		}
+	}
+
+	// Keeps the remaining components in order but changes the index of every component after the removed one
+	pub fn remove_ordered(&mut self, entity_manager: &mut EntityManager, component_index: usize) {
+		self.unassign_all(entity_manager, component_index);
+		self.components.remove(component_index);
+		let handle_index = self.handle_indices.remove(component_index);
+		self.release_handle(handle_index);
+
+		for (shifted_index, (shifted_entities, _)) in self.components.iter().enumerate().skip(component_index) {
+			for (iter_index, shifted_entity) in shifted_entities.iter().enumerate() {
+				self.entity_to_index_map[shifted_entity.index] = Some((shifted_index, iter_index));
+			}
+
+			self.handle_records[self.handle_indices[shifted_index]].component_index = Some(shifted_index);
+		}
+	}
+
+	fn release_handle(&mut self, handle_index: usize) {
+		self.handle_records[handle_index].component_index = None;
+		self.vacant_handle_indices.push(handle_index);
+	}
+
+	pub fn handle(&self, component_index: usize) -> ComponentHandle {
+		let index = self.handle_indices[component_index];
+
+		ComponentHandle {
+			index,
+			generation: self.handle_records[index].generation
+		}
+	}
+
+	// None once the component is removed
+	pub fn index_of(&self, handle: ComponentHandle) -> Option<usize> {
+		let record = self.handle_records.get(handle.index)?;
+
+		if record.generation == handle.generation {
+			record.component_index
+		}
+		else {
+			None
+		}
+	}
+
+	fn unassign_all(&mut self, entity_manager: &mut EntityManager, component_index: usize) {
 		let (entities, _) = &self.components[component_index];
 		for entity in entities {
 			self.entity_to_index_map[entity.index] = None;
 			entity_manager.decrement_component_count(entity.index);
 		}
-
-		self.components.swap_remove(component_index);
-
-		let (swapped_entities, _) = &self.components[component_index];
-		for (iter_index, swapped_entity) in swapped_entities.iter().enumerate() {
-			self.entity_to_index_map[swapped_entity.index] = Some((component_index, iter_index));
-		}
 	}
 
 	pub fn assign(&mut self, entity_manager: &mut EntityManager, entity: Entity, component_index: usize) {
@@ -43,16 +134,81 @@ impl<T> MultiComponentList<T> {
 	}
 
 	pub fn unassign(&mut self, entity_manager: &mut EntityManager, entity: &Entity) {
+		let (component_index, saved_entity_index) = self.take_index(entity_manager, entity);
+		let saved_entities = &mut self.components[component_index].0;
+		saved_entities.swap_remove(saved_entity_index);
+
+		if let Some(swapped_entity) = saved_entities.get(saved_entity_index) {
+			self.entity_to_index_map[swapped_entity.index] = Some((component_index, saved_entity_index));
+		}
+	}
+
+	// Keeps the other instances of the component in the order they were assigned
+	pub fn unassign_ordered(&mut self, entity_manager: &mut EntityManager, entity: &Entity) {
+		let (component_index, saved_entity_index) = self.take_index(entity_manager, entity);
+		let saved_entities = &mut self.components[component_index].0;
+		saved_entities.remove(saved_entity_index);
+
+		for (iter_index, shifted_entity) in saved_entities.iter().enumerate().skip(saved_entity_index) {
+			self.entity_to_index_map[shifted_entity.index] = Some((component_index, iter_index));
+		}
+	}
+
+	fn take_index(&mut self, entity_manager: &mut EntityManager, entity: &Entity) -> (usize, usize) {
 		let component_index_option = self.entity_to_index_map[entity.index];
 		assert!(component_index_option.is_some(), "Cannot unassign component from entity {} because it does not have this component type", entity);
 		let (component_index, saved_entity_index) = component_index_option.unwrap();
-		let (saved_entities, _) = &mut self.components[component_index];
+		let saved_entities = &self.components[component_index].0;
 		assert_eq!(entity.generation, saved_entities[saved_entity_index].generation, "Cannot unassign component from entity {} because it's generation does not match", entity);
-		saved_entities.swap_remove(saved_entity_index);
-		let swapped_entity = saved_entities[saved_entity_index];
-		self.entity_to_index_map[swapped_entity.index] = Some((component_index, saved_entity_index));
 		self.entity_to_index_map[entity.index] = None;
 		entity_manager.decrement_component_count(entity.index);
+		(component_index, saved_entity_index)
+	}
+
+	// Moves the entity over to another component without it ever being without one, an entity without one is just assigned
+	pub fn reassign(&mut self, entity_manager: &mut EntityManager, entity: Entity, component_index: usize) {
+		assert!(component_index < self.components.len(), "Cannot reassign entity {} to component {} because there are only {} components", entity, component_index, self.components.len());
+
+		if self.component_index(&entity).is_some() {
+			self.unassign(entity_manager, &entity);
+		}
+
+		self.assign(entity_manager, entity, component_index);
+	}
+
+	pub fn component_index(&self, entity: &Entity) -> Option<usize> {
+		let (component_index, saved_entity_index) = self.entity_to_index_map[entity.index]?;
+		let (saved_entities, _) = &self.components[component_index];
+		if entity.generation == saved_entities[saved_entity_index].generation {
+			Some(component_index)
+		}
+		else {
+			None
+		}
+	}
+
+	pub fn entities(&self, component_index: usize) -> &[Entity] {
+		&self.components[component_index].0
+	}
+
+	pub fn instance_count(&self, component_index: usize) -> usize {
+		self.components[component_index].0.len()
+	}
+
+	pub fn component(&self, component_index: usize) -> &T {
+		&self.components[component_index].1
+	}
+
+	pub fn component_mut(&mut self, component_index: usize) -> &mut T {
+		&mut self.components[component_index].1
+	}
+
+	pub fn len(&self) -> usize {
+		self.components.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.components.is_empty()
 	}
 
 	pub fn borrow(&self, entity: &Entity) -> &T {
@@ -98,4 +254,361 @@ impl<T> MultiComponentList<T> {
 	pub fn iter(&self) -> impl Iterator<Item = &(Vec<Entity>, T)> {
 		self.components.iter()
 	}
+}
+
+// A mesh asset is the geometry and material shared by its instances, the entities assigned to it. Unlike mesh indices the
+// asset stays the same when other meshes are removed so it can be kept to add more instances later.
+pub type MeshAsset = ComponentHandle;
+
+impl MultiComponentList<Mesh> {
+	pub fn add_asset(&mut self, mesh: Mesh) -> MeshAsset {
+		let component_index = self.add(mesh);
+		self.handle(component_index)
+	}
+
+	pub fn remove_asset(&mut self, entity_manager: &mut EntityManager, asset: MeshAsset) {
+		let component_index = self.asset_index(asset);
+		self.remove(entity_manager, component_index);
+	}
+
+	pub fn add_instance(&mut self, entity_manager: &mut EntityManager, entity: Entity, asset: MeshAsset) {
+		let component_index = self.asset_index(asset);
+		self.assign(entity_manager, entity, component_index);
+	}
+
+	// Moves the instance over to another asset, an entity without a mesh is added as an instance
+	pub fn move_instance(&mut self, entity_manager: &mut EntityManager, entity: Entity, asset: MeshAsset) {
+		let component_index = self.asset_index(asset);
+		self.reassign(entity_manager, entity, component_index);
+	}
+
+	pub fn asset(&self, entity: &Entity) -> Option<MeshAsset> {
+		self.component_index(entity).map(|component_index| self.handle(component_index))
+	}
+
+	pub fn asset_instances(&self, asset: MeshAsset) -> &[Entity] {
+		self.entities(self.asset_index(asset))
+	}
+
+	pub fn asset_instance_count(&self, asset: MeshAsset) -> usize {
+		self.instance_count(self.asset_index(asset))
+	}
+
+	fn asset_index(&self, asset: MeshAsset) -> usize {
+		let component_index = self.index_of(asset);
+		assert!(component_index.is_some(), "Cannot use mesh asset {:?} because it was removed", asset);
+		component_index.unwrap()
+	}
+}
+
+// Setting the material or geometry through borrow_mut changes it for every instance of the mesh. These change it for just
+// the one entity by moving it to a mesh which matches in everything but the change, one is added if there isn't one. An
+// entity which is the only instance of its mesh has the mesh changed in place instead. Returns the entity's mesh index.
+impl MultiComponentList<Mesh> {
+	pub fn change_material(&mut self, entity_manager: &mut EntityManager, entity: Entity, material: Material) -> usize {
+		self.change_mesh(entity_manager, entity, |mesh| mesh.material = material)
+	}
+
+	pub fn change_geometry(&mut self, entity_manager: &mut EntityManager, entity: Entity, geometry_handle: Handle) -> usize {
+		self.change_mesh(entity_manager, entity, |mesh| mesh.geometry_handle = geometry_handle)
+	}
+
+	fn change_mesh(&mut self, entity_manager: &mut EntityManager, entity: Entity, change: impl Fn(&mut Mesh)) -> usize {
+		let component_index = self.component_index(&entity);
+		assert!(component_index.is_some(), "Cannot change the mesh of entity {} because it does not have one or it's generation does not match", entity);
+		let component_index = component_index.unwrap();
+
+		let current = &self.components[component_index].1;
+		let mut changed = Mesh {
+			geometry_handle: current.geometry_handle,
+			material: current.material,
+			layer: current.layer,
+			layer_mask: current.layer_mask,
+			occluder: current.occluder
+		};
+
+		change(&mut changed);
+
+		let matches = |mesh: &Mesh| mesh.geometry_handle == changed.geometry_handle
+			&& mesh.material == changed.material
+			&& mesh.layer == changed.layer
+			&& mesh.layer_mask == changed.layer_mask
+			&& mesh.occluder == changed.occluder;
+
+		if matches(current) {
+			return component_index;
+		}
+
+		if let Some(matching_index) = self.components.iter().position(|(_, mesh)| matches(mesh)) {
+			self.reassign(entity_manager, entity, matching_index);
+			return matching_index;
+		}
+
+		if self.instance_count(component_index) == 1 {
+			self.components[component_index].1 = changed;
+			return component_index;
+		}
+
+		let new_index = self.add(changed);
+		self.reassign(entity_manager, entity, new_index);
+		new_index
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::component::RenderLayer;
+
+	#[test]
+	fn assign_and_unassign() {
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let a = list.add('a');
+		let entities: Vec<Entity> = (0..3).map(|_| entity_manager.create()).collect();
+
+		for entity in &entities {
+			list.assign(&mut entity_manager, *entity, a);
+		}
+
+		assert_eq!(list.instance_count(a), 3);
+		list.unassign(&mut entity_manager, &entities[2]);
+		list.unassign(&mut entity_manager, &entities[0]);
+		assert_eq!(list.instance_count(a), 1);
+		assert!(list.entities(a) == [entities[1]]);
+		assert_eq!(*list.borrow(&entities[1]), 'a');
+		assert!(list.try_borrow(&entities[0]).is_none());
+	}
+
+	#[test]
+	fn reassign() {
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let a = list.add('a');
+		let b = list.add('b');
+		let entity = entity_manager.create();
+
+		list.assign(&mut entity_manager, entity, a);
+		list.reassign(&mut entity_manager, entity, b);
+		assert_eq!(list.instance_count(a), 0);
+		assert_eq!(list.instance_count(b), 1);
+		assert_eq!(list.component_index(&entity), Some(b));
+		assert_eq!(*list.borrow(&entity), 'b');
+
+		// An entity that was never assigned is just assigned
+		let unassigned_entity = entity_manager.create();
+		list.reassign(&mut entity_manager, unassigned_entity, a);
+		assert_eq!(list.component_index(&unassigned_entity), Some(a));
+		assert_eq!(list.instance_count(a), 1);
+	}
+
+	#[test]
+	fn handles_survive_removal() {
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let handles: Vec<ComponentHandle> = (0..3).map(|component| {
+			let component_index = list.add(component);
+			list.handle(component_index)
+		}).collect();
+
+		// The last component is moved into the removed one's index but its handle still finds it
+		list.remove(&mut entity_manager, 0);
+		assert_eq!(list.index_of(handles[0]), None);
+		assert_eq!(list.index_of(handles[2]), Some(0));
+		assert_eq!(*list.component(list.index_of(handles[2]).unwrap()), 2);
+
+		list.remove_ordered(&mut entity_manager, 0);
+		assert_eq!(list.index_of(handles[1]), Some(0));
+
+		// A new component reusing the removed handle's record doesn't make the old handle valid again
+		let component_index = list.add(3);
+		assert_ne!(list.handle(component_index), handles[0]);
+		assert_ne!(list.handle(component_index), handles[2]);
+		assert_eq!(list.index_of(handles[0]), None);
+		assert_eq!(list.index_of(handles[2]), None);
+	}
+
+	#[test]
+	fn remove_moves_last_component() {
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let a = list.add('a');
+		let b = list.add('b');
+		let entity_a = entity_manager.create();
+		let entity_b = entity_manager.create();
+		list.assign(&mut entity_manager, entity_a, a);
+		list.assign(&mut entity_manager, entity_b, b);
+
+		list.remove(&mut entity_manager, a);
+		assert_eq!(list.len(), 1);
+		assert!(list.try_borrow(&entity_a).is_none());
+		assert_eq!(list.component_index(&entity_b), Some(0));
+		assert_eq!(*list.borrow(&entity_b), 'b');
+
+		list.remove(&mut entity_manager, 0);
+		assert!(list.is_empty());
+		entity_manager.destroy(entity_a);
+		entity_manager.destroy(entity_b);
+	}
+
+	#[test]
+	fn ordered_variants_keep_order() {
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let components: Vec<usize> = (0..3).map(|component| list.add(component)).collect();
+		let entities: Vec<Entity> = (0..3).map(|_| entity_manager.create()).collect();
+
+		for entity in &entities {
+			list.assign(&mut entity_manager, *entity, components[2]);
+		}
+
+		list.unassign_ordered(&mut entity_manager, &entities[0]);
+		assert!(list.entities(components[2]) == [entities[1], entities[2]]);
+		assert!(list.component_index(&entities[2]) == Some(components[2]));
+
+		list.remove_ordered(&mut entity_manager, components[0]);
+		assert_eq!(list.iter().map(|(_, component)| *component).collect::<Vec<_>>(), [1, 2]);
+		assert_eq!(*list.borrow(&entities[1]), 2);
+		assert_eq!(list.component_index(&entities[2]), Some(1));
+
+		list.unassign(&mut entity_manager, &entities[1]);
+		assert!(list.entities(1) == [entities[2]]);
+		assert_eq!(*list.borrow(&entities[2]), 2);
+	}
+
+	fn mesh_list() -> (EntityManager, MultiComponentList<Mesh>, Handle, Handle) {
+		let mut geometries = crate::pool::Pool::new();
+		let box_handle = geometries.add(crate::Geometry3D::create_box());
+		let plane_handle = geometries.add(crate::Geometry3D::create_plane());
+		(EntityManager::new(), MultiComponentList::new(), box_handle, plane_handle)
+	}
+
+	#[test]
+	fn mesh_assets_and_instances() {
+		let (mut entity_manager, mut list, box_handle, plane_handle) = mesh_list();
+		let box_asset = list.add_asset(Mesh::new(box_handle, Material::Basic));
+		let plane_asset = list.add_asset(Mesh::new(plane_handle, Material::Basic));
+		let entities: Vec<Entity> = (0..3).map(|_| entity_manager.create()).collect();
+
+		for entity in &entities {
+			list.add_instance(&mut entity_manager, *entity, plane_asset);
+		}
+
+		// The last plane instance takes the moved one's place
+		list.move_instance(&mut entity_manager, entities[0], box_asset);
+		assert_eq!(list.asset(&entities[0]), Some(box_asset));
+		assert_eq!(list.asset_instance_count(box_asset), 1);
+		assert_eq!(list.asset_instance_count(plane_asset), 2);
+
+		// Removing the box mesh moves the plane mesh to another index but not another asset
+		list.remove_asset(&mut entity_manager, box_asset);
+		assert_eq!(list.asset(&entities[0]), None);
+		assert_eq!(list.asset(&entities[1]), Some(plane_asset));
+		assert!(list.asset_instances(plane_asset) == [entities[2], entities[1]]);
+	}
+
+	#[test]
+	fn change_material_of_a_shared_mesh() {
+		let (mut entity_manager, mut list, box_handle, _) = mesh_list();
+		let basic = list.add(Mesh::new(box_handle, Material::Basic));
+		let entity_a = entity_manager.create();
+		let entity_b = entity_manager.create();
+		list.assign(&mut entity_manager, entity_a, basic);
+		list.assign(&mut entity_manager, entity_b, basic);
+
+		// The other instance keeps its material
+		let lambert = list.change_material(&mut entity_manager, entity_a, Material::Lambert);
+		assert_ne!(lambert, basic);
+		assert_eq!(list.borrow(&entity_a).material, Material::Lambert);
+		assert_eq!(list.borrow(&entity_b).material, Material::Basic);
+		assert!(list.borrow(&entity_a).geometry_handle == box_handle);
+
+		// Changing the other one too moves it into the existing mesh instead of adding another
+		assert_eq!(list.change_material(&mut entity_manager, entity_b, Material::Lambert), lambert);
+		assert_eq!(list.instance_count(lambert), 2);
+		assert_eq!(list.instance_count(basic), 0);
+		assert_eq!(list.len(), 2);
+	}
+
+	#[test]
+	fn change_the_only_instance_in_place() {
+		let (mut entity_manager, mut list, box_handle, plane_handle) = mesh_list();
+		let mesh = list.add(Mesh::new(box_handle, Material::Basic));
+		let entity = entity_manager.create();
+		list.assign(&mut entity_manager, entity, mesh);
+
+		assert_eq!(list.change_material(&mut entity_manager, entity, Material::Normal), mesh);
+		assert_eq!(list.change_geometry(&mut entity_manager, entity, plane_handle), mesh);
+		assert_eq!(list.len(), 1);
+		assert_eq!(list.borrow(&entity).material, Material::Normal);
+		assert!(list.borrow(&entity).geometry_handle == plane_handle);
+	}
+
+	#[test]
+	fn change_geometry_keeps_the_layer() {
+		let (mut entity_manager, mut list, box_handle, plane_handle) = mesh_list();
+		let mut overlay_mesh = Mesh::new(box_handle, Material::Basic);
+		overlay_mesh.layer = RenderLayer::Overlay;
+		let overlay = list.add(overlay_mesh);
+		let opaque_plane = list.add(Mesh::new(plane_handle, Material::Basic));
+		let entities: Vec<Entity> = (0..2).map(|_| entity_manager.create()).collect();
+
+		for entity in &entities {
+			list.assign(&mut entity_manager, *entity, overlay);
+		}
+
+		// The plane mesh in the opaque layer doesn't match so a new one is added
+		let overlay_plane = list.change_geometry(&mut entity_manager, entities[0], plane_handle);
+		assert_ne!(overlay_plane, opaque_plane);
+		assert_eq!(list.borrow(&entities[0]).layer, RenderLayer::Overlay);
+		assert!(list.borrow(&entities[1]).geometry_handle == box_handle);
+	}
+	#[test]
+	fn move_between_static_and_dynamic_geometry() {
+		let mut geometries = crate::pool::Pool::new();
+		let mut static_geometry = crate::Geometry3D::create_box();
+		static_geometry.submission_info = Some(crate::geometry3d::SubmissionInfo {
+			generation: 1,
+			index_array_offset: 0,
+			attributes_array_offset: 0,
+			uvs_array_offset: 0,
+			uvs2_array_offset: 0,
+			occlusion_array_offset: 0,
+			colors_array_offset: 0
+		});
+		let static_handle = geometries.add(static_geometry);
+		let dynamic_handle = geometries.add(crate::Geometry3D::create_plane());
+
+		let mut entity_manager = EntityManager::new();
+		let mut list = MultiComponentList::new();
+		let static_asset = list.add_asset(Mesh::new(static_handle, Material::Lambert));
+		let entities: Vec<Entity> = (0..3).map(|_| entity_manager.create()).collect();
+
+		for entity in &entities {
+			list.add_instance(&mut entity_manager, *entity, static_asset);
+		}
+
+		// The instance is grouped with the dynamic geometry and the others stay with the static one
+		let dynamic_index = list.change_geometry(&mut entity_manager, entities[0], dynamic_handle);
+		let dynamic_asset = list.handle(dynamic_index);
+		assert_ne!(dynamic_asset, static_asset);
+		assert!(list.asset_instances(dynamic_asset) == [entities[0]]);
+		assert!(list.asset_instances(static_asset) == [entities[2], entities[1]]);
+		assert_eq!(list.borrow(&entities[0]).material, Material::Lambert);
+
+		// Moving another instance over joins the same mesh instead of adding one
+		list.move_instance(&mut entity_manager, entities[1], dynamic_asset);
+		assert!(list.asset_instances(dynamic_asset) == [entities[0], entities[1]]);
+		assert!(list.asset_instances(static_asset) == [entities[2]]);
+		assert_eq!(list.len(), 2);
+
+		// And back to the static mesh
+		assert_eq!(list.change_geometry(&mut entity_manager, entities[0], static_handle), list.asset_index(static_asset));
+		assert!(list.asset_instances(static_asset) == [entities[2], entities[0]]);
+		assert!(list.asset_instances(dynamic_asset) == [entities[1]]);
+
+		// Regrouping instances doesn't touch the geometries' submissions
+		assert_eq!(geometries.borrow(static_handle).submission_generation(), Some(1));
+		assert_eq!(geometries.borrow(dynamic_handle).submission_generation(), None);
+	}
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+use crate::math::{Spline, Vector3, vector3};
+
+// Moves the entity along the spline at a constant speed, the spline is in the space of the entity's parent
+pub struct PathFollower {
+	pub spline: Spline,
+	// Units per second, negative to go backwards
+	pub speed: f32,
+	pub distance: f32,
+	// Starts over at the other end once the end is reached, otherwise it stops there
+	pub looping: bool,
+	// Turns the entity to face along the spline with the up vector kept up
+	pub orient: bool,
+	pub up: Vector3
+}
+
+impl PathFollower {
+	pub fn new(spline: Spline, speed: f32) -> Self {
+		Self {
+			spline,
+			speed,
+			distance: 0.0,
+			looping: false,
+			orient: true,
+			up: vector3::UNIT_Y
+		}
+	}
+
+	pub fn finished(&self) -> bool {
+		!self.looping && (self.distance <= 0.0 && self.speed < 0.0 || self.distance >= self.spline.length() && self.speed > 0.0)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,34 @@
+// Masks where every bit is set are rendered by and render every camera
+pub const ALL_LAYERS_MASK: u32 = !0;
+
+// Layers are drawn in ascending order, things in the same layer are grouped by material
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RenderLayer {
+	Opaque,
+	Transparent,
+	Overlay,
+	Custom(i32)
+}
+
+impl RenderLayer {
+	pub fn order(&self) -> i32 {
+		match self {
+			Self::Opaque => 0,
+			Self::Transparent => 1000,
+			Self::Overlay => 2000,
+			Self::Custom(order) => *order
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn order() {
+		let mut layers = vec![RenderLayer::Overlay, RenderLayer::Custom(500), RenderLayer::Transparent, RenderLayer::Opaque, RenderLayer::Custom(-1)];
+		layers.sort_by_key(RenderLayer::order);
+		assert_eq!(layers, vec![RenderLayer::Custom(-1), RenderLayer::Opaque, RenderLayer::Custom(500), RenderLayer::Transparent, RenderLayer::Overlay]);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+use std::{path::{Path, PathBuf}, time::SystemTime};
+use rhai::{AST, Dynamic, Map};
+
+// A rhai script the script system runs for the entity it's attached to. The script defines `fn update(entity, delta_time)`
+// which runs every update and optionally `fn init(entity)` which runs before the first one. Functions can't see variables
+// outside of them so state kept between calls goes in `this`, a map which starts out empty.
+pub struct Script {
+	pub(crate) path: PathBuf,
+	pub(crate) ast: AST,
+	pub(crate) modified: Option<SystemTime>,
+	pub(crate) state: Dynamic,
+	pub(crate) initialized: bool,
+	pub(crate) failed: bool
+}
+
+impl Script {
+	pub(crate) fn new(path: PathBuf, ast: AST, modified: Option<SystemTime>) -> Self {
+		Self {
+			path,
+			ast,
+			modified,
+			state: Dynamic::from_map(Map::new()),
+			initialized: false,
+			failed: false
+		}
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	// A script stops running after an error until it's reloaded
+	pub fn failed(&self) -> bool {
+		self.failed
+	}
+
+	pub(crate) fn defines(&self, function_name: &str) -> bool {
+		self.ast.iter_functions().any(|function| function.name == function_name)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,30 @@
+use crate::{Entity, math::{vector2, Vector2}};
+
+// A rectangle in the space of the entity's Transform2D which scrolls the content entities inside of it. Content text outside
+// of the rectangle is clipped.
+pub struct ScrollView {
+	pub size: Vector2,
+	pub content_size: Vector2,
+	// Each entity and its position in the content
+	pub content: Vec<(Entity, Vector2)>,
+	pub scroll_offset: Vector2
+}
+
+impl ScrollView {
+	pub fn new(size: Vector2, content_size: Vector2) -> Self {
+		Self {
+			size,
+			content_size,
+			content: Vec::new(),
+			scroll_offset: vector2::ZERO
+		}
+	}
+
+	// Moves the content by the delta, it can't be scrolled past either end
+	pub fn scroll(&mut self, delta: &Vector2) {
+		let max_x = (self.content_size.x - self.size.x).max(0.0);
+		let max_y = (self.content_size.y - self.size.y).max(0.0);
+		self.scroll_offset.x = (self.scroll_offset.x + delta.x).clamp(0.0, max_x);
+		self.scroll_offset.y = (self.scroll_offset.y + delta.y).clamp(0.0, max_y);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,53 @@
+use crate::{Entity, math::{Quaternion, Vector3, quaternion, vector3}};
+
+// A named place on an entity which other entities can be attached to, such as a hand holding a weapon or the top of a head
+// wearing a hat. It's offset from one of the entity's joints so it moves with the animation, or from the entity itself when
+// there's no joint.
+pub struct Socket {
+	pub name: String,
+	pub joint: Option<Entity>,
+	pub position: Vector3,
+	pub orientation: Quaternion
+}
+
+impl Socket {
+	pub fn new(name: &str, joint: Option<Entity>) -> Self {
+		Self {
+			name: name.to_string(),
+			joint,
+			position: vector3::ZERO,
+			orientation: quaternion::ZERO
+		}
+	}
+}
+
+pub struct Sockets {
+	pub sockets: Vec<Socket>
+}
+
+impl Sockets {
+	pub fn new(sockets: Vec<Socket>) -> Self {
+		Self {
+			sockets
+		}
+	}
+
+	pub fn find(&self, name: &str) -> Option<&Socket> {
+		self.sockets.iter().find(|socket| socket.name == name)
+	}
+}
+
+// Keeps the entity at the socket of another entity, the entity's scale is left as it is
+pub struct Attachment {
+	pub entity: Entity,
+	pub socket: String
+}
+
+impl Attachment {
+	pub fn new(entity: Entity, socket: &str) -> Self {
+		Self {
+			entity,
+			socket: socket.to_string()
+		}
+	}
+}
\ No newline at end of file
@@ -1,10 +1,102 @@
-use crate::pool::Handle;
+use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, sync::atomic::{AtomicU64, Ordering}};
+use crate::{Font, math::{vector2, Color, Vector2}, pool::Handle};
+use super::{RenderLayer, ALL_LAYERS_MASK};
+
+// Every generation of text geometry gets a unique id so renderers can tell when their copy of it is out of date
+static NEXT_GEOMETRY_ID: AtomicU64 = AtomicU64::new(1);
+
+// Set on the ids shared by all text with the same geometry so they never match a unique id
+const SHARED_GEOMETRY_ID_BIT: u64 = 1 << 63;
+
+// Reveals the text a character at a time for dialogue boxes and the like. Each character fades in and eases from the offset
+// into place once the progress reaches it.
+#[derive(Copy, Clone, PartialEq)]
+pub struct TextReveal {
+	// How many characters have started appearing
+	pub progress: f32,
+	// How many characters of progress it takes for one to fully appear, zero makes them appear at once like a typewriter
+	pub fade_length: f32,
+	pub offset: Vector2
+}
+
+impl TextReveal {
+	pub fn new() -> Self {
+		Self {
+			progress: 0.0,
+			fade_length: 0.0,
+			offset: vector2::ZERO
+		}
+	}
+
+	// How far the character has appeared from 0 to 1
+	pub fn amount(&self, char_index: usize) -> f32 {
+		let elapsed = self.progress - char_index as f32;
+
+		if self.fade_length <= 0.0 {
+			return if elapsed > 0.0 { 1.0 } else { 0.0 };
+		}
+
+		let t = (elapsed / self.fade_length).clamp(0.0, 1.0);
+		t * t * (3.0 - 2.0 * t)
+	}
+
+	// Whether every character of the string has fully appeared
+	pub fn finished(&self, string: &str) -> bool {
+		self.progress >= string.chars().count() as f32 + self.fade_length
+	}
+}
+
+// A soft halo drawn behind the text, it's added onto what's behind the text so a bright color makes the text look emissive
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TextGlow {
+	pub color: Color,
+	// How far the glow spreads past the glyphs in the font's pixels
+	pub radius: f32,
+	// Multiplies the color, above 1 for an HDR target or a stronger halo
+	pub intensity: f32
+}
+
+impl TextGlow {
+	pub fn new(color: Color, radius: f32) -> Self {
+		assert!(radius > 0.0, "Glow radius must be positive");
+
+		Self {
+			color,
+			radius,
+			intensity: 1.0
+		}
+	}
+}
+
+// How the glyphs are placed along the line
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextPositioning {
+	// Each glyph starts on a whole pixel and advances a whole number of pixels so small UI text stays crisp. The text's
+	// position is rounded to a whole pixel too.
+	PixelSnapped,
+	// Glyphs are placed exactly where the font's unhinted advances put them which keeps the spacing even for large, scaled,
+	// rotated or moving text at the cost of some blur
+	SubPixel
+}
 
 pub struct Text {
 	pub font: Handle,
 	pub string: String,
+	pub layer: RenderLayer,
+	pub layer_mask: u32,
+	// The min and max corners in framebuffer pixels outside of which the text isn't drawn
+	pub clip_rect: Option<(Vector2, Vector2)>,
+	pub reveal: Option<TextReveal>,
+	pub positioning: TextPositioning,
+	pub glow: Option<TextGlow>,
 	pub(crate) indices: Vec<u16>,
-	pub(crate) attributes: Vec<f32>
+	pub(crate) attributes: Vec<f32>,
+	pub(crate) geometry_id: u64,
+	generated_font: Option<Handle>,
+	generated_string: String,
+	generated_reveal: Option<TextReveal>,
+	generated_positioning: TextPositioning,
+	char_starts: Vec<(usize, f32)>
 }
 
 impl Text {
@@ -12,8 +104,20 @@ impl Text {
 		Self {
 			font,
 			string,
+			layer: RenderLayer::Overlay,
+			layer_mask: ALL_LAYERS_MASK,
+			clip_rect: None,
+			reveal: None,
+			positioning: TextPositioning::PixelSnapped,
+			glow: None,
 			indices: Vec::new(),
-			attributes: Vec::new()
+			attributes: Vec::new(),
+			geometry_id: 0,
+			generated_font: None,
+			generated_string: String::new(),
+			generated_reveal: None,
+			generated_positioning: TextPositioning::PixelSnapped,
+			char_starts: Vec::new()
 		}
 	}
 
@@ -24,4 +128,240 @@ impl Text {
 	pub fn attributes(&self) -> &[f32] {
 		&self.attributes
 	}
+
+	fn reveal_amount(reveal: &Option<TextReveal>, char_index: usize) -> f32 {
+		reveal.map_or(1.0, |reveal| reveal.amount(char_index))
+	}
+
+	// Only the characters after the part of the string that's unchanged since the last generation, and appeared the same
+	// amount, are regenerated
+	pub(crate) fn generate(&mut self, font: &Font) {
+		let same_font = self.generated_font == Some(self.font) && self.positioning == self.generated_positioning;
+
+		if same_font && self.string == self.generated_string && self.reveal == self.generated_reveal {
+			return;
+		}
+
+		let unchanged_char_count = if same_font {
+			self.string.chars().zip(self.generated_string.chars())
+				.enumerate()
+				.take_while(|(i, (a, b))| a == b && Self::reveal_amount(&self.reveal, *i) == Self::reveal_amount(&self.generated_reveal, *i))
+				.count()
+		}
+		else {
+			0
+		};
+
+		// The quad count and cursor position before each character and after the last one are saved to resume from
+		let (mut quad_count, mut cursor_pos) = self.char_starts.get(unchanged_char_count).copied().unwrap_or((0, 0.0));
+		self.char_starts.truncate(unchanged_char_count);
+		self.indices.truncate(quad_count * 6);
+		self.attributes.truncate(quad_count * 20);
+
+		let snapped = self.positioning == TextPositioning::PixelSnapped;
+		let snap = |value: f32| if snapped { value.round() } else { value };
+
+		for (char_index, c) in self.string.chars().enumerate().skip(unchanged_char_count) {
+			self.char_starts.push((quad_count, cursor_pos));
+
+			if c == ' ' {
+				cursor_pos += snap(font.space_advance);
+				continue;
+			}
+
+			let glyph_index = font.glyphs.binary_search_by_key(&(c as u32), |g| g.char_code).unwrap();
+			let glyph = &font.glyphs[glyph_index];
+
+			let index_offset = quad_count as u16 * 4;
+			self.indices.extend_from_slice(&[
+				index_offset, index_offset + 1, index_offset + 2,
+				index_offset, index_offset + 2, index_offset + 3
+			]);
+			
+			// Characters which haven't fully appeared are faded and moved towards the reveal offset
+			let alpha = Self::reveal_amount(&self.reveal, char_index);
+			let offset = self.reveal.map_or(vector2::ZERO, |reveal| reveal.offset * (1.0 - alpha));
+
+			let left = snap(cursor_pos + glyph.bearing_x + offset.x);
+			let right = left + glyph.width;
+			let top = snap(glyph.bearing_y + offset.y);
+			let bottom = top + glyph.height;
+
+			self.attributes.extend_from_slice(&[
+				left, top, glyph.position_x, glyph.position_y, alpha,
+				right, top, glyph.position_x + glyph.width, glyph.position_y, alpha,
+				right, bottom, glyph.position_x + glyph.width, glyph.position_y + glyph.height, alpha,
+				left, bottom, glyph.position_x, glyph.position_y + glyph.height, alpha
+			]);
+
+			quad_count += 1;
+			cursor_pos += snap(glyph.advance);
+		}
+
+		self.char_starts.push((quad_count, cursor_pos));
+		self.generated_font = Some(self.font);
+		self.generated_string.clone_from(&self.string);
+		self.generated_reveal = self.reveal;
+		self.generated_positioning = self.positioning;
+
+		// Fully shown text has the same geometry as all other text with the same font, string and positioning so it shares its
+		// id, the renderer keeps one copy of the geometry and draws them instanced
+		self.geometry_id = if self.reveal.is_none_or(|reveal| reveal.finished(&self.string)) {
+			self.shared_geometry_id()
+		}
+		else {
+			NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed)
+		};
+	}
+
+	fn shared_geometry_id(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		self.font.hash(&mut hasher);
+		self.string.hash(&mut hasher);
+		(self.positioning == TextPositioning::PixelSnapped).hash(&mut hasher);
+		hasher.finish() | SHARED_GEOMETRY_ID_BIT
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{font::Glyph, pool::Pool};
+
+	fn font() -> Font {
+		let glyph = |char_code, position_x| Glyph {
+			char_code,
+			position_x,
+			position_y: 0.0,
+			width: 8.0,
+			height: 10.0,
+			bearing_x: 1.0,
+			bearing_y: -10.0,
+			advance: 9.0
+		};
+
+		Font {
+			fnt_path: String::new(),
+			size: 10,
+			color: false,
+			atlas_width: 32,
+			atlas_height: 10,
+			space_advance: 4.0,
+			glyphs: vec![glyph('a' as u32, 0.0), glyph('b' as u32, 8.0), glyph('c' as u32, 16.0)],
+			generated_atlas: None,
+			submission_info: None
+		}
+	}
+
+	fn font_handle() -> Handle {
+		let mut pool = Pool::new();
+		pool.add(())
+	}
+
+	#[test]
+	fn incremental_generation_matches_full_generation() {
+		let font = font();
+		let mut text = Text::new(font_handle(), String::from("ab c"));
+		text.generate(&font);
+		let first_geometry_id = text.geometry_id;
+
+		text.string = String::from("ab ba");
+		text.generate(&font);
+
+		let mut expected = Text::new(text.font, String::from("ab ba"));
+		expected.generate(&font);
+
+		assert_eq!(text.indices, expected.indices);
+		assert_eq!(text.attributes, expected.attributes);
+		assert_ne!(text.geometry_id, first_geometry_id);
+	}
+
+	#[test]
+	fn reveal_fades_in_characters() {
+		let font = font();
+		let mut text = Text::new(font_handle(), String::from("ab c"));
+		let mut reveal = TextReveal::new();
+		reveal.fade_length = 2.0;
+		reveal.offset = Vector2::new(0.0, 10.0);
+		text.reveal = Some(reveal);
+		text.generate(&font);
+		assert!(text.attributes.chunks_exact(5).all(|vertex| vertex[4] == 0.0));
+
+		// The first character is fully in place, the second is half way and the rest haven't started
+		reveal.progress = 2.0;
+		text.reveal = Some(reveal);
+		text.generate(&font);
+		let alphas: Vec<f32> = text.attributes.chunks_exact(20).map(|quad| quad[4]).collect();
+		assert_eq!(alphas, vec![1.0, 0.5, 0.0]);
+		assert_eq!(text.attributes[20 + 1], -10.0 + 5.0);
+		assert!(!reveal.finished(&text.string));
+
+		// Fully revealed text matches text without a reveal
+		reveal.progress = 6.0;
+		assert!(reveal.finished(&text.string));
+		text.reveal = Some(reveal);
+		text.generate(&font);
+
+		let mut expected = Text::new(text.font, text.string.clone());
+		expected.generate(&font);
+		assert_eq!(text.attributes, expected.attributes);
+	}
+
+	#[test]
+	fn snaps_glyphs_to_pixels() {
+		let mut font = font();
+		font.space_advance = 4.4;
+		font.glyphs[0].advance = 9.4;
+
+		let mut text = Text::new(font_handle(), String::from("aa ab"));
+		text.generate(&font);
+		let lefts: Vec<f32> = text.attributes.chunks_exact(20).map(|quad| quad[0]).collect();
+		assert_eq!(lefts, vec![1.0, 10.0, 23.0, 32.0]);
+
+		// Changing the positioning regenerates every glyph
+		text.positioning = TextPositioning::SubPixel;
+		text.generate(&font);
+		let lefts = text.attributes.chunks_exact(20).map(|quad| quad[0]);
+		assert!(lefts.zip([1.0, 10.4, 24.2, 33.6]).all(|(left, expected)| (left - expected).abs() < 1e-4));
+	}
+
+	#[test]
+	fn unchanged_text_is_not_regenerated() {
+		let font = font();
+		let mut text = Text::new(font_handle(), String::from("abc"));
+		text.generate(&font);
+		let geometry_id = text.geometry_id;
+
+		text.generate(&font);
+		assert_eq!(text.geometry_id, geometry_id);
+		assert_eq!(text.indices.len(), 3 * 6);
+	}
+
+	#[test]
+	fn identical_text_shares_geometry() {
+		let font = font();
+		let handle = font_handle();
+		let mut a = Text::new(handle, String::from("ab"));
+		let mut b = Text::new(handle, String::from("ab"));
+		a.generate(&font);
+		b.generate(&font);
+		assert_eq!(a.geometry_id, b.geometry_id);
+
+		b.positioning = TextPositioning::SubPixel;
+		b.generate(&font);
+		assert_ne!(a.geometry_id, b.geometry_id);
+
+		// Text still being revealed has geometry of its own until it's fully shown
+		let mut reveal = TextReveal::new();
+		reveal.progress = 1.0;
+		b.positioning = TextPositioning::PixelSnapped;
+		b.reveal = Some(reveal);
+		b.generate(&font);
+		assert_ne!(a.geometry_id, b.geometry_id);
+
+		reveal.progress = 2.0;
+		b.reveal = Some(reveal);
+		b.generate(&font);
+		assert_eq!(a.geometry_id, b.geometry_id);
+	}
 }
\ No newline at end of file
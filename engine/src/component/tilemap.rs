@@ -0,0 +1,208 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::pool::Handle;
+
+pub const CHUNK_SIZE: usize = 16;
+
+// Every generation of chunk geometry gets a unique id so the render system only uploads the chunks that changed
+static NEXT_GEOMETRY_ID: AtomicU64 = AtomicU64::new(1);
+
+pub struct TilemapChunk {
+	dirty: bool,
+	pub(crate) geometry_id: u64,
+	indices: Vec<u16>,
+	attributes: Vec<f32>
+}
+
+impl TilemapChunk {
+	pub fn indices(&self) -> &[u16] {
+		&self.indices
+	}
+
+	pub fn attributes(&self) -> &[f32] {
+		&self.attributes
+	}
+}
+
+// A grid of tiles from an atlas texture, drawn in pixels by the render system with the entity's 2D transform. The geometry
+// is split into chunks so editing a tile only regenerates and uploads the chunk it's in.
+pub struct Tilemap {
+	// The atlas in the texture pool, the tilemap isn't drawn until it's submitted to the render system
+	pub texture: Handle,
+	width: usize,
+	height: usize,
+	tile_width: f32,
+	tile_height: f32,
+	atlas_width: f32,
+	atlas_height: f32,
+	atlas_columns: usize,
+	tiles: Vec<Option<u16>>,
+	chunk_columns: usize,
+	chunks: Vec<TilemapChunk>
+}
+
+impl Tilemap {
+	pub fn new(texture: Handle, width: usize, height: usize, tile_width: f32, tile_height: f32, atlas_width: f32, atlas_height: f32) -> Self {
+		let atlas_columns = (atlas_width / tile_width) as usize;
+		assert!(atlas_columns > 0, "Atlas width {} cannot fit a tile {} wide", atlas_width, tile_width);
+
+		let chunk_columns = width.div_ceil(CHUNK_SIZE);
+		let chunk_rows = height.div_ceil(CHUNK_SIZE);
+
+		let chunks = (0..chunk_columns * chunk_rows).map(|_| TilemapChunk {
+			dirty: false,
+			geometry_id: 0,
+			indices: Vec::new(),
+			attributes: Vec::new()
+		}).collect();
+
+		Self {
+			texture,
+			width,
+			height,
+			tile_width,
+			tile_height,
+			atlas_width,
+			atlas_height,
+			atlas_columns,
+			tiles: vec![None; width * height],
+			chunk_columns,
+			chunks
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn tile(&self, x: usize, y: usize) -> Option<u16> {
+		assert!(x < self.width && y < self.height, "Tile ({}, {}) is outside of the {}x{} tilemap", x, y, self.width, self.height);
+		self.tiles[y * self.width + x]
+	}
+
+	pub fn set_tile(&mut self, x: usize, y: usize, tile: Option<u16>) {
+		assert!(x < self.width && y < self.height, "Tile ({}, {}) is outside of the {}x{} tilemap", x, y, self.width, self.height);
+
+		let tile_index = y * self.width + x;
+
+		if self.tiles[tile_index] != tile {
+			self.tiles[tile_index] = tile;
+
+			let chunk_index = (y / CHUNK_SIZE) * self.chunk_columns + x / CHUNK_SIZE;
+			self.chunks[chunk_index].dirty = true;
+		}
+	}
+
+	pub fn chunks(&self) -> &[TilemapChunk] {
+		&self.chunks
+	}
+
+	pub fn generate_dirty_chunks(&mut self) {
+		for (chunk_index, chunk) in self.chunks.iter_mut().enumerate() {
+			if !chunk.dirty {
+				continue;
+			}
+
+			chunk.dirty = false;
+			chunk.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+			chunk.indices.clear();
+			chunk.attributes.clear();
+
+			let start_x = (chunk_index % self.chunk_columns) * CHUNK_SIZE;
+			let start_y = (chunk_index / self.chunk_columns) * CHUNK_SIZE;
+			let end_x = (start_x + CHUNK_SIZE).min(self.width);
+			let end_y = (start_y + CHUNK_SIZE).min(self.height);
+			let mut quad_count = 0;
+
+			for y in start_y..end_y {
+				for x in start_x..end_x {
+					let tile = match self.tiles[y * self.width + x] {
+						Some(tile) => tile as usize,
+						None => continue
+					};
+
+					let index_offset = quad_count * 4;
+					chunk.indices.extend_from_slice(&[
+						index_offset, index_offset + 1, index_offset + 2,
+						index_offset, index_offset + 2, index_offset + 3
+					]);
+
+					let pos_x = x as f32 * self.tile_width;
+					let pos_y = y as f32 * self.tile_height;
+					let u_min = (tile % self.atlas_columns) as f32 * self.tile_width / self.atlas_width;
+					let v_min = (tile / self.atlas_columns) as f32 * self.tile_height / self.atlas_height;
+					let u_max = u_min + self.tile_width / self.atlas_width;
+					let v_max = v_min + self.tile_height / self.atlas_height;
+
+					chunk.attributes.extend_from_slice(&[
+						pos_x, pos_y, u_min, v_min,
+						pos_x + self.tile_width, pos_y, u_max, v_min,
+						pos_x + self.tile_width, pos_y + self.tile_height, u_max, v_max,
+						pos_x, pos_y + self.tile_height, u_min, v_max
+					]);
+
+					quad_count += 1;
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{pool::Pool, Texture};
+
+	fn tilemap() -> Tilemap {
+		let texture = Pool::new().add(Texture::from_color([255; 4]));
+		Tilemap::new(texture, CHUNK_SIZE * 2, CHUNK_SIZE, 16.0, 16.0, 64.0, 16.0)
+	}
+
+	#[test]
+	fn editing_a_tile_regenerates_only_its_chunk() {
+		let mut tilemap = tilemap();
+		tilemap.set_tile(0, 0, Some(1));
+		tilemap.set_tile(CHUNK_SIZE, 0, Some(2));
+		tilemap.generate_dirty_chunks();
+
+		let first_geometry_ids: Vec<u64> = tilemap.chunks().iter().map(|chunk| chunk.geometry_id).collect();
+
+		tilemap.set_tile(CHUNK_SIZE + 1, 0, Some(3));
+		tilemap.generate_dirty_chunks();
+
+		assert_eq!(tilemap.chunks()[0].geometry_id, first_geometry_ids[0]);
+		assert_ne!(tilemap.chunks()[1].geometry_id, first_geometry_ids[1]);
+		assert_eq!(tilemap.chunks()[1].indices().len(), 12);
+	}
+
+	#[test]
+	fn setting_a_tile_to_the_same_value_leaves_its_chunk_clean() {
+		let mut tilemap = tilemap();
+		tilemap.set_tile(0, 0, Some(1));
+		tilemap.generate_dirty_chunks();
+
+		let geometry_id = tilemap.chunks()[0].geometry_id;
+
+		tilemap.set_tile(0, 0, Some(1));
+		tilemap.generate_dirty_chunks();
+
+		assert_eq!(tilemap.chunks()[0].geometry_id, geometry_id);
+	}
+
+	#[test]
+	fn generates_uvs_from_the_atlas() {
+		let mut tilemap = tilemap();
+		tilemap.set_tile(1, 0, Some(2));
+		tilemap.generate_dirty_chunks();
+
+		assert_eq!(tilemap.chunks()[0].attributes(), &[
+			16.0, 0.0, 0.5, 0.0,
+			32.0, 0.0, 0.75, 0.0,
+			32.0, 16.0, 0.75, 1.0,
+			16.0, 16.0, 0.5, 1.0
+		]);
+	}
+}
\ No newline at end of file
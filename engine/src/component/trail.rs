@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use crate::math::{Color, Vector3, color};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TrailBlend {
+	// Adds onto what's behind it, for glowing sword slashes and tracers
+	Additive,
+	// Covers what's behind it by the alpha, for smoke and skid marks
+	Alpha
+}
+
+// Records where the entity has been and is drawn as a ribbon through those positions which always faces the camera. Each
+// recorded position fades out over the lifetime so the tail shrinks away behind the entity.
+pub struct Trail {
+	// Seconds each recorded position lasts
+	pub lifetime: f32,
+	// How far the entity moves before another position is recorded, the newest one follows the entity in between
+	pub min_distance: f32,
+	// The width and color from the newest position to one at the end of its lifetime, the keys are evenly spaced and
+	// interpolated between
+	pub widths: Vec<f32>,
+	pub colors: Vec<Color>,
+	pub blend: TrailBlend,
+	// Stops recording positions while what's already recorded fades out
+	pub emitting: bool,
+	points: VecDeque<TrailPoint>
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TrailPoint {
+	pub position: Vector3,
+	pub age: f32
+}
+
+impl Trail {
+	pub fn new() -> Self {
+		Self {
+			lifetime: 0.5,
+			min_distance: 0.1,
+			widths: vec![0.2, 0.0],
+			colors: vec![color::WHITE, color::TRANSPARENT],
+			blend: TrailBlend::Additive,
+			emitting: true,
+			points: VecDeque::new()
+		}
+	}
+
+	// The recorded positions from newest to oldest
+	pub fn points(&self) -> &VecDeque<TrailPoint> {
+		&self.points
+	}
+
+	// Forgets every recorded position, for when the entity is teleported
+	pub fn clear(&mut self) {
+		self.points.clear();
+	}
+
+	// Ages the recorded positions and records the entity's world space position
+	pub fn update(&mut self, position: Vector3, delta: f32) {
+		for point in &mut self.points {
+			point.age += delta;
+		}
+
+		while self.points.back().is_some_and(|point| point.age >= self.lifetime) {
+			self.points.pop_back();
+		}
+
+		if !self.emitting {
+			return;
+		}
+
+		match self.points.get(1) {
+			Some(previous) if previous.position.distance(&position) < self.min_distance => self.points[0] = TrailPoint { position, age: 0.0 },
+			_ => self.points.push_front(TrailPoint { position, age: 0.0 })
+		}
+	}
+
+	// Two vertices either side of every recorded position joined by triangles, 6 vertices per segment. The ribbon is
+	// widened perpendicular to both the trail and the direction to the camera so it's seen face on.
+	pub fn ribbon(&self, camera_position: &Vector3, vertices: &mut Vec<ColorVertex>) {
+		if self.points.len() < 2 {
+			return;
+		}
+
+		let edges: Vec<[ColorVertex; 2]> = (0..self.points.len())
+			.map(|index| {
+				let point = &self.points[index];
+				let newer = &self.points[index.saturating_sub(1)];
+				let older = &self.points[(index + 1).min(self.points.len() - 1)];
+				let t = (point.age / self.lifetime).clamp(0.0, 1.0);
+
+				let mut side = newer.position - older.position;
+				side.cross(&(camera_position - point.position));
+
+				if side.length_sq() > 0.0 {
+					side.normalize();
+				}
+
+				side *= sample(&self.widths, t, |a, b, t| a + (b - a) * t) * 0.5;
+				let color = sample(&self.colors, t, |a, b, t| a.lerp(b, t)).to_array();
+
+				let left = point.position + side;
+				let right = point.position - side;
+
+				[
+					ColorVertex { position: [left.x, left.y, left.z], color },
+					ColorVertex { position: [right.x, right.y, right.z], color }
+				]
+			})
+			.collect();
+
+		for pair in edges.windows(2) {
+			let [newer, older] = pair else { unreachable!() };
+			vertices.extend_from_slice(&[newer[0], newer[1], older[0], older[0], newer[1], older[1]]);
+		}
+	}
+}
+
+// Must match the trail vertex shader's inputs
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ColorVertex {
+	pub position: [f32; 3],
+	pub color: [f32; 4]
+}
+
+// Interpolates between evenly spaced keys, with no keys it's the default
+fn sample<T: Copy + Default>(keys: &[T], t: f32, lerp: impl Fn(&T, &T, f32) -> T) -> T {
+	match keys {
+		[] => T::default(),
+		[key] => *key,
+		_ => {
+			let position = t * (keys.len() - 1) as f32;
+			let index = (position.floor() as usize).min(keys.len() - 2);
+			lerp(&keys[index], &keys[index + 1], position - index as f32)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn records_and_fades_positions() {
+		let mut trail = Trail::new();
+		trail.lifetime = 1.0;
+		trail.min_distance = 1.0;
+
+		// The newest position follows the entity until it's moved far enough from the last one
+		trail.update(Vector3::new(0.0, 0.0, 0.0), 0.1);
+		trail.update(Vector3::new(0.5, 0.0, 0.0), 0.1);
+		trail.update(Vector3::new(0.8, 0.0, 0.0), 0.1);
+		assert_eq!(trail.points().len(), 2);
+		assert_eq!(trail.points()[0].position, Vector3::new(0.8, 0.0, 0.0));
+		trail.update(Vector3::new(1.5, 0.0, 0.0), 0.1);
+		assert_eq!(trail.points().len(), 3);
+
+		// Positions are forgotten once they're older than the lifetime
+		trail.emitting = false;
+		trail.update(Vector3::new(5.0, 0.0, 0.0), 0.75);
+		assert_eq!(trail.points().len(), 2);
+		trail.update(Vector3::new(5.0, 0.0, 0.0), 1.0);
+		assert!(trail.points().is_empty());
+	}
+
+	#[test]
+	fn ribbon_faces_camera() {
+		let mut trail = Trail::new();
+		trail.lifetime = 1.0;
+		trail.min_distance = 0.0;
+		trail.widths = vec![2.0, 0.0];
+		trail.colors = vec![color::WHITE, color::TRANSPARENT];
+		trail.update(Vector3::new(0.0, 0.0, 0.0), 0.0);
+		trail.update(Vector3::new(1.0, 0.0, 0.0), 0.5);
+
+		// Seen from above the ribbon spreads along z and narrows and fades towards the older end
+		let mut vertices = vec![];
+		trail.ribbon(&Vector3::new(0.0, 10.0, 0.0), &mut vertices);
+		assert_eq!(vertices.len(), 6);
+		assert!(vertices.iter().all(|vertex| vertex.position[1] == 0.0));
+		assert_eq!(vertices[0].position[2].abs(), 1.0);
+		assert_eq!(vertices[0].color, [1.0, 1.0, 1.0, 1.0]);
+		assert_eq!(vertices[2].position[2].abs(), 0.5);
+		assert_eq!(vertices[2].color[3], 0.5);
+	}
+}
\ No newline at end of file
@@ -1,6 +1,13 @@
 use crate::{EntityManager, Entity};
 use super::{ComponentList, Transform3D};
 
+// Which of an entity's matrices stay the same when it's moved to a new parent, the other is recalculated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReparentMode {
+	KeepGlobal,
+	KeepLocal
+}
+
 pub struct Transform3DComponentList {
 	component_list: ComponentList<Transform3D>,
 	dirty_count: usize
@@ -52,6 +59,49 @@ impl Transform3DComponentList {
 		}
 	}
 
+	// Moves the entity and its descendants under a new parent or to the root when there is none. With KeepGlobal the
+	// position, orientation and scale are recalculated from the entity's current global matrix so it must be up to date.
+	pub fn reparent(&mut self, entity: Entity, new_parent_entity: Option<Entity>, mode: ReparentMode) {
+		let mut ancestor_entity = new_parent_entity;
+
+		while let Some(e) = ancestor_entity {
+			assert!(e != entity, "Cannot make entity {} a child of itself or one of its descendants", entity);
+			ancestor_entity = self.component_list.borrow(&e).parent_entity;
+		}
+
+		if let Some(old_parent_entity) = self.component_list.borrow(&entity).parent_entity {
+			let old_parent_transform = self.component_list.borrow_mut(&old_parent_entity);
+			let child_entity_index = old_parent_transform.child_entities.iter().position(|e| *e == entity).unwrap();
+			old_parent_transform.child_entities.swap_remove(child_entity_index);
+		}
+
+		let new_parent_global_matrix = new_parent_entity.map(|new_parent_entity| {
+			let new_parent_transform = self.component_list.borrow_mut(&new_parent_entity);
+			new_parent_transform.child_entities.push(entity);
+			new_parent_transform.global_matrix
+		});
+
+		let transform = self.component_list.borrow_mut(&entity);
+		transform.parent_entity = new_parent_entity;
+
+		if mode == ReparentMode::KeepGlobal {
+			let local_matrix = match new_parent_global_matrix {
+				Some(mut inverse_parent_global_matrix) => {
+					inverse_parent_global_matrix.invert();
+					inverse_parent_global_matrix * transform.global_matrix
+				},
+				None => transform.global_matrix
+			};
+
+			let (position, orientation, scale) = local_matrix.decompose();
+			transform.position = position;
+			transform.orientation = orientation;
+			transform.scale = scale;
+		}
+
+		self.update(entity);
+	}
+
 	pub fn borrow(&self, entity: &Entity) -> &Transform3D {
 		self.component_list.borrow(entity)
 	}
@@ -102,4 +152,66 @@ impl Transform3DComponentList {
 	pub fn check_for_dirties(&self) {
 		assert!(self.dirty_count == 0, "{} global matrix/matrices have not been calculated", self.dirty_count);
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{Vector3, assert_approx_eq};
+
+	fn setup() -> (EntityManager, Transform3DComponentList, Entity, Entity) {
+		let mut entity_manager = EntityManager::new();
+		let mut transforms = Transform3DComponentList::new();
+
+		let parent_entity = entity_manager.create();
+		let mut parent_transform = Transform3D::new();
+		parent_transform.position.set(1.0, 2.0, 3.0);
+		parent_transform.orientation.set_from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), 0.5);
+		parent_transform.scale.set_from_scalar(2.0);
+		transforms.add(&mut entity_manager, parent_entity, parent_transform);
+
+		let child_entity = entity_manager.create();
+		let mut child_transform = Transform3D::new();
+		child_transform.position.set(-1.0, 0.5, 4.0);
+		child_transform.orientation.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 1.0);
+		transforms.add(&mut entity_manager, child_entity, child_transform);
+
+		(entity_manager, transforms, parent_entity, child_entity)
+	}
+
+	#[test]
+	fn reparent_keep_global() {
+		let (_entity_manager, mut transforms, parent_entity, child_entity) = setup();
+		let global_matrix = *transforms.borrow(&child_entity).global_matrix();
+
+		transforms.reparent(child_entity, Some(parent_entity), ReparentMode::KeepGlobal);
+		assert!(transforms.borrow(&parent_entity).child_entities == [child_entity]);
+		assert_approx_eq(transforms.borrow(&child_entity).global_matrix(), &global_matrix, 1e-5);
+
+		transforms.reparent(child_entity, None, ReparentMode::KeepGlobal);
+		let child_transform = transforms.borrow(&child_entity);
+		assert!(transforms.borrow(&parent_entity).child_entities.is_empty());
+		assert!(child_transform.parent_entity.is_none());
+		assert_approx_eq(&child_transform.position, &Vector3::new(-1.0, 0.5, 4.0), 1e-5);
+		assert_approx_eq(&child_transform.scale, &Vector3::from_scalar(1.0), 1e-5);
+	}
+
+	#[test]
+	fn reparent_keep_local() {
+		let (_entity_manager, mut transforms, parent_entity, child_entity) = setup();
+		transforms.reparent(child_entity, Some(parent_entity), ReparentMode::KeepLocal);
+
+		let child_transform = transforms.borrow(&child_entity);
+		let expected_global_matrix = transforms.borrow(&parent_entity).global_matrix() * child_transform.local_matrix();
+		assert_approx_eq(&child_transform.position, &Vector3::new(-1.0, 0.5, 4.0), 0.0);
+		assert_approx_eq(child_transform.global_matrix(), &expected_global_matrix, 0.0);
+	}
+
+	#[test]
+	#[should_panic]
+	fn reparent_under_descendant() {
+		let (_entity_manager, mut transforms, parent_entity, child_entity) = setup();
+		transforms.reparent(child_entity, Some(parent_entity), ReparentMode::KeepLocal);
+		transforms.reparent(parent_entity, Some(child_entity), ReparentMode::KeepLocal);
+	}
 }
\ No newline at end of file
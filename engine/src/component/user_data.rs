@@ -0,0 +1,129 @@
+// A value from a scene file or set by the game, numbers are f64 so whole numbers such as ids round trip
+#[derive(Clone, PartialEq, Debug)]
+pub enum UserValue {
+	Bool(bool),
+	Number(f64),
+	String(String)
+}
+
+impl From<bool> for UserValue {
+	fn from(value: bool) -> Self {
+		Self::Bool(value)
+	}
+}
+
+impl From<f64> for UserValue {
+	fn from(value: f64) -> Self {
+		Self::Number(value)
+	}
+}
+
+impl From<&str> for UserValue {
+	fn from(value: &str) -> Self {
+		Self::String(value.to_string())
+	}
+}
+
+impl From<String> for UserValue {
+	fn from(value: String) -> Self {
+		Self::String(value)
+	}
+}
+
+// Gameplay annotations which don't deserve a component of their own, such as a spawn point's type or a chest's loot table
+// id. Entities usually have a handful of keys so they're kept in a list in the order they were first set.
+#[derive(Clone, PartialEq, Debug)]
+pub struct UserData {
+	values: Vec<(String, UserValue)>
+}
+
+impl UserData {
+	pub fn new() -> Self {
+		Self {
+			values: vec![]
+		}
+	}
+
+	// Replaces the value already set for the key
+	pub fn set(&mut self, key: &str, value: impl Into<UserValue>) {
+		let value = value.into();
+
+		match self.values.iter_mut().find(|(set_key, _)| set_key == key) {
+			Some(entry) => entry.1 = value,
+			None => self.values.push((key.to_string(), value))
+		}
+	}
+
+	pub fn get(&self, key: &str) -> Option<&UserValue> {
+		self.values.iter().find(|(set_key, _)| set_key == key).map(|(_, value)| value)
+	}
+
+	pub fn remove(&mut self, key: &str) -> Option<UserValue> {
+		let index = self.values.iter().position(|(set_key, _)| set_key == key)?;
+		Some(self.values.remove(index).1)
+	}
+
+	pub fn contains(&self, key: &str) -> bool {
+		self.get(key).is_some()
+	}
+
+	// The typed getters are none when the key isn't set or holds another type
+	pub fn get_bool(&self, key: &str) -> Option<bool> {
+		match self.get(key) {
+			Some(UserValue::Bool(value)) => Some(*value),
+			_ => None
+		}
+	}
+
+	pub fn get_number(&self, key: &str) -> Option<f64> {
+		match self.get(key) {
+			Some(UserValue::Number(value)) => Some(*value),
+			_ => None
+		}
+	}
+
+	pub fn get_str(&self, key: &str) -> Option<&str> {
+		match self.get(key) {
+			Some(UserValue::String(value)) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &UserValue)> {
+		self.values.iter().map(|(key, value)| (key.as_str(), value))
+	}
+
+	pub fn len(&self) -> usize {
+		self.values.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.values.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sets_and_gets_typed_values() {
+		let mut user_data = UserData::new();
+		user_data.set("spawn_type", "archer");
+		user_data.set("loot_table", 12.0);
+		user_data.set("hidden", true);
+		user_data.set("spawn_type", "knight");
+
+		assert_eq!(user_data.get_str("spawn_type"), Some("knight"));
+		assert_eq!(user_data.get_number("loot_table"), Some(12.0));
+		assert_eq!(user_data.get_bool("hidden"), Some(true));
+
+		// Asking for the wrong type or a missing key
+		assert_eq!(user_data.get_number("spawn_type"), None);
+		assert_eq!(user_data.get_str("respawn_time"), None);
+
+		assert_eq!(user_data.remove("hidden"), Some(UserValue::Bool(true)));
+		assert!(!user_data.contains("hidden"));
+		assert_eq!(user_data.iter().map(|(key, _)| key).collect::<Vec<_>>(), ["spawn_type", "loot_table"]);
+	}
+}
\ No newline at end of file
@@ -5,7 +5,10 @@ pub const MAX_ENTITY_COUNT: usize = 500;
 pub struct EntityManager {
 	free_entities: Vec<Entity>,
 	alive_entity_count: usize,
-	component_counts: [u16; MAX_ENTITY_COUNT]
+	component_counts: [u16; MAX_ENTITY_COUNT],
+	generations: [u32; MAX_ENTITY_COUNT],
+	names: Vec<Option<String>>,
+	tags: [u32; MAX_ENTITY_COUNT]
 }
 
 impl EntityManager {
@@ -13,7 +16,10 @@ impl EntityManager {
 		Self {
 			free_entities: Vec::new(),
 			alive_entity_count: 0,
-			component_counts: [0; MAX_ENTITY_COUNT]
+			component_counts: [0; MAX_ENTITY_COUNT],
+			generations: [0; MAX_ENTITY_COUNT],
+			names: vec![None; MAX_ENTITY_COUNT],
+			tags: [0; MAX_ENTITY_COUNT]
 		}
 	}
 
@@ -33,10 +39,63 @@ impl EntityManager {
 		let component_count = self.component_counts[entity.index];
 		assert_eq!(component_count, 0, "Cannot destroy entity because it has {} components attached", component_count);
 		entity.generation += 1;
+		self.generations[entity.index] = entity.generation;
+		self.names[entity.index] = None;
+		self.tags[entity.index] = 0;
 		self.free_entities.push(entity);
 		self.alive_entity_count -= 1;
 	}
 
+	// Names don't have to be unique, find_by_name returns the first entity with the name
+	pub fn set_name(&mut self, entity: Entity, name: &str) {
+		self.assert_alive(entity, "name");
+		self.names[entity.index] = Some(String::from(name));
+	}
+
+	pub fn clear_name(&mut self, entity: Entity) {
+		self.assert_alive(entity, "name");
+		self.names[entity.index] = None;
+	}
+
+	pub fn name(&self, entity: Entity) -> Option<&str> {
+		self.assert_alive(entity, "name");
+		self.names[entity.index].as_deref()
+	}
+
+	pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+		self.names.iter()
+			.position(|entity_name| entity_name.as_deref() == Some(name))
+			.map(|index| Entity::new(index, self.generations[index]))
+	}
+
+	// Tags are bit flags like layer masks, the game decides what each bit means
+	pub fn add_tags(&mut self, entity: Entity, tags: u32) {
+		self.assert_alive(entity, "tag");
+		self.tags[entity.index] |= tags;
+	}
+
+	pub fn remove_tags(&mut self, entity: Entity, tags: u32) {
+		self.assert_alive(entity, "tag");
+		self.tags[entity.index] &= !tags;
+	}
+
+	pub fn tags(&self, entity: Entity) -> u32 {
+		self.assert_alive(entity, "tag");
+		self.tags[entity.index]
+	}
+
+	// Entities with any of the given tags
+	pub fn entities_with_tag(&self, tags: u32) -> impl Iterator<Item = Entity> + '_ {
+		self.tags.iter()
+			.enumerate()
+			.filter(move |(_, entity_tags)| *entity_tags & tags != 0)
+			.map(move |(index, _)| Entity::new(index, self.generations[index]))
+	}
+
+	fn assert_alive(&self, entity: Entity, label_kind: &str) {
+		assert_eq!(self.generations[entity.index], entity.generation, "Cannot {} entity {} because it has been destroyed", label_kind, entity);
+	}
+
 	pub(crate) fn increment_component_count(&mut self, entity_index: usize) {
 		self.component_counts[entity_index] += 1;
 	}
@@ -44,4 +103,74 @@ impl EntityManager {
 	pub(crate) fn decrement_component_count(&mut self, entity_index: usize) {
 		self.component_counts[entity_index] -= 1;
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PLAYER_TAG: u32 = 1;
+	const ENEMY_TAG: u32 = 1 << 1;
+
+	#[test]
+	fn find_by_name() {
+		let mut entity_manager = EntityManager::new();
+		let door = entity_manager.create();
+		let lever = entity_manager.create();
+		entity_manager.set_name(door, "door");
+		entity_manager.set_name(lever, "lever");
+
+		assert!(entity_manager.find_by_name("lever") == Some(lever));
+		assert_eq!(entity_manager.name(door), Some("door"));
+		assert!(entity_manager.find_by_name("window").is_none());
+
+		entity_manager.clear_name(lever);
+		assert!(entity_manager.find_by_name("lever").is_none());
+	}
+
+	#[test]
+	fn labels_are_cleared_when_destroyed() {
+		let mut entity_manager = EntityManager::new();
+		let entity = entity_manager.create();
+		entity_manager.set_name(entity, "door");
+		entity_manager.add_tags(entity, PLAYER_TAG);
+		entity_manager.destroy(entity);
+
+		let reused = entity_manager.create();
+		assert_eq!(reused.index(), entity.index());
+		assert!(entity_manager.find_by_name("door").is_none());
+		assert_eq!(entity_manager.tags(reused), 0);
+
+		// The reused entity is found with its new generation
+		entity_manager.set_name(reused, "window");
+		assert!(entity_manager.find_by_name("window") == Some(reused));
+	}
+
+	#[test]
+	fn entities_with_tag() {
+		let mut entity_manager = EntityManager::new();
+		let player = entity_manager.create();
+		let enemies = [entity_manager.create(), entity_manager.create()];
+		entity_manager.create();
+
+		entity_manager.add_tags(player, PLAYER_TAG);
+
+		for enemy in enemies {
+			entity_manager.add_tags(enemy, ENEMY_TAG | PLAYER_TAG);
+			entity_manager.remove_tags(enemy, PLAYER_TAG);
+		}
+
+		assert!(entity_manager.entities_with_tag(ENEMY_TAG).eq(enemies));
+		assert!(entity_manager.entities_with_tag(PLAYER_TAG).eq([player]));
+		assert_eq!(entity_manager.entities_with_tag(PLAYER_TAG | ENEMY_TAG).count(), 3);
+	}
+
+	#[test]
+	#[should_panic]
+	fn naming_a_destroyed_entity() {
+		let mut entity_manager = EntityManager::new();
+		let entity = entity_manager.create();
+		entity_manager.destroy(entity);
+		entity_manager.set_name(entity, "door");
+	}
 }
\ No newline at end of file
@@ -0,0 +1,432 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use crate::{
+	Camera,
+	Entity,
+	binary_reader::{BinaryReader, BinaryReadError},
+	component::{MultiComponentList, Mesh, Transform3DComponentList, ALL_LAYERS_MASK},
+	math::{matrix4, Vector3},
+	system::render_system::FrameCapture
+};
+
+const CUBEMAP_MAGIC: &[u8; 4] = b"VGCM";
+const CUBEMAP_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum CubemapError {
+	Io { path: PathBuf, error: io::Error },
+	Invalid { reason: String }
+}
+
+impl fmt::Display for CubemapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot access cubemap {}: {}", path.display(), error),
+			Self::Invalid { reason } => write!(f, "Cubemap is invalid: {}", reason)
+		}
+	}
+}
+
+impl Error for CubemapError {}
+
+impl From<BinaryReadError> for CubemapError {
+	fn from(error: BinaryReadError) -> Self {
+		Self::Invalid { reason: error.to_string() }
+	}
+}
+
+// In the order of the array layers of a Vulkan cube image
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CubeFace {
+	PositiveX,
+	NegativeX,
+	PositiveY,
+	NegativeY,
+	PositiveZ,
+	NegativeZ
+}
+
+impl CubeFace {
+	pub const ALL: [CubeFace; 6] = [Self::PositiveX, Self::NegativeX, Self::PositiveY, Self::NegativeY, Self::PositiveZ, Self::NegativeZ];
+
+	// The direction the face looks in and the directions along its rows and down its columns, the same as Vulkan samples
+	// cube images with
+	fn axes(&self) -> (Vector3, Vector3, Vector3) {
+		match self {
+			Self::PositiveX => (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, -1.0, 0.0)),
+			Self::NegativeX => (Vector3::new(-1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, -1.0, 0.0)),
+			Self::PositiveY => (Vector3::new(0.0, 1.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+			Self::NegativeY => (Vector3::new(0.0, -1.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)),
+			Self::PositiveZ => (Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, -1.0, 0.0)),
+			Self::NegativeZ => (Vector3::new(0.0, 0.0, -1.0), Vector3::new(-1.0, 0.0, 0.0), Vector3::new(0.0, -1.0, 0.0))
+		}
+	}
+
+	// The direction through the point of the face, s goes left to right and t top to bottom
+	pub fn direction(&self, s: f32, t: f32) -> Vector3 {
+		let (forward, right, down) = self.axes();
+		forward + right * (s * 2.0 - 1.0) + down * (t * 2.0 - 1.0)
+	}
+
+	// The face a direction points through and where on the face
+	pub fn from_direction(direction: &Vector3) -> (Self, f32, f32) {
+		let (x, y, z) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+
+		let face = if x >= y && x >= z {
+			if direction.x > 0.0 { Self::PositiveX } else { Self::NegativeX }
+		}
+		else if y >= z {
+			if direction.y > 0.0 { Self::PositiveY } else { Self::NegativeY }
+		}
+		else if direction.z > 0.0 { Self::PositiveZ } else { Self::NegativeZ };
+
+		let (forward, right, down) = face.axes();
+		let major = direction.dot(&forward);
+		let s = (direction.dot(&right) / major + 1.0) / 2.0;
+		let t = (direction.dot(&down) / major + 1.0) / 2.0;
+		(face, s, t)
+	}
+
+	// A camera at the position with a square 90 degree view through the face. The renderer's image of it comes out mirrored
+	// left to right compared to the face because cubemaps are seen from the inside.
+	pub fn camera(&self, position: &Vector3, near: f32, far: f32) -> Camera {
+		let (forward, right, down) = self.axes();
+		let up = down * -1.0;
+
+		// The view space x, y and z axes point along the face's rows, up and forward
+		let mut orientation_matrix = matrix4::IDENTITY;
+
+		for (column, axis) in [right, up, forward].iter().enumerate() {
+			orientation_matrix.elements[0][column] = axis.x;
+			orientation_matrix.elements[1][column] = axis.y;
+			orientation_matrix.elements[2][column] = axis.z;
+		}
+
+		let mut camera = Camera::new(1.0, 90.0, near, far);
+		camera.fixed_aspect_ratio = Some(1.0);
+		camera.transform.position = *position;
+		camera.transform.orientation.set_from_rotation_matrix(&orientation_matrix);
+		camera.update();
+		camera
+	}
+}
+
+// Six square sRGB 8 bit RGBA images, rows from the top, in the order of CubeFace::ALL
+pub struct Cubemap {
+	pub size: usize,
+	pub faces: [Vec<u8>; 6]
+}
+
+impl Cubemap {
+	pub fn new(size: usize) -> Self {
+		assert!(size > 0, "A cubemap must be at least 1x1");
+
+		Self {
+			size,
+			faces: [(); 6].map(|_| vec![0; size * size * 4])
+		}
+	}
+
+	// The nearest texel in the direction
+	pub fn sample(&self, direction: &Vector3) -> [u8; 4] {
+		let (face, s, t) = CubeFace::from_direction(direction);
+		let x = ((s * self.size as f32) as usize).min(self.size - 1);
+		let y = ((t * self.size as f32) as usize).min(self.size - 1);
+		let offset = (y * self.size + x) * 4;
+		let pixels = &self.faces[face as usize];
+		[pixels[offset], pixels[offset + 1], pixels[offset + 2], pixels[offset + 3]]
+	}
+
+	// The magic, version and size followed by the faces, little endian
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(12 + self.size * self.size * 24);
+		bytes.extend_from_slice(CUBEMAP_MAGIC);
+		bytes.extend_from_slice(&CUBEMAP_VERSION.to_le_bytes());
+		bytes.extend_from_slice(&(self.size as u32).to_le_bytes());
+
+		for face in &self.faces {
+			bytes.extend_from_slice(face);
+		}
+
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, CubemapError> {
+		let mut reader = BinaryReader::new(bytes);
+
+		if reader.read_bytes(4)? != CUBEMAP_MAGIC {
+			return Err(CubemapError::Invalid { reason: String::from("the magic number is wrong") });
+		}
+
+		let version = reader.read_u32()?;
+
+		if version != CUBEMAP_VERSION {
+			return Err(CubemapError::Invalid { reason: format!("version {} is not supported", version) });
+		}
+
+		let size = reader.read_u32()? as usize;
+
+		if size == 0 {
+			return Err(CubemapError::Invalid { reason: String::from("the size is zero") });
+		}
+
+		let mut cubemap = Self::new(size);
+
+		for face in &mut cubemap.faces {
+			face.copy_from_slice(reader.read_bytes(size * size * 4)?);
+		}
+
+		Ok(cubemap)
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CubemapError> {
+		let path = path.as_ref();
+		fs::write(path, self.to_bytes()).map_err(|error| CubemapError::Io { path: path.to_path_buf(), error })
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, CubemapError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|error| CubemapError::Io { path: path.to_path_buf(), error })?;
+		Self::from_bytes(&bytes)
+	}
+}
+
+// Renders a cubemap one face per frame with the render system's frame capture. Every frame until it's finished, render with
+// the camera after requesting a frame capture and add the capture. The faces are cut out of the middle of the window so
+// the window's smaller side is the size of the cubemap.
+//
+// if let Some(camera) = capture.camera() {
+// 	render_system.request_frame_capture();
+// 	render_system.render(&camera, ...);
+// 	capture.add_capture(&render_system.take_frame_capture().unwrap());
+// }
+pub struct CubemapCapture {
+	pub position: Vector3,
+	pub near: f32,
+	pub far: f32,
+	pub layer_mask: u32,
+	faces: Vec<(usize, Vec<u8>)>
+}
+
+impl CubemapCapture {
+	pub fn new(position: Vector3, near: f32, far: f32) -> Self {
+		Self {
+			position,
+			near,
+			far,
+			layer_mask: ALL_LAYERS_MASK,
+			faces: Vec::with_capacity(6)
+		}
+	}
+
+	// The camera to render the next face with, none once every face is captured
+	pub fn camera(&self) -> Option<Camera> {
+		let face = CubeFace::ALL.get(self.faces.len())?;
+		let mut camera = face.camera(&self.position, self.near, self.far);
+		camera.layer_mask = self.layer_mask;
+		Some(camera)
+	}
+
+	pub fn add_capture(&mut self, capture: &FrameCapture) {
+		assert!(self.faces.len() < 6, "Cannot add a capture to a cubemap capture which has every face");
+
+		let (x, y, width, height) = self.camera().unwrap().viewport(capture.width, capture.height);
+		let size = width.min(height) as usize;
+		let (x, y) = (x as usize, y as usize);
+		let mut face = Vec::with_capacity(size * size * 4);
+
+		// Mirror each row back to how the face is seen from the inside
+		for row in y..y + size {
+			let row_start = (row * capture.width as usize + x) * 4;
+
+			for pixel in capture.pixels[row_start..row_start + size * 4].chunks_exact(4).rev() {
+				face.extend_from_slice(pixel);
+			}
+		}
+
+		if let Some((first_size, _)) = self.faces.first() {
+			assert_eq!(*first_size, size, "Every face of a cubemap capture must be the same size, was the window resized?");
+		}
+
+		self.faces.push((size, face));
+	}
+
+	pub fn finished(&self) -> bool {
+		self.faces.len() == 6
+	}
+
+	pub fn into_cubemap(self) -> Cubemap {
+		assert!(self.finished(), "Cannot make a cubemap until all 6 faces are captured");
+
+		let mut cubemap = Cubemap::new(self.faces[0].0);
+
+		for (face, (_, pixels)) in cubemap.faces.iter_mut().zip(self.faces) {
+			*face = pixels;
+		}
+
+		cubemap
+	}
+}
+
+pub struct EnvironmentProbe {
+	pub position: Vector3,
+	pub cubemap: Option<Cubemap>
+}
+
+// Places probes around the scene and gives each mesh the probe nearest to it for image based lighting
+pub struct EnvironmentProbes {
+	probes: Vec<EnvironmentProbe>,
+	// The probe of each mesh, indexed by the entity's index
+	assignments: Vec<Option<(Entity, usize)>>
+}
+
+impl EnvironmentProbes {
+	pub fn new() -> Self {
+		Self {
+			probes: vec![],
+			assignments: vec![]
+		}
+	}
+
+	// Returns the probe's index
+	pub fn add(&mut self, position: Vector3) -> usize {
+		self.probes.push(EnvironmentProbe { position, cubemap: None });
+		self.probes.len() - 1
+	}
+
+	pub fn probe(&self, index: usize) -> &EnvironmentProbe {
+		&self.probes[index]
+	}
+
+	pub fn probe_mut(&mut self, index: usize) -> &mut EnvironmentProbe {
+		&mut self.probes[index]
+	}
+
+	pub fn len(&self) -> usize {
+		self.probes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.probes.is_empty()
+	}
+
+	pub fn nearest(&self, point: &Vector3) -> Option<usize> {
+		self.probes.iter()
+			.enumerate()
+			.min_by(|(_, a), (_, b)| a.position.distance(point).total_cmp(&b.position.distance(point)))
+			.map(|(index, _)| index)
+	}
+
+	// Gives every mesh the probe nearest to its origin, call again after moving meshes or probes
+	pub fn assign(&mut self, mesh_components: &MultiComponentList<Mesh>, transform3d_components: &Transform3DComponentList) {
+		self.assignments.clear();
+
+		for (entities, _) in mesh_components.iter() {
+			for entity in entities {
+				let position = transform3d_components.borrow(entity).global_matrix.extract_position();
+				let probe = match self.nearest(&position) {
+					Some(probe) => probe,
+					None => return
+				};
+
+				if self.assignments.len() <= entity.index {
+					self.assignments.resize(entity.index + 1, None);
+				}
+
+				self.assignments[entity.index] = Some((*entity, probe));
+			}
+		}
+	}
+
+	// The probe the mesh was assigned
+	pub fn probe_for(&self, entity: &Entity) -> Option<usize> {
+		match self.assignments.get(entity.index) {
+			Some(Some((assigned_entity, probe))) if assigned_entity == entity => Some(*probe),
+			_ => None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::{mesh::Material, Transform3D}, pool::Pool, Geometry3D};
+
+	#[test]
+	fn face_cameras_match_cube_sampling() {
+		let position = Vector3::new(1.0, 2.0, 3.0);
+
+		for face in &CubeFace::ALL {
+			let camera = face.camera(&position, 0.1, 10.0);
+			let mut view_matrix = camera.transform.global_matrix;
+			view_matrix.invert();
+			let view_projection_matrix = camera.projection_matrix * view_matrix;
+
+			for (s, t) in [(0.5, 0.5), (0.25, 0.75), (0.9, 0.1)] {
+				let direction = face.direction(s, t);
+				assert_eq!(CubeFace::from_direction(&direction).0, *face);
+
+				// The image is mirrored and rows go down the screen
+				let clip = view_projection_matrix * (position + direction).expand(1.0);
+				let column = (clip.x / clip.w + 1.0) / 2.0;
+				let row = (clip.y / clip.w + 1.0) / 2.0;
+				assert!((1.0 - column - s).abs() < 1e-4 && (row - t).abs() < 1e-4, "{:?} at {}, {}", face, s, t);
+			}
+		}
+	}
+
+	#[test]
+	fn capture_and_sample() {
+		let mut capture = CubemapCapture::new(Vector3::from_scalar(0.0), 0.1, 10.0);
+
+		// A 4x2 window leaves a 2x2 face in the middle, each face is filled with its index apart from one pixel
+		while !capture.finished() {
+			let index = capture.faces.len() as u8;
+			let mut pixels = vec![index; 32];
+			pixels[8..12].copy_from_slice(&[200, 201, 202, 203]);
+			capture.add_capture(&FrameCapture { width: 4, height: 2, pixels });
+		}
+
+		let cubemap = capture.into_cubemap();
+		assert_eq!(cubemap.size, 2);
+		assert_eq!(cubemap.sample(&Vector3::new(0.0, -1.0, 0.0)), [3; 4]);
+
+		// The top right of the middle of the capture is the top left of the face
+		assert_eq!(cubemap.sample(&CubeFace::NegativeZ.direction(0.1, 0.1)), [200, 201, 202, 203]);
+
+		let loaded = Cubemap::from_bytes(&cubemap.to_bytes()).unwrap();
+		assert_eq!(loaded.faces, cubemap.faces);
+		assert!(Cubemap::from_bytes(&cubemap.to_bytes()[..20]).is_err());
+	}
+
+	#[test]
+	fn nearest_probe() {
+		let mut entity_manager = EntityManager::new();
+		let mut geometries = Pool::<Geometry3D>::new();
+		let geometry_handle = geometries.add(Geometry3D::create_box());
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut mesh_components = MultiComponentList::new();
+		let mesh = mesh_components.add(Mesh::new(geometry_handle, Material::Basic));
+
+		let entities: Vec<Entity> = [-5.0, 1.0, 8.0].iter()
+			.map(|x| {
+				let entity = entity_manager.create();
+				let mut transform = Transform3D::new();
+				transform.position.set(*x, 0.0, 0.0);
+				transform3d_components.add(&mut entity_manager, entity, transform);
+				mesh_components.assign(&mut entity_manager, entity, mesh);
+				entity
+			})
+			.collect();
+
+		let mut probes = EnvironmentProbes::new();
+		probes.assign(&mesh_components, &transform3d_components);
+		assert!(probes.probe_for(&entities[0]).is_none());
+
+		let left = probes.add(Vector3::new(-4.0, 0.0, 0.0));
+		let right = probes.add(Vector3::new(6.0, 2.0, 0.0));
+		probes.assign(&mesh_components, &transform3d_components);
+		assert_eq!(probes.probe_for(&entities[0]), Some(left));
+		assert_eq!(probes.probe_for(&entities[1]), Some(left));
+		assert_eq!(probes.probe_for(&entities[2]), Some(right));
+	}
+}
\ No newline at end of file
@@ -1,5 +1,57 @@
-use std::{path, fs, io, ptr, ffi::CString, slice, io::{Read, Write, Seek}, convert::TryInto};
+use std::{path, fs, io, iter, fmt, ptr, ffi::CString, slice};
 use freetype::freetype::*;
+use crate::{AtlasPacker, binary_reader::{BinaryReader, BinaryReadError}, Vfs, VfsError};
+
+#[derive(Debug)]
+pub enum FntError {
+	Io(io::Error),
+	Vfs(VfsError),
+	Read(BinaryReadError),
+	InvalidAtlasSize { width: usize, height: usize },
+	InvalidFlags { flags: u32 },
+	InvalidSpaceAdvance,
+	InvalidGlyph { char_code: u32 },
+	TrailingBytes { count: usize },
+	AtlasSizeChanged,
+	Outdated
+}
+
+impl fmt::Display for FntError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "{}", e),
+			Self::Vfs(e) => write!(f, "{}", e),
+			Self::Read(e) => write!(f, "{}", e),
+			Self::InvalidAtlasSize { width, height } => write!(f, "Invalid atlas size {}x{}", width, height),
+			Self::InvalidFlags { flags } => write!(f, "Unknown flags {:#x}", flags),
+			Self::InvalidSpaceAdvance => write!(f, "Invalid space advance"),
+			Self::InvalidGlyph { char_code } => write!(f, "Glyph {} is not finite or lies outside the atlas", char_code),
+			Self::TrailingBytes { count } => write!(f, "{} unexpected bytes after the last glyph", count),
+			Self::AtlasSizeChanged => write!(f, "The atlas size or format changed since the font was loaded"),
+			Self::Outdated => write!(f, "Generated before glyphs were padded for sub pixel positioning")
+		}
+	}
+}
+
+impl std::error::Error for FntError {}
+
+impl From<io::Error> for FntError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+impl From<VfsError> for FntError {
+	fn from(e: VfsError) -> Self {
+		Self::Vfs(e)
+	}
+}
+
+impl From<BinaryReadError> for FntError {
+	fn from(e: BinaryReadError) -> Self {
+		Self::Read(e)
+	}
+}
 
 pub struct Glyph {
 	pub char_code: u32,
@@ -12,9 +64,19 @@ pub struct Glyph {
 	pub advance: f32
 }
 
+// The atlas holds RGBA texels instead of coverage
+const COLOR_ATLAS_FLAG: u32 = 1;
+
+// Every glyph has a transparent texel around it and unhinted advances, files without it are generated again
+const PADDED_GLYPHS_FLAG: u32 = 2;
+
+// A hash of the font file, size, characters and format the file was generated from follows the glyphs
+const SOURCE_HASH_FLAG: u32 = 4;
+
 struct UnplacedGlyph {
 	char_code: u32,
-	bitmap: Vec<Vec<u8>>,
+	// Coverage fonts only use the first channel of each texel
+	bitmap: Vec<Vec<[u8; 4]>>,
 	width: f32,
 	height: f32,
 	bearing_x: f32,
@@ -22,61 +84,254 @@ struct UnplacedGlyph {
 	advance: f32
 }
 
+// Everything in a font file, the atlas rows are packed top to bottom
+pub struct FntContents<'a> {
+	pub atlas_width: usize,
+	pub atlas_height: usize,
+	pub color: bool,
+	pub atlas: &'a [u8],
+	pub space_advance: f32,
+	pub glyphs: Vec<Glyph>,
+	pub source_hash: Option<u64>
+}
+
+// What to render the glyphs of a font with
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontOptions {
+	// The size in pixels
+	pub size: u32,
+	// Sorted without duplicates or spaces, which don't have glyphs
+	pub char_codes: Vec<u32>,
+	pub color: bool
+}
+
+impl FontOptions {
+	// The printable ASCII characters as coverage
+	pub fn new(size: u32) -> Self {
+		Self {
+			size,
+			char_codes: (33..127).collect(),
+			color: false
+		}
+	}
+
+	pub fn color(size: u32, chars: &str) -> Self {
+		Self {
+			size,
+			char_codes: Font::char_codes(chars),
+			color: true
+		}
+	}
+}
+
+// A generated font, the atlas rows are packed top to bottom
+pub struct FontData {
+	pub size: u32,
+	pub atlas_width: usize,
+	pub atlas_height: usize,
+	pub color: bool,
+	pub atlas: Vec<u8>,
+	pub space_advance: f32,
+	pub glyphs: Vec<Glyph>
+}
+
+// Renders the glyphs of a TrueType or OpenType font file and packs them into an atlas without going through the font file
+// cache, for tools and fonts generated while the game runs
+pub fn generate_font(file_path: &str, options: &FontOptions) -> FontData {
+	let ttf_path = CString::new(file_path).unwrap();
+	let (space_advance, unplaced_glyphs) = Font::load_ttf(ttf_path, options.size, &options.char_codes, options.color);
+	let (atlas_width, atlas_height, atlas, glyphs) = Font::create_atlas(unplaced_glyphs, options.color);
+
+	FontData {
+		size: options.size,
+		atlas_width,
+		atlas_height,
+		color: options.color,
+		atlas,
+		space_advance,
+		glyphs
+	}
+}
+
 pub(crate) struct SubmissionInfo {
 	pub generation: usize,
 	pub index: usize
 }
 
 pub struct Font {
+	// The path of the font file in the Vfs
 	pub fnt_path: String,
+	// The size in pixels the glyphs were rendered at
+	pub size: u32,
+	// The atlas holds colored glyphs like emoji which are drawn as they are instead of coverage which is tinted
+	pub color: bool,
 	pub atlas_width: usize,
 	pub atlas_height: usize,
 	pub space_advance: f32,
 	pub glyphs: Vec<Glyph>,
+	// Fonts made from generated data keep their atlas instead of reading it back from a font file
+	pub(crate) generated_atlas: Option<Vec<u8>>,
 	pub(crate) submission_info: Option<SubmissionInfo>
 }
 
 impl Font {
-	pub fn new(file_path: &str, size: u32) -> Self {
-		let file_path_buf = path::PathBuf::from(file_path);
-		let file_stem = file_path_buf.file_stem().unwrap().to_str().unwrap();
+	pub fn new(vfs: &Vfs, file_path: &str, size: u32) -> Self {
+		Self::load(vfs, file_path, &FontOptions::new(size))
+	}
 
-		let fnt_path = format!("target/fonts/{}{}.fnt", file_stem, size);
+	// Renders the characters with their colors for emoji or bitmap fonts. Fonts which only have bitmaps use the bitmaps of the
+	// size nearest to the requested one.
+	pub fn new_color(vfs: &Vfs, file_path: &str, size: u32, chars: &str) -> Self {
+		Self::load(vfs, file_path, &FontOptions::color(size, chars))
+	}
 
-		let (atlas_width, atlas_height, space_advance, glyphs) = match fs::File::open(fnt_path.to_owned()) {
-			Ok(file) => {
-				println!("Loading font {} at size {}", file_stem, size);
+	// A font which was generated without a font file, such as one generated while the game runs
+	pub fn from_data(data: FontData) -> Self {
+		Self {
+			fnt_path: String::new(),
+			size: data.size,
+			color: data.color,
+			atlas_width: data.atlas_width,
+			atlas_height: data.atlas_height,
+			space_advance: data.space_advance,
+			glyphs: data.glyphs,
+			generated_atlas: Some(data.atlas),
+			submission_info: None
+		}
+	}
 
-				Self::load_fnt(file)
-			},
-			Err(e) => {
-				if e.kind() == io::ErrorKind::NotFound {
-					println!("Generating font {} at size {}", file_stem, size);
+	// The generation of the font submission this font was part of, see RenderSystem::font_status
+	pub fn submission_generation(&self) -> Option<usize> {
+		self.submission_info.as_ref().map(|submission_info| submission_info.generation)
+	}
 
-					let ttf_path = CString::new(file_path).unwrap();
-					let (space_advance, unplaced_glyphs) = Self::load_ttf(ttf_path, size);
-					let (atlas, placed_glyphs) = Self::create_atlas(unplaced_glyphs);
-					Self::save_fnt(&fnt_path, &atlas, space_advance, &placed_glyphs);
+	// The sorted character codes of the string without spaces, which don't have glyphs
+	pub fn char_codes(chars: &str) -> Vec<u32> {
+		let mut char_codes: Vec<u32> = chars.chars().filter(|c| *c != ' ').map(|c| c as u32).collect();
+		char_codes.sort_unstable();
+		char_codes.dedup();
+		char_codes
+	}
 
-					(atlas[0].len(), atlas.len(), space_advance, placed_glyphs)
-				}
-				else {
-					panic!("Cannot load or generate font\n{}", e);
+	// Where the font file for the font is generated, development builds load it from there through the Vfs
+	pub fn fnt_path(file_path: &str, size: u32, color: bool) -> String {
+		format!("target/{}", Self::vfs_fnt_path(file_path, size, color))
+	}
+
+	// Where the font file for the font is loaded from in the Vfs
+	pub fn vfs_fnt_path(file_path: &str, size: u32, color: bool) -> String {
+		let file_stem = path::Path::new(file_path).file_stem().unwrap().to_str().unwrap();
+
+		if color {
+			format!("fonts/{}{}_color.fnt", file_stem, size)
+		}
+		else {
+			format!("fonts/{}{}.fnt", file_stem, size)
+		}
+	}
+
+	fn load(vfs: &Vfs, file_path: &str, options: &FontOptions) -> Self {
+		let file_path_buf = path::PathBuf::from(file_path);
+		let file_stem = file_path_buf.file_stem().unwrap().to_str().unwrap();
+		let (size, color) = (options.size, options.color);
+		let fnt_path = Self::vfs_fnt_path(file_path, size, color);
+
+		// Without the font file there's nothing to check the cache against, so a game shipped with only the cache uses it
+		let source_hash = fs::read(file_path).ok().map(|ttf| Self::source_hash(&ttf, options));
+
+		// A cache file which can't be parsed is treated like a missing one and generated again
+		let cached = match vfs.read(&fnt_path) {
+			Ok(bytes) => match Self::parse_fnt(&bytes) {
+				Ok(fnt) if fnt.color != color => {
+					println!("Font file {} has the wrong atlas format and will be regenerated", fnt_path);
+					None
+				},
+				Ok(fnt) if source_hash.is_some() && fnt.source_hash != source_hash => {
+					println!("Font file {} was generated from a different font or characters and will be regenerated", fnt_path);
+					None
+				},
+				Ok(fnt) => {
+					println!("Loading font {} at size {}", file_stem, size);
+					Some((fnt.atlas_width, fnt.atlas_height, fnt.space_advance, fnt.glyphs))
+				},
+				Err(e) => {
+					println!("Font file {} is invalid and will be regenerated: {}", fnt_path, e);
+					None
 				}
-			}
+			},
+			Err(VfsError::NotFound { .. }) => None,
+			Err(e) => panic!("Cannot load or generate font\n{}", e)
 		};
 
+		let (atlas_width, atlas_height, space_advance, glyphs) = cached.unwrap_or_else(|| {
+			println!("Generating font {} at size {}", file_stem, size);
+
+			let source_hash = source_hash.unwrap_or_else(|| panic!("Cannot read font {} to generate it", file_path));
+			let data = generate_font(file_path, options);
+			let generated_path = Self::fnt_path(file_path, size, color);
+			Self::save_fnt(&generated_path, &data, source_hash).unwrap_or_else(|e| panic!("Cannot save font file {}\n{}", generated_path, e));
+
+			(data.atlas_width, data.atlas_height, data.space_advance, data.glyphs)
+		});
+
 		Self {
 			fnt_path,
+			size,
+			color,
 			atlas_width,
 			atlas_height,
 			space_advance,
 			glyphs,
+			generated_atlas: None,
 			submission_info: None
 		}
 	}
 
-	fn load_ttf(ttf_path: CString, size: u32) -> (f32, Vec<UnplacedGlyph>) {
+	pub fn texel_size(color: bool) -> usize {
+		if color { 4 } else { 1 }
+	}
+
+	// Generates the font file at the path unless it was already generated from the same font file, size, characters and
+	// format, so a build step can run it every time. Returns whether it was generated.
+	pub fn generate(file_path: &str, options: &FontOptions, fnt_path: &str) -> Result<bool, FntError> {
+		let source_hash = Self::source_hash(&fs::read(file_path)?, options);
+
+		let up_to_date = match fs::read(fnt_path) {
+			Ok(bytes) => Self::parse_fnt(&bytes).is_ok_and(|fnt| fnt.color == options.color && fnt.source_hash == Some(source_hash)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+			Err(e) => return Err(e.into())
+		};
+
+		if up_to_date {
+			return Ok(false);
+		}
+
+		Self::save_fnt(fnt_path, &generate_font(file_path, options), source_hash)?;
+		Ok(true)
+	}
+
+	// FNV-1a of everything a font file is generated from
+	fn source_hash(ttf: &[u8], options: &FontOptions) -> u64 {
+		let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+		let mut add = |bytes: &[u8]| {
+			for byte in bytes {
+				hash ^= *byte as u64;
+				hash = hash.wrapping_mul(0x100_0000_01b3);
+			}
+		};
+
+		add(ttf);
+		add(&options.size.to_le_bytes());
+		add(&[options.color as u8]);
+
+		for char_code in &options.char_codes {
+			add(&char_code.to_le_bytes());
+		}
+
+		hash
+	}
+
+	fn load_ttf(ttf_path: CString, size: u32, char_codes: &[u32], color: bool) -> (f32, Vec<UnplacedGlyph>) {
 		let mut library: FT_Library = ptr::null_mut();
 		let error = unsafe { FT_Init_FreeType(&mut library) };
 		assert_eq!(error, 0, "Cannot initialize Freetype, error code {}", error);
@@ -85,20 +340,32 @@ impl Font {
 		let error = unsafe { FT_New_Face(library, ttf_path.as_ptr(), 0, &mut face) };
 		assert_eq!(error, 0, "Cannot load font face, font face {}", error);
 
+		// Fonts with only bitmaps like most color emoji fonts can't be scaled so the nearest bitmap size is picked instead
 		let error = unsafe { FT_Set_Pixel_Sizes(face, 0, size) };
-		assert_eq!(error, 0, "Cannot set the font size, error code {}", error);
+		let fixed_sizes_count = unsafe { (*face).num_fixed_sizes } as usize;
+
+		if error != 0 && fixed_sizes_count > 0 {
+			let fixed_sizes = unsafe { slice::from_raw_parts((*face).available_sizes, fixed_sizes_count) };
+			let nearest_index = (0..fixed_sizes_count).min_by_key(|i| (fixed_sizes[*i].y_ppem / 64 - size as FT_Pos).abs()).unwrap();
+			let error = unsafe { FT_Select_Size(face, nearest_index as FT_Int) };
+			assert_eq!(error, 0, "Cannot select the font's bitmap size, error code {}", error);
+		}
+		else {
+			assert_eq!(error, 0, "Cannot set the font size, error code {}", error);
+		}
+
+		let load_flags = if color { FT_LOAD_COLOR as FT_Int32 } else { 0 };
 
 		let space_glyph_index = unsafe { FT_Get_Char_Index(face, 32) };
-		let error = unsafe { FT_Load_Glyph(face, space_glyph_index, 0) };
+		let error = unsafe { FT_Load_Glyph(face, space_glyph_index, load_flags) };
 		assert_eq!(error, 0, "Cannot load the space glyph, error code {}", error);
-		let space_advance = unsafe { (*(*face).glyph).advance.x / 64 } as f32;
+		let space_advance = Self::glyph_advance(unsafe { &*(*face).glyph });
 
-		let char_codes = 33..127;
 		let mut unplaced_glyphs: Vec<UnplacedGlyph> = Vec::with_capacity(char_codes.len());
 
-		for char_code in char_codes {
-			let glyph_index = unsafe { FT_Get_Char_Index(face, char_code) };
-			let error = unsafe { FT_Load_Glyph(face, glyph_index, 0) };
+		for &char_code in char_codes {
+			let glyph_index = unsafe { FT_Get_Char_Index(face, char_code as FT_ULong) };
+			let error = unsafe { FT_Load_Glyph(face, glyph_index, load_flags) };
 			assert_eq!(error, 0, "Cannot load glyph, error code {}", error);
 
 			let error = unsafe { FT_Render_Glyph((*face).glyph, FT_Render_Mode::FT_RENDER_MODE_NORMAL) };
@@ -108,225 +375,488 @@ impl Font {
 			let ft_bitmap = ft_glyph.bitmap;
 			let rows = ft_bitmap.rows as usize;
 			let width = ft_bitmap.width as usize;
-			let pitch_abs = ft_bitmap.pitch.abs() as usize;
+			let pitch_abs = ft_bitmap.pitch.unsigned_abs() as usize;
+
+			let bgra = ft_bitmap.pixel_mode == FT_Pixel_Mode::FT_PIXEL_MODE_BGRA as u8;
+			let bytes_per_pixel = if bgra { 4 } else { 1 };
 
-			let mut bitmap: Vec<Vec<u8>> = Vec::with_capacity(rows);
+			// The transparent texel around the glyph is sampled instead of the neighboring glyphs when it's drawn between pixels
+			let mut bitmap: Vec<Vec<[u8; 4]>> = Vec::with_capacity(rows + 2);
+			bitmap.push(vec![[0; 4]; width + 2]);
 
 			for row_index in 0..rows {
-				bitmap.push(unsafe { slice::from_raw_parts(ft_bitmap.buffer.add(row_index * pitch_abs), width).to_vec() });
+				let row = unsafe { slice::from_raw_parts(ft_bitmap.buffer.add(row_index * pitch_abs), width * bytes_per_pixel) };
+
+				bitmap.push(iter::once([0; 4]).chain(row.chunks_exact(bytes_per_pixel).map(|pixel| {
+					if bgra {
+						Self::unpremultiply([pixel[2], pixel[1], pixel[0], pixel[3]])
+					}
+					else if color {
+						// Glyphs without colors in a color font are white so they can still be drawn
+						[255, 255, 255, pixel[0]]
+					}
+					else {
+						[pixel[0], 0, 0, 0]
+					}
+				})).chain(iter::once([0; 4])).collect());
 			}
 
+			bitmap.push(vec![[0; 4]; width + 2]);
+
 			unplaced_glyphs.push(UnplacedGlyph {
 				char_code,
 				bitmap,
-				width: ft_bitmap.width as f32,
-				height: ft_bitmap.rows as f32,
-				bearing_x: ft_glyph.bitmap_left as f32,
-				bearing_y: -ft_glyph.bitmap_top as f32,
-				advance: (ft_glyph.advance.x / 64) as f32
+				width: ft_bitmap.width as f32 + 2.0,
+				height: ft_bitmap.rows as f32 + 2.0,
+				bearing_x: ft_glyph.bitmap_left as f32 - 1.0,
+				bearing_y: -ft_glyph.bitmap_top as f32 - 1.0,
+				advance: Self::glyph_advance(&ft_glyph)
 			});
 		}
 
 		(space_advance, unplaced_glyphs)
 	}
 
-	fn create_atlas(unplaced_glyphs: Vec<UnplacedGlyph>) -> (Vec<Vec<u8>>, Vec<Glyph>) {
-		let mut unplaced_glyphs_sorted: Vec<&UnplacedGlyph> = unplaced_glyphs.iter().collect();
-		unplaced_glyphs_sorted.sort_unstable_by_key(|g| -g.width as isize * g.height as isize);
+	// The unhinted advance so text positioned at sub pixels keeps the designed spacing, the hinted one is used by fonts which
+	// only have bitmaps since they have no outlines to measure
+	fn glyph_advance(ft_glyph: &FT_GlyphSlotRec) -> f32 {
+		if ft_glyph.linearHoriAdvance > 0 {
+			ft_glyph.linearHoriAdvance as f32 / 65536.0
+		}
+		else {
+			ft_glyph.advance.x as f32 / 64.0
+		}
+	}
+
+	// Freetype's colors are premultiplied by the alpha but the text is blended with the alpha afterwards
+	fn unpremultiply(texel: [u8; 4]) -> [u8; 4] {
+		let alpha = texel[3] as u32;
+
+		if alpha == 0 {
+			return [0, 0, 0, 0];
+		}
+
+		let channel = |value: u8| (value as u32 * 255 / alpha).min(255) as u8;
+		[channel(texel[0]), channel(texel[1]), channel(texel[2]), texel[3]]
+	}
+
+	// Returns the atlas width, height and texels with texel size bytes each
+	fn create_atlas(unplaced_glyphs: Vec<UnplacedGlyph>, color: bool) -> (usize, usize, Vec<u8>, Vec<Glyph>) {
+		let sizes: Vec<(usize, usize)> = unplaced_glyphs.iter().map(|g| (g.width as usize, g.height as usize)).collect();
+		let (atlas_width, atlas_height, positions) = AtlasPacker::pack(&sizes);
+		let texel_size = Self::texel_size(color);
 
+		// The unused regions are zero
+		let mut atlas = vec![0; atlas_width * atlas_height * texel_size];
 		let mut placed_glyphs: Vec<Glyph> = Vec::with_capacity(unplaced_glyphs.len());
-		let mut atlas: Vec<Vec<Option<u8>>> = Vec::new();
-
-		'glyph_loop: for unplaced_glyph in unplaced_glyphs_sorted {
-			let atlas_height = atlas.len();
-			let atlas_width = if atlas_height == 0 { 0 } else { atlas[0].len() };
-			
-			let glyph_height = unplaced_glyph.height as usize;
-			let glyph_width = unplaced_glyph.width as usize;
-			
-			let atlas_row_bound = atlas_height.saturating_sub(glyph_height - 1);
-			let atlas_col_bound = atlas_width.saturating_sub(glyph_width - 1);
-
-			for atlas_row_index in 0..atlas_row_bound {
-				'atlas_col_loop: for atlas_col_index in 0..atlas_col_bound {
-
-					for glyph_row_index in 0..glyph_height {
-						for glyph_col_index in 0..glyph_width {
-							let texel = atlas[atlas_row_index + glyph_row_index][atlas_col_index + glyph_col_index];
-
-							if texel.is_some() {
-								// Glyph cannot fit here, move on to the next position
-								continue 'atlas_col_loop;
-							}
-						}
-					}
 
-					// Glyph can fit here
-					placed_glyphs.push(Self::place_glyph(&mut atlas, atlas_row_index, atlas_col_index, unplaced_glyph));
-					continue 'glyph_loop;
+		for (unplaced_glyph, (x, y)) in unplaced_glyphs.iter().zip(positions) {
+			for (row, bitmap_row) in unplaced_glyph.bitmap.iter().enumerate() {
+				for (col, texel) in bitmap_row.iter().enumerate() {
+					let index = ((y + row) * atlas_width + x + col) * texel_size;
+					atlas[index..index + texel_size].copy_from_slice(&texel[..texel_size]);
 				}
 			}
 
-			// Glyph cannot fit anywhere, expand atlas in shorter direction and place the glyph
-			let vertical_expansion;
-			let horizontal_expansion;
-			let pos_row;
-			let pos_col;
-
-			if atlas_width + glyph_width > atlas_height + glyph_height {
-				vertical_expansion = glyph_height;
-				horizontal_expansion = glyph_width.saturating_sub(atlas_width);
-				pos_row = atlas_height;
-				pos_col = 0;
-			}
-			else {
-				vertical_expansion = glyph_height.saturating_sub(atlas_height);
-				horizontal_expansion = glyph_width;
-				pos_row = 0;
-				pos_col = atlas_width;
-			}
-
-			Self::expand_atlas(&mut atlas, vertical_expansion, horizontal_expansion);
-			placed_glyphs.push(Self::place_glyph(&mut atlas, pos_row, pos_col, unplaced_glyph));
+			placed_glyphs.push(Glyph {
+				char_code: unplaced_glyph.char_code,
+				position_x: x as f32,
+				position_y: y as f32,
+				width: unplaced_glyph.width,
+				height: unplaced_glyph.height,
+				bearing_x: unplaced_glyph.bearing_x,
+				bearing_y: unplaced_glyph.bearing_y,
+				advance: unplaced_glyph.advance
+			});
 		}
 
-		// Zero out the unused regions
-		let atlas_height = atlas.len();
-		let atlas_width = atlas[0].len();
-		let mut atlas_final = Vec::with_capacity(atlas_height);
-		
-		for row in atlas {
-			let mut row_final = Vec::with_capacity(atlas_width);
+		placed_glyphs.sort_unstable_by_key(|g| g.char_code);
 
-			for texel in row {
-				row_final.push(if let Some(dist) = texel { dist } else { 0 });
-			}
+		(atlas_width, atlas_height, atlas, placed_glyphs)
+	}
+
+	fn fnt_bytes(data: &FontData, source_hash: u64) -> Vec<u8> {
+		let atlas_size = data.atlas.len();
+		let atlas_padding_size = (4 - atlas_size % 4) % 4;
+		let glyph_count = data.glyphs.len();
+		let flags = if data.color { COLOR_ATLAS_FLAG | PADDED_GLYPHS_FLAG | SOURCE_HASH_FLAG } else { PADDED_GLYPHS_FLAG | SOURCE_HASH_FLAG };
+
+		let mut buffer: Vec<u8> = Vec::with_capacity(28 + atlas_size + atlas_padding_size + 32 * glyph_count);
+
+		buffer.extend_from_slice(&(data.atlas_width as u32).to_le_bytes());
+		buffer.extend_from_slice(&(data.atlas_height as u32).to_le_bytes());
+		buffer.extend_from_slice(&flags.to_le_bytes());
+		buffer.extend_from_slice(&data.atlas);
+		buffer.extend_from_slice(&vec![0u8; atlas_padding_size]);
+		buffer.extend_from_slice(&data.space_advance.to_le_bytes());
+		buffer.extend_from_slice(&(glyph_count as u32).to_le_bytes());
 
-			atlas_final.push(row_final);
+		for glyph in &data.glyphs {
+			buffer.extend_from_slice(&glyph.char_code.to_le_bytes());
+			buffer.extend_from_slice(&glyph.position_x.to_le_bytes());
+			buffer.extend_from_slice(&glyph.position_y.to_le_bytes());
+			buffer.extend_from_slice(&glyph.width.to_le_bytes());
+			buffer.extend_from_slice(&glyph.height.to_le_bytes());
+			buffer.extend_from_slice(&glyph.bearing_x.to_le_bytes());
+			buffer.extend_from_slice(&glyph.bearing_y.to_le_bytes());
+			buffer.extend_from_slice(&glyph.advance.to_le_bytes());
 		}
 
-		placed_glyphs.sort_unstable_by_key(|g| g.char_code);
+		buffer.extend_from_slice(&source_hash.to_le_bytes());
+		buffer
+	}
 
-		(atlas_final, placed_glyphs)
+	fn save_fnt(path: &str, data: &FontData, source_hash: u64) -> io::Result<()> {
+		if let Some(directory) = path::Path::new(path).parent() {
+			fs::create_dir_all(directory)?;
+		}
+
+		fs::write(path, Self::fnt_bytes(data, source_hash))
 	}
 
-	fn place_glyph(atlas: &mut Vec<Vec<Option<u8>>>, atlas_row: usize, atlas_col: usize, unplaced_glyph: &UnplacedGlyph) -> Glyph {
-		let glyph_height = unplaced_glyph.height as usize;
-		let glyph_width = unplaced_glyph.width as usize;
-	
-		for glyph_row in 0..glyph_height {
-			for glyph_col in 0..glyph_width {
-				atlas[atlas_row + glyph_row][atlas_col + glyph_col] = Some(unplaced_glyph.bitmap[glyph_row][glyph_col]);
+	// Layout: atlas width, height and flags as u32s, the atlas padded to 4 bytes, the space advance, the glyph count then 8
+	// values per glyph, then the source hash as a u64 when its flag is set. The atlas has 4 bytes per texel when the color flag
+	// is set and 1 otherwise. Everything is checked so a truncated or corrupted file is an error instead of a panic or a bad
+	// allocation.
+	pub fn parse_fnt(bytes: &[u8]) -> Result<FntContents<'_>, FntError> {
+		let mut reader = BinaryReader::new(bytes);
+
+		let atlas_width = reader.read_u32()? as usize;
+		let atlas_height = reader.read_u32()? as usize;
+		let flags = reader.read_u32()?;
+
+		if flags & !(COLOR_ATLAS_FLAG | PADDED_GLYPHS_FLAG | SOURCE_HASH_FLAG) != 0 {
+			return Err(FntError::InvalidFlags { flags });
+		}
+
+		if flags & PADDED_GLYPHS_FLAG == 0 {
+			return Err(FntError::Outdated);
+		}
+
+		let color = flags & COLOR_ATLAS_FLAG != 0;
+
+		let atlas_size = match atlas_width.checked_mul(atlas_height).and_then(|size| size.checked_mul(Self::texel_size(color))) {
+			Some(size) if size > 0 => size,
+			_ => return Err(FntError::InvalidAtlasSize { width: atlas_width, height: atlas_height })
+		};
+
+		let atlas = reader.read_bytes(atlas_size)?;
+		reader.skip((4 - atlas_size % 4) % 4)?;
+
+		let space_advance = reader.read_f32()?;
+		let glyph_count = reader.read_u32()? as usize;
+
+		// Don't trust the count for the allocation, the reader catches a count larger than the data
+		let mut glyphs: Vec<Glyph> = Vec::with_capacity(glyph_count.min(reader.remaining() / 32));
+
+		for _ in 0..glyph_count {
+			let glyph = Glyph {
+				char_code: reader.read_u32()?,
+				position_x: reader.read_f32()?,
+				position_y: reader.read_f32()?,
+				width: reader.read_f32()?,
+				height: reader.read_f32()?,
+				bearing_x: reader.read_f32()?,
+				bearing_y: reader.read_f32()?,
+				advance: reader.read_f32()?
+			};
+
+			let values = [glyph.position_x, glyph.position_y, glyph.width, glyph.height, glyph.bearing_x, glyph.bearing_y, glyph.advance];
+			let in_atlas = glyph.position_x >= 0.0 && glyph.position_y >= 0.0 && glyph.width >= 0.0 && glyph.height >= 0.0
+				&& glyph.position_x + glyph.width <= atlas_width as f32
+				&& glyph.position_y + glyph.height <= atlas_height as f32;
+
+			if !in_atlas || values.iter().any(|value| !value.is_finite()) {
+				return Err(FntError::InvalidGlyph { char_code: glyph.char_code });
 			}
+
+			glyphs.push(glyph);
 		}
 
-		Glyph {
-			char_code: unplaced_glyph.char_code,
-			position_x: atlas_col as f32,
-			position_y: atlas_row as f32,
-			width: unplaced_glyph.width,
-			height: unplaced_glyph.height,
-			bearing_x: unplaced_glyph.bearing_x,
-			bearing_y: unplaced_glyph.bearing_y,
-			advance: unplaced_glyph.advance
+		let source_hash = if flags & SOURCE_HASH_FLAG != 0 { Some(reader.read_u64()?) } else { None };
+
+		if !space_advance.is_finite() {
+			return Err(FntError::InvalidSpaceAdvance);
+		}
+
+		if reader.remaining() > 0 {
+			return Err(FntError::TrailingBytes { count: reader.remaining() });
 		}
+
+		Ok(FntContents {
+			atlas_width,
+			atlas_height,
+			color,
+			atlas,
+			space_advance,
+			glyphs,
+			source_hash
+		})
 	}
-	
-	fn expand_atlas(atlas: &mut Vec<Vec<Option<u8>>>, vertical_len: usize, horizontal_len: usize) {
-		let atlas_width = if atlas.is_empty() { 0 } else { atlas[0].len() };
-		let additional_rows = vec![vec![None; atlas_width]; vertical_len];
-		atlas.extend_from_slice(&additional_rows);
-	
-		let additional_cols = vec![None; horizontal_len];
-		for row in atlas {
-			row.extend_from_slice(&additional_cols);
+
+	fn advance(&self, c: char) -> f32 {
+		if c == ' ' {
+			return self.space_advance;
+		}
+
+		match self.glyphs.binary_search_by_key(&(c as u32), |g| g.char_code) {
+			Ok(index) => self.glyphs[index].advance,
+			Err(_) => 0.0
 		}
 	}
 
-	fn save_fnt(path: &str, atlas: &[Vec<u8>], space_advance: f32, glyphs: &[Glyph]) {
-		let atlas_width = atlas[0].len();
-		let atlas_height = atlas.len();
-		let atlas_padding_size = (4 - (atlas_width * atlas_height) % 4) % 4;
-		let glyph_count = glyphs.len();
+	// The width, height and baseline of the string at the size in pixels without generating any geometry. The height spans the
+	// highest and lowest points of any glyph in the font so it doesn't depend on the string and the baseline is measured down
+	// from the top.
+	pub fn measure(&self, string: &str, size: f32) -> (f32, f32, f32) {
+		let scale = size / self.size as f32;
+		let width: f32 = string.chars().map(|c| self.advance(c)).sum();
 
-		let mut buffer: Vec<u8> = Vec::with_capacity(16 + atlas_width * atlas_height + atlas_padding_size + 32 * glyph_count);
+		let ascent = self.glyphs.iter().map(|g| -g.bearing_y).fold(0.0, f32::max);
+		let descent = self.glyphs.iter().map(|g| g.bearing_y + g.height).fold(0.0, f32::max);
 
-		buffer.extend_from_slice(&(atlas_width as u32).to_le_bytes());
-		buffer.extend_from_slice(&(atlas_height as u32).to_le_bytes());
+		(width * scale, (ascent + descent) * scale, ascent * scale)
+	}
 
-		for row in atlas {
-			buffer.extend_from_slice(&row);
+	// Wraps the string at spaces so each line fits in the max width at the size in pixels. Returns the byte index each line
+	// starts at and the line's width, a word wider than the max width gets a line of its own.
+	pub fn line_breaks(&self, string: &str, size: f32, max_width: f32) -> Vec<(usize, f32)> {
+		let scale = size / self.size as f32;
+		let space_width = self.space_advance * scale;
+		let mut lines = Vec::new();
+		let mut line_start = 0;
+		let mut line_width: Option<f32> = None;
+		let mut word_start = 0;
+
+		for word in string.split(' ') {
+			let word_width = word.chars().map(|c| self.advance(c)).sum::<f32>() * scale;
+
+			line_width = match line_width {
+				Some(width) if width + space_width + word_width > max_width => {
+					lines.push((line_start, width));
+					line_start = word_start;
+					Some(word_width)
+				},
+				Some(width) => Some(width + space_width + word_width),
+				None => Some(word_width)
+			};
+
+			word_start += word.len() + 1;
 		}
 
-		buffer.extend_from_slice(&vec![0u8; atlas_padding_size]);
-		buffer.extend_from_slice(&space_advance.to_le_bytes());
-		buffer.extend_from_slice(&(glyphs.len() as u32).to_le_bytes());
+		lines.push((line_start, line_width.unwrap()));
+		lines
+	}
 
-		for glyph in glyphs {
-			buffer.extend_from_slice(&glyph.char_code.to_le_bytes());
-			buffer.extend_from_slice(&glyph.position_x.to_le_bytes());
-			buffer.extend_from_slice(&glyph.position_y.to_le_bytes());
-			buffer.extend_from_slice(&glyph.width.to_le_bytes());
-			buffer.extend_from_slice(&glyph.height.to_le_bytes());
-			buffer.extend_from_slice(&glyph.bearing_x.to_le_bytes());
-			buffer.extend_from_slice(&glyph.bearing_y.to_le_bytes());
-			buffer.extend_from_slice(&glyph.advance.to_le_bytes());
+	// Reads the atlas back from the font file, fails if the file changed since the font was loaded
+	pub(crate) fn load_atlas(&self, vfs: &Vfs) -> Result<Vec<u8>, FntError> {
+		if let Some(atlas) = &self.generated_atlas {
+			return Ok(atlas.clone());
 		}
 
-		fs::create_dir_all("target/fonts").unwrap();
-		let mut file = fs::File::create(path).unwrap();
-		file.write_all(&buffer).unwrap();
+		let bytes = vfs.read(&self.fnt_path)?;
+		let fnt = Self::parse_fnt(&bytes)?;
+
+		if fnt.atlas_width != self.atlas_width || fnt.atlas_height != self.atlas_height || fnt.color != self.color {
+			return Err(FntError::AtlasSizeChanged);
+		}
+
+		Ok(fnt.atlas.to_vec())
 	}
+}
 
-	fn load_fnt(mut file: fs::File) -> (usize, usize, f32, Vec<Glyph>) {
-		let mut bytes = [0u8; 4];
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn glyph(char_code: u32, position_x: f32, position_y: f32) -> Glyph {
+		Glyph {
+			char_code,
+			position_x,
+			position_y,
+			width: 2.0,
+			height: 2.0,
+			bearing_x: 0.0,
+			bearing_y: -2.0,
+			advance: 3.0
+		}
+	}
 
-		file.read_exact(&mut bytes).unwrap();
-		let atlas_width = u32::from_le_bytes(bytes) as usize;
+	#[test]
+	fn measure() {
+		let glyph = |char_code, bearing_y, height| Glyph {
+			char_code,
+			position_x: 0.0,
+			position_y: 0.0,
+			width: 8.0,
+			height,
+			bearing_x: 1.0,
+			bearing_y,
+			advance: 10.0
+		};
 
-		file.read_exact(&mut bytes).unwrap();
-		let atlas_height = u32::from_le_bytes(bytes) as usize;
+		let font = Font {
+			fnt_path: String::new(),
+			size: 20,
+			color: false,
+			atlas_width: 8,
+			atlas_height: 20,
+			space_advance: 5.0,
+			glyphs: vec![glyph('a' as u32, -10.0, 10.0), glyph('g' as u32, -10.0, 14.0), glyph('h' as u32, -15.0, 15.0)],
+			generated_atlas: None,
+			submission_info: None
+		};
 
-		let atlas_padding_size = (4 - (atlas_width * atlas_height) % 4) % 4;
+		// The height and baseline come from the tallest and lowest glyphs even when they're not in the string
+		assert_eq!(font.measure("aa a", 20.0), (35.0, 19.0, 15.0));
+		assert_eq!(font.measure("", 40.0), (0.0, 38.0, 30.0));
 
-		file.seek(io::SeekFrom::Current((atlas_width * atlas_height + atlas_padding_size) as i64)).unwrap();
+		assert_eq!(font.line_breaks("aa a", 20.0, 100.0), vec![(0, 35.0)]);
+		assert_eq!(font.line_breaks("aa ga hhhh a", 20.0, 50.0), vec![(0, 45.0), (6, 40.0), (11, 10.0)]);
+		assert_eq!(font.line_breaks("", 20.0, 50.0), vec![(0, 0.0)]);
+	}
 
-		file.read_exact(&mut bytes).unwrap();
-		let space_advance = f32::from_le_bytes(bytes);
+	fn fnt_bytes(atlas_width: usize, atlas: Vec<u8>, color: bool, glyphs: Vec<Glyph>) -> Vec<u8> {
+		let data = FontData {
+			size: 16,
+			atlas_width,
+			atlas_height: atlas.len() / atlas_width / Font::texel_size(color),
+			color,
+			atlas,
+			space_advance: 4.0,
+			glyphs
+		};
 
-		file.read_exact(&mut bytes).unwrap();
-		let glyph_count = u32::from_le_bytes(bytes) as usize;
+		Font::fnt_bytes(&data, 0x0123_4567_89ab_cdef)
+	}
 
-		let mut buffer: Vec<u8> = Vec::with_capacity(glyph_count * 32);
-		file.read_to_end(&mut buffer).unwrap();
+	fn valid_fnt() -> Vec<u8> {
+		fnt_bytes(3, vec![1, 2, 3, 4, 5, 6, 7, 8, 9], false, vec![glyph(65, 0.0, 0.0), glyph(66, 1.0, 1.0)])
+	}
 
-		let mut glyphs: Vec<Glyph> = Vec::with_capacity(glyph_count);
-		let get_bytes_at = |offset| buffer[offset..offset + 4].try_into().unwrap();
+	#[test]
+	fn parse_valid() {
+		let bytes = valid_fnt();
+		let fnt = Font::parse_fnt(&bytes).unwrap();
+
+		assert_eq!((fnt.atlas_width, fnt.atlas_height, fnt.color), (3, 3, false));
+		assert_eq!(fnt.atlas, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+		assert_eq!(fnt.space_advance, 4.0);
+		assert_eq!(fnt.glyphs.len(), 2);
+		assert_eq!(fnt.glyphs[1].char_code, 66);
+		assert_eq!(fnt.glyphs[1].position_x, 1.0);
+		assert_eq!(fnt.source_hash, Some(0x0123_4567_89ab_cdef));
+	}
 
-		for glyph_index in 0..glyph_count {
-			let offset = glyph_index * 32;
+	#[test]
+	fn source_hashes() {
+		// Files from before the hash was written load without one
+		let mut bytes = valid_fnt();
+		bytes.truncate(bytes.len() - 8);
+		bytes[8..12].copy_from_slice(&PADDED_GLYPHS_FLAG.to_le_bytes());
+		assert_eq!(Font::parse_fnt(&bytes).unwrap().source_hash, None);
+
+		let options = FontOptions::color(32, "AB");
+		let hash = Font::source_hash(&[1, 2, 3], &options);
+		assert_eq!(hash, Font::source_hash(&[1, 2, 3], &options.clone()));
+		assert_ne!(hash, Font::source_hash(&[1, 2, 4], &options));
+		assert_ne!(hash, Font::source_hash(&[1, 2, 3], &FontOptions::color(16, "AB")));
+		assert_ne!(hash, Font::source_hash(&[1, 2, 3], &FontOptions::color(32, "A")));
+		assert_ne!(hash, Font::source_hash(&[1, 2, 3], &FontOptions { color: false, ..options.clone() }));
+	}
 
-			let char_code = u32::from_le_bytes(get_bytes_at(offset));
-			let position_x = f32::from_le_bytes(get_bytes_at(offset + 4));
-			let position_y = f32::from_le_bytes(get_bytes_at(offset + 8));
-			let width = f32::from_le_bytes(get_bytes_at(offset + 12));
-			let height = f32::from_le_bytes(get_bytes_at(offset + 16));
-			let bearing_x = f32::from_le_bytes(get_bytes_at(offset + 20));
-			let bearing_y = f32::from_le_bytes(get_bytes_at(offset + 24));
-			let advance = f32::from_le_bytes(get_bytes_at(offset + 28));
+	#[test]
+	fn every_truncation_is_an_error() {
+		let bytes = valid_fnt();
 
-			glyphs.push(Glyph {
-				char_code,
-				position_x,
-				position_y,
-				width,
-				height,
-				bearing_x,
-				bearing_y,
-				advance
-			});
+		for length in 0..bytes.len() {
+			assert!(Font::parse_fnt(&bytes[..length]).is_err(), "Truncating to {} bytes was not detected", length);
 		}
+	}
+
+	#[test]
+	fn trailing_bytes_are_an_error() {
+		let mut bytes = valid_fnt();
+		bytes.push(0);
+
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::TrailingBytes { count: 1 })));
+	}
+
+	#[test]
+	fn huge_sizes_are_rejected_without_allocating() {
+		let mut bytes = valid_fnt();
+		bytes[0..8].copy_from_slice(&[0xff; 8]);
+		assert!(Font::parse_fnt(&bytes).is_err());
+
+		// The glyph count follows the 3x3 atlas and its 3 bytes of padding
+		let mut bytes = valid_fnt();
+		bytes[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::Read(_))));
+
+		let mut bytes = valid_fnt();
+		bytes[0..4].copy_from_slice(&0u32.to_le_bytes());
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::InvalidAtlasSize { width: 0, height: 3 })));
+	}
+
+	#[test]
+	fn color_atlases() {
+		let bytes = fnt_bytes(2, [255, 0, 0, 255, 0, 0, 255, 128].repeat(2), true, vec![glyph(65, 0.0, 0.0)]);
+		let fnt = Font::parse_fnt(&bytes).unwrap();
+		assert_eq!((fnt.atlas_width, fnt.atlas_height, fnt.color), (2, 2, true));
+		assert_eq!(fnt.atlas.len(), 16);
+
+		let mut bytes = bytes;
+		bytes[8..12].copy_from_slice(&10u32.to_le_bytes());
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::InvalidFlags { flags: 10 })));
+
+		bytes[8..12].copy_from_slice(&COLOR_ATLAS_FLAG.to_le_bytes());
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::Outdated)));
+
+		assert_eq!(Font::unpremultiply([64, 0, 128, 128]), [127, 0, 255, 128]);
+		assert_eq!(Font::unpremultiply([10, 10, 10, 0]), [0, 0, 0, 0]);
+	}
 
-		(atlas_width, atlas_height, space_advance, glyphs)
+	#[test]
+	fn glyphs_outside_the_atlas_are_rejected() {
+		let bytes = fnt_bytes(3, vec![0; 9], false, vec![glyph(65, 2.0, 0.0)]);
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::InvalidGlyph { char_code: 65 })));
+
+		let bytes = fnt_bytes(3, vec![0; 9], false, vec![glyph(65, f32::NAN, 0.0)]);
+		assert!(matches!(Font::parse_fnt(&bytes), Err(FntError::InvalidGlyph { char_code: 65 })));
+	}
+
+	// Corrupts random bytes of a valid file many times over, parsing must never panic and anything accepted must be usable
+	#[test]
+	fn random_corruption_never_panics() {
+		let valid = valid_fnt();
+		let mut state: u32 = 0x9e37_79b9;
+
+		let mut next = || {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state
+		};
+
+		for _ in 0..10_000 {
+			let mut bytes = valid.clone();
+			let corruption_count = 1 + next() % 4;
+
+			for _ in 0..corruption_count {
+				let index = next() as usize % bytes.len();
+				bytes[index] = next() as u8;
+			}
+
+			let length = if next() % 4 == 0 { next() as usize % bytes.len() } else { bytes.len() };
+
+			if let Ok(fnt) = Font::parse_fnt(&bytes[..length]) {
+				assert_eq!(fnt.atlas.len(), fnt.atlas_width * fnt.atlas_height * Font::texel_size(fnt.color));
+
+				for glyph in &fnt.glyphs {
+					assert!(glyph.position_x + glyph.width <= fnt.atlas_width as f32);
+					assert!(glyph.position_y + glyph.height <= fnt.atlas_height as f32);
+				}
+			}
+		}
 	}
 }
\ No newline at end of file
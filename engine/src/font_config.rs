@@ -0,0 +1,197 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use crate::{Font, FontOptions, font::FntError, settings::Value};
+
+#[derive(Debug)]
+pub enum FontConfigError {
+	Io { path: PathBuf, error: io::Error },
+	Parse { line: usize, reason: String }
+}
+
+impl fmt::Display for FontConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read font config {}: {}", path.display(), error),
+			Self::Parse { line, reason } => write!(f, "Font config line {} is invalid: {}", line, reason)
+		}
+	}
+}
+
+impl Error for FontConfigError {}
+
+// One font file to generate
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontOutput {
+	pub name: String,
+	pub font_path: String,
+	pub options: FontOptions,
+	pub fnt_path: String
+}
+
+impl FontOutput {
+	// Returns whether the font file was generated, it isn't when it's already up to date
+	pub fn generate(&self) -> Result<bool, FntError> {
+		Font::generate(&self.font_path, &self.options, &self.fnt_path)
+	}
+}
+
+// The font files an asset build generates ahead of time, in the same small TOML subset as the settings. Each table is one
+// font file:
+//
+// [roboto32]
+// font = "game/res/roboto.ttf"
+// size = 32
+//
+// [emoji64]
+// font = "game/res/emoji.ttf"
+// size = 64
+// color = true
+// chars = "😀😂👍"
+// output = "target/fonts/emoji64_color.fnt"
+//
+// Coverage fonts have the printable ASCII characters unless chars is given and color fonts need chars. The output defaults to
+// where Font::new and Font::new_color generate the file, which development builds load instead of generating it again.
+pub struct FontConfig {
+	pub outputs: Vec<FontOutput>
+}
+
+// A table while it's being read, the font and size are required
+struct PartialOutput {
+	name: String,
+	line: usize,
+	font_path: Option<String>,
+	size: Option<u32>,
+	color: bool,
+	chars: Option<String>,
+	fnt_path: Option<String>
+}
+
+impl FontConfig {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, FontConfigError> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).map_err(|error| FontConfigError::Io { path: path.to_path_buf(), error })?;
+		Self::parse(&text)
+	}
+
+	pub fn parse(text: &str) -> Result<Self, FontConfigError> {
+		let mut partial_outputs: Vec<PartialOutput> = Vec::new();
+
+		for (index, line) in text.lines().enumerate() {
+			let line = line.trim();
+			let error = |reason: String| FontConfigError::Parse { line: index + 1, reason };
+
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			if let Some(name) = line.strip_prefix('[') {
+				let name = name.strip_suffix(']').ok_or_else(|| error(String::from("the table name isn't closed")))?.trim();
+
+				if partial_outputs.iter().any(|output| output.name == name) {
+					return Err(error(format!("{} is already defined", name)));
+				}
+
+				partial_outputs.push(PartialOutput {
+					name: name.to_string(),
+					line: index + 1,
+					font_path: None,
+					size: None,
+					color: false,
+					chars: None,
+					fnt_path: None
+				});
+
+				continue;
+			}
+
+			let (name, value) = line.split_once('=').ok_or_else(|| error(String::from("expected a key and value separated by =")))?;
+			let value = Value::parse(value.trim()).ok_or_else(|| error(format!("{} is not a boolean, number or string", value.trim())))?;
+			let name = name.trim();
+			let output = partial_outputs.last_mut().ok_or_else(|| error(format!("{} is not in a table", name)))?;
+			let table = output.name.clone();
+			let mismatch = |expected: &str| error(format!("{}.{} must be {}", table, name, expected));
+
+			match name {
+				"font" => output.font_path = Some(value.as_str().ok_or_else(|| mismatch("a string"))?.to_string()),
+				"size" => output.size = Some(value.as_u32().ok_or_else(|| mismatch("a positive whole number"))?),
+				"color" => output.color = value.as_bool().ok_or_else(|| mismatch("true or false"))?,
+				"chars" => output.chars = Some(value.as_str().ok_or_else(|| mismatch("a string"))?.to_string()),
+				"output" => output.fnt_path = Some(value.as_str().ok_or_else(|| mismatch("a string"))?.to_string()),
+				// Unlike settings a typo here would silently generate the wrong font
+				_ => return Err(error(format!("{}.{} is not a font setting", table, name)))
+			}
+		}
+
+		let outputs = partial_outputs.into_iter().map(|PartialOutput { name, line, font_path, size, color, chars, fnt_path }| {
+			let error = |reason: &str| FontConfigError::Parse { line, reason: format!("{} {}", name, reason) };
+			let font_path = font_path.ok_or_else(|| error("has no font"))?;
+			let size = size.ok_or_else(|| error("has no size"))?;
+
+			let char_codes = match (chars, color) {
+				(Some(chars), _) => Font::char_codes(&chars),
+				(None, false) => FontOptions::new(size).char_codes,
+				(None, true) => return Err(error("is a color font without chars"))
+			};
+
+			if char_codes.is_empty() {
+				return Err(error("has no chars"));
+			}
+
+			let fnt_path = fnt_path.unwrap_or_else(|| Font::fnt_path(&font_path, size, color));
+
+			Ok(FontOutput {
+				name,
+				font_path,
+				options: FontOptions { size, char_codes, color },
+				fnt_path
+			})
+		}).collect::<Result<Vec<FontOutput>, FontConfigError>>()?;
+
+		Ok(Self {
+			outputs
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_outputs() {
+		let config = FontConfig::parse("
+			# Fonts for the menus
+			[roboto32]
+			font = \"game/res/roboto.ttf\"
+			size = 32
+
+			[digits]
+			font = \"game/res/roboto.ttf\"
+			size = 64
+			chars = \"9 8 7 0\"
+			output = \"target/fonts/digits.fnt\" # only the timer
+		").unwrap();
+
+		assert_eq!(config.outputs.len(), 2);
+		assert_eq!(config.outputs[0].fnt_path, "target/fonts/roboto32.fnt");
+		assert_eq!(config.outputs[0].options, FontOptions::new(32));
+		assert_eq!(config.outputs[1].options.size, 64);
+		assert_eq!(config.outputs[1].options.char_codes, vec![48, 55, 56, 57]);
+		assert!(!config.outputs[1].options.color);
+		assert_eq!(config.outputs[1].fnt_path, "target/fonts/digits.fnt");
+	}
+
+	#[test]
+	fn reports_invalid_lines() {
+		let error = |text: &str| match FontConfig::parse(text) {
+			Err(FontConfigError::Parse { line, .. }) => line,
+			_ => panic!("{} should be invalid", text)
+		};
+
+		assert_eq!(error("size = 32"), 1);
+		assert_eq!(error("[a]\nfont = \"a.ttf\"\nsize = 0"), 3);
+		assert_eq!(error("[a]\nfont = \"a.ttf\"\nsize = 32\nsise = 32"), 4);
+		assert_eq!(error("[a]\nsize = 32\n\n[b]"), 1);
+		assert_eq!(error("[a]\nfont = \"a.ttf\"\nsize = 32\ncolor = true"), 1);
+		assert_eq!(error("[a]\nfont = \"a.ttf\"\nsize = 32\n[a]"), 4);
+	}
+}
\ No newline at end of file
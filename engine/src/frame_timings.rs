@@ -0,0 +1,189 @@
+use std::{fmt::Write, time::Duration};
+
+pub const FRAME_PHASE_COUNT: usize = 5;
+
+// A frame janks when it takes this many times longer than the median frame
+const JANK_FACTOR: f32 = 2.0;
+
+// Frames aren't checked for jank until there are enough before them for the median to mean something
+const MIN_JANK_HISTORY: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FramePhase {
+	EventPoll,
+	Update,
+	Record,
+	Submit,
+	PresentWait
+}
+
+impl FramePhase {
+	pub const ALL: [Self; FRAME_PHASE_COUNT] = [Self::EventPoll, Self::Update, Self::Record, Self::Submit, Self::PresentWait];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::EventPoll => "event poll",
+			Self::Update => "update",
+			Self::Record => "record",
+			Self::Submit => "submit",
+			Self::PresentWait => "present wait"
+		}
+	}
+}
+
+// How long the CPU spends in each phase of the recent frames. The phases of a frame are added as they finish then the frame
+// is ended which puts it in the history, only the latest frames up to the capacity are kept.
+pub struct FrameTimings {
+	frames: Vec<[Duration; FRAME_PHASE_COUNT]>,
+	capacity: usize,
+	next_frame_index: usize,
+	current_frame: [Duration; FRAME_PHASE_COUNT],
+	jank_frame_count: usize
+}
+
+impl FrameTimings {
+	pub fn new(capacity: usize) -> Self {
+		assert!(capacity > 0, "Frame timings need room for at least one frame");
+
+		Self {
+			frames: Vec::with_capacity(capacity),
+			capacity,
+			next_frame_index: 0,
+			current_frame: [Duration::ZERO; FRAME_PHASE_COUNT],
+			jank_frame_count: 0
+		}
+	}
+
+	// Adds to the phase's time in the current frame so a phase may be added more than once
+	pub fn add(&mut self, phase: FramePhase, duration: Duration) {
+		self.current_frame[phase as usize] += duration;
+	}
+
+	// Puts the current frame in the history and returns whether it janked
+	pub fn end_frame(&mut self) -> bool {
+		let total: Duration = self.current_frame.iter().sum();
+		let janked = self.frames.len() >= MIN_JANK_HISTORY && total > self.frame_percentile(50.0).mul_f32(JANK_FACTOR);
+
+		if janked {
+			self.jank_frame_count += 1;
+		}
+
+		if self.frames.len() < self.capacity {
+			self.frames.push(self.current_frame);
+		}
+		else {
+			self.frames[self.next_frame_index] = self.current_frame;
+		}
+
+		self.next_frame_index = (self.next_frame_index + 1) % self.capacity;
+		self.current_frame = [Duration::ZERO; FRAME_PHASE_COUNT];
+		janked
+	}
+
+	pub fn frame_count(&self) -> usize {
+		self.frames.len()
+	}
+
+	// The number of janked frames since the timings were created
+	pub fn jank_frame_count(&self) -> usize {
+		self.jank_frame_count
+	}
+
+	// The time of the phase which the given percent of the recent frames took at most
+	pub fn percentile(&self, phase: FramePhase, percent: f32) -> Duration {
+		percentile(self.frames.iter().map(|frame| frame[phase as usize]).collect(), percent)
+	}
+
+	// The same over the total time of each frame
+	pub fn frame_percentile(&self, percent: f32) -> Duration {
+		percentile(self.frames.iter().map(|frame| frame.iter().sum()).collect(), percent)
+	}
+
+	// The median and 99th percentile of each phase and the whole frame in milliseconds, meant for an overlay
+	pub fn summary(&self) -> String {
+		let milliseconds = |duration: Duration| duration.as_secs_f32() * 1000.0;
+		let mut summary = String::new();
+
+		for phase in FramePhase::ALL {
+			write!(summary, "{} {:.2}/{:.2}ms  ", phase.name(), milliseconds(self.percentile(phase, 50.0)), milliseconds(self.percentile(phase, 99.0))).unwrap();
+		}
+
+		write!(summary, "frame {:.2}/{:.2}ms  {} janks", milliseconds(self.frame_percentile(50.0)), milliseconds(self.frame_percentile(99.0)), self.jank_frame_count).unwrap();
+		summary
+	}
+}
+
+// Nearest rank percentile, zero when there are no values
+fn percentile(mut values: Vec<Duration>, percent: f32) -> Duration {
+	if values.is_empty() {
+		return Duration::ZERO;
+	}
+
+	values.sort_unstable();
+	let rank = (percent / 100.0 * values.len() as f32).ceil() as usize;
+	values[rank.clamp(1, values.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn milliseconds(milliseconds: u64) -> Duration {
+		Duration::from_millis(milliseconds)
+	}
+
+	#[test]
+	fn nearest_rank_percentile() {
+		let values: Vec<Duration> = [5, 1, 4, 2, 3].iter().map(|m| milliseconds(*m)).collect();
+
+		assert_eq!(percentile(values.clone(), 0.0), milliseconds(1));
+		assert_eq!(percentile(values.clone(), 50.0), milliseconds(3));
+		assert_eq!(percentile(values.clone(), 80.0), milliseconds(4));
+		assert_eq!(percentile(values, 100.0), milliseconds(5));
+		assert_eq!(percentile(vec![], 50.0), Duration::ZERO);
+	}
+
+	#[test]
+	fn phases_are_summed_per_frame() {
+		let mut frame_timings = FrameTimings::new(4);
+		frame_timings.add(FramePhase::Update, milliseconds(2));
+		frame_timings.add(FramePhase::Update, milliseconds(3));
+		frame_timings.add(FramePhase::Record, milliseconds(1));
+		frame_timings.end_frame();
+
+		assert_eq!(frame_timings.percentile(FramePhase::Update, 50.0), milliseconds(5));
+		assert_eq!(frame_timings.percentile(FramePhase::Submit, 50.0), Duration::ZERO);
+		assert_eq!(frame_timings.frame_percentile(50.0), milliseconds(6));
+	}
+
+	#[test]
+	fn only_the_latest_frames_are_kept() {
+		let mut frame_timings = FrameTimings::new(3);
+
+		for frame in 1..=5 {
+			frame_timings.add(FramePhase::Update, milliseconds(frame));
+			frame_timings.end_frame();
+		}
+
+		assert_eq!(frame_timings.frame_count(), 3);
+		assert_eq!(frame_timings.frame_percentile(0.0), milliseconds(3));
+		assert_eq!(frame_timings.frame_percentile(100.0), milliseconds(5));
+	}
+
+	#[test]
+	fn slow_frames_are_jank() {
+		let mut frame_timings = FrameTimings::new(100);
+
+		for _ in 0..MIN_JANK_HISTORY {
+			frame_timings.add(FramePhase::Update, milliseconds(10));
+			assert!(!frame_timings.end_frame());
+		}
+
+		frame_timings.add(FramePhase::Update, milliseconds(15));
+		assert!(!frame_timings.end_frame());
+
+		frame_timings.add(FramePhase::PresentWait, milliseconds(25));
+		assert!(frame_timings.end_frame());
+		assert_eq!(frame_timings.jank_frame_count(), 1);
+	}
+}
\ No newline at end of file
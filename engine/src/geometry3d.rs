@@ -1,4 +1,13 @@
-use crate::math::{Box3, Vector3};
+use std::{collections::HashMap, mem::size_of_val, sync::atomic::{AtomicU64, Ordering}};
+use crate::math::{Box3, Frustum, Matrix4, Vector3, quaternion};
+
+// Every version of geometry data gets a unique id so the renderer can tell when its copy of it is out of date
+static NEXT_GEOMETRY_ID: AtomicU64 = AtomicU64::new(1);
+
+const GRID_MINOR_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const GRID_MAJOR_COLOR: [f32; 4] = [0.4, 0.4, 0.4, 1.0];
+const GRID_X_AXIS_COLOR: [f32; 4] = [0.8, 0.2, 0.2, 1.0];
+const GRID_Z_AXIS_COLOR: [f32; 4] = [0.2, 0.3, 0.8, 1.0];
 
 #[derive(Clone, Copy)]
 pub enum Topology {
@@ -6,10 +15,23 @@ pub enum Topology {
 	Line
 }
 
+// How the vertices are stored on the GPU. Quantized vertices are 12 bytes rather than 24, the position is 4 16 bit values
+// relative to the bounding box, the last unused, and the normal is 2 16 bit values octahedral encoded. The CPU copy of the
+// attributes stays full precision for things like picking. Only triangle geometry can be quantized.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VertexFormat {
+	Float,
+	Quantized
+}
+
 pub(crate) struct SubmissionInfo {
 	pub generation: usize,
 	pub index_array_offset: usize,
-	pub attributes_array_offset: usize
+	pub attributes_array_offset: usize,
+	pub uvs_array_offset: usize,
+	pub uvs2_array_offset: usize,
+	pub occlusion_array_offset: usize,
+	pub colors_array_offset: usize
 }
 
 pub struct Geometry3D {
@@ -17,6 +39,13 @@ pub struct Geometry3D {
 	attributes: Vec<f32>,
 	topology: Topology,
 	bounding_box: Box3,
+	vertex_format: VertexFormat,
+	quantized_attributes: Vec<i16>,
+	uvs: Vec<f32>,
+	uvs2: Vec<f32>,
+	occlusion: Vec<f32>,
+	colors: Vec<f32>,
+	pub(crate) geometry_id: u64,
 	pub(crate) submission_info: Option<SubmissionInfo>
 }
 
@@ -29,10 +58,22 @@ impl Geometry3D {
 			attributes,
 			topology,
 			bounding_box,
+			vertex_format: VertexFormat::Float,
+			quantized_attributes: Vec::new(),
+			uvs: Vec::new(),
+			uvs2: Vec::new(),
+			occlusion: Vec::new(),
+			colors: Vec::new(),
+			geometry_id: NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed),
 			submission_info: None
 		}
 	}
 
+	// The generation of the static geometry submission this geometry was part of, see RenderSystem::static_geometry_status
+	pub fn submission_generation(&self) -> Option<usize> {
+		self.submission_info.as_ref().map(|submission_info| submission_info.generation)
+	}
+
 	pub fn indices(&self) -> &[u16] {
 		&self.indices
 	}
@@ -49,14 +90,272 @@ impl Geometry3D {
 		&self.bounding_box
 	}
 
+	// Two per vertex, or none for geometry without texture coordinates
+	pub fn uvs(&self) -> &[f32] {
+		&self.uvs
+	}
+
+	// UVs are a separate stream from the attributes so materials which don't sample textures keep the smaller layout. Setting
+	// the indices and attributes clears them, so set them after.
+	pub fn set_uvs(&mut self, uvs: Vec<f32>) {
+		self.check_uvs(&uvs);
+		self.uvs = uvs;
+		self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+		self.submission_info = None;
+	}
+
+	// The second UV set, usually where each triangle goes in a lightmap so no two triangles may overlap
+	pub fn uvs2(&self) -> &[f32] {
+		&self.uvs2
+	}
+
+	pub fn set_uvs2(&mut self, uvs2: Vec<f32>) {
+		self.check_uvs(&uvs2);
+		self.uvs2 = uvs2;
+		self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+		self.submission_info = None;
+	}
+
+	// How much of the sky each vertex sees from 0 to 1, baked offline by the OcclusionBaker. Lit materials scale the ambient
+	// light by it, geometry without it is unoccluded.
+	pub fn occlusion(&self) -> &[f32] {
+		&self.occlusion
+	}
+
+	pub fn set_occlusion(&mut self, occlusion: Vec<f32>) {
+		assert!(occlusion.is_empty() || matches!(self.topology, Topology::Triangle), "Only triangle geometry can have occlusion");
+		assert!(occlusion.is_empty() || occlusion.len() == self.attributes.len() / 6, "There must be 1 occlusion value for every vertex");
+		self.occlusion = occlusion;
+		self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+		self.submission_info = None;
+	}
+
+	// Four per vertex, RGBA. Materials read them as masks rather than as a color, foliage uses them to tell how much each
+	// vertex sways in the wind.
+	pub fn colors(&self) -> &[f32] {
+		&self.colors
+	}
+
+	// Line geometry without colors is drawn dark gray
+	pub fn set_colors(&mut self, colors: Vec<f32>) {
+		let vertex_count = match self.topology {
+			Topology::Triangle => self.attributes.len() / 6,
+			Topology::Line => self.attributes.len() / 3
+		};
+
+		assert!(colors.is_empty() || colors.len() == vertex_count * 4, "There must be 4 color values for every vertex");
+		self.colors = colors;
+		self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+		self.submission_info = None;
+	}
+
+	fn check_uvs(&self, uvs: &[f32]) {
+		assert!(uvs.is_empty() || matches!(self.topology, Topology::Triangle), "Only triangle geometry can have UVs");
+		assert!(uvs.is_empty() || uvs.len() == self.attributes.len() / 6 * 2, "There must be 2 UV values for every vertex");
+	}
+
 	pub fn set(&mut self, indices: Vec<u16>, attributes: Vec<f32>, topology: Topology) {
+		assert!(matches!(topology, Topology::Triangle) || self.vertex_format == VertexFormat::Float, "Only triangle geometry can be quantized");
+
 		self.indices = indices;
 		self.attributes = attributes;
 		self.topology = topology;
 		self.bounding_box = Self::calculate_bounding_box(&self.attributes, self.topology);
+		self.uvs.clear();
+		self.uvs2.clear();
+		self.occlusion.clear();
+		self.colors.clear();
+		self.quantize();
+		self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
 		self.submission_info = None;
 	}
 
+	pub fn vertex_format(&self) -> VertexFormat {
+		self.vertex_format
+	}
+
+	pub fn set_vertex_format(&mut self, vertex_format: VertexFormat) {
+		assert!(matches!(self.topology, Topology::Triangle) || vertex_format == VertexFormat::Float, "Only triangle geometry can be quantized");
+
+		if self.vertex_format != vertex_format {
+			self.vertex_format = vertex_format;
+			self.quantize();
+			self.geometry_id = NEXT_GEOMETRY_ID.fetch_add(1, Ordering::Relaxed);
+			self.submission_info = None;
+		}
+	}
+
+	// Rebuilds the normals from the triangles. Corners at the same position share a normal when the angle between their
+	// triangles is at most the smooth angle in radians, otherwise the edge between them stays hard. 0 gives flat shading and
+	// PI smooths everything. Each triangle's normal is weighted by its angle at the corner so the way faces are split into
+	// triangles doesn't skew the result.
+	pub fn recompute_normals(&mut self, smooth_angle: f32) {
+		assert!(matches!(self.topology, Topology::Triangle), "Only triangle geometry has normals");
+
+		// Positions are compared exactly, adding 0 turns -0 into 0 so they match
+		let key = |vector: &Vector3| [(vector.x + 0.0).to_bits(), (vector.y + 0.0).to_bits(), (vector.z + 0.0).to_bits()];
+		let mut position_indices = HashMap::new();
+		let mut positions = Vec::new();
+		let mut corner_positions = Vec::with_capacity(self.indices.len());
+
+		for index in &self.indices {
+			let offset = *index as usize * 6;
+			let position = Vector3::new(self.attributes[offset], self.attributes[offset + 1], self.attributes[offset + 2]);
+
+			let position_index = *position_indices.entry(key(&position)).or_insert_with(|| {
+				positions.push(position);
+				positions.len() - 1
+			});
+
+			corner_positions.push(position_index);
+		}
+
+		let triangle_count = corner_positions.len() / 3;
+		let mut triangle_normals = Vec::with_capacity(triangle_count);
+		let mut position_triangles: Vec<Vec<(usize, f32)>> = vec![Vec::new(); positions.len()];
+
+		for triangle in 0..triangle_count {
+			let [a, b, c] = [0, 1, 2].map(|corner| positions[corner_positions[triangle * 3 + corner]]);
+			let mut normal = b - a;
+			normal.cross(&(c - a));
+			normal.normalize();
+			triangle_normals.push(normal);
+
+			for (corner, (from, to_a, to_b)) in [(a, b, c), (b, c, a), (c, a, b)].iter().enumerate() {
+				let mut edge_a = to_a - from;
+				let mut edge_b = to_b - from;
+				edge_a.normalize();
+				edge_b.normalize();
+
+				let angle = edge_a.dot(&edge_b).clamp(-1.0, 1.0).acos();
+				position_triangles[corner_positions[triangle * 3 + corner]].push((triangle, angle));
+			}
+		}
+
+		let min_cos = smooth_angle.cos() - 1e-5;
+		let mut vertex_indices = HashMap::new();
+		let mut indices = Vec::with_capacity(self.indices.len());
+		let mut attributes = Vec::new();
+		let mut uvs = Vec::new();
+		let mut uvs2 = Vec::new();
+		let mut occlusion = Vec::new();
+		let mut colors = Vec::new();
+
+		for (corner, position_index) in corner_positions.iter().enumerate() {
+			let triangle_normal = &triangle_normals[corner / 3];
+			let mut normal = Vector3::default();
+
+			for (triangle, angle) in &position_triangles[*position_index] {
+				if triangle_normals[*triangle].dot(triangle_normal) >= min_cos {
+					normal += triangle_normals[*triangle] * *angle;
+				}
+			}
+
+			normal.normalize();
+
+			// Corners on a seam in either UV set or with different occlusion or colors keep their own vertices
+			let uv_offset = self.indices[corner] as usize * 2;
+			let color_offset = self.indices[corner] as usize * 4;
+			let uv = self.uvs.get(uv_offset..uv_offset + 2).unwrap_or(&[0.0, 0.0]);
+			let uv2 = self.uvs2.get(uv_offset..uv_offset + 2).unwrap_or(&[0.0, 0.0]);
+			let corner_occlusion = self.occlusion.get(self.indices[corner] as usize).copied().unwrap_or(1.0);
+			let color = self.colors.get(color_offset..color_offset + 4).unwrap_or(&[0.0, 0.0, 0.0, 0.0]);
+			let uv_key = [
+				uv[0].to_bits(), uv[1].to_bits(), uv2[0].to_bits(), uv2[1].to_bits(), corner_occlusion.to_bits(),
+				color[0].to_bits(), color[1].to_bits(), color[2].to_bits(), color[3].to_bits()
+			];
+
+			let index = *vertex_indices.entry((*position_index, key(&normal), uv_key)).or_insert_with(|| {
+				let position = &positions[*position_index];
+				attributes.extend_from_slice(&[position.x, position.y, position.z, normal.x, normal.y, normal.z]);
+				uvs.extend_from_slice(uv);
+				uvs2.extend_from_slice(uv2);
+				occlusion.push(corner_occlusion);
+				colors.extend_from_slice(color);
+				attributes.len() / 6 - 1
+			});
+
+			assert!(index <= u16::MAX as usize, "Too many vertices after splitting hard edges");
+			indices.push(index as u16);
+		}
+
+		let has_uvs = !self.uvs.is_empty();
+		let has_uvs2 = !self.uvs2.is_empty();
+		let has_occlusion = !self.occlusion.is_empty();
+		let has_colors = !self.colors.is_empty();
+		self.set(indices, attributes, Topology::Triangle);
+
+		if has_uvs {
+			self.uvs = uvs;
+		}
+
+		if has_uvs2 {
+			self.uvs2 = uvs2;
+		}
+
+		if has_occlusion {
+			self.occlusion = occlusion;
+		}
+
+		if has_colors {
+			self.colors = colors;
+		}
+	}
+
+	// Turns the quantized positions back into geometry space, the renderer applies it to the instance matrices. The scale is
+	// the same on every axis so normals transformed by the inverse transpose only change length.
+	pub fn dequantization_matrix(&self) -> Matrix4 {
+		let (center, scale) = self.quantization_range();
+		let mut matrix = Matrix4::default();
+		matrix.compose(&center, &quaternion::ZERO, &Vector3::from_scalar(scale));
+		matrix
+	}
+
+	// What's uploaded for drawing, the quantized attributes when the geometry is quantized
+	pub(crate) fn vertex_data(&self) -> (*const u8, usize) {
+		match self.vertex_format {
+			VertexFormat::Float => (self.attributes.as_ptr() as *const u8, size_of_val(self.attributes.as_slice())),
+			VertexFormat::Quantized => (self.quantized_attributes.as_ptr() as *const u8, size_of_val(self.quantized_attributes.as_slice()))
+		}
+	}
+
+	// The center of the bounding box and half the length of its longest side
+	fn quantization_range(&self) -> (Vector3, f32) {
+		if self.attributes.is_empty() {
+			return (Vector3::default(), 1.0);
+		}
+
+		let center = (self.bounding_box.min + self.bounding_box.max) * 0.5;
+		let extent = self.bounding_box.max - self.bounding_box.min;
+		let scale = extent.x.max(extent.y).max(extent.z) * 0.5;
+
+		(center, if scale > 0.0 { scale } else { 1.0 })
+	}
+
+	fn quantize(&mut self) {
+		self.quantized_attributes.clear();
+
+		if self.vertex_format == VertexFormat::Float {
+			return;
+		}
+
+		let (center, scale) = self.quantization_range();
+		self.quantized_attributes.reserve(self.attributes.len());
+
+		for vertex in self.attributes.chunks_exact(6) {
+			let position = (Vector3::new(vertex[0], vertex[1], vertex[2]) - center) / scale;
+			let normal = encode_octahedral(&Vector3::new(vertex[3], vertex[4], vertex[5]));
+
+			self.quantized_attributes.extend_from_slice(&[
+				to_snorm16(position.x),
+				to_snorm16(position.y),
+				to_snorm16(position.z),
+				0,
+				to_snorm16(normal.0),
+				to_snorm16(normal.1)]);
+		}
+	}
+
 	fn calculate_bounding_box(attributes: &[f32], topology: Topology) -> Box3 {
 		let mut min = Vector3::from_scalar(f32::INFINITY);
 		let mut max = Vector3::from_scalar(f32::NEG_INFINITY);
@@ -96,7 +395,16 @@ impl Geometry3D {
 			 1.0, 0.0, -1.0, 0.0, 1.0, 0.0
 		];
 
-		Self::new(indices, attributes, Topology::Triangle)
+		let uvs = vec![
+			1.0, 1.0,
+			0.0, 1.0,
+			0.0, 0.0,
+			1.0, 0.0
+		];
+
+		let mut geometry = Self::new(indices, attributes, Topology::Triangle);
+		geometry.uvs = uvs;
+		geometry
 	}
 
 	pub fn create_box() -> Self {
@@ -142,7 +450,88 @@ impl Geometry3D {
 			 1.0, -1.0, -1.0,  0.0,  0.0, -1.0
 		];
 
-		Self::new(indices, attributes, Topology::Triangle)
+		// Each face has the whole texture the right way up when looking at it, the top and bottom have -z and z up
+		let uvs = vec![
+			1.0, 1.0, // top
+			0.0, 1.0,
+			0.0, 0.0,
+			1.0, 0.0,
+			1.0, 0.0, // bottom
+			0.0, 0.0,
+			0.0, 1.0,
+			1.0, 1.0,
+			1.0, 0.0, // right
+			0.0, 0.0,
+			0.0, 1.0,
+			1.0, 1.0,
+			0.0, 0.0, // left
+			1.0, 0.0,
+			1.0, 1.0,
+			0.0, 1.0,
+			1.0, 0.0, // front
+			0.0, 0.0,
+			0.0, 1.0,
+			1.0, 1.0,
+			0.0, 0.0, // back
+			1.0, 0.0,
+			1.0, 1.0,
+			0.0, 1.0
+		];
+
+		let mut geometry = Self::new(indices, attributes, Topology::Triangle);
+		geometry.uvs = uvs;
+		geometry
+	}
+
+	// A unit sphere made of rings from the top down, the UVs wrap around it once horizontally so there's a seam of duplicate
+	// vertices at the back
+	pub fn create_sphere(width_segments: u16, height_segments: u16) -> Self {
+		assert!(width_segments >= 3 && height_segments >= 2, "A sphere needs at least 3 width segments and 2 height segments");
+		assert!((width_segments as usize + 1) * (height_segments as usize + 1) <= u16::MAX as usize + 1, "Too many segments for 16 bit indices");
+
+		let row_length = width_segments + 1;
+		let mut indices = Vec::with_capacity(width_segments as usize * height_segments as usize * 6);
+		let mut attributes = Vec::with_capacity(row_length as usize * (height_segments as usize + 1) * 6);
+		let mut uvs = Vec::with_capacity(row_length as usize * (height_segments as usize + 1) * 2);
+
+		for y in 0..=height_segments {
+			let v = y as f32 / height_segments as f32;
+
+			// The last ring and column are exact so the vertices at the bottom pole and on the seam are in the same places
+			let (ring_sin, ring_cos) = if y == height_segments { (0.0, -1.0) } else { (v * std::f32::consts::PI).sin_cos() };
+
+			for x in 0..=width_segments {
+				let u = x as f32 / width_segments as f32;
+				let (sin, cos) = if x == width_segments { (0.0, 1.0) } else { (u * std::f32::consts::TAU).sin_cos() };
+				let position = [-cos * ring_sin, ring_cos, sin * ring_sin];
+
+				attributes.extend_from_slice(&position);
+				attributes.extend_from_slice(&position);
+				uvs.extend_from_slice(&[u, v]);
+			}
+		}
+
+		// The rings at the poles are single points so only one triangle of their quads is kept
+		for y in 0..height_segments {
+			for x in 0..width_segments {
+				let a = y * row_length + x + 1;
+				let b = y * row_length + x;
+				let c = (y + 1) * row_length + x;
+				let d = (y + 1) * row_length + x + 1;
+
+				if y != 0 {
+					indices.extend_from_slice(&[a, b, d]);
+				}
+
+				if y != height_segments - 1 {
+					indices.extend_from_slice(&[b, c, d]);
+				}
+			}
+		}
+
+		let mut geometry = Self::new(indices, attributes, Topology::Triangle);
+		geometry.uvs = uvs;
+		geometry
 	}
 
 	pub fn create_axis_helper() -> Self {
@@ -197,4 +586,322 @@ impl Geometry3D {
 
 		self.set(indices, attributes, Topology::Line);
 	}
+
+	// Three unit circles around the x, y and z axes
+	pub fn create_sphere_helper() -> Self {
+		let segment_count: u16 = 32;
+		let mut indices = Vec::with_capacity(segment_count as usize * 6);
+		let mut attributes = Vec::with_capacity(segment_count as usize * 9);
+
+		for axis in 0..3 {
+			let first_index = axis * segment_count;
+
+			for i in 0..segment_count {
+				let angle = i as f32 / segment_count as f32 * std::f32::consts::TAU;
+				let (sin, cos) = angle.sin_cos();
+
+				let vertex = match axis {
+					0 => [0.0, cos, sin],
+					1 => [cos, 0.0, sin],
+					_ => [cos, sin, 0.0]
+				};
+
+				attributes.extend_from_slice(&vertex);
+				indices.extend_from_slice(&[first_index + i, first_index + (i + 1) % segment_count]);
+			}
+		}
+
+		Self::new(indices, attributes, Topology::Line)
+	}
+
+	// The edges of the frustum the projection matrix transforms into clip space, in the camera's local space
+	pub fn create_frustum_helper(projection_matrix: &Matrix4) -> Self {
+		let (indices, attributes) = Self::frustum_helper_data(projection_matrix);
+		Self::new(indices, attributes, Topology::Line)
+	}
+
+	pub fn make_frustum_helper(&mut self, projection_matrix: &Matrix4) {
+		let (indices, attributes) = Self::frustum_helper_data(projection_matrix);
+		self.set(indices, attributes, Topology::Line);
+	}
+
+	fn frustum_helper_data(projection_matrix: &Matrix4) -> (Vec<u16>, Vec<f32>) {
+		let indices = vec![0, 1, 1, 2, 2, 3, 3, 0, 4, 5, 5, 6, 6, 7, 7, 4, 0, 4, 1, 5, 2, 6, 3, 7];
+		let mut attributes = Vec::with_capacity(24);
+
+		for corner in &Frustum::corners(projection_matrix) {
+			attributes.extend_from_slice(&[corner.x, corner.y, corner.z]);
+		}
+
+		(indices, attributes)
+	}
+
+	// A square grid of lines on the xz plane in world space, around the center which is rounded to the spacing. Every
+	// major_every line is brighter and the lines along the x and z axes are red and blue. Meant to be moved with the camera by
+	// making it again around the camera, see DebugHelperSystem::update_grid.
+	pub fn create_grid_helper(center_x: f32, center_z: f32, half_size: f32, spacing: f32, major_every: u32) -> Self {
+		let (indices, attributes, colors) = Self::grid_helper_data(center_x, center_z, half_size, spacing, major_every);
+		let mut geometry = Self::new(indices, attributes, Topology::Line);
+		geometry.set_colors(colors);
+		geometry
+	}
+
+	pub fn make_grid_helper(&mut self, center_x: f32, center_z: f32, half_size: f32, spacing: f32, major_every: u32) {
+		let (indices, attributes, colors) = Self::grid_helper_data(center_x, center_z, half_size, spacing, major_every);
+		self.set(indices, attributes, Topology::Line);
+		self.set_colors(colors);
+	}
+
+	fn grid_helper_data(center_x: f32, center_z: f32, half_size: f32, spacing: f32, major_every: u32) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
+		assert!(spacing > 0.0 && half_size >= spacing, "The grid must be at least one line spacing in size");
+		assert!(major_every > 0, "Major lines cannot be every 0 lines");
+
+		// Lines are numbered from the world origin so the major lines and axes stay put as the center moves
+		let half_line_count = (half_size / spacing) as i64;
+		let line_count = (half_line_count * 2 + 1) as usize;
+		assert!(line_count * 4 <= u16::MAX as usize + 1, "The grid has too many lines for 16 bit indices");
+
+		let first_x = (center_x / spacing).round() as i64 - half_line_count;
+		let first_z = (center_z / spacing).round() as i64 - half_line_count;
+		let (min_x, max_x) = (first_x as f32 * spacing, (first_x + half_line_count * 2) as f32 * spacing);
+		let (min_z, max_z) = (first_z as f32 * spacing, (first_z + half_line_count * 2) as f32 * spacing);
+
+		let mut indices = Vec::with_capacity(line_count * 4);
+		let mut attributes = Vec::with_capacity(line_count * 12);
+		let mut colors = Vec::with_capacity(line_count * 16);
+
+		let color = |line: i64, axis_color: [f32; 4]| {
+			if line == 0 {
+				axis_color
+			}
+			else if line % major_every as i64 == 0 {
+				GRID_MAJOR_COLOR
+			}
+			else {
+				GRID_MINOR_COLOR
+			}
+		};
+
+		for i in 0..half_line_count * 2 + 1 {
+			// Along z, the one through the origin is the z axis
+			let x = (first_x + i) as f32 * spacing;
+			attributes.extend_from_slice(&[x, 0.0, min_z, x, 0.0, max_z]);
+			let z_line_color = color(first_x + i, GRID_Z_AXIS_COLOR);
+
+			// Along x
+			let z = (first_z + i) as f32 * spacing;
+			attributes.extend_from_slice(&[min_x, 0.0, z, max_x, 0.0, z]);
+			let x_line_color = color(first_z + i, GRID_X_AXIS_COLOR);
+
+			for line_color in [z_line_color, z_line_color, x_line_color, x_line_color] {
+				colors.extend_from_slice(&line_color);
+			}
+
+			let first_index = i as u16 * 4;
+			indices.extend_from_slice(&[first_index, first_index + 1, first_index + 2, first_index + 3]);
+		}
+
+		(indices, attributes, colors)
+	}
+}
+
+fn to_snorm16(value: f32) -> i16 {
+	(value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+// Maps the unit sphere onto an octahedron and unfolds it into a square from -1 to 1, the shaders do the opposite
+fn encode_octahedral(normal: &Vector3) -> (f32, f32) {
+	let length = normal.x.abs() + normal.y.abs() + normal.z.abs();
+
+	if length == 0.0 {
+		return (0.0, 0.0);
+	}
+
+	let x = normal.x / length;
+	let y = normal.y / length;
+
+	if normal.z >= 0.0 {
+		(x, y)
+	}
+	else {
+		((1.0 - y.abs()) * x.signum(), (1.0 - x.abs()) * y.signum())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{matrix4, Vector4, assert_approx_eq};
+
+	fn decode_octahedral(x: f32, y: f32) -> Vector3 {
+		let mut normal = Vector3::new(x, y, 1.0 - x.abs() - y.abs());
+
+		if normal.z < 0.0 {
+			normal.x = (1.0 - y.abs()) * x.signum();
+			normal.y = (1.0 - x.abs()) * y.signum();
+		}
+
+		normal.normalize();
+		normal
+	}
+
+	#[test]
+	fn sphere_helper() {
+		let geometry = Geometry3D::create_sphere_helper();
+		assert_eq!(geometry.indices().len(), 32 * 6);
+		assert_approx_eq(&geometry.bounding_box().min, &Vector3::from_scalar(-1.0), 1e-6);
+		assert_approx_eq(&geometry.bounding_box().max, &Vector3::from_scalar(1.0), 1e-6);
+	}
+
+	#[test]
+	fn frustum_helper() {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_orthographic(4.0, 2.0, 1.0, 10.0);
+
+		let geometry = Geometry3D::create_frustum_helper(&projection_matrix);
+		assert_approx_eq(&geometry.bounding_box().min, &Vector3::new(-2.0, -1.0, 1.0), 1e-5);
+		assert_approx_eq(&geometry.bounding_box().max, &Vector3::new(2.0, 1.0, 10.0), 1e-5);
+	}
+
+	#[test]
+	fn grid_helper() {
+		let geometry = Geometry3D::create_grid_helper(2.4, -0.3, 2.0, 1.0, 2);
+		assert_eq!(geometry.indices().len(), 5 * 4);
+		assert_eq!(geometry.colors().len(), 5 * 4 * 4);
+		assert_approx_eq(&geometry.bounding_box().min, &Vector3::new(0.0, 0.0, -2.0), 1e-6);
+		assert_approx_eq(&geometry.bounding_box().max, &Vector3::new(4.0, 0.0, 2.0), 1e-6);
+
+		// The first line along z is the z axis and the first along x is a major line 2 units behind the x axis
+		let colors = geometry.colors();
+		assert_eq!(colors[0..4], GRID_Z_AXIS_COLOR);
+		assert_eq!(colors[8..12], GRID_MAJOR_COLOR);
+		assert_eq!(colors[16..20], GRID_MINOR_COLOR);
+
+		// The middle lines, x = 2 is major and z = 0 is the x axis
+		assert_eq!(colors[32..36], GRID_MAJOR_COLOR);
+		assert_eq!(colors[40..44], GRID_X_AXIS_COLOR);
+	}
+
+	#[test]
+	fn octahedral_normals() {
+		for (x, y, z) in [(0.0, 0.0, 1.0), (0.0, 0.0, -1.0), (1.0, -2.0, 3.0), (-4.0, 1.0, -0.5), (0.3, 0.9, -0.1)] {
+			let mut normal = Vector3::new(x, y, z);
+			normal.normalize();
+
+			let (u, v) = encode_octahedral(&normal);
+			let decoded = decode_octahedral(to_snorm16(u) as f32 / i16::MAX as f32, to_snorm16(v) as f32 / i16::MAX as f32);
+			assert_approx_eq(&decoded, &normal, 1e-4);
+		}
+	}
+
+	#[test]
+	fn quantized_positions() {
+		let mut geometry = Geometry3D::create_box();
+		let mut attributes = geometry.attributes().to_vec();
+
+		for position in attributes.chunks_exact_mut(6) {
+			position[0] = position[0] * 10.0 + 3.0;
+			position[2] -= 7.0;
+		}
+
+		geometry.set(geometry.indices().to_vec(), attributes, Topology::Triangle);
+		let id = geometry.geometry_id;
+		geometry.set_vertex_format(VertexFormat::Quantized);
+		assert_ne!(geometry.geometry_id, id);
+		assert_eq!(geometry.vertex_data().1, geometry.attributes().len() / 6 * 12);
+
+		let matrix = geometry.dequantization_matrix();
+
+		for (quantized, vertex) in geometry.quantized_attributes.chunks_exact(6).zip(geometry.attributes().chunks_exact(6)) {
+			let snorm = |index: usize| quantized[index] as f32 / i16::MAX as f32;
+			let position = matrix * Vector4::new(snorm(0), snorm(1), snorm(2), 1.0);
+			assert_approx_eq(&Vector3::new(position.x, position.y, position.z), &Vector3::new(vertex[0], vertex[1], vertex[2]), 1e-3);
+		}
+	}
+
+	#[test]
+	fn recompute_normals() {
+		let original = Geometry3D::create_box();
+		let mut geometry = Geometry3D::create_box();
+		let normal = |geometry: &Geometry3D, corner: usize| {
+			let offset = geometry.indices()[corner] as usize * 6 + 3;
+			Vector3::new(geometry.attributes()[offset], geometry.attributes()[offset + 1], geometry.attributes()[offset + 2])
+		};
+
+		geometry.recompute_normals(0.5);
+		assert_eq!(geometry.attributes().len(), 24 * 6);
+
+		for corner in 0..36 {
+			assert_approx_eq(&normal(&geometry, corner), &normal(&original, corner), 1e-6);
+		}
+
+		// Smoothing everything welds the corners and points their normals away from the center, the UVs would keep them apart
+		geometry.set_uvs(Vec::new());
+		geometry.recompute_normals(std::f32::consts::PI);
+		assert_eq!(geometry.attributes().len(), 8 * 6);
+		assert_eq!(geometry.indices().len(), 36);
+
+		for vertex in geometry.attributes().chunks_exact(6) {
+			let mut expected = Vector3::new(vertex[0], vertex[1], vertex[2]);
+			expected.normalize();
+			assert_approx_eq(&Vector3::new(vertex[3], vertex[4], vertex[5]), &expected, 1e-5);
+		}
+
+		// And flat shading splits them again
+		geometry.recompute_normals(0.0);
+		assert_eq!(geometry.attributes().len(), 24 * 6);
+
+		// Occlusion follows the corners when they're split and keeps corners with different occlusion apart when welding
+		let occlusion: Vec<f32> = (0..24).map(|vertex| if vertex < 12 { 0.5 } else { 1.0 }).collect();
+		geometry.set_occlusion(occlusion);
+		let corner_occlusion: Vec<f32> = geometry.indices().iter().map(|index| geometry.occlusion()[*index as usize]).collect();
+		geometry.recompute_normals(std::f32::consts::PI);
+		assert!(geometry.attributes().len() > 8 * 6);
+
+		for (corner, index) in geometry.indices().iter().enumerate() {
+			assert_eq!(geometry.occlusion()[*index as usize], corner_occlusion[corner]);
+		}
+
+		// Colors follow the corners the same way
+		let colors: Vec<f32> = (0..geometry.attributes().len() / 6 * 4).map(|value| value as f32).collect();
+		geometry.set_colors(colors);
+		let corner_colors: Vec<Vec<f32>> = geometry.indices().iter().map(|index| geometry.colors()[*index as usize * 4..][..4].to_vec()).collect();
+		geometry.recompute_normals(0.0);
+
+		for (corner, index) in geometry.indices().iter().enumerate() {
+			assert_eq!(&geometry.colors()[*index as usize * 4..][..4], corner_colors[corner].as_slice());
+		}
+	}
+
+	#[test]
+	fn sphere() {
+		let mut geometry = Geometry3D::create_sphere(16, 8);
+		assert_eq!(geometry.uvs().len(), 17 * 9 * 2);
+		assert_eq!(geometry.indices().len(), 16 * (8 - 1) * 6);
+		assert_approx_eq(&geometry.bounding_box().max, &Vector3::from_scalar(1.0), 1e-6);
+
+		// The triangles wind counter clockwise from the outside so flat normals point outwards
+		let vertex = |geometry: &Geometry3D, index: u16| {
+			let attributes = &geometry.attributes()[index as usize * 6..index as usize * 6 + 6];
+			(Vector3::new(attributes[0], attributes[1], attributes[2]), Vector3::new(attributes[3], attributes[4], attributes[5]))
+		};
+
+		geometry.recompute_normals(0.0);
+
+		for index in geometry.indices() {
+			let (position, normal) = vertex(&geometry, *index);
+			assert!(normal.dot(&position) > 0.9);
+		}
+
+		// Smoothing welds everything except the seam and the poles which have different UVs, the first and last points of the
+		// poles aren't used
+		geometry.recompute_normals(std::f32::consts::PI);
+		assert_eq!(geometry.attributes().len() / 6, 17 * 9 - 2);
+		assert_eq!(geometry.uvs().len(), (17 * 9 - 2) * 2);
+
+		for index in geometry.indices() {
+			let (position, normal) = vertex(&geometry, *index);
+			assert!(normal.dot(&position) > 0.99);
+		}
+	}
 }
\ No newline at end of file
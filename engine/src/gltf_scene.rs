@@ -0,0 +1,471 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use gltf::{Gltf, buffer::Source, mesh::Mode, material::AlphaMode};
+use crate::{
+	Entity,
+	EntityManager,
+	Geometry3D,
+	component::{Mesh, MultiComponentList, RenderLayer, Transform3D, Transform3DComponentList, mesh::Material},
+	geometry3d::Topology,
+	math::{Color, Quaternion, Vector3},
+	pool::{Handle, Pool}
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+	Io { path: PathBuf, error: io::Error },
+	Gltf(gltf::Error),
+	Invalid { reason: String }
+}
+
+impl fmt::Display for GltfError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read glTF file {}: {}", path.display(), error),
+			Self::Gltf(error) => write!(f, "glTF file is invalid: {}", error),
+			Self::Invalid { reason } => write!(f, "glTF file cannot be loaded: {}", reason)
+		}
+	}
+}
+
+impl Error for GltfError {}
+
+impl From<gltf::Error> for GltfError {
+	fn from(error: gltf::Error) -> Self {
+		Self::Gltf(error)
+	}
+}
+
+// What the renderer can use of a glTF material, textures aren't loaded
+pub struct GltfMaterial {
+	pub name: Option<String>,
+	pub base_color: Color,
+	// Blended materials go in the transparent render layer, masked ones are drawn opaque
+	pub transparent: bool,
+	pub double_sided: bool
+}
+
+#[derive(Clone, Copy)]
+pub struct GltfPrimitive {
+	pub geometry_handle: Handle,
+	pub topology: Topology,
+	pub material_index: Option<usize>
+}
+
+pub struct GltfNode {
+	pub name: Option<String>,
+	// Parents always come before their children
+	pub parent_index: Option<usize>,
+	pub position: Vector3,
+	pub orientation: Quaternion,
+	pub scale: Vector3,
+	pub primitives: Vec<GltfPrimitive>
+}
+
+// The nodes of a glTF or GLB file's default scene, or its first scene when there's no default, with their meshes added to
+// the geometry pool. Meshes are made of u16 indexed triangles or lines so a primitive can have at most 65536 vertices.
+// Geometry without normals gets flat ones, the first UV set, second UV set and first color set are kept when present.
+// Skins, morph targets, animations, cameras and textures aren't loaded.
+pub struct GltfScene {
+	pub nodes: Vec<GltfNode>,
+	pub materials: Vec<GltfMaterial>
+}
+
+impl GltfScene {
+	pub fn load(path: impl AsRef<Path>, geometries: &mut Pool<Geometry3D>) -> Result<Self, GltfError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|error| GltfError::Io { path: path.to_path_buf(), error })?;
+		Self::from_bytes(&bytes, path.parent(), geometries)
+	}
+
+	// External buffers are read relative to the base directory, without one only embedded buffers can be read
+	pub fn from_bytes(bytes: &[u8], base_directory: Option<&Path>, geometries: &mut Pool<Geometry3D>) -> Result<Self, GltfError> {
+		let Gltf { document, mut blob } = Gltf::from_slice(bytes)?;
+		let mut buffers = Vec::with_capacity(document.buffers().len());
+
+		for buffer in document.buffers() {
+			let data = match buffer.source() {
+				Source::Bin => blob.take().ok_or_else(|| invalid(format!("buffer {} is in a missing binary chunk", buffer.index())))?,
+				Source::Uri(uri) => match uri.strip_prefix("data:") {
+					Some(data) => {
+						let (_, encoded) = data.split_once(";base64,").ok_or_else(|| invalid(format!("buffer {} isn't base64", buffer.index())))?;
+						decode_base64(encoded).ok_or_else(|| invalid(format!("buffer {} has invalid base64", buffer.index())))?
+					},
+					None => {
+						let base_directory = base_directory.ok_or_else(|| invalid(format!("buffer {} is in {} but there's no directory to read it from", buffer.index(), uri)))?;
+						let path = base_directory.join(uri);
+						fs::read(&path).map_err(|error| GltfError::Io { path, error })?
+					}
+				}
+			};
+
+			if data.len() < buffer.length() {
+				return Err(invalid(format!("buffer {} is {} bytes, shorter than {}", buffer.index(), data.len(), buffer.length())));
+			}
+
+			buffers.push(data);
+		}
+
+		// Every primitive is loaded before any are added to the pool so nothing is left in it when one is invalid
+		let mut meshes = Vec::with_capacity(document.meshes().len());
+
+		for mesh in document.meshes() {
+			let mut primitives = Vec::new();
+
+			for primitive in mesh.primitives() {
+				let geometry = load_primitive(&primitive, &buffers).map_err(|reason| {
+					invalid(format!("mesh {} primitive {} {}", mesh.name().unwrap_or(&mesh.index().to_string()), primitive.index(), reason))
+				})?;
+
+				primitives.push((geometry, primitive.material().index()));
+			}
+
+			meshes.push(primitives);
+		}
+
+		// Each mesh is added once however many nodes use it
+		let meshes: Vec<Vec<GltfPrimitive>> = meshes.into_iter()
+			.map(|primitives| primitives.into_iter()
+				.map(|(geometry, material_index)| GltfPrimitive {
+					topology: *geometry.topology(),
+					geometry_handle: geometries.add(geometry),
+					material_index
+				})
+				.collect())
+			.collect();
+
+		let materials = document.materials()
+			.map(|material| {
+				let [r, g, b, a] = material.pbr_metallic_roughness().base_color_factor();
+
+				GltfMaterial {
+					name: material.name().map(String::from),
+					base_color: Color::new(r, g, b, a),
+					transparent: material.alpha_mode() == AlphaMode::Blend,
+					double_sided: material.double_sided()
+				}
+			})
+			.collect();
+
+		let scene = match document.default_scene().or_else(|| document.scenes().next()) {
+			Some(scene) => scene,
+			None => return Ok(Self { nodes: Vec::new(), materials })
+		};
+
+		let mut nodes = Vec::new();
+		// Popped in the order they're in the file
+		let mut stack: Vec<(gltf::Node, Option<usize>)> = scene.nodes().map(|node| (node, None)).collect();
+		stack.reverse();
+
+		while let Some((node, parent_index)) = stack.pop() {
+			let primitives = node.mesh().map_or_else(Vec::new, |mesh| meshes[mesh.index()].clone());
+			let (position, [x, y, z, w], scale) = node.transform().decomposed();
+			let node_index = nodes.len();
+
+			nodes.push(GltfNode {
+				name: node.name().map(String::from),
+				parent_index,
+				position: Vector3::new(position[0], position[1], position[2]),
+				orientation: Quaternion::new(x, y, z, w),
+				scale: Vector3::new(scale[0], scale[1], scale[2]),
+				primitives
+			});
+
+			let first_child = stack.len();
+			stack.extend(node.children().map(|child| (child, Some(node_index))));
+			stack[first_child..].reverse();
+		}
+
+		Ok(Self {
+			nodes,
+			materials
+		})
+	}
+
+	// Creates an entity for each node with its transform under its parent's, returned in the order of the nodes. A mesh
+	// entity can have only one mesh so the first primitive goes on the node's entity and the others on child entities. Triangles
+	// are lambert shaded and put in the transparent layer when their material is blended.
+	pub fn spawn(
+		&self,
+		entity_manager: &mut EntityManager,
+		transform3d_components: &mut Transform3DComponentList,
+		mesh_components: &mut MultiComponentList<Mesh>) -> Vec<Entity>
+	{
+		let mut entities: Vec<Entity> = Vec::with_capacity(self.nodes.len());
+
+		for node in &self.nodes {
+			let entity = entity_manager.create();
+			let mut transform = Transform3D::new();
+			transform.position = node.position;
+			transform.orientation = node.orientation;
+			transform.scale = node.scale;
+
+			match node.parent_index {
+				Some(parent_index) => transform3d_components.add_child(entity_manager, entities[parent_index], entity, transform),
+				None => transform3d_components.add(entity_manager, entity, transform)
+			}
+
+			for (index, primitive) in node.primitives.iter().enumerate() {
+				let primitive_entity = if index == 0 {
+					entity
+				}
+				else {
+					let primitive_entity = entity_manager.create();
+					transform3d_components.add_child(entity_manager, entity, primitive_entity, Transform3D::new());
+					primitive_entity
+				};
+
+				let material = primitive.material_index.map(|index| &self.materials[index]);
+
+				let mut mesh = match primitive.topology {
+					Topology::Triangle => Mesh::new(primitive.geometry_handle, Material::Lambert),
+					Topology::Line => Mesh::new(primitive.geometry_handle, Material::Line)
+				};
+
+				if material.is_some_and(|material| material.transparent) {
+					mesh.layer = RenderLayer::Transparent;
+				}
+
+				let mesh_index = mesh_components.add(mesh);
+				mesh_components.assign(entity_manager, primitive_entity, mesh_index);
+			}
+
+			entities.push(entity);
+		}
+
+		entities
+	}
+}
+
+fn invalid(reason: String) -> GltfError {
+	GltfError::Invalid { reason }
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+	let encoded = encoded.trim_end_matches('=').as_bytes();
+	let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+
+	for chunk in encoded.chunks(4) {
+		let mut n = 0u32;
+
+		for (i, c) in chunk.iter().enumerate() {
+			let value = match c {
+				b'A'..=b'Z' => c - b'A',
+				b'a'..=b'z' => c - b'a' + 26,
+				b'0'..=b'9' => c - b'0' + 52,
+				b'+' => 62,
+				b'/' => 63,
+				_ => return None
+			};
+
+			n |= (value as u32) << (18 - i * 6);
+		}
+
+		// A lone character can't make a byte
+		if chunk.len() == 1 {
+			return None;
+		}
+
+		bytes.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+	}
+
+	Some(bytes)
+}
+
+fn load_primitive(primitive: &gltf::Primitive, buffers: &[Vec<u8>]) -> Result<Geometry3D, String> {
+	let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+	let positions: Vec<[f32; 3]> = reader.read_positions().ok_or("has no positions")?.collect();
+
+	if positions.len() > u16::MAX as usize + 1 {
+		return Err(format!("has {} vertices, more than 16 bit indices can reach", positions.len()));
+	}
+
+	let vertex_indices: Vec<u32> = match reader.read_indices() {
+		Some(indices) => indices.into_u32().collect(),
+		None => (0..positions.len() as u32).collect()
+	};
+
+	if vertex_indices.iter().any(|index| *index as usize >= positions.len()) {
+		return Err(String::from("has an index past its last vertex"));
+	}
+
+	// Strips, loops and fans are turned into lists
+	let (topology, indices): (Topology, Vec<u32>) = match primitive.mode() {
+		Mode::Triangles => (Topology::Triangle, vertex_indices),
+		Mode::TriangleStrip => (Topology::Triangle, vertex_indices.windows(3).enumerate()
+			.flat_map(|(i, w)| if i % 2 == 0 { [w[0], w[1], w[2]] } else { [w[1], w[0], w[2]] })
+			.collect()),
+		Mode::TriangleFan => (Topology::Triangle, vertex_indices.windows(2).skip(1)
+			.flat_map(|w| [vertex_indices[0], w[0], w[1]])
+			.collect()),
+		Mode::Lines => (Topology::Line, vertex_indices),
+		Mode::LineStrip => (Topology::Line, vertex_indices.windows(2).flat_map(|w| [w[0], w[1]]).collect()),
+		Mode::LineLoop => (Topology::Line, (0..vertex_indices.len())
+			.flat_map(|i| [vertex_indices[i], vertex_indices[(i + 1) % vertex_indices.len()]])
+			.collect()),
+		Mode::Points => return Err(String::from("is points which cannot be drawn"))
+	};
+
+	let indices: Vec<u16> = indices.into_iter().map(|index| index as u16).collect();
+
+	let mut geometry = match topology {
+		Topology::Triangle => {
+			let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+
+			if normals.as_ref().is_some_and(|normals| normals.len() != positions.len()) {
+				return Err(String::from("has a different number of normals and positions"));
+			}
+
+			let mut attributes = Vec::with_capacity(positions.len() * 6);
+
+			for (i, position) in positions.iter().enumerate() {
+				attributes.extend_from_slice(position);
+				attributes.extend_from_slice(normals.as_ref().map_or(&[0.0; 3], |normals| &normals[i]));
+			}
+
+			let mut geometry = Geometry3D::new(indices, attributes, Topology::Triangle);
+
+			if normals.is_none() {
+				geometry.recompute_normals(0.0);
+			}
+
+			if let Some(uvs) = reader.read_tex_coords(0) {
+				geometry.set_uvs(uvs.into_f32().flatten().collect());
+			}
+
+			if let Some(uvs2) = reader.read_tex_coords(1) {
+				geometry.set_uvs2(uvs2.into_f32().flatten().collect());
+			}
+
+			geometry
+		},
+		Topology::Line => Geometry3D::new(indices, positions.concat(), Topology::Line)
+	};
+
+	if let Some(colors) = reader.read_colors(0) {
+		geometry.set_colors(colors.into_rgba_f32().flatten().collect());
+	}
+
+	Ok(geometry)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{assert_approx_eq, vector3};
+
+	// A GLB file so the test needs no other files. A triangle without normals drawn by a parent node and a child node moved 2
+	// along x, and a line strip.
+	fn glb_bytes(line_mode: u32) -> Vec<u8> {
+		let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+		let indices = [0u16, 1, 2, 0];
+		let mut bin: Vec<u8> = positions.iter().flat_map(|value| value.to_le_bytes()).collect();
+		bin.extend(indices.iter().flat_map(|index| index.to_le_bytes()));
+
+		let mut json = format!(r#"{{
+			"asset": {{ "version": "2.0" }},
+			"scene": 0,
+			"scenes": [{{ "nodes": [0] }}],
+			"nodes": [
+				{{ "name": "parent", "mesh": 0, "children": [1], "scale": [2.0, 2.0, 2.0] }},
+				{{ "name": "child", "mesh": 0, "translation": [2.0, 0.0, 0.0] }}
+			],
+			"meshes": [{{ "primitives": [
+				{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }},
+				{{ "attributes": {{ "POSITION": 0 }}, "mode": {} }}
+			] }}],
+			"materials": [{{ "name": "glass", "alphaMode": "BLEND", "pbrMetallicRoughness": {{ "baseColorFactor": [1.0, 0.5, 0.0, 0.5] }} }}],
+			"accessors": [
+				{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+				{{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+			],
+			"bufferViews": [
+				{{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+				{{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+			],
+			"buffers": [{{ "byteLength": {} }}]
+		}}"#, line_mode, bin.len()).into_bytes();
+
+		// Chunks are padded to 4 bytes
+		while json.len() % 4 != 0 {
+			json.push(b' ');
+		}
+
+		let length = 12 + 8 + json.len() + 8 + bin.len();
+		let mut glb = b"glTF".to_vec();
+		glb.extend_from_slice(&2u32.to_le_bytes());
+		glb.extend_from_slice(&(length as u32).to_le_bytes());
+		glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+		glb.extend_from_slice(b"JSON");
+		glb.extend_from_slice(&json);
+		glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+		glb.extend_from_slice(b"BIN\0");
+		glb.extend_from_slice(&bin);
+		glb
+	}
+
+	#[test]
+	fn loads_nodes_and_meshes() {
+		let mut geometries = Pool::new();
+		let scene = GltfScene::from_bytes(&glb_bytes(3), None, &mut geometries).unwrap();
+
+		assert_eq!(scene.nodes.len(), 2);
+		assert_eq!(scene.nodes[0].name.as_deref(), Some("parent"));
+		assert_eq!(scene.nodes[0].scale, Vector3::from_scalar(2.0));
+		assert_eq!(scene.nodes[1].parent_index, Some(0));
+		assert_eq!(scene.nodes[1].position, Vector3::new(2.0, 0.0, 0.0));
+		assert!(scene.materials[0].transparent);
+		assert_eq!(scene.materials[0].base_color, Color::new(1.0, 0.5, 0.0, 0.5));
+
+		// Both nodes draw the same geometry
+		let primitives = &scene.nodes[0].primitives;
+		assert_eq!(primitives.len(), 2);
+		assert!(primitives[0].geometry_handle == scene.nodes[1].primitives[0].geometry_handle);
+
+		let triangle = geometries.borrow(primitives[0].geometry_handle);
+		assert_eq!(triangle.indices(), &[0, 1, 2]);
+		assert_approx_eq(&Vector3::new(triangle.attributes()[3], triangle.attributes()[4], triangle.attributes()[5]), &vector3::UNIT_Z, 1e-6);
+
+		let line_strip = geometries.borrow(primitives[1].geometry_handle);
+		assert!(matches!(primitives[1].topology, Topology::Line));
+		assert_eq!(line_strip.indices(), &[0, 1, 1, 2]);
+	}
+
+	#[test]
+	fn spawns_entities() {
+		let mut geometries = Pool::new();
+		let scene = GltfScene::from_bytes(&glb_bytes(3), None, &mut geometries).unwrap();
+		let mut entity_manager = EntityManager::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut mesh_components = MultiComponentList::new();
+
+		let entities = scene.spawn(&mut entity_manager, &mut transform3d_components, &mut mesh_components);
+		transform3d_components.update(entities[0]);
+
+		// The child is 2 along x under a parent scaled by 2
+		let child_position = transform3d_components.borrow(&entities[1]).global_matrix().extract_position();
+		assert_approx_eq(&child_position, &Vector3::new(4.0, 0.0, 0.0), 1e-6);
+
+		let mesh = mesh_components.borrow(&entities[0]);
+		assert_eq!(mesh.material, Material::Lambert);
+		assert!(mesh.layer == RenderLayer::Transparent);
+		assert_eq!(mesh_components.len(), 4);
+	}
+
+	#[test]
+	fn rejects_points() {
+		match GltfScene::from_bytes(&glb_bytes(0), None, &mut Pool::new()) {
+			Err(GltfError::Invalid { reason }) => assert!(reason.contains("points"), "{}", reason),
+			_ => panic!("Points should be rejected")
+		}
+	}
+
+	#[test]
+	fn decodes_base64() {
+		assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+		assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+		assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+		assert!(decode_base64("TWF").is_some());
+		assert!(decode_base64("T").is_none());
+		assert!(decode_base64("TW!u").is_none());
+	}
+}
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}, time::SystemTime};
+use libloading::Library;
+
+#[derive(Debug)]
+pub enum HotReloadError {
+	Io { path: PathBuf, error: io::Error },
+	Load { path: PathBuf, error: libloading::Error },
+	MissingSymbol { symbol: String, error: libloading::Error }
+}
+
+impl fmt::Display for HotReloadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot copy library {}: {}", path.display(), error),
+			Self::Load { path, error } => write!(f, "Cannot load library {}: {}", path.display(), error),
+			Self::MissingSymbol { symbol, error } => write!(f, "Cannot find symbol {}: {}", symbol, error)
+		}
+	}
+}
+
+impl Error for HotReloadError {}
+
+// A dynamic library which is loaded again whenever the file changes, meant for a development build where the game's systems
+// live in a cdylib so changing them only needs that library rebuilt. All the state such as the entity manager and component
+// lists belongs to the executable and is passed to the library's functions so it's kept across reloads, the library must not
+// keep anything in statics or hand out pointers into itself. Both sides have to be built by the same compiler because the
+// functions use the Rust ABI.
+//
+// The library is copied before it's loaded so the build can overwrite the original, each copy gets a new name because
+// loading a path which is already loaded returns the old library on some platforms.
+pub struct HotReloadLibrary {
+	path: PathBuf,
+	library: Option<Library>,
+	loaded_path: PathBuf,
+	modified: Option<SystemTime>,
+	reload_count: u32
+}
+
+impl HotReloadLibrary {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, HotReloadError> {
+		let path = path.as_ref().to_path_buf();
+		let modified = modified_time(&path);
+		let (library, loaded_path) = load_copy(&path, 0)?;
+
+		Ok(Self {
+			path,
+			library: Some(library),
+			loaded_path,
+			modified,
+			reload_count: 0
+		})
+	}
+
+	pub fn reload_count(&self) -> u32 {
+		self.reload_count
+	}
+
+	// Returns whether the library was reloaded. When the new library can't be loaded, most likely because the build is still
+	// writing it, the old one stays loaded and the reload is tried again next time.
+	pub fn reload_if_changed(&mut self) -> Result<bool, HotReloadError> {
+		let modified = modified_time(&self.path);
+
+		if modified.is_none() || modified <= self.modified {
+			return Ok(false);
+		}
+
+		let (library, loaded_path) = load_copy(&self.path, self.reload_count + 1)?;
+
+		// Dropping the old library unloads it, the copy can be removed after that
+		self.library = Some(library);
+		fs::remove_file(&self.loaded_path).ok();
+		self.loaded_path = loaded_path;
+		self.modified = modified;
+		self.reload_count += 1;
+		println!("Reloaded {}", self.path.display());
+		Ok(true)
+	}
+
+	/// Looks up a function, it must be looked up again after every reload
+	///
+	/// # Safety
+	/// T must be the function pointer type the library exports under the name
+	pub unsafe fn function<T: Copy>(&self, symbol: &str) -> Result<T, HotReloadError> {
+		let library = self.library.as_ref().unwrap();
+		let function = library.get::<T>(symbol.as_bytes()).map_err(|error| HotReloadError::MissingSymbol { symbol: String::from(symbol), error })?;
+		Ok(*function)
+	}
+}
+
+impl Drop for HotReloadLibrary {
+	fn drop(&mut self) {
+		self.library = None;
+		fs::remove_file(&self.loaded_path).ok();
+	}
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Next to the original so it's found the same way, libgame.so becomes libgame-hot3.so
+fn copy_path(path: &Path, reload_count: u32) -> PathBuf {
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+	let file_name = match path.extension() {
+		Some(extension) => format!("{}-hot{}.{}", stem, reload_count, extension.to_string_lossy()),
+		None => format!("{}-hot{}", stem, reload_count)
+	};
+
+	path.with_file_name(file_name)
+}
+
+fn load_copy(path: &Path, reload_count: u32) -> Result<(Library, PathBuf), HotReloadError> {
+	let loaded_path = copy_path(path, reload_count);
+	fs::copy(path, &loaded_path).map_err(|error| HotReloadError::Io { path: path.to_path_buf(), error })?;
+
+	match unsafe { Library::new(&loaded_path) } {
+		Ok(library) => Ok((library, loaded_path)),
+		Err(error) => {
+			fs::remove_file(&loaded_path).ok();
+			Err(HotReloadError::Load { path: path.to_path_buf(), error })
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn copy_paths() {
+		assert_eq!(copy_path(Path::new("target/debug/libgame.so"), 3), Path::new("target/debug/libgame-hot3.so"));
+		assert_eq!(copy_path(Path::new("game"), 0), Path::new("game-hot0"));
+	}
+
+	#[test]
+	fn loading_something_other_than_a_library() {
+		let path = std::env::temp_dir().join("engine_hot_reload_not_a_library.so");
+		fs::write(&path, b"not a library").unwrap();
+
+		assert!(matches!(HotReloadLibrary::load(&path), Err(HotReloadError::Load { .. })));
+		assert!(!copy_path(&path, 0).exists());
+		assert!(matches!(HotReloadLibrary::load("missing.so"), Err(HotReloadError::Io { .. })));
+		fs::remove_file(path).unwrap();
+	}
+}
\ No newline at end of file
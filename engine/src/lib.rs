@@ -3,15 +3,86 @@ pub use glfw;
 pub(crate) mod vulkan;
 pub mod math;
 pub mod pool;
+pub mod binary_reader;
+pub mod vfs;
+pub use vfs::{Vfs, VfsError};
+
+#[cfg(feature = "hot_reload")]
+pub mod hot_reload;
+
+pub mod settings;
+pub use settings::{Settings, SettingsError};
 
 pub mod geometry3d;
 pub use geometry3d::Geometry3D;
 
+pub mod bvh;
+pub use bvh::{Bvh, RayHit};
+
+pub mod spatial_index;
+pub use spatial_index::SpatialIndex;
+
+pub mod lightmap;
+pub use lightmap::{Lightmap, LightmapBaker};
+
+pub mod occlusion_baker;
+pub use occlusion_baker::OcclusionBaker;
+
+pub mod environment_probe;
+pub use environment_probe::{Cubemap, CubemapCapture, EnvironmentProbes};
+
+pub mod gltf_scene;
+pub use gltf_scene::{GltfError, GltfMaterial, GltfNode, GltfPrimitive, GltfScene};
+
+pub mod obj_model;
+pub use obj_model::{ObjError, ObjMaterial, ObjMesh, ObjModel};
+
+pub mod static_batch;
+pub use static_batch::batch_static_meshes;
+
+pub mod asset_streamer;
+pub use asset_streamer::{AssetStreamer, GeometryData};
+
+pub mod world_streamer;
+pub use world_streamer::{WorldStreamer, WorldStreamerChanges};
+
+pub mod animation_clip;
+pub use animation_clip::{AnimationClip, AnimationTrack, Keyframe};
+
 pub mod camera;
 pub use camera::Camera;
 
+pub mod atlas_packer;
+pub use atlas_packer::AtlasPacker;
+
 pub mod font;
-pub use font::Font;
+pub use font::{Font, FontData, FontOptions, generate_font};
+
+pub mod texture;
+pub use texture::Texture;
+
+pub mod font_config;
+pub use font_config::{FontConfig, FontConfigError, FontOutput};
+
+pub mod frame_timings;
+pub use frame_timings::{FrameTimings, FramePhase};
+
+pub mod time_slicer;
+pub use time_slicer::{TaskId, TaskStatus, TimeSlicer};
+
+pub mod wind;
+pub use wind::Wind;
+
+pub mod particle_emitter;
+pub use particle_emitter::ParticleEmitter;
+
+pub mod video;
+pub use video::{ApngVideo, VideoDecoder, VideoPlayer};
+
+pub mod audio;
+
+pub mod color_grading_lut;
+pub use color_grading_lut::{ColorGradingLut, LutError};
 
 pub mod entity;
 pub use entity::Entity;
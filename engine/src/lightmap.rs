@@ -0,0 +1,423 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use crate::{
+	Bvh,
+	Geometry3D,
+	binary_reader::{BinaryReader, BinaryReadError},
+	component::{ComponentList, Light, Transform3DComponentList, light::{AmbientLight, Falloff, PointLight}},
+	geometry3d::Topology,
+	math::{Color, Matrix4, Ray, Vector3, vector3}
+};
+
+const LIGHTMAP_MAGIC: &[u8; 4] = b"VGLM";
+const LIGHTMAP_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum LightmapError {
+	Io { path: PathBuf, error: io::Error },
+	Invalid { reason: String }
+}
+
+impl fmt::Display for LightmapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot access lightmap {}: {}", path.display(), error),
+			Self::Invalid { reason } => write!(f, "Lightmap is invalid: {}", reason)
+		}
+	}
+}
+
+impl Error for LightmapError {}
+
+impl From<BinaryReadError> for LightmapError {
+	fn from(error: BinaryReadError) -> Self {
+		Self::Invalid { reason: error.to_string() }
+	}
+}
+
+// The irradiance reaching each texel of the second UV set, in linear space. The renderer multiplies it with the mesh's
+// color so a lightmapped mesh needs no lights at run time.
+pub struct Lightmap {
+	pub width: usize,
+	pub height: usize,
+	// Row by row starting at v = 0
+	pub texels: Vec<Color>
+}
+
+impl Lightmap {
+	pub fn new(width: usize, height: usize) -> Self {
+		assert!(width > 0 && height > 0, "A lightmap must be at least 1x1");
+
+		Self {
+			width,
+			height,
+			texels: vec![Color::rgb(1.0, 1.0, 1.0); width * height]
+		}
+	}
+
+	pub fn texel(&self, x: usize, y: usize) -> &Color {
+		&self.texels[y * self.width + x]
+	}
+
+	// sRGB 8 bit, which is the format of the texture the renderer samples. Irradiance above 1 is clamped.
+	pub fn to_rgba8(&self) -> Vec<u8> {
+		self.texels.iter().flat_map(|texel| texel.to_srgb8()).collect()
+	}
+
+	// The magic, version, width and height followed by the texels as sRGB 8 bit, little endian
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(16 + self.texels.len() * 4);
+		bytes.extend_from_slice(LIGHTMAP_MAGIC);
+		bytes.extend_from_slice(&LIGHTMAP_VERSION.to_le_bytes());
+		bytes.extend_from_slice(&(self.width as u32).to_le_bytes());
+		bytes.extend_from_slice(&(self.height as u32).to_le_bytes());
+		bytes.extend(self.to_rgba8());
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, LightmapError> {
+		let mut reader = BinaryReader::new(bytes);
+
+		if reader.read_bytes(4)? != LIGHTMAP_MAGIC {
+			return Err(LightmapError::Invalid { reason: String::from("the magic number is wrong") });
+		}
+
+		let version = reader.read_u32()?;
+
+		if version != LIGHTMAP_VERSION {
+			return Err(LightmapError::Invalid { reason: format!("version {} is not supported", version) });
+		}
+
+		let width = reader.read_u32()? as usize;
+		let height = reader.read_u32()? as usize;
+
+		if width == 0 || height == 0 {
+			return Err(LightmapError::Invalid { reason: String::from("the size is zero") });
+		}
+
+		let texels = reader.read_bytes(width * height * 4)?
+			.chunks_exact(4)
+			.map(|texel| Color::from_srgb8(texel[0], texel[1], texel[2], texel[3]))
+			.collect();
+
+		Ok(Self { width, height, texels })
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LightmapError> {
+		let path = path.as_ref();
+		fs::write(path, self.to_bytes()).map_err(|error| LightmapError::Io { path: path.to_path_buf(), error })
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, LightmapError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|error| LightmapError::Io { path: path.to_path_buf(), error })?;
+		Self::from_bytes(&bytes)
+	}
+}
+
+struct BakeTriangle {
+	positions: [Vector3; 3],
+	normals: [Vector3; 3],
+	uvs: [[f32; 2]; 3]
+}
+
+struct BakeLight {
+	position: Vector3,
+	color: Vector3,
+	range: f32,
+	falloff: Falloff
+}
+
+// Bakes direct lighting with hard shadows into a lightmap offline. Every geometry added shares the lightmap so their second
+// UV sets must not overlap each other. Each texel covered by a triangle is lit at the point it lands on, uncovered texels
+// next to covered ones are filled in afterwards so bilinear filtering doesn't bleed black into the edges of UV islands.
+pub struct LightmapBaker {
+	width: usize,
+	height: usize,
+	// How many texels are filled in around UV islands
+	pub padding: usize,
+	// How far along the normal shadow rays start so surfaces don't shadow themselves
+	pub shadow_bias: f32,
+	triangles: Vec<BakeTriangle>,
+	lights: Vec<BakeLight>,
+	ambient: Vector3
+}
+
+impl LightmapBaker {
+	pub fn new(width: usize, height: usize) -> Self {
+		assert!(width > 0 && height > 0, "A lightmap must be at least 1x1");
+
+		Self {
+			width,
+			height,
+			padding: 2,
+			shadow_bias: 0.001,
+			triangles: Vec::new(),
+			lights: Vec::new(),
+			ambient: vector3::ZERO
+		}
+	}
+
+	// The geometry both receives light and casts shadows
+	pub fn add_geometry(&mut self, geometry: &Geometry3D, global_matrix: &Matrix4) {
+		assert!(matches!(geometry.topology(), Topology::Triangle), "Only triangle geometry can be lightmapped");
+		assert!(!geometry.uvs2().is_empty(), "Geometry must have a second UV set to be lightmapped");
+
+		let mut normal_matrix = *global_matrix;
+		normal_matrix.invert();
+		normal_matrix.transpose();
+
+		let attributes = geometry.attributes();
+		let uvs2 = geometry.uvs2();
+
+		for triangle in geometry.indices().chunks_exact(3) {
+			let mut positions = [vector3::ZERO; 3];
+			let mut normals = [vector3::ZERO; 3];
+			let mut uvs = [[0.0; 2]; 3];
+
+			for (corner, index) in triangle.iter().enumerate() {
+				let offset = *index as usize * 6;
+				positions[corner] = Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2]);
+				positions[corner].apply_matrix4(global_matrix);
+				normals[corner] = Vector3::new(attributes[offset + 3], attributes[offset + 4], attributes[offset + 5]);
+				normals[corner].transform_direction(&normal_matrix);
+				normals[corner].normalize();
+				uvs[corner] = [uvs2[*index as usize * 2], uvs2[*index as usize * 2 + 1]];
+			}
+
+			self.triangles.push(BakeTriangle { positions, normals, uvs });
+		}
+	}
+
+	pub fn add_point_light(&mut self, position: Vector3, point_light: &PointLight) {
+		self.lights.push(BakeLight {
+			position,
+			color: point_light.color.to_vector3() * point_light.intensity,
+			range: point_light.range,
+			falloff: point_light.falloff
+		});
+	}
+
+	pub fn add_ambient_light(&mut self, ambient_light: &AmbientLight) {
+		self.ambient += ambient_light.color.to_vector3() * ambient_light.intensity;
+	}
+
+	// Every light in the scene, the point lights need a transform
+	pub fn add_lights(&mut self, light_components: &ComponentList<Light>, transform3d_components: &Transform3DComponentList) {
+		for (entity, light) in light_components.iter() {
+			match light {
+				Light::AmbientLight(ambient_light) => self.add_ambient_light(ambient_light),
+				Light::PointLight(point_light) => {
+					let position = transform3d_components.borrow(entity).global_matrix().extract_position();
+					self.add_point_light(position, point_light);
+				}
+			}
+		}
+	}
+
+	pub fn bake(&self) -> Lightmap {
+		let bvh = Bvh::from_triangles(self.triangles.iter().map(|triangle| triangle.positions).collect());
+		let mut irradiance = vec![vector3::ZERO; self.width * self.height];
+		let mut covered = vec![false; self.width * self.height];
+
+		for triangle in &self.triangles {
+			let texel_positions = triangle.uvs.map(|[u, v]| [u * self.width as f32, v * self.height as f32]);
+			let [a, b, c] = texel_positions;
+			let area = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+
+			// Degenerate in UV space so it covers no texels
+			if area.abs() < 1e-12 {
+				continue;
+			}
+
+			let min_x = (a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize).min(self.width - 1);
+			let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(self.width);
+			let min_y = (a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize).min(self.height - 1);
+			let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(self.height);
+
+			for y in min_y..max_y {
+				for x in min_x..max_x {
+					let point = [x as f32 + 0.5, y as f32 + 0.5];
+					let edge = |p: [f32; 2], q: [f32; 2]| ((q[0] - p[0]) * (point[1] - p[1]) - (point[0] - p[0]) * (q[1] - p[1])) / area;
+					let weights = [edge(b, c), edge(c, a), edge(a, b)];
+
+					if weights.iter().any(|weight| *weight < -1e-5) {
+						continue;
+					}
+
+					let mut position = vector3::ZERO;
+					let mut normal = vector3::ZERO;
+
+					for (corner, weight) in weights.iter().enumerate() {
+						position += triangle.positions[corner] * *weight;
+						normal += triangle.normals[corner] * *weight;
+					}
+
+					normal.normalize();
+
+					let index = y * self.width + x;
+					irradiance[index] = self.irradiance(&bvh, &position, &normal);
+					covered[index] = true;
+				}
+			}
+		}
+
+		self.dilate(&mut irradiance, &mut covered);
+
+		let texels = irradiance.iter().map(|texel| Color::rgb(texel.x, texel.y, texel.z)).collect();
+		Lightmap { width: self.width, height: self.height, texels }
+	}
+
+	// Lambert lighting with the same falloff as the renderer, a point light only counts if nothing is between it and the point
+	fn irradiance(&self, bvh: &Bvh, position: &Vector3, normal: &Vector3) -> Vector3 {
+		let mut irradiance = self.ambient;
+		let origin = position + normal * self.shadow_bias;
+
+		for light in &self.lights {
+			let mut direction = light.position - position;
+			let distance = direction.length();
+
+			if distance >= light.range || distance <= 0.0 {
+				continue;
+			}
+
+			direction /= distance;
+			let lambert = normal.dot(&direction);
+
+			if lambert <= 0.0 {
+				continue;
+			}
+
+			let attenuation = match light.falloff {
+				Falloff::None => 1.0,
+				Falloff::Linear => 1.0 - distance / light.range,
+				Falloff::InverseSquare => {
+					let window = (1.0 - (distance / light.range).powi(4)).clamp(0.0, 1.0);
+					window * window / (distance * distance).max(0.0001)
+				}
+			};
+
+			if bvh.intersects_ray(&Ray::new(origin, direction), light.position.distance(&origin) - self.shadow_bias) {
+				continue;
+			}
+
+			irradiance += light.color * (lambert * attenuation);
+		}
+
+		irradiance
+	}
+
+	// Each pass fills the uncovered texels next to covered ones with the average of those neighbors
+	fn dilate(&self, irradiance: &mut [Vector3], covered: &mut [bool]) {
+		for _ in 0..self.padding {
+			let previous_covered = covered.to_vec();
+
+			for y in 0..self.height {
+				for x in 0..self.width {
+					if previous_covered[y * self.width + x] {
+						continue;
+					}
+
+					let mut sum = vector3::ZERO;
+					let mut count = 0;
+
+					for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)] {
+						let (nx, ny) = (x as isize + dx, y as isize + dy);
+
+						if nx < 0 || ny < 0 || nx >= self.width as isize || ny >= self.height as isize {
+							continue;
+						}
+
+						let neighbor = ny as usize * self.width + nx as usize;
+
+						if previous_covered[neighbor] {
+							sum += irradiance[neighbor];
+							count += 1;
+						}
+					}
+
+					if count > 0 {
+						irradiance[y * self.width + x] = sum / count as f32;
+						covered[y * self.width + x] = true;
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{matrix4, quaternion};
+
+	// A 2x2 plane facing up with the second UV set the same as the first
+	fn plane() -> Geometry3D {
+		let mut geometry = Geometry3D::create_plane();
+		let uvs = geometry.uvs().to_vec();
+		geometry.set_uvs2(uvs);
+		geometry
+	}
+
+	#[test]
+	fn bake_with_shadows() {
+		let geometry = plane();
+		let normal = vector3::UNIT_Y;
+		let mut light = PointLight::new(Color::rgb(1.0, 0.5, 0.25), 2.0);
+		light.range = 10.0;
+
+		let mut baker = LightmapBaker::new(8, 8);
+		baker.add_geometry(&geometry, &matrix4::IDENTITY);
+		baker.add_point_light(normal * 4.0, &light);
+		baker.add_ambient_light(&AmbientLight { color: Color::rgb(0.1, 0.1, 0.1), intensity: 1.0 });
+		let lit = baker.bake();
+
+		// The texel under the light is lit fully, the corners are further away and at an angle
+		let center = lit.texel(4, 4);
+		assert!((center.r - 2.1).abs() < 0.05 && (center.g - 1.1).abs() < 0.05);
+		assert!(lit.texel(0, 0).r < center.r);
+
+		// A box between the light and the middle of the plane
+		let mut occluder_matrix = Matrix4::default();
+		occluder_matrix.compose(&(normal * 2.0), &quaternion::ZERO, &Vector3::from_scalar(0.2));
+		let mut occluder = Geometry3D::create_box();
+		let uvs = occluder.uvs().iter().map(|uv| uv * 0.0).collect();
+		occluder.set_uvs2(uvs);
+		baker.add_geometry(&occluder, &occluder_matrix);
+
+		let shadowed = baker.bake();
+		assert!((shadowed.texel(4, 4).r - 0.1).abs() < 1e-4);
+		assert!((shadowed.texel(0, 0).r - lit.texel(0, 0).r).abs() < 1e-4);
+	}
+
+	#[test]
+	fn dilation() {
+		// Only covers the lower left half of the lightmap
+		let mut geometry = plane();
+		let uvs = geometry.uvs().iter().map(|uv| uv * 0.5).collect();
+		geometry.set_uvs2(uvs);
+
+		let mut baker = LightmapBaker::new(8, 8);
+		baker.padding = 1;
+		baker.add_geometry(&geometry, &matrix4::IDENTITY);
+		baker.add_ambient_light(&AmbientLight { color: Color::rgb(1.0, 1.0, 1.0), intensity: 0.5 });
+		let lightmap = baker.bake();
+
+		assert_eq!(lightmap.texel(4, 4).r, 0.5);
+		assert_eq!(lightmap.texel(6, 6).r, 0.0);
+	}
+
+	#[test]
+	fn bytes_roundtrip() {
+		let mut lightmap = Lightmap::new(3, 2);
+		lightmap.texels[4] = Color::rgb(0.25, 0.5, 0.0);
+
+		let bytes = lightmap.to_bytes();
+		let loaded = Lightmap::from_bytes(&bytes).unwrap();
+		assert_eq!((loaded.width, loaded.height), (3, 2));
+		assert_eq!(loaded.texel(1, 1).to_srgb8(), lightmap.texel(1, 1).to_srgb8());
+		assert_eq!(loaded.texel(0, 0), &Color::rgb(1.0, 1.0, 1.0));
+
+		assert!(matches!(Lightmap::from_bytes(&bytes[..bytes.len() - 1]), Err(LightmapError::Invalid { .. })));
+		assert!(matches!(Lightmap::from_bytes(b"NOPE"), Err(LightmapError::Invalid { .. })));
+	}
+}
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::Vector3;
+use super::{Vector3, Matrix4};
 
 pub const DEFAULT_SQUARE: Box3 = Box3 {
 	min: Vector3 { x: -1.0, y: -1.0, z: -1.0 },
@@ -21,6 +21,66 @@ impl Box3 {
 		self.max = max;
 	}
 
+	// The smallest box containing the points, the points must not be empty
+	pub fn from_points(points: &[Vector3]) -> Self {
+		assert!(!points.is_empty(), "Cannot create a box from no points");
+		let mut box3 = Self::new(points[0], points[0]);
+
+		for point in &points[1..] {
+			box3.expand_by_point(point);
+		}
+
+		box3
+	}
+
+	pub fn center(&self) -> Vector3 {
+		(self.min + self.max) * 0.5
+	}
+
+	pub fn half_size(&self) -> Vector3 {
+		(self.max - self.min) * 0.5
+	}
+
+	pub fn expand_by_point(&mut self, point: &Vector3) {
+		self.min.min(point);
+		self.max.max(point);
+	}
+
+	pub fn union(&mut self, other: &Self) {
+		self.min.min(&other.min);
+		self.max.max(&other.max);
+	}
+
+	pub fn contains_point(&self, point: &Vector3) -> bool {
+		point.x >= self.min.x && point.x <= self.max.x
+			&& point.y >= self.min.y && point.y <= self.max.y
+			&& point.z >= self.min.z && point.z <= self.max.z
+	}
+
+	pub fn intersects_box(&self, other: &Self) -> bool {
+		self.min.x <= other.max.x && self.max.x >= other.min.x
+			&& self.min.y <= other.max.y && self.max.y >= other.min.y
+			&& self.min.z <= other.max.z && self.max.z >= other.min.z
+	}
+
+	pub fn clamp_point(&self, point: &Vector3) -> Vector3 {
+		Vector3::new(
+			point.x.clamp(self.min.x, self.max.x),
+			point.y.clamp(self.min.y, self.max.y),
+			point.z.clamp(self.min.z, self.max.z))
+	}
+
+	// Becomes the box containing the transformed corners so it grows when rotated
+	pub fn apply_matrix4(&mut self, m: &Matrix4) {
+		let mut vertices = self.as_vertices();
+
+		for vertex in &mut vertices {
+			vertex.apply_matrix4(m);
+		}
+
+		*self = Self::from_points(&vertices);
+	}
+
 	pub fn as_vertices(&self) -> [Vector3; 8] {
 		let min = &self.min;
 		let max = &self.max;
@@ -41,6 +101,7 @@ impl Box3 {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::math::{Quaternion, assert_approx_eq};
 
 	#[test]
 	fn new() {
@@ -61,6 +122,29 @@ mod tests {
 		assert_eq!(b, Box3 { min, max });
 	}
 
+	#[test]
+	fn union_and_intersection() {
+		let mut a = Box3::from_points(&[Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, -1.0, 1.0)]);
+		let b = Box3::new(Vector3::from_scalar(1.5), Vector3::from_scalar(3.0));
+		assert_eq!(a, Box3::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(2.0, 0.0, 1.0)));
+		assert!(!a.intersects_box(&b));
+
+		a.union(&b);
+		assert!(a.contains_point(&Vector3::new(2.5, -0.5, 2.0)));
+		assert_eq!(a.center(), Vector3::new(1.5, 1.0, 1.5));
+	}
+
+	#[test]
+	fn apply_matrix4() {
+		let mut matrix = Matrix4::default();
+		matrix.compose(&Vector3::new(1.0, 0.0, 0.0), &Quaternion::new(0.0, 0.0, std::f32::consts::FRAC_1_SQRT_2, std::f32::consts::FRAC_1_SQRT_2), &Vector3::new(2.0, 1.0, 1.0));
+
+		let mut b = DEFAULT_SQUARE;
+		b.apply_matrix4(&matrix);
+		assert_approx_eq(&b.min, &Vector3::new(0.0, -2.0, -1.0), 1e-5);
+		assert_approx_eq(&b.max, &Vector3::new(2.0, 2.0, 1.0), 1e-5);
+	}
+
 	#[test]
 	fn as_vertices() {
 		let expected = [
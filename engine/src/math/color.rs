@@ -0,0 +1,167 @@
+use auto_ops::impl_op_ex;
+use super::{Vector3, ApproxEq};
+
+pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+pub const RED: Color = Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+pub const GREEN: Color = Color { r: 0.0, g: 1.0, b: 0.0, a: 1.0 };
+pub const BLUE: Color = Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+pub const TRANSPARENT: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+
+// A color in linear space, which is what lighting and blending work in. Colors picked in an image editor or color picker are
+// sRGB and need to be converted with the from_srgb functions. Alpha is always linear.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct Color {
+	pub r: f32,
+	pub g: f32,
+	pub b: f32,
+	pub a: f32
+}
+
+impl Color {
+	pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+		Self { r, g, b, a }
+	}
+
+	pub fn rgb(r: f32, g: f32, b: f32) -> Self {
+		Self { r, g, b, a: 1.0 }
+	}
+
+	pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+		Self::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+	}
+
+	pub fn from_srgb8(r: u8, g: u8, b: u8, a: u8) -> Self {
+		Self::from_srgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0)
+	}
+
+	// An opaque sRGB color written 0xRRGGBB
+	pub fn from_hex(hex: u32) -> Self {
+		Self::from_srgb8((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255)
+	}
+
+	// The hue is in degrees, the saturation and value are in [0, 1]. Like color pickers the result is taken as sRGB.
+	pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+		let hue = hue.rem_euclid(360.0) / 60.0;
+		let chroma = value * saturation;
+		let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+
+		let (r, g, b) = match hue as u32 {
+			0 => (chroma, x, 0.0),
+			1 => (x, chroma, 0.0),
+			2 => (0.0, chroma, x),
+			3 => (0.0, x, chroma),
+			4 => (x, 0.0, chroma),
+			_ => (chroma, 0.0, x)
+		};
+
+		let m = value - chroma;
+		Self::from_srgb(r + m, g + m, b + m, 1.0)
+	}
+
+	pub fn to_srgb(&self) -> [f32; 4] {
+		[linear_to_srgb(self.r), linear_to_srgb(self.g), linear_to_srgb(self.b), self.a]
+	}
+
+	pub fn to_srgb8(&self) -> [u8; 4] {
+		let srgb = self.to_srgb();
+		let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+		[to_u8(srgb[0]), to_u8(srgb[1]), to_u8(srgb[2]), to_u8(srgb[3])]
+	}
+
+	pub fn with_alpha(&self, a: f32) -> Self {
+		Self { a, ..*self }
+	}
+
+	pub fn lerp(&self, other: &Self, t: f32) -> Self {
+		*self + (*other - *self) * t
+	}
+
+	// The color channels without alpha, as the shaders take light colors
+	pub fn to_vector3(&self) -> Vector3 {
+		Vector3::new(self.r, self.g, self.b)
+	}
+
+	pub fn to_array(&self) -> [f32; 4] {
+		[self.r, self.g, self.b, self.a]
+	}
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+	if value <= 0.04045 {
+		value / 12.92
+	}
+	else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+	if value <= 0.0031308 {
+		value * 12.92
+	}
+	else {
+		1.055 * value.powf(1.0 / 2.4) - 0.055
+	}
+}
+
+impl_op_ex!(+ |a: &Color, b: &Color| -> Color {
+	Color::new(a.r + b.r, a.g + b.g, a.b + b.b, a.a + b.a)
+});
+
+impl_op_ex!(- |a: &Color, b: &Color| -> Color {
+	Color::new(a.r - b.r, a.g - b.g, a.b - b.b, a.a - b.a)
+});
+
+// Modulates one color by another
+impl_op_ex!(* |a: &Color, b: &Color| -> Color {
+	Color::new(a.r * b.r, a.g * b.g, a.b * b.b, a.a * b.a)
+});
+
+impl_op_ex!(* |a: &Color, b: f32| -> Color {
+	Color::new(a.r * b, a.g * b, a.b * b, a.a * b)
+});
+
+impl ApproxEq for Color {
+	fn approx_eq(&self, other: &Self, tol: f32) -> bool {
+		(self.r - other.r).abs() <= tol
+			&& (self.g - other.g).abs() <= tol
+			&& (self.b - other.b).abs() <= tol
+			&& (self.a - other.a).abs() <= tol
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::assert_approx_eq;
+
+	#[test]
+	fn srgb() {
+		let color = Color::from_srgb(0.5, 0.0, 1.0, 0.5);
+		assert_approx_eq(&color, &Color::new(0.21404, 0.0, 1.0, 0.5), 1e-5);
+		assert_approx_eq(&Color::from_hex(0x808080), &Color::rgb(0.21586, 0.21586, 0.21586), 1e-5);
+
+		for value in 0..=255 {
+			let color = Color::from_srgb8(value, 255 - value, value / 2, value);
+			assert_eq!(color.to_srgb8(), [value, 255 - value, value / 2, value]);
+		}
+	}
+
+	#[test]
+	fn from_hsv() {
+		assert_approx_eq(&Color::from_hsv(0.0, 1.0, 1.0), &RED, 1e-6);
+		assert_approx_eq(&Color::from_hsv(120.0, 1.0, 1.0), &GREEN, 1e-6);
+		assert_approx_eq(&Color::from_hsv(600.0, 1.0, 1.0), &BLUE, 1e-6);
+		assert_approx_eq(&Color::from_hsv(60.0, 0.0, 1.0), &WHITE, 1e-6);
+		assert_eq!(Color::from_hsv(30.0, 1.0, 1.0).to_srgb8(), [255, 128, 0, 255]);
+	}
+
+	#[test]
+	fn lerp() {
+		assert_approx_eq(&BLACK.lerp(&WHITE, 0.25), &Color::rgb(0.25, 0.25, 0.25), 1e-6);
+		assert_approx_eq(&RED.lerp(&TRANSPARENT, 1.0), &TRANSPARENT, 1e-6);
+		assert_eq!((RED * Color::rgb(0.5, 1.0, 1.0)).to_vector3(), Vector3::new(0.5, 0.0, 0.0));
+	}
+}
\ No newline at end of file
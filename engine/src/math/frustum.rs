@@ -0,0 +1,134 @@
+use super::{Vector3, Vector4, Matrix4, Box3, Plane, Sphere};
+
+// The volume a projection or view projection matrix transforms into clip space, with the depth range being zero to one. The
+// plane normals point inwards and are unit length so distances to them are in world units.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+	// Left, right, bottom, top, near and far
+	pub planes: [Plane; 6]
+}
+
+impl Frustum {
+	// Gribb and Hartmann's method, the planes are combinations of the matrix rows
+	pub fn from_matrix(matrix: &Matrix4) -> Self {
+		let [r0, r1, r2, r3] = matrix.elements;
+		let plane = |row: [f32; 4], sign: f32, base: [f32; 4]| {
+			let mut plane = Plane::new(
+				Vector3::new(base[0] + row[0] * sign, base[1] + row[1] * sign, base[2] + row[2] * sign),
+				base[3] + row[3] * sign);
+
+			plane.normalize();
+			plane
+		};
+
+		Self {
+			planes: [
+				plane(r0, 1.0, r3),
+				plane(r0, -1.0, r3),
+				plane(r1, 1.0, r3),
+				plane(r1, -1.0, r3),
+				plane(r2, 1.0, [0.0; 4]),
+				plane(r2, -1.0, r3)
+			]
+		}
+	}
+
+	// The corners of the volume the matrix transforms into clip space, near then far, each counter clockwise from the
+	// bottom left in clip space
+	pub fn corners(matrix: &Matrix4) -> [Vector3; 8] {
+		let mut inverse_matrix = *matrix;
+		inverse_matrix.invert();
+
+		let mut corners = [Vector3::default(); 8];
+		let clip_corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+		for (index, corner) in corners.iter_mut().enumerate() {
+			let (x, y) = clip_corners[index % 4];
+			let z = (index / 4) as f32;
+			let point = inverse_matrix * Vector4::new(x, y, z, 1.0);
+			*corner = Vector3::new(point.x / point.w, point.y / point.w, point.z / point.w);
+		}
+
+		corners
+	}
+
+	pub fn contains_point(&self, point: &Vector3) -> bool {
+		self.planes.iter().all(|plane| plane.distance_to_point(point) >= 0.0)
+	}
+
+	// Conservative near the edges, see Sphere::intersects_planes
+	pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+		sphere.intersects_planes(&self.planes)
+	}
+
+	// Tests the corner of the box furthest along each plane's normal, conservative near the edges like the sphere test
+	pub fn intersects_box(&self, box3: &Box3) -> bool {
+		self.planes.iter().all(|plane| {
+			let furthest_corner = Vector3::new(
+				if plane.normal.x > 0.0 { box3.max.x } else { box3.min.x },
+				if plane.normal.y > 0.0 { box3.max.y } else { box3.min.y },
+				if plane.normal.z > 0.0 { box3.max.z } else { box3.min.z });
+
+			plane.distance_to_point(&furthest_corner) >= 0.0
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{matrix4, assert_approx_eq};
+
+	// The camera looks down positive z
+	fn perspective_frustum() -> Frustum {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_perspective(1.0, 90.0, 0.1, 100.0);
+		Frustum::from_matrix(&projection_matrix)
+	}
+
+	#[test]
+	fn points() {
+		let frustum = perspective_frustum();
+
+		assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, 10.0)));
+		assert!(frustum.contains_point(&Vector3::new(9.0, -9.0, 10.0)));
+		assert!(!frustum.contains_point(&Vector3::new(11.0, 0.0, 10.0)));
+		assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, 0.05)));
+		assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, 101.0)));
+		assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -10.0)));
+	}
+
+	#[test]
+	fn spheres() {
+		let frustum = perspective_frustum();
+
+		assert!(frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 10.0), 1.0)));
+		assert!(!frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, -10.0), 5.0)));
+		assert!(frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, -10.0), 15.0)));
+		assert!(!frustum.intersects_sphere(&Sphere::new(Vector3::new(30.0, 0.0, 10.0), 5.0)));
+		assert!(!frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 120.0), 10.0)));
+		assert!(frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, -10.0), f32::INFINITY)));
+	}
+
+	#[test]
+	fn boxes() {
+		let frustum = perspective_frustum();
+
+		assert!(frustum.intersects_box(&Box3::new(Vector3::new(-1.0, -1.0, 5.0), Vector3::new(1.0, 1.0, 6.0))));
+		assert!(frustum.intersects_box(&Box3::new(Vector3::new(-100.0, -100.0, -100.0), Vector3::new(100.0, 100.0, 200.0))));
+		assert!(frustum.intersects_box(&Box3::new(Vector3::new(9.0, 0.0, 10.0), Vector3::new(20.0, 1.0, 11.0))));
+		assert!(!frustum.intersects_box(&Box3::new(Vector3::new(12.0, 0.0, 10.0), Vector3::new(20.0, 1.0, 11.0))));
+		assert!(!frustum.intersects_box(&Box3::new(Vector3::new(-1.0, -1.0, -6.0), Vector3::new(1.0, 1.0, -5.0))));
+	}
+
+	#[test]
+	fn corners() {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_orthographic(4.0, 2.0, 1.0, 10.0);
+		let corners = Frustum::corners(&projection_matrix);
+
+		assert_approx_eq(&Box3::from_points(&corners).min, &Vector3::new(-2.0, -1.0, 1.0), 1e-5);
+		assert_approx_eq(&Box3::from_points(&corners).max, &Vector3::new(2.0, 1.0, 10.0), 1e-5);
+		assert!(corners.iter().all(|corner| Frustum::from_matrix(&projection_matrix).planes.iter().all(|plane| plane.distance_to_point(corner) > -1e-4)));
+	}
+}
\ No newline at end of file
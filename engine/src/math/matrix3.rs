@@ -48,6 +48,39 @@ impl Matrix3 {
 		se[2][1] = 0.0;
 		se[2][2] = 1.0;
 	}
+
+	pub fn invert(&mut self) {
+		let m = &mut self.elements;
+
+		let (m00, m01, m02) = (m[0][0], m[0][1], m[0][2]);
+		let (m10, m11, m12) = (m[1][0], m[1][1], m[1][2]);
+		let (m20, m21, m22) = (m[2][0], m[2][1], m[2][2]);
+
+		let t00 = m11 * m22 - m12 * m21;
+		let t10 = m12 * m20 - m10 * m22;
+		let t20 = m10 * m21 - m11 * m20;
+
+		let det = m00 * t00 + m01 * t10 + m02 * t20;
+
+		if det == 0.0 {
+			*m = IDENTITY.elements;
+			return;
+		}
+
+		let det_rec = 1.0 / det;
+
+		m[0][0] = t00 * det_rec;
+		m[0][1] = (m02 * m21 - m01 * m22) * det_rec;
+		m[0][2] = (m01 * m12 - m02 * m11) * det_rec;
+
+		m[1][0] = t10 * det_rec;
+		m[1][1] = (m00 * m22 - m02 * m20) * det_rec;
+		m[1][2] = (m02 * m10 - m00 * m12) * det_rec;
+
+		m[2][0] = t20 * det_rec;
+		m[2][1] = (m01 * m20 - m00 * m21) * det_rec;
+		m[2][2] = (m00 * m11 - m01 * m10) * det_rec;
+	}
 }
 
 impl_op_ex!(+ |a: &Matrix3, b: &Matrix3| -> Matrix3 {
@@ -214,6 +247,24 @@ mod tests {
 		assert_approx_eq(&m, &expected, 1e-6);
 	}
 
+	#[test]
+	fn invert() {
+		let mut m = IDENTITY;
+		m.compose(&Vector2::new(100.0, 200.0), 0.5, &Vector2::new(3.0, 4.0));
+		let mut inverse = m;
+		inverse.invert();
+		assert_approx_eq(&(m * inverse), &IDENTITY, 1e-4);
+
+		// Singular matrices become the identity
+		let mut m = Matrix3::new([
+			[1.0, 2.0, 3.0],
+			[4.0, 5.0, 6.0],
+			[7.0, 8.0, 9.0]]);
+
+		m.invert();
+		assert_eq!(m, IDENTITY);
+	}
+
 	#[test]
 	fn add() {
 		let a = Matrix3::new([
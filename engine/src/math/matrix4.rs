@@ -124,6 +124,38 @@ impl Matrix4 {
 		se[3][3] = 1.0;
 	}
 
+	// Undoes compose, the matrix must not contain any shear
+	pub fn decompose(&self) -> (Vector3, Quaternion, Vector3) {
+		let se = &self.elements;
+		let mut scale = Vector3::new(
+			Vector3::new(se[0][0], se[1][0], se[2][0]).length(),
+			Vector3::new(se[0][1], se[1][1], se[2][1]).length(),
+			Vector3::new(se[0][2], se[1][2], se[2][2]).length());
+
+		// A negative determinant means the matrix mirrors, which is put on the x axis
+		let determinant =
+			se[0][0] * (se[1][1] * se[2][2] - se[1][2] * se[2][1]) -
+			se[0][1] * (se[1][0] * se[2][2] - se[1][2] * se[2][0]) +
+			se[0][2] * (se[1][0] * se[2][1] - se[1][1] * se[2][0]);
+
+		if determinant < 0.0 {
+			scale.x = -scale.x;
+		}
+
+		let mut rotation = *self;
+
+		for row in &mut rotation.elements[0..3] {
+			row[0] /= scale.x;
+			row[1] /= scale.y;
+			row[2] /= scale.z;
+		}
+
+		let mut orientation = Quaternion::default();
+		orientation.set_from_rotation_matrix(&rotation);
+
+		(self.extract_position(), orientation, scale)
+	}
+
 	pub fn extract_position(&self) -> Vector3 {
 		let se = &self.elements;
 		Vector3::new(se[0][3], se[1][3], se[2][3])
@@ -155,6 +187,31 @@ impl Matrix4 {
 		se[3][3] = 0.0;
 	}
 
+	pub fn make_orthographic(&mut self, width: f32, height: f32, near: f32, far: f32) {
+		let d = far - near;
+		let se = &mut self.elements;
+
+		se[0][0] = -2.0 / width;
+		se[0][1] = 0.0;
+		se[0][2] = 0.0;
+		se[0][3] = 0.0;
+
+		se[1][0] = 0.0;
+		se[1][1] = -2.0 / height;
+		se[1][2] = 0.0;
+		se[1][3] = 0.0;
+
+		se[2][0] = 0.0;
+		se[2][1] = 0.0;
+		se[2][2] = 1.0 / d;
+		se[2][3] = -near / d;
+
+		se[3][0] = 0.0;
+		se[3][1] = 0.0;
+		se[3][2] = 0.0;
+		se[3][3] = 1.0;
+	}
+
 	pub fn make_orientation_from_quaternion(&mut self, q: &Quaternion) {
 		self.compose(&vector3::ZERO, q, &vector3::ONE);
 	}
@@ -501,6 +558,21 @@ mod tests {
 		assert_eq!(m, expected);
 	}
 
+	#[test]
+	fn decompose() {
+		let pos = Vector3::new(1.0, 2.0, 3.0);
+		let mut rot = Quaternion::new(1.0, 2.0, 3.0, 4.0);
+		rot.normalize();
+		let scale = Vector3::new(-3.0, 4.0, 5.0);
+		let mut m = IDENTITY;
+		m.compose(&pos, &rot, &scale);
+
+		let (decomposed_pos, decomposed_rot, decomposed_scale) = m.decompose();
+		assert_approx_eq(&decomposed_pos, &pos, 1e-6);
+		assert_approx_eq(&decomposed_rot, &rot, 1e-6);
+		assert_approx_eq(&decomposed_scale, &scale, 1e-5);
+	}
+
 	#[test]
 	fn extract_position() {
 		let m = Matrix4::new([
@@ -526,6 +598,20 @@ mod tests {
 		assert_eq!(m, expected);
 	}
 
+	#[test]
+	fn make_orthographic() {
+		let mut m = IDENTITY;
+		m.make_orthographic(4.0, 2.0, 1.0, 5.0);
+
+		let expected = Matrix4::new([
+			[-0.5, 0.0, 0.0, 0.0],
+			[0.0, -1.0, 0.0, 0.0],
+			[0.0, 0.0, 0.25, -0.25],
+			[0.0, 0.0, 0.0, 1.0]]);
+
+		assert_eq!(m, expected);
+	}
+
 	#[test]
 	fn make_orientation_from_quaternion() {
 		let mut m = IDENTITY;
@@ -23,6 +23,33 @@ pub use euler::Euler;
 pub mod box3;
 pub use box3::Box3;
 
+pub mod plane;
+pub use plane::Plane;
+
+pub mod ray;
+pub use ray::Ray;
+
+pub mod sphere;
+pub use sphere::Sphere;
+
+pub mod obb;
+pub use obb::Obb;
+
+pub mod frustum;
+pub use frustum::Frustum;
+
+pub mod spline;
+pub use spline::{Spline, SplineKind};
+
+pub mod noise;
+pub use noise::{Noise, Fbm};
+
+pub mod rng;
+pub use rng::Rng;
+
+pub mod color;
+pub use color::Color;
+
 use std::fmt::Debug;
 
 pub trait ApproxEq {
@@ -0,0 +1,275 @@
+use super::{Rng, Vector2, Vector3};
+
+// Skew factors which map between the simplex grid and the square grid
+const F2: f32 = 0.36602542; // (sqrt(3) - 1) / 2
+const G2: f32 = 0.21132487; // (3 - sqrt(3)) / 6
+const F3: f32 = 1.0 / 3.0;
+const G3: f32 = 1.0 / 6.0;
+
+// The edge midpoints of a cube, used as the gradients in 3D. The 2D gradients are the first 8 with z dropped.
+const GRADIENTS3: [[f32; 3]; 12] = [
+	[1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+	[1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+	[0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0]
+];
+
+// Scales the raw sums so the results span about [-1, 1]
+const PERLIN2_SCALE: f32 = 1.0 / std::f32::consts::SQRT_2;
+const SIMPLEX2_SCALE: f32 = 70.0;
+const SIMPLEX3_SCALE: f32 = 32.0;
+
+// Gradient noise, all functions return values in about [-1, 1] and return 0 on integer coordinates for Perlin noise. The same
+// seed always gives the same noise.
+pub struct Noise {
+	permutation: [u8; 512]
+}
+
+impl Noise {
+	pub fn new(seed: u64) -> Self {
+		let mut table: [u8; 256] = [0; 256];
+
+		for (index, value) in table.iter_mut().enumerate() {
+			*value = index as u8;
+		}
+
+		Rng::new(seed).shuffle(&mut table);
+
+		// Doubled so lookups of a hash plus a coordinate don't need wrapping
+		let mut permutation = [0; 512];
+
+		for (index, value) in permutation.iter_mut().enumerate() {
+			*value = table[index & 255];
+		}
+
+		Self {
+			permutation
+		}
+	}
+
+	fn hash(&self, x: i32, y: i32, z: i32) -> usize {
+		let x = self.permutation[(x & 255) as usize] as i32;
+		let y = self.permutation[((x + y) & 255) as usize] as i32;
+		self.permutation[((y + z) & 255) as usize] as usize
+	}
+
+	fn gradient2(&self, hash: usize, x: f32, y: f32) -> f32 {
+		let gradient = GRADIENTS3[hash & 7];
+		gradient[0] * x + gradient[1] * y
+	}
+
+	fn gradient3(&self, hash: usize, x: f32, y: f32, z: f32) -> f32 {
+		let gradient = GRADIENTS3[hash % 12];
+		gradient[0] * x + gradient[1] * y + gradient[2] * z
+	}
+
+	pub fn perlin2(&self, point: &Vector2) -> f32 {
+		let (xi, yi) = (point.x.floor(), point.y.floor());
+		let (x, y) = (point.x - xi, point.y - yi);
+		let (xi, yi) = (xi as i32, yi as i32);
+		let (u, v) = (fade(x), fade(y));
+
+		let n00 = self.gradient2(self.hash(xi, yi, 0), x, y);
+		let n10 = self.gradient2(self.hash(xi + 1, yi, 0), x - 1.0, y);
+		let n01 = self.gradient2(self.hash(xi, yi + 1, 0), x, y - 1.0);
+		let n11 = self.gradient2(self.hash(xi + 1, yi + 1, 0), x - 1.0, y - 1.0);
+
+		lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * PERLIN2_SCALE
+	}
+
+	pub fn perlin3(&self, point: &Vector3) -> f32 {
+		let (xi, yi, zi) = (point.x.floor(), point.y.floor(), point.z.floor());
+		let (x, y, z) = (point.x - xi, point.y - yi, point.z - zi);
+		let (xi, yi, zi) = (xi as i32, yi as i32, zi as i32);
+		let (u, v, w) = (fade(x), fade(y), fade(z));
+
+		let corner = |dx: i32, dy: i32, dz: i32| {
+			self.gradient3(self.hash(xi + dx, yi + dy, zi + dz), x - dx as f32, y - dy as f32, z - dz as f32)
+		};
+
+		let bottom = lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), u), lerp(corner(0, 1, 0), corner(1, 1, 0), u), v);
+		let top = lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), u), lerp(corner(0, 1, 1), corner(1, 1, 1), u), v);
+		lerp(bottom, top, w)
+	}
+
+	pub fn simplex2(&self, point: &Vector2) -> f32 {
+		// Find the simplex cell and the offsets from its three corners
+		let skew = (point.x + point.y) * F2;
+		let (i, j) = ((point.x + skew).floor(), (point.y + skew).floor());
+		let unskew = (i + j) * G2;
+		let x0 = point.x - (i - unskew);
+		let y0 = point.y - (j - unskew);
+		let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+		let (i, j) = (i as i32, j as i32);
+
+		let corners = [
+			(x0, y0, 0, 0),
+			(x0 - i1 as f32 + G2, y0 - j1 as f32 + G2, i1, j1),
+			(x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2, 1, 1)
+		];
+
+		let sum: f32 = corners.iter()
+			.map(|(x, y, di, dj)| {
+				let t = 0.5 - x * x - y * y;
+
+				if t < 0.0 {
+					0.0
+				}
+				else {
+					let t2 = t * t;
+					t2 * t2 * self.gradient2(self.hash(i + di, j + dj, 0), *x, *y)
+				}
+			})
+			.sum();
+
+		sum * SIMPLEX2_SCALE
+	}
+
+	pub fn simplex3(&self, point: &Vector3) -> f32 {
+		let skew = (point.x + point.y + point.z) * F3;
+		let (i, j, k) = ((point.x + skew).floor(), (point.y + skew).floor(), (point.z + skew).floor());
+		let unskew = (i + j + k) * G3;
+		let (x0, y0, z0) = (point.x - (i - unskew), point.y - (j - unskew), point.z - (k - unskew));
+
+		// Which of the six tetrahedra in the cube the point is in
+		let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+			if y0 >= z0 { ((1, 0, 0), (1, 1, 0)) }
+			else if x0 >= z0 { ((1, 0, 0), (1, 0, 1)) }
+			else { ((0, 0, 1), (1, 0, 1)) }
+		}
+		else if y0 < z0 { ((0, 0, 1), (0, 1, 1)) }
+		else if x0 < z0 { ((0, 1, 0), (0, 1, 1)) }
+		else { ((0, 1, 0), (1, 1, 0)) };
+
+		let (i, j, k) = (i as i32, j as i32, k as i32);
+		let offsets = [(0, 0, 0), (i1, j1, k1), (i2, j2, k2), (1, 1, 1)];
+
+		let sum: f32 = offsets.iter()
+			.enumerate()
+			.map(|(corner, (di, dj, dk))| {
+				let g = G3 * corner as f32;
+				let (x, y, z) = (x0 - *di as f32 + g, y0 - *dj as f32 + g, z0 - *dk as f32 + g);
+				let t = 0.6 - x * x - y * y - z * z;
+
+				if t < 0.0 {
+					0.0
+				}
+				else {
+					let t2 = t * t;
+					t2 * t2 * self.gradient3(self.hash(i + di, j + dj, k + dk), x, y, z)
+				}
+			})
+			.sum();
+
+		sum * SIMPLEX3_SCALE
+	}
+
+	// Sums octaves of simplex noise at rising frequencies and falling amplitudes, normalized back to about [-1, 1]
+	pub fn fbm2(&self, point: &Vector2, fbm: &Fbm) -> f32 {
+		fbm.sum(|frequency| self.simplex2(&(*point * frequency)))
+	}
+
+	pub fn fbm3(&self, point: &Vector3, fbm: &Fbm) -> f32 {
+		fbm.sum(|frequency| self.simplex3(&(*point * frequency)))
+	}
+}
+
+// Fractal Brownian motion settings
+#[derive(Clone, Copy, Debug)]
+pub struct Fbm {
+	pub octaves: u32,
+	// How much the frequency is multiplied by each octave
+	pub lacunarity: f32,
+	// How much the amplitude is multiplied by each octave
+	pub gain: f32
+}
+
+impl Fbm {
+	pub fn new(octaves: u32) -> Self {
+		Self {
+			octaves,
+			lacunarity: 2.0,
+			gain: 0.5
+		}
+	}
+
+	fn sum(&self, noise: impl Fn(f32) -> f32) -> f32 {
+		let mut sum = 0.0;
+		let mut amplitude = 1.0;
+		let mut frequency = 1.0;
+		let mut amplitude_sum = 0.0;
+
+		for _ in 0..self.octaves {
+			sum += noise(frequency) * amplitude;
+			amplitude_sum += amplitude;
+			amplitude *= self.gain;
+			frequency *= self.lacunarity;
+		}
+
+		if amplitude_sum > 0.0 { sum / amplitude_sum } else { 0.0 }
+	}
+}
+
+// Perlin's quintic which has zero first and second derivatives at 0 and 1
+fn fade(t: f32) -> f32 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+	a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Samples on a grid which doesn't line up with the integer lattice
+	fn samples() -> impl Iterator<Item = Vector3> {
+		(0..20).flat_map(|x| (0..20).flat_map(move |y| (0..5).map(move |z| Vector3::new(x as f32 * 0.37, y as f32 * 0.29 - 3.0, z as f32 * 0.53))))
+	}
+
+	#[test]
+	fn seeded() {
+		let (a, b, c) = (Noise::new(1), Noise::new(1), Noise::new(2));
+		let point = Vector3::new(1.3, 2.7, -0.4);
+
+		assert_eq!(a.perlin3(&point), b.perlin3(&point));
+		assert_eq!(a.simplex3(&point), b.simplex3(&point));
+		assert!(samples().any(|point| a.simplex3(&point) != c.simplex3(&point)));
+	}
+
+	#[test]
+	fn perlin_is_zero_on_the_lattice() {
+		let noise = Noise::new(7);
+		assert_eq!(noise.perlin2(&Vector2::new(3.0, -2.0)), 0.0);
+		assert_eq!(noise.perlin3(&Vector3::new(3.0, -2.0, 5.0)), 0.0);
+	}
+
+	#[test]
+	fn ranges() {
+		let noise = Noise::new(42);
+		let fbm = Fbm::new(4);
+
+		for point in samples() {
+			let point2 = Vector2::new(point.x, point.y);
+
+			for value in [noise.perlin2(&point2), noise.perlin3(&point), noise.simplex2(&point2), noise.simplex3(&point), noise.fbm2(&point2, &fbm), noise.fbm3(&point, &fbm)] {
+				assert!((-1.05..=1.05).contains(&value), "Noise value {} is out of range", value);
+			}
+		}
+
+		// Not flat
+		let values: Vec<f32> = samples().map(|point| noise.simplex3(&point)).collect();
+		assert!(values.iter().any(|value| *value > 0.3) && values.iter().any(|value| *value < -0.3));
+	}
+
+	#[test]
+	fn continuous() {
+		let noise = Noise::new(3);
+		let step = Vector3::new(0.001, 0.001, 0.001);
+
+		for point in samples() {
+			assert!((noise.perlin3(&point) - noise.perlin3(&(point + step))).abs() < 0.02);
+			assert!((noise.simplex3(&point) - noise.simplex3(&(point + step))).abs() < 0.05);
+		}
+	}
+}
\ No newline at end of file
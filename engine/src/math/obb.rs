@@ -0,0 +1,213 @@
+use super::{Vector3, Box3, Matrix4, Plane, Ray, Sphere, vector3};
+
+// An oriented bounding box, a box rotated by the orthonormal axes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Obb {
+	pub center: Vector3,
+	pub half_size: Vector3,
+	pub axes: [Vector3; 3]
+}
+
+impl Obb {
+	pub fn new(center: Vector3, half_size: Vector3, axes: [Vector3; 3]) -> Self {
+		Self { center, half_size, axes }
+	}
+
+	pub fn from_box(box3: &Box3) -> Self {
+		Self::new(box3.center(), box3.half_size(), [vector3::UNIT_X, vector3::UNIT_Y, vector3::UNIT_Z])
+	}
+
+	// The matrix may scale but not shear, as with a transform's global matrix
+	pub fn apply_matrix4(&mut self, m: &Matrix4) {
+		self.center.apply_matrix4(m);
+
+		for axis in 0..3 {
+			let mut scaled_axis = self.axes[axis] * self.half_size.get_from_index(axis as u32);
+			scaled_axis.transform_direction(m);
+			let length = scaled_axis.length();
+			self.half_size.set_from_index(axis as u32, length);
+
+			// Keep the old axis rotated when the box is flat along it
+			if length > 0.0 {
+				self.axes[axis] = scaled_axis / length;
+			}
+			else {
+				self.axes[axis].transform_direction(m);
+				self.axes[axis].normalize();
+			}
+		}
+	}
+
+	pub fn vertices(&self) -> [Vector3; 8] {
+		let [x, y, z] = [self.axes[0] * self.half_size.x, self.axes[1] * self.half_size.y, self.axes[2] * self.half_size.z];
+		let c = self.center;
+
+		[c + x + y + z, c - x + y + z, c - x + y - z, c + x + y - z, c + x - y + z, c - x - y + z, c - x - y - z, c + x - y - z]
+	}
+
+	// Grows along its own axes to contain the other box, so the result keeps this box's orientation
+	pub fn merge(&mut self, other: &Self) {
+		let mut local_box = Box3::new(-self.half_size, self.half_size);
+
+		for vertex in &other.vertices() {
+			local_box.expand_by_point(&self.local_point(vertex));
+		}
+
+		self.center = self.world_point(&local_box.center());
+		self.half_size = local_box.half_size();
+	}
+
+	pub fn bounding_box(&self) -> Box3 {
+		Box3::from_points(&self.vertices())
+	}
+
+	pub fn bounding_sphere(&self) -> Sphere {
+		Sphere::new(self.center, self.half_size.length())
+	}
+
+	pub fn contains_point(&self, point: &Vector3) -> bool {
+		let local = self.local_point(point);
+		local.x.abs() <= self.half_size.x && local.y.abs() <= self.half_size.y && local.z.abs() <= self.half_size.z
+	}
+
+	pub fn closest_point(&self, point: &Vector3) -> Vector3 {
+		let local = self.local_point(point);
+		self.world_point(&Box3::new(-self.half_size, self.half_size).clamp_point(&local))
+	}
+
+	pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+		self.closest_point(&sphere.center).distance(&sphere.center) <= sphere.radius
+	}
+
+	pub fn intersects_box(&self, box3: &Box3) -> bool {
+		self.intersects_obb(&Self::from_box(box3))
+	}
+
+	// Separating axis test over the 3 face normals of each box and the 9 cross products of their edges
+	pub fn intersects_obb(&self, other: &Self) -> bool {
+		let offset = other.center - self.center;
+		let mut axes = Vec::with_capacity(15);
+		axes.extend_from_slice(&self.axes);
+		axes.extend_from_slice(&other.axes);
+
+		for a in &self.axes {
+			for b in &other.axes {
+				let mut axis = *a;
+				axis.cross(b);
+
+				// Parallel edges give no new axis, the face normals cover that case
+				if axis.length_sq() > 1e-6 {
+					axes.push(axis);
+				}
+			}
+		}
+
+		axes.iter().all(|axis| {
+			let distance = offset.dot(axis).abs();
+			distance <= self.projected_radius(axis) + other.projected_radius(axis)
+		})
+	}
+
+	// The same as Sphere::intersects_planes
+	pub fn intersects_planes(&self, planes: &[Plane]) -> bool {
+		planes.iter().all(|plane| plane.distance_to_point(&self.center) >= -self.projected_radius(&plane.normal))
+	}
+
+	pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+		let local_ray = Ray::new(self.local_point(&ray.origin), self.rotate_to_local(&ray.direction));
+		local_ray.intersect_box(&Box3::new(-self.half_size, self.half_size))
+	}
+
+	// Half the length of the box projected onto the axis, scaled by the axis length
+	fn projected_radius(&self, axis: &Vector3) -> f32 {
+		(0..3).map(|index| self.axes[index].dot(axis).abs() * self.half_size.get_from_index(index as u32)).sum()
+	}
+
+	fn rotate_to_local(&self, direction: &Vector3) -> Vector3 {
+		Vector3::new(direction.dot(&self.axes[0]), direction.dot(&self.axes[1]), direction.dot(&self.axes[2]))
+	}
+
+	fn local_point(&self, point: &Vector3) -> Vector3 {
+		self.rotate_to_local(&(*point - self.center))
+	}
+
+	fn world_point(&self, local: &Vector3) -> Vector3 {
+		self.center + self.axes[0] * local.x + self.axes[1] * local.y + self.axes[2] * local.z
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::FRAC_PI_4;
+	use crate::math::{Quaternion, assert_approx_eq, box3};
+
+	// A unit box rotated 45 degrees around y and moved
+	fn rotated_obb(position: Vector3) -> Obb {
+		let mut orientation = Quaternion::default();
+		orientation.set_from_axis_angle(&vector3::UNIT_Y, FRAC_PI_4);
+		let mut matrix = Matrix4::default();
+		matrix.compose(&position, &orientation, &Vector3::from_scalar(1.0));
+
+		let mut obb = Obb::from_box(&box3::DEFAULT_SQUARE);
+		obb.apply_matrix4(&matrix);
+		obb
+	}
+
+	#[test]
+	fn apply_matrix4() {
+		let obb = rotated_obb(Vector3::new(5.0, 0.0, 0.0));
+		let diagonal = 2.0f32.sqrt();
+
+		assert_approx_eq(&obb.center, &Vector3::new(5.0, 0.0, 0.0), 1e-6);
+		assert_approx_eq(&obb.half_size, &Vector3::from_scalar(1.0), 1e-6);
+		assert_approx_eq(&obb.bounding_box().max, &Vector3::new(5.0 + diagonal, 1.0, diagonal), 1e-5);
+		assert!(obb.contains_point(&Vector3::new(5.0 + diagonal - 0.01, 0.0, 0.0)));
+		assert!(!obb.contains_point(&Vector3::new(6.0, 0.0, 1.0)));
+	}
+
+	#[test]
+	fn scaled() {
+		let mut matrix = Matrix4::default();
+		matrix.compose(&vector3::ZERO, &Quaternion::new(0.0, 0.0, 0.0, 1.0), &Vector3::new(2.0, 3.0, 0.5));
+		let mut obb = Obb::from_box(&box3::DEFAULT_SQUARE);
+		obb.apply_matrix4(&matrix);
+
+		assert_approx_eq(&obb.half_size, &Vector3::new(2.0, 3.0, 0.5), 1e-6);
+		assert_approx_eq(&obb.axes[2], &vector3::UNIT_Z, 1e-6);
+	}
+
+	#[test]
+	fn intersections() {
+		let obb = rotated_obb(vector3::ZERO);
+
+		// The rotated box's corner reaches past an axis aligned box's face
+		let box3 = Box3::new(Vector3::new(1.3, -1.0, -0.1), Vector3::new(2.0, 1.0, 0.1));
+		assert!(obb.intersects_box(&box3));
+		assert!(!Obb::from_box(&box3::DEFAULT_SQUARE).intersects_box(&box3));
+
+		let box3 = Box3::new(Vector3::new(1.3, -1.0, 1.0), Vector3::new(2.0, 1.0, 2.0));
+		assert!(!obb.intersects_box(&box3));
+
+		assert!(obb.intersects_sphere(&Sphere::new(Vector3::new(2.0, 0.0, 0.0), 0.6)));
+		assert!(!obb.intersects_sphere(&Sphere::new(Vector3::new(1.5, 0.0, 1.5), 0.6)));
+
+		let ray = Ray::new(Vector3::new(-5.0, 0.0, 0.0), vector3::UNIT_X);
+		assert!((obb.intersect_ray(&ray).unwrap() - (5.0 - 2.0f32.sqrt())).abs() < 1e-5);
+		assert_eq!(obb.intersect_ray(&Ray::new(Vector3::new(-5.0, 2.0, 0.0), vector3::UNIT_X)), None);
+
+		let planes = [Plane::new(Vector3::new(-1.0, 0.0, 0.0), -1.3)];
+		assert!(obb.intersects_planes(&planes));
+		assert!(!Obb::from_box(&box3::DEFAULT_SQUARE).intersects_planes(&planes));
+	}
+
+	#[test]
+	fn merge() {
+		let mut obb = Obb::from_box(&box3::DEFAULT_SQUARE);
+		obb.merge(&rotated_obb(Vector3::new(3.0, 0.0, 0.0)));
+
+		let diagonal = 2.0f32.sqrt();
+		assert_approx_eq(&obb.center, &Vector3::new((2.0 + diagonal) / 2.0, 0.0, 0.0), 1e-5);
+		assert_approx_eq(&obb.half_size, &Vector3::new((4.0 + diagonal) / 2.0, 1.0, diagonal), 1e-5);
+	}
+}
\ No newline at end of file
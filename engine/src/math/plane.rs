@@ -0,0 +1,45 @@
+use super::Vector3;
+
+// The points where normal · point + constant = 0, the normal points to the positive side
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+	pub normal: Vector3,
+	pub constant: f32
+}
+
+impl Plane {
+	pub fn new(normal: Vector3, constant: f32) -> Self {
+		Self { normal, constant }
+	}
+
+	pub fn from_normal_and_point(normal: Vector3, point: &Vector3) -> Self {
+		Self::new(normal, -normal.dot(point))
+	}
+
+	// Scales the normal to unit length so distances are in world units
+	pub fn normalize(&mut self) {
+		let length = self.normal.length();
+		self.normal /= length;
+		self.constant /= length;
+	}
+
+	// Negative behind the plane
+	pub fn distance_to_point(&self, point: &Vector3) -> f32 {
+		self.normal.dot(point) + self.constant
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn distance_to_point() {
+		let mut plane = Plane::from_normal_and_point(Vector3::new(0.0, 2.0, 0.0), &Vector3::new(5.0, 1.0, 5.0));
+		plane.normalize();
+
+		assert_eq!(plane, Plane::new(Vector3::new(0.0, 1.0, 0.0), -1.0));
+		assert_eq!(plane.distance_to_point(&Vector3::new(3.0, 4.0, 0.0)), 3.0);
+		assert_eq!(plane.distance_to_point(&Vector3::new(3.0, -1.0, 0.0)), -2.0);
+	}
+}
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::fmt::Display;
-use super::{Vector3, Euler, Order, ApproxEq};
+use super::{Vector3, Euler, Order, ApproxEq, Matrix4};
 use auto_ops::impl_op_ex;
 
 pub const ZERO: Quaternion = Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
@@ -78,6 +78,90 @@ impl Quaternion {
 		}
 	}
 
+	// The upper 3x3 of the matrix must be a pure rotation
+	pub fn set_from_rotation_matrix(&mut self, m: &Matrix4) {
+		let e = &m.elements;
+		let (m00, m01, m02) = (e[0][0], e[0][1], e[0][2]);
+		let (m10, m11, m12) = (e[1][0], e[1][1], e[1][2]);
+		let (m20, m21, m22) = (e[2][0], e[2][1], e[2][2]);
+		let trace = m00 + m11 + m22;
+
+		if trace > 0.0 {
+			let s = 0.5 / (trace + 1.0).sqrt();
+			self.w = 0.25 / s;
+			self.x = (m21 - m12) * s;
+			self.y = (m02 - m20) * s;
+			self.z = (m10 - m01) * s;
+		}
+		else if m00 > m11 && m00 > m22 {
+			let s = 2.0 * (1.0 + m00 - m11 - m22).sqrt();
+			self.w = (m21 - m12) / s;
+			self.x = 0.25 * s;
+			self.y = (m01 + m10) / s;
+			self.z = (m02 + m20) / s;
+		}
+		else if m11 > m22 {
+			let s = 2.0 * (1.0 + m11 - m00 - m22).sqrt();
+			self.w = (m02 - m20) / s;
+			self.x = (m01 + m10) / s;
+			self.y = 0.25 * s;
+			self.z = (m12 + m21) / s;
+		}
+		else {
+			let s = 2.0 * (1.0 + m22 - m00 - m11).sqrt();
+			self.w = (m10 - m01) / s;
+			self.x = (m02 + m20) / s;
+			self.y = (m12 + m21) / s;
+			self.z = 0.25 * s;
+		}
+	}
+
+	// The shortest rotation which turns the from direction into the to direction, both must be normalized
+	pub fn set_from_unit_vectors(&mut self, from: &Vector3, to: &Vector3) {
+		let r = from.dot(to) + 1.0;
+
+		if r < 1e-6 {
+			// Opposite directions, turn half way around any axis perpendicular to from
+			if from.x.abs() > from.z.abs() {
+				self.set(-from.y, from.x, 0.0, 0.0);
+			}
+			else {
+				self.set(0.0, -from.z, from.y, 0.0);
+			}
+		}
+		else {
+			let mut axis = *from;
+			axis.cross(to);
+			self.set(axis.x, axis.y, axis.z, r);
+		}
+
+		self.normalize();
+	}
+
+	// Spherical interpolation towards the other orientation the shorter way around, both must be normalized
+	pub fn slerp(&mut self, other: &Self, t: f32) {
+		let mut other = *other;
+		let mut cos = self.dot(&other);
+
+		if cos < 0.0 {
+			other.set(-other.x, -other.y, -other.z, -other.w);
+			cos = -cos;
+		}
+
+		// Nearly the same orientation where the sine is too small to divide by, a normalized lerp is just as good there
+		let (a, b) = if cos > 0.9995 {
+			(1.0 - t, t)
+		}
+		else {
+			let angle = cos.acos();
+			let sin = angle.sin();
+			(((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+		};
+
+		self.set(self.x * a + other.x * b, self.y * a + other.y * b, self.z * a + other.z * b, self.w * a + other.w * b);
+		self.normalize();
+	}
+
 	pub fn conjigate(&mut self) {
 		self.x = -self.x;
 		self.y = -self.y;
@@ -201,6 +285,48 @@ mod tests {
 		assert_approx_eq(&q, &Quaternion { x: 0.0, y: FRAC_1_SQRT_2, z: 0.0, w: FRAC_1_SQRT_2 }, 1e-6);
 	}
 
+	#[test]
+	fn set_from_rotation_matrix() {
+		let mut expected = Quaternion::new(1.0, 2.0, 3.0, 4.0);
+		expected.normalize();
+
+		let mut m = Matrix4::default();
+		m.compose(&Vector3::new(1.0, 2.0, 3.0), &expected, &Vector3::from_scalar(1.0));
+
+		let mut q = ZERO;
+		q.set_from_rotation_matrix(&m);
+		assert_approx_eq(&q, &expected, 1e-6);
+
+		// Half turn about x where the trace is negative
+		m.compose(&Vector3::from_scalar(0.0), &Quaternion::new(1.0, 0.0, 0.0, 0.0), &Vector3::from_scalar(1.0));
+		q.set_from_rotation_matrix(&m);
+		assert_approx_eq(&q, &Quaternion::new(1.0, 0.0, 0.0, 0.0), 1e-6);
+	}
+
+	#[test]
+	fn set_from_unit_vectors() {
+		let mut q = ZERO;
+		q.set_from_unit_vectors(&Vector3::new(1.0, 0.0, 0.0), &Vector3::new(0.0, 1.0, 0.0));
+		assert_approx_eq(&q, &Quaternion { x: 0.0, y: 0.0, z: FRAC_1_SQRT_2, w: FRAC_1_SQRT_2 }, 1e-6);
+
+		let mut v = Vector3::new(0.0, 0.0, 1.0);
+		q.set_from_unit_vectors(&v, &Vector3::new(0.0, 0.0, -1.0));
+		v.apply_quaternion(&q);
+		assert_approx_eq(&v, &Vector3::new(0.0, 0.0, -1.0), 1e-6);
+	}
+
+	#[test]
+	fn slerp() {
+		let mut q = ZERO;
+		let mut turned = ZERO;
+		turned.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 2.0);
+		q.slerp(&turned, 0.25);
+
+		let mut expected = ZERO;
+		expected.set_from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5);
+		assert_approx_eq(&q, &expected, 1e-6);
+	}
+
 	#[test]
 	fn conjigate() {
 		let mut q = Quaternion::new(1.0, 2.0, 3.0, 4.0);
@@ -0,0 +1,123 @@
+use super::{Vector3, Box3, Matrix4};
+
+// A half line, the direction should be unit length for the distances returned by intersections to be in world units
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+	pub origin: Vector3,
+	pub direction: Vector3
+}
+
+impl Ray {
+	pub fn new(origin: Vector3, direction: Vector3) -> Self {
+		Self { origin, direction }
+	}
+
+	pub fn at(&self, distance: f32) -> Vector3 {
+		self.origin + self.direction * distance
+	}
+
+	pub fn apply_matrix4(&mut self, m: &Matrix4) {
+		self.origin.apply_matrix4(m);
+		self.direction.transform_direction(m);
+	}
+
+	// The distance to where the ray enters the box, zero when it starts inside. Slab test, axes the direction is parallel to
+	// divide to infinity which the comparisons handle.
+	pub fn intersect_box(&self, box3: &Box3) -> Option<f32> {
+		let mut near = 0.0f32;
+		let mut far = f32::INFINITY;
+
+		for axis in 0..3 {
+			let origin = self.origin.get_from_index(axis);
+			let inverse_direction = 1.0 / self.direction.get_from_index(axis);
+			let mut t0 = (box3.min.get_from_index(axis) - origin) * inverse_direction;
+			let mut t1 = (box3.max.get_from_index(axis) - origin) * inverse_direction;
+
+			if t0 > t1 {
+				std::mem::swap(&mut t0, &mut t1);
+			}
+
+			// NaN comes from a zero direction with the origin on the slab's boundary, treat it as inside
+			if !t0.is_nan() {
+				near = near.max(t0);
+			}
+
+			if !t1.is_nan() {
+				far = far.min(t1);
+			}
+
+			if near > far {
+				return None;
+			}
+		}
+
+		Some(near)
+	}
+
+	// Möller-Trumbore, both sides of the triangle are hit
+	pub fn intersect_triangle(&self, a: &Vector3, b: &Vector3, c: &Vector3) -> Option<f32> {
+		let edge1 = *b - *a;
+		let edge2 = *c - *a;
+		let mut p = self.direction;
+		p.cross(&edge2);
+		let determinant = edge1.dot(&p);
+
+		if determinant.abs() < f32::EPSILON {
+			return None;
+		}
+
+		let inverse_determinant = 1.0 / determinant;
+		let s = self.origin - *a;
+		let u = s.dot(&p) * inverse_determinant;
+
+		if !(0.0..=1.0).contains(&u) {
+			return None;
+		}
+
+		let mut q = s;
+		q.cross(&edge1);
+		let v = self.direction.dot(&q) * inverse_determinant;
+
+		if v < 0.0 || u + v > 1.0 {
+			return None;
+		}
+
+		let distance = edge2.dot(&q) * inverse_determinant;
+
+		if distance >= 0.0 { Some(distance) } else { None }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::box3;
+
+	#[test]
+	fn intersect_box() {
+		let ray = Ray::new(Vector3::new(-5.0, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0));
+		assert_eq!(ray.intersect_box(&box3::DEFAULT_SQUARE), Some(4.0));
+
+		let inside = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+		assert_eq!(inside.intersect_box(&box3::DEFAULT_SQUARE), Some(0.0));
+
+		let away = Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+		assert_eq!(away.intersect_box(&box3::DEFAULT_SQUARE), None);
+
+		let past = Ray::new(Vector3::new(-5.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+		assert_eq!(past.intersect_box(&box3::DEFAULT_SQUARE), None);
+	}
+
+	#[test]
+	fn intersect_triangle() {
+		let (a, b, c) = (Vector3::new(0.0, 0.0, 2.0), Vector3::new(1.0, 0.0, 2.0), Vector3::new(0.0, 1.0, 2.0));
+		let ray = Ray::new(Vector3::new(0.25, 0.25, 0.0), Vector3::new(0.0, 0.0, 1.0));
+		assert_eq!(ray.intersect_triangle(&a, &b, &c), Some(2.0));
+
+		let miss = Ray::new(Vector3::new(0.75, 0.75, 0.0), Vector3::new(0.0, 0.0, 1.0));
+		assert_eq!(miss.intersect_triangle(&a, &b, &c), None);
+
+		let behind = Ray::new(Vector3::new(0.25, 0.25, 3.0), Vector3::new(0.0, 0.0, 1.0));
+		assert_eq!(behind.intersect_triangle(&a, &b, &c), None);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,149 @@
+use std::f32::consts::TAU;
+use super::{Box3, Quaternion, Vector2, Vector3};
+
+// A seedable random number generator based on splitmix64, fast and good enough for gameplay and effects but not for
+// anything which needs to be unpredictable. The same seed always gives the same sequence so it can be kept as a resource
+// and reseeded to replay something.
+#[derive(Clone, Debug)]
+pub struct Rng {
+	state: u64
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		Self {
+			state: seed
+		}
+	}
+
+	// Seeded from the clock
+	pub fn from_time() -> Self {
+		let nanos = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map_or(0, |duration| duration.as_nanos() as u64);
+
+		Self::new(nanos)
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^ (z >> 31)
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		(self.next_u64() >> 32) as u32
+	}
+
+	// In [0, 1)
+	pub fn next_f32(&mut self) -> f32 {
+		// The top 24 bits fill the mantissa exactly so 1 is never returned
+		(self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+	}
+
+	// In [min, max)
+	pub fn range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.next_f32()
+	}
+
+	// In [0, count), the count must not be 0
+	pub fn index(&mut self, count: usize) -> usize {
+		assert!(count > 0, "Cannot pick a random index out of 0");
+		// Multiply and shift instead of modulo to avoid most of the bias
+		((self.next_u32() as u64 * count as u64) >> 32) as usize
+	}
+
+	pub fn chance(&mut self, probability: f32) -> bool {
+		self.next_f32() < probability
+	}
+
+	pub fn shuffle<T>(&mut self, items: &mut [T]) {
+		for index in (1..items.len()).rev() {
+			items.swap(index, self.index(index + 1));
+		}
+	}
+
+	pub fn random_unit_vector2(&mut self) -> Vector2 {
+		let angle = self.range(0.0, TAU);
+		Vector2::new(angle.cos(), angle.sin())
+	}
+
+	// Uniformly distributed over the sphere
+	pub fn random_unit_vector3(&mut self) -> Vector3 {
+		let z = self.range(-1.0, 1.0);
+		let angle = self.range(0.0, TAU);
+		let radius = (1.0 - z * z).sqrt();
+		Vector3::new(radius * angle.cos(), radius * angle.sin(), z)
+	}
+
+	// Uniformly distributed through the volume of a sphere of the radius
+	pub fn random_in_sphere(&mut self, radius: f32) -> Vector3 {
+		self.random_unit_vector3() * (radius * self.next_f32().cbrt())
+	}
+
+	pub fn random_in_box(&mut self, box3: &Box3) -> Vector3 {
+		Vector3::new(
+			self.range(box3.min.x, box3.max.x),
+			self.range(box3.min.y, box3.max.y),
+			self.range(box3.min.z, box3.max.z))
+	}
+
+	// Uniformly distributed over all orientations, Shoemake's method
+	pub fn random_rotation(&mut self) -> Quaternion {
+		let u = self.next_f32();
+		let (a, b) = ((1.0 - u).sqrt(), u.sqrt());
+		let (angle1, angle2) = (self.range(0.0, TAU), self.range(0.0, TAU));
+		Quaternion::new(a * angle1.sin(), a * angle1.cos(), b * angle2.sin(), b * angle2.cos())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn seeded() {
+		let mut a = Rng::new(5);
+		let mut b = Rng::new(5);
+		let mut c = Rng::new(6);
+		let sequence: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+
+		assert_eq!(sequence, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+		assert_ne!(sequence, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn ranges() {
+		let mut rng = Rng::new(1);
+		let box3 = Box3::new(Vector3::new(-1.0, 2.0, 3.0), Vector3::new(0.0, 4.0, 3.5));
+
+		for _ in 0..1000 {
+			let value = rng.next_f32();
+			assert!((0.0..1.0).contains(&value));
+			assert!(rng.index(3) < 3);
+			assert!((rng.random_unit_vector3().length() - 1.0).abs() < 1e-5);
+			assert!(rng.random_in_sphere(2.0).length() <= 2.0 + 1e-5);
+			assert!((rng.random_rotation().length() - 1.0).abs() < 1e-5);
+
+			let point = rng.random_in_box(&box3);
+			assert!(point.x >= -1.0 && point.x < 0.0 && point.y >= 2.0 && point.y < 4.0 && point.z >= 3.0 && point.z < 3.5);
+		}
+	}
+
+	#[test]
+	fn roughly_uniform() {
+		let mut rng = Rng::new(9);
+		let mut counts = [0; 4];
+		let mut direction_sum = Vector3::default();
+
+		for _ in 0..4000 {
+			counts[rng.index(4)] += 1;
+			direction_sum += rng.random_unit_vector3();
+		}
+
+		assert!(counts.iter().all(|count| (900..1100).contains(count)), "{:?}", counts);
+		assert!((direction_sum / 4000.0).length() < 0.05);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,159 @@
+use super::{Vector3, Box3, Matrix4, Plane, Ray};
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+	pub center: Vector3,
+	pub radius: f32
+}
+
+impl Sphere {
+	pub fn new(center: Vector3, radius: f32) -> Self {
+		Self { center, radius }
+	}
+
+	// Centered on the box so it's not the tightest sphere around whatever is in the box
+	pub fn from_box(box3: &Box3) -> Self {
+		Self::new(box3.center(), box3.half_size().length())
+	}
+
+	// Centered on the points' bounding box with the radius reaching the farthest point
+	pub fn from_points(points: &[Vector3]) -> Self {
+		let center = Box3::from_points(points).center();
+		let radius = points.iter().map(|point| point.distance(&center)).fold(0.0, f32::max);
+		Self::new(center, radius)
+	}
+
+	// The radius is scaled by the largest scale on any axis so it still contains everything under non-uniform scaling
+	pub fn apply_matrix4(&mut self, m: &Matrix4) {
+		self.center.apply_matrix4(m);
+		let e = &m.elements;
+		let scale_sq = (0..3)
+			.map(|column| e[0][column] * e[0][column] + e[1][column] * e[1][column] + e[2][column] * e[2][column])
+			.fold(0.0, f32::max);
+		self.radius *= scale_sq.sqrt();
+	}
+
+	// Becomes the smallest sphere containing both
+	pub fn merge(&mut self, other: &Self) {
+		let offset = other.center - self.center;
+		let distance = offset.length();
+
+		if distance + other.radius <= self.radius {
+			return;
+		}
+
+		if distance + self.radius <= other.radius {
+			*self = *other;
+			return;
+		}
+
+		let radius = (distance + self.radius + other.radius) * 0.5;
+		self.center += offset * ((radius - self.radius) / distance);
+		self.radius = radius;
+	}
+
+	pub fn bounding_box(&self) -> Box3 {
+		Box3::new(self.center - self.radius, self.center + self.radius)
+	}
+
+	pub fn contains_point(&self, point: &Vector3) -> bool {
+		point.distance(&self.center) <= self.radius
+	}
+
+	pub fn intersects_sphere(&self, other: &Self) -> bool {
+		self.center.distance(&other.center) <= self.radius + other.radius
+	}
+
+	pub fn intersects_box(&self, box3: &Box3) -> bool {
+		box3.clamp_point(&self.center).distance(&self.center) <= self.radius
+	}
+
+	// Whether any of the sphere is on the positive side of every plane, such as the inside of a frustum. Conservative near
+	// the edges where the sphere can be outside two planes but on the positive side of each.
+	pub fn intersects_planes(&self, planes: &[Plane]) -> bool {
+		planes.iter().all(|plane| plane.distance_to_point(&self.center) >= -self.radius)
+	}
+
+	// The distance along the ray to where it enters the sphere, zero when it starts inside
+	pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+		let to_center = self.center - ray.origin;
+		let direction_length_sq = ray.direction.length_sq();
+		let projection = to_center.dot(&ray.direction) / direction_length_sq;
+		let distance_sq = (to_center - ray.direction * projection).length_sq();
+		let radius_sq = self.radius * self.radius;
+
+		if distance_sq > radius_sq {
+			return None;
+		}
+
+		let half_chord = ((radius_sq - distance_sq) / direction_length_sq).sqrt();
+		let (near, far) = (projection - half_chord, projection + half_chord);
+
+		if far < 0.0 {
+			None
+		}
+		else {
+			Some(near.max(0.0))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{Quaternion, assert_approx_eq, box3};
+
+	#[test]
+	fn from_box_and_points() {
+		let sphere = Sphere::from_box(&box3::DEFAULT_SQUARE);
+		assert_eq!(sphere.center, Vector3::from_scalar(0.0));
+		assert!((sphere.radius - 3.0f32.sqrt()).abs() < 1e-6);
+
+		let sphere = Sphere::from_points(&[Vector3::new(-2.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)]);
+		assert_eq!(sphere, Sphere::new(Vector3::new(0.0, 0.5, 0.0), 4.25f32.sqrt()));
+	}
+
+	#[test]
+	fn apply_matrix4() {
+		let mut matrix = Matrix4::default();
+		matrix.compose(&Vector3::new(1.0, 2.0, 3.0), &Quaternion::new(0.0, 0.0, 0.0, 1.0), &Vector3::new(1.0, 3.0, 2.0));
+		let mut sphere = Sphere::new(Vector3::new(1.0, 0.0, 0.0), 2.0);
+		sphere.apply_matrix4(&matrix);
+		assert_approx_eq(&sphere.center, &Vector3::new(2.0, 2.0, 3.0), 1e-6);
+		assert!((sphere.radius - 6.0).abs() < 1e-5);
+	}
+
+	#[test]
+	fn merge() {
+		let mut a = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0);
+		a.merge(&Sphere::new(Vector3::new(4.0, 0.0, 0.0), 1.0));
+		assert_eq!(a, Sphere::new(Vector3::new(2.0, 0.0, 0.0), 3.0));
+
+		// Already contained
+		a.merge(&Sphere::new(Vector3::new(2.0, 1.0, 0.0), 1.0));
+		assert_eq!(a, Sphere::new(Vector3::new(2.0, 0.0, 0.0), 3.0));
+
+		let mut small = Sphere::new(Vector3::new(2.0, 0.0, 0.0), 0.5);
+		small.merge(&a);
+		assert_eq!(small, a);
+	}
+
+	#[test]
+	fn intersections() {
+		let sphere = Sphere::new(Vector3::new(3.0, 0.0, 0.0), 1.5);
+		assert!(Sphere::new(Vector3::new(2.0, 0.0, 0.0), 1.5).intersects_box(&box3::DEFAULT_SQUARE));
+		assert!(!Sphere::new(Vector3::new(2.0, 2.0, 2.0), 1.5).intersects_box(&box3::DEFAULT_SQUARE));
+		assert!(sphere.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.5)));
+		assert!(!sphere.intersects_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.4)));
+
+		let planes = [Plane::new(Vector3::new(1.0, 0.0, 0.0), 0.0), Plane::new(Vector3::new(-1.0, 0.0, 0.0), 1.0)];
+		assert!(sphere.intersects_planes(&planes[..1]));
+		assert!(!sphere.intersects_planes(&planes));
+
+		let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+		assert_eq!(sphere.intersect_ray(&ray), Some(1.5));
+		assert_eq!(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0).intersect_ray(&ray), Some(0.0));
+		assert_eq!(Sphere::new(Vector3::new(-3.0, 0.0, 0.0), 1.0).intersect_ray(&ray), None);
+		assert_eq!(Sphere::new(Vector3::new(3.0, 2.0, 0.0), 1.0).intersect_ray(&ray), None);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,270 @@
+use super::{Vector3, Quaternion, Matrix4};
+
+// Samples per segment in the table which maps distance along the spline to the curve parameter
+const ARC_LENGTH_SAMPLES_PER_SEGMENT: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SplineKind {
+	// Chained cubic Bezier curves, every segment takes 3 points and the last point of one segment is the first of the next
+	// so there are 3n + 1 points. The curve passes through every third point.
+	Bezier,
+	// Passes through every point with the tangents taken from the neighbouring points, the first and last points are
+	// repeated to give the ends a tangent unless the spline is closed
+	CatmullRom
+}
+
+// A curve through 3D space which is evaluated either by the curve parameter t, where each segment covers an equal range of
+// [0, 1] no matter how long it is, or by the distance along it which moves at a constant speed
+#[derive(Clone, Debug)]
+pub struct Spline {
+	kind: SplineKind,
+	points: Vec<Vector3>,
+	closed: bool,
+	// The distance along the spline at evenly spaced values of t
+	arc_lengths: Vec<f32>
+}
+
+impl Spline {
+	pub fn bezier(points: Vec<Vector3>) -> Self {
+		assert!(points.len() >= 4 && (points.len() - 1).is_multiple_of(3), "A Bezier spline needs 3n + 1 points but {} were given", points.len());
+		Self::new(SplineKind::Bezier, points, false)
+	}
+
+	pub fn catmull_rom(points: Vec<Vector3>, closed: bool) -> Self {
+		assert!(points.len() >= 2, "A Catmull-Rom spline needs at least 2 points but {} were given", points.len());
+		Self::new(SplineKind::CatmullRom, points, closed)
+	}
+
+	fn new(kind: SplineKind, points: Vec<Vector3>, closed: bool) -> Self {
+		let mut spline = Self {
+			kind,
+			points,
+			closed,
+			arc_lengths: Vec::new()
+		};
+
+		let sample_count = spline.segment_count() * ARC_LENGTH_SAMPLES_PER_SEGMENT;
+		let mut length = 0.0;
+		let mut previous_point = spline.point(0.0);
+		spline.arc_lengths.push(0.0);
+
+		for sample in 1..=sample_count {
+			let point = spline.point(sample as f32 / sample_count as f32);
+			length += (point - previous_point).length();
+			spline.arc_lengths.push(length);
+			previous_point = point;
+		}
+
+		spline
+	}
+
+	pub fn kind(&self) -> SplineKind {
+		self.kind
+	}
+
+	pub fn points(&self) -> &[Vector3] {
+		&self.points
+	}
+
+	pub fn closed(&self) -> bool {
+		self.closed
+	}
+
+	pub fn segment_count(&self) -> usize {
+		match self.kind {
+			SplineKind::Bezier => (self.points.len() - 1) / 3,
+			SplineKind::CatmullRom if self.closed => self.points.len(),
+			SplineKind::CatmullRom => self.points.len() - 1
+		}
+	}
+
+	// Approximated by the sampled points so it's slightly shorter than the true length
+	pub fn length(&self) -> f32 {
+		*self.arc_lengths.last().unwrap()
+	}
+
+	pub fn point(&self, t: f32) -> Vector3 {
+		let (segment, local_t) = self.segment(t);
+		let [p0, p1, p2, p3] = self.segment_points(segment);
+
+		match self.kind {
+			SplineKind::Bezier => bezier_point(p0, p1, p2, p3, local_t),
+			SplineKind::CatmullRom => catmull_rom_point(p0, p1, p2, p3, local_t)
+		}
+	}
+
+	// The derivative with respect to t, not normalized
+	pub fn tangent(&self, t: f32) -> Vector3 {
+		let (segment, local_t) = self.segment(t);
+		let [p0, p1, p2, p3] = self.segment_points(segment);
+		let segment_count = self.segment_count() as f32;
+
+		let tangent = match self.kind {
+			SplineKind::Bezier => bezier_tangent(p0, p1, p2, p3, local_t),
+			SplineKind::CatmullRom => catmull_rom_tangent(p0, p1, p2, p3, local_t)
+		};
+
+		tangent * segment_count
+	}
+
+	// The curve parameter at the distance along the spline, the distance is clamped to the length
+	pub fn t_at_distance(&self, distance: f32) -> f32 {
+		let distance = distance.clamp(0.0, self.length());
+		let sample_count = self.arc_lengths.len() - 1;
+		let index = self.arc_lengths.partition_point(|arc_length| *arc_length < distance).max(1);
+		let (start, end) = (self.arc_lengths[index - 1], self.arc_lengths[index]);
+		let fraction = if end > start { (distance - start) / (end - start) } else { 0.0 };
+		(index as f32 - 1.0 + fraction) / sample_count as f32
+	}
+
+	pub fn point_at_distance(&self, distance: f32) -> Vector3 {
+		self.point(self.t_at_distance(distance))
+	}
+
+	pub fn tangent_at_distance(&self, distance: f32) -> Vector3 {
+		self.tangent(self.t_at_distance(distance))
+	}
+
+	// An orientation whose z axis faces along the spline with the x axis kept level, the up vector must not be parallel to
+	// the tangent
+	pub fn orientation_at_distance(&self, distance: f32, up: &Vector3) -> Quaternion {
+		let mut z = self.tangent_at_distance(distance);
+		z.normalize();
+		let mut x = *up;
+		x.cross(&z);
+		x.normalize();
+		let mut y = z;
+		y.cross(&x);
+
+		let rotation = Matrix4::new([
+			[x.x, y.x, z.x, 0.0],
+			[x.y, y.y, z.y, 0.0],
+			[x.z, y.z, z.z, 0.0],
+			[0.0, 0.0, 0.0, 1.0]
+		]);
+
+		let mut orientation = Quaternion::default();
+		orientation.set_from_rotation_matrix(&rotation);
+		orientation
+	}
+
+	// The segment t falls in and t within that segment
+	fn segment(&self, t: f32) -> (usize, f32) {
+		let segment_count = self.segment_count();
+		let scaled_t = t.clamp(0.0, 1.0) * segment_count as f32;
+		let segment = (scaled_t as usize).min(segment_count - 1);
+		(segment, scaled_t - segment as f32)
+	}
+
+	fn segment_points(&self, segment: usize) -> [Vector3; 4] {
+		match self.kind {
+			SplineKind::Bezier => {
+				let start = segment * 3;
+				[self.points[start], self.points[start + 1], self.points[start + 2], self.points[start + 3]]
+			},
+			SplineKind::CatmullRom => {
+				let point = |index: isize| {
+					let count = self.points.len() as isize;
+
+					if self.closed {
+						self.points[index.rem_euclid(count) as usize]
+					}
+					else {
+						self.points[index.clamp(0, count - 1) as usize]
+					}
+				};
+
+				let segment = segment as isize;
+				[point(segment - 1), point(segment), point(segment + 1), point(segment + 2)]
+			}
+		}
+	}
+}
+
+fn bezier_point(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+	let u = 1.0 - t;
+	p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+fn bezier_tangent(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+	let u = 1.0 - t;
+	(p1 - p0) * (3.0 * u * u) + (p2 - p1) * (6.0 * u * t) + (p3 - p2) * (3.0 * t * t)
+}
+
+// Uniform Catmull-Rom, the segment goes from p1 to p2
+fn catmull_rom_point(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+	let (t2, t3) = (t * t, t * t * t);
+	(p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+fn catmull_rom_tangent(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+	let t2 = t * t;
+	((p2 - p0) + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (2.0 * t) + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (3.0 * t2)) * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{assert_approx_eq, vector3};
+
+	#[test]
+	fn bezier() {
+		let spline = Spline::bezier(vec![
+			Vector3::new(0.0, 0.0, 0.0),
+			Vector3::new(1.0, 0.0, 0.0),
+			Vector3::new(2.0, 0.0, 0.0),
+			Vector3::new(3.0, 0.0, 0.0),
+			Vector3::new(3.0, 1.0, 0.0),
+			Vector3::new(3.0, 2.0, 0.0),
+			Vector3::new(3.0, 3.0, 0.0)
+		]);
+
+		assert_eq!(spline.segment_count(), 2);
+		assert_approx_eq(&spline.point(0.25), &Vector3::new(1.5, 0.0, 0.0), 1e-5);
+		assert_approx_eq(&spline.point(0.5), &Vector3::new(3.0, 0.0, 0.0), 1e-5);
+		assert_approx_eq(&spline.point(1.0), &Vector3::new(3.0, 3.0, 0.0), 1e-5);
+		assert_approx_eq(&spline.tangent(0.75), &Vector3::new(0.0, 6.0, 0.0), 1e-5);
+		assert!((spline.length() - 6.0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn catmull_rom_passes_through_points() {
+		let points = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 2.0, 0.0), Vector3::new(3.0, 1.0, 1.0), Vector3::new(4.0, 0.0, 0.0)];
+		let open = Spline::catmull_rom(points.clone(), false);
+		let closed = Spline::catmull_rom(points.clone(), true);
+
+		for (index, point) in points.iter().enumerate() {
+			assert_approx_eq(&open.point(index as f32 / 3.0), point, 1e-5);
+			assert_approx_eq(&closed.point(index as f32 / 4.0), point, 1e-5);
+		}
+
+		// A closed spline ends where it starts
+		assert_approx_eq(&closed.point(1.0), &points[0], 1e-5);
+	}
+
+	#[test]
+	fn arc_length_parameterization() {
+		// The control points bunch up at the start so t moves slowly there but distance doesn't
+		let spline = Spline::bezier(vec![
+			Vector3::new(0.0, 0.0, 0.0),
+			Vector3::new(0.0, 0.0, 0.0),
+			Vector3::new(0.0, 0.0, 0.0),
+			Vector3::new(0.0, 0.0, 8.0)
+		]);
+
+		assert!((spline.length() - 8.0).abs() < 1e-4);
+		assert_approx_eq(&spline.point_at_distance(2.0), &Vector3::new(0.0, 0.0, 2.0), 5e-2);
+		assert_approx_eq(&spline.point_at_distance(6.0), &Vector3::new(0.0, 0.0, 6.0), 5e-2);
+		assert_eq!(spline.t_at_distance(-1.0), 0.0);
+		assert_eq!(spline.t_at_distance(100.0), 1.0);
+	}
+
+	#[test]
+	fn orientation_faces_along_the_spline() {
+		let spline = Spline::catmull_rom(vec![vector3::ZERO, Vector3::new(5.0, 0.0, 0.0)], false);
+		let orientation = spline.orientation_at_distance(2.0, &vector3::UNIT_Y);
+		let mut forward = vector3::UNIT_Z;
+		forward.apply_quaternion(&orientation);
+		assert_approx_eq(&forward, &vector3::UNIT_X, 1e-5);
+	}
+}
\ No newline at end of file
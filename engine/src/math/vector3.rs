@@ -1,4 +1,4 @@
-use super::{Vector4, Quaternion, ApproxEq};
+use super::{Vector4, Quaternion, Matrix4, ApproxEq};
 use std::fmt::Display;
 use auto_ops::impl_op_ex;
 
@@ -100,6 +100,28 @@ impl Vector3 {
 		self.z = iz * q.w + iw * -q.z + ix * -q.y - iy * -q.x;
 	}
 	
+	// Transforms the vector as a point, the matrix must be affine
+	pub fn apply_matrix4(&mut self, m: &Matrix4) {
+		let e = &m.elements;
+		let (x, y, z) = (self.x, self.y, self.z);
+		self.x = e[0][0] * x + e[0][1] * y + e[0][2] * z + e[0][3];
+		self.y = e[1][0] * x + e[1][1] * y + e[1][2] * z + e[1][3];
+		self.z = e[2][0] * x + e[2][1] * y + e[2][2] * z + e[2][3];
+	}
+
+	// Transforms the vector as a direction so the translation is ignored
+	pub fn transform_direction(&mut self, m: &Matrix4) {
+		let e = &m.elements;
+		let (x, y, z) = (self.x, self.y, self.z);
+		self.x = e[0][0] * x + e[0][1] * y + e[0][2] * z;
+		self.y = e[1][0] * x + e[1][1] * y + e[1][2] * z;
+		self.z = e[2][0] * x + e[2][1] * y + e[2][2] * z;
+	}
+
+	pub fn distance(&self, other: &Self) -> f32 {
+		(*self - *other).length()
+	}
+
 	pub fn min(&mut self, other: &Self) {
 		self.x = self.x.min(other.x);
 		self.y = self.y.min(other.y);
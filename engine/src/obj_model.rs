@@ -0,0 +1,391 @@
+use std::{collections::HashMap, error::Error, f32::consts::PI, fmt, fs, io, path::{Path, PathBuf}};
+use crate::{Geometry3D, geometry3d::Topology, math::Color};
+
+#[derive(Debug)]
+pub enum ObjError {
+	Io { path: PathBuf, error: io::Error },
+	Parse { line: usize, reason: String },
+	MaterialParse { path: PathBuf, line: usize, reason: String }
+}
+
+impl fmt::Display for ObjError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read {}: {}", path.display(), error),
+			Self::Parse { line, reason } => write!(f, "OBJ line {} is invalid: {}", line, reason),
+			Self::MaterialParse { path, line, reason } => write!(f, "{} line {} is invalid: {}", path.display(), line, reason)
+		}
+	}
+}
+
+impl Error for ObjError {}
+
+// What the renderer can use of an MTL material, textures aren't loaded
+#[derive(Clone, PartialEq, Debug)]
+pub struct ObjMaterial {
+	pub name: String,
+	pub diffuse: Color,
+	// The dissolve is below 1
+	pub transparent: bool
+}
+
+pub struct ObjMesh {
+	// The object or group the faces are in
+	pub name: Option<String>,
+	pub material: Option<String>,
+	pub geometry: Geometry3D
+}
+
+// The triangles of an OBJ file with a mesh for each object, group and material used in them. Polygons are split into fans of
+// triangles. Meshes without normals get flat ones, or smooth ones when a smoothing group is on. Texture coordinates are
+// flipped vertically to have the origin at the top left like the renderer. Only polygonal faces are loaded, points, lines and
+// curves are skipped. Each mesh can have at most 65536 unique vertices since they're indexed with 16 bits.
+pub struct ObjModel {
+	pub meshes: Vec<ObjMesh>,
+	pub materials: Vec<ObjMaterial>,
+	// The MTL files from mtllib statements, loading the model reads them next to it
+	pub material_libraries: Vec<String>
+}
+
+// The faces of a mesh while it's being read
+struct PartialMesh {
+	name: Option<String>,
+	material: Option<String>,
+	smooth: bool,
+	vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u16>,
+	indices: Vec<u16>,
+	attributes: Vec<f32>,
+	uvs: Vec<f32>,
+	has_normals: bool,
+	has_uvs: bool
+}
+
+impl PartialMesh {
+	fn new(name: Option<String>, material: Option<String>, smooth: bool) -> Self {
+		Self {
+			name,
+			material,
+			smooth,
+			vertex_indices: HashMap::new(),
+			indices: Vec::new(),
+			attributes: Vec::new(),
+			uvs: Vec::new(),
+			has_normals: false,
+			has_uvs: false
+		}
+	}
+
+	fn finish(self) -> Option<ObjMesh> {
+		if self.indices.is_empty() {
+			return None;
+		}
+
+		let mut geometry = Geometry3D::new(self.indices, self.attributes, Topology::Triangle);
+
+		if !self.has_normals {
+			geometry.recompute_normals(if self.smooth { PI } else { 0.0 });
+		}
+
+		if self.has_uvs {
+			geometry.set_uvs(self.uvs);
+		}
+
+		Some(ObjMesh {
+			name: self.name,
+			material: self.material,
+			geometry
+		})
+	}
+}
+
+impl ObjModel {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })?;
+		let mut model = Self::parse(&text)?;
+		let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+		for library in &model.material_libraries {
+			let library_path = directory.join(library);
+			let text = fs::read_to_string(&library_path).map_err(|error| ObjError::Io { path: library_path.clone(), error })?;
+
+			let materials = ObjMaterial::parse(&text).map_err(|error| match error {
+				ObjError::Parse { line, reason } => ObjError::MaterialParse { path: library_path.clone(), line, reason },
+				error => error
+			})?;
+
+			model.materials.extend(materials);
+		}
+
+		Ok(model)
+	}
+
+	// Materials aren't read, see material_libraries
+	pub fn parse(text: &str) -> Result<Self, ObjError> {
+		let mut positions: Vec<[f32; 3]> = Vec::new();
+		let mut normals: Vec<[f32; 3]> = Vec::new();
+		let mut uvs: Vec<[f32; 2]> = Vec::new();
+		let mut material_libraries = Vec::new();
+		let mut meshes = Vec::new();
+		let mut mesh = PartialMesh::new(None, None, false);
+
+		for (index, line) in text.lines().enumerate() {
+			let error = |reason: String| ObjError::Parse { line: index + 1, reason };
+			let line = line.split('#').next().unwrap().trim();
+			let mut words = line.split_whitespace();
+
+			let keyword = match words.next() {
+				Some(keyword) => keyword,
+				None => continue
+			};
+
+			let numbers = |count: usize| -> Result<Vec<f32>, ObjError> {
+				let numbers = words.clone()
+					.map(|word| word.parse::<f32>().map_err(|_| error(format!("{} is not a number", word))))
+					.collect::<Result<Vec<f32>, ObjError>>()?;
+
+				if numbers.len() < count {
+					return Err(error(format!("{} needs {} numbers", keyword, count)));
+				}
+
+				Ok(numbers)
+			};
+
+			match keyword {
+				"v" => {
+					let v = numbers(3)?;
+					positions.push([v[0], v[1], v[2]]);
+				},
+				"vn" => {
+					let n = numbers(3)?;
+					normals.push([n[0], n[1], n[2]]);
+				},
+				"vt" => {
+					let t = numbers(1)?;
+					uvs.push([t[0], 1.0 - t.get(1).unwrap_or(&0.0)]);
+				},
+				"o" | "g" | "usemtl" => {
+					let name = line[keyword.len()..].trim();
+					let name = if name.is_empty() { None } else { Some(name.to_string()) };
+
+					let (mesh_name, material) = match keyword {
+						"usemtl" => (mesh.name.clone(), name),
+						_ => (name, mesh.material.clone())
+					};
+
+					let next_mesh = PartialMesh::new(mesh_name, material, mesh.smooth);
+					meshes.extend(std::mem::replace(&mut mesh, next_mesh).finish());
+				},
+				"s" => mesh.smooth = !matches!(words.next(), Some("off") | Some("0") | None),
+				"mtllib" => material_libraries.push(line[keyword.len()..].trim().to_string()),
+				"f" => {
+					let mut corners = Vec::new();
+
+					for word in words {
+						let mut parts = word.split('/');
+
+						// Negative indices count back from the last one read
+						let resolve = |part: Option<&str>, count: usize, name: &str| -> Result<Option<usize>, ObjError> {
+							let part = match part {
+								Some(part) if !part.is_empty() => part,
+								_ => return Ok(None)
+							};
+
+							let index: isize = part.parse().map_err(|_| error(format!("{} is not a {} index", part, name)))?;
+							let resolved = if index < 0 { count as isize + index } else { index - 1 };
+
+							if resolved < 0 || resolved as usize >= count {
+								return Err(error(format!("{} index {} is out of range", name, index)));
+							}
+
+							Ok(Some(resolved as usize))
+						};
+
+						let position_index = resolve(parts.next(), positions.len(), "vertex")?.ok_or_else(|| error(format!("{} has no vertex", word)))?;
+						let uv_index = resolve(parts.next(), uvs.len(), "texture coordinate")?;
+						let normal_index = resolve(parts.next(), normals.len(), "normal")?;
+						let key = (position_index, uv_index, normal_index);
+
+						let vertex_index = match mesh.vertex_indices.get(&key) {
+							Some(vertex_index) => *vertex_index,
+							None => {
+								let vertex_count = mesh.vertex_indices.len();
+
+								if vertex_count > u16::MAX as usize {
+									return Err(error(String::from("the mesh has more vertices than 16 bit indices can reach")));
+								}
+
+								mesh.attributes.extend_from_slice(&positions[position_index]);
+								mesh.attributes.extend_from_slice(&normal_index.map_or([0.0; 3], |normal_index| normals[normal_index]));
+								mesh.uvs.extend_from_slice(&uv_index.map_or([0.0; 2], |uv_index| uvs[uv_index]));
+								mesh.has_normals |= normal_index.is_some();
+								mesh.has_uvs |= uv_index.is_some();
+								mesh.vertex_indices.insert(key, vertex_count as u16);
+								vertex_count as u16
+							}
+						};
+
+						corners.push(vertex_index);
+					}
+
+					if corners.len() < 3 {
+						return Err(error(String::from("a face needs at least 3 vertices")));
+					}
+
+					for i in 1..corners.len() - 1 {
+						mesh.indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+					}
+				},
+				// Points, lines, curves and render attributes
+				_ => ()
+			}
+		}
+
+		meshes.extend(mesh.finish());
+
+		Ok(Self {
+			meshes,
+			materials: Vec::new(),
+			material_libraries
+		})
+	}
+}
+
+impl ObjMaterial {
+	// The materials in an MTL file, errors are ObjError::Parse with the line in the MTL file
+	pub fn parse(text: &str) -> Result<Vec<Self>, ObjError> {
+		let mut materials: Vec<Self> = Vec::new();
+
+		for (index, line) in text.lines().enumerate() {
+			let error = |reason: String| ObjError::Parse { line: index + 1, reason };
+			let line = line.split('#').next().unwrap().trim();
+			let mut words = line.split_whitespace();
+
+			let keyword = match words.next() {
+				Some(keyword) => keyword,
+				None => continue
+			};
+
+			if keyword == "newmtl" {
+				materials.push(Self {
+					name: line[keyword.len()..].trim().to_string(),
+					diffuse: Color::rgb(1.0, 1.0, 1.0),
+					transparent: false
+				});
+
+				continue;
+			}
+
+			// Textures, lighting models and the other colors
+			if !matches!(keyword, "Kd" | "d" | "Tr") {
+				continue;
+			}
+
+			let material = materials.last_mut().ok_or_else(|| error(format!("{} is not in a material", keyword)))?;
+
+			let numbers = words
+				.map(|word| word.parse::<f32>().map_err(|_| error(format!("{} is not a number", word))))
+				.collect::<Result<Vec<f32>, ObjError>>()?;
+
+			match (keyword, numbers.as_slice()) {
+				("Kd", [r, g, b, ..]) => material.diffuse = Color::new(*r, *g, *b, material.diffuse.a),
+				("d", [dissolve, ..]) => {
+					material.diffuse.a = *dissolve;
+					material.transparent = *dissolve < 1.0;
+				},
+				// The inverse of the dissolve some exporters write instead
+				("Tr", [transparency, ..]) => {
+					material.diffuse.a = 1.0 - transparency;
+					material.transparent = *transparency > 0.0;
+				},
+				_ => return Err(error(format!("{} needs more numbers", keyword)))
+			}
+		}
+
+		Ok(materials)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{Vector3, assert_approx_eq};
+
+	fn normal(geometry: &Geometry3D, vertex: usize) -> Vector3 {
+		let attributes = &geometry.attributes()[vertex * 6 + 3..vertex * 6 + 6];
+		Vector3::new(attributes[0], attributes[1], attributes[2])
+	}
+
+	#[test]
+	fn parses_faces() {
+		let model = ObjModel::parse("
+			# A quad with normals and texture coordinates then a triangle without either
+			mtllib scene.mtl
+			o quad
+			v 0 0 0
+			v 1 0 0
+			v 1 1 0
+			v 0 1 0
+			vt 0 0
+			vt 1 1
+			vn 0 0 1
+			usemtl red
+			f 1/1/1 2/1/1 3/2/1 4/2/1
+			g tri
+			f -4 -3 -1
+		").unwrap();
+
+		assert_eq!(model.material_libraries, vec!["scene.mtl"]);
+		assert_eq!(model.meshes.len(), 2);
+
+		let quad = &model.meshes[0];
+		assert_eq!(quad.name.as_deref(), Some("quad"));
+		assert_eq!(quad.material.as_deref(), Some("red"));
+		assert_eq!(quad.geometry.indices(), &[0, 1, 2, 0, 2, 3]);
+		assert_eq!(quad.geometry.uvs(), &[0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0]);
+		assert_eq!(normal(&quad.geometry, 2), Vector3::new(0.0, 0.0, 1.0));
+
+		// The group keeps the material and gets flat normals
+		let triangle = &model.meshes[1];
+		assert_eq!(triangle.name.as_deref(), Some("tri"));
+		assert_eq!(triangle.material.as_deref(), Some("red"));
+		assert_eq!(triangle.geometry.indices().len(), 3);
+		assert!(triangle.geometry.uvs().is_empty());
+		assert_approx_eq(&normal(&triangle.geometry, 0), &Vector3::new(0.0, 0.0, 1.0), 1e-6);
+	}
+
+	#[test]
+	fn reports_invalid_lines() {
+		let error = |text: &str| match ObjModel::parse(text) {
+			Err(ObjError::Parse { line, .. }) => line,
+			_ => panic!("{} should be invalid", text)
+		};
+
+		assert_eq!(error("v 0 0 0\nv 1 0 0\nf 1 2 3"), 3);
+		assert_eq!(error("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2"), 4);
+		assert_eq!(error("v 0 0"), 1);
+		assert_eq!(error("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1/1 2 3"), 4);
+		assert_eq!(error("v 0 0 zero"), 1);
+	}
+
+	#[test]
+	fn parses_materials() {
+		let materials = ObjMaterial::parse("
+			newmtl red
+			Ka 0 0 0
+			Kd 1 0 0
+			map_Kd red.png
+
+			newmtl glass
+			Kd 0.5 0.5 0.5
+			d 0.25
+		").unwrap();
+
+		assert_eq!(materials, vec![
+			ObjMaterial { name: String::from("red"), diffuse: Color::rgb(1.0, 0.0, 0.0), transparent: false },
+			ObjMaterial { name: String::from("glass"), diffuse: Color::new(0.5, 0.5, 0.5, 0.25), transparent: true }
+		]);
+
+		assert!(matches!(ObjMaterial::parse("Kd 1 1 1"), Err(ObjError::Parse { line: 1, .. })));
+	}
+}
\ No newline at end of file
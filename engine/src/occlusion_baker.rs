@@ -0,0 +1,150 @@
+use std::f32::consts::PI;
+use crate::{
+	Bvh,
+	Geometry3D,
+	geometry3d::Topology,
+	math::{Matrix4, Ray, Vector3}
+};
+
+// Bakes ambient occlusion per vertex offline by casting rays over the hemisphere around each vertex's normal against every
+// occluder added. A much cheaper alternative to a lightmap for static scenes, the result goes into Geometry3D::set_occlusion.
+pub struct OcclusionBaker {
+	// Rays cast per vertex
+	pub sample_count: usize,
+	// Occluders farther than this don't darken a vertex, keeps open rooms from going dark in the corners of the far walls
+	pub max_distance: f32,
+	// How far along the normal rays start so surfaces don't occlude themselves
+	pub bias: f32,
+	occluders: Vec<[Vector3; 3]>
+}
+
+impl OcclusionBaker {
+	pub fn new() -> Self {
+		Self {
+			sample_count: 64,
+			max_distance: 2.0,
+			bias: 0.001,
+			occluders: Vec::new()
+		}
+	}
+
+	// The triangles of the geometry block rays, usually every static geometry in the scene including the ones being baked
+	pub fn add_occluder(&mut self, geometry: &Geometry3D, global_matrix: &Matrix4) {
+		if !matches!(geometry.topology(), Topology::Triangle) {
+			return;
+		}
+
+		let attributes = geometry.attributes();
+
+		for triangle in geometry.indices().chunks_exact(3) {
+			self.occluders.push([0, 1, 2].map(|corner| {
+				let offset = triangle[corner] as usize * 6;
+				let mut position = Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2]);
+				position.apply_matrix4(global_matrix);
+				position
+			}));
+		}
+	}
+
+	// The fraction of rays from each vertex which escape, 1 is fully open and 0 fully occluded
+	pub fn bake(&self, geometry: &Geometry3D, global_matrix: &Matrix4) -> Vec<f32> {
+		assert!(matches!(geometry.topology(), Topology::Triangle), "Only triangle geometry can have occlusion baked");
+
+		let bvh = Bvh::from_triangles(self.occluders.clone());
+		let directions = self.hemisphere_directions();
+
+		let mut normal_matrix = *global_matrix;
+		normal_matrix.invert();
+		normal_matrix.transpose();
+
+		geometry.attributes()
+			.chunks_exact(6)
+			.map(|vertex| {
+				let mut position = Vector3::new(vertex[0], vertex[1], vertex[2]);
+				position.apply_matrix4(global_matrix);
+				let mut normal = Vector3::new(vertex[3], vertex[4], vertex[5]);
+				normal.transform_direction(&normal_matrix);
+				normal.normalize();
+
+				let (tangent, bitangent) = tangents(&normal);
+				let origin = position + normal * self.bias;
+
+				let escaped = directions.iter()
+					.filter(|[x, y, z]| {
+						let direction = tangent * *x + bitangent * *y + normal * *z;
+						!bvh.intersects_ray(&Ray::new(origin, direction), self.max_distance)
+					})
+					.count();
+
+				escaped as f32 / directions.len() as f32
+			})
+			.collect()
+	}
+
+	// Spread evenly over the hemisphere around +z with more towards the pole, the same cosine weighting as the light a
+	// surface receives from each direction
+	fn hemisphere_directions(&self) -> Vec<[f32; 3]> {
+		let sample_count = self.sample_count.max(1);
+
+		(0..sample_count)
+			.map(|index| {
+				let u = (index as f32 + 0.5) / sample_count as f32;
+				let v = radical_inverse(index);
+				let radius = u.sqrt();
+				let angle = 2.0 * PI * v;
+				[radius * angle.cos(), radius * angle.sin(), (1.0 - u).sqrt()]
+			})
+			.collect()
+	}
+}
+
+// The index's bits mirrored around the binary point, a low discrepancy sequence in [0, 1)
+fn radical_inverse(index: usize) -> f32 {
+	(index as u32).reverse_bits() as f32 / 2f32.powi(32)
+}
+
+// Two unit vectors perpendicular to the normal and each other
+fn tangents(normal: &Vector3) -> (Vector3, Vector3) {
+	let mut tangent = if normal.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+	tangent.cross(normal);
+	tangent.normalize();
+
+	let mut bitangent = *normal;
+	bitangent.cross(&tangent);
+	(tangent, bitangent)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::{matrix4, quaternion};
+
+	#[test]
+	fn ceiling_occludes_floor() {
+		let floor = Geometry3D::create_plane();
+		let ceiling = Geometry3D::create_plane();
+		let mut ceiling_matrix = matrix4::IDENTITY;
+		ceiling_matrix.compose(&Vector3::new(0.0, 1.0, 0.0), &quaternion::ZERO, &Vector3::from_scalar(50.0));
+
+		// Nothing above the floor
+		let mut baker = OcclusionBaker::new();
+		baker.add_occluder(&floor, &matrix4::IDENTITY);
+		assert!(baker.bake(&floor, &matrix4::IDENTITY).iter().all(|occlusion| *occlusion == 1.0));
+
+		// Almost every ray hits the ceiling
+		baker.add_occluder(&ceiling, &ceiling_matrix);
+		baker.max_distance = 100.0;
+		let occlusion = baker.bake(&floor, &matrix4::IDENTITY);
+		assert_eq!(occlusion.len(), 4);
+		assert!(occlusion.iter().all(|occlusion| *occlusion < 0.1));
+
+		// A quarter of the light comes from within 30 degrees of the horizon where the ceiling is over 2 away
+		baker.max_distance = 2.0;
+		let occlusion = baker.bake(&floor, &matrix4::IDENTITY);
+		assert!(occlusion.iter().all(|occlusion| (occlusion - 0.25).abs() < 0.05));
+
+		// Nothing counts when it's too far away
+		baker.max_distance = 0.5;
+		assert!(baker.bake(&floor, &matrix4::IDENTITY).iter().all(|occlusion| *occlusion == 1.0));
+	}
+}
\ No newline at end of file
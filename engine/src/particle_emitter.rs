@@ -0,0 +1,100 @@
+use crate::math::{Color, Vector3, color};
+
+// Spawns particles which the render system simulates and draws entirely on the GPU. Each particle takes the emitter's
+// parameters as they were when it spawned, so changing them only affects particles spawned after.
+pub struct ParticleEmitter {
+	pub position: Vector3,
+	// The velocity particles start with, each one gets a random velocity up to the spread added on top
+	pub velocity: Vector3,
+	pub spread: f32,
+	// Gravity or wind applied every frame
+	pub acceleration: Vector3,
+	// The fraction of the velocity lost every second
+	pub drag: f32,
+	// Seconds each particle lives
+	pub lifetime: f32,
+	// Particles fade from the start to the end color and size over their lifetime, they're drawn additively so the alpha
+	// scales how bright they are
+	pub start_color: Color,
+	pub end_color: Color,
+	pub start_size: f32,
+	pub end_size: f32,
+	// Particles spawned every second while emitting
+	pub rate: f32,
+	pub emitting: bool,
+	spawn_accumulator: f32,
+	spawn_count: usize
+}
+
+impl ParticleEmitter {
+	pub fn new(position: Vector3) -> Self {
+		Self {
+			position,
+			velocity: Vector3::new(0.0, 1.0, 0.0),
+			spread: 0.5,
+			acceleration: Vector3::new(0.0, 0.0, 0.0),
+			drag: 0.0,
+			lifetime: 2.0,
+			start_color: color::WHITE,
+			end_color: color::TRANSPARENT,
+			start_size: 0.1,
+			end_size: 0.1,
+			rate: 100.0,
+			emitting: true,
+			spawn_accumulator: 0.0,
+			spawn_count: 0
+		}
+	}
+
+	// Spawns the particles due over the time, fractions carry over so low rates still spawn evenly
+	pub fn update(&mut self, delta: f32) {
+		if !self.emitting {
+			return;
+		}
+
+		self.spawn_accumulator += self.rate.max(0.0) * delta;
+		let whole = self.spawn_accumulator.floor();
+		self.spawn_accumulator -= whole;
+		self.spawn_count += whole as usize;
+	}
+
+	// Spawns the particles all at once the next frame, for explosions and impacts
+	pub fn burst(&mut self, count: usize) {
+		self.spawn_count += count;
+	}
+
+	// The particles to spawn the next frame
+	pub fn spawn_count(&self) -> usize {
+		self.spawn_count
+	}
+
+	pub(crate) fn take_spawn_count(&mut self) -> usize {
+		std::mem::take(&mut self.spawn_count)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn spawns_at_rate() {
+		let mut emitter = ParticleEmitter::new(Vector3::new(0.0, 0.0, 0.0));
+		emitter.rate = 10.0;
+
+		// A particle every 0.1 seconds no matter how the time is split up
+		for _ in 0..25 {
+			emitter.update(0.01);
+		}
+
+		assert_eq!(emitter.take_spawn_count(), 2);
+		emitter.update(0.06);
+		assert_eq!(emitter.take_spawn_count(), 1);
+		assert_eq!(emitter.spawn_count(), 0);
+
+		emitter.emitting = false;
+		emitter.update(1.0);
+		emitter.burst(50);
+		assert_eq!(emitter.take_spawn_count(), 50);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,277 @@
+use std::{error::Error, fmt, fs, io, path::{Path, PathBuf}};
+use glfw::Key;
+
+// The keys which can be bound by name in a settings file, the names are the variant names
+const BINDABLE_KEYS: [Key; 74] = [
+	Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M, Key::N, Key::O,
+	Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+	Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+	Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+	Key::Space, Key::Escape, Key::Enter, Key::Tab, Key::Backspace, Key::Insert, Key::Delete,
+	Key::Up, Key::Down, Key::Left, Key::Right, Key::PageUp, Key::PageDown, Key::Home, Key::End,
+	Key::LeftShift, Key::LeftControl, Key::LeftAlt, Key::RightShift, Key::RightControl, Key::RightAlt,
+	Key::Minus, Key::Equal, Key::Comma, Key::Period, Key::GraveAccent
+];
+
+#[derive(Debug)]
+pub enum SettingsError {
+	Io { path: PathBuf, error: io::Error },
+	Parse { line: usize, reason: String }
+}
+
+impl fmt::Display for SettingsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot access settings file {}: {}", path.display(), error),
+			Self::Parse { line, reason } => write!(f, "Settings line {} is invalid: {}", line, reason)
+		}
+	}
+}
+
+impl Error for SettingsError {}
+
+// What the player can change from an options menu, kept in a small TOML file next to the game. Only what the engine writes
+// is understood, tables of keys with boolean, number and string values:
+//
+// [video]
+// width = 1280
+// height = 720
+// fullscreen = false
+// vsync = true
+// render_scale = 1
+//
+// [audio]
+// volume = 0.8
+//
+// [key_bindings]
+// move_forward = "W"
+//
+// Anything the file doesn't mention keeps the value it had before loading, so set the defaults and bindings first. Unknown
+// tables and keys are skipped so older builds can read newer files.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+	pub window_width: u32,
+	pub window_height: u32,
+	pub fullscreen: bool,
+	pub vsync: bool,
+	pub render_scale: f32,
+	// From 0 to 1, the engine doesn't play audio so it's up to the game to apply it
+	pub volume: f32,
+	key_bindings: Vec<(String, Key)>
+}
+
+impl Settings {
+	pub fn new() -> Self {
+		Self {
+			window_width: 1280,
+			window_height: 720,
+			fullscreen: false,
+			vsync: true,
+			render_scale: 1.0,
+			volume: 1.0,
+			key_bindings: vec![]
+		}
+	}
+
+	// The key bound to an action, such as "move_forward"
+	pub fn key(&self, action: &str) -> Option<Key> {
+		self.key_bindings.iter().find(|(bound_action, _)| bound_action == action).map(|(_, key)| *key)
+	}
+
+	pub fn bind_key(&mut self, action: &str, key: Key) {
+		assert!(BINDABLE_KEYS.contains(&key), "Key {:?} cannot be bound", key);
+
+		match self.key_bindings.iter_mut().find(|(bound_action, _)| bound_action == action) {
+			Some(binding) => binding.1 = key,
+			None => self.key_bindings.push((action.to_string(), key))
+		}
+	}
+
+	pub fn key_bindings(&self) -> &[(String, Key)] {
+		&self.key_bindings
+	}
+
+	// A missing file isn't an error, the settings are left as they are until they're first saved. Returns whether the file
+	// was there.
+	pub fn load(&mut self, path: impl AsRef<Path>) -> Result<bool, SettingsError> {
+		let path = path.as_ref();
+
+		match fs::read_to_string(path) {
+			Ok(text) => self.parse(&text).map(|_| true),
+			Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+			Err(error) => Err(SettingsError::Io { path: path.to_path_buf(), error })
+		}
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SettingsError> {
+		let path = path.as_ref();
+		fs::write(path, self.to_toml()).map_err(|error| SettingsError::Io { path: path.to_path_buf(), error })
+	}
+
+	pub fn parse(&mut self, text: &str) -> Result<(), SettingsError> {
+		let mut table = String::new();
+
+		for (index, line) in text.lines().enumerate() {
+			let line = line.trim();
+			let error = |reason: String| SettingsError::Parse { line: index + 1, reason };
+
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			if let Some(name) = line.strip_prefix('[') {
+				table = name.strip_suffix(']').ok_or_else(|| error(String::from("the table name isn't closed")))?.trim().to_string();
+				continue;
+			}
+
+			let (name, value) = line.split_once('=').ok_or_else(|| error(String::from("expected a key and value separated by =")))?;
+			let value = Value::parse(value.trim()).ok_or_else(|| error(format!("{} is not a boolean, number or string", value.trim())))?;
+			let name = name.trim();
+
+			let mismatch = |expected: &str| error(format!("{}.{} must be {}", table, name, expected));
+
+			match (table.as_str(), name) {
+				("video", "width") => self.window_width = value.as_u32().ok_or_else(|| mismatch("a positive whole number"))?,
+				("video", "height") => self.window_height = value.as_u32().ok_or_else(|| mismatch("a positive whole number"))?,
+				("video", "fullscreen") => self.fullscreen = value.as_bool().ok_or_else(|| mismatch("true or false"))?,
+				("video", "vsync") => self.vsync = value.as_bool().ok_or_else(|| mismatch("true or false"))?,
+				("video", "render_scale") => self.render_scale = value.as_f32().filter(|scale| *scale > 0.0).ok_or_else(|| mismatch("a positive number"))?,
+				("audio", "volume") => self.volume = value.as_f32().filter(|volume| (0.0..=1.0).contains(volume)).ok_or_else(|| mismatch("a number from 0 to 1"))?,
+				("key_bindings", action) => {
+					let key = value.as_str().and_then(key_from_name).ok_or_else(|| mismatch("the name of a key"))?;
+					self.bind_key(action, key);
+				},
+				_ => println!("Skipping unknown setting {}.{}", table, name)
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn to_toml(&self) -> String {
+		let mut text = format!(
+			"[video]\nwidth = {}\nheight = {}\nfullscreen = {}\nvsync = {}\nrender_scale = {}\n\n[audio]\nvolume = {}\n\n[key_bindings]\n",
+			self.window_width, self.window_height, self.fullscreen, self.vsync, self.render_scale, self.volume);
+
+		for (action, key) in &self.key_bindings {
+			text += &format!("{} = \"{:?}\"\n", action, key);
+		}
+
+		text
+	}
+}
+
+pub(crate) enum Value<'a> {
+	Bool(bool),
+	Number(f64),
+	String(&'a str)
+}
+
+impl<'a> Value<'a> {
+	// Strings are in double quotes without escapes, anything else after the value has to be a comment
+	pub(crate) fn parse(text: &'a str) -> Option<Self> {
+		if let Some(rest) = text.strip_prefix('"') {
+			let (string, rest) = rest.split_once('"')?;
+			return (rest.trim().is_empty() || rest.trim().starts_with('#')).then_some(Self::String(string));
+		}
+
+		let text = text.split('#').next().unwrap().trim();
+
+		match text {
+			"true" => Some(Self::Bool(true)),
+			"false" => Some(Self::Bool(false)),
+			_ => text.parse().ok().map(Self::Number)
+		}
+	}
+
+	pub(crate) fn as_bool(&self) -> Option<bool> {
+		match self {
+			Self::Bool(value) => Some(*value),
+			_ => None
+		}
+	}
+
+	pub(crate) fn as_f32(&self) -> Option<f32> {
+		match self {
+			Self::Number(value) => Some(*value as f32),
+			_ => None
+		}
+	}
+
+	pub(crate) fn as_u32(&self) -> Option<u32> {
+		match self {
+			Self::Number(value) if value.fract() == 0.0 && *value >= 1.0 && *value <= u32::MAX as f64 => Some(*value as u32),
+			_ => None
+		}
+	}
+
+	pub(crate) fn as_str(&self) -> Option<&'a str> {
+		match self {
+			Self::String(value) => Some(value),
+			_ => None
+		}
+	}
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+	BINDABLE_KEYS.iter().find(|key| format!("{:?}", key) == name).copied()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_and_round_trips() {
+		let mut settings = Settings::new();
+		settings.bind_key("move_forward", Key::W);
+		settings.bind_key("jump", Key::Space);
+
+		settings.parse("
+			# Written by the options menu
+			[video]
+			width = 1920
+			fullscreen = true # exclusive
+			render_scale = 0.75
+
+			[audio]
+			volume = 0.5
+
+			[key_bindings]
+			move_forward = \"Up\"
+			crouch = \"LeftControl\"
+
+			[future]
+			hdr = true
+		").unwrap();
+
+		assert_eq!(settings.window_width, 1920);
+		assert_eq!(settings.window_height, 720);
+		assert!(settings.fullscreen && settings.vsync);
+		assert_eq!(settings.render_scale, 0.75);
+		assert_eq!(settings.volume, 0.5);
+		assert_eq!(settings.key("move_forward"), Some(Key::Up));
+		assert_eq!(settings.key("jump"), Some(Key::Space));
+		assert_eq!(settings.key("crouch"), Some(Key::LeftControl));
+		assert_eq!(settings.key("fire"), None);
+
+		let mut loaded = Settings::new();
+		loaded.parse(&settings.to_toml()).unwrap();
+		assert_eq!(loaded, settings);
+	}
+
+	#[test]
+	fn reports_invalid_lines() {
+		let error = |text: &str| match Settings::new().parse(text) {
+			Err(SettingsError::Parse { line, .. }) => line,
+			_ => panic!("{} should be invalid", text)
+		};
+
+		assert_eq!(error("[video]\nwidth = wide"), 2);
+		assert_eq!(error("[video]\n\nwidth = -5"), 3);
+		assert_eq!(error("[audio]\nvolume = 2"), 2);
+		assert_eq!(error("[key_bindings]\njump = \"Hyperspace\""), 2);
+		assert_eq!(error("[video\nvsync = true"), 1);
+		assert_eq!(error("vsync true"), 1);
+	}
+}
\ No newline at end of file
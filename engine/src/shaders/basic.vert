@@ -19,6 +19,9 @@ vec3 colors[3] = vec3[](
 	vec3(0.0, 0.0, 1.0)
 );
 
+// The depth prepass runs this shader in a separate pipeline so the position must be computed identically
+invariant gl_Position;
+
 void main() {
 	gl_Position = projectionMatrix * viewMatrix * modelMatrix[gl_InstanceIndex] * vec4(inPosition, 1.0);
 	fragColor = vec3(0.1, 0.1, 0.1);
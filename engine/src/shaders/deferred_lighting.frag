@@ -0,0 +1,114 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define FALLOFF_NONE 0
+#define FALLOFF_LINEAR 1
+#define FALLOFF_INVERSE_SQUARE 2
+
+struct PointLight {
+	vec3 position;
+	float range;
+	vec3 color;
+	uint falloff;
+};
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+	vec3 ambientLight;
+	uint pointLightCount;
+	uvec3 clusterGridSize;
+	float clusterDepthScale;
+	float clusterDepthBias;
+};
+
+layout(set = 0, binding = 1, std430) readonly buffer PointLights {
+	PointLight pointLights[];
+};
+
+// The offset into the light indices and the number of lights for every cluster
+layout(set = 0, binding = 2, std430) readonly buffer ClusterRanges {
+	uvec2 clusterRanges[];
+};
+
+layout(set = 0, binding = 3, std430) readonly buffer LightIndices {
+	uint lightIndices[];
+};
+
+layout(set = 1, binding = 0) uniform sampler2D albedoImage;
+layout(set = 1, binding = 1) uniform sampler2D normalImage;
+layout(set = 1, binding = 2) uniform sampler2D depthImage;
+
+layout(set = 2, binding = 0) uniform sampler2D ambientOcclusion;
+
+layout(push_constant, row_major) uniform PushConstants {
+	mat4 inverseProjectionMatrix;
+	mat4 cameraMatrix;
+};
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out vec4 outColor;
+
+float attenuation(PointLight light, float distance) {
+	if (distance >= light.range) {
+		return 0.0;
+	}
+
+	if (light.falloff == FALLOFF_LINEAR) {
+		return 1.0 - distance / light.range;
+	}
+
+	if (light.falloff == FALLOFF_INVERSE_SQUARE) {
+		float window = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+		return window * window / max(distance * distance, 0.0001);
+	}
+
+	return 1.0;
+}
+
+// Must match how the clusters are laid out when lights are assigned to them
+uint clusterIndex(vec3 viewPosition) {
+	vec4 clipPosition = projectionMatrix * vec4(viewPosition, 1.0);
+	vec2 tile = clamp(floor((clipPosition.xy / clipPosition.w * 0.5 + 0.5) * vec2(clusterGridSize.xy)), vec2(0.0), vec2(clusterGridSize.xy - 1u));
+	float slice = clamp(floor(log(max(viewPosition.z, 0.0001)) * clusterDepthScale - clusterDepthBias), 0.0, float(clusterGridSize.z - 1u));
+	return (uint(slice) * clusterGridSize.y + uint(tile.y)) * clusterGridSize.x + uint(tile.x);
+}
+
+void main() {
+	// Nothing was drawn into the G-buffer here so the forward meshes and the clear color are left alone
+	float depth = texture(depthImage, fragTexPosition).r;
+
+	if (depth == 1.0) {
+		discard;
+	}
+
+	gl_FragDepth = depth;
+
+	vec3 albedo = texture(albedoImage, fragTexPosition).rgb;
+	vec4 normal = texture(normalImage, fragTexPosition);
+
+	if (normal.w == 0.0) {
+		outColor = vec4(albedo, 1.0);
+		return;
+	}
+
+	vec4 viewPosition = inverseProjectionMatrix * vec4(fragTexPosition * 2.0 - 1.0, depth, 1.0);
+	viewPosition /= viewPosition.w;
+	vec3 position = vec3(cameraMatrix * viewPosition);
+
+	// The same lighting as the lambert material
+	vec3 diffuse = vec3(0.0);
+	uvec2 clusterRange = clusterRanges[clusterIndex(viewPosition.xyz)];
+
+	for (uint i = clusterRange.x; i < clusterRange.x + clusterRange.y; i++) {
+		PointLight light = pointLights[lightIndices[i]];
+		vec3 lightOffset = light.position - position;
+		float lambert = max(dot(normalize(normal.xyz), normalize(lightOffset)), 0.0);
+		diffuse += light.color * lambert * attenuation(light, length(lightOffset));
+	}
+
+	// The ambient occlusion is rendered at half the resolution of the scene
+	float occlusion = texture(ambientOcclusion, gl_FragCoord.xy / vec2(textureSize(ambientOcclusion, 0) * 2)).r;
+	outColor = vec4(albedo * (ambientLight * occlusion + diffuse), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,85 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define FALLOFF_NONE 0
+#define FALLOFF_LINEAR 1
+#define FALLOFF_INVERSE_SQUARE 2
+
+struct PointLight {
+	vec3 position;
+	float range;
+	vec3 color;
+	uint falloff;
+};
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+	vec3 ambientLight;
+	uint pointLightCount;
+	uvec3 clusterGridSize;
+	float clusterDepthScale;
+	float clusterDepthBias;
+};
+
+layout(set = 0, binding = 1, std430) readonly buffer PointLights {
+	PointLight pointLights[];
+};
+
+// The offset into the light indices and the number of lights for every cluster
+layout(set = 0, binding = 2, std430) readonly buffer ClusterRanges {
+	uvec2 clusterRanges[];
+};
+
+layout(set = 0, binding = 3, std430) readonly buffer LightIndices {
+	uint lightIndices[];
+};
+
+layout(location = 0) in vec3 fragAmbient;
+layout(location = 1) in vec3 fragPosition;
+layout(location = 2) in vec3 fragNormal;
+layout(location = 3) in vec3 fragViewPosition;
+
+layout(location = 0) out vec4 outColor;
+
+float attenuation(PointLight light, float distance) {
+	if (distance >= light.range) {
+		return 0.0;
+	}
+
+	if (light.falloff == FALLOFF_LINEAR) {
+		return 1.0 - distance / light.range;
+	}
+
+	if (light.falloff == FALLOFF_INVERSE_SQUARE) {
+		float window = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+		return window * window / max(distance * distance, 0.0001);
+	}
+
+	return 1.0;
+}
+
+// Must match how the clusters are laid out when lights are assigned to them
+uint clusterIndex() {
+	vec4 clipPosition = projectionMatrix * vec4(fragViewPosition, 1.0);
+	vec2 tile = clamp(floor((clipPosition.xy / clipPosition.w * 0.5 + 0.5) * vec2(clusterGridSize.xy)), vec2(0.0), vec2(clusterGridSize.xy - 1u));
+	float slice = clamp(floor(log(max(fragViewPosition.z, 0.0001)) * clusterDepthScale - clusterDepthBias), 0.0, float(clusterGridSize.z - 1u));
+	return (uint(slice) * clusterGridSize.y + uint(tile.y)) * clusterGridSize.x + uint(tile.x);
+}
+
+void main() {
+	// Both sides of a leaf are lit as if they faced the viewer
+	vec3 normal = normalize(fragNormal) * (gl_FrontFacing ? 1.0 : -1.0);
+	vec3 diffuse = vec3(0.0);
+	uvec2 clusterRange = clusterRanges[clusterIndex()];
+
+	for (uint i = clusterRange.x; i < clusterRange.x + clusterRange.y; i++) {
+		PointLight light = pointLights[lightIndices[i]];
+		vec3 lightOffset = light.position - fragPosition;
+		float lambert = max(dot(normal, normalize(lightOffset)), 0.0);
+		diffuse += light.color * lambert * attenuation(light, length(lightOffset));
+	}
+
+	// Foliage isn't drawn into the ambient occlusion so it's left out
+	outColor = vec4(fragAmbient + diffuse, 1.0);
+}
\ No newline at end of file
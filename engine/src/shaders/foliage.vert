@@ -0,0 +1,80 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define PI 3.14159265
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+	vec3 ambientLight;
+	uint pointLightCount;
+	uvec3 clusterGridSize;
+	float clusterDepthScale;
+	float clusterDepthBias;
+	vec3 windDirection;
+	float windStrength;
+	float windTime;
+	float windFrequency;
+	float windWavelength;
+	float windFlutterStrength;
+	float windFlutterFrequency;
+};
+
+layout(set = 1, binding = 0, std140, row_major) buffer InstanceData {
+	mat4 modelMatrix[];
+};
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec3 fragAmbient;
+layout(location = 1) out vec3 fragPosition;
+layout(location = 2) out vec3 fragNormal;
+layout(location = 3) out vec3 fragViewPosition;
+
+// Quantized geometry has its normal octahedral encoded in the first 2 components
+layout(constant_id = 0) const bool QUANTIZED = false;
+
+vec3 decodeNormal(vec3 normal) {
+	if (!QUANTIZED) {
+		return normal;
+	}
+
+	vec3 decoded = vec3(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+
+	if (decoded.z < 0.0) {
+		vec2 signs = mix(vec2(-1.0), vec2(1.0), greaterThanEqual(decoded.xy, vec2(0.0)));
+		decoded.xy = (1.0 - abs(decoded.yx)) * signs;
+	}
+
+	return normalize(decoded);
+}
+
+// Must match Wind::displacement, red bends the vertex with its branch, green flutters it along its normal and blue offsets
+// the phase
+vec3 windDisplacement(vec3 position, vec3 normal, vec4 color) {
+	float phase = color.b * 2.0 * PI;
+
+	float gustPhase = (windTime * windFrequency - dot(position, windDirection) / max(windWavelength, 0.0001)) * 2.0 * PI;
+	float bend = color.r * windStrength * (0.5 + 0.5 * sin(gustPhase + phase));
+
+	float flutterPhase = windTime * windFlutterFrequency * 2.0 * PI + position.x + position.y + position.z;
+	float flutter = color.g * windFlutterStrength * sin(flutterPhase + phase);
+
+	return windDirection * bend + normal * flutter;
+}
+
+void main() {
+	vec3 worldPosition = vec3(modelMatrix[gl_InstanceIndex] * vec4(inPosition, 1.0));
+	vec3 worldNormal = normalize(mat3(transpose(inverse(modelMatrix[gl_InstanceIndex]))) * decodeNormal(inNormal));
+	worldPosition += windDisplacement(worldPosition, worldNormal, inColor);
+
+	vec4 vertexPositionViewSpace = viewMatrix * vec4(worldPosition, 1.0);
+	gl_Position = projectionMatrix * vertexPositionViewSpace;
+
+	fragAmbient = ambientLight;
+	fragPosition = worldPosition;
+	fragNormal = worldNormal;
+	fragViewPosition = vec3(vertexPositionViewSpace);
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// The material enum's values
+#define MATERIAL_BASIC 1
+#define MATERIAL_NORMAL 2
+#define MATERIAL_LAMBERT 3
+
+layout(location = 0) in vec3 fragNormal;
+layout(location = 1) in vec3 fragGeometryNormal;
+
+layout(location = 0) out vec4 outAlbedo;
+layout(location = 1) out vec4 outNormal;
+
+layout(constant_id = 1) const int MATERIAL = MATERIAL_LAMBERT;
+
+// The colors must match the forward material shaders, the normal's w is 1 for pixels the lighting pass lights and 0 for
+// pixels it outputs the albedo of as is
+void main() {
+	if (MATERIAL == MATERIAL_BASIC) {
+		outAlbedo = vec4(0.1, 0.1, 0.1, 1.0);
+		outNormal = vec4(0.0);
+	}
+	else if (MATERIAL == MATERIAL_NORMAL) {
+		outAlbedo = vec4(fragGeometryNormal * 0.5 + 0.5, 1.0);
+		outNormal = vec4(0.0);
+	}
+	else {
+		outAlbedo = vec4(1.0);
+		outNormal = vec4(normalize(fragNormal), 1.0);
+	}
+}
\ No newline at end of file
@@ -1,19 +1,10 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
 
-#define MAX_POINT_LIGHTS 5
-
-struct PointLight {
-	vec3 position;
-	vec3 color;
-};
-
 layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
 	mat4 projectionMatrix;
 	mat4 viewMatrix;
 	vec3 ambientLight;
-	uint pointLightCount;
-	PointLight pointLights[MAX_POINT_LIGHTS];
 };
 
 layout(set = 1, binding = 0, std140, row_major) buffer InstanceData {
@@ -22,21 +13,44 @@ layout(set = 1, binding = 0, std140, row_major) buffer InstanceData {
 
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inNormal;
+layout(location = 2) in float inOcclusion;
+
+layout(location = 0) out vec3 fragAmbient;
+layout(location = 1) out vec3 fragPosition;
+layout(location = 2) out vec3 fragNormal;
+layout(location = 3) out vec3 fragViewPosition;
+layout(location = 4) out float fragOcclusion;
+
+// Quantized geometry has its normal octahedral encoded in the first 2 components
+layout(constant_id = 0) const bool QUANTIZED = false;
+
+vec3 decodeNormal(vec3 normal) {
+	if (!QUANTIZED) {
+		return normal;
+	}
 
-layout(location = 0) out vec3 fragColor;
+	vec3 decoded = vec3(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+
+	if (decoded.z < 0.0) {
+		vec2 signs = mix(vec2(-1.0), vec2(1.0), greaterThanEqual(decoded.xy, vec2(0.0)));
+		decoded.xy = (1.0 - abs(decoded.yx)) * signs;
+	}
+
+	return normalize(decoded);
+}
+
+// The depth prepass runs this shader in a separate pipeline so the position must be computed identically
+invariant gl_Position;
 
 void main() {
 	vec4 vertexPositionObjectSpaceVec4 = modelMatrix[gl_InstanceIndex] * vec4(inPosition, 1.0);
-	vec3 vertexPositionObjectSpaceVec3 = vec3(vertexPositionObjectSpaceVec4);
-	vec3 vertexNormalObjectSpace = mat3(transpose(inverse(modelMatrix[gl_InstanceIndex]))) * inNormal;
+	vec4 vertexPositionViewSpace = viewMatrix * vertexPositionObjectSpaceVec4;
 	
-	gl_Position = projectionMatrix * viewMatrix * vertexPositionObjectSpaceVec4;
-
-	fragColor = ambientLight;
+	gl_Position = projectionMatrix * vertexPositionViewSpace;
 
-	for (int i = 0; i < pointLightCount; i++) {
-		vec3 lightDirection = normalize(pointLights[i].position - vertexPositionObjectSpaceVec3);
-		float diffuse = max(dot(vertexNormalObjectSpace, lightDirection), 0.0f);
-		fragColor += pointLights[i].color * diffuse;
-	}
+	fragAmbient = ambientLight;
+	fragPosition = vec3(vertexPositionObjectSpaceVec4);
+	fragNormal = mat3(transpose(inverse(modelMatrix[gl_InstanceIndex]))) * decodeNormal(inNormal);
+	fragViewPosition = vec3(vertexPositionViewSpace);
+	fragOcclusion = inOcclusion;
 }
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// The baked irradiance, all of the lighting comes from it
+layout(set = 3, binding = 0) uniform sampler2D lightmap;
+
+layout(location = 0) in vec2 fragLightmapUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+	outColor = vec4(texture(lightmap, fragLightmapUv).rgb, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 fragPosition;
+layout(location = 1) in vec4 fragPreviousPosition;
+
+layout(location = 0) out vec2 outVelocity;
+
+void main() {
+	// Normalized device coordinates span twice the range of texture coordinates
+	outVelocity = (fragPosition.xy / fragPosition.w - fragPreviousPosition.xy / fragPreviousPosition.w) * 0.5;
+}
\ No newline at end of file
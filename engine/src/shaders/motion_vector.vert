@@ -0,0 +1,27 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant, row_major) uniform PushConstants {
+	mat4 viewProjectionMatrix;
+	mat4 previousViewProjectionMatrix;
+};
+
+struct InstanceMatrices {
+	mat4 modelMatrix;
+	mat4 previousModelMatrix;
+};
+
+layout(set = 0, binding = 0, std140, row_major) buffer InstanceData {
+	InstanceMatrices instanceMatrices[];
+};
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 0) out vec4 fragPosition;
+layout(location = 1) out vec4 fragPreviousPosition;
+
+void main() {
+	InstanceMatrices matrices = instanceMatrices[gl_InstanceIndex];
+	fragPosition = viewProjectionMatrix * matrices.modelMatrix * vec4(inPosition, 1.0);
+	fragPreviousPosition = previousViewProjectionMatrix * matrices.previousModelMatrix * vec4(inPosition, 1.0);
+	gl_Position = fragPosition;
+}
\ No newline at end of file
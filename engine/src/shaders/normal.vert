@@ -14,7 +14,28 @@ layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inNormal;
 layout(location = 0) out vec3 fragColor;
 
+// Quantized geometry has its normal octahedral encoded in the first 2 components
+layout(constant_id = 0) const bool QUANTIZED = false;
+
+vec3 decodeNormal(vec3 normal) {
+	if (!QUANTIZED) {
+		return normal;
+	}
+
+	vec3 decoded = vec3(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+
+	if (decoded.z < 0.0) {
+		vec2 signs = mix(vec2(-1.0), vec2(1.0), greaterThanEqual(decoded.xy, vec2(0.0)));
+		decoded.xy = (1.0 - abs(decoded.yx)) * signs;
+	}
+
+	return normalize(decoded);
+}
+
+// The depth prepass runs this shader in a separate pipeline so the position must be computed identically
+invariant gl_Position;
+
 void main() {
 	gl_Position = projectionMatrix * viewMatrix * modelMatrix[gl_InstanceIndex] * vec4(inPosition, 1.0);
-	fragColor = inNormal * 0.5 + 0.5;
+	fragColor = decodeNormal(inNormal) * 0.5 + 0.5;
 }
\ No newline at end of file
@@ -0,0 +1,57 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+struct Particle {
+	vec3 position;
+	float age;
+	vec3 velocity;
+	float lifetime;
+	vec4 startColor;
+	vec4 endColor;
+	vec3 acceleration;
+	float drag;
+	float startSize;
+	float endSize;
+};
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+};
+
+layout(set = 1, binding = 0, std430) readonly buffer Particles {
+	Particle particles[];
+};
+
+layout(set = 1, binding = 2, std430) readonly buffer AliveLists {
+	uint aliveCounts[2];
+	uint aliveIndices[];
+};
+
+layout(push_constant) uniform PushConstants {
+	uint drawList;
+	uint maxParticles;
+};
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragCorner;
+
+const vec2 corners[6] = vec2[](
+	vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+	vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+// Expands each alive particle into a quad facing the camera
+void main() {
+	Particle particle = particles[aliveIndices[drawList * maxParticles + gl_InstanceIndex]];
+	float t = clamp(particle.age / particle.lifetime, 0.0, 1.0);
+	float size = mix(particle.startSize, particle.endSize, t);
+	vec2 corner = corners[gl_VertexIndex];
+
+	vec4 viewPosition = viewMatrix * vec4(particle.position, 1.0);
+	viewPosition.xy += corner * size;
+	gl_Position = projectionMatrix * viewPosition;
+
+	fragColor = mix(particle.startColor, particle.endColor, t);
+	fragCorner = corner;
+}
\ No newline at end of file
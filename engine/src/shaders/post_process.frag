@@ -0,0 +1,58 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define MOTION_BLUR_SAMPLE_COUNT 8
+
+layout(set = 0, binding = 0) uniform sampler2D sceneImage;
+layout(set = 0, binding = 1) uniform sampler3D lut;
+layout(set = 0, binding = 2) uniform sampler3D nextLut;
+layout(set = 0, binding = 3) uniform sampler2D velocityImage;
+
+layout(push_constant) uniform PushConstants {
+	float lutBlend;
+	float motionBlurScale;
+};
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 linearToSrgb(vec3 color) {
+	return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
+vec3 srgbToLinear(vec3 color) {
+	return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
+vec3 sampleLut(sampler3D lutSampler, vec3 color) {
+	// Sample at texel centers so the ends of the range map to the first and last entries
+	float size = float(textureSize(lutSampler, 0).x);
+	vec3 coordinates = color * ((size - 1.0) / size) + 0.5 / size;
+	return texture(lutSampler, coordinates).rgb;
+}
+
+// Averages the scene along the path the pixel took since the previous frame, scaled like a shutter being open for part of
+// the frame
+vec3 sampleScene() {
+	vec2 velocity = texture(velocityImage, fragTexPosition).xy * motionBlurScale;
+
+	if (motionBlurScale <= 0.0 || dot(velocity, velocity) == 0.0) {
+		return texture(sceneImage, fragTexPosition).rgb;
+	}
+
+	vec3 color = vec3(0.0);
+
+	for (int i = 0; i < MOTION_BLUR_SAMPLE_COUNT; i++) {
+		float offset = float(i) / float(MOTION_BLUR_SAMPLE_COUNT - 1) - 0.5;
+		color += texture(sceneImage, fragTexPosition - velocity * offset).rgb;
+	}
+
+	return color / float(MOTION_BLUR_SAMPLE_COUNT);
+}
+
+void main() {
+	vec3 color = clamp(linearToSrgb(sampleScene()), 0.0, 1.0);
+	vec3 graded = mix(sampleLut(lut, color), sampleLut(nextLut, color), lutBlend);
+	outColor = vec4(srgbToLinear(graded), 1.0);
+}
\ No newline at end of file
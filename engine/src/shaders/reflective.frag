@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define TINT vec3(0.9, 0.9, 0.95)
+
+// The scene seen by the camera mirrored across the reflection plane, it's rendered flipped left to right so its triangles
+// wind the usual way
+layout(set = 5, binding = 0) uniform sampler2D reflection;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+	vec2 uv = gl_FragCoord.xy / vec2(textureSize(reflection, 0));
+	outColor = vec4(texture(reflection, vec2(1.0 - uv.x, uv.y)).rgb * TINT, 1.0);
+}
\ No newline at end of file
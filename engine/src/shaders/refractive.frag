@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// How far in texture coordinates the copy of the scene is offset where the surface faces sideways
+#define DISTORTION_STRENGTH 0.05
+#define TINT vec3(0.9, 0.95, 1.0)
+
+// The color of the scene copied before refractive meshes are drawn
+layout(set = 4, binding = 0) uniform sampler2D refraction;
+
+layout(location = 0) in vec3 fragViewNormal;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+	// The view space normal bends the line of sight, where the surface faces the camera the scene behind is barely moved
+	vec2 distortion = normalize(fragViewNormal).xy * DISTORTION_STRENGTH;
+	vec2 uv = gl_FragCoord.xy / vec2(textureSize(refraction, 0)) + vec2(distortion.x, -distortion.y);
+	outColor = vec4(texture(refraction, uv).rgb * TINT, 1.0);
+}
\ No newline at end of file
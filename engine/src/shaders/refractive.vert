@@ -0,0 +1,40 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+};
+
+layout(set = 1, binding = 0, std140, row_major) buffer InstanceData {
+	mat4 modelMatrix[];
+};
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+
+layout(location = 0) out vec3 fragViewNormal;
+
+// Quantized geometry has its normal octahedral encoded in the first 2 components
+layout(constant_id = 0) const bool QUANTIZED = false;
+
+vec3 decodeNormal(vec3 normal) {
+	if (!QUANTIZED) {
+		return normal;
+	}
+
+	vec3 decoded = vec3(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+
+	if (decoded.z < 0.0) {
+		vec2 signs = mix(vec2(-1.0), vec2(1.0), greaterThanEqual(decoded.xy, vec2(0.0)));
+		decoded.xy = (1.0 - abs(decoded.yx)) * signs;
+	}
+
+	return normalize(decoded);
+}
+
+void main() {
+	mat4 modelViewMatrix = viewMatrix * modelMatrix[gl_InstanceIndex];
+	gl_Position = projectionMatrix * modelViewMatrix * vec4(inPosition, 1.0);
+	fragViewNormal = mat3(transpose(inverse(modelViewMatrix))) * decodeNormal(inNormal);
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant, row_major) uniform Sprite {
+	mat3 matrix;
+	vec4 color;
+	uint textureIndex;
+} sprite;
+
+layout(set = 0, binding = 0) uniform sampler samp;
+layout(constant_id = 0) const uint TEXTURE_COUNT = 10;
+layout(set = 1, binding = 0) uniform texture2D textures[TEXTURE_COUNT];
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+	outColor = texture(sampler2D(textures[sprite.textureIndex], samp), fragTexPosition) * sprite.color;
+}
\ No newline at end of file
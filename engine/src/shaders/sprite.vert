@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant, row_major) uniform Sprite {
+	mat3 matrix;
+	vec4 color;
+	uint textureIndex;
+} sprite;
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inTexPosition;
+
+layout(location = 0) out vec2 fragTexPosition;
+
+// Sprite and tilemap quads are in pixels, the matrix takes them into normalized device coordinates
+void main() {
+	vec3 normalizedPosition = sprite.matrix * vec3(inPosition, 1.0);
+	gl_Position = vec4(normalizedPosition.xy, 0.0, 1.0);
+	fragTexPosition = inTexPosition;
+}
\ No newline at end of file
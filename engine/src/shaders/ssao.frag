@@ -0,0 +1,68 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define SAMPLE_COUNT 16
+#define RADIUS 0.5
+#define BIAS 0.025
+#define GOLDEN_ANGLE 2.39996323
+
+layout(set = 0, binding = 0) uniform sampler2D normalImage;
+layout(set = 0, binding = 1) uniform sampler2D depthImage;
+
+layout(push_constant, row_major) uniform PushConstants {
+	mat4 projectionMatrix;
+	mat4 inverseProjectionMatrix;
+};
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out float outOcclusion;
+
+vec3 viewPosition(vec2 texPosition) {
+	float depth = texture(depthImage, texPosition).r;
+	vec4 position = inverseProjectionMatrix * vec4(texPosition * 2.0 - 1.0, depth, 1.0);
+	return position.xyz / position.w;
+}
+
+float hash(vec2 p) {
+	return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+	// Nothing was rendered here so there is nothing to occlude
+	if (texture(depthImage, fragTexPosition).r == 1.0) {
+		outOcclusion = 1.0;
+		return;
+	}
+
+	vec3 position = viewPosition(fragTexPosition);
+	vec3 normal = normalize(texture(normalImage, fragTexPosition).xyz);
+
+	// Rotate the sample kernel randomly per pixel, the blur pass removes the resulting noise
+	float angle = hash(gl_FragCoord.xy) * 6.2831853;
+	vec3 randomDirection = vec3(cos(angle), sin(angle), 0.0);
+	vec3 tangent = randomDirection - normal * dot(randomDirection, normal);
+	tangent = length(tangent) < 0.001 ? vec3(0.0, 0.0, 1.0) : normalize(tangent);
+	vec3 bitangent = cross(normal, tangent);
+	mat3 tbn = mat3(tangent, bitangent, normal);
+
+	float occlusion = 0.0;
+
+	for (int i = 0; i < SAMPLE_COUNT; i++) {
+		// Spread the samples over the hemisphere around the normal with more of them close to the fragment
+		float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+		float phi = float(i) * GOLDEN_ANGLE;
+		vec3 direction = vec3(cos(phi) * sqrt(t), sin(phi) * sqrt(t), sqrt(1.0 - t));
+		vec3 samplePosition = position + tbn * direction * RADIUS * mix(0.1, 1.0, t * t);
+
+		vec4 sampleClipPosition = projectionMatrix * vec4(samplePosition, 1.0);
+		vec2 sampleTexPosition = sampleClipPosition.xy / sampleClipPosition.w * 0.5 + 0.5;
+		float sceneDepth = viewPosition(sampleTexPosition).z;
+
+		// The camera looks down positive z so the sample is occluded when the scene in front of it is closer
+		float rangeCheck = smoothstep(0.0, 1.0, RADIUS / abs(position.z - sceneDepth));
+		occlusion += (sceneDepth <= samplePosition.z - BIAS ? 1.0 : 0.0) * rangeCheck;
+	}
+
+	outOcclusion = 1.0 - occlusion / float(SAMPLE_COUNT);
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define BLUR_RADIUS 2
+
+layout(set = 0, binding = 0) uniform sampler2D occlusionImage;
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out float outOcclusion;
+
+void main() {
+	vec2 texelSize = 1.0 / vec2(textureSize(occlusionImage, 0));
+	float occlusion = 0.0;
+
+	for (int x = -BLUR_RADIUS; x < BLUR_RADIUS; x++) {
+		for (int y = -BLUR_RADIUS; y < BLUR_RADIUS; y++) {
+			occlusion += texture(occlusionImage, fragTexPosition + (vec2(x, y) + 0.5) * texelSize).r;
+		}
+	}
+
+	outOcclusion = occlusion / float(4 * BLUR_RADIUS * BLUR_RADIUS);
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 fragNormal;
+
+layout(location = 0) out vec4 outNormal;
+
+void main() {
+	outNormal = vec4(normalize(fragNormal), 0.0);
+}
\ No newline at end of file
@@ -0,0 +1,42 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D sceneImage;
+layout(set = 0, binding = 1) uniform sampler2D historyImage;
+layout(set = 0, binding = 2) uniform sampler2D velocityImage;
+
+layout(push_constant) uniform PushConstants {
+	float historyWeight;
+};
+
+layout(location = 0) in vec2 fragTexPosition;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+	vec3 color = texture(sceneImage, fragTexPosition).rgb;
+
+	// The range of colors around the pixel this frame, history outside of it belongs to something else
+	vec2 texelSize = 1.0 / vec2(textureSize(sceneImage, 0));
+	vec3 minColor = color;
+	vec3 maxColor = color;
+
+	for (int x = -1; x <= 1; x++) {
+		for (int y = -1; y <= 1; y++) {
+			vec3 neighbor = texture(sceneImage, fragTexPosition + vec2(x, y) * texelSize).rgb;
+			minColor = min(minColor, neighbor);
+			maxColor = max(maxColor, neighbor);
+		}
+	}
+
+	// Find where the pixel was last frame, pixels which came in from off screen have no history
+	vec2 historyPosition = fragTexPosition - texture(velocityImage, fragTexPosition).xy;
+
+	if (any(lessThan(historyPosition, vec2(0.0))) || any(greaterThan(historyPosition, vec2(1.0)))) {
+		outColor = vec4(color, 1.0);
+		return;
+	}
+
+	vec3 history = clamp(texture(historyImage, historyPosition).rgb, minColor, maxColor);
+	outColor = vec4(mix(color, history, historyWeight), 1.0);
+}
\ No newline at end of file
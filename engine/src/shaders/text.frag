@@ -2,14 +2,16 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(set = 1, binding = 0) uniform sampler samp;
-layout(set = 2, binding = 0) uniform texture2D atlases[10];
+layout(constant_id = 0) const uint ATLAS_COUNT = 10;
+layout(set = 2, binding = 0) uniform texture2D atlases[ATLAS_COUNT];
 
 layout(location = 0) in vec2 fragTexPosition;
 layout(location = 1) in flat uint atlasIndex;
+layout(location = 2) in float fragAlpha;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
 	float alpha = texture(sampler2D(atlases[atlasIndex], samp), fragTexPosition).r;
-	outColor = vec4(1, 1, 1, alpha);
+	outColor = vec4(1, 1, 1, alpha * fragAlpha);
 }
\ No newline at end of file
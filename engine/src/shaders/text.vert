@@ -12,9 +12,11 @@ layout(set = 0, binding = 0, std140, row_major) buffer InstanceDataBlock {
 
 layout(location = 0) in vec2 inPosition;
 layout(location = 1) in vec2 inTexPosition;
+layout(location = 2) in float inAlpha;
 
 layout(location = 0) out vec2 fragTexPosition;
 layout(location = 1) out flat uint outAtlasIndex;
+layout(location = 2) out float fragAlpha;
 
 void main() {
 	InstanceData currentInstanceData = instanceData[gl_InstanceIndex];
@@ -24,4 +26,5 @@ void main() {
 
 	outAtlasIndex = currentInstanceData.atlasIndex;
 	fragTexPosition = inTexPosition;
+	fragAlpha = inAlpha;
 }
\ No newline at end of file
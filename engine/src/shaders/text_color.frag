@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 1, binding = 0) uniform sampler samp;
+layout(constant_id = 0) const uint ATLAS_COUNT = 10;
+layout(set = 2, binding = 0) uniform texture2D atlases[ATLAS_COUNT];
+
+layout(location = 0) in vec2 fragTexPosition;
+layout(location = 1) in flat uint atlasIndex;
+layout(location = 2) in float fragAlpha;
+
+layout(location = 0) out vec4 outColor;
+
+// Color atlases hold the glyphs' own colors so they're drawn as they are instead of tinted
+void main() {
+	vec4 color = texture(sampler2D(atlases[atlasIndex], samp), fragTexPosition);
+	outColor = vec4(color.rgb, color.a * fragAlpha);
+}
\ No newline at end of file
@@ -0,0 +1,56 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 1, binding = 0) uniform sampler samp;
+layout(constant_id = 0) const uint ATLAS_COUNT = 10;
+layout(set = 2, binding = 0) uniform texture2D atlases[ATLAS_COUNT];
+
+layout(push_constant) uniform Glow {
+	vec4 color;
+	float radius;
+	uint colorAtlas;
+} glow;
+
+layout(location = 0) in vec2 fragTexPosition;
+layout(location = 1) in flat uint atlasIndex;
+layout(location = 2) in float fragAlpha;
+layout(location = 3) in vec2 fragQuadPosition;
+
+layout(location = 0) out vec4 outColor;
+
+const int TAP_RADIUS = 3;
+
+// Blurs the glyph's coverage over the radius and adds the glow color by it
+void main() {
+	// The size of the grown quad in texels from how fast the texture position changes compared to the position across the
+	// quad, which gives the glyph's rect in the atlas. Taps outside of it would pick up the neighboring glyphs.
+	vec2 texGradientLengths = vec2(length(vec2(dFdx(fragTexPosition.x), dFdy(fragTexPosition.x))), length(vec2(dFdx(fragTexPosition.y), dFdy(fragTexPosition.y))));
+	vec2 quadGradientLengths = vec2(length(vec2(dFdx(fragQuadPosition.x), dFdy(fragQuadPosition.x))), length(vec2(dFdx(fragQuadPosition.y), dFdy(fragQuadPosition.y))));
+	vec2 quadSize = texGradientLengths / max(quadGradientLengths, vec2(1e-6));
+	vec2 glyphMin = fragTexPosition - fragQuadPosition * quadSize + glow.radius;
+	vec2 glyphMax = glyphMin + quadSize - 2.0 * glow.radius;
+
+	float coverage = 0.0;
+	float totalWeight = 0.0;
+
+	for (int y = -TAP_RADIUS; y <= TAP_RADIUS; y++) {
+		for (int x = -TAP_RADIUS; x <= TAP_RADIUS; x++) {
+			// A gaussian whose standard deviation is half the radius
+			vec2 offset = vec2(x, y) / float(TAP_RADIUS) * glow.radius;
+			float weight = exp(-2.0 * dot(offset, offset) / (glow.radius * glow.radius));
+			vec2 tapPosition = fragTexPosition + offset;
+			totalWeight += weight;
+
+			if (any(lessThan(tapPosition, glyphMin)) || any(greaterThan(tapPosition, glyphMax))) {
+				continue;
+			}
+
+			vec4 texel = textureLod(sampler2D(atlases[atlasIndex], samp), tapPosition, 0.0);
+			coverage += weight * (glow.colorAtlas != 0 ? texel.a : texel.r);
+		}
+	}
+
+	// Blended additively so the alpha is left alone
+	float alpha = coverage / totalWeight * glow.color.a * fragAlpha;
+	outColor = vec4(glow.color.rgb * alpha, 0.0);
+}
\ No newline at end of file
@@ -0,0 +1,46 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+struct InstanceData {
+	mat3 matrix;
+	uint atlasIndex;
+};
+
+layout(set = 0, binding = 0, std140, row_major) buffer InstanceDataBlock {
+	InstanceData instanceData[];
+};
+
+layout(push_constant) uniform Glow {
+	vec4 color;
+	float radius;
+	uint colorAtlas;
+} glow;
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inTexPosition;
+layout(location = 2) in float inAlpha;
+
+layout(location = 0) out vec2 fragTexPosition;
+layout(location = 1) out flat uint outAtlasIndex;
+layout(location = 2) out float fragAlpha;
+layout(location = 3) out vec2 fragQuadPosition;
+
+// The vertices of each glyph's quad are its top left, top right, bottom right and bottom left corners
+const vec2 CORNERS[4] = vec2[](vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0));
+
+void main() {
+	InstanceData currentInstanceData = instanceData[gl_InstanceIndex];
+
+	// The quad grows by the radius so the glow spreads past the glyph, glyphs are a texel per unit so the texture position
+	// grows the same amount
+	vec2 corner = CORNERS[gl_VertexIndex % 4];
+	vec2 growth = (corner * 2.0 - 1.0) * glow.radius;
+
+	vec3 normalized_position = currentInstanceData.matrix * vec3(inPosition + growth, 1.0);
+	gl_Position = vec4(normalized_position.xy, 0.0, 1.0);
+
+	outAtlasIndex = currentInstanceData.atlasIndex;
+	fragTexPosition = inTexPosition + growth;
+	fragAlpha = inAlpha;
+	fragQuadPosition = corner;
+}
\ No newline at end of file
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0, std140, row_major) uniform FrameData {
+	mat4 projectionMatrix;
+	mat4 viewMatrix;
+};
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec4 inColor;
+
+layout(location = 0) out vec4 fragColor;
+
+// Trail ribbons are built in world space on the CPU each frame
+void main() {
+	gl_Position = projectionMatrix * viewMatrix * vec4(inPosition, 1.0);
+	fragColor = inColor;
+}
\ No newline at end of file
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use crate::{Entity, component::Transform3DComponentList, math::{Box3, Frustum, Matrix4, Sphere, Vector3}};
+
+struct Item {
+	entity: Entity,
+	local_bounds: Sphere,
+	bounds: Sphere,
+	level: usize,
+	cell: [i32; 3]
+}
+
+// A loose grid hierarchy over entities' bounding spheres, like a loose octree without a fixed root so the world can be any
+// size. Each level's cells are half the size of the previous level's. An entity goes in the smallest cells at least as big
+// as its sphere, in the cell its center is in, so it can poke out of the cell by up to half a cell on every side.
+// Queries only visit the cells which could hold something touching the query region.
+pub struct SpatialIndex {
+	cell_size: f32,
+	// The occupied cells of each level
+	levels: Vec<HashMap<[i32; 3], Vec<Entity>>>,
+	// The largest radius ever stored on each level, which is how far things can poke out of their cells. Only the top level
+	// can hold spheres bigger than half a cell.
+	max_radii: Vec<f32>,
+	items: Vec<Option<Item>>
+}
+
+impl SpatialIndex {
+	// The cells of the top level are the cell size across, the rest halve in size for every level down
+	pub fn new(cell_size: f32, level_count: usize) -> Self {
+		assert!(level_count > 0, "A spatial index needs at least 1 level");
+
+		Self {
+			cell_size,
+			levels: (0..level_count).map(|_| HashMap::new()).collect(),
+			max_radii: vec![0.0; level_count],
+			items: Vec::new()
+		}
+	}
+
+	// The bounds are in the entity's local space such as the sphere around its geometry's bounding box
+	pub fn insert(&mut self, entity: Entity, local_bounds: Sphere, global_matrix: &Matrix4) {
+		self.remove(&entity);
+
+		let mut bounds = local_bounds;
+		bounds.apply_matrix4(global_matrix);
+		let (level, cell) = self.locate(&bounds);
+		self.add_to_cell(entity, level, cell, bounds.radius);
+
+		if self.items.len() <= entity.index {
+			self.items.resize_with(entity.index + 1, || None);
+		}
+
+		self.items[entity.index] = Some(Item { entity, local_bounds, bounds, level, cell });
+	}
+
+	pub fn remove(&mut self, entity: &Entity) {
+		let (level, cell) = match self.items.get(entity.index) {
+			Some(Some(item)) if item.entity == *entity => (item.level, item.cell),
+			_ => return
+		};
+
+		self.remove_from_cell(*entity, level, cell);
+		self.items[entity.index] = None;
+	}
+
+	pub fn contains(&self, entity: &Entity) -> bool {
+		self.bounds(entity).is_some()
+	}
+
+	// The world space bounds as of the last update
+	pub fn bounds(&self, entity: &Entity) -> Option<&Sphere> {
+		match self.items.get(entity.index) {
+			Some(Some(item)) if item.entity == *entity => Some(&item.bounds),
+			_ => None
+		}
+	}
+
+	// Moves every entity to where its transform is now, call it after the transforms have been updated. Entities whose
+	// transform has been removed are dropped.
+	pub fn update(&mut self, transform_components: &Transform3DComponentList) {
+		for index in 0..self.items.len() {
+			let (entity, local_bounds, old_level, old_cell) = match &self.items[index] {
+				Some(item) => (item.entity, item.local_bounds, item.level, item.cell),
+				None => continue
+			};
+
+			let transform = match transform_components.try_borrow(&entity) {
+				Some(transform) => transform,
+				None => {
+					self.remove(&entity);
+					continue;
+				}
+			};
+
+			let mut bounds = local_bounds;
+			bounds.apply_matrix4(transform.global_matrix());
+			let (level, cell) = self.locate(&bounds);
+
+			if level != old_level || cell != old_cell {
+				self.remove_from_cell(entity, old_level, old_cell);
+				self.add_to_cell(entity, level, cell, bounds.radius);
+			}
+			else {
+				self.max_radii[level] = self.max_radii[level].max(bounds.radius);
+			}
+
+			let item = self.items[index].as_mut().unwrap();
+			item.bounds = bounds;
+			item.level = level;
+			item.cell = cell;
+		}
+	}
+
+	pub fn entities_within(&self, radius: f32, point: &Vector3) -> Vec<Entity> {
+		let query = Sphere::new(*point, radius);
+		self.query(&query.bounding_box(), |bounds| bounds.intersects_sphere(&query))
+	}
+
+	pub fn entities_in_box(&self, box3: &Box3) -> Vec<Entity> {
+		self.query(box3, |bounds| bounds.intersects_box(box3))
+	}
+
+	// Every cell of every level is tested against the frustum, so prefer the box query when the frustum's bounds are known
+	pub fn entities_in_frustum(&self, frustum: &Frustum) -> Vec<Entity> {
+		let mut entities = Vec::new();
+
+		for (level, cells) in self.levels.iter().enumerate() {
+			let size = self.level_cell_size(level);
+			let padding = self.max_radii[level];
+
+			for (cell, cell_entities) in cells {
+				let min = Vector3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32) * size;
+				let loose_bounds = Box3::new(min - padding, min + size + padding);
+
+				if frustum.intersects_box(&loose_bounds) {
+					entities.extend(cell_entities.iter().filter(|entity| frustum.intersects_sphere(&self.items[entity.index].as_ref().unwrap().bounds)));
+				}
+			}
+		}
+
+		entities
+	}
+
+	// Every pair of entities whose spheres overlap, for a collision broad phase
+	pub fn overlapping_pairs(&self) -> Vec<(Entity, Entity)> {
+		let mut pairs = Vec::new();
+
+		for item in self.items.iter().flatten() {
+			let others = self.query(&item.bounds.bounding_box(), |bounds| bounds.intersects_sphere(&item.bounds));
+
+			for other in others {
+				// Each pair is found from both sides, keep one
+				if other.index > item.entity.index {
+					pairs.push((item.entity, other));
+				}
+			}
+		}
+
+		pairs
+	}
+
+	fn query(&self, region: &Box3, test: impl Fn(&Sphere) -> bool) -> Vec<Entity> {
+		let mut entities = Vec::new();
+
+		for (level, cells) in self.levels.iter().enumerate() {
+			if cells.is_empty() {
+				continue;
+			}
+
+			let size = self.level_cell_size(level);
+			let padding = self.max_radii[level];
+			let min = self.cell_of(&(region.min - padding), size);
+			let max = self.cell_of(&(region.max + padding), size);
+			let mut visit = |cell_entities: &Vec<Entity>| {
+				entities.extend(cell_entities.iter().filter(|entity| test(&self.items[entity.index].as_ref().unwrap().bounds)));
+			};
+
+			// A big region on a level with small cells covers more cells than are occupied
+			let range_count = (0..3).map(|axis| (max[axis] - min[axis] + 1) as u64).product::<u64>();
+
+			if range_count > cells.len() as u64 {
+				for (cell, cell_entities) in cells {
+					if (0..3).all(|axis| cell[axis] >= min[axis] && cell[axis] <= max[axis]) {
+						visit(cell_entities);
+					}
+				}
+			}
+			else {
+				for x in min[0]..=max[0] {
+					for y in min[1]..=max[1] {
+						for z in min[2]..=max[2] {
+							if let Some(cell_entities) = cells.get(&[x, y, z]) {
+								visit(cell_entities);
+							}
+						}
+					}
+				}
+			}
+		}
+
+		entities
+	}
+
+	fn level_cell_size(&self, level: usize) -> f32 {
+		self.cell_size / (1 << level) as f32
+	}
+
+	fn cell_of(&self, point: &Vector3, size: f32) -> [i32; 3] {
+		[(point.x / size).floor() as i32, (point.y / size).floor() as i32, (point.z / size).floor() as i32]
+	}
+
+	// The deepest level whose cells are at least the sphere's diameter
+	fn locate(&self, bounds: &Sphere) -> (usize, [i32; 3]) {
+		let mut level = 0;
+
+		while level + 1 < self.levels.len() && self.level_cell_size(level + 1) >= bounds.radius * 2.0 {
+			level += 1;
+		}
+
+		(level, self.cell_of(&bounds.center, self.level_cell_size(level)))
+	}
+
+	fn add_to_cell(&mut self, entity: Entity, level: usize, cell: [i32; 3], radius: f32) {
+		self.levels[level].entry(cell).or_default().push(entity);
+		self.max_radii[level] = self.max_radii[level].max(radius);
+	}
+
+	fn remove_from_cell(&mut self, entity: Entity, level: usize, cell: [i32; 3]) {
+		let cells = &mut self.levels[level];
+		let cell_entities = cells.get_mut(&cell).unwrap();
+		let position = cell_entities.iter().position(|cell_entity| *cell_entity == entity).unwrap();
+		cell_entities.swap_remove(position);
+
+		if cell_entities.is_empty() {
+			cells.remove(&cell);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform3D, math::{matrix4, quaternion}};
+
+	fn sorted(mut entities: Vec<Entity>) -> Vec<usize> {
+		let mut indices: Vec<usize> = entities.drain(..).map(|entity| entity.index).collect();
+		indices.sort_unstable();
+		indices
+	}
+
+	#[test]
+	fn queries() {
+		let mut entity_manager = EntityManager::new();
+		let mut index = SpatialIndex::new(64.0, 6);
+		let mut matrix = matrix4::IDENTITY;
+
+		// A row of small spheres and one huge one
+		let small: Vec<Entity> = (0..20).map(|x| {
+			let entity = entity_manager.create();
+			matrix.compose(&Vector3::new(x as f32 * 3.0, 0.0, 0.0), &quaternion::ZERO, &Vector3::from_scalar(1.0));
+			index.insert(entity, Sphere::new(Vector3::default(), 0.5), &matrix);
+			entity
+		}).collect();
+
+		let huge = entity_manager.create();
+		index.insert(huge, Sphere::new(Vector3::new(0.0, 200.0, 0.0), 150.0), &matrix4::IDENTITY);
+
+		assert_eq!(sorted(index.entities_within(2.0, &Vector3::new(9.0, 1.0, 0.0))), vec![small[3].index]);
+		assert_eq!(sorted(index.entities_within(3.0, &Vector3::new(7.5, 0.0, 0.0))), vec![small[2].index, small[3].index]);
+		assert_eq!(sorted(index.entities_within(1.0, &Vector3::new(0.0, 60.0, 0.0))), vec![huge.index]);
+		assert_eq!(index.entities_in_box(&Box3::new(Vector3::new(-10.0, -1.0, -1.0), Vector3::new(100.0, 1.0, 1.0))).len(), 20);
+
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_orthographic(4.0, 2.0, -10.0, 10.0);
+		assert_eq!(sorted(index.entities_in_frustum(&Frustum::from_matrix(&projection_matrix))), vec![small[0].index]);
+
+		index.remove(&small[3]);
+		assert!(!index.contains(&small[3]));
+		assert!(index.entities_within(2.0, &Vector3::new(9.0, 1.0, 0.0)).is_empty());
+	}
+
+	#[test]
+	fn follows_transforms() {
+		let mut entity_manager = EntityManager::new();
+		let mut transforms = Transform3DComponentList::new();
+		let mut index = SpatialIndex::new(16.0, 4);
+
+		let entities: Vec<Entity> = (0..3).map(|_| {
+			let entity = entity_manager.create();
+			transforms.add(&mut entity_manager, entity, Transform3D::new());
+			index.insert(entity, Sphere::new(Vector3::default(), 1.0), &matrix4::IDENTITY);
+			entity
+		}).collect();
+
+		transforms.borrow_mut(&entities[1]).position.set(100.0, 0.0, 0.0);
+		transforms.update(entities[1]);
+		transforms.borrow_mut(&entities[2]).position.set(1.5, 0.0, 0.0);
+		transforms.borrow_mut(&entities[2]).scale.set_from_scalar(3.0);
+		transforms.update(entities[2]);
+		index.update(&transforms);
+
+		assert_eq!(sorted(index.entities_within(1.0, &Vector3::new(100.0, 1.5, 0.0))), vec![entities[1].index]);
+		assert_eq!(index.bounds(&entities[2]), Some(&Sphere::new(Vector3::new(1.5, 0.0, 0.0), 3.0)));
+
+		let pairs: Vec<(usize, usize)> = index.overlapping_pairs().iter().map(|(a, b)| (a.index, b.index)).collect();
+		assert_eq!(pairs, vec![(entities[0].index, entities[2].index)]);
+
+		transforms.remove(&mut entity_manager, entities[0]);
+		index.update(&transforms);
+		assert!(!index.contains(&entities[0]));
+		assert!(index.overlapping_pairs().is_empty());
+	}
+}
\ No newline at end of file
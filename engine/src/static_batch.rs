@@ -0,0 +1,157 @@
+use crate::{
+	Entity,
+	Geometry3D,
+	component::{Mesh, MultiComponentList, Transform3DComponentList},
+	geometry3d::{Topology, VertexFormat},
+	math::Vector3,
+	pool::Pool
+};
+
+// Merges the geometry of many static meshes sharing a material into one geometry in world space, so a scene full of small
+// props can be drawn by one mesh with one instance at the identity instead of an instance per prop. The extra vertex streams
+// are kept when any of the geometries has them, the geometries without them get 0 UVs, no occlusion and 0 colors. Lightmap
+// UVs of separate geometries overlap so bake the lightmap after batching. The merged geometry is quantized when every
+// geometry was.
+pub fn batch_static_meshes(
+	entities: &[Entity],
+	mesh_components: &MultiComponentList<Mesh>,
+	transform3d_components: &Transform3DComponentList,
+	geometries: &Pool<Geometry3D>)
+	-> Geometry3D
+{
+	let mut indices = vec![];
+	let mut attributes = vec![];
+	let mut uvs = vec![];
+	let mut uvs2 = vec![];
+	let mut occlusion = vec![];
+	let mut colors = vec![];
+	let mut quantized = true;
+
+	let material = entities.first().map(|entity| mesh_components.borrow(entity).material);
+	let batched_geometries: Vec<&Geometry3D> = entities.iter().map(|entity| geometries.borrow(mesh_components.borrow(entity).geometry_handle)).collect();
+	let has_uvs = batched_geometries.iter().any(|geometry| !geometry.uvs().is_empty());
+	let has_uvs2 = batched_geometries.iter().any(|geometry| !geometry.uvs2().is_empty());
+	let has_occlusion = batched_geometries.iter().any(|geometry| !geometry.occlusion().is_empty());
+	let has_colors = batched_geometries.iter().any(|geometry| !geometry.colors().is_empty());
+
+	for (entity, geometry) in entities.iter().zip(batched_geometries) {
+		assert!(Some(mesh_components.borrow(entity).material) == material, "Only meshes sharing a material can be batched");
+		assert!(matches!(geometry.topology(), Topology::Triangle), "Only triangle geometry can be batched");
+
+		let first_vertex = attributes.len() / 6;
+		let vertex_count = geometry.attributes().len() / 6;
+		assert!(first_vertex + vertex_count <= u16::MAX as usize + 1, "Batched geometry can have at most {} vertices, split the meshes into more batches", u16::MAX as usize + 1);
+
+		let global_matrix = &transform3d_components.borrow(entity).global_matrix;
+		let mut normal_matrix = *global_matrix;
+		normal_matrix.invert();
+		normal_matrix.transpose();
+
+		for vertex in geometry.attributes().chunks_exact(6) {
+			let mut position = Vector3::new(vertex[0], vertex[1], vertex[2]);
+			position.apply_matrix4(global_matrix);
+
+			let mut normal = Vector3::new(vertex[3], vertex[4], vertex[5]);
+			normal.transform_direction(&normal_matrix);
+			normal.normalize();
+
+			attributes.extend_from_slice(&[position.x, position.y, position.z, normal.x, normal.y, normal.z]);
+		}
+
+		// A mirroring transform turns the triangles inside out, swap two corners to keep them facing the same way
+		let elements = &global_matrix.elements;
+		let mut x_axis = Vector3::new(elements[0][0], elements[1][0], elements[2][0]);
+		x_axis.cross(&Vector3::new(elements[0][1], elements[1][1], elements[2][1]));
+		let mirrored = x_axis.dot(&Vector3::new(elements[0][2], elements[1][2], elements[2][2])) < 0.0;
+
+		for triangle in geometry.indices().chunks_exact(3) {
+			let corners = if mirrored { [triangle[0], triangle[2], triangle[1]] } else { [triangle[0], triangle[1], triangle[2]] };
+			indices.extend(corners.map(|index| (first_vertex + index as usize) as u16));
+		}
+
+		let extend = |stream: &mut Vec<f32>, has_stream: bool, values: &[f32], per_vertex: usize, default: f32| {
+			if !has_stream {
+				return;
+			}
+
+			if values.is_empty() {
+				stream.resize(stream.len() + vertex_count * per_vertex, default);
+			}
+			else {
+				stream.extend_from_slice(values);
+			}
+		};
+
+		extend(&mut uvs, has_uvs, geometry.uvs(), 2, 0.0);
+		extend(&mut uvs2, has_uvs2, geometry.uvs2(), 2, 0.0);
+		extend(&mut occlusion, has_occlusion, geometry.occlusion(), 1, 1.0);
+		extend(&mut colors, has_colors, geometry.colors(), 4, 0.0);
+		quantized &= geometry.vertex_format() == VertexFormat::Quantized;
+	}
+
+	let mut batched_geometry = Geometry3D::new(indices, attributes, Topology::Triangle);
+	batched_geometry.set_uvs(uvs);
+	batched_geometry.set_uvs2(uvs2);
+	batched_geometry.set_occlusion(occlusion);
+	batched_geometry.set_colors(colors);
+
+	if quantized && !entities.is_empty() {
+		batched_geometry.set_vertex_format(VertexFormat::Quantized);
+	}
+
+	batched_geometry
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::{Transform3D, mesh::Material}};
+
+	#[test]
+	fn merges_meshes_in_world_space() {
+		let mut entity_manager = EntityManager::new();
+		let mut mesh_components = MultiComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut geometries = Pool::new();
+
+		let mut plane = Geometry3D::create_plane();
+		plane.set_occlusion(vec![0.5; 4]);
+		let plane_handle = geometries.add(plane);
+		let box_handle = geometries.add(Geometry3D::create_box());
+
+		let mut add = |geometry_handle, position: Vector3, scale: Vector3| {
+			let entity = entity_manager.create();
+			let mut transform = Transform3D::new();
+			transform.position = position;
+			transform.scale = scale;
+			transform3d_components.add(&mut entity_manager, entity, transform);
+			let index = mesh_components.add(Mesh::new(geometry_handle, Material::Lambert));
+			mesh_components.assign(&mut entity_manager, entity, index);
+			entity
+		};
+
+		let plane_entity = add(plane_handle, Vector3::new(10.0, 0.0, 0.0), Vector3::from_scalar(2.0));
+		let mirrored_entity = add(plane_handle, Vector3::new(-10.0, 0.0, 0.0), Vector3::new(-1.0, 1.0, 1.0));
+		let box_entity = add(box_handle, Vector3::new(0.0, 5.0, 0.0), Vector3::from_scalar(1.0));
+
+		let batched = batch_static_meshes(&[plane_entity, mirrored_entity, box_entity], &mesh_components, &transform3d_components, &geometries);
+		let plane = geometries.borrow(plane_handle);
+		let box_geometry = geometries.borrow(box_handle);
+		assert_eq!(batched.attributes().len(), plane.attributes().len() * 2 + box_geometry.attributes().len());
+		assert_eq!(batched.indices().len(), plane.indices().len() * 2 + box_geometry.indices().len());
+		assert_eq!(batched.bounding_box().min.x, -11.0);
+		assert_eq!(batched.bounding_box().max.x, 12.0);
+		assert_eq!(batched.bounding_box().max.y, 6.0);
+
+		// The second plane's indices follow the first's vertices and are wound the other way
+		let plane_vertex_count = plane.attributes().len() / 6;
+		assert_eq!(batched.indices()[plane.indices().len()], plane.indices()[0] + plane_vertex_count as u16);
+		assert_eq!(batched.indices()[plane.indices().len() + 1], plane.indices()[2] + plane_vertex_count as u16);
+
+		// The box has no occlusion so it's unoccluded
+		assert_eq!(&batched.occlusion()[..plane_vertex_count * 2], &vec![0.5; plane_vertex_count * 2][..]);
+		assert!(batched.occlusion()[plane_vertex_count * 2..].iter().all(|value| *value == 1.0));
+		assert_eq!(batched.uvs().len(), batched.attributes().len() / 3);
+		assert!(batched.colors().is_empty());
+	}
+}
\ No newline at end of file
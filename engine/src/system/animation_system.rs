@@ -0,0 +1,204 @@
+use std::time::Duration;
+use crate::{
+	AnimationClip,
+	component::{AnimationController, ComponentList, Transform3DComponentList},
+	math::{Quaternion, Vector3, quaternion, vector3},
+	pool::Pool
+};
+
+// A weighted sum of positions and orientations
+struct Blend {
+	position: Vector3,
+	orientation: Quaternion,
+	weight: f32
+}
+
+impl Blend {
+	fn new() -> Self {
+		Self {
+			position: vector3::ZERO,
+			orientation: Quaternion::new(0.0, 0.0, 0.0, 0.0),
+			weight: 0.0
+		}
+	}
+
+	fn add(&mut self, position: &Vector3, orientation: &Quaternion, weight: f32) {
+		let mut orientation = *orientation;
+
+		// The same orientation can be either sign, keep them on the same side so they don't cancel out
+		if self.weight > 0.0 && self.orientation.dot(&orientation) < 0.0 {
+			orientation.set(-orientation.x, -orientation.y, -orientation.z, -orientation.w);
+		}
+
+		self.position += position * weight;
+		self.orientation.x += orientation.x * weight;
+		self.orientation.y += orientation.y * weight;
+		self.orientation.z += orientation.z * weight;
+		self.orientation.w += orientation.w * weight;
+		self.weight += weight;
+	}
+
+	fn result(&self) -> (Vector3, Quaternion) {
+		let mut orientation = self.orientation;
+		orientation.normalize();
+		(self.position / self.weight, orientation)
+	}
+}
+
+pub struct AnimationSystem;
+
+impl AnimationSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	// Poses the joints of each controller, run it before anything which depends on where the joints are such as constraints
+	// and IK. Joints none of the playing clips animate are left as they are.
+	pub fn update(
+		&self,
+		delta_time: &Duration,
+		animation_controller_components: &mut ComponentList<AnimationController>,
+		clips: &Pool<AnimationClip>,
+		transform3d_components: &mut Transform3DComponentList)
+	{
+		for (entity, animation_controller) in animation_controller_components.iter_mut() {
+			animation_controller.advance(delta_time.as_secs_f32(), clips);
+
+			let root_motion = animation_controller.root_motion;
+			let mut joint_blends: Vec<Option<Blend>> = animation_controller.joints.iter().map(|_| None).collect();
+			let mut root_motion_blend = Blend::new();
+
+			for sample in animation_controller.clip_samples(clips) {
+				let clip = clips.borrow(sample.handle);
+
+				for track in &clip.tracks {
+					let Some(joint_blend) = joint_blends.get_mut(track.joint) else {
+						continue;
+					};
+
+					let (position, orientation) = match root_motion {
+						Some(root_motion) if root_motion.joint == track.joint => {
+							let (moved, turn) = track.root_motion(sample.previous_time, sample.time, clip.duration, root_motion.flatten);
+							root_motion_blend.add(&moved, &turn, sample.weight);
+							track.sample_without_root_motion(sample.time, root_motion.flatten)
+						},
+						_ => track.sample(sample.time)
+					};
+
+					joint_blend.get_or_insert_with(Blend::new).add(&position, &orientation, sample.weight);
+				}
+			}
+
+			for (joint_entity, joint_blend) in animation_controller.joints.iter().zip(joint_blends) {
+				let Some(joint_blend) = joint_blend else {
+					continue;
+				};
+
+				let (position, orientation) = joint_blend.result();
+				let transform = transform3d_components.borrow_mut(joint_entity);
+				transform.position = position;
+				transform.orientation = orientation;
+				transform3d_components.update(*joint_entity);
+			}
+
+			if let Some(root_motion) = root_motion {
+				assert!(animation_controller.joints[root_motion.joint] != *entity, "Root motion moves the animation controller's entity so the root joint has to be under it");
+
+				animation_controller.root_motion_delta = if root_motion_blend.weight > 0.0 { root_motion_blend.result() } else { (vector3::ZERO, quaternion::ZERO) };
+				let (mut moved, turn) = animation_controller.root_motion_delta;
+
+				let transform = transform3d_components.borrow_mut(entity);
+				moved *= transform.scale;
+				moved.apply_quaternion(&transform.orientation);
+				transform.position += moved;
+				transform.orientation *= turn;
+				transform.orientation.normalize();
+				transform3d_components.update(*entity);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::FRAC_PI_2;
+	use crate::{
+		EntityManager,
+		animation_clip::{AnimationTrack, Keyframe},
+		component::{AnimationCondition, AnimationState, AnimationTransition, Motion, RootMotion, Transform3D},
+		math::{assert_approx_eq, quaternion}
+	};
+
+	#[test]
+	fn cross_fades_between_clips() {
+		let mut entity_manager = EntityManager::new();
+		let mut animation_controller_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let system = AnimationSystem::new();
+
+		let entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, entity, Transform3D::new());
+
+		let pose = |x: f32| AnimationClip::new(1.0, vec![AnimationTrack::new(0, vec![Keyframe { time: 0.0, position: Vector3::new(x, 0.0, 0.0), orientation: quaternion::ZERO }])]);
+		let mut clips = Pool::new();
+		let left = clips.add(pose(-1.0));
+		let right = clips.add(pose(1.0));
+
+		let mut animation_controller = AnimationController::new(vec![entity]);
+		animation_controller.add_state(AnimationState::new("left", Motion::Clip(left)));
+		let right_state = animation_controller.add_state(AnimationState::new("right", Motion::Clip(right)));
+		animation_controller.add_transition(AnimationTransition::new(None, right_state, 1.0, vec![AnimationCondition::Bool(String::from("right"), true)]));
+		animation_controller_components.add(&mut entity_manager, entity, animation_controller);
+
+		system.update(&Duration::from_millis(100), &mut animation_controller_components, &clips, &mut transform3d_components);
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(-1.0, 0.0, 0.0), 1e-6);
+
+		animation_controller_components.borrow_mut(&entity).set_bool("right", true);
+		system.update(&Duration::from_millis(500), &mut animation_controller_components, &clips, &mut transform3d_components);
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(0.0, 0.0, 0.0), 1e-5);
+
+		system.update(&Duration::from_millis(500), &mut animation_controller_components, &clips, &mut transform3d_components);
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(1.0, 0.0, 0.0), 1e-6);
+		transform3d_components.check_for_dirties();
+	}
+
+	#[test]
+	fn moves_the_entity_by_root_motion() {
+		let mut entity_manager = EntityManager::new();
+		let mut animation_controller_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let system = AnimationSystem::new();
+
+		// A character turned to face +x with a hips joint which walks 2 units forward a second
+		let entity = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.rotate_y(FRAC_PI_2);
+		transform3d_components.add(&mut entity_manager, entity, transform);
+
+		let hips_entity = entity_manager.create();
+		transform3d_components.add_child(&mut entity_manager, entity, hips_entity, Transform3D::new());
+
+		let walk = AnimationClip::new(1.0, vec![AnimationTrack::new(0, vec![
+			Keyframe { time: 0.0, position: Vector3::new(0.0, 1.0, 0.0), orientation: quaternion::ZERO },
+			Keyframe { time: 1.0, position: Vector3::new(0.0, 1.0, 2.0), orientation: quaternion::ZERO }
+		])]);
+
+		let mut clips = Pool::new();
+		let walk = clips.add(walk);
+
+		let mut animation_controller = AnimationController::new(vec![hips_entity]);
+		animation_controller.add_state(AnimationState::new("walk", Motion::Clip(walk)));
+		animation_controller.root_motion = Some(RootMotion::new(0));
+		animation_controller_components.add(&mut entity_manager, entity, animation_controller);
+
+		for _ in 0..3 {
+			system.update(&Duration::from_millis(500), &mut animation_controller_components, &clips, &mut transform3d_components);
+		}
+
+		// Looped one and a half times
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(3.0, 0.0, 0.0), 1e-4);
+		assert_approx_eq(&transform3d_components.borrow(&hips_entity).position, &Vector3::new(0.0, 1.0, 0.0), 1e-6);
+		transform3d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::{
+	collections::VecDeque,
+	fmt::{self, Display},
+	path::Path,
+	sync::{Arc, Mutex, mpsc::{self, Receiver, Sender}},
+	thread::{self, JoinHandle},
+	time::Duration
+};
+use cpal::{FromSample, SizedSample, traits::{DeviceTrait, HostTrait, StreamTrait}};
+use crate::audio::{Mixer, LoopRegion, MusicStream, MusicStreamError, CHANNEL_COUNT};
+
+// How many frames the audio thread mixes at a time
+const CHUNK_FRAME_COUNT: usize = 1024;
+
+// The audio thread keeps this much mixed ahead of the output so decoding a packet doesn't starve it
+const BUFFERED_DURATION: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum AudioError {
+	NoOutputDevice,
+	UnsupportedSampleFormat(cpal::SampleFormat),
+	Config(cpal::DefaultStreamConfigError),
+	BuildStream(cpal::BuildStreamError),
+	PlayStream(cpal::PlayStreamError)
+}
+
+impl Display for AudioError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::NoOutputDevice => write!(f, "There is no audio output device"),
+			Self::UnsupportedSampleFormat(sample_format) => write!(f, "The output's {} sample format is not supported", sample_format),
+			Self::Config(error) => write!(f, "Failed to get the output's config: {}", error),
+			Self::BuildStream(error) => write!(f, "Failed to create the output stream: {}", error),
+			Self::PlayStream(error) => write!(f, "Failed to start the output stream: {}", error)
+		}
+	}
+}
+
+impl From<cpal::DefaultStreamConfigError> for AudioError {
+	fn from(error: cpal::DefaultStreamConfigError) -> Self {
+		Self::Config(error)
+	}
+}
+
+impl From<cpal::BuildStreamError> for AudioError {
+	fn from(error: cpal::BuildStreamError) -> Self {
+		Self::BuildStream(error)
+	}
+}
+
+impl From<cpal::PlayStreamError> for AudioError {
+	fn from(error: cpal::PlayStreamError) -> Self {
+		Self::PlayStream(error)
+	}
+}
+
+enum Command {
+	Play {
+		stream: MusicStream,
+		loop_region: Option<LoopRegion>,
+		crossfade: Duration
+	},
+	Stop(Duration),
+	SetVolume(f32),
+	Quit
+}
+
+// Plays music on the default output device. The audio thread streams the tracks from disk and mixes them a chunk at a time
+// into a queue which the output drains.
+pub struct AudioSystem {
+	sample_rate: u32,
+	commands: Sender<Command>,
+	thread: Option<JoinHandle<()>>,
+	_stream: cpal::Stream
+}
+
+impl AudioSystem {
+	pub fn new() -> Result<Self, AudioError> {
+		let device = cpal::default_host().default_output_device().ok_or(AudioError::NoOutputDevice)?;
+		let supported_config = device.default_output_config()?;
+		let sample_format = supported_config.sample_format();
+		let config: cpal::StreamConfig = supported_config.into();
+		let sample_rate = config.sample_rate.0;
+		let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+		let stream = match sample_format {
+			cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, queue.clone())?,
+			cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, queue.clone())?,
+			cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, queue.clone())?,
+			sample_format => return Err(AudioError::UnsupportedSampleFormat(sample_format))
+		};
+
+		stream.play()?;
+
+		let (commands, receiver) = mpsc::channel();
+		let thread = thread::Builder::new()
+			.name("audio".to_string())
+			.spawn(move || run_audio_thread(Mixer::new(sample_rate), receiver, queue))
+			.unwrap();
+
+		Ok(Self {
+			sample_rate,
+			commands,
+			thread: Some(thread),
+			_stream: stream
+		})
+	}
+
+	// The track is opened here so a missing or unsupported file is reported straight away, it's decoded on the audio thread.
+	// Whatever was playing fades out over the crossfade.
+	pub fn play_music(&self, path: impl AsRef<Path>, loop_region: Option<LoopRegion>, crossfade: Duration) -> Result<(), MusicStreamError> {
+		let stream = MusicStream::open(path, self.sample_rate)?;
+		self.commands.send(Command::Play { stream, loop_region, crossfade }).unwrap();
+		Ok(())
+	}
+
+	pub fn stop_music(&self, fade: Duration) {
+		self.commands.send(Command::Stop(fade)).unwrap();
+	}
+
+	pub fn set_music_volume(&self, volume: f32) {
+		self.commands.send(Command::SetVolume(volume)).unwrap();
+	}
+}
+
+impl Drop for AudioSystem {
+	fn drop(&mut self) {
+		// The thread is already gone if it panicked
+		let _ = self.commands.send(Command::Quit);
+
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+// The output plays silence if the audio thread falls behind. Mono outputs get both sides and channels past the first two are
+// left silent.
+fn build_stream<T: SizedSample + FromSample<f32>>(device: &cpal::Device, config: &cpal::StreamConfig, queue: Arc<Mutex<VecDeque<f32>>>) -> Result<cpal::Stream, AudioError> {
+	let channel_count = config.channels as usize;
+
+	let stream = device.build_output_stream(
+		config,
+		move |data: &mut [T], _| {
+			let mut queue = queue.lock().unwrap();
+
+			for frame in data.chunks_mut(channel_count) {
+				let left = queue.pop_front().unwrap_or(0.0);
+				let right = queue.pop_front().unwrap_or(0.0);
+
+				if channel_count == 1 {
+					frame[0] = T::from_sample((left + right) * 0.5);
+					continue;
+				}
+
+				for (channel, sample) in frame.iter_mut().enumerate() {
+					*sample = T::from_sample(match channel {
+						0 => left,
+						1 => right,
+						_ => 0.0
+					});
+				}
+			}
+		},
+		|error| println!("Audio output error: {}", error),
+		None)?;
+
+	Ok(stream)
+}
+
+fn run_audio_thread(mut mixer: Mixer, commands: Receiver<Command>, queue: Arc<Mutex<VecDeque<f32>>>) {
+	let buffered_frame_count = (BUFFERED_DURATION.as_secs_f64() * mixer.sample_rate() as f64) as usize;
+	let mut chunk = vec![0.0; CHUNK_FRAME_COUNT * CHANNEL_COUNT];
+
+	loop {
+		for command in commands.try_iter() {
+			match command {
+				Command::Play { stream, loop_region, crossfade } => mixer.play(Box::new(stream), loop_region, crossfade),
+				Command::Stop(fade) => mixer.stop(fade),
+				Command::SetVolume(volume) => mixer.set_volume(volume),
+				Command::Quit => return
+			}
+		}
+
+		let queued_frame_count = queue.lock().unwrap().len() / CHANNEL_COUNT;
+
+		// Wait for the output to drain some of the queue
+		if queued_frame_count >= buffered_frame_count {
+			thread::sleep(Duration::from_millis(5));
+			continue;
+		}
+
+		mixer.mix(&mut chunk);
+		queue.lock().unwrap().extend(&chunk);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use crate::{
+	Bvh,
+	Geometry3D,
+	component::{BlobShadow, ComponentList, Transform3DComponentList, blob_shadow::BlobShadowGround},
+	geometry3d::Topology,
+	math::{Matrix4, Ray, Vector3}
+};
+
+// Places each blob shadow on the ground straight below its entity by casting a ray against the ground geometry added
+pub struct BlobShadowSystem {
+	triangles: Vec<[Vector3; 3]>,
+	bvh: Bvh
+}
+
+impl BlobShadowSystem {
+	pub fn new() -> Self {
+		Self {
+			triangles: Vec::new(),
+			bvh: Bvh::from_triangles(Vec::new())
+		}
+	}
+
+	// The triangles of the geometry catch shadows, usually the static level geometry
+	pub fn add_ground(&mut self, geometry: &Geometry3D, global_matrix: &Matrix4) {
+		if !matches!(geometry.topology(), Topology::Triangle) {
+			return;
+		}
+
+		let attributes = geometry.attributes();
+
+		for triangle in geometry.indices().chunks_exact(3) {
+			self.triangles.push([0, 1, 2].map(|corner| {
+				let offset = triangle[corner] as usize * 6;
+				let mut position = Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2]);
+				position.apply_matrix4(global_matrix);
+				position
+			}));
+		}
+
+		self.bvh = Bvh::from_triangles(self.triangles.clone());
+	}
+
+	pub fn clear_ground(&mut self) {
+		self.triangles.clear();
+		self.bvh = Bvh::from_triangles(Vec::new());
+	}
+
+	pub fn update(&self, blob_shadow_components: &mut ComponentList<BlobShadow>, transform3d_components: &Transform3DComponentList) {
+		for (entity, blob_shadow) in blob_shadow_components.iter_mut() {
+			let origin = transform3d_components.borrow(entity).global_matrix().extract_position();
+			let ray = Ray::new(origin, Vector3::new(0.0, -1.0, 0.0));
+
+			blob_shadow.ground = self.bvh.intersect_ray(&ray)
+				.filter(|hit| hit.distance <= blob_shadow.max_distance)
+				.map(|hit| {
+					let [a, b, c] = self.triangles[hit.triangle];
+					let mut normal = b - a;
+					normal.cross(&(c - a));
+					normal.normalize();
+
+					// Faces up whichever way the triangle is wound
+					if normal.y < 0.0 {
+						normal *= -1.0;
+					}
+
+					BlobShadowGround { position: ray.at(hit.distance), normal, distance: hit.distance }
+				});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform3D, math::{matrix4, quaternion}};
+
+	#[test]
+	fn shadows_land_on_ground() {
+		let mut entity_manager = EntityManager::new();
+		let mut blob_shadow_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut system = BlobShadowSystem::new();
+
+		let mut ground_matrix = matrix4::IDENTITY;
+		ground_matrix.compose(&Vector3::new(0.0, 1.0, 0.0), &quaternion::ZERO, &Vector3::from_scalar(10.0));
+		system.add_ground(&Geometry3D::create_plane(), &ground_matrix);
+
+		let above = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(2.0, 3.0, -1.0);
+		transform3d_components.add(&mut entity_manager, above, transform);
+		blob_shadow_components.add(&mut entity_manager, above, BlobShadow::new(0.5));
+
+		let too_high = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(0.0, 20.0, 0.0);
+		transform3d_components.add(&mut entity_manager, too_high, transform);
+		blob_shadow_components.add(&mut entity_manager, too_high, BlobShadow::new(0.5));
+
+		let off_the_edge = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(20.0, 3.0, 0.0);
+		transform3d_components.add(&mut entity_manager, off_the_edge, transform);
+		blob_shadow_components.add(&mut entity_manager, off_the_edge, BlobShadow::new(0.5));
+
+		system.update(&mut blob_shadow_components, &transform3d_components);
+
+		let ground = blob_shadow_components.borrow(&above).ground().unwrap();
+		assert!((ground.distance - 2.0).abs() < 1e-5);
+		assert!(ground.position.distance(&Vector3::new(2.0, 1.0, -1.0)) < 1e-5);
+		assert_eq!(ground.normal, Vector3::new(0.0, 1.0, 0.0));
+		assert!(blob_shadow_components.borrow(&too_high).ground().is_none());
+		assert!(blob_shadow_components.borrow(&off_the_edge).ground().is_none());
+
+		system.clear_ground();
+		system.update(&mut blob_shadow_components, &transform3d_components);
+		assert!(blob_shadow_components.borrow(&above).ground().is_none());
+	}
+}
\ No newline at end of file
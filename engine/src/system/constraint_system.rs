@@ -0,0 +1,87 @@
+use std::time::Duration;
+use crate::{component::{ComponentList, Constraints, Transform3DComponentList}, math::Matrix4};
+
+// Run it after everything else which moves transforms so the targets are where they'll be drawn. An entity constrained to
+// another constrained entity sees where that one was put if its constraints were added after it.
+pub struct ConstraintSystem;
+
+impl ConstraintSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn update(&self, delta_time: &Duration, constraints_components: &mut ComponentList<Constraints>, transform3d_components: &mut Transform3DComponentList) {
+		for (entity, constraints) in constraints_components.iter_mut() {
+			let target_global_matrices: Vec<Option<Matrix4>> = constraints.constraints.iter()
+				.map(|constraint| transform3d_components.try_borrow(&constraint.target).map(|target_transform| target_transform.global_matrix))
+				.collect();
+
+			let parent_global_matrix = transform3d_components.borrow(entity).parent_entity
+				.map(|parent_entity| transform3d_components.borrow(&parent_entity).global_matrix);
+
+			let transform = transform3d_components.borrow_mut(entity);
+			constraints.apply(transform, parent_global_matrix.as_ref(), &target_global_matrices, delta_time.as_secs_f32());
+			transform3d_components.update(*entity);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::{Constraint, Transform3D}, math::{Vector3, assert_approx_eq, vector3}};
+
+	#[test]
+	fn tracks_targets() {
+		let mut entity_manager = EntityManager::new();
+		let mut constraints_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let system = ConstraintSystem::new();
+
+		let target_entity = entity_manager.create();
+		let mut target_transform = Transform3D::new();
+		target_transform.position = Vector3::new(10.0, 0.0, 0.0);
+		transform3d_components.add(&mut entity_manager, target_entity, target_transform);
+
+		// A turret on a moved parent which looks at the target and a camera which follows behind it
+		let base_entity = entity_manager.create();
+		let mut base_transform = Transform3D::new();
+		base_transform.position = Vector3::new(0.0, 0.0, 5.0);
+		transform3d_components.add(&mut entity_manager, base_entity, base_transform);
+
+		let turret_entity = entity_manager.create();
+		transform3d_components.add_child(&mut entity_manager, base_entity, turret_entity, Transform3D::new());
+		constraints_components.add(&mut entity_manager, turret_entity, Constraints::new(vec![Constraint::look_at(target_entity)]));
+
+		let camera_entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, camera_entity, Transform3D::new());
+		let follow = Constraint::follow(target_entity, Vector3::new(0.0, 2.0, -4.0), 50.0);
+		constraints_components.add(&mut entity_manager, camera_entity, Constraints::new(vec![follow, Constraint::look_at(target_entity)]));
+
+		system.update(&Duration::from_millis(16), &mut constraints_components, &mut transform3d_components);
+
+		let mut forward = vector3::UNIT_Z;
+		forward.transform_direction(transform3d_components.borrow(&turret_entity).global_matrix());
+		let mut expected = Vector3::new(10.0, 0.0, -5.0);
+		expected.normalize();
+		assert_approx_eq(&forward, &expected, 1e-4);
+
+		// The follower moves some of the way at first and settles at the offset
+		let camera_position = transform3d_components.borrow(&camera_entity).global_matrix().extract_position();
+		assert!(camera_position.x > 0.0 && camera_position.x < 10.0);
+
+		for _ in 0..300 {
+			system.update(&Duration::from_millis(16), &mut constraints_components, &mut transform3d_components);
+		}
+
+		let camera_transform = transform3d_components.borrow(&camera_entity);
+		assert_approx_eq(&camera_transform.position, &Vector3::new(10.0, 2.0, -4.0), 1e-3);
+
+		let mut forward = vector3::UNIT_Z;
+		forward.transform_direction(camera_transform.global_matrix());
+		let mut expected = Vector3::new(0.0, -2.0, 4.0);
+		expected.normalize();
+		assert_approx_eq(&forward, &expected, 1e-3);
+		transform3d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
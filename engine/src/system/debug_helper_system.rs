@@ -0,0 +1,98 @@
+use crate::{
+	Camera,
+	Entity,
+	Geometry3D,
+	component::{ComponentList, Light, LightHelper, Mesh, MultiComponentList, Transform3DComponentList, ALL_LAYERS_MASK},
+	pool::Pool
+};
+
+// Moves the line meshes visualizing lights and cameras to match them. Helpers are only rendered while enabled, their mesh's
+// layer mask is overwritten every update so each helper should have a mesh of its own.
+pub struct DebugHelperSystem {
+	pub enabled: bool,
+	pub light_entities: Vec<Entity>,
+	pub grid_half_size: f32,
+	pub grid_spacing: f32,
+	pub grid_major_every: u32,
+	// The major line the grid was last made around, it's made again when the camera gets to another one
+	grid_center: Option<(i64, i64)>
+}
+
+impl DebugHelperSystem {
+	pub fn new() -> Self {
+		Self {
+			enabled: false,
+			light_entities: Vec::new(),
+			grid_half_size: 100.0,
+			grid_spacing: 1.0,
+			grid_major_every: 10,
+			grid_center: None
+		}
+	}
+
+	// Point lights are shown with a sphere helper scaled to their range, lights without a position or range aren't shown
+	pub fn update_lights(
+		&self,
+		transform_components: &mut Transform3DComponentList,
+		light_components: &ComponentList<Light>,
+		mesh_components: &mut MultiComponentList<Mesh>,
+		light_helper_components: &ComponentList<LightHelper>)
+	{
+		for entity in &self.light_entities {
+			let helper_entity = light_helper_components.borrow(entity).helper_entity;
+
+			let range = match light_components.borrow(entity) {
+				Light::PointLight(point_light) if point_light.range.is_finite() => Some(point_light.range),
+				_ => None
+			};
+
+			let visible = self.enabled && range.is_some();
+			mesh_components.borrow_mut(&helper_entity).layer_mask = if visible { ALL_LAYERS_MASK } else { 0 };
+
+			if let Some(range) = range {
+				let position = transform_components.borrow(entity).global_matrix().extract_position();
+				let helper_transform = transform_components.borrow_mut(&helper_entity);
+				helper_transform.position = position;
+				helper_transform.scale.set_from_scalar(range);
+				transform_components.update(helper_entity);
+			}
+		}
+	}
+
+	// The helper's geometry is made with Geometry3D::create_frustum_helper from the camera's projection matrix
+	pub fn update_camera(&self, camera: &Camera, helper_entity: Entity, transform_components: &mut Transform3DComponentList, mesh_components: &mut MultiComponentList<Mesh>) {
+		mesh_components.borrow_mut(&helper_entity).layer_mask = if self.enabled { ALL_LAYERS_MASK } else { 0 };
+
+		let helper_transform = transform_components.borrow_mut(&helper_entity);
+		helper_transform.position = camera.transform.position;
+		helper_transform.orientation = camera.transform.orientation;
+		helper_transform.scale = camera.transform.scale;
+		transform_components.update(helper_entity);
+	}
+
+	// Keeps a grid around the camera so it looks endless. The helper's geometry is made with Geometry3D::create_grid_helper and
+	// its transform is left as the identity since the grid is made in world space.
+	pub fn update_grid(&mut self, camera: &Camera, helper_entity: Entity, mesh_components: &mut MultiComponentList<Mesh>, geometries: &mut Pool<Geometry3D>) {
+		let mesh = mesh_components.borrow_mut(&helper_entity);
+		mesh.layer_mask = if self.enabled { ALL_LAYERS_MASK } else { 0 };
+
+		if !self.enabled {
+			return;
+		}
+
+		let major_spacing = self.grid_spacing * self.grid_major_every as f32;
+		let position = &camera.transform.position;
+		let center = ((position.x / major_spacing).round() as i64, (position.z / major_spacing).round() as i64);
+
+		if self.grid_center != Some(center) {
+			self.grid_center = Some(center);
+
+			geometries.borrow_mut(mesh.geometry_handle).make_grid_helper(
+				center.0 as f32 * major_spacing,
+				center.1 as f32 * major_spacing,
+				self.grid_half_size,
+				self.grid_spacing,
+				self.grid_major_every);
+		}
+	}
+}
\ No newline at end of file
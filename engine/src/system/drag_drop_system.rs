@@ -0,0 +1,168 @@
+use crate::{Entity, component::{ComponentList, Draggable, DropTarget, HitArea2D, TextComponentList, Transform2DComponentList}, math::Vector2};
+use super::HitTestSystem;
+
+pub struct Drop2D {
+	pub entity: Entity,
+	// The drop target it was dropped on if it accepts the draggable's groups
+	pub target: Option<Entity>
+}
+
+struct Drag {
+	entity: Entity,
+	grab_offset: Vector2,
+	start_position: Vector2
+}
+
+// Picks up draggables under the cursor, moves them with it and finds the drop target they're let go over. Positions are in
+// framebuffer pixels.
+pub struct DragDropSystem {
+	drag: Option<Drag>
+}
+
+impl DragDropSystem {
+	pub fn new() -> Self {
+		Self {
+			drag: None
+		}
+	}
+
+	pub fn dragged_entity(&self) -> Option<Entity> {
+		self.drag.as_ref().map(|drag| drag.entity)
+	}
+
+	// Picks up the top most draggable under the cursor, returns whether one was picked up
+	pub fn begin(
+		&mut self,
+		cursor_position: &Vector2,
+		hit_test_system: &HitTestSystem,
+		hit_area_components: &ComponentList<HitArea2D>,
+		draggable_components: &ComponentList<Draggable>,
+		transform2d_components: &Transform2DComponentList) -> bool
+	{
+		let entity = hit_test_system.entities_at(cursor_position, hit_area_components, transform2d_components).into_iter()
+			.find(|entity| draggable_components.try_borrow(entity).is_some());
+
+		self.drag = entity.map(|entity| {
+			let position = transform2d_components.borrow(&entity).position;
+
+			Drag {
+				entity,
+				grab_offset: position - cursor_position,
+				start_position: position
+			}
+		});
+
+		self.drag.is_some()
+	}
+
+	// Moves the dragged entity with the cursor, it's no longer clipped by the scroll view it may have been in so this is
+	// called after the scroll view system
+	pub fn update(
+		&self,
+		cursor_position: &Vector2,
+		transform2d_components: &mut Transform2DComponentList,
+		text_components: &mut TextComponentList,
+		hit_area_components: &mut ComponentList<HitArea2D>)
+	{
+		let drag = match &self.drag {
+			Some(drag) => drag,
+			None => return
+		};
+
+		transform2d_components.borrow_mut(&drag.entity).position = cursor_position + drag.grab_offset;
+		transform2d_components.update(&drag.entity);
+
+		if text_components.try_borrow(&drag.entity).is_some() {
+			text_components.borrow_mut(drag.entity).clip_rect = None;
+		}
+
+		hit_area_components.borrow_mut(&drag.entity).clip_rect = None;
+	}
+
+	// Lets go of the dragged entity, it's returned to where it was picked up if it's not over an accepting drop target and
+	// should return on a miss
+	#[allow(clippy::too_many_arguments)]
+	pub fn end(
+		&mut self,
+		cursor_position: &Vector2,
+		hit_test_system: &HitTestSystem,
+		hit_area_components: &ComponentList<HitArea2D>,
+		draggable_components: &ComponentList<Draggable>,
+		drop_target_components: &ComponentList<DropTarget>,
+		transform2d_components: &mut Transform2DComponentList) -> Option<Drop2D>
+	{
+		let drag = self.drag.take()?;
+		let draggable = draggable_components.borrow(&drag.entity);
+
+		let target = hit_test_system.entities_at(cursor_position, hit_area_components, transform2d_components).into_iter()
+			.filter(|entity| *entity != drag.entity)
+			.find(|entity| match drop_target_components.try_borrow(entity) {
+				Some(drop_target) => drop_target.groups & draggable.groups != 0,
+				None => false
+			});
+
+		if target.is_none() && draggable.return_on_miss {
+			transform2d_components.borrow_mut(&drag.entity).position = drag.start_position;
+			transform2d_components.update(&drag.entity);
+		}
+
+		Some(Drop2D {
+			entity: drag.entity,
+			target
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform2D, math::{assert_approx_eq, vector2}};
+
+	#[test]
+	fn drags_onto_accepting_targets() {
+		let mut entity_manager = EntityManager::new();
+		let mut hit_area_components = ComponentList::new();
+		let mut draggable_components = ComponentList::new();
+		let mut drop_target_components = ComponentList::new();
+		let mut transform2d_components = Transform2DComponentList::new();
+		let mut text_components = TextComponentList::new();
+		let hit_test_system = HitTestSystem::new();
+		let mut system = DragDropSystem::new();
+
+		let mut add = |position: Vector2| {
+			let entity = entity_manager.create();
+			let mut transform = Transform2D::new();
+			transform.position = position;
+			transform2d_components.add(&mut entity_manager, entity, transform);
+			hit_area_components.add(&mut entity_manager, entity, HitArea2D::new(vector2::ZERO, Vector2::new(10.0, 10.0)));
+			entity
+		};
+
+		let item = add(vector2::ZERO);
+		let accepting_target = add(Vector2::new(100.0, 0.0));
+		let other_target = add(Vector2::new(200.0, 0.0));
+		draggable_components.add(&mut entity_manager, item, Draggable::new(0b01));
+		drop_target_components.add(&mut entity_manager, accepting_target, DropTarget::new(0b11));
+		drop_target_components.add(&mut entity_manager, other_target, DropTarget::new(0b10));
+
+		assert!(!system.begin(&Vector2::new(50.0, 5.0), &hit_test_system, &hit_area_components, &draggable_components, &transform2d_components));
+		assert!(system.begin(&Vector2::new(2.0, 5.0), &hit_test_system, &hit_area_components, &draggable_components, &transform2d_components));
+		assert!(system.dragged_entity() == Some(item));
+
+		// The entity keeps where it was grabbed under the cursor
+		system.update(&Vector2::new(103.0, 5.0), &mut transform2d_components, &mut text_components, &mut hit_area_components);
+		assert_approx_eq(&transform2d_components.borrow(&item).position, &Vector2::new(101.0, 0.0), 1e-6);
+
+		let drop = system.end(&Vector2::new(103.0, 5.0), &hit_test_system, &hit_area_components, &draggable_components, &drop_target_components, &mut transform2d_components).unwrap();
+		assert!(drop.entity == item && drop.target == Some(accepting_target));
+		assert!(system.dragged_entity().is_none());
+
+		// Targets which don't accept the groups are misses
+		system.begin(&Vector2::new(103.0, 5.0), &hit_test_system, &hit_area_components, &draggable_components, &transform2d_components);
+		system.update(&Vector2::new(205.0, 5.0), &mut transform2d_components, &mut text_components, &mut hit_area_components);
+		let drop = system.end(&Vector2::new(205.0, 5.0), &hit_test_system, &hit_area_components, &draggable_components, &drop_target_components, &mut transform2d_components).unwrap();
+		assert!(drop.target.is_none());
+		assert_approx_eq(&transform2d_components.borrow(&item).position, &Vector2::new(101.0, 0.0), 1e-6);
+		transform2d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
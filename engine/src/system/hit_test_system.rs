@@ -0,0 +1,122 @@
+use crate::{Entity, component::{ComponentList, HitArea2D, Transform2DComponentList}, math::{Vector2, Vector3}};
+
+// Finds which hit area is under the cursor. The cursor is brought into the space of each entity's Transform2D so rotated and
+// scaled areas are hit where they're drawn. 2D transforms don't have parents so their matrices are already in screen space.
+pub struct HitTestSystem {
+	hovered_entity: Option<Entity>
+}
+
+impl HitTestSystem {
+	pub fn new() -> Self {
+		Self {
+			hovered_entity: None
+		}
+	}
+
+	pub fn hovered_entity(&self) -> Option<Entity> {
+		self.hovered_entity
+	}
+
+	// Updates the hovered entity, the cursor position is in framebuffer pixels
+	pub fn update(&mut self, cursor_position: &Vector2, hit_area_components: &ComponentList<HitArea2D>, transform2d_components: &Transform2DComponentList) {
+		self.hovered_entity = self.entity_at(cursor_position, hit_area_components, transform2d_components);
+	}
+
+	// The top most entity whose hit area contains the point, when the orders are equal the one added last is on top
+	pub fn entity_at(&self, point: &Vector2, hit_area_components: &ComponentList<HitArea2D>, transform2d_components: &Transform2DComponentList) -> Option<Entity> {
+		Self::gather_hits(point, hit_area_components, transform2d_components).into_iter()
+			.max_by_key(|(_, order)| *order)
+			.map(|(entity, _)| entity)
+	}
+
+	// Every entity whose hit area contains the point from the top most to the bottom most
+	pub fn entities_at(&self, point: &Vector2, hit_area_components: &ComponentList<HitArea2D>, transform2d_components: &Transform2DComponentList) -> Vec<Entity> {
+		let mut hits = Self::gather_hits(point, hit_area_components, transform2d_components);
+		hits.reverse();
+		hits.sort_by_key(|(_, order)| -order);
+		hits.into_iter().map(|(entity, _)| entity).collect()
+	}
+
+	fn gather_hits(point: &Vector2, hit_area_components: &ComponentList<HitArea2D>, transform2d_components: &Transform2DComponentList) -> Vec<(Entity, i32)> {
+		hit_area_components.iter()
+			.filter(|(entity, hit_area)| {
+				if let Some((min, max)) = hit_area.clip_rect {
+					if point.x < min.x || point.x > max.x || point.y < min.y || point.y > max.y {
+						return false;
+					}
+				}
+
+				let mut inverse_matrix = transform2d_components.borrow(entity).matrix;
+				inverse_matrix.invert();
+
+				let local_point = inverse_matrix * Vector3::new(point.x, point.y, 1.0);
+				hit_area.contains_point(&Vector2::new(local_point.x, local_point.y))
+			})
+			.map(|(entity, hit_area)| (*entity, hit_area.order))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform2D};
+
+	#[test]
+	fn hits_rotated_and_scaled_areas() {
+		let mut entity_manager = EntityManager::new();
+		let mut hit_area_components = ComponentList::new();
+		let mut transform2d_components = Transform2DComponentList::new();
+		let system = HitTestSystem::new();
+
+		let rotated = entity_manager.create();
+		let mut transform = Transform2D::new();
+		transform.position.set(100.0, 100.0);
+		transform.orientation = std::f32::consts::FRAC_PI_2;
+		transform2d_components.add(&mut entity_manager, rotated, transform);
+		hit_area_components.add(&mut entity_manager, rotated, HitArea2D::new(Vector2::new(0.0, 0.0), Vector2::new(50.0, 10.0)));
+
+		// The area is turned to point down the y axis
+		assert!(system.entity_at(&Vector2::new(95.0, 140.0), &hit_area_components, &transform2d_components) == Some(rotated));
+		assert!(system.entity_at(&Vector2::new(140.0, 105.0), &hit_area_components, &transform2d_components).is_none());
+
+		let scaled = entity_manager.create();
+		let mut transform = Transform2D::new();
+		transform.position.set(300.0, 0.0);
+		transform.scale.set(4.0, 2.0);
+		transform2d_components.add(&mut entity_manager, scaled, transform);
+		hit_area_components.add(&mut entity_manager, scaled, HitArea2D::new(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0)));
+
+		assert!(system.entity_at(&Vector2::new(335.0, 15.0), &hit_area_components, &transform2d_components) == Some(scaled));
+		assert!(system.entity_at(&Vector2::new(335.0, 25.0), &hit_area_components, &transform2d_components).is_none());
+	}
+
+	#[test]
+	fn top_most_area_wins() {
+		let mut entity_manager = EntityManager::new();
+		let mut hit_area_components = ComponentList::new();
+		let mut transform2d_components = Transform2DComponentList::new();
+		let mut system = HitTestSystem::new();
+
+		let mut add = |order: i32| {
+			let entity = entity_manager.create();
+			transform2d_components.add(&mut entity_manager, entity, Transform2D::new());
+			let mut hit_area = HitArea2D::new(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0));
+			hit_area.order = order;
+			hit_area_components.add(&mut entity_manager, entity, hit_area);
+			entity
+		};
+
+		let back = add(0);
+		let front = add(1);
+		let later = add(0);
+
+		let point = Vector2::new(5.0, 5.0);
+		system.update(&point, &hit_area_components, &transform2d_components);
+		assert!(system.hovered_entity() == Some(front));
+		assert!(system.entities_at(&point, &hit_area_components, &transform2d_components) == vec![front, later, back]);
+
+		system.update(&Vector2::new(20.0, 5.0), &hit_area_components, &transform2d_components);
+		assert!(system.hovered_entity().is_none());
+	}
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+use crate::{component::{ComponentList, IkChain, Transform3DComponentList}, math::{Vector3, quaternion}};
+
+// Run it after animating the joints and moving the targets, each chain is solved from where its joints are posed that frame
+pub struct IkSystem;
+
+impl IkSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn update(&self, ik_chain_components: &ComponentList<IkChain>, transform3d_components: &mut Transform3DComponentList) {
+		for (_, ik_chain) in ik_chain_components.iter() {
+			let Some(target_transform) = transform3d_components.try_borrow(&ik_chain.target) else {
+				continue;
+			};
+
+			let target = target_transform.global_matrix.extract_position();
+			let pole = ik_chain.pole
+				.and_then(|pole_entity| transform3d_components.try_borrow(&pole_entity))
+				.map(|pole_transform| pole_transform.global_matrix.extract_position());
+
+			for pair in ik_chain.joints.windows(2) {
+				assert!(transform3d_components.borrow(&pair[1]).parent_entity == Some(pair[0]), "Each joint of an IK chain must be a child of the joint before it");
+			}
+
+			let joint_position = |transform3d_components: &Transform3DComponentList, index: usize| transform3d_components.borrow(&ik_chain.joints[index]).global_matrix.extract_position();
+			let mut positions: Vec<Vector3> = (0..ik_chain.joints.len()).map(|index| joint_position(transform3d_components, index)).collect();
+			ik_chain.solver.solve(&mut positions, &target, pole.as_ref());
+
+			// Turn each bone from where it points to where it was solved to point, from the root down since turning a joint
+			// moves every joint after it
+			for (index, joint_entity) in ik_chain.joints.iter().enumerate().take(ik_chain.joints.len() - 1) {
+				let start = joint_position(transform3d_components, index);
+				let mut from = joint_position(transform3d_components, index + 1) - start;
+				let mut to = positions[index + 1] - start;
+
+				if from.length_sq() == 0.0 || to.length_sq() == 0.0 {
+					continue;
+				}
+
+				from.normalize();
+				to.normalize();
+
+				let mut rotation = quaternion::ZERO;
+				rotation.set_from_unit_vectors(&from, &to);
+
+				let transform = transform3d_components.borrow(joint_entity);
+				let (_, orientation, _) = transform.global_matrix.decompose();
+				let parent_orientation = transform.parent_entity.map_or(quaternion::ZERO, |parent_entity| {
+					let (_, mut parent_orientation, _) = transform3d_components.borrow(&parent_entity).global_matrix.decompose();
+					parent_orientation.conjigate();
+					parent_orientation
+				});
+
+				let local_orientation = parent_orientation * (rotation * orientation);
+				transform3d_components.borrow_mut(joint_entity).orientation = local_orientation;
+				transform3d_components.update(*joint_entity);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform3D};
+
+	#[test]
+	fn rotates_joints_to_reach_target() {
+		let mut entity_manager = EntityManager::new();
+		let mut ik_chain_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let system = IkSystem::new();
+
+		// A leg hanging down from a hip, the knee a unit below it and the ankle a unit below that
+		let hip_entity = entity_manager.create();
+		let mut hip_transform = Transform3D::new();
+		hip_transform.position = Vector3::new(0.0, 2.0, 0.0);
+		transform3d_components.add(&mut entity_manager, hip_entity, hip_transform);
+
+		let mut parent_entity = hip_entity;
+		let mut joints = vec![hip_entity];
+
+		for _ in 0..2 {
+			let entity = entity_manager.create();
+			let mut transform = Transform3D::new();
+			transform.position = Vector3::new(0.0, -1.0, 0.0);
+			transform3d_components.add_child(&mut entity_manager, parent_entity, entity, transform);
+			joints.push(entity);
+			parent_entity = entity;
+		}
+
+		let target_entity = entity_manager.create();
+		let mut target_transform = Transform3D::new();
+		target_transform.position = Vector3::new(0.0, 0.5, 0.8);
+		transform3d_components.add(&mut entity_manager, target_entity, target_transform);
+
+		let pole_entity = entity_manager.create();
+		let mut pole_transform = Transform3D::new();
+		pole_transform.position = Vector3::new(0.0, 1.0, 5.0);
+		transform3d_components.add(&mut entity_manager, pole_entity, pole_transform);
+
+		let mut ik_chain = IkChain::two_bone([joints[0], joints[1], joints[2]], target_entity);
+		ik_chain.pole = Some(pole_entity);
+		ik_chain_components.add(&mut entity_manager, hip_entity, ik_chain);
+
+		system.update(&ik_chain_components, &mut transform3d_components);
+
+		let position = |entity| transform3d_components.borrow(entity).global_matrix().extract_position();
+		assert!(position(&joints[2]).distance(&Vector3::new(0.0, 0.5, 0.8)) < 1e-4);
+		assert!(position(&joints[1]).z > 0.8);
+		assert!((position(&joints[0]).distance(&position(&joints[1])) - 1.0).abs() < 1e-4);
+		transform3d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
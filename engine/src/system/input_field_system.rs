@@ -0,0 +1,440 @@
+use std::time::Duration;
+use crate::{Entity, Font, component::{ComponentList, InputField, Text, TextComponentList, TextPositioning}, pool::Pool};
+
+const CARET_BLINK_INTERVAL_SECONDS: f32 = 0.5;
+const CARET_WIDTH: f32 = 1.0;
+
+pub struct InputFieldSystem {
+	focused_entity: Option<Entity>,
+	caret_visible: bool,
+	caret_blink_duration: Duration
+}
+
+impl InputFieldSystem {
+	pub fn new() -> Self {
+		Self {
+			focused_entity: None,
+			caret_visible: true,
+			caret_blink_duration: Duration::new(0, 0)
+		}
+	}
+
+	pub fn focused_entity(&self) -> Option<Entity> {
+		self.focused_entity
+	}
+
+	pub fn focus(&mut self, entity: Entity) {
+		self.focused_entity = Some(entity);
+		self.reset_caret_blink();
+	}
+
+	pub fn unfocus(&mut self) {
+		self.focused_entity = None;
+	}
+
+	pub fn handle_event(
+		&mut self,
+		event: &glfw::WindowEvent,
+		window: &mut glfw::Window,
+		input_field_components: &mut ComponentList<InputField>,
+		text_components: &mut TextComponentList,
+		fonts: &Pool<Font>) -> bool
+	{
+		let entity = match self.focused_entity {
+			Some(entity) => entity,
+			None => return false
+		};
+
+		let input_field = input_field_components.borrow_mut(&entity);
+
+		match *event {
+			glfw::WindowEvent::Char(c) => {
+				let font = fonts.borrow(text_components.borrow(&entity).font);
+
+				if Self::supported_char(font, c) {
+					let text = text_components.borrow_mut(entity);
+					Self::delete_selection(input_field, &mut text.string);
+					Self::insert(input_field, &mut text.string, &c.to_string());
+				}
+			},
+			glfw::WindowEvent::Key(key, _, glfw::Action::Press, modifiers) | glfw::WindowEvent::Key(key, _, glfw::Action::Repeat, modifiers) => {
+				if key == glfw::Key::Enter || key == glfw::Key::KpEnter {
+					self.focused_entity = None;
+					return true;
+				}
+
+				let shift = modifiers.contains(glfw::Modifiers::Shift);
+				let control = modifiers.contains(glfw::Modifiers::Control);
+
+				// Only what the font can draw is pasted
+				let pasted = if key == glfw::Key::V && control {
+					let font = fonts.borrow(text_components.borrow(&entity).font);
+					window.get_clipboard_string().map(|clipboard| clipboard.chars().filter(|c| Self::supported_char(font, *c)).collect::<String>())
+				}
+				else {
+					None
+				};
+
+				let text = text_components.borrow_mut(entity);
+
+				if let Some(copied) = Self::handle_key(input_field, &mut text.string, key, shift, control, pasted.as_deref()) {
+					window.set_clipboard_string(&copied);
+				}
+			},
+			glfw::WindowEvent::Key(..) => (),
+			_ => return false
+		}
+
+		self.reset_caret_blink();
+		true
+	}
+
+	// Edits the string or moves the caret for the key, returns what was copied or cut for the clipboard
+	fn handle_key(input_field: &mut InputField, string: &mut String, key: glfw::Key, shift: bool, control: bool, pasted: Option<&str>) -> Option<String> {
+		let char_count = string.chars().count();
+
+		match key {
+			glfw::Key::Backspace => Self::delete_before_caret(input_field, string),
+			glfw::Key::Delete => Self::delete_after_caret(input_field, string),
+			glfw::Key::Left => {
+				let target = match input_field.selection() {
+					Some((start, _)) if !shift => start,
+					_ => input_field.caret_index.saturating_sub(1)
+				};
+
+				Self::move_caret(input_field, target, shift);
+			},
+			glfw::Key::Right => {
+				let target = match input_field.selection() {
+					Some((_, end)) if !shift => end,
+					_ => (input_field.caret_index + 1).min(char_count)
+				};
+
+				Self::move_caret(input_field, target, shift);
+			},
+			glfw::Key::Home => Self::move_caret(input_field, 0, shift),
+			glfw::Key::End => Self::move_caret(input_field, char_count, shift),
+			glfw::Key::A if control => {
+				input_field.selection_anchor = Some(0);
+				input_field.caret_index = char_count;
+			},
+			glfw::Key::C if control => return Self::selected_string(input_field, string),
+			glfw::Key::X if control => {
+				let selected = Self::selected_string(input_field, string);
+				Self::delete_selection(input_field, string);
+				return selected;
+			},
+			glfw::Key::V if control => {
+				if let Some(pasted) = pasted {
+					Self::delete_selection(input_field, string);
+					Self::insert(input_field, string, pasted);
+				}
+			},
+			_ => ()
+		}
+
+		None
+	}
+
+	pub fn update(
+		&mut self,
+		delta_time: &Duration,
+		input_field_components: &mut ComponentList<InputField>,
+		text_components: &TextComponentList,
+		fonts: &Pool<Font>)
+	{
+		self.caret_blink_duration += *delta_time;
+
+		if self.caret_blink_duration >= Duration::from_secs_f32(CARET_BLINK_INTERVAL_SECONDS) {
+			self.caret_visible = !self.caret_visible;
+			self.caret_blink_duration = Duration::new(0, 0);
+		}
+
+		for (entity, input_field) in input_field_components.iter_mut() {
+			let focused = self.focused_entity == Some(*entity);
+			let text = text_components.borrow(entity);
+			let font = fonts.borrow(text.font);
+
+			// The quads span the font's tallest glyphs above and below the baseline, the text's origin
+			let (_, height, baseline) = font.measure("", font.size as f32);
+			let top = -baseline;
+			let bottom = height - baseline;
+
+			input_field.caret_quad = if focused && self.caret_visible {
+				let left = Self::caret_offset(font, text, input_field.caret_index);
+				Some(Self::quad(left, top, left + CARET_WIDTH, bottom))
+			}
+			else {
+				None
+			};
+
+			input_field.selection_quad = match input_field.selection() {
+				Some((start, end)) if focused => Some(Self::quad(Self::caret_offset(font, text, start), top, Self::caret_offset(font, text, end), bottom)),
+				_ => None
+			};
+		}
+	}
+
+	// How far along the line the caret is before the character at the index, snapped like the glyphs when the text is
+	fn caret_offset(font: &Font, text: &Text, char_index: usize) -> f32 {
+		let snapped = text.positioning == TextPositioning::PixelSnapped;
+
+		text.string.chars()
+			.take(char_index)
+			.map(|c| if snapped { Self::advance(font, c).round() } else { Self::advance(font, c) })
+			.sum()
+	}
+
+	// The quad's texture positions all sample the middle of the render system's solid texture
+	fn quad(left: f32, top: f32, right: f32, bottom: f32) -> [f32; 16] {
+		[
+			left, top, 0.5, 0.5,
+			right, top, 0.5, 0.5,
+			right, bottom, 0.5, 0.5,
+			left, bottom, 0.5, 0.5
+		]
+	}
+
+	fn reset_caret_blink(&mut self) {
+		self.caret_visible = true;
+		self.caret_blink_duration = Duration::new(0, 0);
+	}
+
+	fn supported_char(font: &Font, c: char) -> bool {
+		c == ' ' || font.glyphs.binary_search_by_key(&(c as u32), |g| g.char_code).is_ok()
+	}
+
+	fn advance(font: &Font, c: char) -> f32 {
+		if c == ' ' {
+			font.space_advance
+		}
+		else {
+			let glyph_index = font.glyphs.binary_search_by_key(&(c as u32), |g| g.char_code).unwrap();
+			font.glyphs[glyph_index].advance
+		}
+	}
+
+	fn byte_index(string: &str, char_index: usize) -> usize {
+		string.char_indices().nth(char_index).map_or(string.len(), |(byte_index, _)| byte_index)
+	}
+
+	fn move_caret(input_field: &mut InputField, char_index: usize, extend_selection: bool) {
+		if extend_selection {
+			input_field.selection_anchor.get_or_insert(input_field.caret_index);
+		}
+		else {
+			input_field.selection_anchor = None;
+		}
+
+		input_field.caret_index = char_index;
+	}
+
+	fn insert(input_field: &mut InputField, string: &mut String, inserted: &str) {
+		let byte_index = Self::byte_index(string, input_field.caret_index);
+		string.insert_str(byte_index, inserted);
+		input_field.caret_index += inserted.chars().count();
+	}
+
+	// Deletes the selection, or the character before the caret without one
+	fn delete_before_caret(input_field: &mut InputField, string: &mut String) {
+		if !Self::delete_selection(input_field, string) && input_field.caret_index > 0 {
+			input_field.caret_index -= 1;
+			let byte_index = Self::byte_index(string, input_field.caret_index);
+			string.remove(byte_index);
+		}
+	}
+
+	// Deletes the selection, or the character after the caret without one
+	fn delete_after_caret(input_field: &mut InputField, string: &mut String) {
+		if !Self::delete_selection(input_field, string) && input_field.caret_index < string.chars().count() {
+			let byte_index = Self::byte_index(string, input_field.caret_index);
+			string.remove(byte_index);
+		}
+	}
+
+	fn selected_string(input_field: &InputField, string: &str) -> Option<String> {
+		let (start, end) = input_field.selection()?;
+		Some(string.chars().skip(start).take(end - start).collect())
+	}
+
+	fn delete_selection(input_field: &mut InputField, string: &mut String) -> bool {
+		let selection = input_field.selection();
+		input_field.selection_anchor = None;
+
+		let (start, end) = match selection {
+			Some(selection) => selection,
+			None => return false
+		};
+
+		let start_byte_index = Self::byte_index(string, start);
+		let end_byte_index = Self::byte_index(string, end);
+		string.replace_range(start_byte_index..end_byte_index, "");
+		input_field.caret_index = start;
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, font::Glyph, math::color};
+
+	fn press(input_field: &mut InputField, string: &mut String, key: glfw::Key, shift: bool) -> Option<String> {
+		InputFieldSystem::handle_key(input_field, string, key, shift, false, None)
+	}
+
+	fn press_with_control(input_field: &mut InputField, string: &mut String, key: glfw::Key, pasted: Option<&str>) -> Option<String> {
+		InputFieldSystem::handle_key(input_field, string, key, false, true, pasted)
+	}
+
+	// A field with the caret at the end of the string
+	fn input_field(string: &str) -> (InputField, String) {
+		let mut input_field = InputField::new(color::WHITE, color::BLUE);
+		input_field.caret_index = string.chars().count();
+		(input_field, String::from(string))
+	}
+
+	#[test]
+	fn moves_the_caret_within_the_string() {
+		let (mut input_field, mut string) = input_field("abc");
+
+		press(&mut input_field, &mut string, glfw::Key::Right, false);
+		assert_eq!(input_field.caret_index(), 3);
+
+		press(&mut input_field, &mut string, glfw::Key::Left, false);
+		assert_eq!(input_field.caret_index(), 2);
+
+		press(&mut input_field, &mut string, glfw::Key::Home, false);
+		assert_eq!(input_field.caret_index(), 0);
+
+		press(&mut input_field, &mut string, glfw::Key::Left, false);
+		assert_eq!(input_field.caret_index(), 0);
+
+		press(&mut input_field, &mut string, glfw::Key::End, false);
+		assert_eq!(input_field.caret_index(), 3);
+		assert_eq!(input_field.selection(), None);
+	}
+
+	#[test]
+	fn shift_extends_the_selection_and_arrows_collapse_it() {
+		let (mut input_field, mut string) = input_field("abcd");
+
+		press(&mut input_field, &mut string, glfw::Key::Left, true);
+		press(&mut input_field, &mut string, glfw::Key::Left, true);
+		assert_eq!(input_field.selection(), Some((2, 4)));
+
+		// Moving the caret back over the anchor flips which end it's at
+		press(&mut input_field, &mut string, glfw::Key::End, true);
+		press(&mut input_field, &mut string, glfw::Key::Home, true);
+		assert_eq!(input_field.selection(), Some((0, 4)));
+
+		press(&mut input_field, &mut string, glfw::Key::Right, false);
+		assert_eq!(input_field.selection(), None);
+		assert_eq!(input_field.caret_index(), 4);
+	}
+
+	#[test]
+	fn backspace_and_delete_remove_a_character_or_the_selection() {
+		let (mut input_field, mut string) = input_field("aébc");
+
+		press(&mut input_field, &mut string, glfw::Key::Left, false);
+		press(&mut input_field, &mut string, glfw::Key::Left, false);
+		press(&mut input_field, &mut string, glfw::Key::Backspace, false);
+		assert_eq!(string, "abc");
+		assert_eq!(input_field.caret_index(), 1);
+
+		press(&mut input_field, &mut string, glfw::Key::Delete, false);
+		assert_eq!(string, "ac");
+		assert_eq!(input_field.caret_index(), 1);
+
+		press_with_control(&mut input_field, &mut string, glfw::Key::A, None);
+		press(&mut input_field, &mut string, glfw::Key::Delete, false);
+		assert_eq!(string, "");
+		assert_eq!(input_field.caret_index(), 0);
+	}
+
+	#[test]
+	fn copies_cuts_and_pastes_the_selection() {
+		let (mut input_field, mut string) = input_field("abcd");
+
+		press(&mut input_field, &mut string, glfw::Key::Left, true);
+		press(&mut input_field, &mut string, glfw::Key::Left, true);
+
+		assert_eq!(press_with_control(&mut input_field, &mut string, glfw::Key::C, None).as_deref(), Some("cd"));
+		assert_eq!(string, "abcd");
+
+		assert_eq!(press_with_control(&mut input_field, &mut string, glfw::Key::X, None).as_deref(), Some("cd"));
+		assert_eq!(string, "ab");
+		assert_eq!(input_field.caret_index(), 2);
+
+		// Nothing is copied without a selection
+		assert_eq!(press_with_control(&mut input_field, &mut string, glfw::Key::C, None), None);
+
+		press(&mut input_field, &mut string, glfw::Key::Home, true);
+		press_with_control(&mut input_field, &mut string, glfw::Key::V, Some("xyz"));
+		assert_eq!(string, "xyz");
+		assert_eq!(input_field.caret_index(), 3);
+		assert_eq!(input_field.selection(), None);
+	}
+
+	#[test]
+	fn places_the_caret_and_selection_from_glyph_advances() {
+		let glyph = |char_code| Glyph {
+			char_code,
+			position_x: 0.0,
+			position_y: 0.0,
+			width: 8.0,
+			height: 12.0,
+			bearing_x: 1.0,
+			bearing_y: -10.0,
+			advance: 9.4
+		};
+
+		let mut fonts = Pool::new();
+		let font = fonts.add(Font {
+			fnt_path: String::new(),
+			size: 10,
+			color: false,
+			atlas_width: 16,
+			atlas_height: 12,
+			space_advance: 4.0,
+			glyphs: vec![glyph('a' as u32), glyph('b' as u32)],
+			generated_atlas: None,
+			submission_info: None
+		});
+
+		let mut entity_manager = EntityManager::new();
+		let entity = entity_manager.create();
+		let mut text_components = TextComponentList::new();
+		text_components.add(&mut entity_manager, entity, Text::new(font, String::from("a b")));
+
+		let (mut input_field, _) = input_field("a b");
+		input_field.selection_anchor = Some(1);
+		let mut input_field_components = ComponentList::new();
+		input_field_components.add(&mut entity_manager, entity, input_field);
+
+		let mut system = InputFieldSystem::new();
+		system.update(&Duration::new(0, 0), &mut input_field_components, &text_components, &fonts);
+
+		let input_field = input_field_components.borrow(&entity);
+		assert_eq!(input_field.caret_quad(), None);
+		assert_eq!(input_field.selection_quad(), None);
+
+		// The advances are rounded like the pixel snapped glyphs, the quads span the glyphs' 10 pixels above the baseline and 2 below
+		system.focus(entity);
+		system.update(&Duration::new(0, 0), &mut input_field_components, &text_components, &fonts);
+
+		let input_field = input_field_components.borrow(&entity);
+		assert_eq!(&input_field.caret_quad().unwrap()[..8], &[
+			22.0, -10.0, 0.5, 0.5,
+			23.0, -10.0, 0.5, 0.5
+		]);
+		assert_eq!(input_field.caret_quad().unwrap()[9], 2.0);
+		assert_eq!(input_field.selection_quad().unwrap(), &[
+			9.0, -10.0, 0.5, 0.5,
+			22.0, -10.0, 0.5, 0.5,
+			22.0, 2.0, 0.5, 0.5,
+			9.0, 2.0, 0.5, 0.5
+		]);
+	}
+}
\ No newline at end of file
@@ -1,44 +1,269 @@
-use crate::{Entity, Geometry3D, component::{ComponentList, MultiComponentList, Mesh, MeshBoundsHelper, Transform3DComponentList}, math::{Box3, Vector3}, pool::Pool};
+use std::collections::HashSet;
+use crate::{
+	Entity,
+	Geometry3D,
+	component::{ComponentList, MultiComponentList, Mesh, MeshBoundsHelper, Transform3DComponentList, ALL_LAYERS_MASK},
+	geometry3d::Topology,
+	math::{Matrix4, Vector3},
+	pool::Pool
+};
 
+const BOUNDS_COLOR: [f32; 4] = [0.9, 0.8, 0.2, 1.0];
+const NORMAL_COLOR: [f32; 4] = [0.2, 0.8, 0.9, 1.0];
+const WIREFRAME_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+
+// The edges of a box with its corners in the order create_box_helper puts them
+const BOX_EDGES: [u16; 24] = [0, 1, 1, 2, 2, 3, 3, 0, 0, 4, 1, 5, 2, 6, 3, 7, 4, 5, 5, 6, 6, 7, 7, 4];
+
+// The lines of one update in world space
+struct LineBatch {
+	indices: Vec<u16>,
+	positions: Vec<f32>,
+	colors: Vec<f32>
+}
+
+impl LineBatch {
+	fn vertex_count(&self) -> usize {
+		self.positions.len() / 3
+	}
+
+	fn fits(&self, vertex_count: usize) -> bool {
+		self.vertex_count() + vertex_count <= u16::MAX as usize + 1
+	}
+
+	// Adds the vertices and lines between them, the indices are into the given vertices
+	fn add(&mut self, vertices: &[Vector3], indices: impl Iterator<Item = u16>, color: [f32; 4]) {
+		let first_index = self.vertex_count() as u16;
+
+		for vertex in vertices {
+			self.positions.extend_from_slice(&[vertex.x, vertex.y, vertex.z]);
+			self.colors.extend_from_slice(&color);
+		}
+
+		self.indices.extend(indices.map(|index| first_index + index));
+	}
+}
+
+// Draws debug lines for every entity with a MeshBoundsHelper, its bounding box, vertex normals and wireframe as each is
+// toggled on the component. They're batched into the line geometry of a single entity which is made again every update, its
+// transform should be left as the identity since the lines are in world space. Meshes that don't fit in 16 bit indices
+// anymore are left out.
 pub struct MeshBoundsHelperSystem {
-	pub entities: Vec<Entity>
+	pub normal_length: f32,
+	warned: bool
 }
 
 impl MeshBoundsHelperSystem {
 	pub fn new() -> Self {
 		Self {
-			entities: Vec::new()
+			normal_length: 0.25,
+			warned: false
 		}
 	}
 
-	pub fn update(&self, transform_components: &mut Transform3DComponentList, mesh_components: &MultiComponentList<Mesh>, geometries: &mut Pool<Geometry3D>, mesh_bounds_helper_components: &ComponentList<MeshBoundsHelper>) {
-		for entity in &self.entities {
-			let transform = transform_components.borrow(entity);
-			let transform_position = transform.position;
-			let global_matrix = transform.global_matrix().truncate();
-			let mesh = mesh_components.borrow(entity);
-			let geometry = geometries.borrow(mesh.geometry_handle);
-			let bounding_box_vertices = geometry.bounding_box().as_vertices();
+	pub fn update(
+		&mut self,
+		batch_entity: Entity,
+		transform_components: &Transform3DComponentList,
+		mesh_components: &mut MultiComponentList<Mesh>,
+		geometries: &mut Pool<Geometry3D>,
+		mesh_bounds_helper_components: &ComponentList<MeshBoundsHelper>)
+	{
+		let mut batch = LineBatch {
+			indices: Vec::new(),
+			positions: Vec::new(),
+			colors: Vec::new()
+		};
+
+		let mut left_out = 0;
 
-			let mut min = Vector3::from_scalar(f32::INFINITY);
-			let mut max = Vector3::from_scalar(f32::NEG_INFINITY);
-			
-			for vertex in &bounding_box_vertices {
-				let transformed_vertex = global_matrix * vertex;
+		for (entity, helper) in mesh_bounds_helper_components.iter() {
+			let geometry = geometries.borrow(mesh_components.borrow(entity).geometry_handle);
+			let global_matrix = transform_components.borrow(entity).global_matrix();
 
-				min.min(&transformed_vertex);
-				max.max(&transformed_vertex);
+			if helper.bounds && !Self::add_bounds(&mut batch, geometry, global_matrix) {
+				left_out += 1;
 			}
 
-			let bounds_entity = mesh_bounds_helper_components.borrow(entity).bounds_entity;
-			
-			let bounds_mesh = mesh_components.borrow(&bounds_entity);
-			let bounds_geo = geometries.borrow_mut(bounds_mesh.geometry_handle);
-			bounds_geo.make_box_helper(&Box3::new(min, max));
+			if helper.normals && !Self::add_normals(&mut batch, geometry, global_matrix, self.normal_length) {
+				left_out += 1;
+			}
+
+			if helper.wireframe && !Self::add_wireframe(&mut batch, geometry, global_matrix) {
+				left_out += 1;
+			}
+		}
 
-			let bounds_transform = transform_components.borrow_mut(&bounds_entity);
-			bounds_transform.position = transform_position;
-			transform_components.update(bounds_entity);
+		if left_out > 0 && !self.warned {
+			println!("{} mesh helper(s) left out, there are too many vertices for one batch", left_out);
+			self.warned = true;
 		}
+
+		// Empty geometry has no bounding box so the old lines are hidden instead
+		let batch_mesh = mesh_components.borrow_mut(&batch_entity);
+		batch_mesh.layer_mask = if batch.indices.is_empty() { 0 } else { ALL_LAYERS_MASK };
+
+		if !batch.indices.is_empty() {
+			let batch_geometry = geometries.borrow_mut(batch_mesh.geometry_handle);
+			batch_geometry.set(batch.indices, batch.positions, Topology::Line);
+			batch_geometry.set_colors(batch.colors);
+		}
+	}
+
+	// The world space box around the transformed bounding box
+	fn add_bounds(batch: &mut LineBatch, geometry: &Geometry3D, global_matrix: &Matrix4) -> bool {
+		if !batch.fits(8) {
+			return false;
+		}
+
+		let mut min = Vector3::from_scalar(f32::INFINITY);
+		let mut max = Vector3::from_scalar(f32::NEG_INFINITY);
+
+		for mut vertex in geometry.bounding_box().as_vertices() {
+			vertex.apply_matrix4(global_matrix);
+			min.min(&vertex);
+			max.max(&vertex);
+		}
+
+		let corners = [
+			Vector3::new(max.x, max.y, max.z),
+			Vector3::new(min.x, max.y, max.z),
+			Vector3::new(min.x, max.y, min.z),
+			Vector3::new(max.x, max.y, min.z),
+			Vector3::new(max.x, min.y, max.z),
+			Vector3::new(min.x, min.y, max.z),
+			Vector3::new(min.x, min.y, min.z),
+			Vector3::new(max.x, min.y, min.z)
+		];
+
+		batch.add(&corners, BOX_EDGES.iter().copied(), BOUNDS_COLOR);
+		true
+	}
+
+	fn add_normals(batch: &mut LineBatch, geometry: &Geometry3D, global_matrix: &Matrix4, length: f32) -> bool {
+		// Line geometry has no normals
+		if matches!(geometry.topology(), Topology::Line) {
+			return true;
+		}
+
+		let attributes = geometry.attributes();
+		let vertex_count = attributes.len() / 6;
+
+		if !batch.fits(vertex_count * 2) {
+			return false;
+		}
+
+		let mut normal_matrix = *global_matrix;
+		normal_matrix.invert();
+		normal_matrix.transpose();
+
+		let vertices: Vec<Vector3> = attributes.chunks_exact(6)
+			.flat_map(|vertex| {
+				let mut position = Vector3::new(vertex[0], vertex[1], vertex[2]);
+				position.apply_matrix4(global_matrix);
+
+				let mut normal = Vector3::new(vertex[3], vertex[4], vertex[5]);
+				normal.transform_direction(&normal_matrix);
+				normal.normalize();
+
+				[position, position + normal * length]
+			})
+			.collect();
+
+		batch.add(&vertices, 0..vertex_count as u16 * 2, NORMAL_COLOR);
+		true
+	}
+
+	// Triangle edges are only drawn once even when triangles share them
+	fn add_wireframe(batch: &mut LineBatch, geometry: &Geometry3D, global_matrix: &Matrix4) -> bool {
+		let stride = match geometry.topology() {
+			Topology::Triangle => 6,
+			Topology::Line => 3
+		};
+
+		let vertices: Vec<Vector3> = geometry.attributes().chunks_exact(stride)
+			.map(|vertex| {
+				let mut position = Vector3::new(vertex[0], vertex[1], vertex[2]);
+				position.apply_matrix4(global_matrix);
+				position
+			})
+			.collect();
+
+		if !batch.fits(vertices.len()) {
+			return false;
+		}
+
+		let indices: Vec<u16> = match geometry.topology() {
+			Topology::Triangle => {
+				let mut edges = HashSet::new();
+
+				geometry.indices().chunks_exact(3)
+					.flat_map(|triangle| [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])])
+					.filter(|(a, b)| edges.insert((*a.min(b), *a.max(b))))
+					.flat_map(|(a, b)| [a, b])
+					.collect()
+			},
+			Topology::Line => geometry.indices().to_vec()
+		};
+
+		batch.add(&vertices, indices.into_iter(), WIREFRAME_COLOR);
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::{Transform3D, mesh::Material}, math::assert_approx_eq};
+
+	#[test]
+	fn batches_enabled_helpers() {
+		let mut entity_manager = EntityManager::new();
+		let mut transform_components = Transform3DComponentList::new();
+		let mut mesh_components = MultiComponentList::new();
+		let mut geometries = Pool::new();
+		let mut mesh_bounds_helper_components = ComponentList::new();
+
+		let batch_entity = entity_manager.create();
+		transform_components.add(&mut entity_manager, batch_entity, Transform3D::new());
+		let geometry_handle = geometries.add(Geometry3D::create_axis_helper());
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Line));
+		mesh_components.assign(&mut entity_manager, batch_entity, index);
+
+		// A 2 unit box scaled by 2
+		let box_entity = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(10.0, 0.0, 0.0);
+		transform.scale.set_from_scalar(2.0);
+		transform_components.add(&mut entity_manager, box_entity, transform);
+		let index = mesh_components.add(Mesh::new(geometries.add(Geometry3D::create_box()), Material::Basic));
+		mesh_components.assign(&mut entity_manager, box_entity, index);
+		mesh_bounds_helper_components.add(&mut entity_manager, box_entity, MeshBoundsHelper::new());
+
+		let mut system = MeshBoundsHelperSystem::new();
+		let update = |system: &mut MeshBoundsHelperSystem, helpers: &ComponentList<MeshBoundsHelper>, mesh_components: &mut MultiComponentList<Mesh>, geometries: &mut Pool<Geometry3D>| {
+			system.update(batch_entity, &transform_components, mesh_components, geometries, helpers);
+			geometries.borrow(mesh_components.borrow(&batch_entity).geometry_handle).indices().len()
+		};
+
+		assert_eq!(update(&mut system, &mesh_bounds_helper_components, &mut mesh_components, &mut geometries), 24);
+		let bounding_box = geometries.borrow(geometry_handle).bounding_box();
+		assert_approx_eq(&bounding_box.min, &Vector3::new(8.0, -2.0, -2.0), 1e-5);
+		assert_approx_eq(&bounding_box.max, &Vector3::new(12.0, 2.0, 2.0), 1e-5);
+
+		// A line for each of the 24 vertices and 5 edges for each of the 6 faces, the diagonals aren't doubled
+		let helper = mesh_bounds_helper_components.borrow_mut(&box_entity);
+		helper.normals = true;
+		helper.wireframe = true;
+		assert_eq!(update(&mut system, &mesh_bounds_helper_components, &mut mesh_components, &mut geometries), 24 + 24 * 2 + 30 * 2);
+		assert_approx_eq(&geometries.borrow(geometry_handle).bounding_box().max, &Vector3::new(12.25, 2.25, 2.25), 1e-5);
+		assert_eq!(mesh_components.borrow(&batch_entity).layer_mask, ALL_LAYERS_MASK);
+
+		let helper = mesh_bounds_helper_components.borrow_mut(&box_entity);
+		helper.bounds = false;
+		helper.normals = false;
+		helper.wireframe = false;
+		update(&mut system, &mesh_bounds_helper_components, &mut mesh_components, &mut geometries);
+		assert_eq!(mesh_components.borrow(&batch_entity).layer_mask, 0);
 	}
 }
\ No newline at end of file
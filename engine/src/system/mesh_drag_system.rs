@@ -0,0 +1,281 @@
+use crate::{
+	Entity,
+	Geometry3D,
+	component::{ComponentList, Draggable3D, DragConstraint, Mesh, MultiComponentList, Transform3DComponentList},
+	geometry3d::Topology,
+	math::{Ray, Vector3},
+	pool::Pool
+};
+
+// Closer to parallel than this the ray can't tell where along the plane or axis the cursor is
+const PARALLEL_TOLERANCE: f32 = 1e-4;
+
+struct Drag {
+	entity: Entity,
+	constraint: DragConstraint,
+	snap: Option<f32>,
+	grab_point: Vector3,
+	start_global_position: Vector3,
+	start_position: Vector3
+}
+
+// Grabs meshes with a Draggable3D under the cursor and moves them along their plane or axis. The cursor is given as a ray such
+// as the camera's cursor ray, the entity keeps the offset it had from the point under the cursor when it was grabbed.
+pub struct MeshDragSystem {
+	drag: Option<Drag>
+}
+
+impl MeshDragSystem {
+	pub fn new() -> Self {
+		Self {
+			drag: None
+		}
+	}
+
+	pub fn dragged_entity(&self) -> Option<Entity> {
+		self.drag.as_ref().map(|drag| drag.entity)
+	}
+
+	// Grabs the closest draggable the ray hits, returns whether one was grabbed
+	pub fn begin(
+		&mut self,
+		ray: &Ray,
+		draggable_components: &ComponentList<Draggable3D>,
+		mesh_components: &MultiComponentList<Mesh>,
+		geometries: &Pool<Geometry3D>,
+		transform3d_components: &Transform3DComponentList) -> bool
+	{
+		let hit = draggable_components.iter()
+			.filter_map(|(entity, _)| Some((*entity, Self::intersect_mesh(ray, entity, mesh_components, geometries, transform3d_components)?)))
+			.min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+		self.drag = hit.map(|(entity, distance)| {
+			let draggable = draggable_components.borrow(&entity);
+			let transform = transform3d_components.borrow(&entity);
+
+			Drag {
+				entity,
+				constraint: draggable.constraint,
+				snap: draggable.snap,
+				grab_point: ray.at(distance),
+				start_global_position: transform.global_matrix().extract_position(),
+				start_position: transform.position
+			}
+		});
+
+		self.drag.is_some()
+	}
+
+	// Moves the dragged entity to where the ray crosses its plane or axis. It stays where it is while the ray is too close to
+	// parallel with them or crosses the plane behind where the ray starts.
+	pub fn update(&self, ray: &Ray, transform3d_components: &mut Transform3DComponentList) {
+		let drag = match &self.drag {
+			Some(drag) => drag,
+			None => return
+		};
+
+		let point = match Self::constrained_point(ray, &drag.constraint, &drag.grab_point) {
+			Some(point) => point,
+			None => return
+		};
+
+		let mut offset = point - drag.grab_point;
+
+		if let Some(snap) = drag.snap {
+			let snap_value = |value: f32| (value / snap).round() * snap;
+
+			offset = match drag.constraint {
+				// Steps along the axis instead of the world axes so it stays on the axis
+				DragConstraint::Axis { mut direction } => {
+					direction.normalize();
+					direction * snap_value(offset.dot(&direction))
+				},
+				DragConstraint::Plane { .. } => Vector3::new(snap_value(offset.x), snap_value(offset.y), snap_value(offset.z))
+			};
+		}
+
+		let mut position = drag.start_global_position + offset;
+
+		// Into the parent's space
+		if let Some(parent_entity) = transform3d_components.borrow(&drag.entity).parent_entity {
+			let mut inverse_parent_global_matrix = *transform3d_components.borrow(&parent_entity).global_matrix();
+			inverse_parent_global_matrix.invert();
+			position.apply_matrix4(&inverse_parent_global_matrix);
+		}
+
+		transform3d_components.borrow_mut(&drag.entity).position = position;
+		transform3d_components.update(drag.entity);
+	}
+
+	// Lets go of the dragged entity where it is and returns it
+	pub fn end(&mut self) -> Option<Entity> {
+		self.drag.take().map(|drag| drag.entity)
+	}
+
+	// Lets go of the dragged entity and puts it back where it was grabbed, such as when the player presses escape
+	pub fn cancel(&mut self, transform3d_components: &mut Transform3DComponentList) {
+		if let Some(drag) = self.drag.take() {
+			transform3d_components.borrow_mut(&drag.entity).position = drag.start_position;
+			transform3d_components.update(drag.entity);
+		}
+	}
+
+	// The distance along the ray to the closest triangle of the entity's mesh, or to its bounding box for line meshes. The
+	// ray is taken into the mesh's space where the distance along it stays the same.
+	pub fn intersect_mesh(
+		ray: &Ray,
+		entity: &Entity,
+		mesh_components: &MultiComponentList<Mesh>,
+		geometries: &Pool<Geometry3D>,
+		transform3d_components: &Transform3DComponentList) -> Option<f32>
+	{
+		let mesh = mesh_components.try_borrow(entity)?;
+		let geometry = geometries.try_borrow(mesh.geometry_handle)?;
+
+		let mut inverse_global_matrix = *transform3d_components.try_borrow(entity)?.global_matrix();
+		inverse_global_matrix.invert();
+		let mut local_ray = *ray;
+		local_ray.apply_matrix4(&inverse_global_matrix);
+
+		let box_distance = local_ray.intersect_box(geometry.bounding_box())?;
+
+		match geometry.topology() {
+			Topology::Line => Some(box_distance),
+			Topology::Triangle => {
+				let attributes = geometry.attributes();
+
+				let position = |index: u16| {
+					let offset = index as usize * 6;
+					Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2])
+				};
+
+				geometry.indices().chunks_exact(3)
+					.filter_map(|triangle| local_ray.intersect_triangle(&position(triangle[0]), &position(triangle[1]), &position(triangle[2])))
+					.min_by(|a, b| a.total_cmp(b))
+			}
+		}
+	}
+
+	fn constrained_point(ray: &Ray, constraint: &DragConstraint, grab_point: &Vector3) -> Option<Vector3> {
+		match *constraint {
+			DragConstraint::Plane { mut normal } => {
+				normal.normalize();
+				let denominator = normal.dot(&ray.direction);
+
+				if denominator.abs() < PARALLEL_TOLERANCE {
+					return None;
+				}
+
+				let distance = normal.dot(&(*grab_point - ray.origin)) / denominator;
+				if distance >= 0.0 { Some(ray.at(distance)) } else { None }
+			},
+			// The point on the axis closest to the ray's line
+			DragConstraint::Axis { mut direction } => {
+				direction.normalize();
+				let start_offset = *grab_point - ray.origin;
+				let b = direction.dot(&ray.direction);
+				let c = ray.direction.dot(&ray.direction);
+				let d = direction.dot(&start_offset);
+				let e = ray.direction.dot(&start_offset);
+				let denominator = c - b * b;
+
+				if denominator < PARALLEL_TOLERANCE {
+					return None;
+				}
+
+				Some(*grab_point + direction * ((b * e - c * d) / denominator))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::{Transform3D, mesh::Material}, math::{assert_approx_eq, vector3}};
+
+	struct Scene {
+		entity_manager: EntityManager,
+		draggable_components: ComponentList<Draggable3D>,
+		mesh_components: MultiComponentList<Mesh>,
+		geometries: Pool<Geometry3D>,
+		transform3d_components: Transform3DComponentList
+	}
+
+	impl Scene {
+		fn new() -> Self {
+			Self {
+				entity_manager: EntityManager::new(),
+				draggable_components: ComponentList::new(),
+				mesh_components: MultiComponentList::new(),
+				geometries: Pool::new(),
+				transform3d_components: Transform3DComponentList::new()
+			}
+		}
+
+		// A 2 unit box
+		fn add_box(&mut self, parent: Option<Entity>, position: Vector3, draggable: Draggable3D) -> Entity {
+			let entity = self.entity_manager.create();
+			let mut transform = Transform3D::new();
+			transform.position = position;
+
+			match parent {
+				Some(parent) => self.transform3d_components.add_child(&mut self.entity_manager, parent, entity, transform),
+				None => self.transform3d_components.add(&mut self.entity_manager, entity, transform)
+			}
+
+			let geometry_handle = self.geometries.add(Geometry3D::create_box());
+			let mesh = self.mesh_components.add(Mesh::new(geometry_handle, Material::Basic));
+			self.mesh_components.assign(&mut self.entity_manager, entity, mesh);
+			self.draggable_components.add(&mut self.entity_manager, entity, draggable);
+			entity
+		}
+
+		fn begin(&self, system: &mut MeshDragSystem, ray: &Ray) -> bool {
+			system.begin(ray, &self.draggable_components, &self.mesh_components, &self.geometries, &self.transform3d_components)
+		}
+	}
+
+	#[test]
+	fn drags_along_a_plane_with_snapping() {
+		let mut scene = Scene::new();
+		let mut draggable = Draggable3D::new(DragConstraint::Plane { normal: vector3::UNIT_Y });
+		draggable.snap = Some(1.0);
+		let front = scene.add_box(None, vector3::ZERO, draggable);
+		scene.add_box(None, Vector3::new(0.0, 0.0, -5.0), draggable);
+		let mut system = MeshDragSystem::new();
+
+		assert!(!scene.begin(&mut system, &Ray::new(Vector3::new(5.0, 0.0, 10.0), -vector3::UNIT_Z)));
+		assert!(scene.begin(&mut system, &Ray::new(Vector3::new(0.0, 0.0, 10.0), -vector3::UNIT_Z)));
+		assert!(system.dragged_entity() == Some(front));
+
+		// Grabbed on its front face, the ray looking down crosses the plane 3.3 to the right and 1.6 forward of there
+		system.update(&Ray::new(Vector3::new(3.3, 10.0, 2.6), -vector3::UNIT_Y), &mut scene.transform3d_components);
+		assert_approx_eq(&scene.transform3d_components.borrow(&front).position, &Vector3::new(3.0, 0.0, 2.0), 1e-5);
+
+		// A ray along the plane doesn't move it
+		system.update(&Ray::new(Vector3::new(0.0, 0.0, 10.0), vector3::UNIT_X), &mut scene.transform3d_components);
+		assert_approx_eq(&scene.transform3d_components.borrow(&front).position, &Vector3::new(3.0, 0.0, 2.0), 1e-5);
+
+		system.cancel(&mut scene.transform3d_components);
+		assert_eq!(scene.transform3d_components.borrow(&front).position, vector3::ZERO);
+		assert!(system.end().is_none());
+	}
+
+	#[test]
+	fn drags_children_along_an_axis() {
+		let mut scene = Scene::new();
+		let parent = scene.add_box(None, Vector3::new(10.0, 0.0, 0.0), Draggable3D::new(DragConstraint::Axis { direction: vector3::UNIT_Y }));
+		let child = scene.add_box(Some(parent), Vector3::new(0.0, 0.0, 4.0), Draggable3D::new(DragConstraint::Axis { direction: vector3::UNIT_X }));
+		let mut system = MeshDragSystem::new();
+
+		// The child is in front of its parent
+		assert!(scene.begin(&mut system, &Ray::new(Vector3::new(10.0, 0.0, 20.0), -vector3::UNIT_Z)));
+		assert!(system.dragged_entity() == Some(child));
+
+		system.update(&Ray::new(Vector3::new(14.0, 3.0, 20.0), -vector3::UNIT_Z), &mut scene.transform3d_components);
+		assert_approx_eq(&scene.transform3d_components.borrow(&child).position, &Vector3::new(4.0, 0.0, 4.0), 1e-5);
+		assert!(system.end() == Some(child));
+		scene.transform3d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
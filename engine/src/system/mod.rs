@@ -1,5 +1,60 @@
 pub mod render_system;
-pub use render_system::RenderSystem;
+pub use render_system::{RenderSystem, RenderScene, RenderSystemSettings, RenderStats, SubmissionStatus, MemoryCategory, MemoryUsage, HeapUsage, FrameCapture, FontSubmissionError, TextureSubmissionError};
 
 pub mod mesh_bounds_helper_system;
-pub use mesh_bounds_helper_system::MeshBoundsHelperSystem;
\ No newline at end of file
+pub use mesh_bounds_helper_system::MeshBoundsHelperSystem;
+
+pub mod debug_helper_system;
+pub use debug_helper_system::DebugHelperSystem;
+
+pub mod input_field_system;
+pub use input_field_system::InputFieldSystem;
+
+pub mod sprite_animation_system;
+pub use sprite_animation_system::SpriteAnimationSystem;
+
+pub mod physics2d_system;
+pub use physics2d_system::{Physics2DSystem, Contact2D};
+
+pub mod hit_test_system;
+pub use hit_test_system::HitTestSystem;
+
+pub mod scroll_view_system;
+pub use scroll_view_system::ScrollViewSystem;
+
+pub mod drag_drop_system;
+pub use drag_drop_system::{DragDropSystem, Drop2D};
+
+pub mod mesh_drag_system;
+pub use mesh_drag_system::MeshDragSystem;
+
+pub mod path_follower_system;
+pub use path_follower_system::PathFollowerSystem;
+
+pub mod constraint_system;
+pub use constraint_system::ConstraintSystem;
+
+pub mod ik_system;
+pub use ik_system::IkSystem;
+
+pub mod animation_system;
+pub use animation_system::AnimationSystem;
+
+pub mod socket_system;
+pub use socket_system::SocketSystem;
+
+pub mod trail_system;
+pub use trail_system::TrailSystem;
+
+pub mod blob_shadow_system;
+pub use blob_shadow_system::BlobShadowSystem;
+
+#[cfg(feature = "scripting")]
+pub mod script_system;
+#[cfg(feature = "scripting")]
+pub use script_system::{ScriptSystem, ScriptInput, ScriptError};
+
+#[cfg(feature = "audio")]
+pub mod audio_system;
+#[cfg(feature = "audio")]
+pub use audio_system::{AudioSystem, AudioError};
\ No newline at end of file
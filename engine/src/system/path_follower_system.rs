@@ -0,0 +1,70 @@
+use std::time::Duration;
+use crate::component::{ComponentList, PathFollower, Transform3DComponentList};
+
+pub struct PathFollowerSystem;
+
+impl PathFollowerSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn update(&self, delta_time: &Duration, path_follower_components: &mut ComponentList<PathFollower>, transform3d_components: &mut Transform3DComponentList) {
+		for (entity, path_follower) in path_follower_components.iter_mut() {
+			let length = path_follower.spline.length();
+			let distance = path_follower.distance + path_follower.speed * delta_time.as_secs_f32();
+
+			path_follower.distance = if path_follower.looping && length > 0.0 {
+				distance.rem_euclid(length)
+			}
+			else {
+				distance.clamp(0.0, length)
+			};
+
+			let transform = transform3d_components.borrow_mut(entity);
+			transform.position = path_follower.spline.point_at_distance(path_follower.distance);
+
+			if path_follower.orient {
+				transform.orientation = path_follower.spline.orientation_at_distance(path_follower.distance, &path_follower.up);
+			}
+
+			transform3d_components.update(*entity);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform3D, math::{Spline, Vector3, assert_approx_eq, vector3}};
+
+	#[test]
+	fn follows_the_spline() {
+		let mut entity_manager = EntityManager::new();
+		let mut path_follower_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let system = PathFollowerSystem::new();
+
+		let entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, entity, Transform3D::new());
+		let spline = Spline::catmull_rom(vec![vector3::ZERO, Vector3::new(4.0, 0.0, 0.0)], false);
+		path_follower_components.add(&mut entity_manager, entity, PathFollower::new(spline, 2.0));
+
+		system.update(&Duration::from_secs(1), &mut path_follower_components, &mut transform3d_components);
+		let transform = transform3d_components.borrow(&entity);
+		assert_approx_eq(&transform.position, &Vector3::new(2.0, 0.0, 0.0), 1e-3);
+
+		let mut forward = vector3::UNIT_Z;
+		forward.apply_quaternion(&transform.orientation);
+		assert_approx_eq(&forward, &vector3::UNIT_X, 1e-4);
+
+		// Stops at the end unless looping
+		system.update(&Duration::from_secs(2), &mut path_follower_components, &mut transform3d_components);
+		assert!(path_follower_components.borrow(&entity).finished());
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(4.0, 0.0, 0.0), 1e-3);
+
+		path_follower_components.borrow_mut(&entity).looping = true;
+		system.update(&Duration::from_secs(1), &mut path_follower_components, &mut transform3d_components);
+		assert_approx_eq(&transform3d_components.borrow(&entity).position, &Vector3::new(2.0, 0.0, 0.0), 1e-3);
+		transform3d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
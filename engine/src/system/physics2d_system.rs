@@ -0,0 +1,94 @@
+use crate::{Entity, component::{Collider2D, ComponentList, Shape2D, Transform2DComponentList}, math::Vector2};
+
+pub struct Contact2D {
+	pub entity_a: Entity,
+	pub entity_b: Entity,
+	pub normal: Vector2,
+	pub depth: f32
+}
+
+pub struct Physics2DSystem;
+
+impl Physics2DSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn find_contacts(&self, collider_components: &ComponentList<Collider2D>, transform_components: &Transform2DComponentList) -> Vec<Contact2D> {
+		let bodies = Self::gather_bodies(collider_components, transform_components);
+		let mut contacts = Vec::new();
+
+		for (i, (entity_a, collider_a, shape_a, position_a)) in bodies.iter().enumerate() {
+			for (entity_b, collider_b, shape_b, position_b) in &bodies[i + 1..] {
+				if collider_a.is_static && collider_b.is_static {
+					continue;
+				}
+
+				if let Some((normal, depth)) = shape_a.overlap(position_a, shape_b, position_b) {
+					contacts.push(Contact2D {
+						entity_a: *entity_a,
+						entity_b: *entity_b,
+						normal,
+						depth
+					});
+				}
+			}
+		}
+
+		contacts
+	}
+
+	pub fn query_point(&self, point: &Vector2, collider_components: &ComponentList<Collider2D>, transform_components: &Transform2DComponentList) -> Vec<Entity> {
+		Self::gather_bodies(collider_components, transform_components).into_iter()
+			.filter(|(_, _, shape, position)| shape.contains_point(position, point))
+			.map(|(entity, _, _, _)| entity)
+			.collect()
+	}
+
+	pub fn query_overlaps(&self, shape: &Shape2D, position: &Vector2, collider_components: &ComponentList<Collider2D>, transform_components: &Transform2DComponentList) -> Vec<Entity> {
+		Self::gather_bodies(collider_components, transform_components).into_iter()
+			.filter(|(_, _, other_shape, other_position)| shape.overlap(position, other_shape, other_position).is_some())
+			.map(|(entity, _, _, _)| entity)
+			.collect()
+	}
+
+	pub fn resolve(&self, collider_components: &ComponentList<Collider2D>, transform_components: &mut Transform2DComponentList) -> Vec<Contact2D> {
+		let contacts = self.find_contacts(collider_components, transform_components);
+
+		for contact in &contacts {
+			let a_static = collider_components.borrow(&contact.entity_a).is_static;
+			let b_static = collider_components.borrow(&contact.entity_b).is_static;
+
+			// Split the correction between both colliders unless one of them can't move
+			let (a_share, b_share) = match (a_static, b_static) {
+				(true, false) => (0.0, 1.0),
+				(false, true) => (1.0, 0.0),
+				_ => (0.5, 0.5)
+			};
+
+			let correction = contact.normal * contact.depth;
+
+			if a_share > 0.0 {
+				transform_components.borrow_mut(&contact.entity_a).position -= correction * a_share;
+				transform_components.update(&contact.entity_a);
+			}
+
+			if b_share > 0.0 {
+				transform_components.borrow_mut(&contact.entity_b).position += correction * b_share;
+				transform_components.update(&contact.entity_b);
+			}
+		}
+
+		contacts
+	}
+
+	fn gather_bodies<'a>(collider_components: &'a ComponentList<Collider2D>, transform_components: &Transform2DComponentList) -> Vec<(Entity, &'a Collider2D, Shape2D, Vector2)> {
+		collider_components.iter().map(|(entity, collider)| {
+			let transform = transform_components.borrow(entity);
+			let shape = collider.scaled_shape(&transform.scale);
+			let position = transform.position + collider.offset * transform.scale;
+
+			(*entity, collider, shape, position)
+		}).collect()
+	}
+}
\ No newline at end of file
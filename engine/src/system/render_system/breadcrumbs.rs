@@ -0,0 +1,140 @@
+use ash::vk;
+use crate::vulkan::{Buffer, Context, MemoryCategory};
+
+// The passes of the primary command buffer in the order they're recorded, a pass which is disabled for a frame is still marked
+// so the pass after the last marked one is always the one which didn't finish
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum BreadcrumbPass {
+	GeometryUploads = 1,
+	Ssao,
+	DeferredGeometry,
+	MotionVectors,
+	ParticleSimulation,
+	Reflection,
+	Scene,
+	Refraction,
+	Taa,
+	Overlay,
+	FrameCapture
+}
+
+const PASSES: [BreadcrumbPass; 11] = [
+	BreadcrumbPass::GeometryUploads,
+	BreadcrumbPass::Ssao,
+	BreadcrumbPass::DeferredGeometry,
+	BreadcrumbPass::MotionVectors,
+	BreadcrumbPass::ParticleSimulation,
+	BreadcrumbPass::Reflection,
+	BreadcrumbPass::Scene,
+	BreadcrumbPass::Refraction,
+	BreadcrumbPass::Taa,
+	BreadcrumbPass::Overlay,
+	BreadcrumbPass::FrameCapture
+];
+
+// A frame number and the last pass to finish
+const SLOT_SIZE: vk::DeviceSize = 8;
+
+// Each in flight frame writes the frame number it's rendering and then a marker after each of its passes finishes into a
+// host visible buffer. The buffer stays mapped so it can still be read once the device is lost, which tells which pass the
+// GPU was in when it hung or crashed.
+pub(super) struct Breadcrumbs {
+	buffer: Buffer,
+	buffer_ptr: *const u32,
+	in_flight_frames_count: usize
+}
+
+impl Breadcrumbs {
+	pub fn new(context: &Context, in_flight_frames_count: usize) -> Self {
+		let buffer = Buffer::new(
+			context,
+			SLOT_SIZE * in_flight_frames_count as vk::DeviceSize,
+			vk::BufferUsageFlags::TRANSFER_DST,
+			vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+			MemoryCategory::Other);
+
+		let buffer_ptr = unsafe {
+			let buffer_ptr = context.logical_device.map_memory(buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+			(buffer_ptr as *mut u32).write_bytes(0, in_flight_frames_count * 2);
+			buffer_ptr as *const u32
+		};
+
+		Self {
+			buffer,
+			buffer_ptr,
+			in_flight_frames_count
+		}
+	}
+
+	// Only frame numbers up to 2^32 are told apart which is over two years at 60 frames per second
+	pub unsafe fn record_frame_start(&self, logical_device: &ash::Device, command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, frame_number: u64) {
+		let offset = in_flight_frame_index as vk::DeviceSize * SLOT_SIZE;
+		logical_device.cmd_fill_buffer(command_buffer, self.buffer.handle, offset, 4, frame_number as u32);
+		logical_device.cmd_fill_buffer(command_buffer, self.buffer.handle, offset + 4, 4, 0);
+	}
+
+	// Has to be recorded outside of a render pass
+	pub unsafe fn record_pass_finished(&self, logical_device: &ash::Device, command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, pass: BreadcrumbPass) {
+		let memory_barrier = vk::MemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+			.dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+
+		logical_device.cmd_pipeline_barrier(
+			command_buffer,
+			vk::PipelineStageFlags::ALL_COMMANDS,
+			vk::PipelineStageFlags::TRANSFER,
+			vk::DependencyFlags::empty(),
+			&[memory_barrier.build()],
+			&[],
+			&[]);
+
+		let offset = in_flight_frame_index as vk::DeviceSize * SLOT_SIZE + 4;
+		logical_device.cmd_fill_buffer(command_buffer, self.buffer.handle, offset, 4, pass as u32);
+	}
+
+	// Prints the furthest each in flight frame got, the GPU was working on the pass after the last finished one
+	pub fn log(&self) {
+		println!("GPU breadcrumbs:");
+
+		for in_flight_frame_index in 0..self.in_flight_frames_count {
+			let (frame_number, marker) = unsafe {
+				let slot_ptr = self.buffer_ptr.add(in_flight_frame_index * 2);
+				(slot_ptr.read_volatile(), slot_ptr.add(1).read_volatile())
+			};
+
+			if frame_number == 0 {
+				println!("  In flight frame {} never started", in_flight_frame_index);
+				continue;
+			}
+
+			println!("  In flight frame {} was rendering frame {}, {}", in_flight_frame_index, frame_number, describe_marker(marker));
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe { logical_device.unmap_memory(self.buffer.memory) };
+		self.buffer.drop(logical_device);
+	}
+}
+
+fn describe_marker(marker: u32) -> String {
+	match marker as usize {
+		0 => format!("no pass finished so it stopped in {:?}", PASSES[0]),
+		marker if marker == PASSES.len() => String::from("every pass finished"),
+		marker if marker < PASSES.len() => format!("the last pass to finish was {:?} so it stopped in {:?}", PASSES[marker - 1], PASSES[marker]),
+		_ => format!("the marker {} is not a pass", marker)
+	}
+}
+
+// Logs the breadcrumbs when there are any and then gives up, a lost device can't be recovered from without recreating
+// everything
+pub(super) fn device_failed(breadcrumbs: &Option<Breadcrumbs>, action: &str, error: vk::Result) -> ! {
+	if let Some(breadcrumbs) = breadcrumbs {
+		breadcrumbs.log();
+	}
+	else if error == vk::Result::ERROR_DEVICE_LOST {
+		println!("Device lost, enable breadcrumbs in the render system settings to find which pass it happened in");
+	}
+
+	panic!("Could not {}: {}", action, error);
+}
\ No newline at end of file
@@ -1,28 +1,48 @@
-use std::{mem::{MaybeUninit, transmute}, cmp::{min, max}};
-use ash::{vk, version::DeviceV1_0, version::InstanceV1_0, extensions::khr};
-use crate::vulkan::{Context, Buffer};
-use super::{Swapchain, DepthImageResources, SwapchainFrame, InFlightFrame, InstanceDataResources, IN_FLIGHT_FRAMES_COUNT, FRAME_DATA_MEMORY_SIZE, MAX_FONTS};
+use std::cmp::{min, max};
+use ash::{vk, extensions::khr};
+use crate::vulkan::{Context, Buffer, MemoryCategory};
+use super::{Swapchain, SceneTarget, SsaoRenderSystem, MotionVectorRenderSystem, TaaRenderSystem, DeferredRenderSystem, RefractionRenderSystem, ReflectionRenderSystem, ImageResources, SwapchainFrame, InFlightFrame, InstanceDataResources, FRAME_DATA_MEMORY_SIZE};
+
+// The continuation render pass is compatible with the scene render pass and picks up where it left off after the opaque color
+// is copied for refraction, it loads the attachments instead of clearing them
+pub fn create_scene_render_pass(context: &Context, continuation: bool) -> vk::RenderPass {
+	// With separate depth stencil layouts a depth format without a stencil aspect doesn't need a layout for one
+	let depth_format = context.depth_format;
+
+	let depth_attachment_layout = if context.physical_device.separate_depth_stencil_layouts_supported && !depth_format.has_stencil {
+		vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+	}
+	else {
+		vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+	};
+
+	let (load_op, color_initial_layout, depth_initial_layout) = if continuation {
+		(vk::AttachmentLoadOp::LOAD, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, depth_attachment_layout)
+	}
+	else {
+		(vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED, vk::ImageLayout::UNDEFINED)
+	};
 
-pub fn create_render_pass(context: &Context) -> vk::RenderPass {
 	let color_attachment_description = vk::AttachmentDescription::builder()
 		.format(context.surface.format.format)
 		.samples(vk::SampleCountFlags::TYPE_1)
-		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.load_op(load_op)
 		.store_op(vk::AttachmentStoreOp::STORE)
 		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
 		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-		.initial_layout(vk::ImageLayout::UNDEFINED)
-		.final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+		.initial_layout(color_initial_layout)
+		.final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
 
+	// The depth is stored so the continuation render pass can keep testing against it
 	let depth_attachment_description = vk::AttachmentDescription::builder()
-		.format(vk::Format::D32_SFLOAT)
+		.format(depth_format.format)
 		.samples(vk::SampleCountFlags::TYPE_1)
-		.load_op(vk::AttachmentLoadOp::CLEAR)
-		.store_op(vk::AttachmentStoreOp::DONT_CARE)
-		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-		.initial_layout(vk::ImageLayout::UNDEFINED)
-		.final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+		.load_op(load_op)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(if depth_format.has_stencil { load_op } else { vk::AttachmentLoadOp::DONT_CARE })
+		.stencil_store_op(if depth_format.has_stencil { vk::AttachmentStoreOp::STORE } else { vk::AttachmentStoreOp::DONT_CARE })
+		.initial_layout(depth_initial_layout)
+		.final_layout(depth_attachment_layout);
 
 	let attachment_descriptions = [color_attachment_description.build(), depth_attachment_description.build()];
 	
@@ -33,14 +53,92 @@ pub fn create_render_pass(context: &Context) -> vk::RenderPass {
 
 	let depth_attachment_ref = vk::AttachmentReference::builder()
 		.attachment(1)
-		.layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+		.layout(depth_attachment_layout);
 	
+	// The first subpass is the optional depth prepass, the second shades the meshes
+	let depth_prepass_subpass_description = vk::SubpassDescription::builder()
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+		.depth_stencil_attachment(&depth_attachment_ref);
+
 	let subpass_description = vk::SubpassDescription::builder()
 		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
 		.color_attachments(&color_attachment_refs)
 		.depth_stencil_attachment(&depth_attachment_ref);
+	let subpass_descriptions = [depth_prepass_subpass_description.build(), subpass_description.build()];
+
+	// The scene images are shared by the in flight frames so wait for the previous frame to finish with them before writing
+	// to them, then make the color writes visible to this frame's post process pass
+	let begin_depth_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(vk::SUBPASS_EXTERNAL)
+		.dst_subpass(0)
+		.src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+		.src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+		.dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+	let begin_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(vk::SUBPASS_EXTERNAL)
+		.dst_subpass(1)
+		.src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+		.src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+		.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+	let depth_prepass_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(0)
+		.dst_subpass(1)
+		.src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+		.src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+		.dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+	let end_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(1)
+		.dst_subpass(vk::SUBPASS_EXTERNAL)
+		.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+		.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+	let subpass_dependencies = [
+		begin_depth_subpass_dependency.build(),
+		begin_subpass_dependency.build(),
+		depth_prepass_subpass_dependency.build(),
+		end_subpass_dependency.build()
+	];
+	
+	let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+		.attachments(&attachment_descriptions)
+		.subpasses(&subpass_descriptions)
+		.dependencies(&subpass_dependencies);
+	
+	unsafe { context.logical_device.create_render_pass(&render_pass_create_info, None).unwrap() }
+}
+
+pub fn create_overlay_render_pass(context: &Context) -> vk::RenderPass {
+	let color_attachment_description = vk::AttachmentDescription::builder()
+		.format(context.surface.format.format)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+
+	let attachment_descriptions = [color_attachment_description.build()];
+	
+	let color_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(0)
+		.layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+	let color_attachment_refs = [color_attachment_ref.build()];
+	
+	let subpass_description = vk::SubpassDescription::builder()
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+		.color_attachments(&color_attachment_refs);
 	let subpass_descriptions = [subpass_description.build()];
 
+	// The post process pass covers the whole swapchain image so only wait for it to be acquired before writing to it
 	let subpass_dependency = vk::SubpassDependency::builder()
 		.src_subpass(vk::SUBPASS_EXTERNAL)
 		.dst_subpass(0)
@@ -58,10 +156,16 @@ pub fn create_render_pass(context: &Context) -> vk::RenderPass {
 	unsafe { context.logical_device.create_render_pass(&render_pass_create_info, None).unwrap() }
 }
 
-pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, framebuffer_height: u32, render_pass: vk::RenderPass) -> Swapchain {
-	// Get present mode
+pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, framebuffer_height: u32, render_pass: vk::RenderPass, old_swapchain: vk::SwapchainKHR, vsync: bool) -> Swapchain {
+	// Get present mode, without vsync mailbox doesn't tear so it's preferred over immediate
 	let present_modes = unsafe { context.surface.extension.get_physical_device_surface_present_modes(context.physical_device.handle, context.surface.handle).unwrap() };
-	let present_mode_option = present_modes.iter().find(|&&m| m == vk::PresentModeKHR::FIFO);
+	let preferred_present_modes: &[vk::PresentModeKHR] = if vsync {
+		&[vk::PresentModeKHR::FIFO]
+	}
+	else {
+		&[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::FIFO]
+	};
+	let present_mode_option = preferred_present_modes.iter().find(|m| present_modes.contains(m));
 	let present_mode = *present_mode_option.unwrap_or_else(|| &present_modes[0]);
 
 	// Create extent
@@ -82,6 +186,15 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 		image_count = capabilities.max_image_count;
 	}
 
+	// Frames can only be captured if the swapchain images can be copied from
+	let capture_supported = capabilities.supported_usage_flags.contains(vk::ImageUsageFlags::TRANSFER_SRC);
+	let image_usage = if capture_supported {
+		vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+	}
+	else {
+		vk::ImageUsageFlags::COLOR_ATTACHMENT
+	};
+
 	let mut swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
 		.surface(context.surface.handle)
 		.min_image_count(image_count)
@@ -89,11 +202,12 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 		.image_color_space(context.surface.format.color_space)
 		.image_extent(extent)
 		.image_array_layers(1)
-		.image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+		.image_usage(image_usage)
 		.pre_transform(capabilities.current_transform)
 		.composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
 		.present_mode(present_mode)
-		.clipped(true);
+		.clipped(true)
+		.old_swapchain(old_swapchain);
 	
 	let graphics_queue_family_index = context.physical_device.graphics_queue_family;
 	let present_queue_family_index = context.physical_device.present_queue_family;
@@ -111,66 +225,6 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 	let handle = unsafe { extension.create_swapchain(&swapchain_create_info, None).unwrap() };
 	let images = unsafe { extension.get_swapchain_images(handle).unwrap() };
 
-	// Ensure D32_SFLOAT format is supported for depth buffering
-	let required_format = vk::Format::D32_SFLOAT;
-	let format_properties = unsafe { context.instance.get_physical_device_format_properties(context.physical_device.handle, required_format) };
-	let required_format_feature = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT;
-	if format_properties.optimal_tiling_features & required_format_feature != required_format_feature {
-		panic!("Required format for depth buffering not supported");
-	}
-
-	// Create depth image
-	let image_create_info = vk::ImageCreateInfo::builder()
-		.image_type(vk::ImageType::TYPE_2D)
-		.extent(vk::Extent3D::builder()
-			.width(extent.width)
-			.height(extent.height)
-			.depth(1)
-			.build())
-		.mip_levels(1)
-		.array_layers(1)
-		.format(required_format)
-		.tiling(vk::ImageTiling::OPTIMAL)
-		.initial_layout(vk::ImageLayout::UNDEFINED)
-		.usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-		.samples(vk::SampleCountFlags::TYPE_1)
-		.sharing_mode(vk::SharingMode::EXCLUSIVE);
-
-	let depth_image = unsafe { context.logical_device.create_image(&image_create_info, None).unwrap() };
-
-	// Allocate depth image memory and bind it to the image
-	let memory_requirements = unsafe { context.logical_device.get_image_memory_requirements(depth_image) };
-	let memory_type_index = context.physical_device.find_memory_type_index(memory_requirements.memory_type_bits, vk::MemoryPropertyFlags::DEVICE_LOCAL);
-
-	let allocate_info = vk::MemoryAllocateInfo::builder()
-		.allocation_size(memory_requirements.size)
-		.memory_type_index(memory_type_index as u32);
-
-	let depth_image_memory = unsafe { context.logical_device.allocate_memory(&allocate_info, None).unwrap() };
-	unsafe { context.logical_device.bind_image_memory(depth_image, depth_image_memory, 0).unwrap() };
-
-	// Create depth image view
-	let image_view_create_info = vk::ImageViewCreateInfo::builder()
-		.image(depth_image)
-		.view_type(vk::ImageViewType::TYPE_2D)
-		.format(required_format)
-		.subresource_range(vk::ImageSubresourceRange::builder()
-			.aspect_mask(vk::ImageAspectFlags::DEPTH)
-			.base_mip_level(0)
-			.level_count(1)
-			.base_array_layer(0)
-			.layer_count(1)
-			.build());
-	
-	let depth_image_view = unsafe { context.logical_device.create_image_view(&image_view_create_info, None).unwrap() };
-	
-	// Create the container struct
-	let depth_image_resources = DepthImageResources {
-		image: depth_image,
-		image_view: depth_image_view,
-		memory: depth_image_memory
-	};
-
 	// Create swapchain frames
 	let mut frames = Vec::with_capacity(images.len());
 	for image in images {
@@ -196,7 +250,7 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 		let image_view = unsafe { context.logical_device.create_image_view(&image_view_create_info, None).unwrap() };
 
 		// Create framebuffer
-		let attachments = [image_view, depth_image_view];
+		let attachments = [image_view];
 
 		let create_info = vk::FramebufferCreateInfo::builder()
 			.render_pass(render_pass)
@@ -207,13 +261,11 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 		
 		let framebuffer = unsafe { context.logical_device.create_framebuffer(&create_info, None).unwrap() };
 
-		// Create fence
-		let fence = vk::Fence::null();
-
 		frames.push(SwapchainFrame {
+			image,
 			image_view,
 			framebuffer,
-			fence
+			frame_number: 0
 		});
 	}
 
@@ -221,40 +273,218 @@ pub(super) fn create_swapchain(context: &Context, framebuffer_width: u32, frameb
 		extension,
 		handle,
 		extent,
+		frames,
+		capture_supported
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn create_scene_target(context: &Context, extent: vk::Extent2D, render_pass: vk::RenderPass, ssao_resources: &SsaoRenderSystem, motion_vector_resources: &MotionVectorRenderSystem, taa_resources: &TaaRenderSystem, deferred_resources: &DeferredRenderSystem, refraction_resources: &RefractionRenderSystem, reflection_resources: &ReflectionRenderSystem) -> SceneTarget {
+	// Create color and depth images, the color is copied from for refraction
+	let color_image_resources = create_image_resources(
+		context,
+		extent,
+		context.surface.format.format,
+		vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+		vk::ImageAspectFlags::COLOR);
+
+	// The depth image outlives the scene render pass when it's continued after the refraction copy
+	let depth_image_resources = create_image_resources(
+		context,
+		extent,
+		context.depth_format.format,
+		vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+		context.depth_format.aspect_mask());
+
+	// Create framebuffer
+	let attachments = [color_image_resources.image_view, depth_image_resources.image_view];
+
+	let create_info = vk::FramebufferCreateInfo::builder()
+		.render_pass(render_pass)
+		.attachments(&attachments)
+		.width(extent.width)
+		.height(extent.height)
+		.layers(1);
+	
+	let framebuffer = unsafe { context.logical_device.create_framebuffer(&create_info, None).unwrap() };
+
+	// Create ambient occlusion images
+	let ssao_target = ssao_resources.create_target(context, extent);
+
+	// Create velocity image
+	let motion_vector_target = motion_vector_resources.create_target(context, extent);
+
+	// Create temporal anti-aliasing history images
+	let taa_target = taa_resources.create_target(context, extent);
+
+	// Create G-buffer images
+	let deferred_target = deferred_resources.create_target(context, extent);
+
+	// Create the image the opaque color is copied into for refraction
+	let refraction_target = refraction_resources.create_target(context, extent);
+
+	// Create the images the reflection is rendered into
+	let reflection_target = reflection_resources.create_target(context, extent, render_pass);
+
+	SceneTarget {
+		extent,
+		color_image_resources,
 		depth_image_resources,
-		frames
+		framebuffer,
+		ssao_target,
+		motion_vector_target,
+		taa_target,
+		deferred_target,
+		refraction_target,
+		reflection_target
+	}
+}
+
+pub(super) fn create_image_resources(context: &Context, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags, aspect_mask: vk::ImageAspectFlags) -> ImageResources {
+	let image = create_image(context, extent, format, usage);
+	let memory_requirements = unsafe { context.logical_device.get_image_memory_requirements(image) };
+	let (memory, allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::RenderTargets);
+	unsafe { context.logical_device.bind_image_memory(image, memory, 0).unwrap() };
+
+	let image_view = create_image_view(context, image, format, aspect_mask);
+
+	ImageResources {
+		image,
+		image_view,
+		memory,
+		_allocation: Some(allocation)
+	}
+}
+
+// Transient attachments are only used within a render pass so on tiled GPUs with lazily allocated memory they may never
+// be backed by any memory at all, elsewhere they get regular device local memory
+pub(super) fn create_transient_image_resources(context: &Context, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags, aspect_mask: vk::ImageAspectFlags) -> ImageResources {
+	let image = create_image(context, extent, format, usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT);
+	let memory_requirements = unsafe { context.logical_device.get_image_memory_requirements(image) };
+
+	let lazily_allocated = context.physical_device.try_find_memory_type_index(memory_requirements.memory_type_bits, vk::MemoryPropertyFlags::LAZILY_ALLOCATED).is_some();
+	let properties = if lazily_allocated { vk::MemoryPropertyFlags::LAZILY_ALLOCATED } else { vk::MemoryPropertyFlags::DEVICE_LOCAL };
+
+	let (memory, allocation) = context.allocate_memory(&memory_requirements, properties, MemoryCategory::RenderTargets);
+	unsafe { context.logical_device.bind_image_memory(image, memory, 0).unwrap() };
+
+	let image_view = create_image_view(context, image, format, aspect_mask);
+
+	ImageResources {
+		image,
+		image_view,
+		memory,
+		_allocation: Some(allocation)
+	}
+}
+
+// The images share one allocation so they must only be used by passes which don't overlap and each pass must start from
+// an undefined layout. The first image owns the memory, freeing it while the other images still exist is fine as long as
+// they're destroyed without being used again.
+pub(super) fn create_aliased_image_resources(context: &Context, descriptions: &[(vk::Extent2D, vk::Format, vk::ImageUsageFlags, vk::ImageAspectFlags)]) -> Vec<ImageResources> {
+	let images: Vec<vk::Image> = descriptions.iter()
+		.map(|(extent, format, usage, _)| create_image(context, *extent, *format, *usage))
+		.collect();
+
+	let mut memory_requirements = vk::MemoryRequirements::builder()
+		.memory_type_bits(!0)
+		.build();
+
+	for image in &images {
+		let image_memory_requirements = unsafe { context.logical_device.get_image_memory_requirements(*image) };
+		memory_requirements.size = memory_requirements.size.max(image_memory_requirements.size);
+		memory_requirements.alignment = memory_requirements.alignment.max(image_memory_requirements.alignment);
+		memory_requirements.memory_type_bits &= image_memory_requirements.memory_type_bits;
 	}
+
+	let (memory, allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::RenderTargets);
+	let mut allocation = Some(allocation);
+
+	images.iter().zip(descriptions).enumerate().map(|(index, (image, (_, format, _, aspect_mask)))| {
+		unsafe { context.logical_device.bind_image_memory(*image, memory, 0).unwrap() };
+
+		ImageResources {
+			image: *image,
+			image_view: create_image_view(context, *image, *format, *aspect_mask),
+			memory: if index == 0 { memory } else { vk::DeviceMemory::null() },
+			_allocation: allocation.take()
+		}
+	}).collect()
 }
 
-pub fn create_descriptor_pool(context: &Context) -> vk::DescriptorPool {
-	let frames_count = IN_FLIGHT_FRAMES_COUNT as u32;
+fn create_image(context: &Context, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags) -> vk::Image {
+	let image_create_info = vk::ImageCreateInfo::builder()
+		.image_type(vk::ImageType::TYPE_2D)
+		.extent(vk::Extent3D::builder()
+			.width(extent.width)
+			.height(extent.height)
+			.depth(1)
+			.build())
+		.mip_levels(1)
+		.array_layers(1)
+		.format(format)
+		.tiling(vk::ImageTiling::OPTIMAL)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.usage(usage)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+	unsafe { context.logical_device.create_image(&image_create_info, None).unwrap() }
+}
+
+fn create_image_view(context: &Context, image: vk::Image, format: vk::Format, aspect_mask: vk::ImageAspectFlags) -> vk::ImageView {
+	let image_view_create_info = vk::ImageViewCreateInfo::builder()
+		.image(image)
+		.view_type(vk::ImageViewType::TYPE_2D)
+		.format(format)
+		.subresource_range(vk::ImageSubresourceRange::builder()
+			.aspect_mask(aspect_mask)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build());
+	
+	unsafe { context.logical_device.create_image_view(&image_view_create_info, None).unwrap() }
+}
+
+pub fn create_timestamp_query_pool(logical_device: &ash::Device, in_flight_frames_count: usize) -> vk::QueryPool {
+	let create_info = vk::QueryPoolCreateInfo::builder()
+		.query_type(vk::QueryType::TIMESTAMP)
+		.query_count(in_flight_frames_count as u32 * 2);
+
+	unsafe { logical_device.create_query_pool(&create_info, None) }.unwrap()
+}
+
+pub fn create_descriptor_pool(context: &Context, in_flight_frames_count: usize) -> vk::DescriptorPool {
+	let frames_count = in_flight_frames_count as u32;
 
 	let storage_buffer_pool_size = vk::DescriptorPoolSize::builder()
 		.ty(vk::DescriptorType::STORAGE_BUFFER)
-		.descriptor_count(frames_count * 5 + 4);
+		.descriptor_count(frames_count * 21 + 4);
 	
 	let uniform_buffer_pool_size = vk::DescriptorPoolSize::builder()
 		.ty(vk::DescriptorType::UNIFORM_BUFFER)
-		.descriptor_count(frames_count);
+		.descriptor_count(frames_count * 2);
 	
 	let sampler_pool_size = vk::DescriptorPoolSize::builder()
 		.ty(vk::DescriptorType::SAMPLER)
-		.descriptor_count(1);
+		.descriptor_count(2);
 	
-	let sampled_image_pool_size = vk::DescriptorPoolSize::builder()
-		.ty(vk::DescriptorType::SAMPLED_IMAGE)
-		.descriptor_count(MAX_FONTS as u32);
+	let combined_image_sampler_pool_size = vk::DescriptorPoolSize::builder()
+		.ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(frames_count * 16 + 2);
 	
 	let pool_sizes = [
 		storage_buffer_pool_size.build(),
 		uniform_buffer_pool_size.build(),
 		sampler_pool_size.build(),
-		sampled_image_pool_size.build()
+		combined_image_sampler_pool_size.build()
 	];
 	
 	let create_info = vk::DescriptorPoolCreateInfo::builder()
 		.pool_sizes(&pool_sizes)
-		.max_sets(frames_count * 6 + 6);
+		.max_sets(frames_count * 21 + 8);
 	
 	unsafe { context.logical_device.create_descriptor_pool(&create_info, None) }.unwrap()
 }
@@ -267,13 +497,22 @@ pub fn create_command_pool(context: &Context) -> vk::CommandPool {
 	unsafe { context.logical_device.create_command_pool(&create_info, None) }.unwrap()
 }
 
+// The frame data uniform buffer followed by the point lights, cluster ranges and light indices storage buffers
 pub fn create_frame_data_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
-	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+	let frame_data_layout_binding = vk::DescriptorSetLayoutBinding::builder()
 		.binding(0)
 		.descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
 		.descriptor_count(1)
-		.stage_flags(vk::ShaderStageFlags::VERTEX);
-	let layout_bindings = [layout_binding.build()];
+		.stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT);
+
+	let light_data_layout_bindings = (1..4).map(|binding| vk::DescriptorSetLayoutBinding::builder()
+		.binding(binding)
+		.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT)
+		.build());
+
+	let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = std::iter::once(frame_data_layout_binding.build()).chain(light_data_layout_bindings).collect();
 
 	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
 		.bindings(&layout_bindings);
@@ -301,25 +540,23 @@ pub(super) fn create_in_flight_frames(
 	descriptor_pool: vk::DescriptorPool,
 	command_pool: vk::CommandPool,
 	frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
-	instance_data_descriptor_set_layout: vk::DescriptorSetLayout)
-	-> [InFlightFrame; IN_FLIGHT_FRAMES_COUNT]
+	instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
+	in_flight_frames_count: usize)
+	-> Vec<InFlightFrame>
 {
 	let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
 
-	let fence_create_info = vk::FenceCreateInfo::builder()
-		.flags(vk::FenceCreateFlags::SIGNALED);
-
 	let primary_command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
 		.command_pool(command_pool)
 		.level(vk::CommandBufferLevel::PRIMARY)
-		.command_buffer_count(IN_FLIGHT_FRAMES_COUNT as u32);
+		.command_buffer_count(in_flight_frames_count as u32);
 	
 	let primary_command_buffers = unsafe { context.logical_device.allocate_command_buffers(&primary_command_buffer_allocate_info) }.unwrap();
 
 	let secondary_command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
 		.command_pool(command_pool)
 		.level(vk::CommandBufferLevel::SECONDARY)
-		.command_buffer_count(IN_FLIGHT_FRAMES_COUNT as u32 * 5);
+		.command_buffer_count(in_flight_frames_count as u32);
 	
 	let secondary_command_buffers = unsafe { context.logical_device.allocate_command_buffers(&secondary_command_buffer_allocate_info) }.unwrap();
 
@@ -329,6 +566,10 @@ pub(super) fn create_in_flight_frames(
 		instance_data_descriptor_set_layout,
 		instance_data_descriptor_set_layout,
 		instance_data_descriptor_set_layout,
+		instance_data_descriptor_set_layout,
+		instance_data_descriptor_set_layout,
+		instance_data_descriptor_set_layout,
+		instance_data_descriptor_set_layout,
 		instance_data_descriptor_set_layout
 	];
 
@@ -336,21 +577,27 @@ pub(super) fn create_in_flight_frames(
 		.descriptor_pool(descriptor_pool)
 		.set_layouts(&descriptor_set_layouts);
 
-	let mut frames: [MaybeUninit<InFlightFrame>; IN_FLIGHT_FRAMES_COUNT] = unsafe { MaybeUninit::uninit().assume_init() };
+	let mut frames = Vec::with_capacity(in_flight_frames_count);
 	
-	for (index, frame) in frames.iter_mut().enumerate() {
+	for index in 0..in_flight_frames_count {
 		let image_available = unsafe { context.logical_device.create_semaphore(&semaphore_create_info, None) }.unwrap();
 		let render_finished = unsafe { context.logical_device.create_semaphore(&semaphore_create_info, None) }.unwrap();
-		let fence = unsafe { context.logical_device.create_fence(&fence_create_info, None) }.unwrap();
 		let descriptor_sets = unsafe { context.logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap();
 		let frame_data_descriptor_set = descriptor_sets[0];
 		let primary_command_buffer = primary_command_buffers[index];
+		let text_command_buffer = secondary_command_buffers[index];
 
-		let frame_data_buffer = Buffer::new(context, FRAME_DATA_MEMORY_SIZE as u64, vk::BufferUsageFlags::UNIFORM_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE);
+		let frame_data_buffer = Buffer::new(context, FRAME_DATA_MEMORY_SIZE as u64, vk::BufferUsageFlags::UNIFORM_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::InstanceData);
 
 		let instance_data_buffer = Buffer::null(
-			vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
-			vk::MemoryPropertyFlags::HOST_VISIBLE);
+			vk::BufferUsageFlags::STORAGE_BUFFER,
+			vk::MemoryPropertyFlags::HOST_VISIBLE,
+			MemoryCategory::InstanceData);
+
+		let light_data_buffer = Buffer::null(
+			vk::BufferUsageFlags::STORAGE_BUFFER,
+			vk::MemoryPropertyFlags::HOST_VISIBLE,
+			MemoryCategory::InstanceData);
 		
 		let frame_data_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
 			.buffer(frame_data_buffer.handle)
@@ -370,55 +617,79 @@ pub(super) fn create_in_flight_frames(
 
 		let line_instance_data_resources = InstanceDataResources {
 			descriptor_set: descriptor_sets[1],
-			secondary_command_buffer: secondary_command_buffers[5 * index],
 			array_offset: 0,
 			array_size: 0
 		};
 
 		let basic_instance_data_resources = InstanceDataResources {
 			descriptor_set: descriptor_sets[2],
-			secondary_command_buffer: secondary_command_buffers[5 * index + 1],
 			array_offset: 0,
 			array_size: 0
 		};
 
 		let normal_instance_data_resources = InstanceDataResources {
 			descriptor_set: descriptor_sets[3],
-			secondary_command_buffer: secondary_command_buffers[5 * index + 2],
 			array_offset: 0,
 			array_size: 0
 		};
 
 		let lambert_instance_data_resources = InstanceDataResources {
 			descriptor_set: descriptor_sets[4],
-			secondary_command_buffer: secondary_command_buffers[5 * index + 3],
 			array_offset: 0,
 			array_size: 0
 		};
 
-		let text_instance_data_resources = InstanceDataResources {
+		let lightmapped_instance_data_resources = InstanceDataResources {
 			descriptor_set: descriptor_sets[5],
-			secondary_command_buffer: secondary_command_buffers[5 * index + 4],
 			array_offset: 0,
 			array_size: 0
 		};
 
-		*frame = MaybeUninit::new(InFlightFrame {
+		let refractive_instance_data_resources = InstanceDataResources {
+			descriptor_set: descriptor_sets[6],
+			array_offset: 0,
+			array_size: 0
+		};
+
+		let reflective_instance_data_resources = InstanceDataResources {
+			descriptor_set: descriptor_sets[7],
+			array_offset: 0,
+			array_size: 0
+		};
+
+		let foliage_instance_data_resources = InstanceDataResources {
+			descriptor_set: descriptor_sets[8],
+			array_offset: 0,
+			array_size: 0
+		};
+
+		let text_instance_data_resources = InstanceDataResources {
+			descriptor_set: descriptor_sets[9],
+			array_offset: 0,
+			array_size: 0
+		};
+
+		frames.push(InFlightFrame {
 			image_available,
 			render_finished,
-			fence,
 			frame_data_descriptor_set,
 			primary_command_buffer,
 			frame_data_buffer,
 			instance_data_buffer,
+			light_data_buffer,
 			line_instance_data_resources,
 			basic_instance_data_resources,
 			normal_instance_data_resources,
 			lambert_instance_data_resources,
+			lightmapped_instance_data_resources,
+			refractive_instance_data_resources,
+			reflective_instance_data_resources,
+			foliage_instance_data_resources,
 			text_instance_data_resources,
-			index_arrays_offset: 0
+			text_command_buffer,
+			timestamps_written: false
 		});
 	}
 
-	unsafe { transmute::<_, [InFlightFrame; IN_FLIGHT_FRAMES_COUNT]>(frames) }
+	frames
 }
\ No newline at end of file
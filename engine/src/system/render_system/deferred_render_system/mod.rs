@@ -0,0 +1,267 @@
+use std::{mem::size_of_val, slice};
+use ash::vk;
+use crate::{component::mesh::Material, math::Matrix4, vulkan::{Context, DepthFormat}, Vfs};
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+const ALBEDO_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const NORMAL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+// Draws opaque meshes into a G-buffer and lights them all at once in a full screen pass at the start of the scene's shading
+// subpass. Only the materials which don't need anything but an albedo and a normal are drawn this way, the rest stay forward.
+pub struct DeferredRenderSystem {
+	depth_format: DepthFormat,
+	pub geometry_render_pass: vk::RenderPass,
+	g_buffer_descriptor_set_layout: vk::DescriptorSetLayout,
+	pub geometry_pipeline_layout: vk::PipelineLayout,
+	basic_pipeline: vk::Pipeline,
+	normal_pipeline: vk::Pipeline,
+	lambert_pipeline: vk::Pipeline,
+	basic_quantized_pipeline: vk::Pipeline,
+	normal_quantized_pipeline: vk::Pipeline,
+	lambert_quantized_pipeline: vk::Pipeline,
+	lighting_pipeline_layout: vk::PipelineLayout,
+	lighting_pipeline: vk::Pipeline,
+	sampler: vk::Sampler,
+	g_buffer_descriptor_sets: Vec<vk::DescriptorSet>,
+	pub geometry_command_buffers: Vec<vk::CommandBuffer>,
+	lighting_command_buffers: Vec<vk::CommandBuffer>
+}
+
+pub struct DeferredTarget {
+	extent: vk::Extent2D,
+	albedo_image_resources: ImageResources,
+	normal_image_resources: ImageResources,
+	depth_image_resources: ImageResources,
+	pub geometry_framebuffer: vk::Framebuffer
+}
+
+impl DeferredTarget {
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_framebuffer(self.geometry_framebuffer, None);
+			self.albedo_image_resources.drop(logical_device);
+			self.normal_image_resources.drop(logical_device);
+			self.depth_image_resources.drop(logical_device);
+		}
+	}
+}
+
+impl DeferredRenderSystem {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		context: &Context,
+		vfs: &Vfs,
+		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		ambient_occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+		scene_render_pass: vk::RenderPass,
+		descriptor_pool: vk::DescriptorPool,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
+		let logical_device = &context.logical_device;
+		let depth_format = context.sampled_depth_format;
+		let geometry_render_pass = create_geometry_render_pass(logical_device, depth_format);
+		let g_buffer_descriptor_set_layout = create_g_buffer_descriptor_set_layout(logical_device);
+
+		let geometry_pipeline_layout = create_pipeline_layout(logical_device, &[frame_data_descriptor_set_layout, instance_data_descriptor_set_layout], 0);
+		let create_geometry_pipeline = |material: Material, quantized: bool| create_geometry_pipeline(logical_device, vfs, geometry_pipeline_layout, geometry_render_pass, material, quantized);
+
+		// The lighting pass gets the inverse projection matrix and the camera's matrix as push constants to find the world
+		// position of each pixel
+		let lighting_pipeline_layout = create_pipeline_layout(
+			logical_device,
+			&[frame_data_descriptor_set_layout, g_buffer_descriptor_set_layout, ambient_occlusion_descriptor_set_layout],
+			2 * 16 * 4);
+
+		Self {
+			depth_format,
+			geometry_render_pass,
+			g_buffer_descriptor_set_layout,
+			geometry_pipeline_layout,
+			basic_pipeline: create_geometry_pipeline(Material::Basic, false),
+			normal_pipeline: create_geometry_pipeline(Material::Normal, false),
+			lambert_pipeline: create_geometry_pipeline(Material::Lambert, false),
+			basic_quantized_pipeline: create_geometry_pipeline(Material::Basic, true),
+			normal_quantized_pipeline: create_geometry_pipeline(Material::Normal, true),
+			lambert_quantized_pipeline: create_geometry_pipeline(Material::Lambert, true),
+			lighting_pipeline_layout,
+			lighting_pipeline: create_lighting_pipeline(logical_device, vfs, lighting_pipeline_layout, scene_render_pass),
+			sampler: create_sampler(logical_device),
+			g_buffer_descriptor_sets: create_descriptor_sets(logical_device, g_buffer_descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			geometry_command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count),
+			lighting_command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count)
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D) -> DeferredTarget {
+		create_target(context, extent, self.depth_format, self.geometry_render_pass)
+	}
+
+	// The G-buffer pipeline of the material, lines, lightmapped, reflective and foliage meshes are always drawn forward
+	pub fn geometry_pipeline(&self, material: Material, quantized: bool) -> Option<vk::Pipeline> {
+		match (material, quantized) {
+			(Material::Basic, false) => Some(self.basic_pipeline),
+			(Material::Normal, false) => Some(self.normal_pipeline),
+			(Material::Lambert, false) => Some(self.lambert_pipeline),
+			(Material::Basic, true) => Some(self.basic_quantized_pipeline),
+			(Material::Normal, true) => Some(self.normal_quantized_pipeline),
+			(Material::Lambert, true) => Some(self.lambert_quantized_pipeline),
+			(Material::Line, _) | (Material::Lightmapped, _) | (Material::Refractive, _) | (Material::Reflective, _) | (Material::Foliage, _) => None
+		}
+	}
+
+	// Points the G-buffer descriptor set at the current target and records the lighting command buffer, it's executed in the
+	// scene render pass's shading subpass before the render layers
+	#[allow(clippy::too_many_arguments)]
+	pub fn record_lighting_command_buffer(
+		&self,
+		logical_device: &ash::Device,
+		in_flight_frame_index: usize,
+		target: &DeferredTarget,
+		scene_render_pass: vk::RenderPass,
+		scene_framebuffer: vk::Framebuffer,
+		frame_data_descriptor_set: vk::DescriptorSet,
+		ambient_occlusion_descriptor_set: vk::DescriptorSet,
+		projection_matrix: &Matrix4,
+		camera_matrix: &Matrix4)
+		-> vk::CommandBuffer
+	{
+		let g_buffer_descriptor_set = self.g_buffer_descriptor_sets[in_flight_frame_index];
+
+		let image_info = |image_view: vk::ImageView, image_layout: vk::ImageLayout| [
+			vk::DescriptorImageInfo::builder()
+				.image_layout(image_layout)
+				.image_view(image_view)
+				.sampler(self.sampler)
+				.build()
+		];
+
+		let albedo_image_infos = image_info(target.albedo_image_resources.image_view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+		let normal_image_infos = image_info(target.normal_image_resources.image_view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+		let depth_image_infos = image_info(target.depth_image_resources.image_view, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+
+		let write_descriptor_set = |binding: u32, image_infos: &[vk::DescriptorImageInfo]| {
+			vk::WriteDescriptorSet::builder()
+				.dst_set(g_buffer_descriptor_set)
+				.dst_binding(binding)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(image_infos)
+				.build()
+		};
+
+		let write_descriptor_sets = [
+			write_descriptor_set(0, &albedo_image_infos),
+			write_descriptor_set(1, &normal_image_infos),
+			write_descriptor_set(2, &depth_image_infos)
+		];
+
+		unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+
+		let mut inverse_projection_matrix = *projection_matrix;
+		inverse_projection_matrix.invert();
+		let push_constants = [inverse_projection_matrix.elements, camera_matrix.elements];
+		let push_constants_bytes = unsafe { slice::from_raw_parts(push_constants.as_ptr() as *const u8, size_of_val(&push_constants)) };
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(target.extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(target.extent.width as f32)
+			.height(target.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		let command_buffer = self.lighting_command_buffers[in_flight_frame_index];
+
+		let command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(scene_render_pass)
+			.subpass(1)
+			.framebuffer(scene_framebuffer);
+
+		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&command_buffer_inheritance_info);
+
+		unsafe {
+			logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.lighting_pipeline);
+			logical_device.cmd_set_viewport(command_buffer, 0, &[viewport.build()]);
+			logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+			logical_device.cmd_bind_descriptor_sets(
+				command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.lighting_pipeline_layout,
+				0,
+				&[frame_data_descriptor_set, g_buffer_descriptor_set, ambient_occlusion_descriptor_set],
+				&[]);
+			logical_device.cmd_push_constants(command_buffer, self.lighting_pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, push_constants_bytes);
+			logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+			logical_device.end_command_buffer(command_buffer).unwrap();
+		}
+
+		command_buffer
+	}
+
+	// Records the G-buffer pass into the primary command buffer, the geometry command buffer must already be recorded
+	pub fn record_geometry_pass(&self, logical_device: &ash::Device, primary_command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, target: &DeferredTarget) {
+		let clear_values = [
+			vk::ClearValue {
+				color: vk::ClearColorValue {
+					float32: [0.0, 0.0, 0.0, 0.0]
+				}
+			},
+			vk::ClearValue {
+				color: vk::ClearColorValue {
+					float32: [0.0, 0.0, 0.0, 0.0]
+				}
+			},
+			vk::ClearValue {
+				depth_stencil: vk::ClearDepthStencilValue {
+					depth: 1.0,
+					stencil: 0
+				}
+			}
+		];
+
+		let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.geometry_render_pass)
+			.framebuffer(target.geometry_framebuffer)
+			.render_area(vk::Rect2D::builder()
+				.offset(vk::Offset2D::builder().x(0).y(0).build())
+				.extent(target.extent)
+				.build())
+			.clear_values(&clear_values);
+
+		unsafe {
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(primary_command_buffer, &[self.geometry_command_buffers[in_flight_frame_index]]);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_pipeline(self.lighting_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_pipeline, None);
+			logical_device.destroy_pipeline_layout(self.lighting_pipeline_layout, None);
+			logical_device.destroy_pipeline_layout(self.geometry_pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.g_buffer_descriptor_set_layout, None);
+			logical_device.destroy_render_pass(self.geometry_render_pass, None);
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,90 @@
+const MIN_RENDER_SCALE: f32 = 0.5;
+const MAX_RENDER_SCALE: f32 = 1.0;
+const RENDER_SCALE_STEPS_PER_UNIT: f32 = 10.0;
+const RENDER_SCALE_STEP: f32 = 1.0 / RENDER_SCALE_STEPS_PER_UNIT;
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+const COOLDOWN_FRAMES: usize = 30;
+const UPSCALE_HEADROOM: f32 = 0.8;
+
+pub struct DynamicResolution {
+	pub target_frame_time: f32,
+	smoothed_frame_time: Option<f32>,
+	cooldown: usize
+}
+
+impl DynamicResolution {
+	pub fn new(target_frame_time: f32) -> Self {
+		Self {
+			target_frame_time,
+			smoothed_frame_time: None,
+			cooldown: COOLDOWN_FRAMES
+		}
+	}
+
+	// Takes the GPU time of the latest frame in seconds and returns a new render scale if it should change
+	pub fn update(&mut self, gpu_frame_time: f32, render_scale: f32) -> Option<f32> {
+		let smoothed_frame_time = match self.smoothed_frame_time {
+			Some(smoothed_frame_time) => smoothed_frame_time + (gpu_frame_time - smoothed_frame_time) * FRAME_TIME_SMOOTHING,
+			None => gpu_frame_time
+		};
+
+		self.smoothed_frame_time = Some(smoothed_frame_time);
+
+		if self.cooldown > 0 {
+			self.cooldown -= 1;
+			return None;
+		}
+
+		// The GPU time scales roughly with the pixel count which is the square of the render scale
+		let new_render_scale = if smoothed_frame_time > self.target_frame_time {
+			(render_scale - RENDER_SCALE_STEP).max(MIN_RENDER_SCALE)
+		}
+		else if smoothed_frame_time * ((render_scale + RENDER_SCALE_STEP) / render_scale).powi(2) < self.target_frame_time * UPSCALE_HEADROOM {
+			(render_scale + RENDER_SCALE_STEP).min(MAX_RENDER_SCALE)
+		}
+		else {
+			render_scale
+		};
+
+		// Snap to the step so repeated steps don't accumulate rounding error
+		let new_render_scale = (new_render_scale * RENDER_SCALE_STEPS_PER_UNIT).round() / RENDER_SCALE_STEPS_PER_UNIT;
+
+		if (new_render_scale - render_scale).abs() < f32::EPSILON {
+			return None;
+		}
+
+		// Give the new resolution some frames to settle before judging it
+		self.cooldown = COOLDOWN_FRAMES;
+		self.smoothed_frame_time = None;
+		Some(new_render_scale)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(dynamic_resolution: &mut DynamicResolution, gpu_frame_time: f32, render_scale: f32) -> Option<f32> {
+		(0..=COOLDOWN_FRAMES).find_map(|_| dynamic_resolution.update(gpu_frame_time, render_scale))
+	}
+
+	#[test]
+	fn scales_down_when_over_budget() {
+		let mut dynamic_resolution = DynamicResolution::new(1.0 / 60.0);
+		assert_eq!(run(&mut dynamic_resolution, 1.0 / 30.0, 1.0), Some(0.9));
+		assert_eq!(run(&mut dynamic_resolution, 1.0 / 30.0, MIN_RENDER_SCALE), None);
+	}
+
+	#[test]
+	fn scales_up_when_under_budget() {
+		let mut dynamic_resolution = DynamicResolution::new(1.0 / 60.0);
+		assert_eq!(run(&mut dynamic_resolution, 1.0 / 240.0, 0.5), Some(0.6));
+		assert_eq!(run(&mut dynamic_resolution, 1.0 / 240.0, MAX_RENDER_SCALE), None);
+	}
+
+	#[test]
+	fn holds_when_near_budget() {
+		let mut dynamic_resolution = DynamicResolution::new(1.0 / 60.0);
+		assert_eq!(run(&mut dynamic_resolution, 1.0 / 65.0, 0.8), None);
+	}
+}
\ No newline at end of file
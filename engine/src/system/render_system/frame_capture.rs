@@ -0,0 +1,162 @@
+use std::ptr::copy_nonoverlapping;
+use ash::vk;
+use crate::vulkan::{Buffer, Context, MemoryCategory};
+
+// A frame read back from the swapchain, the pixels are tightly packed RGBA rows starting at the top
+pub struct FrameCapture {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<u8>
+}
+
+// Once requested the swapchain image of the next frame rendered is copied into a host visible buffer
+pub(super) struct FrameCapturer {
+	buffer: Buffer,
+	requested: bool,
+	recorded_extent: Option<vk::Extent2D>
+}
+
+impl FrameCapturer {
+	pub fn new() -> Self {
+		Self {
+			buffer: Buffer::null(vk::BufferUsageFlags::TRANSFER_DST, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Other),
+			requested: false,
+			recorded_extent: None
+		}
+	}
+
+	pub fn request(&mut self) {
+		self.requested = true;
+	}
+
+	// Records the copy after the overlay render pass has left the image ready to present and puts it back the same way
+	pub fn record(&mut self, context: &Context, command_buffer: vk::CommandBuffer, image: vk::Image, extent: vk::Extent2D) {
+		if !self.requested {
+			return;
+		}
+
+		self.requested = false;
+		self.recorded_extent = Some(extent);
+
+		let size = extent.width as u64 * extent.height as u64 * 4;
+
+		if size > self.buffer.capacity {
+			self.buffer.reallocate(context, size);
+		}
+
+		let subresource_range = vk::ImageSubresourceRange::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build();
+
+		let to_transfer_barrier = vk::ImageMemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+			.dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+			.old_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+			.new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.image(image)
+			.subresource_range(subresource_range);
+
+		let region = vk::BufferImageCopy::builder()
+			.buffer_offset(0)
+			.buffer_row_length(0)
+			.buffer_image_height(0)
+			.image_subresource(vk::ImageSubresourceLayers::builder()
+				.aspect_mask(vk::ImageAspectFlags::COLOR)
+				.mip_level(0)
+				.base_array_layer(0)
+				.layer_count(1)
+				.build())
+			.image_offset(vk::Offset3D::default())
+			.image_extent(vk::Extent3D::builder().width(extent.width).height(extent.height).depth(1).build());
+
+		let to_present_barrier = vk::ImageMemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::TRANSFER_READ)
+			.dst_access_mask(vk::AccessFlags::empty())
+			.old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+			.new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.image(image)
+			.subresource_range(subresource_range);
+
+		// Make the copy visible to the host once the command buffer's fence or a wait idle says it's done
+		let host_barrier = vk::BufferMemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+			.dst_access_mask(vk::AccessFlags::HOST_READ)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.buffer(self.buffer.handle)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		let logical_device = &context.logical_device;
+
+		unsafe {
+			logical_device.cmd_pipeline_barrier(
+				command_buffer,
+				vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&[to_transfer_barrier.build()]);
+
+			logical_device.cmd_copy_image_to_buffer(command_buffer, image, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, self.buffer.handle, &[region.build()]);
+
+			logical_device.cmd_pipeline_barrier(
+				command_buffer,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::PipelineStageFlags::BOTTOM_OF_PIPE | vk::PipelineStageFlags::HOST,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[host_barrier.build()],
+				&[to_present_barrier.build()]);
+		}
+	}
+
+	// Waits for the frame the copy was recorded in to finish and reads it back, swapping the red and blue channels of BGRA
+	// swapchain formats
+	pub fn take(&mut self, context: &Context, bgra: bool) -> Option<FrameCapture> {
+		let extent = self.recorded_extent.take()?;
+		let logical_device = &context.logical_device;
+		let size = extent.width as usize * extent.height as usize * 4;
+		let mut pixels = vec![0; size];
+
+		unsafe {
+			context.wait_idle();
+
+			let buffer_ptr = logical_device.map_memory(self.buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+
+			let range = vk::MappedMemoryRange::builder()
+				.memory(self.buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			logical_device.invalidate_mapped_memory_ranges(&[range.build()]).unwrap();
+			copy_nonoverlapping(buffer_ptr as *const u8, pixels.as_mut_ptr(), size);
+			logical_device.unmap_memory(self.buffer.memory);
+		}
+
+		if bgra {
+			for pixel in pixels.chunks_exact_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+
+		Some(FrameCapture {
+			width: extent.width,
+			height: extent.height,
+			pixels
+		})
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		self.buffer.drop(logical_device);
+	}
+}
\ No newline at end of file
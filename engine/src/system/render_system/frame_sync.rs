@@ -0,0 +1,175 @@
+use std::fmt;
+use ash::vk;
+use crate::vulkan::Context;
+
+#[derive(Debug)]
+pub enum WaitError {
+	Device(vk::Result),
+	// The frame number is ahead of anything submitted which is a bug in the caller, there is nothing on the GPU to wait for
+	NotSubmitted { frame_number: u64 }
+}
+
+impl fmt::Display for WaitError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Device(e) => write!(f, "{}", e),
+			Self::NotSubmitted { frame_number } => write!(f, "frame {} hasn't been submitted", frame_number)
+		}
+	}
+}
+
+// Frames are numbered from 1 in submission order and the GPU signals a frame's number once it's finished with it, 0 is
+// never signaled and is used for frames that don't need to be waited on. A timeline semaphore holds the latest finished
+// frame number when it's supported, otherwise there is a fence per in flight frame.
+pub struct FrameSync {
+	timeline_semaphore: Option<vk::Semaphore>,
+	fences: Vec<vk::Fence>,
+	fence_frame_numbers: Vec<u64>
+}
+
+impl FrameSync {
+	pub fn new(context: &Context, in_flight_frames_count: usize) -> Self {
+		let logical_device = &context.logical_device;
+
+		if context.physical_device.timeline_semaphores_supported {
+			let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo::builder()
+				.semaphore_type(vk::SemaphoreType::TIMELINE)
+				.initial_value(0);
+
+			let semaphore_create_info = vk::SemaphoreCreateInfo::builder()
+				.push_next(&mut semaphore_type_create_info);
+
+			let timeline_semaphore = unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }.unwrap();
+
+			Self {
+				timeline_semaphore: Some(timeline_semaphore),
+				fences: vec![],
+				fence_frame_numbers: vec![]
+			}
+		}
+		else {
+			println!("Timeline semaphores not supported, falling back to fences");
+
+			let fence_create_info = vk::FenceCreateInfo::builder()
+				.flags(vk::FenceCreateFlags::SIGNALED);
+
+			let fences = (0..in_flight_frames_count)
+				.map(|_| unsafe { logical_device.create_fence(&fence_create_info, None) }.unwrap())
+				.collect();
+
+			Self {
+				timeline_semaphore: None,
+				fences,
+				fence_frame_numbers: vec![0; in_flight_frames_count]
+			}
+		}
+	}
+
+	// Fails when the device is lost or the frame hasn't been submitted yet
+	pub fn wait(&self, logical_device: &ash::Device, frame_number: u64) -> Result<(), WaitError> {
+		if frame_number == 0 {
+			return Ok(());
+		}
+
+		match self.timeline_semaphore {
+			Some(timeline_semaphore) => {
+				let semaphores = [timeline_semaphore];
+				let values = [frame_number];
+
+				let semaphore_wait_info = vk::SemaphoreWaitInfo::builder()
+					.semaphores(&semaphores)
+					.values(&values);
+
+				unsafe { logical_device.wait_semaphores(&semaphore_wait_info, u64::MAX) }.map_err(WaitError::Device)
+			},
+			None => {
+				// The fence may have since been reused by a later frame in which case this waits longer than it needs to
+				let fence_index = self.fence_index(frame_number);
+				if self.fence_frame_numbers[fence_index] < frame_number {
+					return Err(WaitError::NotSubmitted { frame_number });
+				}
+
+				unsafe { logical_device.wait_for_fences(&[self.fences[fence_index]], true, u64::MAX) }.map_err(WaitError::Device)
+			}
+		}
+	}
+
+	// Submits the frame's command buffers which signal the frame number and the render finished semaphore when they're done,
+	// fails when the device is lost
+	pub fn submit(
+		&mut self,
+		context: &Context,
+		frame_number: u64,
+		wait_semaphore: vk::Semaphore,
+		wait_stage: vk::PipelineStageFlags,
+		command_buffer: vk::CommandBuffer,
+		render_finished_semaphore: vk::Semaphore)
+		-> Result<(), vk::Result>
+	{
+		assert!(frame_number > 0, "Frame numbers start at 1");
+		let logical_device = &context.logical_device;
+
+		let wait_semaphores = [wait_semaphore];
+		let wait_stages = [wait_stage];
+		let command_buffers = [command_buffer];
+
+		match self.timeline_semaphore {
+			Some(timeline_semaphore) => {
+				// Binary semaphores ignore their values
+				let signal_semaphores = [render_finished_semaphore, timeline_semaphore];
+				let wait_values = [0];
+				let signal_values = [0, frame_number];
+
+				let mut timeline_semaphore_submit_info = vk::TimelineSemaphoreSubmitInfo::builder()
+					.wait_semaphore_values(&wait_values)
+					.signal_semaphore_values(&signal_values);
+
+				let submit_info = vk::SubmitInfo::builder()
+					.wait_semaphores(&wait_semaphores)
+					.wait_dst_stage_mask(&wait_stages)
+					.command_buffers(&command_buffers)
+					.signal_semaphores(&signal_semaphores)
+					.push_next(&mut timeline_semaphore_submit_info);
+
+				context.submit(&[submit_info.build()], vk::Fence::null())
+			},
+			None => {
+				let fence_index = self.fence_index(frame_number);
+				let fence = self.fences[fence_index];
+				let signal_semaphores = [render_finished_semaphore];
+
+				let submit_info = vk::SubmitInfo::builder()
+					.wait_semaphores(&wait_semaphores)
+					.wait_dst_stage_mask(&wait_stages)
+					.command_buffers(&command_buffers)
+					.signal_semaphores(&signal_semaphores);
+
+				unsafe {
+					logical_device.wait_for_fences(&[fence], true, u64::MAX)?;
+					logical_device.reset_fences(&[fence])?;
+				}
+
+				context.submit(&[submit_info.build()], fence)?;
+
+				self.fence_frame_numbers[fence_index] = frame_number;
+				Ok(())
+			}
+		}
+	}
+
+	fn fence_index(&self, frame_number: u64) -> usize {
+		(frame_number - 1) as usize % self.fences.len()
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			if let Some(timeline_semaphore) = self.timeline_semaphore {
+				logical_device.destroy_semaphore(timeline_semaphore, None);
+			}
+
+			for fence in &self.fences {
+				logical_device.destroy_fence(*fence, None);
+			}
+		}
+	}
+}
\ No newline at end of file
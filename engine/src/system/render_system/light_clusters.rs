@@ -0,0 +1,216 @@
+use crate::math::{Matrix4, Vector3, Vector4};
+
+pub const CLUSTER_GRID_WIDTH: usize = 16;
+pub const CLUSTER_GRID_HEIGHT: usize = 9;
+pub const CLUSTER_GRID_DEPTH: usize = 24;
+pub const CLUSTER_COUNT: usize = CLUSTER_GRID_WIDTH * CLUSTER_GRID_HEIGHT * CLUSTER_GRID_DEPTH;
+const MIN_NEAR: f32 = 0.01;
+
+// Laid out like the point light struct in the lambert shader
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PointLightData {
+	pub position: Vector3,
+	pub range: f32,
+	pub color: Vector3,
+	pub falloff: u32
+}
+
+// The view frustum is divided into a grid of clusters, evenly across the screen and exponentially in depth so clusters
+// far from the camera aren't much longer than they are wide. Each cluster gets a list of the lights whose range reaches
+// it so a fragment only loops over the lights in its cluster. The slice a view space depth falls in is
+// floor(ln(depth) * depth_scale - depth_bias) which the fragment shader computes the same way.
+pub struct LightClusters {
+	pub depth_scale: f32,
+	pub depth_bias: f32,
+	// The offset into the light indices and the number of lights for every cluster
+	pub cluster_ranges: Vec<[u32; 2]>,
+	pub light_indices: Vec<u32>,
+	near: f32,
+	far: f32
+}
+
+impl LightClusters {
+	pub fn new() -> Self {
+		Self {
+			depth_scale: 0.0,
+			depth_bias: 0.0,
+			cluster_ranges: vec![[0, 0]; CLUSTER_COUNT],
+			light_indices: Vec::new(),
+			near: MIN_NEAR,
+			far: 1.0
+		}
+	}
+
+	// Takes the view space position and range of each light, lights outside the frustum end up in no cluster
+	pub fn update(&mut self, projection_matrix: &Matrix4, lights: &[(Vector3, f32)]) {
+		self.update_depth_slices(projection_matrix);
+
+		// Find the range of clusters each light reaches
+		let mut light_cluster_bounds: Vec<(u32, [usize; 6])> = Vec::with_capacity(lights.len());
+
+		for (light_index, (center, range)) in lights.iter().enumerate() {
+			if let Some(bounds) = self.cluster_bounds(projection_matrix, center, *range) {
+				light_cluster_bounds.push((light_index as u32, bounds));
+			}
+		}
+
+		// Count the lights in each cluster then lay the lists out one after another
+		let mut counts = vec![0; CLUSTER_COUNT];
+
+		for (_, bounds) in &light_cluster_bounds {
+			for_each_cluster(bounds, |cluster_index| counts[cluster_index] += 1);
+		}
+
+		let mut offset = 0;
+
+		for (cluster_range, count) in self.cluster_ranges.iter_mut().zip(&counts) {
+			*cluster_range = [offset, 0];
+			offset += count;
+		}
+
+		self.light_indices.clear();
+		self.light_indices.resize(offset as usize, 0);
+
+		for (light_index, bounds) in &light_cluster_bounds {
+			let cluster_ranges = &mut self.cluster_ranges;
+			let light_indices = &mut self.light_indices;
+
+			for_each_cluster(bounds, |cluster_index| {
+				let [offset, count] = &mut cluster_ranges[cluster_index];
+				light_indices[(*offset + *count) as usize] = *light_index;
+				*count += 1;
+			});
+		}
+	}
+
+	// The near and far planes are found by unprojecting the center of the near and far planes in clip space
+	fn update_depth_slices(&mut self, projection_matrix: &Matrix4) {
+		let mut inverse_projection_matrix = *projection_matrix;
+		inverse_projection_matrix.invert();
+
+		let near = inverse_projection_matrix * Vector4::new(0.0, 0.0, 0.0, 1.0);
+		let far = inverse_projection_matrix * Vector4::new(0.0, 0.0, 1.0, 1.0);
+
+		self.near = (near.z / near.w).max(MIN_NEAR);
+		self.far = (far.z / far.w).max(self.near * 2.0);
+
+		let log_depth_ratio = (self.far / self.near).ln();
+		self.depth_scale = CLUSTER_GRID_DEPTH as f32 / log_depth_ratio;
+		self.depth_bias = CLUSTER_GRID_DEPTH as f32 * self.near.ln() / log_depth_ratio;
+	}
+
+	fn depth_slice(&self, depth: f32) -> usize {
+		let slice = (depth.max(self.near).ln() * self.depth_scale - self.depth_bias).floor();
+		(slice.max(0.0) as usize).min(CLUSTER_GRID_DEPTH - 1)
+	}
+
+	// Projects the corners of the part of the light's bounding box in front of the near plane to find the tiles it covers
+	fn cluster_bounds(&self, projection_matrix: &Matrix4, center: &Vector3, range: f32) -> Option<[usize; 6]> {
+		let min_depth = (center.z - range).max(self.near);
+		let max_depth = (center.z + range).min(self.far);
+
+		if min_depth > max_depth {
+			return None;
+		}
+
+		let min_slice = self.depth_slice(min_depth);
+		let max_slice = self.depth_slice(max_depth);
+
+		if !range.is_finite() {
+			return Some([0, CLUSTER_GRID_WIDTH - 1, 0, CLUSTER_GRID_HEIGHT - 1, min_slice, max_slice]);
+		}
+
+		let mut min_ndc = (f32::MAX, f32::MAX);
+		let mut max_ndc = (f32::MIN, f32::MIN);
+
+		for x in [center.x - range, center.x + range] {
+			for y in [center.y - range, center.y + range] {
+				for z in [min_depth, max_depth] {
+					let clip = projection_matrix * Vector4::new(x, y, z, 1.0);
+					let ndc = (clip.x / clip.w, clip.y / clip.w);
+					min_ndc = (min_ndc.0.min(ndc.0), min_ndc.1.min(ndc.1));
+					max_ndc = (max_ndc.0.max(ndc.0), max_ndc.1.max(ndc.1));
+				}
+			}
+		}
+
+		if max_ndc.0 < -1.0 || min_ndc.0 > 1.0 || max_ndc.1 < -1.0 || min_ndc.1 > 1.0 {
+			return None;
+		}
+
+		Some([
+			tile(min_ndc.0, CLUSTER_GRID_WIDTH),
+			tile(max_ndc.0, CLUSTER_GRID_WIDTH),
+			tile(min_ndc.1, CLUSTER_GRID_HEIGHT),
+			tile(max_ndc.1, CLUSTER_GRID_HEIGHT),
+			min_slice,
+			max_slice
+		])
+	}
+}
+
+fn tile(ndc: f32, tile_count: usize) -> usize {
+	let tile = ((ndc * 0.5 + 0.5) * tile_count as f32).floor();
+	(tile.max(0.0) as usize).min(tile_count - 1)
+}
+
+fn for_each_cluster(bounds: &[usize; 6], mut f: impl FnMut(usize)) {
+	let [min_x, max_x, min_y, max_y, min_z, max_z] = *bounds;
+
+	for z in min_z..=max_z {
+		for y in min_y..=max_y {
+			for x in min_x..=max_x {
+				f((z * CLUSTER_GRID_HEIGHT + y) * CLUSTER_GRID_WIDTH + x);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::matrix4;
+
+	fn projection_matrix() -> Matrix4 {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_perspective(16.0 / 9.0, 75.0, 0.1, 50.0);
+		projection_matrix
+	}
+
+	fn clusters_with_light(light_clusters: &LightClusters, light_index: u32) -> usize {
+		light_clusters.cluster_ranges.iter()
+			.filter(|[offset, count]| light_clusters.light_indices[*offset as usize..(offset + count) as usize].contains(&light_index))
+			.count()
+	}
+
+	#[test]
+	fn depth_slices_span_near_to_far() {
+		let mut light_clusters = LightClusters::new();
+		light_clusters.update(&projection_matrix(), &[]);
+
+		assert_eq!(light_clusters.depth_slice(0.1), 0);
+		assert_eq!(light_clusters.depth_slice(0.11), 0);
+		assert_eq!(light_clusters.depth_slice(49.0), CLUSTER_GRID_DEPTH - 1);
+		assert_eq!(light_clusters.depth_slice(1000.0), CLUSTER_GRID_DEPTH - 1);
+		assert!(light_clusters.depth_slice(1.0) < light_clusters.depth_slice(10.0));
+	}
+
+	#[test]
+	fn lights_are_assigned_to_the_clusters_they_reach() {
+		let mut light_clusters = LightClusters::new();
+		let lights = [
+			(Vector3::new(0.0, 0.0, 5.0), 1.0),
+			(Vector3::new(0.0, 0.0, -5.0), 1.0),
+			(Vector3::new(0.0, 0.0, 10.0), f32::INFINITY)
+		];
+
+		light_clusters.update(&projection_matrix(), &lights);
+
+		let small_light_cluster_count = clusters_with_light(&light_clusters, 0);
+		assert!(small_light_cluster_count > 0 && small_light_cluster_count < CLUSTER_COUNT / 4);
+		assert_eq!(clusters_with_light(&light_clusters, 1), 0);
+		assert_eq!(clusters_with_light(&light_clusters, 2), CLUSTER_COUNT);
+		assert_eq!(light_clusters.light_indices.len(), small_light_cluster_count + CLUSTER_COUNT);
+	}
+}
\ No newline at end of file
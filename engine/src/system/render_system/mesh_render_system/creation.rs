@@ -1,14 +1,40 @@
-use std::ffi::CString;
-use ash::{vk, version::DeviceV1_0};
-use super::super::create_shader_module;
+use std::{ffi::CString, mem::{size_of, size_of_val}, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{Lightmap, vulkan::{Buffer, Context, MemoryCategory}, Vfs};
+use super::super::{create_shader_module, ImageResources};
+
+pub fn create_lightmap_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+	let layout_bindings = [layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
 
 pub fn create_pipeline_layout(
 	logical_device: &ash::Device,
 	frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
-	instance_data_descriptor_set_layout: vk::DescriptorSetLayout)
+	instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
+	ambient_occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+	lightmap_descriptor_set_layout: vk::DescriptorSetLayout,
+	refraction_descriptor_set_layout: vk::DescriptorSetLayout,
+	reflection_descriptor_set_layout: vk::DescriptorSetLayout)
 	-> vk::PipelineLayout
 {
-	let descriptor_set_layouts = [frame_data_descriptor_set_layout, instance_data_descriptor_set_layout];
+	let descriptor_set_layouts = [
+		frame_data_descriptor_set_layout,
+		instance_data_descriptor_set_layout,
+		ambient_occlusion_descriptor_set_layout,
+		lightmap_descriptor_set_layout,
+		refraction_descriptor_set_layout,
+		reflection_descriptor_set_layout
+	];
 
 	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
 		.set_layouts(&descriptor_set_layouts);
@@ -16,7 +42,7 @@ pub fn create_pipeline_layout(
 	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
 }
 
-pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass) -> Vec<vk::Pipeline> {
+pub fn create_pipelines(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass) -> Vec<vk::Pipeline> {
 	// Shared
 	let entry_point = CString::new("main").unwrap();
 	let entry_point_cstr = entry_point.as_c_str();
@@ -45,23 +71,13 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
 		.primitive_restart_enable(false);
 
-	let viewport = vk::Viewport::builder()
-		.x(0.0)
-		.y(0.0)
-		.width(extent.width as f32)
-		.height(extent.height as f32)
-		.min_depth(0.0)
-		.max_depth(1.0);
-	let viewports = [viewport.build()];
-
-	let scissor = vk::Rect2D::builder()
-		.offset(vk::Offset2D::builder().x(0).y(0).build())
-		.extent(extent);
-	let scissors = [scissor.build()];
-
 	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
-		.viewports(&viewports)
-		.scissors(&scissors);
+		.viewport_count(1)
+		.scissor_count(1);
+
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
 
 	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
 		.depth_clamp_enable(false)
@@ -84,7 +100,7 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.stencil_test_enable(false);
 
 	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
-		.color_write_mask(vk::ColorComponentFlags::all())
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
 		.blend_enable(false);
 	let color_blend_attachment_states = [color_blend_attachment_state.build()];
 
@@ -92,14 +108,14 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.logic_op_enable(false)
 		.attachments(&color_blend_attachment_states);
 	
-	// Line
-	let line_vert_module = create_shader_module(logical_device, "basic.vert.spv");
+	// Line, the colors are a separate vertex buffer and geometry without any is drawn with a buffer of dark gray
+	let line_vert_module = create_shader_module(logical_device, vfs, "line.vert.spv");
 	let line_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::VERTEX)
 		.module(line_vert_module)
 		.name(entry_point_cstr);
 	
-	let line_frag_module = create_shader_module(logical_device, "basic.frag.spv");
+	let line_frag_module = create_shader_module(logical_device, vfs, "basic.frag.spv");
 	let line_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::FRAGMENT)
 		.module(line_frag_module)
@@ -111,9 +127,20 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.binding(0)
 		.stride(12)
 		.input_rate(vk::VertexInputRate::VERTEX);
-	let line_input_binding_descriptions = [line_input_binding_description.build()];
+	let line_color_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(1)
+		.stride(16)
+		.input_rate(vk::VertexInputRate::VERTEX);
+	let line_input_binding_descriptions = [line_input_binding_description.build(), line_color_input_binding_description.build()];
 
-	let line_input_attribute_descriptions = [input_attribute_description_position];
+	let line_color_input_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(1)
+		.location(1)
+		.format(vk::Format::R32G32B32A32_SFLOAT)
+		.offset(0)
+		.build();
+
+	let line_input_attribute_descriptions = [input_attribute_description_position, line_color_input_attribute_description];
 
 	let line_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
 		.vertex_binding_descriptions(&line_input_binding_descriptions)
@@ -141,18 +168,19 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.multisample_state(&multisample_state_create_info)
 		.depth_stencil_state(&depth_stencil_state_create_info)
 		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
 		.layout(pipeline_layout)
 		.render_pass(render_pass)
-		.subpass(0);
+		.subpass(1);
 
 	// Basic
-	let basic_vert_module = create_shader_module(logical_device, "basic.vert.spv");
+	let basic_vert_module = create_shader_module(logical_device, vfs, "basic.vert.spv");
 	let basic_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::VERTEX)
 		.module(basic_vert_module)
 		.name(entry_point_cstr);
 	
-	let basic_frag_module = create_shader_module(logical_device, "basic.frag.spv");
+	let basic_frag_module = create_shader_module(logical_device, vfs, "basic.frag.spv");
 	let basic_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::FRAGMENT)
 		.module(basic_frag_module)
@@ -174,18 +202,19 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.multisample_state(&multisample_state_create_info)
 		.depth_stencil_state(&depth_stencil_state_create_info)
 		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
 		.layout(pipeline_layout)
 		.render_pass(render_pass)
-		.subpass(0);
+		.subpass(1);
 	
 	// Normal
-	let normal_vert_module = create_shader_module(logical_device, "normal.vert.spv");
+	let normal_vert_module = create_shader_module(logical_device, vfs, "normal.vert.spv");
 	let normal_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::VERTEX)
 		.module(normal_vert_module)
 		.name(entry_point_cstr);
 
-	let normal_frag_module =  create_shader_module(logical_device, "normal.frag.spv");
+	let normal_frag_module =  create_shader_module(logical_device, vfs, "normal.frag.spv");
 	let normal_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::FRAGMENT)
 		.module(normal_frag_module)
@@ -207,18 +236,19 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.multisample_state(&multisample_state_create_info)
 		.depth_stencil_state(&depth_stencil_state_create_info)
 		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
 		.layout(pipeline_layout)
 		.render_pass(render_pass)
-		.subpass(0);
+		.subpass(1);
 	
 	// Lambert
-	let lambert_vert_module = create_shader_module(logical_device, "lambert.vert.spv");
+	let lambert_vert_module = create_shader_module(logical_device, vfs, "lambert.vert.spv");
 	let lambert_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::VERTEX)
 		.module(lambert_vert_module)
 		.name(entry_point_cstr);
 
-	let lambert_frag_module =  create_shader_module(logical_device, "lambert.frag.spv");
+	let lambert_frag_module =  create_shader_module(logical_device, vfs, "lambert.frag.spv");
 	let lambert_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::FRAGMENT)
 		.module(lambert_frag_module)
@@ -231,25 +261,680 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 		.vertex_binding_descriptions(&input_binding_descriptions)
 		.vertex_attribute_descriptions(&lambert_input_attribute_descriptions);
 
+	// Lambert reads the baked occlusion from a separate vertex buffer, geometry without any is drawn with a buffer of ones
+	let occlusion_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(1)
+		.stride(4)
+		.input_rate(vk::VertexInputRate::VERTEX)
+		.build();
+
+	let occlusion_input_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(1)
+		.location(2)
+		.format(vk::Format::R32_SFLOAT)
+		.offset(0)
+		.build();
+
+	let occluded_input_binding_descriptions = [input_binding_descriptions[0], occlusion_input_binding_description];
+	let occluded_input_attribute_descriptions = [input_attribute_description_position, input_attribute_description_normal, occlusion_input_attribute_description];
+
+	let occluded_vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&occluded_input_binding_descriptions)
+		.vertex_attribute_descriptions(&occluded_input_attribute_descriptions);
+
 	let lambert_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
 		.stages(&lambert_stage_create_infos)
-		.vertex_input_state(&lambert_vert_input_state_create_info)
+		.vertex_input_state(&occluded_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+	
+	// Depth equal variants used when the depth prepass has already written the depth, lines are not drawn in the prepass so
+	// they don't have one
+	let depth_equal_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(true)
+		.depth_write_enable(false)
+		.depth_compare_op(vk::CompareOp::EQUAL)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let basic_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&basic_stage_create_infos)
+		.vertex_input_state(&basic_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let normal_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&normal_stage_create_infos)
+		.vertex_input_state(&normal_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lambert_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lambert_stage_create_infos)
+		.vertex_input_state(&occluded_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	// Depth prepass variants only run the vertex shaders, they're the same shaders as the shaded pipelines so the depths match exactly
+	let basic_depth_prepass_stage_create_infos = [basic_stage_create_infos[0]];
+	let normal_depth_prepass_stage_create_infos = [normal_stage_create_infos[0]];
+	let lambert_depth_prepass_stage_create_infos = [lambert_stage_create_infos[0]];
+	let depth_prepass_color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false);
+
+	let basic_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&basic_depth_prepass_stage_create_infos)
+		.vertex_input_state(&basic_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let normal_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&normal_depth_prepass_stage_create_infos)
+		.vertex_input_state(&normal_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let lambert_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lambert_depth_prepass_stage_create_infos)
+		.vertex_input_state(&occluded_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	// Quantized variants of the triangle pipelines, the vertex shaders decode the normal when the specialization constant is set
+	let quantized_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(0)
+		.stride(12)
+		.input_rate(vk::VertexInputRate::VERTEX);
+	let quantized_input_binding_descriptions = [quantized_input_binding_description.build()];
+
+	let quantized_input_attribute_description_position = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(0)
+		.format(vk::Format::R16G16B16A16_SNORM)
+		.offset(0)
+		.build();
+
+	let quantized_input_attribute_description_normal = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(1)
+		.format(vk::Format::R16G16_SNORM)
+		.offset(8)
+		.build();
+
+	let basic_quantized_input_attribute_descriptions = [quantized_input_attribute_description_position];
+
+	let basic_quantized_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&quantized_input_binding_descriptions)
+		.vertex_attribute_descriptions(&basic_quantized_input_attribute_descriptions);
+
+	let quantized_input_attribute_descriptions = [quantized_input_attribute_description_position, quantized_input_attribute_description_normal];
+
+	let quantized_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&quantized_input_binding_descriptions)
+		.vertex_attribute_descriptions(&quantized_input_attribute_descriptions);
+
+	let occluded_quantized_input_binding_descriptions = [quantized_input_binding_descriptions[0], occlusion_input_binding_description];
+	let occluded_quantized_input_attribute_descriptions = [quantized_input_attribute_description_position, quantized_input_attribute_description_normal, occlusion_input_attribute_description];
+
+	let occluded_quantized_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&occluded_quantized_input_binding_descriptions)
+		.vertex_attribute_descriptions(&occluded_quantized_input_attribute_descriptions);
+
+	let quantized_specialization_map_entry = vk::SpecializationMapEntry::builder()
+		.constant_id(0)
+		.offset(0)
+		.size(size_of::<vk::Bool32>());
+	let quantized_specialization_map_entries = [quantized_specialization_map_entry.build()];
+
+	let quantized_specialization_data = vk::TRUE.to_ne_bytes();
+	let quantized_specialization_info = vk::SpecializationInfo::builder()
+		.map_entries(&quantized_specialization_map_entries)
+		.data(&quantized_specialization_data);
+
+	let normal_quantized_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(normal_vert_module)
+		.name(entry_point_cstr)
+		.specialization_info(&quantized_specialization_info);
+
+	let lambert_quantized_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(lambert_vert_module)
+		.name(entry_point_cstr)
+		.specialization_info(&quantized_specialization_info);
+
+	let normal_quantized_stage_create_infos = [normal_quantized_vert_stage_create_info.build(), normal_stage_create_infos[1]];
+	let lambert_quantized_stage_create_infos = [lambert_quantized_vert_stage_create_info.build(), lambert_stage_create_infos[1]];
+	let normal_quantized_depth_prepass_stage_create_infos = [normal_quantized_stage_create_infos[0]];
+	let lambert_quantized_depth_prepass_stage_create_infos = [lambert_quantized_stage_create_infos[0]];
+
+	let basic_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&basic_stage_create_infos)
+		.vertex_input_state(&basic_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let normal_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&normal_quantized_stage_create_infos)
+		.vertex_input_state(&quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lambert_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lambert_quantized_stage_create_infos)
+		.vertex_input_state(&occluded_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let basic_quantized_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&basic_stage_create_infos)
+		.vertex_input_state(&basic_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let normal_quantized_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&normal_quantized_stage_create_infos)
+		.vertex_input_state(&quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lambert_quantized_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lambert_quantized_stage_create_infos)
+		.vertex_input_state(&occluded_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let basic_quantized_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&basic_depth_prepass_stage_create_infos)
+		.vertex_input_state(&basic_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let normal_quantized_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&normal_quantized_depth_prepass_stage_create_infos)
+		.vertex_input_state(&quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let lambert_quantized_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lambert_quantized_depth_prepass_stage_create_infos)
+		.vertex_input_state(&occluded_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	// Lightmapped, the second UV set is a separate vertex buffer after the attributes so it has a binding of its own. The
+	// quantized variants only differ in the position format since there's no normal to decode.
+	let lightmapped_vert_module = create_shader_module(logical_device, vfs, "lightmapped.vert.spv");
+	let lightmapped_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(lightmapped_vert_module)
+		.name(entry_point_cstr);
+
+	let lightmapped_frag_module = create_shader_module(logical_device, vfs, "lightmapped.frag.spv");
+	let lightmapped_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(lightmapped_frag_module)
+		.name(entry_point_cstr);
+
+	let lightmapped_stage_create_infos = [lightmapped_vert_stage_create_info.build(), lightmapped_frag_stage_create_info.build()];
+	let lightmapped_depth_prepass_stage_create_infos = [lightmapped_stage_create_infos[0]];
+
+	let lightmap_uv_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(1)
+		.stride(8)
+		.input_rate(vk::VertexInputRate::VERTEX)
+		.build();
+
+	let lightmap_uv_input_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(1)
+		.location(1)
+		.format(vk::Format::R32G32_SFLOAT)
+		.offset(0)
+		.build();
+
+	let lightmapped_input_binding_descriptions = [input_binding_descriptions[0], lightmap_uv_input_binding_description];
+	let lightmapped_input_attribute_descriptions = [input_attribute_description_position, lightmap_uv_input_attribute_description];
+
+	let lightmapped_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&lightmapped_input_binding_descriptions)
+		.vertex_attribute_descriptions(&lightmapped_input_attribute_descriptions);
+
+	let lightmapped_quantized_input_binding_descriptions = [quantized_input_binding_descriptions[0], lightmap_uv_input_binding_description];
+	let lightmapped_quantized_input_attribute_descriptions = [quantized_input_attribute_description_position, lightmap_uv_input_attribute_description];
+
+	let lightmapped_quantized_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&lightmapped_quantized_input_binding_descriptions)
+		.vertex_attribute_descriptions(&lightmapped_quantized_input_attribute_descriptions);
+
+	let lightmapped_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_stage_create_infos)
+		.vertex_input_state(&lightmapped_vertex_input_state_create_info)
 		.input_assembly_state(&input_assembly_state_create_info)
 		.viewport_state(&viewport_state_create_info)
 		.rasterization_state(&rasterization_state_create_info)
 		.multisample_state(&multisample_state_create_info)
 		.depth_stencil_state(&depth_stencil_state_create_info)
 		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lightmapped_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_stage_create_infos)
+		.vertex_input_state(&lightmapped_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lightmapped_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_depth_prepass_stage_create_infos)
+		.vertex_input_state(&lightmapped_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let lightmapped_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_stage_create_infos)
+		.vertex_input_state(&lightmapped_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lightmapped_quantized_depth_equal_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_stage_create_infos)
+		.vertex_input_state(&lightmapped_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_equal_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let lightmapped_quantized_depth_prepass_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&lightmapped_depth_prepass_stage_create_infos)
+		.vertex_input_state(&lightmapped_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&depth_prepass_color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
 		.layout(pipeline_layout)
 		.render_pass(render_pass)
 		.subpass(0);
+
+	// Refractive, drawn after the copy of the color buffer is taken so it doesn't write the depth like other transparent
+	// surfaces. It replaces the color behind it with the distorted copy so it isn't blended.
+	let refractive_vert_module = create_shader_module(logical_device, vfs, "refractive.vert.spv");
+	let refractive_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(refractive_vert_module)
+		.name(entry_point_cstr);
+
+	let refractive_frag_module = create_shader_module(logical_device, vfs, "refractive.frag.spv");
+	let refractive_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(refractive_frag_module)
+		.name(entry_point_cstr);
+
+	let refractive_quantized_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(refractive_vert_module)
+		.name(entry_point_cstr)
+		.specialization_info(&quantized_specialization_info);
+
+	let refractive_stage_create_infos = [refractive_vert_stage_create_info.build(), refractive_frag_stage_create_info.build()];
+	let refractive_quantized_stage_create_infos = [refractive_quantized_vert_stage_create_info.build(), refractive_stage_create_infos[1]];
+
+	let refractive_depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(true)
+		.depth_write_enable(false)
+		.depth_compare_op(vk::CompareOp::LESS)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let refractive_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&refractive_stage_create_infos)
+		.vertex_input_state(&lambert_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&refractive_depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let refractive_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&refractive_quantized_stage_create_infos)
+		.vertex_input_state(&quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&refractive_depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	// Reflective, opaque but shaded with the reflection rendered before the scene instead of being lit. It reuses the refractive
+	// vertex shader and leaves the normals it passes on unused.
+	let reflective_frag_module = create_shader_module(logical_device, vfs, "reflective.frag.spv");
+	let reflective_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(reflective_frag_module)
+		.name(entry_point_cstr);
+
+	let reflective_stage_create_infos = [refractive_stage_create_infos[0], reflective_frag_stage_create_info.build()];
+	let reflective_quantized_stage_create_infos = [refractive_quantized_stage_create_infos[0], reflective_stage_create_infos[1]];
+
+	let reflective_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&reflective_stage_create_infos)
+		.vertex_input_state(&lambert_vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let reflective_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&reflective_quantized_stage_create_infos)
+		.vertex_input_state(&quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	// Foliage, the wind masks in the colors are a separate vertex buffer like the lightmap UVs. Leaves are usually cards seen
+	// from both sides so nothing is culled and it's left out of the depth prepass since the vertices move every frame.
+	let foliage_vert_module = create_shader_module(logical_device, vfs, "foliage.vert.spv");
+	let foliage_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(foliage_vert_module)
+		.name(entry_point_cstr);
+
+	let foliage_frag_module = create_shader_module(logical_device, vfs, "foliage.frag.spv");
+	let foliage_frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(foliage_frag_module)
+		.name(entry_point_cstr);
+
+	let foliage_quantized_vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(foliage_vert_module)
+		.name(entry_point_cstr)
+		.specialization_info(&quantized_specialization_info);
+
+	let foliage_stage_create_infos = [foliage_vert_stage_create_info.build(), foliage_frag_stage_create_info.build()];
+	let foliage_quantized_stage_create_infos = [foliage_quantized_vert_stage_create_info.build(), foliage_stage_create_infos[1]];
+
+	let color_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(1)
+		.stride(16)
+		.input_rate(vk::VertexInputRate::VERTEX)
+		.build();
+
+	let color_input_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(1)
+		.location(2)
+		.format(vk::Format::R32G32B32A32_SFLOAT)
+		.offset(0)
+		.build();
+
+	let foliage_input_binding_descriptions = [input_binding_descriptions[0], color_input_binding_description];
+	let foliage_input_attribute_descriptions = [input_attribute_description_position, input_attribute_description_normal, color_input_attribute_description];
+
+	let foliage_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&foliage_input_binding_descriptions)
+		.vertex_attribute_descriptions(&foliage_input_attribute_descriptions);
+
+	let foliage_quantized_input_binding_descriptions = [quantized_input_binding_descriptions[0], color_input_binding_description];
+	let foliage_quantized_input_attribute_descriptions = [quantized_input_attribute_description_position, quantized_input_attribute_description_normal, color_input_attribute_description];
+
+	let foliage_quantized_vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&foliage_quantized_input_binding_descriptions)
+		.vertex_attribute_descriptions(&foliage_quantized_input_attribute_descriptions);
+
+	let foliage_rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::NONE)
+		.front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let foliage_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&foliage_stage_create_infos)
+		.vertex_input_state(&foliage_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&foliage_rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let foliage_quantized_pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&foliage_quantized_stage_create_infos)
+		.vertex_input_state(&foliage_quantized_vertex_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&foliage_rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
 	
 	// Create pipelines
 	let pipeline_create_infos = [
 		line_pipeline_create_info.build(),
 		basic_pipeline_create_info.build(),
 		normal_pipeline_create_info.build(),
-		lambert_pipeline_create_info.build()];
+		lambert_pipeline_create_info.build(),
+		basic_depth_equal_pipeline_create_info.build(),
+		normal_depth_equal_pipeline_create_info.build(),
+		lambert_depth_equal_pipeline_create_info.build(),
+		basic_depth_prepass_pipeline_create_info.build(),
+		normal_depth_prepass_pipeline_create_info.build(),
+		lambert_depth_prepass_pipeline_create_info.build(),
+		basic_quantized_pipeline_create_info.build(),
+		normal_quantized_pipeline_create_info.build(),
+		lambert_quantized_pipeline_create_info.build(),
+		basic_quantized_depth_equal_pipeline_create_info.build(),
+		normal_quantized_depth_equal_pipeline_create_info.build(),
+		lambert_quantized_depth_equal_pipeline_create_info.build(),
+		basic_quantized_depth_prepass_pipeline_create_info.build(),
+		normal_quantized_depth_prepass_pipeline_create_info.build(),
+		lambert_quantized_depth_prepass_pipeline_create_info.build(),
+		lightmapped_pipeline_create_info.build(),
+		lightmapped_depth_equal_pipeline_create_info.build(),
+		lightmapped_depth_prepass_pipeline_create_info.build(),
+		lightmapped_quantized_pipeline_create_info.build(),
+		lightmapped_quantized_depth_equal_pipeline_create_info.build(),
+		lightmapped_quantized_depth_prepass_pipeline_create_info.build(),
+		refractive_pipeline_create_info.build(),
+		refractive_quantized_pipeline_create_info.build(),
+		reflective_pipeline_create_info.build(),
+		reflective_quantized_pipeline_create_info.build(),
+		foliage_pipeline_create_info.build(),
+		foliage_quantized_pipeline_create_info.build()];
 	
 	let pipelines = unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_create_infos, None) }.unwrap();
 
@@ -266,6 +951,16 @@ pub fn create_pipelines(logical_device: &ash::Device, extent: vk::Extent2D, pipe
 
 		logical_device.destroy_shader_module(lambert_vert_module, None);
 		logical_device.destroy_shader_module(lambert_frag_module, None);
+
+		logical_device.destroy_shader_module(lightmapped_vert_module, None);
+		logical_device.destroy_shader_module(lightmapped_frag_module, None);
+
+		logical_device.destroy_shader_module(refractive_vert_module, None);
+		logical_device.destroy_shader_module(refractive_frag_module, None);
+		logical_device.destroy_shader_module(reflective_frag_module, None);
+
+		logical_device.destroy_shader_module(foliage_vert_module, None);
+		logical_device.destroy_shader_module(foliage_frag_module, None);
 	}
 
 	pipelines
@@ -278,4 +973,220 @@ pub fn create_static_descriptor_sets(logical_device: &ash::Device, descriptor_po
 		.set_layouts(&descriptor_set_layouts);
 	
 	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub fn create_depth_prepass_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
+
+pub fn create_lightmap_descriptor_set(logical_device: &ash::Device, descriptor_pool: vk::DescriptorPool, lightmap_descriptor_set_layout: vk::DescriptorSetLayout) -> vk::DescriptorSet {
+	let descriptor_set_layouts = [lightmap_descriptor_set_layout];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()[0]
+}
+
+pub fn create_lightmap_sampler(logical_device: &ash::Device) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(vk::Filter::LINEAR)
+		.min_filter(vk::Filter::LINEAR)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
+
+pub fn update_lightmap_descriptor_set(logical_device: &ash::Device, descriptor_set: vk::DescriptorSet, sampler: vk::Sampler, lightmap: &ImageResources) {
+	let descriptor_image_info = vk::DescriptorImageInfo::builder()
+		.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+		.image_view(lightmap.image_view)
+		.sampler(sampler);
+	let descriptor_image_infos = [descriptor_image_info.build()];
+
+	let write_descriptor_set = vk::WriteDescriptorSet::builder()
+		.dst_set(descriptor_set)
+		.dst_binding(0)
+		.dst_array_element(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.image_info(&descriptor_image_infos);
+
+	unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set.build()], &[]) };
+}
+
+pub fn upload_lightmap(context: &Context, lightmap: &Lightmap) -> ImageResources {
+	let logical_device = &context.logical_device;
+	let extent = vk::Extent3D::builder().width(lightmap.width as u32).height(lightmap.height as u32).depth(1).build();
+	let format = vk::Format::R8G8B8A8_SRGB;
+
+	// Create image
+	let image_create_info = vk::ImageCreateInfo::builder()
+		.image_type(vk::ImageType::TYPE_2D)
+		.extent(extent)
+		.mip_levels(1)
+		.array_layers(1)
+		.format(format)
+		.tiling(vk::ImageTiling::OPTIMAL)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+		.sharing_mode(vk::SharingMode::EXCLUSIVE)
+		.samples(vk::SampleCountFlags::TYPE_1);
+
+	let image = unsafe { logical_device.create_image(&image_create_info, None) }.unwrap();
+
+	// Allocate device local memory and bind it to the image
+	let memory_requirements = unsafe { logical_device.get_image_memory_requirements(image) };
+	let (memory, allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::Textures);
+	unsafe { logical_device.bind_image_memory(image, memory, 0) }.unwrap();
+
+	// Create image view
+	let subresource_range = vk::ImageSubresourceRange::builder()
+		.aspect_mask(vk::ImageAspectFlags::COLOR)
+		.base_mip_level(0)
+		.level_count(1)
+		.base_array_layer(0)
+		.layer_count(1)
+		.build();
+
+	let image_view_create_info = vk::ImageViewCreateInfo::builder()
+		.image(image)
+		.view_type(vk::ImageViewType::TYPE_2D)
+		.format(format)
+		.subresource_range(subresource_range);
+
+	let image_view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }.unwrap();
+
+	// Copy the texels into a staging buffer
+	let pixels = lightmap.to_rgba8();
+	let staging_buffer = Buffer::new(context, pixels.len() as u64, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging);
+	let staging_buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+	let range = vk::MappedMemoryRange::builder()
+		.memory(staging_buffer.memory)
+		.offset(0)
+		.size(vk::WHOLE_SIZE);
+
+	unsafe {
+		copy_nonoverlapping(pixels.as_ptr(), staging_buffer_ptr as *mut u8, pixels.len());
+		logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+		logical_device.unmap_memory(staging_buffer.memory);
+	}
+
+	// Record command buffer to copy the staging buffer into the image
+	let transfer_image_memory_barrier = vk::ImageMemoryBarrier::builder()
+		.old_layout(vk::ImageLayout::UNDEFINED)
+		.new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+		.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.image(image)
+		.subresource_range(subresource_range)
+		.src_access_mask(vk::AccessFlags::empty())
+		.dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+
+	let shader_read_image_memory_barrier = vk::ImageMemoryBarrier::builder()
+		.old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+		.new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+		.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.image(image)
+		.subresource_range(subresource_range)
+		.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+		.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+	let region = vk::BufferImageCopy::builder()
+		.buffer_offset(0)
+		.buffer_row_length(0)
+		.buffer_image_height(0)
+		.image_subresource(vk::ImageSubresourceLayers::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.mip_level(0)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build())
+		.image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+		.image_extent(extent);
+
+	let command_pool = context.thread_command_pool();
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.level(vk::CommandBufferLevel::PRIMARY)
+		.command_pool(command_pool)
+		.command_buffer_count(1);
+
+	let command_buffer = unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()[0];
+
+	let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+		.flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+	unsafe {
+		logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+		logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[transfer_image_memory_barrier.build()]);
+		logical_device.cmd_copy_buffer_to_image(command_buffer, staging_buffer.handle, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region.build()]);
+		logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &[shader_read_image_memory_barrier.build()]);
+		logical_device.end_command_buffer(command_buffer).unwrap();
+	}
+
+	// Submit command buffer
+	let command_buffers = [command_buffer];
+	let submit_info = vk::SubmitInfo::builder()
+		.command_buffers(&command_buffers);
+
+	unsafe {
+		context.submit_and_wait(&[submit_info.build()]);
+		logical_device.free_command_buffers(command_pool, &command_buffers);
+	}
+
+	// Destroy staging buffer
+	staging_buffer.drop(logical_device);
+
+	ImageResources {
+		image,
+		image_view,
+		memory,
+		_allocation: Some(allocation)
+	}
+}
+
+pub fn create_unoccluded_buffer(context: &Context) -> Buffer {
+	create_constant_vertex_buffer(context, &[1.0])
+}
+
+pub fn create_uncolored_line_buffer(context: &Context) -> Buffer {
+	create_constant_vertex_buffer(context, &[0.1, 0.1, 0.1, 1.0])
+}
+
+// The same vertex value for as many vertices as 16 bit indices can reach
+fn create_constant_vertex_buffer(context: &Context, value: &[f32]) -> Buffer {
+	let values = value.repeat(u16::MAX as usize + 1);
+	let size = size_of_val(values.as_slice()) as u64;
+	let buffer = Buffer::new(context, size, vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Other);
+	let logical_device = &context.logical_device;
+
+	let range = vk::MappedMemoryRange::builder()
+		.memory(buffer.memory)
+		.offset(0)
+		.size(vk::WHOLE_SIZE);
+
+	unsafe {
+		let buffer_ptr = logical_device.map_memory(buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+		copy_nonoverlapping(values.as_ptr(), buffer_ptr as *mut f32, values.len());
+		logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+		logical_device.unmap_memory(buffer.memory);
+	}
+
+	buffer
 }
\ No newline at end of file
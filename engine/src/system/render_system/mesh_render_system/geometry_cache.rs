@@ -0,0 +1,227 @@
+use std::{collections::HashMap, mem::size_of_val, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{geometry3d::Geometry3D, pool::{Pool, Handle}, vulkan::{Buffer, Context, MemoryCategory}};
+
+// Dynamic mesh geometry is kept in a device local buffer shared by all in flight frames. Geometry is uploaded the first
+// time it's drawn and again only when it changes, new uploads are appended after what's there so nothing an in flight
+// frame might be reading is overwritten. Each entry counts the instance groups which referenced it in the latest frame
+// and entries nothing references are dropped when the buffer fills up and is compacted.
+pub struct MeshGeometryCache {
+	pub buffer: Buffer,
+	entries: HashMap<Handle, MeshGeometryEntry>,
+	used_size: usize,
+	staging_buffers: Vec<Buffer>,
+	pending_copies: Vec<vk::BufferCopy>
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct MeshGeometryEntry {
+	pub index_array_offset: usize,
+	pub attribute_array_offset: usize,
+	// Only meaningful when the geometry has the UV sets, occlusion and colors
+	pub uv_array_offset: usize,
+	pub uv2_array_offset: usize,
+	pub occlusion_array_offset: usize,
+	pub colors_array_offset: usize,
+	geometry_id: u64,
+	reference_count: usize
+}
+
+impl MeshGeometryCache {
+	pub fn new(in_flight_frames_count: usize) -> Self {
+		Self {
+			buffer: Buffer::null(
+				vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER,
+				vk::MemoryPropertyFlags::DEVICE_LOCAL,
+				MemoryCategory::DynamicGeometry),
+			entries: HashMap::new(),
+			used_size: 0,
+			staging_buffers: (0..in_flight_frames_count)
+				.map(|_| Buffer::null(vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging))
+				.collect(),
+			pending_copies: vec![]
+		}
+	}
+
+	// Takes the geometry handle of each instance group drawn this frame and copies the geometry which isn't in the buffer
+	// into the in flight frame's staging buffer, the copies into the buffer are recorded with record_uploads
+	pub fn update(&mut self, context: &Context, in_flight_frame_index: usize, geometries: &Pool<Geometry3D>, handles: &[Handle]) {
+		let logical_device = &context.logical_device;
+
+		for entry in self.entries.values_mut() {
+			entry.reference_count = 0;
+		}
+
+		let mut stale_handles: Vec<Handle> = vec![];
+
+		for handle in handles {
+			let geometry = geometries.borrow(*handle);
+			let entry = self.entries.entry(*handle).or_default();
+			entry.reference_count += 1;
+
+			if entry.reference_count == 1 && entry.geometry_id != geometry.geometry_id {
+				stale_handles.push(*handle);
+			}
+		}
+
+		if stale_handles.is_empty() {
+			return;
+		}
+
+		let mut missing_size: usize = stale_handles.iter().map(|handle| Self::size(geometries.borrow(*handle))).sum();
+
+		// Start over from the beginning of the buffer when the new geometry doesn't fit after the old, everything still
+		// referenced is uploaded again once the in flight frames are done reading the buffer
+		if self.used_size + missing_size > self.buffer.capacity as usize {
+			context.wait_idle();
+
+			self.entries.retain(|_, entry| entry.reference_count > 0);
+			stale_handles = self.entries.keys().copied().collect();
+			missing_size = stale_handles.iter().map(|handle| Self::size(geometries.borrow(*handle))).sum();
+			self.used_size = 0;
+
+			if missing_size > self.buffer.capacity as usize {
+				let capacity = missing_size.max(self.buffer.capacity as usize * 2);
+				self.buffer.reallocate(context, capacity as u64);
+				println!("Mesh geometry buffer reallocated");
+			}
+		}
+
+		// The in flight frame has been waited on so its staging buffer is free to reuse
+		let staging_buffer = &mut self.staging_buffers[in_flight_frame_index];
+
+		if missing_size as u64 > staging_buffer.capacity {
+			staging_buffer.reallocate(context, missing_size as u64);
+		}
+
+		// Geometry without any indices or attributes takes no space and has nothing to copy
+		let staging_buffer_ptr = if missing_size > 0 {
+			unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap()
+		}
+		else {
+			std::ptr::null_mut()
+		};
+
+		let mut staging_offset = 0;
+
+		for handle in &stale_handles {
+			let geometry = geometries.borrow(*handle);
+			let indices = geometry.indices();
+			let (vertex_data_ptr, vertex_data_size) = geometry.vertex_data();
+			let uvs = geometry.uvs();
+			let uvs2 = geometry.uvs2();
+			let occlusion = geometry.occlusion();
+			let colors = geometry.colors();
+			let size = Self::size(geometry);
+
+			let index_array_padding = (4 - size_of_val(indices) % 4) % 4;
+			let relative_attribute_array_offset = size_of_val(indices) + index_array_padding;
+			let relative_uv_array_offset = relative_attribute_array_offset + vertex_data_size;
+			let relative_uv2_array_offset = relative_uv_array_offset + size_of_val(uvs);
+			let relative_occlusion_array_offset = relative_uv2_array_offset + size_of_val(uvs2);
+			let relative_colors_array_offset = relative_occlusion_array_offset + size_of_val(occlusion);
+
+			if size > 0 {
+				unsafe {
+					let index_array_dst_ptr = staging_buffer_ptr.add(staging_offset) as *mut u16;
+					copy_nonoverlapping(indices.as_ptr(), index_array_dst_ptr, indices.len());
+
+					let attribute_array_dst_ptr = staging_buffer_ptr.add(staging_offset + relative_attribute_array_offset) as *mut u8;
+					copy_nonoverlapping(vertex_data_ptr, attribute_array_dst_ptr, vertex_data_size);
+
+					let uv_array_dst_ptr = staging_buffer_ptr.add(staging_offset + relative_uv_array_offset) as *mut f32;
+					copy_nonoverlapping(uvs.as_ptr(), uv_array_dst_ptr, uvs.len());
+
+					let uv2_array_dst_ptr = staging_buffer_ptr.add(staging_offset + relative_uv2_array_offset) as *mut f32;
+					copy_nonoverlapping(uvs2.as_ptr(), uv2_array_dst_ptr, uvs2.len());
+
+					let occlusion_array_dst_ptr = staging_buffer_ptr.add(staging_offset + relative_occlusion_array_offset) as *mut f32;
+					copy_nonoverlapping(occlusion.as_ptr(), occlusion_array_dst_ptr, occlusion.len());
+
+					let colors_array_dst_ptr = staging_buffer_ptr.add(staging_offset + relative_colors_array_offset) as *mut f32;
+					copy_nonoverlapping(colors.as_ptr(), colors_array_dst_ptr, colors.len());
+				}
+
+				let region = vk::BufferCopy::builder()
+					.src_offset(staging_offset as u64)
+					.dst_offset(self.used_size as u64)
+					.size(size as u64);
+				self.pending_copies.push(region.build());
+			}
+
+			let entry = self.entries.get_mut(handle).unwrap();
+			entry.index_array_offset = self.used_size;
+			entry.attribute_array_offset = self.used_size + relative_attribute_array_offset;
+			entry.uv_array_offset = self.used_size + relative_uv_array_offset;
+			entry.uv2_array_offset = self.used_size + relative_uv2_array_offset;
+			entry.occlusion_array_offset = self.used_size + relative_occlusion_array_offset;
+			entry.colors_array_offset = self.used_size + relative_colors_array_offset;
+			entry.geometry_id = geometry.geometry_id;
+
+			staging_offset += size;
+			self.used_size += size;
+		}
+
+		if missing_size > 0 {
+			let range = vk::MappedMemoryRange::builder()
+				.memory(staging_buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(staging_buffer.memory);
+			}
+		}
+	}
+
+	pub fn entry(&self, handle: Handle) -> MeshGeometryEntry {
+		self.entries[&handle]
+	}
+
+	// Records the copies from the staging buffer made by the last update and makes them visible to vertex input
+	pub fn record_uploads(&mut self, logical_device: &ash::Device, command_buffer: vk::CommandBuffer, in_flight_frame_index: usize) {
+		if self.pending_copies.is_empty() {
+			return;
+		}
+
+		let barrier = vk::BufferMemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+			.dst_access_mask(vk::AccessFlags::INDEX_READ | vk::AccessFlags::VERTEX_ATTRIBUTE_READ)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.buffer(self.buffer.handle)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		unsafe {
+			logical_device.cmd_copy_buffer(command_buffer, self.staging_buffers[in_flight_frame_index].handle, self.buffer.handle, &self.pending_copies);
+			logical_device.cmd_pipeline_barrier(
+				command_buffer,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::PipelineStageFlags::VERTEX_INPUT,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[barrier.build()],
+				&[]);
+		}
+
+		self.pending_copies.clear();
+	}
+
+	// The index array is padded so the attribute array after it is 4 byte aligned, the UV, occlusion and color arrays follow
+	// the attributes
+	fn size(geometry: &Geometry3D) -> usize {
+		let index_array_size = size_of_val(geometry.indices());
+		index_array_size + (4 - index_array_size % 4) % 4 + geometry.vertex_data().1 + size_of_val(geometry.uvs()) + size_of_val(geometry.uvs2())
+			+ size_of_val(geometry.occlusion()) + size_of_val(geometry.colors())
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		self.buffer.drop(logical_device);
+
+		for staging_buffer in &self.staging_buffers {
+			staging_buffer.drop(logical_device);
+		}
+	}
+}
\ No newline at end of file
@@ -1,17 +1,53 @@
 use std::{mem::size_of_val, ptr::copy_nonoverlapping};
-use ash::{vk, version::DeviceV1_0};
-use crate::{component::mesh::Material, geometry3d::{Geometry3D, SubmissionInfo}, pool::{Pool, Handle}, vulkan::{Buffer, Context}};
-use super::MATERIALS_COUNT;
+use ash::vk;
+use crate::{Lightmap, component::mesh::Material, geometry3d::{Geometry3D, SubmissionInfo}, pool::{Pool, Handle}, vulkan::{Buffer, Context, MemoryCategory}, Vfs};
+use super::{ImageResources, MATERIALS_COUNT};
 
 mod creation;
 use creation::*;
 
+mod geometry_cache;
+use geometry_cache::MeshGeometryCache;
+
 pub struct MeshRenderSystem {
 	pub pipeline_layout: vk::PipelineLayout,
 	pub line_pipeline: vk::Pipeline,
 	pub basic_pipeline: vk::Pipeline,
 	pub normal_pipeline: vk::Pipeline,
 	pub lambert_pipeline: vk::Pipeline,
+	pub basic_depth_equal_pipeline: vk::Pipeline,
+	pub normal_depth_equal_pipeline: vk::Pipeline,
+	pub lambert_depth_equal_pipeline: vk::Pipeline,
+	pub basic_depth_prepass_pipeline: vk::Pipeline,
+	pub normal_depth_prepass_pipeline: vk::Pipeline,
+	pub lambert_depth_prepass_pipeline: vk::Pipeline,
+	pub basic_quantized_pipeline: vk::Pipeline,
+	pub normal_quantized_pipeline: vk::Pipeline,
+	pub lambert_quantized_pipeline: vk::Pipeline,
+	pub basic_quantized_depth_equal_pipeline: vk::Pipeline,
+	pub normal_quantized_depth_equal_pipeline: vk::Pipeline,
+	pub lambert_quantized_depth_equal_pipeline: vk::Pipeline,
+	pub basic_quantized_depth_prepass_pipeline: vk::Pipeline,
+	pub normal_quantized_depth_prepass_pipeline: vk::Pipeline,
+	pub lambert_quantized_depth_prepass_pipeline: vk::Pipeline,
+	pub lightmapped_pipeline: vk::Pipeline,
+	pub lightmapped_depth_equal_pipeline: vk::Pipeline,
+	pub lightmapped_depth_prepass_pipeline: vk::Pipeline,
+	pub lightmapped_quantized_pipeline: vk::Pipeline,
+	pub lightmapped_quantized_depth_equal_pipeline: vk::Pipeline,
+	pub lightmapped_quantized_depth_prepass_pipeline: vk::Pipeline,
+	pub refractive_pipeline: vk::Pipeline,
+	pub refractive_quantized_pipeline: vk::Pipeline,
+	pub reflective_pipeline: vk::Pipeline,
+	pub reflective_quantized_pipeline: vk::Pipeline,
+	pub foliage_pipeline: vk::Pipeline,
+	pub foliage_quantized_pipeline: vk::Pipeline,
+	lightmap_descriptor_set_layout: vk::DescriptorSetLayout,
+	pub lightmap_descriptor_set: vk::DescriptorSet,
+	lightmap_sampler: vk::Sampler,
+	lightmap: ImageResources,
+	pub depth_prepass_command_buffers: Vec<vk::CommandBuffer>,
+	layer_command_buffers: Vec<Vec<vk::CommandBuffer>>,
 	pub line_static_descriptor_set: vk::DescriptorSet,
 	pub basic_static_descriptor_set: vk::DescriptorSet,
 	pub normal_static_descriptor_set: vk::DescriptorSet,
@@ -20,7 +56,16 @@ pub struct MeshRenderSystem {
 	pub static_geometry_infos: Vec<StaticGeometryInfo>,
 	pub static_instance_groups: Vec<StaticInstanceGroup>,
 	pub static_material_counts: [usize; MATERIALS_COUNT],
-	static_geometry_submission_generation: usize
+	pub static_geometry_submission_generation: usize,
+	// What's in the static geometry buffer and how many bytes each takes, the space of removed geometry is only reclaimed
+	// when everything is resubmitted
+	static_geometries: Vec<(Handle, usize)>,
+	static_geometry_size: usize,
+	static_geometry_unused_size: usize,
+	pub geometry_cache: MeshGeometryCache,
+	// Bound in place of the occlusion of geometry which has none, one for every vertex a 16 bit index can reach
+	pub unoccluded_buffer: Buffer,
+	pub uncolored_line_buffer: Buffer
 }
 
 #[derive(Clone)]
@@ -38,22 +83,38 @@ pub struct StaticInstanceGroup {
 }
 
 impl MeshRenderSystem {
+	#[allow(clippy::too_many_arguments)]
 	pub fn new(
-		logical_device: &ash::Device,
+		context: &Context,
+		vfs: &Vfs,
 		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
 		instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
-		extent: vk::Extent2D,
+		ambient_occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+		refraction_descriptor_set_layout: vk::DescriptorSetLayout,
+		reflection_descriptor_set_layout: vk::DescriptorSetLayout,
 		render_pass: vk::RenderPass,
-		descriptor_pool: vk::DescriptorPool)
+		descriptor_pool: vk::DescriptorPool,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
 		-> Self
 	{
-		let pipeline_layout = create_pipeline_layout(logical_device, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout);
-		let pipelines = create_pipelines(logical_device, extent, pipeline_layout, render_pass);
+		let logical_device = &context.logical_device;
+		let lightmap_descriptor_set_layout = create_lightmap_descriptor_set_layout(logical_device);
+		let pipeline_layout = create_pipeline_layout(logical_device, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout, ambient_occlusion_descriptor_set_layout, lightmap_descriptor_set_layout, refraction_descriptor_set_layout, reflection_descriptor_set_layout);
+		let pipelines = create_pipelines(logical_device, vfs, pipeline_layout, render_pass);
 		let static_descriptor_sets = create_static_descriptor_sets(logical_device, descriptor_pool, instance_data_descriptor_set_layout);
+		let depth_prepass_command_buffers = create_depth_prepass_command_buffers(logical_device, command_pool, in_flight_frames_count);
+
+		// Until a baked lightmap is submitted lightmapped meshes are lit fully
+		let lightmap_descriptor_set = create_lightmap_descriptor_set(logical_device, descriptor_pool, lightmap_descriptor_set_layout);
+		let lightmap_sampler = create_lightmap_sampler(logical_device);
+		let lightmap = upload_lightmap(context, &Lightmap::new(1, 1));
+		update_lightmap_descriptor_set(logical_device, lightmap_descriptor_set, lightmap_sampler, &lightmap);
 
 		let static_geometry_buffer = Buffer::null(
 			vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER,
-			vk::MemoryPropertyFlags::DEVICE_LOCAL);
+			vk::MemoryPropertyFlags::DEVICE_LOCAL,
+			MemoryCategory::StaticGeometry);
 
 		Self {
 			pipeline_layout,
@@ -61,6 +122,39 @@ impl MeshRenderSystem {
 			basic_pipeline: pipelines[1],
 			normal_pipeline: pipelines[2],
 			lambert_pipeline: pipelines[3],
+			basic_depth_equal_pipeline: pipelines[4],
+			normal_depth_equal_pipeline: pipelines[5],
+			lambert_depth_equal_pipeline: pipelines[6],
+			basic_depth_prepass_pipeline: pipelines[7],
+			normal_depth_prepass_pipeline: pipelines[8],
+			lambert_depth_prepass_pipeline: pipelines[9],
+			basic_quantized_pipeline: pipelines[10],
+			normal_quantized_pipeline: pipelines[11],
+			lambert_quantized_pipeline: pipelines[12],
+			basic_quantized_depth_equal_pipeline: pipelines[13],
+			normal_quantized_depth_equal_pipeline: pipelines[14],
+			lambert_quantized_depth_equal_pipeline: pipelines[15],
+			basic_quantized_depth_prepass_pipeline: pipelines[16],
+			normal_quantized_depth_prepass_pipeline: pipelines[17],
+			lambert_quantized_depth_prepass_pipeline: pipelines[18],
+			lightmapped_pipeline: pipelines[19],
+			lightmapped_depth_equal_pipeline: pipelines[20],
+			lightmapped_depth_prepass_pipeline: pipelines[21],
+			lightmapped_quantized_pipeline: pipelines[22],
+			lightmapped_quantized_depth_equal_pipeline: pipelines[23],
+			lightmapped_quantized_depth_prepass_pipeline: pipelines[24],
+			refractive_pipeline: pipelines[25],
+			refractive_quantized_pipeline: pipelines[26],
+			reflective_pipeline: pipelines[27],
+			reflective_quantized_pipeline: pipelines[28],
+			foliage_pipeline: pipelines[29],
+			foliage_quantized_pipeline: pipelines[30],
+			lightmap_descriptor_set_layout,
+			lightmap_descriptor_set,
+			lightmap_sampler,
+			lightmap,
+			depth_prepass_command_buffers,
+			layer_command_buffers: vec![vec![]; in_flight_frames_count],
 			line_static_descriptor_set: static_descriptor_sets[0],
 			basic_static_descriptor_set: static_descriptor_sets[1],
 			normal_static_descriptor_set: static_descriptor_sets[2],
@@ -69,36 +163,129 @@ impl MeshRenderSystem {
 			static_geometry_infos: vec![],
 			static_instance_groups: vec![],
 			static_material_counts: [0; MATERIALS_COUNT],
-			static_geometry_submission_generation: 0
+			static_geometry_submission_generation: 0,
+			static_geometries: vec![],
+			static_geometry_size: 0,
+			static_geometry_unused_size: 0,
+			geometry_cache: MeshGeometryCache::new(in_flight_frames_count),
+			unoccluded_buffer: create_unoccluded_buffer(context),
+			uncolored_line_buffer: create_uncolored_line_buffer(context)
 		}
 	}
 
-	pub fn handle_swapchain_recreation(&mut self, logical_device: &ash::Device, extent: vk::Extent2D, render_pass: vk::RenderPass) {
-		unsafe {
-			logical_device.destroy_pipeline(self.lambert_pipeline, None);
-			logical_device.destroy_pipeline(self.normal_pipeline, None);
-			logical_device.destroy_pipeline(self.basic_pipeline, None);
-			logical_device.destroy_pipeline(self.line_pipeline, None);
+	// The pipeline meshes of the material are shaded with, the depth equal pipelines are for meshes already in the depth prepass
+	pub fn pipeline(&self, material: Material, depth_equal: bool, quantized: bool) -> vk::Pipeline {
+		match (material, depth_equal, quantized) {
+			(Material::Line, _, _) => self.line_pipeline,
+			(Material::Basic, false, false) => self.basic_pipeline,
+			(Material::Basic, true, false) => self.basic_depth_equal_pipeline,
+			(Material::Normal, false, false) => self.normal_pipeline,
+			(Material::Normal, true, false) => self.normal_depth_equal_pipeline,
+			(Material::Lambert, false, false) => self.lambert_pipeline,
+			(Material::Lambert, true, false) => self.lambert_depth_equal_pipeline,
+			(Material::Basic, false, true) => self.basic_quantized_pipeline,
+			(Material::Basic, true, true) => self.basic_quantized_depth_equal_pipeline,
+			(Material::Normal, false, true) => self.normal_quantized_pipeline,
+			(Material::Normal, true, true) => self.normal_quantized_depth_equal_pipeline,
+			(Material::Lambert, false, true) => self.lambert_quantized_pipeline,
+			(Material::Lambert, true, true) => self.lambert_quantized_depth_equal_pipeline,
+			(Material::Lightmapped, false, false) => self.lightmapped_pipeline,
+			(Material::Lightmapped, true, false) => self.lightmapped_depth_equal_pipeline,
+			(Material::Lightmapped, false, true) => self.lightmapped_quantized_pipeline,
+			(Material::Lightmapped, true, true) => self.lightmapped_quantized_depth_equal_pipeline,
+			(Material::Refractive, _, false) => self.refractive_pipeline,
+			(Material::Refractive, _, true) => self.refractive_quantized_pipeline,
+			(Material::Reflective, _, false) => self.reflective_pipeline,
+			(Material::Reflective, _, true) => self.reflective_quantized_pipeline,
+			(Material::Foliage, _, false) => self.foliage_pipeline,
+			(Material::Foliage, _, true) => self.foliage_quantized_pipeline
 		}
+	}
+
+	// Each render layer drawn in a frame gets its own secondary command buffer, more are allocated as more layers are used
+	pub fn layer_command_buffer(&mut self, logical_device: &ash::Device, command_pool: vk::CommandPool, in_flight_frame_index: usize, layer_index: usize) -> vk::CommandBuffer {
+		let command_buffers = &mut self.layer_command_buffers[in_flight_frame_index];
 
-		let pipelines = create_pipelines(logical_device, extent, self.pipeline_layout, render_pass);
+		if layer_index >= command_buffers.len() {
+			let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+				.command_pool(command_pool)
+				.level(vk::CommandBufferLevel::SECONDARY)
+				.command_buffer_count((layer_index + 1 - command_buffers.len()) as u32);
 
-		self.line_pipeline = pipelines[0];
-		self.basic_pipeline = pipelines[1];
-		self.normal_pipeline = pipelines[2];
-		self.lambert_pipeline = pipelines[3];
+			command_buffers.extend(unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap());
+		}
+
+		command_buffers[layer_index]
 	}
 
-	pub fn submit_static_geometries(&mut self, context: &Context, command_pool: vk::CommandPool, geometries: &mut Pool<Geometry3D>, handles: &[Handle]) {
-		// Don't forget to increment the submission generation
+	// Replaces the lightmap sampled by every lightmapped mesh
+	pub fn submit_lightmap(&mut self, context: &Context, lightmap: &Lightmap) {
 		let logical_device = &context.logical_device;
+		let new_lightmap = upload_lightmap(context, lightmap);
+
+		// The old lightmap may still be sampled by in flight frames
+		unsafe { logical_device.device_wait_idle() }.unwrap();
+		update_lightmap_descriptor_set(logical_device, self.lightmap_descriptor_set, self.lightmap_sampler, &new_lightmap);
+
+		let old_lightmap = std::mem::replace(&mut self.lightmap, new_lightmap);
+		unsafe { old_lightmap.drop(logical_device) };
+	}
 
-		let mut buffer_size = 0;
+	pub fn submit_static_geometries(&mut self, context: &Context, geometries: &mut Pool<Geometry3D>, handles: &[Handle]) {
+		self.static_geometries.clear();
+		self.static_geometry_size = 0;
+		self.static_geometry_unused_size = 0;
+		self.static_geometry_submission_generation += 1;
+		self.write_static_geometries(context, geometries, handles);
+	}
+
+	// Adds and removes static geometries without resubmitting the rest, such as when a streamed cell of the world loads or
+	// unloads. Added geometries are appended after what's already in the buffer. Everything is resubmitted instead when they
+	// don't fit or when more than half of the buffer is taken up by removed geometries. Removed geometries don't have to be in
+	// the pool anymore.
+	pub fn update_static_geometries(&mut self, context: &Context, geometries: &mut Pool<Geometry3D>, added: &[Handle], removed: &[Handle]) {
+		for (handle, size) in &self.static_geometries {
+			if removed.contains(handle) {
+				self.static_geometry_unused_size += size;
+
+				if let Some(geometry) = geometries.try_borrow_mut(*handle) {
+					geometry.submission_info = None;
+				}
+			}
+		}
+
+		self.static_geometries.retain(|(handle, _)| !removed.contains(handle));
+
+		// Up to 3 bytes of padding go before each geometry's attributes
+		let added_size: usize = added.iter().map(|handle| static_geometry_size(geometries.borrow(*handle)) + 3).sum();
+		let fits = self.static_geometry_size + added_size <= self.static_geometry_buffer.capacity as usize;
+		let mostly_unused = self.static_geometry_unused_size * 2 > self.static_geometry_size;
+
+		if fits && !mostly_unused {
+			self.write_static_geometries(context, geometries, added);
+		}
+		else {
+			let handles: Vec<Handle> = self.static_geometries.iter().map(|(handle, _)| *handle).chain(added.iter().copied()).collect();
+			self.submit_static_geometries(context, geometries, &handles);
+		}
+	}
+
+	// Appends the geometries after what's already in the static geometry buffer. The buffer is only reallocated when nothing
+	// is in it yet, otherwise there has to be room.
+	fn write_static_geometries(&mut self, context: &Context, geometries: &mut Pool<Geometry3D>, handles: &[Handle]) {
+		let logical_device = &context.logical_device;
+
+		let start = self.static_geometry_size;
+		let mut buffer_size = start;
 
 		for handle in handles {
 			let geometry = geometries.borrow_mut(*handle);
 			let index_array_size = size_of_val(geometry.indices());
-			let attributes_array_size = size_of_val(geometry.attributes());
+			let attributes_array_size = geometry.vertex_data().1;
+			let uvs_array_size = size_of_val(geometry.uvs());
+			let uvs2_array_size = size_of_val(geometry.uvs2());
+			let occlusion_array_size = size_of_val(geometry.occlusion());
+			let colors_array_size = size_of_val(geometry.colors());
 
 			let index_array_offset = buffer_size;
 			let unaligned_attributes_array_offset = index_array_offset + index_array_size;
@@ -108,39 +295,69 @@ impl MeshRenderSystem {
 			geometry.submission_info = Some(SubmissionInfo {
 				generation: self.static_geometry_submission_generation,
 				index_array_offset,
-				attributes_array_offset
+				attributes_array_offset,
+				uvs_array_offset: attributes_array_offset + attributes_array_size,
+				uvs2_array_offset: attributes_array_offset + attributes_array_size + uvs_array_size,
+				occlusion_array_offset: attributes_array_offset + attributes_array_size + uvs_array_size + uvs2_array_size,
+				colors_array_offset: attributes_array_offset + attributes_array_size + uvs_array_size + uvs2_array_size + occlusion_array_size
 			});
 
-			buffer_size += index_array_size + attributes_array_padding + attributes_array_size;
+			let size = index_array_size + attributes_array_padding + attributes_array_size + uvs_array_size + uvs2_array_size + occlusion_array_size + colors_array_size;
+			self.static_geometries.push((*handle, size));
+			buffer_size += size;
 		}
-		
+
+		if buffer_size == start {
+			return;
+		}
+
+		self.static_geometry_size = buffer_size;
 		let buffer_size = buffer_size as u64;
+		let written_size = buffer_size - start as u64;
 
 		// Create a host visible staging buffer
-		let staging_buffer = Buffer::new(&context, buffer_size, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE);
+		let staging_buffer = Buffer::new(context, written_size, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging);
 
 		// Allocate larger device local buffer if necessary and update descriptor sets to reference new buffer
 		if buffer_size > self.static_geometry_buffer.capacity {
-			unsafe { logical_device.queue_wait_idle(context.graphics_queue) }.unwrap();
-			self.static_geometry_buffer.reallocate(&context, buffer_size);
+			assert!(start == 0, "Static geometries can only be appended when there's room for them");
+			context.wait_idle();
+			self.static_geometry_buffer.reallocate(context, buffer_size);
 			println!("Static mesh buffer reallocated");
 		}
 
 		// Copy mesh data into staging buffer and save draw information
 		let buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
 
+		// The staging buffer only holds what's being written, which goes after what's already in the buffer
 		for handle in handles {
 			let geometry = geometries.borrow(*handle);
 			let submission_info = geometry.submission_info.as_ref().unwrap();
 			let indices = geometry.indices();
-			let attributes = geometry.attributes();
+			let (vertex_data_ptr, vertex_data_size) = geometry.vertex_data();
+			let uvs = geometry.uvs();
+			let uvs2 = geometry.uvs2();
+			let occlusion = geometry.occlusion();
+			let colors = geometry.colors();
 
 			unsafe {
-				let index_array_dst_ptr = buffer_ptr.add(submission_info.index_array_offset) as *mut u16;
+				let index_array_dst_ptr = buffer_ptr.add(submission_info.index_array_offset - start) as *mut u16;
 				copy_nonoverlapping(indices.as_ptr(), index_array_dst_ptr, indices.len());
 
-				let attribute_array_dst_ptr = buffer_ptr.add(submission_info.attributes_array_offset) as *mut f32;
-				copy_nonoverlapping(attributes.as_ptr(), attribute_array_dst_ptr, attributes.len());
+				let attribute_array_dst_ptr = buffer_ptr.add(submission_info.attributes_array_offset - start) as *mut u8;
+				copy_nonoverlapping(vertex_data_ptr, attribute_array_dst_ptr, vertex_data_size);
+
+				let uv_array_dst_ptr = buffer_ptr.add(submission_info.uvs_array_offset - start) as *mut f32;
+				copy_nonoverlapping(uvs.as_ptr(), uv_array_dst_ptr, uvs.len());
+
+				let uv2_array_dst_ptr = buffer_ptr.add(submission_info.uvs2_array_offset - start) as *mut f32;
+				copy_nonoverlapping(uvs2.as_ptr(), uv2_array_dst_ptr, uvs2.len());
+
+				let occlusion_array_dst_ptr = buffer_ptr.add(submission_info.occlusion_array_offset - start) as *mut f32;
+				copy_nonoverlapping(occlusion.as_ptr(), occlusion_array_dst_ptr, occlusion.len());
+
+				let colors_array_dst_ptr = buffer_ptr.add(submission_info.colors_array_offset - start) as *mut f32;
+				copy_nonoverlapping(colors.as_ptr(), colors_array_dst_ptr, colors.len());
 			}
 		}
 
@@ -156,6 +373,7 @@ impl MeshRenderSystem {
 		}
 
 		// Record a command buffer to copy the data from the staging buffer to the device local buffer
+		let command_pool = context.thread_command_pool();
 		let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
 			.level(vk::CommandBufferLevel::PRIMARY)
 			.command_pool(command_pool)
@@ -167,7 +385,8 @@ impl MeshRenderSystem {
 			.flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 		
 		let region = vk::BufferCopy::builder()
-			.size(buffer_size);
+			.dst_offset(start as u64)
+			.size(written_size);
 		
 		unsafe {
 			logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
@@ -181,9 +400,8 @@ impl MeshRenderSystem {
 			.command_buffers(&command_buffers);
 		
 		unsafe {
-			logical_device.queue_wait_idle(context.graphics_queue).unwrap();
-			logical_device.queue_submit(context.graphics_queue, &[submit_info.build()], vk::Fence::null()).unwrap();
-			logical_device.queue_wait_idle(context.graphics_queue).unwrap();
+			context.wait_idle();
+			context.submit_and_wait(&[submit_info.build()]);
 			logical_device.free_command_buffers(command_pool, &command_buffers);
 		}
 
@@ -192,13 +410,51 @@ impl MeshRenderSystem {
 
 	pub fn drop(&self, logical_device: &ash::Device) {
 		self.static_geometry_buffer.drop(logical_device);
+		self.geometry_cache.drop(logical_device);
+		self.unoccluded_buffer.drop(logical_device);
+		self.uncolored_line_buffer.drop(logical_device);
 		
 		unsafe {
+			self.lightmap.drop(logical_device);
+			logical_device.destroy_sampler(self.lightmap_sampler, None);
+			logical_device.destroy_pipeline(self.foliage_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.foliage_pipeline, None);
+			logical_device.destroy_pipeline(self.reflective_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.reflective_pipeline, None);
+			logical_device.destroy_pipeline(self.refractive_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.refractive_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_quantized_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_quantized_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.lightmapped_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_quantized_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_quantized_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_quantized_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_quantized_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_quantized_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_quantized_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_quantized_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_depth_prepass_pipeline, None);
+			logical_device.destroy_pipeline(self.lambert_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.normal_depth_equal_pipeline, None);
+			logical_device.destroy_pipeline(self.basic_depth_equal_pipeline, None);
 			logical_device.destroy_pipeline(self.lambert_pipeline, None);
 			logical_device.destroy_pipeline(self.normal_pipeline, None);
 			logical_device.destroy_pipeline(self.basic_pipeline, None);
 			logical_device.destroy_pipeline(self.line_pipeline, None);
 			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.lightmap_descriptor_set_layout, None);
 		}
 	}
+}
+
+// The most a geometry takes up in the static geometry buffer, without the padding before its attributes
+fn static_geometry_size(geometry: &Geometry3D) -> usize {
+	size_of_val(geometry.indices()) + geometry.vertex_data().1 + size_of_val(geometry.uvs()) + size_of_val(geometry.uvs2()) + size_of_val(geometry.occlusion()) + size_of_val(geometry.colors())
 }
\ No newline at end of file
@@ -1,15 +1,24 @@
-use std::{cmp::max, fs::File, mem::size_of_val, ptr::copy_nonoverlapping};
+use std::{borrow::Cow, cmp::max, collections::HashMap, io::Cursor, mem::size_of_val, ptr::copy_nonoverlapping, time::{Duration, Instant}};
 use crate::{
 	Camera,
+	camera::letterbox_viewport,
+	ColorGradingLut,
 	Entity,
-	component::{ComponentList, MultiComponentList, Light, Mesh, TextComponentList, Transform2DComponentList, Transform3DComponentList, mesh::Material, Text},
+	component::{AnimatedSprite, BlobShadow, ComponentList, InputField, MultiComponentList, Light, light::Falloff, Mesh, RenderLayer, TextComponentList, Trail, Transform2DComponentList, Transform3DComponentList, mesh::Material, Text, TextPositioning, Tilemap},
 	Font,
+	FramePhase,
 	Geometry3D,
-	math::{vector3, Vector3},
+	Lightmap,
+	geometry3d::{Topology, VertexFormat},
+	math::{vector3, Box3, Frustum, Matrix4, Plane, Sphere, Vector2, Vector3},
 	pool::{Pool, Handle},
-	vulkan::{Context, Buffer}
+	ParticleEmitter,
+	Texture,
+	Vfs,
+	vulkan::{Allocation, Context, Buffer},
+	Wind
 };
-use ash::{vk, version::DeviceV1_0, extensions::khr};
+use ash::{vk, extensions::khr};
 
 mod creation;
 use creation::*;
@@ -19,76 +28,295 @@ use mesh_render_system::*;
 
 mod text_render_system;
 use text_render_system::*;
+pub use text_render_system::FontSubmissionError;
 
-const IN_FLIGHT_FRAMES_COUNT: usize = 2;
-const FRAME_DATA_MEMORY_SIZE: usize = 76 * 4;
-const MATERIALS_COUNT: usize = 4;
-const MAX_POINT_LIGHTS: usize = 5;
-const MAX_FONTS: usize = 10;
+mod ssao_render_system;
+use ssao_render_system::*;
+
+mod post_process_render_system;
+use post_process_render_system::*;
+
+mod motion_vector_render_system;
+use motion_vector_render_system::*;
+
+mod taa_render_system;
+use taa_render_system::*;
+
+mod deferred_render_system;
+use deferred_render_system::*;
+
+mod refraction_render_system;
+use refraction_render_system::*;
+
+mod reflection_render_system;
+use reflection_render_system::*;
+
+mod particle_render_system;
+use particle_render_system::*;
+
+mod trail_render_system;
+use trail_render_system::*;
+
+mod sprite_render_system;
+use sprite_render_system::*;
+
+mod texture_table;
+use texture_table::TextureTable;
+
+mod texture_store;
+use texture_store::TextureStore;
+pub use texture_store::TextureSubmissionError;
+
+mod frame_sync;
+use frame_sync::{FrameSync, WaitError};
+
+mod dynamic_resolution;
+use dynamic_resolution::DynamicResolution;
+
+mod render_stats;
+pub use render_stats::RenderStats;
+
+mod submission_status;
+pub use submission_status::SubmissionStatus;
+
+pub use crate::vulkan::memory_budget::{MemoryCategory, MemoryUsage, HeapUsage};
+
+mod light_clusters;
+use light_clusters::*;
+
+mod frame_capture;
+use frame_capture::FrameCapturer;
+pub use frame_capture::FrameCapture;
+
+mod occlusion_culler;
+use occlusion_culler::*;
+
+mod breadcrumbs;
+use breadcrumbs::*;
+
+mod scene_pacer;
+use scene_pacer::ScenePacer;
+
+const FRAME_DATA_MEMORY_SIZE: usize = 56 * 4;
+const MATERIALS_COUNT: usize = 8;
+const FALLBACK_MAX_FONTS: usize = 16;
+const FALLBACK_MAX_TEXTURES: usize = 16;
+const MIN_RENDER_SCALE: f32 = 0.5;
+const MAX_RENDER_SCALE: f32 = 2.0;
+
+// Options which are fixed once the render system is created
+pub struct RenderSystemSettings {
+	// Gives the scene depth attachment a stencil aspect for features like outlines and masking
+	pub stencil_enabled: bool,
+	// 2 or 3, a third frame lets the CPU run further ahead of the GPU which evens out uneven frames at the cost of latency
+	pub in_flight_frames_count: usize,
+	// The most particles alive at once across every emitter, spawns are dropped while the pool is full
+	pub max_particles: usize,
+	// Waits for the display's refresh to present, without it frames are presented as soon as they're done
+	pub vsync: bool,
+	// Marks each pass as it finishes on the GPU so a lost device logs the pass it happened in, costs a barrier per pass
+	pub breadcrumbs_enabled: bool,
+	// Where shaders and font files are read from
+	pub vfs: Vfs
+}
+
+impl Default for RenderSystemSettings {
+	fn default() -> Self {
+		Self {
+			stencil_enabled: false,
+			in_flight_frames_count: 2,
+			max_particles: 262144,
+			vsync: true,
+			breadcrumbs_enabled: false,
+			vfs: Vfs::with_default_mounts().unwrap_or_else(|e| panic!("Cannot mount the default pack\n{}", e))
+		}
+	}
+}
 
 pub struct RenderSystem {
 	context: Context,
-	render_pass: vk::RenderPass,
+	vfs: Vfs,
+	scene_render_pass: vk::RenderPass,
+	scene_continuation_render_pass: vk::RenderPass,
+	overlay_render_pass: vk::RenderPass,
 	swapchain: Swapchain,
+	scene_target: SceneTarget,
+	retired_swapchains: Vec<Retired<Swapchain>>,
+	retired_scene_targets: Vec<Retired<SceneTarget>>,
 	descriptor_pool: vk::DescriptorPool,
 	command_pool: vk::CommandPool,
 	frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
 	instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
-	in_flight_frames: [InFlightFrame; IN_FLIGHT_FRAMES_COUNT],
+	in_flight_frames: Vec<InFlightFrame>,
 	current_in_flight_frame_index: usize,
+	submitted_frame_count: usize,
+	frame_sync: FrameSync,
 	mesh_resources: MeshRenderSystem,
-	text_resources: TextRenderSystem
+	text_resources: TextRenderSystem,
+	texture_table: TextureTable,
+	texture_store: TextureStore,
+	// White, untextured quads like the input fields' carets are drawn with it tinted
+	solid_texture: Texture,
+	sprite_resources: SpriteRenderSystem,
+	ssao_resources: SsaoRenderSystem,
+	post_process_resources: PostProcessRenderSystem,
+	motion_vector_resources: MotionVectorRenderSystem,
+	taa_resources: TaaRenderSystem,
+	deferred_resources: DeferredRenderSystem,
+	refraction_resources: RefractionRenderSystem,
+	reflection_resources: ReflectionRenderSystem,
+	particle_resources: ParticleRenderSystem,
+	trail_resources: TrailRenderSystem,
+	ssao_enabled: bool,
+	motion_blur_strength: f32,
+	taa_enabled: bool,
+	deferred_enabled: bool,
+	depth_prepass_enabled: bool,
+	vsync: bool,
+	occlusion_culler: OcclusionCuller,
+	occlusion_culling_enabled: bool,
+	render_scale: f32,
+	fixed_aspect_ratio: Option<f32>,
+	dynamic_resolution: Option<DynamicResolution>,
+	scene_pacer: ScenePacer,
+	scene_rendered: bool,
+	timestamp_query_pool: Option<vk::QueryPool>,
+	gpu_frame_time: Option<f32>,
+	stats: RenderStats,
+	cpu_timings: [(FramePhase, Duration); 3],
+	light_clusters: LightClusters,
+	frame_capturer: FrameCapturer,
+	breadcrumbs: Option<Breadcrumbs>,
+	wind: Wind,
+	reflection_plane: Option<Plane>,
+	// Set when the last frame skipped text because its font wasn't part of the last font submission
+	fonts_need_submission: bool
 }
 
 struct Swapchain {
 	extension: khr::Swapchain,
 	handle: vk::SwapchainKHR,
 	extent: vk::Extent2D,
-	depth_image_resources: DepthImageResources,
-	frames: Vec<SwapchainFrame>
+	frames: Vec<SwapchainFrame>,
+	capture_supported: bool
+}
+
+struct SceneTarget {
+	extent: vk::Extent2D,
+	color_image_resources: ImageResources,
+	depth_image_resources: ImageResources,
+	framebuffer: vk::Framebuffer,
+	ssao_target: SsaoTarget,
+	motion_vector_target: MotionVectorTarget,
+	taa_target: TaaTarget,
+	deferred_target: DeferredTarget,
+	refraction_target: RefractionTarget,
+	reflection_target: ReflectionTarget
+}
+
+struct Retired<T> {
+	resource: T,
+	retired_frame_count: usize
 }
 
-struct DepthImageResources {
+struct ImageResources {
 	image: vk::Image,
 	image_view: vk::ImageView,
-	memory: vk::DeviceMemory
+	memory: vk::DeviceMemory,
+	// None for images aliasing another image's memory
+	_allocation: Option<Allocation>
 }
 
 struct SwapchainFrame {
+	image: vk::Image,
 	image_view: vk::ImageView,
 	framebuffer: vk::Framebuffer,
-	fence: vk::Fence
+	frame_number: u64
 }
 
 struct InFlightFrame {
 	image_available: vk::Semaphore,
 	render_finished: vk::Semaphore,
-	fence: vk::Fence,
 	frame_data_descriptor_set: vk::DescriptorSet,
 	primary_command_buffer: vk::CommandBuffer,
 	frame_data_buffer: Buffer,
 	instance_data_buffer: Buffer,
+	light_data_buffer: Buffer,
 	line_instance_data_resources: InstanceDataResources,
 	basic_instance_data_resources: InstanceDataResources,
 	normal_instance_data_resources: InstanceDataResources,
 	lambert_instance_data_resources: InstanceDataResources,
+	lightmapped_instance_data_resources: InstanceDataResources,
+	refractive_instance_data_resources: InstanceDataResources,
+	reflective_instance_data_resources: InstanceDataResources,
+	foliage_instance_data_resources: InstanceDataResources,
 	text_instance_data_resources: InstanceDataResources,
-	index_arrays_offset: usize,
+	text_command_buffer: vk::CommandBuffer,
+	timestamps_written: bool
 }
 
 struct InstanceDataResources {
 	descriptor_set: vk::DescriptorSet,
-	secondary_command_buffer: vk::CommandBuffer,
 	array_offset: usize,
 	array_size: usize
 }
 
-fn create_shader_module(logical_device: &ash::Device, filename: &str) -> vk::ShaderModule {
-	let mut file_path = String::from("target/shaders/");
-	file_path.push_str(filename);
+// Everything a frame is rendered from
+#[derive(Clone, Copy)]
+pub struct RenderScene<'a> {
+	pub camera: &'a Camera,
+	pub light_components: &'a ComponentList<Light>,
+	pub geometries: &'a Pool<Geometry3D>,
+	pub mesh_components: &'a MultiComponentList<Mesh>,
+	pub transform3d_components: &'a Transform3DComponentList,
+	pub trail_components: &'a ComponentList<Trail>,
+	pub blob_shadow_components: &'a ComponentList<BlobShadow>,
+	pub fonts: &'a Pool<Font>,
+	pub text_components: &'a TextComponentList,
+	pub transform2d_components: &'a Transform2DComponentList,
+	pub textures: &'a Pool<Texture>,
+	pub animated_sprite_components: &'a ComponentList<AnimatedSprite>,
+	pub tilemap_components: &'a ComponentList<Tilemap>,
+	pub input_field_components: &'a ComponentList<InputField>
+}
 
-	let mut file = File::open(file_path).unwrap();
-	let file_contents = ash::util::read_spv(&mut file).unwrap();
+// What the frame data buffer holds, the reflection has its own from the reflected camera
+#[derive(Clone, Copy)]
+struct FrameData {
+	projection_matrix: Matrix4,
+	inverse_view_matrix: Matrix4,
+	ambient_light: Vector3,
+	point_light_count: u32,
+	cluster_grid_size: [u32; 3],
+	cluster_depth_parameters: [f32; 2],
+	wind_direction: Vector3,
+	wind_parameters: [f32; 6]
+}
+
+// Where an instance data array is in the instance data buffer
+#[derive(Clone, Copy)]
+struct InstanceDataArray {
+	offset: usize,
+	size: usize
+}
+
+// The instance data array of each material and of text
+struct InstanceDataLayout {
+	line: InstanceDataArray,
+	basic: InstanceDataArray,
+	normal: InstanceDataArray,
+	lambert: InstanceDataArray,
+	lightmapped: InstanceDataArray,
+	refractive: InstanceDataArray,
+	reflective: InstanceDataArray,
+	foliage: InstanceDataArray,
+	text: InstanceDataArray
+}
+
+fn create_shader_module(logical_device: &ash::Device, vfs: &Vfs, filename: &str) -> vk::ShaderModule {
+	let path = format!("shaders/{}", filename);
+	let bytes = vfs.read(&path).unwrap_or_else(|e| panic!("Cannot load shader {}\n{}", path, e));
+	let file_contents = ash::util::read_spv(&mut Cursor::new(bytes)).unwrap();
 
 	let create_info = vk::ShaderModuleCreateInfo::builder()
 		.code(&file_contents);
@@ -96,211 +324,826 @@ fn create_shader_module(logical_device: &ash::Device, filename: &str) -> vk::Sha
 	unsafe { logical_device.create_shader_module(&create_info, None) }.unwrap()
 }
 
+impl Swapchain {
+	fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			self.extension.destroy_swapchain(self.handle, None);
+
+			for frame in &self.frames {
+				logical_device.destroy_image_view(frame.image_view, None);
+				logical_device.destroy_framebuffer(frame.framebuffer, None);
+			}
+		}
+	}
+}
+
+impl SceneTarget {
+	fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_framebuffer(self.framebuffer, None);
+			self.color_image_resources.drop(logical_device);
+			self.depth_image_resources.drop(logical_device);
+		}
+
+		self.ssao_target.drop(logical_device);
+		self.motion_vector_target.drop(logical_device);
+		self.taa_target.drop(logical_device);
+		self.deferred_target.drop(logical_device);
+		self.refraction_target.drop(logical_device);
+		self.reflection_target.drop(logical_device);
+	}
+}
+
+impl<T> Retired<T> {
+	// Every frame submitted before the resource was retired has been waited on once another in flight frames count - 1 frames have been submitted
+	fn is_unused(&self, submitted_frame_count: usize, in_flight_frames_count: usize) -> bool {
+		submitted_frame_count + 1 >= self.retired_frame_count + in_flight_frames_count
+	}
+}
+
+impl ImageResources {
+	unsafe fn drop(&self, logical_device: &ash::Device) {
+		logical_device.destroy_image_view(self.image_view, None);
+		logical_device.destroy_image(self.image, None);
+		logical_device.free_memory(self.memory, None);
+	}
+}
+
+fn scale_extent(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+	vk::Extent2D::builder()
+		.width(max(1, (extent.width as f32 * scale).round() as u32))
+		.height(max(1, (extent.height as f32 * scale).round() as u32))
+		.build()
+}
+
+// The regions of the extent on either side of the viewport, either above and below it or left and right of it
+fn letterbox_bars(extent: vk::Extent2D, viewport: vk::Rect2D) -> Vec<vk::Rect2D> {
+	let rect = |x: u32, y: u32, width: u32, height: u32| vk::Rect2D::builder()
+		.offset(vk::Offset2D::builder().x(x as i32).y(y as i32).build())
+		.extent(vk::Extent2D::builder().width(width).height(height).build())
+		.build();
+
+	let x = viewport.offset.x as u32;
+	let y = viewport.offset.y as u32;
+	let right = x + viewport.extent.width;
+	let bottom = y + viewport.extent.height;
+
+	let bars = [
+		rect(0, 0, x, extent.height),
+		rect(right, 0, extent.width - right, extent.height),
+		rect(0, 0, extent.width, y),
+		rect(0, bottom, extent.width, extent.height - bottom)
+	];
+
+	bars.iter().filter(|bar| bar.extent.width > 0 && bar.extent.height > 0).copied().collect()
+}
+
+// The part of the extent inside the clip rect, scissors can't be negative or reach past the framebuffer
+fn clip_rect_scissor(min: &Vector2, max: &Vector2, extent: vk::Extent2D) -> vk::Rect2D {
+	let x = min.x.clamp(0.0, extent.width as f32) as u32;
+	let y = min.y.clamp(0.0, extent.height as f32) as u32;
+	let right = max.x.clamp(x as f32, extent.width as f32) as u32;
+	let bottom = max.y.clamp(y as f32, extent.height as f32) as u32;
+
+	vk::Rect2D::builder()
+		.offset(vk::Offset2D::builder().x(x as i32).y(y as i32).build())
+		.extent(vk::Extent2D::builder().width(right - x).height(bottom - y).build())
+		.build()
+}
+
+impl FrameData {
+	fn write(&self, logical_device: &ash::Device, frame_data_buffer: &Buffer) {
+		let range = vk::MappedMemoryRange::builder()
+			.memory(frame_data_buffer.memory)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		unsafe {
+			let frame_data_buffer_ptr = logical_device.map_memory(frame_data_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+
+			copy_nonoverlapping(self.projection_matrix.elements.as_ptr(), frame_data_buffer_ptr as *mut [f32; 4], 4);
+			copy_nonoverlapping(self.inverse_view_matrix.elements.as_ptr(), frame_data_buffer_ptr.add(16 * 4) as *mut [f32; 4], 4);
+			copy_nonoverlapping(&self.ambient_light as *const Vector3, frame_data_buffer_ptr.add(32 * 4) as *mut Vector3, 1);
+			copy_nonoverlapping(&self.point_light_count as *const u32, frame_data_buffer_ptr.add(35 * 4) as *mut u32, 1);
+			copy_nonoverlapping(self.cluster_grid_size.as_ptr(), frame_data_buffer_ptr.add(36 * 4) as *mut u32, 3);
+			copy_nonoverlapping(self.cluster_depth_parameters.as_ptr(), frame_data_buffer_ptr.add(39 * 4) as *mut f32, 2);
+			copy_nonoverlapping(&self.wind_direction as *const Vector3, frame_data_buffer_ptr.add(44 * 4) as *mut Vector3, 1);
+			copy_nonoverlapping(self.wind_parameters.as_ptr(), frame_data_buffer_ptr.add(47 * 4) as *mut f32, self.wind_parameters.len());
+
+			logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+			logical_device.unmap_memory(frame_data_buffer.memory);
+		}
+	}
+}
+
 impl InFlightFrame {
-	#[allow(clippy::clippy::too_many_arguments)]
-	fn update_descriptor_sets(
-		&mut self,
-		logical_device: &ash::Device,
-		line_instance_data_array_offset: usize,
-		line_instance_data_array_size: usize,
-		basic_instance_data_array_offset: usize,
-		basic_instance_data_array_size: usize,
-		normal_instance_data_array_offset: usize,
-		normal_instance_data_array_size: usize,
-		lambert_instance_data_array_offset: usize,
-		lambert_instance_data_array_size: usize,
-		text_instance_data_array_offset: usize,
-		text_instance_data_array_size: usize,
-		index_arrays_offset: usize)
-	{
-		// Line
-		let line_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
-			.buffer(self.instance_data_buffer.handle)
-			.offset(line_instance_data_array_offset as u64)
-			.range(max(1, line_instance_data_array_size) as u64);
-		let line_descriptor_buffer_infos = [line_descriptor_buffer_info.build()];
-
-		let line_write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.line_instance_data_resources.descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-			.buffer_info(&line_descriptor_buffer_infos);
-		
-		// Basic
-		let basic_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
-			.buffer(self.instance_data_buffer.handle)
-			.offset(basic_instance_data_array_offset as u64)
-			.range(max(1, basic_instance_data_array_size) as u64);
-		let basic_descriptor_buffer_infos = [basic_descriptor_buffer_info.build()];
-
-		let basic_write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.basic_instance_data_resources.descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-			.buffer_info(&basic_descriptor_buffer_infos);
-		
-		// Normal
-		let normal_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
-			.buffer(self.instance_data_buffer.handle)
-			.offset(normal_instance_data_array_offset as u64)
-			.range(max(1, normal_instance_data_array_size) as u64);
-		let normal_descriptor_buffer_infos = [normal_descriptor_buffer_info.build()];
-
-		let normal_write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.normal_instance_data_resources.descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-			.buffer_info(&normal_descriptor_buffer_infos);
-		
-		// Lambert
-		let lambert_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
-			.buffer(self.instance_data_buffer.handle)
-			.offset(lambert_instance_data_array_offset as u64)
-			.range(max(1, lambert_instance_data_array_size) as u64);
-		let lambert_descriptor_buffer_infos = [lambert_descriptor_buffer_info.build()];
-
-		let lambert_write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.lambert_instance_data_resources.descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-			.buffer_info(&lambert_descriptor_buffer_infos);
-		
-		// Text
-		let text_descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
-			.buffer(self.instance_data_buffer.handle)
-			.offset(text_instance_data_array_offset as u64)
-			.range(max(1, text_instance_data_array_size) as u64);
-		let text_descriptor_buffer_infos = [text_descriptor_buffer_info.build()];
-
-		let text_write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.text_instance_data_resources.descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-			.buffer_info(&text_descriptor_buffer_infos);
-		
-		// Update descriptor sets
-		let write_descriptor_sets = [
-			line_write_descriptor_set.build(),
-			basic_write_descriptor_set.build(),
-			normal_write_descriptor_set.build(),
-			lambert_write_descriptor_set.build(),
-			text_write_descriptor_set.build()
+	fn update_descriptor_sets(&mut self, logical_device: &ash::Device, layout: &InstanceDataLayout) {
+		let instance_data_buffer = self.instance_data_buffer.handle;
+		let resources_and_arrays = [
+			(&mut self.line_instance_data_resources, layout.line),
+			(&mut self.basic_instance_data_resources, layout.basic),
+			(&mut self.normal_instance_data_resources, layout.normal),
+			(&mut self.lambert_instance_data_resources, layout.lambert),
+			(&mut self.lightmapped_instance_data_resources, layout.lightmapped),
+			(&mut self.refractive_instance_data_resources, layout.refractive),
+			(&mut self.reflective_instance_data_resources, layout.reflective),
+			(&mut self.foliage_instance_data_resources, layout.foliage),
+			(&mut self.text_instance_data_resources, layout.text)
 		];
+
+		// The buffer infos have to outlive the writes that point to them
+		let descriptor_buffer_infos: Vec<[vk::DescriptorBufferInfo; 1]> = resources_and_arrays.iter()
+			.map(|(_, array)| [vk::DescriptorBufferInfo::builder()
+				.buffer(instance_data_buffer)
+				.offset(array.offset as u64)
+				.range(max(1, array.size) as u64)
+				.build()])
+			.collect();
+
+		let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = resources_and_arrays.iter()
+			.zip(&descriptor_buffer_infos)
+			.map(|((resources, _), descriptor_buffer_infos)| vk::WriteDescriptorSet::builder()
+				.dst_set(resources.descriptor_set)
+				.dst_binding(0)
+				.dst_array_element(0)
+				.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+				.buffer_info(descriptor_buffer_infos)
+				.build())
+			.collect();
 		
 		unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
 
 		// Set offsets and sizes
-		self.line_instance_data_resources.array_offset = line_instance_data_array_offset;
-		self.line_instance_data_resources.array_size = line_instance_data_array_size;
+		for (resources, array) in resources_and_arrays {
+			resources.array_offset = array.offset;
+			resources.array_size = array.size;
+		}
+	}
 
-		self.basic_instance_data_resources.array_offset = basic_instance_data_array_offset;
-		self.basic_instance_data_resources.array_size = basic_instance_data_array_size;
+	fn update_light_data(&mut self, context: &Context, point_lights: &[PointLightData], light_clusters: &LightClusters) {
+		write_light_data(context, &mut self.light_data_buffer, self.frame_data_descriptor_set, point_lights, &light_clusters.cluster_ranges, &light_clusters.light_indices);
+	}
+}
 
-		self.normal_instance_data_resources.array_offset = normal_instance_data_array_offset;
-		self.normal_instance_data_resources.array_size = normal_instance_data_array_size;
+// The point lights, cluster ranges and light indices are bound as three storage buffers at aligned offsets into the
+// light data buffer. The frame that last used the buffer has been waited on so the descriptors are updated every frame.
+fn write_light_data(
+	context: &Context,
+	light_data_buffer: &mut Buffer,
+	frame_data_descriptor_set: vk::DescriptorSet,
+	point_lights: &[PointLightData],
+	cluster_ranges: &[[u32; 2]],
+	light_indices: &[u32])
+{
+	let logical_device = &context.logical_device;
+	let alignment = context.physical_device.min_storage_buffer_offset_alignment as usize;
+	let align = |offset: usize| offset + (alignment - offset % alignment) % alignment;
+
+	let point_lights_size = size_of_val(point_lights);
+	let cluster_ranges_offset = align(point_lights_size);
+	let cluster_ranges_size = size_of_val(cluster_ranges);
+	let light_indices_offset = align(cluster_ranges_offset + cluster_ranges_size);
+	let light_indices_size = size_of_val(light_indices);
+	let buffer_size = (light_indices_offset + max(1, light_indices_size)) as u64;
+
+	if buffer_size > light_data_buffer.capacity {
+		light_data_buffer.reallocate(context, buffer_size);
+	}
 
-		self.lambert_instance_data_resources.array_offset = lambert_instance_data_array_offset;
-		self.lambert_instance_data_resources.array_size = lambert_instance_data_array_size;
+	unsafe {
+		let light_data_buffer_ptr = logical_device.map_memory(light_data_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+		copy_nonoverlapping(point_lights.as_ptr(), light_data_buffer_ptr as *mut PointLightData, point_lights.len());
+		copy_nonoverlapping(cluster_ranges.as_ptr(), light_data_buffer_ptr.add(cluster_ranges_offset) as *mut [u32; 2], cluster_ranges.len());
+		copy_nonoverlapping(light_indices.as_ptr(), light_data_buffer_ptr.add(light_indices_offset) as *mut u32, light_indices.len());
 
-		self.text_instance_data_resources.array_offset = text_instance_data_array_offset;
-		self.text_instance_data_resources.array_size = text_instance_data_array_size;
+		let range = vk::MappedMemoryRange::builder()
+			.memory(light_data_buffer.memory)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
 
-		self.index_arrays_offset = index_arrays_offset;
+		logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+		logical_device.unmap_memory(light_data_buffer.memory);
 	}
+
+	let descriptor_buffer_infos = [
+		(0, point_lights_size),
+		(cluster_ranges_offset, cluster_ranges_size),
+		(light_indices_offset, light_indices_size)
+	].map(|(offset, size)| [vk::DescriptorBufferInfo::builder()
+		.buffer(light_data_buffer.handle)
+		.offset(offset as u64)
+		.range(max(1, size) as u64)
+		.build()]);
+
+	let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = descriptor_buffer_infos.iter().enumerate()
+		.map(|(index, descriptor_buffer_info)| vk::WriteDescriptorSet::builder()
+			.dst_set(frame_data_descriptor_set)
+			.dst_binding(index as u32 + 1)
+			.dst_array_element(0)
+			.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+			.buffer_info(descriptor_buffer_info)
+			.build())
+		.collect();
+
+	unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
 }
 
 impl RenderSystem {
 	pub fn new(glfw: &glfw::Glfw, window: &glfw::Window) -> Self {
-		let context = Context::new(glfw, window);
-		let render_pass = create_render_pass(&context);
+		Self::with_settings(glfw, window, RenderSystemSettings::default())
+	}
+
+	pub fn with_settings(glfw: &glfw::Glfw, window: &glfw::Window, settings: RenderSystemSettings) -> Self {
+		let in_flight_frames_count = settings.in_flight_frames_count;
+		assert!(in_flight_frames_count == 2 || in_flight_frames_count == 3, "Cannot have {} frames in flight, it must be 2 or 3", in_flight_frames_count);
+
+		let context = Context::new(glfw, window, settings.stencil_enabled);
+		let vfs = settings.vfs;
+		let scene_render_pass = create_scene_render_pass(&context, false);
+		let scene_continuation_render_pass = create_scene_render_pass(&context, true);
+		let overlay_render_pass = create_overlay_render_pass(&context);
 		let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
-		let swapchain = create_swapchain(&context, framebuffer_width as u32, framebuffer_height as u32, render_pass);
-		let descriptor_pool = create_descriptor_pool(&context);
+		let swapchain = create_swapchain(&context, framebuffer_width as u32, framebuffer_height as u32, overlay_render_pass, vk::SwapchainKHR::null(), settings.vsync);
+		let descriptor_pool = create_descriptor_pool(&context, in_flight_frames_count);
 		let command_pool = create_command_pool(&context);
 		let frame_data_descriptor_set_layout = create_frame_data_descriptor_set_layout(&context.logical_device);
 		let instance_data_descriptor_set_layout = create_instance_data_descriptor_set_layout(&context.logical_device);
-		let in_flight_frames = create_in_flight_frames(&context, descriptor_pool, command_pool, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout);
-		let mesh_resources = MeshRenderSystem::new(&context.logical_device, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout, swapchain.extent, render_pass, descriptor_pool);
-		let text_renderer = TextRenderSystem::new(&context.logical_device, instance_data_descriptor_set_layout, swapchain.extent, render_pass, descriptor_pool);
+		let in_flight_frames = create_in_flight_frames(&context, descriptor_pool, command_pool, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout, in_flight_frames_count);
+		let frame_sync = FrameSync::new(&context, in_flight_frames_count);
+		let ssao_resources = SsaoRenderSystem::new(&context, &vfs, frame_data_descriptor_set_layout, instance_data_descriptor_set_layout, descriptor_pool, command_pool, in_flight_frames_count);
+		let motion_vector_resources = MotionVectorRenderSystem::new(&context, &vfs, descriptor_pool, in_flight_frames_count);
+		let taa_resources = TaaRenderSystem::new(&context, &vfs, descriptor_pool, in_flight_frames_count);
+		let deferred_resources = DeferredRenderSystem::new(
+			&context,
+			&vfs,
+			frame_data_descriptor_set_layout,
+			instance_data_descriptor_set_layout,
+			ssao_resources.ambient_occlusion_descriptor_set_layout,
+			scene_render_pass,
+			descriptor_pool,
+			command_pool,
+			in_flight_frames_count);
+		let refraction_resources = RefractionRenderSystem::new(&context.logical_device, descriptor_pool, in_flight_frames_count);
+		let texture_table = TextureTable::new(&context, FALLBACK_MAX_FONTS, FALLBACK_MAX_TEXTURES);
+		let mut texture_store = TextureStore::new(&context, &texture_table, in_flight_frames_count);
+		let mut solid_texture = Texture::from_color([255; 4]);
+		texture_store.submit(&context, &texture_table, &mut solid_texture).unwrap();
+		let reflection_resources = ReflectionRenderSystem::new(
+			&context,
+			frame_data_descriptor_set_layout,
+			ssao_resources.ambient_occlusion_descriptor_set_layout,
+			texture_store.image_view(&solid_texture).unwrap(),
+			descriptor_pool,
+			command_pool,
+			in_flight_frames_count);
+		let scene_target = create_scene_target(&context, swapchain.extent, scene_render_pass, &ssao_resources, &motion_vector_resources, &taa_resources, &deferred_resources, &refraction_resources, &reflection_resources);
+		let mesh_resources = MeshRenderSystem::new(
+			&context,
+			&vfs,
+			frame_data_descriptor_set_layout,
+			instance_data_descriptor_set_layout,
+			ssao_resources.ambient_occlusion_descriptor_set_layout,
+			refraction_resources.descriptor_set_layout,
+			reflection_resources.descriptor_set_layout,
+			scene_render_pass,
+			descriptor_pool,
+			command_pool,
+			in_flight_frames_count);
+		let particle_resources = ParticleRenderSystem::new(
+			&context,
+			&vfs,
+			frame_data_descriptor_set_layout,
+			scene_render_pass,
+			command_pool,
+			descriptor_pool,
+			in_flight_frames_count,
+			settings.max_particles);
+		let trail_resources = TrailRenderSystem::new(&context.logical_device, &vfs, frame_data_descriptor_set_layout, scene_render_pass, command_pool, in_flight_frames_count);
+		let sprite_resources = SpriteRenderSystem::new(
+			&context.logical_device,
+			&vfs,
+			texture_table.descriptor_set_layout,
+			texture_table.capacity,
+			overlay_render_pass,
+			descriptor_pool,
+			command_pool,
+			in_flight_frames_count);
+		let text_renderer = TextRenderSystem::new(&context.logical_device, &vfs, instance_data_descriptor_set_layout, &texture_table, swapchain.extent, overlay_render_pass, descriptor_pool, in_flight_frames_count);
+		let post_process_resources = PostProcessRenderSystem::new(&context, &vfs, overlay_render_pass, descriptor_pool, command_pool, in_flight_frames_count);
+
+		let breadcrumbs = settings.breadcrumbs_enabled.then(|| Breadcrumbs::new(&context, in_flight_frames_count));
+
+		let timestamp_query_pool = if context.physical_device.timestamps_supported {
+			Some(create_timestamp_query_pool(&context.logical_device, in_flight_frames_count))
+		}
+		else {
+			None
+		};
 
 		Self {
 			context,
-			render_pass,
+			vfs,
+			scene_render_pass,
+			scene_continuation_render_pass,
+			overlay_render_pass,
 			swapchain,
+			scene_target,
+			retired_swapchains: vec![],
+			retired_scene_targets: vec![],
 			descriptor_pool,
 			command_pool,
 			frame_data_descriptor_set_layout,
 			instance_data_descriptor_set_layout,
 			in_flight_frames,
 			current_in_flight_frame_index: 0,
+			submitted_frame_count: 0,
+			frame_sync,
 			mesh_resources,
-			text_resources: text_renderer
+			text_resources: text_renderer,
+			texture_table,
+			texture_store,
+			solid_texture,
+			sprite_resources,
+			ssao_resources,
+			post_process_resources,
+			motion_vector_resources,
+			taa_resources,
+			deferred_resources,
+			refraction_resources,
+			reflection_resources,
+			particle_resources,
+			trail_resources,
+			ssao_enabled: true,
+			motion_blur_strength: 0.0,
+			taa_enabled: false,
+			deferred_enabled: false,
+			depth_prepass_enabled: false,
+			vsync: settings.vsync,
+			occlusion_culler: OcclusionCuller::new(OCCLUSION_BUFFER_WIDTH, OCCLUSION_BUFFER_HEIGHT),
+			occlusion_culling_enabled: false,
+			render_scale: 1.0,
+			fixed_aspect_ratio: None,
+			dynamic_resolution: None,
+			scene_pacer: ScenePacer::new(),
+			scene_rendered: false,
+			timestamp_query_pool,
+			gpu_frame_time: None,
+			stats: RenderStats::default(),
+			cpu_timings: [(FramePhase::Record, Duration::ZERO), (FramePhase::Submit, Duration::ZERO), (FramePhase::PresentWait, Duration::ZERO)],
+			light_clusters: LightClusters::new(),
+			frame_capturer: FrameCapturer::new(),
+			breadcrumbs,
+			wind: Wind::new(),
+			reflection_plane: None,
+			fonts_need_submission: false
 		}
 	}
 
+	// Where the render system reads shaders and font files from, the game can read its other assets through it too
+	pub fn vfs(&self) -> &Vfs {
+		&self.vfs
+	}
+
+	pub fn in_flight_frames_count(&self) -> usize {
+		self.in_flight_frames.len()
+	}
+
 	pub fn get_swapchain_extent(&self) -> (u32, u32) {
 		let extent = &self.swapchain.extent;
 		(extent.width, extent.height)
 	}
 
 	pub fn recreate_swapchain(&mut self, framebuffer_width: i32, framebuffer_height: i32) -> (u32, u32) {
+		// The old swapchain is handed to the new one so the presentation engine can reuse its resources, it's then retired
+		// instead of destroyed because in flight frames may still be rendering to or presenting its images
+		let swapchain = create_swapchain(&self.context, framebuffer_width as u32, framebuffer_height as u32, self.overlay_render_pass, self.swapchain.handle, self.vsync);
+		let old_swapchain = std::mem::replace(&mut self.swapchain, swapchain);
+
+		self.retired_swapchains.push(Retired {
+			resource: old_swapchain,
+			retired_frame_count: self.submitted_frame_count
+		});
+
+		self.recreate_scene_target();
+		self.text_resources.handle_swapchain_recreation(self.swapchain.extent);
+		println!("Swapchain recreated");
+
+		let extent = &self.swapchain.extent;
+		(extent.width, extent.height)
+	}
+
+	// The region of the swapchain image the scene is drawn to, the rest is covered by bars when the camera keeps a fixed
+	// aspect ratio
+	fn scene_viewport(&self) -> vk::Rect2D {
+		let (x, y, width, height) = letterbox_viewport(self.fixed_aspect_ratio, self.swapchain.extent.width, self.swapchain.extent.height);
+
+		vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(x as i32).y(y as i32).build())
+			.extent(vk::Extent2D::builder().width(width).height(height).build())
+			.build()
+	}
+
+	fn recreate_scene_target(&mut self) {
+		let extent = scale_extent(self.scene_viewport().extent, self.render_scale);
+		let scene_target = create_scene_target(&self.context, extent, self.scene_render_pass, &self.ssao_resources, &self.motion_vector_resources, &self.taa_resources, &self.deferred_resources, &self.refraction_resources, &self.reflection_resources);
+		let old_scene_target = std::mem::replace(&mut self.scene_target, scene_target);
+
+		self.retired_scene_targets.push(Retired {
+			resource: old_scene_target,
+			retired_frame_count: self.submitted_frame_count
+		});
+
+		// The new target has nothing in it yet
+		self.scene_pacer.require_frame();
+	}
+
+	fn destroy_unused_retired_resources(&mut self) {
 		let logical_device = &self.context.logical_device;
+		let submitted_frame_count = self.submitted_frame_count;
+		let in_flight_frames_count = self.in_flight_frames.len();
 
-		unsafe {
-			logical_device.device_wait_idle().unwrap();
+		self.retired_swapchains.retain(|retired_swapchain| {
+			if retired_swapchain.is_unused(submitted_frame_count, in_flight_frames_count) {
+				retired_swapchain.resource.drop(logical_device);
+				false
+			}
+			else {
+				true
+			}
+		});
 
-			self.swapchain.extension.destroy_swapchain(self.swapchain.handle, None);
-			logical_device.destroy_image(self.swapchain.depth_image_resources.image, None);
-			logical_device.destroy_image_view(self.swapchain.depth_image_resources.image_view, None);
-			logical_device.free_memory(self.swapchain.depth_image_resources.memory, None);
+		self.retired_scene_targets.retain(|retired_scene_target| {
+			if retired_scene_target.is_unused(submitted_frame_count, in_flight_frames_count) {
+				retired_scene_target.resource.drop(logical_device);
+				false
+			}
+			else {
+				true
+			}
+		});
 
-			for frame in &self.swapchain.frames {
-				logical_device.destroy_image_view(frame.image_view, None);
-				logical_device.destroy_framebuffer(frame.framebuffer, None);
+		self.post_process_resources.destroy_unused_retired_luts(logical_device, submitted_frame_count, in_flight_frames_count);
+		self.texture_store.destroy_unused_retired_images(logical_device, submitted_frame_count, in_flight_frames_count);
+	}
+
+	pub fn render_scale(&self) -> f32 {
+		self.render_scale
+	}
+
+	pub fn set_render_scale(&mut self, render_scale: f32) {
+		assert!((MIN_RENDER_SCALE..=MAX_RENDER_SCALE).contains(&render_scale), "Render scale {} is outside of the allowed range {} to {}", render_scale, MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+
+		// A fixed render scale overrides dynamic resolution
+		self.dynamic_resolution = None;
+
+		if (render_scale - self.render_scale).abs() > f32::EPSILON {
+			self.render_scale = render_scale;
+			self.recreate_scene_target();
+			println!("Render scale set to {}", render_scale);
+		}
+	}
+
+	pub fn gpu_frame_time(&self) -> Option<Duration> {
+		self.gpu_frame_time.map(Duration::from_secs_f32)
+	}
+
+	// What was drawn for the camera of the last frame which rendered the scene and the commands recorded to draw it
+	pub fn stats(&self) -> RenderStats {
+		self.stats
+	}
+
+	// The device memory allocated so far by category and by heap. A warning is printed when a heap gets close to full, a
+	// category which keeps growing while the scene doesn't change, such as fonts being submitted repeatedly, points at a leak.
+	pub fn memory_usage(&self) -> MemoryUsage {
+		self.context.memory_budget.usage()
+	}
+
+	// How long the CPU spent recording, submitting and waiting on the GPU and presentation engine in the last rendered frame
+	pub fn cpu_timings(&self) -> [(FramePhase, Duration); 3] {
+		self.cpu_timings
+	}
+
+	pub fn enable_dynamic_resolution(&mut self, target_frame_time: Duration) {
+		assert!(self.timestamp_query_pool.is_some(), "Cannot enable dynamic resolution because the graphics queue does not support timestamps");
+		self.dynamic_resolution = Some(DynamicResolution::new(target_frame_time.as_secs_f32()));
+	}
+
+	pub fn disable_dynamic_resolution(&mut self) {
+		self.dynamic_resolution = None;
+	}
+
+	pub fn max_scene_frame_rate(&self) -> Option<f32> {
+		self.scene_pacer.max_frame_rate
+	}
+
+	// Renders the scene at most this many times a second while the overlay and text are still rendered every frame over the
+	// latest scene image, which keeps menus responsive when the scene is heavy. Lights, meshes and the camera are only read on
+	// the frames which render the scene. 0 freezes the scene until a scene frame is requested or it has to be rendered again
+	// such as after a resize, None renders it every frame.
+	pub fn set_max_scene_frame_rate(&mut self, max_frame_rate: Option<f32>) {
+		if let Some(max_frame_rate) = max_frame_rate {
+			assert!(max_frame_rate >= 0.0, "Max scene frame rate {} cannot be negative", max_frame_rate);
+		}
+
+		self.scene_pacer.max_frame_rate = max_frame_rate;
+	}
+
+	// Renders the scene next frame whatever the max scene frame rate
+	pub fn request_scene_frame(&mut self) {
+		self.scene_pacer.require_frame();
+	}
+
+	// Whether the last rendered frame rendered the scene or only the overlay
+	pub fn scene_rendered(&self) -> bool {
+		self.scene_rendered
+	}
+
+	fn read_gpu_frame_time(&mut self) {
+		let query_pool = match self.timestamp_query_pool {
+			Some(query_pool) => query_pool,
+			None => return
+		};
+
+		let in_flight_frame = &self.in_flight_frames[self.current_in_flight_frame_index];
+
+		if !in_flight_frame.timestamps_written {
+			return;
+		}
+
+		// The frame that last used this in flight frame has been waited on so its timestamps are available
+		let mut timestamps = [0u64; 2];
+		let first_query = self.current_in_flight_frame_index as u32 * 2;
+		unsafe { self.context.logical_device.get_query_pool_results(query_pool, first_query, 2, &mut timestamps, vk::QueryResultFlags::TYPE_64) }.unwrap();
+
+		let ticks = timestamps[1].saturating_sub(timestamps[0]);
+		let gpu_frame_time = ticks as f64 * self.context.physical_device.timestamp_period as f64 / 1_000_000_000.0;
+		self.gpu_frame_time = Some(gpu_frame_time as f32);
+
+		if let Some(dynamic_resolution) = &mut self.dynamic_resolution {
+			if let Some(render_scale) = dynamic_resolution.update(gpu_frame_time as f32, self.render_scale) {
+				self.render_scale = render_scale;
+				self.recreate_scene_target();
+				println!("Render scale changed to {}", render_scale);
 			}
 		}
+	}
 
-		self.swapchain = create_swapchain(&self.context, framebuffer_width as u32, framebuffer_height as u32, self.render_pass);
-		self.mesh_resources.handle_swapchain_recreation(&self.context.logical_device, self.swapchain.extent, self.render_pass);
-		self.text_resources.handle_swapchain_recreation(&self.context.logical_device, self.swapchain.extent, self.render_pass);
-		println!("Swapchain recreated");
+	pub fn ssao_enabled(&self) -> bool {
+		self.ssao_enabled
+	}
 
-		let extent = &self.swapchain.extent;
-		(extent.width, extent.height)
+	pub fn set_ssao_enabled(&mut self, enabled: bool) {
+		self.ssao_enabled = enabled;
+	}
+
+	pub fn motion_blur_strength(&self) -> f32 {
+		self.motion_blur_strength
+	}
+
+	// How much of the motion since the previous frame is blurred over, 1 blurs across all of it and 0 turns motion blur and
+	// the motion vector pass off
+	pub fn set_motion_blur_strength(&mut self, strength: f32) {
+		assert!(strength >= 0.0, "Motion blur strength {} cannot be negative", strength);
+
+		// Turning motion blur on needs velocities for the scene image being shown
+		if strength > 0.0 && self.motion_blur_strength == 0.0 {
+			self.scene_pacer.require_frame();
+		}
+
+		self.motion_blur_strength = strength;
+	}
+
+	pub fn taa_enabled(&self) -> bool {
+		self.taa_enabled
+	}
+
+	// Temporal anti-aliasing, it turns the motion vector pass on
+	pub fn set_taa_enabled(&mut self, enabled: bool) {
+		if enabled != self.taa_enabled {
+			self.scene_pacer.require_frame();
+		}
+
+		self.taa_enabled = enabled;
+	}
+
+	pub fn deferred_enabled(&self) -> bool {
+		self.deferred_enabled
+	}
+
+	// Draws opaque basic, normal and lambert meshes into a G-buffer and lights them in one full screen pass instead of
+	// shading every mesh, lines, lightmapped meshes and the other render layers are still drawn forward
+	pub fn set_deferred_enabled(&mut self, enabled: bool) {
+		self.deferred_enabled = enabled;
+	}
+
+	pub fn depth_prepass_enabled(&self) -> bool {
+		self.depth_prepass_enabled
+	}
+
+	// Draws the depth of the opaque meshes before shading them so each pixel is only shaded once
+	pub fn set_depth_prepass_enabled(&mut self, enabled: bool) {
+		self.depth_prepass_enabled = enabled;
+	}
+
+	pub fn vsync(&self) -> bool {
+		self.vsync
+	}
+
+	// Recreates the swapchain with the new present mode
+	pub fn set_vsync(&mut self, vsync: bool) {
+		if self.vsync != vsync {
+			self.vsync = vsync;
+			let extent = self.swapchain.extent;
+			self.recreate_swapchain(extent.width as i32, extent.height as i32);
+		}
+	}
+
+	pub fn occlusion_culling_enabled(&self) -> bool {
+		self.occlusion_culling_enabled
+	}
+
+	// Rasterizes the occluder meshes on the CPU and leaves out the instances hidden behind them, it only pays off when
+	// there's a lot hidden behind a few big occluders
+	pub fn set_occlusion_culling_enabled(&mut self, enabled: bool) {
+		self.occlusion_culling_enabled = enabled;
+	}
+
+	// Sways the foliage meshes, update it every frame to animate them
+	pub fn wind(&self) -> &Wind {
+		&self.wind
+	}
+
+	pub fn wind_mut(&mut self) -> &mut Wind {
+		&mut self.wind
+	}
+
+	pub fn reflection_plane(&self) -> Option<Plane> {
+		self.reflection_plane
+	}
+
+	// Reflective meshes show the scene mirrored across the plane, the scene is rendered a second time for it every frame the
+	// plane is set. Occlusion culling is skipped meanwhile since what's hidden from the camera may still be in the reflection.
+	pub fn set_reflection_plane(&mut self, plane: Option<Plane>) {
+		self.reflection_plane = plane;
+	}
+
+	// The emitters whose particles are simulated and drawn on the GPU
+	pub fn particle_emitters(&self) -> &Pool<ParticleEmitter> {
+		&self.particle_resources.emitters
+	}
+
+	pub fn particle_emitters_mut(&mut self) -> &mut Pool<ParticleEmitter> {
+		&mut self.particle_resources.emitters
+	}
+
+	// Spawns particles from the emitters, update it every frame to animate them
+	pub fn update_particles(&mut self, delta: f32) {
+		self.particle_resources.update(delta);
+	}
+
+	pub fn set_color_grading_lut(&mut self, lut: &ColorGradingLut) {
+		self.post_process_resources.set_lut(&self.context, lut, self.submitted_frame_count);
+	}
+
+	pub fn blend_to_color_grading_lut(&mut self, lut: &ColorGradingLut, duration: Duration) {
+		self.post_process_resources.blend_to_lut(&self.context, lut, duration, self.submitted_frame_count);
 	}
 
 	pub fn submit_static_geometries(&mut self, geometries: &mut Pool<Geometry3D>, handles: &[Handle]) {
-		self.mesh_resources.submit_static_geometries(&self.context, self.command_pool, geometries, handles);
+		self.mesh_resources.submit_static_geometries(&self.context, geometries, handles);
 		println!("Static meshes submitted");
 	}
 
-	pub fn submit_fonts(&mut self, fonts: &mut Pool<Font>) {
-		self.text_resources.submit_fonts(&self.context, self.command_pool, fonts);
+	// Only uploads the added geometries instead of resubmitting every static geometry, see MeshRenderSystem
+	pub fn update_static_geometries(&mut self, geometries: &mut Pool<Geometry3D>, added: &[Handle], removed: &[Handle]) {
+		self.mesh_resources.update_static_geometries(&self.context, geometries, added, removed);
+	}
+
+	// The lightmap sampled by every mesh with the lightmapped material, such as one made by the lightmap baker
+	pub fn submit_lightmap(&mut self, lightmap: &Lightmap) {
+		self.mesh_resources.submit_lightmap(&self.context, lightmap);
+		println!("Lightmap submitted");
+	}
+
+	// Without descriptor indexing only a few fonts fit in the texture table
+	pub fn max_fonts(&self) -> usize {
+		self.texture_table.font_capacity
+	}
+
+	// Textures share the texture table with the fonts, taking the slots after them. One is taken by the render system's own
+	// solid texture.
+	pub fn max_textures(&self) -> usize {
+		self.texture_table.texture_capacity()
+	}
+
+	// Sprites are only drawn with textures which have been submitted, submitting a texture again updates it
+	pub fn submit_texture(&mut self, texture: &mut Texture) -> Result<(), TextureSubmissionError> {
+		self.texture_store.submit(&self.context, &self.texture_table, texture)
+	}
+
+	// Copies the texture's pixels into its image before the next frame is drawn, such as for each frame of a video
+	pub fn update_texture(&mut self, texture: &Texture) {
+		self.texture_store.update(texture);
+	}
+
+	pub fn remove_texture(&mut self, texture: &mut Texture) {
+		self.texture_store.remove(&self.context.logical_device, &self.texture_table, texture, self.submitted_frame_count);
+	}
+
+	pub fn submit_fonts(&mut self, fonts: &mut Pool<Font>) -> Result<(), FontSubmissionError> {
+		self.text_resources.submit_fonts(&self.context, &self.vfs, &self.texture_table, fonts)?;
+		self.fonts_need_submission = false;
 		println!("Fonts submitted");
+		Ok(())
+	}
+
+	// Text is only drawn with fonts which are current, the others need the fonts to be submitted again
+	pub fn font_status(&self, font: &Font) -> SubmissionStatus {
+		SubmissionStatus::new(font.submission_generation(), self.text_resources.submission_generation)
+	}
+
+	// Whether text was skipped in the last frame because its font isn't current, submit the fonts again to draw it
+	pub fn fonts_need_submission(&self) -> bool {
+		self.fonts_need_submission
+	}
+
+	// Updating the static geometries keeps the ones already submitted current, submitting them replaces them all
+	pub fn static_geometry_status(&self, geometry: &Geometry3D) -> SubmissionStatus {
+		SubmissionStatus::new(geometry.submission_generation(), self.mesh_resources.static_geometry_submission_generation)
+	}
+
+	// The next frame rendered is read back from the swapchain and returned by take_frame_capture
+	pub fn request_frame_capture(&mut self) {
+		assert!(self.swapchain.capture_supported, "Cannot capture frames because the swapchain images cannot be copied from");
+
+		let format = self.context.surface.format.format;
+		assert!(
+			[vk::Format::B8G8R8A8_SRGB, vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_SRGB, vk::Format::R8G8B8A8_UNORM].contains(&format),
+			"Cannot capture frames because the swapchain format {:?} is not 8 bit RGBA or BGRA", format);
+
+		self.frame_capturer.request();
+	}
+
+	// Waits for the requested frame capture to finish rendering, returns None if it hasn't been rendered
+	pub fn take_frame_capture(&mut self) -> Option<FrameCapture> {
+		let format = self.context.surface.format.format;
+		let bgra = format == vk::Format::B8G8R8A8_SRGB || format == vk::Format::B8G8R8A8_UNORM;
+		self.frame_capturer.take(&self.context, bgra)
 	}
 
-	pub fn render(&mut self,
-		camera: &Camera,
-		light_components: &ComponentList<Light>,
-		geometries: &Pool<Geometry3D>,
-		mesh_components: &MultiComponentList<Mesh>,
-		transform3d_components: &Transform3DComponentList,
-		fonts: &Pool<Font>,
-		text_components: &TextComponentList,
-		transform2d_components: &Transform2DComponentList) -> bool
-	{
+	pub fn render(&mut self, scene: &RenderScene) -> bool {
+		let RenderScene {
+			camera,
+			light_components,
+			geometries,
+			mesh_components,
+			transform3d_components,
+			trail_components,
+			blob_shadow_components,
+			fonts,
+			text_components,
+			transform2d_components,
+			textures,
+			animated_sprite_components,
+			tilemap_components,
+			input_field_components
+		} = *scene;
+
+		// Wait for the frame that last used this in flight frame to finish
+		let render_start = Instant::now();
+		let frame_number = self.submitted_frame_count as u64 + 1;
+		match self.frame_sync.wait(&self.context.logical_device, frame_number.saturating_sub(self.in_flight_frames.len() as u64)) {
+			Err(WaitError::Device(e)) => device_failed(&self.breadcrumbs, "wait for an in flight frame", e),
+			Err(e) => println!("Could not wait for an in flight frame: {}", e),
+			Ok(()) => ()
+		}
+		let mut present_wait = render_start.elapsed();
+
+		// Destroy retired resources that are no longer used by any in flight frame
+		self.destroy_unused_retired_resources();
+
+		// Read how long the GPU took the last time this in flight frame was rendered and adjust the render scale
+		self.read_gpu_frame_time();
+
+		// The scene target matches the region of the window the camera renders to
+		if camera.fixed_aspect_ratio != self.fixed_aspect_ratio {
+			self.fixed_aspect_ratio = camera.fixed_aspect_ratio;
+			self.recreate_scene_target();
+		}
+
+		let scene_viewport = self.scene_viewport();
+
+		// When the scene isn't rendered this frame the overlay is drawn over the previous scene image
+		let render_scene = self.scene_pacer.update(render_start);
+
+		// Advance the color grading LUT transition
+		let lut_blend = self.post_process_resources.update_lut_blend(self.submitted_frame_count);
+
 		let logical_device = &self.context.logical_device;
 		let in_flight_frame = &mut self.in_flight_frames[self.current_in_flight_frame_index];
 		
-		// Wait for this in flight frame to become available
-		unsafe { logical_device.wait_for_fences(&[in_flight_frame.fence], true, std::u64::MAX) }.unwrap();
-		
 		// Acquire a swapchain image to render to
+		let acquire_start = Instant::now();
 		let result = unsafe {
 			self.swapchain.extension.acquire_next_image(self.swapchain.handle,
 				std::u64::MAX,
@@ -318,144 +1161,267 @@ impl RenderSystem {
 		let swapchain_frame = &mut self.swapchain.frames[image_index as usize];
 
 		// Wait for swapchain frame to become available
-		if swapchain_frame.fence != vk::Fence::null() {
-			unsafe { logical_device.wait_for_fences(&[swapchain_frame.fence], true, std::u64::MAX) }.unwrap();
+		match self.frame_sync.wait(logical_device, swapchain_frame.frame_number) {
+			Err(WaitError::Device(e)) => device_failed(&self.breadcrumbs, "wait for a swapchain frame", e),
+			Err(e) => println!("Could not wait for a swapchain frame: {}", e),
+			Ok(()) => ()
 		}
+		swapchain_frame.frame_number = frame_number;
+		present_wait += acquire_start.elapsed();
 
-		swapchain_frame.fence = in_flight_frame.fence;
-
-		// Map frame data buffer
-		let frame_data_buffer_ptr = unsafe { logical_device.map_memory(in_flight_frame.frame_data_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
-		
-		// Copy camera data into frame data buffer
-		let projection_matrix = &camera.projection_matrix.elements;
-		let projection_matrix_dst_ptr = frame_data_buffer_ptr as *mut [f32; 4];
-		unsafe { copy_nonoverlapping(projection_matrix.as_ptr(), projection_matrix_dst_ptr, 4) };
+		// Temporal anti-aliasing moves the projection by a fraction of a pixel every frame
+		let projection_matrix = if self.taa_enabled {
+			jitter_projection_matrix(&camera.projection_matrix, self.submitted_frame_count, self.scene_target.extent)
+		}
+		else {
+			camera.projection_matrix
+		};
 
 		let mut inverse_view_matrix = camera.transform.global_matrix;
 		inverse_view_matrix.invert();
-		unsafe {
-			let inverse_view_matrix_dst_ptr = frame_data_buffer_ptr.add(16 * 4) as *mut [f32; 4];
-			copy_nonoverlapping(inverse_view_matrix.elements.as_ptr(), inverse_view_matrix_dst_ptr, 4);
-		}
+
+		// The reflection is rendered from the camera mirrored across the reflection plane. Its projection flips it left to
+		// right so its triangles wind the usual way again, reflective meshes flip it back when they sample it.
+		let reflection_matrices = self.reflection_plane.filter(|_| render_scene).map(|plane| {
+			let reflected_camera = camera.reflected(&plane);
+			let mut projection_matrix = reflected_camera.projection_matrix;
+			projection_matrix.elements[0] = projection_matrix.elements[0].map(|element| -element);
+
+			let mut inverse_view_matrix = reflected_camera.transform.global_matrix;
+			inverse_view_matrix.invert();
+			(projection_matrix, inverse_view_matrix)
+		});
 
 		// Iterate over lights to
 		// - Calculate the total ambient light color and intensity
-		// - Copy the point light data into the frame data buffer
+		// - Skip point lights whose range doesn't reach into the camera's view
+		// - Gather the point light data and the view space position and range used to assign them to clusters
+		// - Gather the point lights reaching into the reflection's view
 		let mut total_ambient_light_color = vector3::ZERO;
 		let mut total_ambient_light_intensity = 0.0;
 
-		let mut point_light_count = 0;
-		let position_base_offest = 36 * 4;
-		let color_base_offest = 40 * 4;
-		let stride = 8 * 4;
+		let view_projection_matrix = camera.projection_matrix * inverse_view_matrix;
+		let frustum = Frustum::from_matrix(&view_projection_matrix);
+		let reflection_frustum = reflection_matrices.map(|(projection_matrix, inverse_view_matrix)| Frustum::from_matrix(&(projection_matrix * inverse_view_matrix)));
+		let mut point_lights: Vec<PointLightData> = vec![];
+		let mut reflection_point_lights: Vec<PointLightData> = vec![];
+		let mut cluster_lights: Vec<(Vector3, f32)> = vec![];
+		let mut culled_point_lights = 0;
 
 		for (entity, light) in light_components.iter() {
 			match light {
 				Light::AmbientLight(ambient_light) => {
-					total_ambient_light_color += ambient_light.color;
+					total_ambient_light_color += ambient_light.color.to_vector3();
 					total_ambient_light_intensity += ambient_light.intensity;
 				},
 				Light::PointLight(point_light) => {
-					let intensified_color = point_light.color * point_light.intensity;
 					let position = transform3d_components.borrow(entity).global_matrix.extract_position();
+					let sphere = Sphere::new(position, point_light.range);
+
+					let falloff: u32 = match point_light.falloff {
+						Falloff::None => 0,
+						Falloff::Linear => 1,
+						Falloff::InverseSquare => 2
+					};
+
+					let point_light_data = PointLightData {
+						position,
+						range: point_light.range,
+						color: point_light.color.to_vector3() * point_light.intensity,
+						falloff
+					};
+
+					if reflection_frustum.as_ref().is_some_and(|reflection_frustum| reflection_frustum.intersects_sphere(&sphere)) {
+						reflection_point_lights.push(point_light_data);
+					}
 
-					unsafe {
-						let position_dst_ptr = frame_data_buffer_ptr.add(position_base_offest + stride * point_light_count) as *mut Vector3;
-						copy_nonoverlapping(&position as *const Vector3, position_dst_ptr, 1);
-
-						let color_dst_ptr = frame_data_buffer_ptr.add(color_base_offest + stride * point_light_count) as *mut Vector3;
-						copy_nonoverlapping(&intensified_color as *const Vector3, color_dst_ptr, 1);
+					if !frustum.intersects_sphere(&sphere) {
+						culled_point_lights += 1;
+						continue;
 					}
 
-					point_light_count += 1;
+					point_lights.push(point_light_data);
+
+					let view_position = inverse_view_matrix * position.expand(1.0);
+					cluster_lights.push((Vector3::new(view_position.x, view_position.y, view_position.z), point_light.range));
 				}
 			}
 		}
 
-		// Copy point light count into frame data buffer
-		assert!(point_light_count <= MAX_POINT_LIGHTS, "Cannot render scene because {} point lights is more than the limit {}", point_light_count, MAX_POINT_LIGHTS);
-		unsafe {
-			let point_light_count_dst_ptr = frame_data_buffer_ptr.add(35 * 4) as *mut u32;
-			copy_nonoverlapping(&(point_light_count as u32) as *const u32, point_light_count_dst_ptr, 1);
-		}
+		// Assign the point lights to the clusters they reach
+		self.light_clusters.update(&camera.projection_matrix, &cluster_lights);
+
+		// Copy the camera, lights, cluster parameters and wind into the frame data buffer
+		let frame_data = FrameData {
+			projection_matrix,
+			inverse_view_matrix,
+			ambient_light: total_ambient_light_color * total_ambient_light_intensity,
+			point_light_count: point_lights.len() as u32,
+			cluster_grid_size: [CLUSTER_GRID_WIDTH as u32, CLUSTER_GRID_HEIGHT as u32, CLUSTER_GRID_DEPTH as u32],
+			cluster_depth_parameters: [self.light_clusters.depth_scale, self.light_clusters.depth_bias],
+			wind_direction: self.wind.normalized_direction(),
+			wind_parameters: [
+				self.wind.strength,
+				self.wind.time(),
+				self.wind.frequency,
+				self.wind.wavelength,
+				self.wind.flutter_strength,
+				self.wind.flutter_frequency
+			]
+		};
 
-		// Copy total intensified ambient light color into frame data buffer
-		let total_ambient_light_intensified_color = total_ambient_light_color * total_ambient_light_intensity;
-		unsafe {
-			let ambient_light_dst_ptr = frame_data_buffer_ptr.add(32 * 4) as *mut Vector3;
-			copy_nonoverlapping(&total_ambient_light_intensified_color as *const Vector3, ambient_light_dst_ptr, 1);
+		frame_data.write(logical_device, &in_flight_frame.frame_data_buffer);
+
+		// Copy the point lights, cluster ranges and light indices into the light data buffer
+		in_flight_frame.update_light_data(&self.context, &point_lights, &self.light_clusters);
+
+		// The reflection's point lights are all in a single cluster, it's usually a small part of the screen so they aren't
+		// worth assigning to clusters of their own
+		if let Some((reflection_projection_matrix, reflection_inverse_view_matrix)) = reflection_matrices {
+			let reflection_frame_data = FrameData {
+				projection_matrix: reflection_projection_matrix,
+				inverse_view_matrix: reflection_inverse_view_matrix,
+				point_light_count: reflection_point_lights.len() as u32,
+				cluster_grid_size: [1, 1, 1],
+				cluster_depth_parameters: [0.0, 0.0],
+				..frame_data
+			};
+
+			let reflection_resources = &mut self.reflection_resources;
+			let light_indices: Vec<u32> = (0..reflection_point_lights.len() as u32).collect();
+			reflection_frame_data.write(logical_device, &reflection_resources.frame_data_buffers[self.current_in_flight_frame_index]);
+			write_light_data(
+				&self.context,
+				&mut reflection_resources.light_data_buffers[self.current_in_flight_frame_index],
+				reflection_resources.frame_data_descriptor_sets[self.current_in_flight_frame_index],
+				&reflection_point_lights,
+				&[[0, reflection_point_lights.len() as u32]],
+				&light_indices);
 		}
 
-		// Flush and unmap frame data buffer
-		let range = vk::MappedMemoryRange::builder()
-			.memory(in_flight_frame.frame_data_buffer.memory)
-			.offset(0)
-			.size(vk::WHOLE_SIZE);
-		
-		unsafe {		
-			logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
-			logical_device.unmap_memory(in_flight_frame.frame_data_buffer.memory);
-		}
+		// Rasterize the occluders into the occlusion culling depth buffer
+		let occlusion_culling_enabled = self.occlusion_culling_enabled && self.reflection_plane.is_none();
+
+		if occlusion_culling_enabled && render_scene {
+			self.occlusion_culler.clear(&view_projection_matrix);
+
+			for (instances, mesh) in mesh_components.iter().filter(|(_, mesh)| mesh.occluder && camera.renders(mesh.layer_mask)) {
+				let geometry = geometries.borrow(mesh.geometry_handle);
+
+				if !matches!(geometry.topology(), Topology::Triangle) {
+					continue;
+				}
 
-		// Iterate over meshes to
-		// - Calculate the offsets and size of the data
-		// - Count the number of entities of each material to render
-		struct InstanceGroupInfo<'a> {
-			tuple: &'a (Vec<Entity>, Mesh),
-			index_array_relative_offset: usize,
-			attribute_array_relative_offset: usize
+				let attributes = geometry.attributes();
+
+				for instance in instances {
+					let global_matrix = &transform3d_components.borrow(instance).global_matrix;
+
+					for triangle in geometry.indices().chunks_exact(3) {
+						self.occlusion_culler.rasterize_triangle(&[0, 1, 2].map(|corner| {
+							let offset = triangle[corner] as usize * 6;
+							let mut position = Vector3::new(attributes[offset], attributes[offset + 1], attributes[offset + 2]);
+							position.apply_matrix4(global_matrix);
+							position
+						}));
+					}
+				}
+			}
 		}
 
-		let mut instance_group_infos: Vec<InstanceGroupInfo> = Vec::new();
-		let mut index_arrays_size = 0;
-		let mut attribute_arrays_size = 0;
+		// Iterate over meshes to
+		// - Skip every mesh when the scene isn't rendered this frame
+		// - Skip meshes without instances, meshes the camera doesn't render and meshes without geometry to draw
+		// - Leave out the instances hidden behind the occluders, occluders themselves are always drawn
+		// - Count the number of entities of each material to render and the instances and triangles for the stats
+		let mut instance_groups: Vec<(Cow<[Entity]>, &Mesh)> = Vec::new();
 		let mut material_counts = [0; MATERIALS_COUNT];
+		let mut submitted_instances = 0;
+		let mut occluded_instances = 0;
+		let mut drawn_instances = 0;
+		let mut triangles = 0;
+		let occlusion_culler = &self.occlusion_culler;
 
-		for tuple in mesh_components.iter() {
-			instance_group_infos.push(InstanceGroupInfo {
-				tuple,
-				index_array_relative_offset: index_arrays_size,
-				attribute_array_relative_offset: attribute_arrays_size
-			});
-			
-			let (instances, mesh) = tuple;
+		for (instances, mesh) in mesh_components.iter() {
 			let geometry = geometries.borrow(mesh.geometry_handle);
 
-			index_arrays_size += size_of_val(geometry.indices());
-			attribute_arrays_size += size_of_val(geometry.attributes());
-			material_counts[mesh.material as usize] += instances.len();
-		}
+			if !render_scene || instances.is_empty() || !camera.renders(mesh.layer_mask) || geometry.indices().is_empty() {
+				continue;
+			}
 
-		// Iterate over text to
-		struct TextInfo<'a> {
-			tuple: &'a (Entity, Text),
-			index_array_relative_offset: usize,
-			attribute_array_relative_offset: usize
-		}
+			submitted_instances += instances.len();
 
-		let mut text_infos: Vec<TextInfo> = Vec::new();
+			let instances = if occlusion_culling_enabled && !mesh.occluder {
+				let visible_instances: Vec<Entity> = instances.iter()
+					.filter(|instance| {
+						let mut bounding_box: Box3 = *geometry.bounding_box();
+						bounding_box.apply_matrix4(&transform3d_components.borrow(instance).global_matrix);
+						occlusion_culler.is_visible(&bounding_box)
+					})
+					.copied()
+					.collect();
 
-		for tuple in text_components.iter() {
-			let (_, text) = tuple;
+				occluded_instances += instances.len() - visible_instances.len();
 
-			if text.string.is_empty() {
-				continue;
+				if visible_instances.is_empty() {
+					continue;
+				}
+
+				Cow::Owned(visible_instances)
 			}
+			else {
+				Cow::Borrowed(instances.as_slice())
+			};
 
-			let vertex_indices_size = size_of_val(text.indices());
-			let vertex_attributes_size = size_of_val(text.attributes());
+			if matches!(geometry.topology(), Topology::Triangle) {
+				triangles += geometry.indices().len() / 3 * instances.len();
+			}
+
+			drawn_instances += instances.len();
+			material_counts[mesh.material as usize] += instances.len();
+			instance_groups.push((instances, mesh));
+		}
 
-			text_infos.push(TextInfo {
-				tuple,
-				index_array_relative_offset: index_arrays_size,
-				attribute_array_relative_offset: attribute_arrays_size
-			});
+		// Sort the instance groups by render layer so each layer is drawn in order, then by material so each layer binds each
+		// pipeline once and then by geometry so instance groups sharing geometry are drawn one after another
+		instance_groups.sort_by_key(|(_, mesh)| (mesh.layer.order(), mesh.material as usize, mesh.geometry_handle));
+
+		// Upload geometry which isn't resident yet or changed since it was uploaded, the copies are recorded at the start of
+		// the primary command buffer
+		let geometry_handles: Vec<Handle> = instance_groups.iter().map(|(_, mesh)| mesh.geometry_handle).collect();
+		self.mesh_resources.geometry_cache.update(&self.context, self.current_in_flight_frame_index, geometries, &geometry_handles);
+		let geometry_buffer = self.mesh_resources.geometry_cache.buffer.handle;
+
+		// Gather the text to draw in render layer order, the sort is stable so text in the same layer keeps its order
+		let mut texts: Vec<&(Entity, Text)> = text_components.iter()
+			.filter(|(_, text)| !text.string.is_empty() && camera.renders(text.layer_mask))
+			.collect();
+
+		// Text whose font wasn't part of the last font submission has no atlas to draw with, it's skipped instead of crashing
+		// until the fonts are submitted again
+		let font_generation = self.text_resources.submission_generation;
+		let text_count = texts.len();
+		texts.retain(|(_, text)| {
+			let generation = fonts.try_borrow(text.font).and_then(Font::submission_generation);
+			SubmissionStatus::new(generation, font_generation) == SubmissionStatus::Current
+		});
+
+		if texts.len() < text_count && !self.fonts_need_submission {
+			println!("Skipping {} texts whose fonts haven't been submitted since they were added or the fonts were last submitted", text_count - texts.len());
+		}
+
+		self.fonts_need_submission = texts.len() < text_count;
 
-			index_arrays_size += vertex_indices_size;
-			attribute_arrays_size += vertex_attributes_size;
+		// Text in the same layer with the same geometry, such as damage numbers or grid labels, is put next to each other so it
+		// can be drawn instanced. It's drawn where the first of it is in the layer.
+		let mut first_geometry_indices: HashMap<u64, usize> = HashMap::new();
+
+		for (index, (_, text)) in texts.iter().enumerate() {
+			first_geometry_indices.entry(text.geometry_id).or_insert(index);
 		}
 
+		texts.sort_by_key(|(_, text)| (text.layer.order(), first_geometry_indices[&text.geometry_id]));
+
 		// Calculate offsets
 		let alignment = self.context.physical_device.min_storage_buffer_offset_alignment as usize;
 
@@ -477,36 +1443,51 @@ impl RenderSystem {
 		let lambert_instance_data_array_offset = unaligned_lambert_instance_data_array_offset + lambert_instance_data_array_padding;
 		let lambert_instance_data_array_size = 4 * 16 * material_counts[Material::Lambert as usize];
 
-		let unaligned_text_instance_data_array_offset = lambert_instance_data_array_offset + lambert_instance_data_array_size;
+		let unaligned_lightmapped_instance_data_array_offset = lambert_instance_data_array_offset + lambert_instance_data_array_size;
+		let lightmapped_instance_data_array_padding = (alignment - unaligned_lightmapped_instance_data_array_offset % alignment) % alignment;
+		let lightmapped_instance_data_array_offset = unaligned_lightmapped_instance_data_array_offset + lightmapped_instance_data_array_padding;
+		let lightmapped_instance_data_array_size = 4 * 16 * material_counts[Material::Lightmapped as usize];
+
+		let unaligned_refractive_instance_data_array_offset = lightmapped_instance_data_array_offset + lightmapped_instance_data_array_size;
+		let refractive_instance_data_array_padding = (alignment - unaligned_refractive_instance_data_array_offset % alignment) % alignment;
+		let refractive_instance_data_array_offset = unaligned_refractive_instance_data_array_offset + refractive_instance_data_array_padding;
+		let refractive_instance_data_array_size = 4 * 16 * material_counts[Material::Refractive as usize];
+
+		let unaligned_reflective_instance_data_array_offset = refractive_instance_data_array_offset + refractive_instance_data_array_size;
+		let reflective_instance_data_array_padding = (alignment - unaligned_reflective_instance_data_array_offset % alignment) % alignment;
+		let reflective_instance_data_array_offset = unaligned_reflective_instance_data_array_offset + reflective_instance_data_array_padding;
+		let reflective_instance_data_array_size = 4 * 16 * material_counts[Material::Reflective as usize];
+
+		let unaligned_foliage_instance_data_array_offset = reflective_instance_data_array_offset + reflective_instance_data_array_size;
+		let foliage_instance_data_array_padding = (alignment - unaligned_foliage_instance_data_array_offset % alignment) % alignment;
+		let foliage_instance_data_array_offset = unaligned_foliage_instance_data_array_offset + foliage_instance_data_array_padding;
+		let foliage_instance_data_array_size = 4 * 16 * material_counts[Material::Foliage as usize];
+
+		let unaligned_text_instance_data_array_offset = foliage_instance_data_array_offset + foliage_instance_data_array_size;
 		let text_instance_data_array_padding = (alignment - unaligned_text_instance_data_array_offset % alignment) % alignment;
 		let text_instance_data_array_offset = unaligned_text_instance_data_array_offset + text_instance_data_array_padding;
-		let text_instance_data_array_size = 4 * 16 * text_infos.len();
-
-		let index_arrays_offset = text_instance_data_array_offset + text_instance_data_array_size;
-		
-		let unaligned_attribute_arrays_offset = index_arrays_offset + index_arrays_size;
-		let attribute_arrays_padding = (4 - unaligned_attribute_arrays_offset % 4) % 4;
-		let attribute_arrays_offset = unaligned_attribute_arrays_offset + attribute_arrays_padding;
+		let text_instance_data_array_size = 4 * 16 * texts.len();
+
+		let array = |offset: usize, size: usize| InstanceDataArray { offset, size };
+		let instance_data_layout = InstanceDataLayout {
+			line: array(line_instance_data_array_offset, line_instance_data_array_size),
+			basic: array(basic_instance_data_array_offset, basic_instance_data_array_size),
+			normal: array(normal_instance_data_array_offset, normal_instance_data_array_size),
+			lambert: array(lambert_instance_data_array_offset, lambert_instance_data_array_size),
+			lightmapped: array(lightmapped_instance_data_array_offset, lightmapped_instance_data_array_size),
+			refractive: array(refractive_instance_data_array_offset, refractive_instance_data_array_size),
+			reflective: array(reflective_instance_data_array_offset, reflective_instance_data_array_size),
+			foliage: array(foliage_instance_data_array_offset, foliage_instance_data_array_size),
+			text: array(text_instance_data_array_offset, text_instance_data_array_size)
+		};
 
-		// Allocate larger mesh data buffer and update descriptor sets if necessary
-		let buffer_size = (attribute_arrays_offset + attribute_arrays_size) as u64;
+		// Allocate larger instance data buffer and update descriptor sets if necessary
+		let buffer_size = (text_instance_data_array_offset + text_instance_data_array_size) as u64;
 
 		if buffer_size > in_flight_frame.instance_data_buffer.capacity {
 			in_flight_frame.instance_data_buffer.reallocate(&self.context, buffer_size);
 
-			in_flight_frame.update_descriptor_sets(
-				logical_device,
-				line_instance_data_array_offset,
-				line_instance_data_array_size,
-				basic_instance_data_array_offset,
-				basic_instance_data_array_size,
-				normal_instance_data_array_offset,
-				normal_instance_data_array_size,
-				lambert_instance_data_array_offset,
-				lambert_instance_data_array_size,
-				text_instance_data_array_offset,
-				text_instance_data_array_size,
-				index_arrays_offset);
+			in_flight_frame.update_descriptor_sets(logical_device, &instance_data_layout);
 			
 			println!("In flight frame {} instance data buffer reallocated", self.current_in_flight_frame_index);
 		}
@@ -514,21 +1495,13 @@ impl RenderSystem {
 			basic_instance_data_array_size > in_flight_frame.basic_instance_data_resources.array_size ||
 			normal_instance_data_array_size > in_flight_frame.normal_instance_data_resources.array_size ||
 			lambert_instance_data_array_size > in_flight_frame.lambert_instance_data_resources.array_size ||
+			lightmapped_instance_data_array_size > in_flight_frame.lightmapped_instance_data_resources.array_size ||
+			refractive_instance_data_array_size > in_flight_frame.refractive_instance_data_resources.array_size ||
+			reflective_instance_data_array_size > in_flight_frame.reflective_instance_data_resources.array_size ||
+			foliage_instance_data_array_size > in_flight_frame.foliage_instance_data_resources.array_size ||
 			text_instance_data_array_size > in_flight_frame.text_instance_data_resources.array_size
 		{
-			in_flight_frame.update_descriptor_sets(
-				logical_device,
-				line_instance_data_array_offset,
-				line_instance_data_array_size,
-				basic_instance_data_array_offset,
-				basic_instance_data_array_size,
-				normal_instance_data_array_offset,
-				normal_instance_data_array_size,
-				lambert_instance_data_array_offset,
-				lambert_instance_data_array_size,
-				text_instance_data_array_offset,
-				text_instance_data_array_size,
-				index_arrays_offset);
+			in_flight_frame.update_descriptor_sets(logical_device, &instance_data_layout);
 		}
 
 		let in_flight_frame = &self.in_flight_frames[self.current_in_flight_frame_index];
@@ -536,257 +1509,775 @@ impl RenderSystem {
 		let basic_instance_data_resources = &in_flight_frame.basic_instance_data_resources;
 		let normal_instance_data_resources = &in_flight_frame.normal_instance_data_resources;
 		let lambert_instance_data_resources = &in_flight_frame.lambert_instance_data_resources;
+		let lightmapped_instance_data_resources = &in_flight_frame.lightmapped_instance_data_resources;
+		let refractive_instance_data_resources = &in_flight_frame.refractive_instance_data_resources;
+		let reflective_instance_data_resources = &in_flight_frame.reflective_instance_data_resources;
+		let foliage_instance_data_resources = &in_flight_frame.foliage_instance_data_resources;
 		let text_instance_data_resources = &in_flight_frame.text_instance_data_resources;
 
 		let instance_data_buffer_ptr = unsafe { logical_device.map_memory(in_flight_frame.instance_data_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
 
-		// Begin mesh command buffers
+		// Create the viewports and scissors which are dynamic state in every pipeline, the scene is rendered at the
+		// scaled resolution while the overlay is rendered at the swapchain's
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(self.scene_target.extent.width as f32)
+			.height(self.scene_target.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+		let viewports = [viewport.build()];
+
+		let scissor = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(self.scene_target.extent);
+		let scissors = [scissor.build()];
+
+		let overlay_viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(self.swapchain.extent.width as f32)
+			.height(self.swapchain.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+		let overlay_viewports = [overlay_viewport.build()];
+
+		let overlay_scissor = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(self.swapchain.extent);
+		let overlay_scissors = [overlay_scissor.build()];
+
+		// The post process pass draws the scene into its region of the swapchain image and clears the bars around it
+		let post_process_viewport = vk::Viewport::builder()
+			.x(scene_viewport.offset.x as f32)
+			.y(scene_viewport.offset.y as f32)
+			.width(scene_viewport.extent.width as f32)
+			.height(scene_viewport.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+		let post_process_viewports = [post_process_viewport.build()];
+		let post_process_scissors = [scene_viewport];
+		let letterbox_bars = letterbox_bars(self.swapchain.extent, scene_viewport);
+
+		// Mesh command buffers are begun as the render layers are reached
 		let command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
-			.render_pass(self.render_pass)
-			.subpass(0)
-			.framebuffer(swapchain_frame.framebuffer);
+			.render_pass(self.scene_render_pass)
+			.subpass(1)
+			.framebuffer(self.scene_target.framebuffer);
 
 		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
 			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
 			.inheritance_info(&command_buffer_inheritance_info);
 
+		// Point the ambient occlusion descriptor sets at the current scene target and begin the ambient occlusion geometry command buffer
+		self.ssao_resources.update_descriptor_sets(logical_device, self.current_in_flight_frame_index, &self.scene_target.ssao_target);
+		let ambient_occlusion_descriptor_set = self.ssao_resources.ambient_occlusion_descriptor_sets[self.current_in_flight_frame_index];
+
+		// Point the refraction descriptor set at the current scene target's copy of the color
+		self.refraction_resources.update_descriptor_set(logical_device, self.current_in_flight_frame_index, &self.scene_target.refraction_target);
+		let refraction_descriptor_set = self.refraction_resources.descriptor_sets[self.current_in_flight_frame_index];
+
+		// Point the reflection descriptor set at the current scene target's reflection
+		self.reflection_resources.update_descriptor_set(logical_device, self.current_in_flight_frame_index, &self.scene_target.reflection_target);
+		let reflection_descriptor_set = self.reflection_resources.descriptor_sets[self.current_in_flight_frame_index];
+		let ssao_geometry_command_buffer = self.ssao_resources.geometry_command_buffers[self.current_in_flight_frame_index];
+
+		let depth_prepass_command_buffer = self.mesh_resources.depth_prepass_command_buffers[self.current_in_flight_frame_index];
+
+		let depth_prepass_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.scene_render_pass)
+			.subpass(0)
+			.framebuffer(self.scene_target.framebuffer);
+
+		let depth_prepass_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&depth_prepass_command_buffer_inheritance_info);
+
+		let ssao_geometry_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.ssao_resources.geometry_render_pass)
+			.subpass(0)
+			.framebuffer(self.scene_target.ssao_target.geometry_framebuffer);
+
+		let ssao_geometry_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&ssao_geometry_command_buffer_inheritance_info);
+
+		let deferred_geometry_command_buffer = self.deferred_resources.geometry_command_buffers[self.current_in_flight_frame_index];
+
+		let deferred_geometry_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.deferred_resources.geometry_render_pass)
+			.subpass(0)
+			.framebuffer(self.scene_target.deferred_target.geometry_framebuffer);
+
+		let deferred_geometry_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&deferred_geometry_command_buffer_inheritance_info);
+
+		let reflection_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.scene_render_pass)
+			.subpass(1)
+			.framebuffer(self.scene_target.reflection_target.framebuffer);
+
+		let reflection_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&reflection_command_buffer_inheritance_info);
+
+		// The reflection is only drawn into when it's rendered this frame
+		let reflection_command_buffer = self.reflection_resources.command_buffers[self.current_in_flight_frame_index];
+		let reflection_command_buffer = reflection_matrices.map(|_| reflection_command_buffer);
+
 		unsafe {
-			// Line
-			logical_device.begin_command_buffer(line_instance_data_resources.secondary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_bind_pipeline(line_instance_data_resources.secondary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.mesh_resources.line_pipeline);
-			logical_device.cmd_bind_descriptor_sets(
-				line_instance_data_resources.secondary_command_buffer,
-				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
-				0,
-				&[in_flight_frame.frame_data_descriptor_set],
-				&[]);
-			logical_device.cmd_bind_descriptor_sets(
-				line_instance_data_resources.secondary_command_buffer,
-				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
-				1,
-				&[line_instance_data_resources.descriptor_set],
-				&[]);
-			
-			// Basic
-			logical_device.begin_command_buffer(basic_instance_data_resources.secondary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_bind_pipeline(basic_instance_data_resources.secondary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.mesh_resources.basic_pipeline);
+			// Depth prepass
+			logical_device.begin_command_buffer(depth_prepass_command_buffer, &depth_prepass_command_buffer_begin_info).unwrap();
+			logical_device.cmd_set_viewport(depth_prepass_command_buffer, 0, &viewports);
+			logical_device.cmd_set_scissor(depth_prepass_command_buffer, 0, &scissors);
 			logical_device.cmd_bind_descriptor_sets(
-				basic_instance_data_resources.secondary_command_buffer,
+				depth_prepass_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
 				self.mesh_resources.pipeline_layout,
 				0,
 				&[in_flight_frame.frame_data_descriptor_set],
 				&[]);
+
+			// G-buffer geometry
+			logical_device.begin_command_buffer(deferred_geometry_command_buffer, &deferred_geometry_command_buffer_begin_info).unwrap();
+			logical_device.cmd_set_viewport(deferred_geometry_command_buffer, 0, &viewports);
+			logical_device.cmd_set_scissor(deferred_geometry_command_buffer, 0, &scissors);
 			logical_device.cmd_bind_descriptor_sets(
-				basic_instance_data_resources.secondary_command_buffer,
-				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
-				1,
-				&[basic_instance_data_resources.descriptor_set],
-				&[]);
-			
-			// Normal
-			logical_device.begin_command_buffer(normal_instance_data_resources.secondary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_bind_pipeline(normal_instance_data_resources.secondary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.mesh_resources.normal_pipeline);
-			logical_device.cmd_bind_descriptor_sets(
-				normal_instance_data_resources.secondary_command_buffer,
+				deferred_geometry_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
+				self.deferred_resources.geometry_pipeline_layout,
 				0,
 				&[in_flight_frame.frame_data_descriptor_set],
 				&[]);
+
+			// Ambient occlusion geometry, only meshes with normals are drawn into it
+			logical_device.begin_command_buffer(ssao_geometry_command_buffer, &ssao_geometry_command_buffer_begin_info).unwrap();
+			logical_device.cmd_set_viewport(ssao_geometry_command_buffer, 0, &viewports);
+			logical_device.cmd_set_scissor(ssao_geometry_command_buffer, 0, &scissors);
 			logical_device.cmd_bind_descriptor_sets(
-				normal_instance_data_resources.secondary_command_buffer,
-				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
-				1,
-				&[normal_instance_data_resources.descriptor_set],
-				&[]);
-			
-			// Lambert
-			logical_device.begin_command_buffer(lambert_instance_data_resources.secondary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_bind_pipeline(lambert_instance_data_resources.secondary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.mesh_resources.lambert_pipeline);
-			logical_device.cmd_bind_descriptor_sets(
-				lambert_instance_data_resources.secondary_command_buffer,
+				ssao_geometry_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
+				self.ssao_resources.geometry_pipeline_layout,
 				0,
 				&[in_flight_frame.frame_data_descriptor_set],
 				&[]);
-			logical_device.cmd_bind_descriptor_sets(
-				lambert_instance_data_resources.secondary_command_buffer,
-				vk::PipelineBindPoint::GRAPHICS,
-				self.mesh_resources.pipeline_layout,
-				1,
-				&[lambert_instance_data_resources.descriptor_set],
-				&[]);
+
+			// Reflection, drawn with the forward pipelines from the reflection's frame data. Nothing in it is occluded.
+			if let Some(reflection_command_buffer) = reflection_command_buffer {
+				logical_device.begin_command_buffer(reflection_command_buffer, &reflection_command_buffer_begin_info).unwrap();
+				logical_device.cmd_set_viewport(reflection_command_buffer, 0, &viewports);
+				logical_device.cmd_set_scissor(reflection_command_buffer, 0, &scissors);
+				logical_device.cmd_bind_descriptor_sets(
+					reflection_command_buffer,
+					vk::PipelineBindPoint::GRAPHICS,
+					self.mesh_resources.pipeline_layout,
+					0,
+					&[self.reflection_resources.frame_data_descriptor_sets[self.current_in_flight_frame_index]],
+					&[]);
+				logical_device.cmd_bind_descriptor_sets(
+					reflection_command_buffer,
+					vk::PipelineBindPoint::GRAPHICS,
+					self.mesh_resources.pipeline_layout,
+					2,
+					&[self.reflection_resources.ambient_occlusion_descriptor_set, self.mesh_resources.lightmap_descriptor_set],
+					&[]);
+			}
 		}
 		
-		let index_arrays_offset = in_flight_frame.index_arrays_offset;
-		let unaligned_attribute_arrays_offset = index_arrays_offset + index_arrays_size;
-		let attribute_arrays_padding = (4 - unaligned_attribute_arrays_offset % 4) % 4;
-		let attribute_arrays_offset = unaligned_attribute_arrays_offset + attribute_arrays_padding;
-
 		let mut instance_group_indices = [0; MATERIALS_COUNT];
+		let mut layer_command_buffers: Vec<vk::CommandBuffer> = vec![];
+		let mut current_layer_order = None;
+		let mut first_refractive_command_buffer_index = None;
+		let mut current_pipeline = None;
+		let mut current_geometry = None;
+		let mut current_depth_prepass_pipeline = None;
+		let mut current_depth_prepass_geometry = None;
+		let mut current_ssao_geometry_pipeline = None;
+		let mut current_ssao_geometry_descriptor_set = None;
+		let mut current_ssao_geometry = None;
+		let mut current_deferred_pipeline = None;
+		let mut current_deferred_descriptor_set = None;
+		let mut current_deferred_geometry = None;
+		let mut current_reflection_pipeline = None;
+		let mut current_reflection_geometry = None;
+		let mut depth_cleared = false;
+		let mut stats = RenderStats {
+			submitted_instances,
+			occluded_instances,
+			drawn_instances,
+			triangles,
+			point_lights: point_lights.len(),
+			culled_point_lights,
+			..Default::default()
+		};
+		let motion_vectors_enabled = self.motion_blur_strength > 0.0 || self.taa_enabled;
 
-		for instance_group in &instance_group_infos {
-			let index_array_offset = index_arrays_offset + instance_group.index_array_relative_offset;
-			let attribute_array_offset = attribute_arrays_offset + instance_group.attribute_array_relative_offset;
-			let (instances, mesh) = instance_group.tuple;
+		for (instances, mesh) in &instance_groups {
 			let geometry = geometries.borrow(mesh.geometry_handle);
+			let geometry_entry = self.mesh_resources.geometry_cache.entry(mesh.geometry_handle);
+			let index_array_offset = geometry_entry.index_array_offset;
+			let attribute_array_offset = geometry_entry.attribute_array_offset;
+			let quantized = geometry.vertex_format() == VertexFormat::Quantized;
+			let lightmapped = mesh.material == Material::Lightmapped;
+			let refractive = mesh.material == Material::Refractive;
+			let reflective = mesh.material == Material::Reflective;
+			let foliage = mesh.material == Material::Foliage;
+			assert!(!lightmapped || !geometry.uvs2().is_empty(), "Lightmapped meshes need geometry with a second UV set");
+			assert!(!refractive || mesh.layer != RenderLayer::Opaque, "Refractive meshes cannot be in the opaque render layer");
+			assert!(!reflective || self.reflection_plane.is_some(), "Reflective meshes need the reflection plane to be set");
+			assert!(!foliage || !geometry.colors().is_empty(), "Foliage meshes need geometry with colors");
+
+			// Lightmapped meshes read the second UV set from a second vertex buffer, lambert meshes the baked occlusion,
+			// foliage meshes the wind masks in the colors and line meshes their colors
+			let occluded = !geometry.occlusion().is_empty();
+			let colored = !geometry.colors().is_empty();
+
+			let second_vertex_buffer = match mesh.material {
+				Material::Lightmapped => Some((geometry_buffer, geometry_entry.uv2_array_offset as u64)),
+				Material::Lambert if occluded => Some((geometry_buffer, geometry_entry.occlusion_array_offset as u64)),
+				Material::Lambert => Some((self.mesh_resources.unoccluded_buffer.handle, 0)),
+				Material::Foliage => Some((geometry_buffer, geometry_entry.colors_array_offset as u64)),
+				Material::Line if colored => Some((geometry_buffer, geometry_entry.colors_array_offset as u64)),
+				Material::Line => Some((self.mesh_resources.uncolored_line_buffer.handle, 0)),
+				_ => None
+			};
+
+			let (second_buffer, second_offset) = second_vertex_buffer.unwrap_or((geometry_buffer, 0));
+			let vertex_buffers = [geometry_buffer, second_buffer];
+			let vertex_buffer_offsets = [attribute_array_offset as u64, second_offset];
+			let vertex_buffer_count = if second_vertex_buffer.is_some() { 2 } else { 1 };
 
-			// Copy geometry data
-			let indices = geometry.indices();
-			let attributes = geometry.attributes();
+			// Copy instance data
+			let instance_data_resources = match mesh.material {
+				Material::Line => line_instance_data_resources,
+				Material::Basic => basic_instance_data_resources,
+				Material::Normal => normal_instance_data_resources,
+				Material::Lambert => lambert_instance_data_resources,
+				Material::Lightmapped => lightmapped_instance_data_resources,
+				Material::Refractive => refractive_instance_data_resources,
+				Material::Reflective => reflective_instance_data_resources,
+				Material::Foliage => foliage_instance_data_resources
+			};
 
-			unsafe {
-				let index_array_dst_ptr = instance_data_buffer_ptr.add(index_array_offset) as *mut u16;
-				copy_nonoverlapping(indices.as_ptr(), index_array_dst_ptr, indices.len());
+			let instance_group_index = &mut instance_group_indices[mesh.material as usize];
 
-				let attribute_array_dst_ptr = instance_data_buffer_ptr.add(attribute_array_offset) as *mut f32;
-				copy_nonoverlapping(attributes.as_ptr(), attribute_array_dst_ptr, attributes.len());
+			// Quantized positions are turned back into geometry space by the instance matrix
+			let dequantization_matrix = if quantized { Some(geometry.dequantization_matrix()) } else { None };
+
+			for (instance_index, instance) in instances.iter().enumerate() {
+				let global_matrix = &transform3d_components.borrow(instance).global_matrix;
+				let offset = instance_data_resources.array_offset + 4 * 16 * (*instance_group_index + instance_index);
+
+				let matrix = match &dequantization_matrix {
+					Some(dequantization_matrix) => global_matrix * dequantization_matrix,
+					None => *global_matrix
+				};
+
+				unsafe {
+					let instance_data_dst_ptr = instance_data_buffer_ptr.add(offset) as *mut [f32; 4];
+					copy_nonoverlapping(matrix.elements.as_ptr(), instance_data_dst_ptr, 4);
+				}
 			}
 
-			// Copy instance data
-			let instance_group_index = &mut instance_group_indices[mesh.material as usize];
-			let secondary_command_buffer;
+			// Opaque meshes whose material can be shaded from the G-buffer are drawn into it instead of the render layers, the
+			// G-buffer has no room for baked occlusion so meshes with it are drawn forward
+			let deferred_pipeline = if self.deferred_enabled && mesh.layer == RenderLayer::Opaque && !occluded {
+				self.deferred_resources.geometry_pipeline(mesh.material, quantized)
+			}
+			else {
+				None
+			};
+
+			if let Some(pipeline) = deferred_pipeline {
+				unsafe {
+					if current_deferred_pipeline != Some(pipeline) {
+						logical_device.cmd_bind_pipeline(deferred_geometry_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+						current_deferred_pipeline = Some(pipeline);
+						stats.pipeline_binds += 1;
+					}
 
-			match mesh.material {
-				Material::Line => {
-					for (instance_index, instance) in instances.iter().enumerate() {
-						let transform_ptr = transform3d_components.borrow(instance).global_matrix.elements.as_ptr();
-						let offset = line_instance_data_resources.array_offset + 4 * 16 * (*instance_group_index + instance_index);
+					if current_deferred_descriptor_set != Some(instance_data_resources.descriptor_set) {
+						logical_device.cmd_bind_descriptor_sets(
+							deferred_geometry_command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.deferred_resources.geometry_pipeline_layout,
+							1,
+							&[instance_data_resources.descriptor_set],
+							&[]);
+						current_deferred_descriptor_set = Some(instance_data_resources.descriptor_set);
+						stats.descriptor_set_binds += 1;
+					}
 
-						unsafe {
-							let instance_data_dst_ptr = instance_data_buffer_ptr.add(offset) as *mut [f32; 4];
-							copy_nonoverlapping(transform_ptr, instance_data_dst_ptr, 4);
-						}
+					if current_deferred_geometry != Some(index_array_offset) {
+						logical_device.cmd_bind_index_buffer(deferred_geometry_command_buffer, geometry_buffer, index_array_offset as u64, vk::IndexType::UINT16);
+						logical_device.cmd_bind_vertex_buffers(deferred_geometry_command_buffer, 0, &[geometry_buffer], &[attribute_array_offset as u64]);
+						current_deferred_geometry = Some(index_array_offset);
+						stats.geometry_binds += 1;
 					}
 
-					secondary_command_buffer = line_instance_data_resources.secondary_command_buffer;
-				},
-				Material::Basic => {
-					for (instance_index, instance) in instances.iter().enumerate() {
-						let transform_ptr = transform3d_components.borrow(instance).global_matrix.elements.as_ptr();
-						let offset = basic_instance_data_resources.array_offset + 4 * 16 * (*instance_group_index + instance_index);
-
-						unsafe {
-							let instance_data_dst_ptr = instance_data_buffer_ptr.add(offset) as *mut [f32; 4];
-							copy_nonoverlapping(transform_ptr, instance_data_dst_ptr, 4);
-						}
+					logical_device.cmd_draw_indexed(deferred_geometry_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
+					stats.draws += 1;
+				}
+			}
+			else {
+				// Begin a new command buffer when the render layer changes, refractive meshes are sorted to the end of their layer and
+				// get a command buffer of their own since they're drawn after the copy of the color
+				let layer_order = (mesh.layer.order(), refractive);
+
+				if current_layer_order != Some(layer_order) {
+					if refractive && first_refractive_command_buffer_index.is_none() {
+						first_refractive_command_buffer_index = Some(layer_command_buffers.len());
 					}
 
-					secondary_command_buffer = basic_instance_data_resources.secondary_command_buffer;
-				},
-				Material::Normal => {
-					for (instance_index, instance) in instances.iter().enumerate() {
-						let transform_ptr = transform3d_components.borrow(instance).global_matrix.elements.as_ptr();
-						let instance_data_offset = normal_instance_data_resources.array_offset + 4 * 16 * (*instance_group_index + instance_index);
-
-						unsafe {
-							let instance_data_dst_ptr = instance_data_buffer_ptr.add(instance_data_offset) as *mut [f32; 4];
-							copy_nonoverlapping(transform_ptr, instance_data_dst_ptr, 4);
-						}
+					let command_buffer = self.mesh_resources.layer_command_buffer(logical_device, self.command_pool, self.current_in_flight_frame_index, layer_command_buffers.len());
+
+					unsafe {
+						logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+						logical_device.cmd_set_viewport(command_buffer, 0, &viewports);
+						logical_device.cmd_set_scissor(command_buffer, 0, &scissors);
+						logical_device.cmd_bind_descriptor_sets(
+							command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.mesh_resources.pipeline_layout,
+							0,
+							&[in_flight_frame.frame_data_descriptor_set],
+							&[]);
+						logical_device.cmd_bind_descriptor_sets(
+							command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.mesh_resources.pipeline_layout,
+							2,
+							&[ambient_occlusion_descriptor_set, self.mesh_resources.lightmap_descriptor_set, refraction_descriptor_set, reflection_descriptor_set],
+							&[]);
 					}
 
-					secondary_command_buffer = normal_instance_data_resources.secondary_command_buffer;
-				},
-				Material::Lambert => {
-					for (instance_index, instance) in instances.iter().enumerate() {
-						let transform_ptr = transform3d_components.borrow(instance).global_matrix.elements.as_ptr();
-						let instance_data_offset = lambert_instance_data_resources.array_offset + 4 * 16 * (*instance_group_index + instance_index);
-
-						unsafe {
-							let instance_data_dst_ptr = instance_data_buffer_ptr.add(instance_data_offset) as *mut [f32; 4];
-							copy_nonoverlapping(transform_ptr, instance_data_dst_ptr, 4);
+					layer_command_buffers.push(command_buffer);
+					current_layer_order = Some(layer_order);
+					current_pipeline = None;
+					current_geometry = None;
+					stats.descriptor_set_binds += 5;
+				}
+
+				let layer_command_buffer = *layer_command_buffers.last().unwrap();
+
+				// Overlay meshes such as a first person weapon are drawn over everything before them
+				if mesh.layer == RenderLayer::Overlay && !depth_cleared {
+					let clear_attachment = vk::ClearAttachment::builder()
+						.aspect_mask(vk::ImageAspectFlags::DEPTH)
+						.clear_value(vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } });
+
+					let clear_rect = vk::ClearRect::builder()
+						.rect(scissors[0])
+						.base_array_layer(0)
+						.layer_count(1);
+
+					unsafe { logical_device.cmd_clear_attachments(layer_command_buffer, &[clear_attachment.build()], &[clear_rect.build()]) };
+					depth_cleared = true;
+				}
+
+				// Only opaque meshes are in the depth prepass so only they are shaded with the depth equal pipelines
+				let depth_equal = self.depth_prepass_enabled && mesh.layer == RenderLayer::Opaque;
+
+				let pipeline = self.mesh_resources.pipeline(mesh.material, depth_equal, quantized);
+
+				// Record draw commands, rebinding the pipeline and instance data when the material changes and the index and vertex
+				// buffers when the geometry changes
+				unsafe {
+					if current_pipeline != Some(pipeline) {
+						logical_device.cmd_bind_pipeline(layer_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+						logical_device.cmd_bind_descriptor_sets(
+							layer_command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.mesh_resources.pipeline_layout,
+							1,
+							&[instance_data_resources.descriptor_set],
+							&[]);
+
+						current_pipeline = Some(pipeline);
+						stats.pipeline_binds += 1;
+						stats.descriptor_set_binds += 1;
+					}
+
+					if current_geometry != Some((index_array_offset, second_vertex_buffer)) {
+						logical_device.cmd_bind_index_buffer(layer_command_buffer, geometry_buffer, index_array_offset as u64, vk::IndexType::UINT16);
+						logical_device.cmd_bind_vertex_buffers(layer_command_buffer, 0, &vertex_buffers[..vertex_buffer_count], &vertex_buffer_offsets[..vertex_buffer_count]);
+						current_geometry = Some((index_array_offset, second_vertex_buffer));
+						stats.geometry_binds += 1;
+					}
+
+					logical_device.cmd_draw_indexed(layer_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
+					stats.draws += 1;
+				}
+			}
+
+			// Record depth prepass draw commands, lines, reflective meshes, foliage, deferred meshes and meshes outside the opaque
+			// layer are left out of it
+			if self.depth_prepass_enabled && mesh.layer == RenderLayer::Opaque && deferred_pipeline.is_none() {
+				let depth_prepass_pipeline = match (mesh.material, quantized) {
+					(Material::Line, _) | (Material::Refractive, _) | (Material::Reflective, _) | (Material::Foliage, _) => None,
+					(Material::Basic, false) => Some(self.mesh_resources.basic_depth_prepass_pipeline),
+					(Material::Normal, false) => Some(self.mesh_resources.normal_depth_prepass_pipeline),
+					(Material::Lambert, false) => Some(self.mesh_resources.lambert_depth_prepass_pipeline),
+					(Material::Basic, true) => Some(self.mesh_resources.basic_quantized_depth_prepass_pipeline),
+					(Material::Normal, true) => Some(self.mesh_resources.normal_quantized_depth_prepass_pipeline),
+					(Material::Lambert, true) => Some(self.mesh_resources.lambert_quantized_depth_prepass_pipeline),
+					(Material::Lightmapped, false) => Some(self.mesh_resources.lightmapped_depth_prepass_pipeline),
+					(Material::Lightmapped, true) => Some(self.mesh_resources.lightmapped_quantized_depth_prepass_pipeline)
+				};
+
+				if let Some(pipeline) = depth_prepass_pipeline {
+					unsafe {
+						if current_depth_prepass_pipeline != Some(pipeline) {
+							logical_device.cmd_bind_pipeline(depth_prepass_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+							logical_device.cmd_bind_descriptor_sets(
+								depth_prepass_command_buffer,
+								vk::PipelineBindPoint::GRAPHICS,
+								self.mesh_resources.pipeline_layout,
+								1,
+								&[instance_data_resources.descriptor_set],
+								&[]);
+							current_depth_prepass_pipeline = Some(pipeline);
+						}
+
+						if current_depth_prepass_geometry != Some((index_array_offset, second_vertex_buffer)) {
+							logical_device.cmd_bind_index_buffer(depth_prepass_command_buffer, geometry_buffer, index_array_offset as u64, vk::IndexType::UINT16);
+							logical_device.cmd_bind_vertex_buffers(depth_prepass_command_buffer, 0, &vertex_buffers[..vertex_buffer_count], &vertex_buffer_offsets[..vertex_buffer_count]);
+							current_depth_prepass_geometry = Some((index_array_offset, second_vertex_buffer));
 						}
+
+						logical_device.cmd_draw_indexed(depth_prepass_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
+					}
+				}
+			}
+
+			// Record ambient occlusion geometry draw commands for opaque meshes with normals
+			let has_normals = matches!(mesh.material, Material::Normal | Material::Lambert);
+
+			if has_normals && mesh.layer == RenderLayer::Opaque {
+				let ssao_geometry_pipeline = if quantized { self.ssao_resources.quantized_geometry_pipeline } else { self.ssao_resources.geometry_pipeline };
+
+				unsafe {
+					if current_ssao_geometry_pipeline != Some(ssao_geometry_pipeline) {
+						logical_device.cmd_bind_pipeline(ssao_geometry_command_buffer, vk::PipelineBindPoint::GRAPHICS, ssao_geometry_pipeline);
+						current_ssao_geometry_pipeline = Some(ssao_geometry_pipeline);
+					}
+
+					if current_ssao_geometry_descriptor_set != Some(instance_data_resources.descriptor_set) {
+						logical_device.cmd_bind_descriptor_sets(
+							ssao_geometry_command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.ssao_resources.geometry_pipeline_layout,
+							1,
+							&[instance_data_resources.descriptor_set],
+							&[]);
+						current_ssao_geometry_descriptor_set = Some(instance_data_resources.descriptor_set);
+					}
+
+					if current_ssao_geometry != Some(index_array_offset) {
+						logical_device.cmd_bind_index_buffer(ssao_geometry_command_buffer, geometry_buffer, index_array_offset as u64, vk::IndexType::UINT16);
+						logical_device.cmd_bind_vertex_buffers(ssao_geometry_command_buffer, 0, &[geometry_buffer], &[attribute_array_offset as u64]);
+						current_ssao_geometry = Some(index_array_offset);
 					}
 
-					secondary_command_buffer = lambert_instance_data_resources.secondary_command_buffer;
+					logical_device.cmd_draw_indexed(ssao_geometry_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
 				}
 			}
 
-			// Record draw commands
-			unsafe {
-				logical_device.cmd_bind_index_buffer(secondary_command_buffer, in_flight_frame.instance_data_buffer.handle, index_array_offset as u64, vk::IndexType::UINT16);
-				logical_device.cmd_bind_vertex_buffers(secondary_command_buffer, 0, &[in_flight_frame.instance_data_buffer.handle], &[attribute_array_offset as u64]);
-				logical_device.cmd_draw_indexed(secondary_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
+			// Opaque triangle meshes are drawn into the motion vectors, everything else is treated as not moving. Foliage is left
+			// out since the motion vector shader doesn't know about the wind.
+			if motion_vectors_enabled && mesh.layer == RenderLayer::Opaque && !matches!(mesh.material, Material::Line | Material::Foliage) {
+				self.motion_vector_resources.add_draw(
+					instances,
+					transform3d_components,
+					dequantization_matrix.as_ref(),
+					index_array_offset,
+					attribute_array_offset,
+					geometry.indices().len());
+			}
+
+			// Record reflection draw commands for opaque meshes, all of them are drawn forward since the reflection has no G-buffer.
+			// Reflective meshes can't see themselves.
+			if let Some(reflection_command_buffer) = reflection_command_buffer.filter(|_| mesh.layer == RenderLayer::Opaque && !reflective) {
+				let pipeline = self.mesh_resources.pipeline(mesh.material, false, quantized);
+
+				unsafe {
+					if current_reflection_pipeline != Some(pipeline) {
+						logical_device.cmd_bind_pipeline(reflection_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+						logical_device.cmd_bind_descriptor_sets(
+							reflection_command_buffer,
+							vk::PipelineBindPoint::GRAPHICS,
+							self.mesh_resources.pipeline_layout,
+							1,
+							&[instance_data_resources.descriptor_set],
+							&[]);
+
+						current_reflection_pipeline = Some(pipeline);
+						stats.pipeline_binds += 1;
+						stats.descriptor_set_binds += 1;
+					}
+
+					if current_reflection_geometry != Some((index_array_offset, second_vertex_buffer)) {
+						logical_device.cmd_bind_index_buffer(reflection_command_buffer, geometry_buffer, index_array_offset as u64, vk::IndexType::UINT16);
+						logical_device.cmd_bind_vertex_buffers(reflection_command_buffer, 0, &vertex_buffers[..vertex_buffer_count], &vertex_buffer_offsets[..vertex_buffer_count]);
+						current_reflection_geometry = Some((index_array_offset, second_vertex_buffer));
+						stats.geometry_binds += 1;
+					}
+
+					logical_device.cmd_draw_indexed(reflection_command_buffer, geometry.indices().len() as u32, instances.len() as u32, 0, 0, *instance_group_index as u32);
+					stats.draws += 1;
+				}
 			}
 
 			*instance_group_index += instances.len();
 		}
 
-		// End command buffers and add to submission list if there are meshes to draw
+		// End command buffers, the layer command buffers are executed in order
 		unsafe {
-			logical_device.end_command_buffer(line_instance_data_resources.secondary_command_buffer).unwrap();
-			logical_device.end_command_buffer(basic_instance_data_resources.secondary_command_buffer).unwrap();
-			logical_device.end_command_buffer(normal_instance_data_resources.secondary_command_buffer).unwrap();
-			logical_device.end_command_buffer(lambert_instance_data_resources.secondary_command_buffer).unwrap();
+			for layer_command_buffer in &layer_command_buffers {
+				logical_device.end_command_buffer(*layer_command_buffer).unwrap();
+			}
+
+			logical_device.end_command_buffer(depth_prepass_command_buffer).unwrap();
+			logical_device.end_command_buffer(ssao_geometry_command_buffer).unwrap();
+			logical_device.end_command_buffer(deferred_geometry_command_buffer).unwrap();
+
+			if let Some(reflection_command_buffer) = reflection_command_buffer {
+				logical_device.end_command_buffer(reflection_command_buffer).unwrap();
+			}
+		}
+
+		// The deferred meshes are lit before the render layers are drawn so the forward meshes are depth tested against them. The
+		// command buffers from the first refractive one on are executed after the color is copied.
+		let mut secondary_command_buffers = layer_command_buffers;
+		let refractive_command_buffers = match first_refractive_command_buffer_index {
+			Some(index) => secondary_command_buffers.split_off(index),
+			None => vec![]
+		};
+
+		// Blob shadows, trails and particles are blended over everything but the refractive meshes, which see them through the copied color
+		let trail_command_buffer = self.trail_resources.record_command_buffer(
+			&self.context,
+			self.current_in_flight_frame_index,
+			self.scene_target.extent,
+			self.scene_render_pass,
+			self.scene_target.framebuffer,
+			in_flight_frame.frame_data_descriptor_set,
+			trail_components,
+			blob_shadow_components,
+			&camera.transform.global_matrix().extract_position());
+
+		secondary_command_buffers.extend(trail_command_buffer);
+
+		let particle_command_buffer = self.particle_resources.record_draw_command_buffer(
+			logical_device,
+			self.current_in_flight_frame_index,
+			self.scene_target.extent,
+			self.scene_render_pass,
+			self.scene_target.framebuffer,
+			in_flight_frame.frame_data_descriptor_set);
+
+		secondary_command_buffers.push(particle_command_buffer);
+
+		if self.deferred_enabled {
+			let deferred_lighting_command_buffer = self.deferred_resources.record_lighting_command_buffer(
+				logical_device,
+				self.current_in_flight_frame_index,
+				&self.scene_target.deferred_target,
+				self.scene_render_pass,
+				self.scene_target.framebuffer,
+				in_flight_frame.frame_data_descriptor_set,
+				ambient_occlusion_descriptor_set,
+				&projection_matrix,
+				&camera.transform.global_matrix);
+
+			secondary_command_buffers.insert(0, deferred_lighting_command_buffer);
 		}
 
-		let mut secondary_command_buffers = vec![];
+		if render_scene {
+			self.stats = stats;
+		}
+
+		// Begin overlay command buffers
+		let overlay_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.overlay_render_pass)
+			.subpass(0)
+			.framebuffer(swapchain_frame.framebuffer);
 
-		if material_counts[Material::Line as usize] != 0 || self.mesh_resources.static_material_counts[Material::Line as usize] != 0 {
-			secondary_command_buffers.push(line_instance_data_resources.secondary_command_buffer);
+		let overlay_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&overlay_command_buffer_inheritance_info);
+
+		// Point the temporal anti-aliasing descriptor set at the current scene target, the post process pass reads the resolved
+		// image instead of the scene when it's enabled
+		let scene_image_view = self.scene_target.color_image_resources.image_view;
+		let velocity_image_view = self.scene_target.motion_vector_target.velocity_image_view();
+
+		let post_process_image_view = if self.taa_enabled && render_scene {
+			self.taa_resources.update_descriptor_set(logical_device, self.current_in_flight_frame_index, &self.scene_target.taa_target, scene_image_view, velocity_image_view);
+			self.scene_target.taa_target.resolved_image_view()
+		}
+		else if self.taa_enabled {
+			self.scene_target.taa_target.last_resolved_image_view()
 		}
+		else {
+			scene_image_view
+		};
+
+		// Record post process command buffer which draws the scene into the swapchain image through the color grading LUTs
+		let post_process_command_buffer = self.post_process_resources.command_buffers[self.current_in_flight_frame_index];
+		let post_process_descriptor_set = self.post_process_resources.descriptor_sets[self.current_in_flight_frame_index];
+		self.post_process_resources.update_descriptor_set(logical_device, self.current_in_flight_frame_index, post_process_image_view, velocity_image_view);
+		let post_process_push_constants = [lut_blend.to_ne_bytes(), self.motion_blur_strength.to_ne_bytes()].concat();
+
+		unsafe {
+			logical_device.begin_command_buffer(post_process_command_buffer, &overlay_command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(post_process_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.post_process_resources.pipeline);
+			logical_device.cmd_set_viewport(post_process_command_buffer, 0, &post_process_viewports);
+			logical_device.cmd_set_scissor(post_process_command_buffer, 0, &post_process_scissors);
+			logical_device.cmd_bind_descriptor_sets(
+				post_process_command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.post_process_resources.pipeline_layout,
+				0,
+				&[post_process_descriptor_set],
+				&[]);
+			logical_device.cmd_push_constants(post_process_command_buffer, self.post_process_resources.pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, &post_process_push_constants);
+			logical_device.cmd_draw(post_process_command_buffer, 3, 1, 0, 0);
 
-		if material_counts[Material::Basic as usize] != 0 || self.mesh_resources.static_material_counts[Material::Basic as usize] != 0 {
-			secondary_command_buffers.push(basic_instance_data_resources.secondary_command_buffer);
+			if !letterbox_bars.is_empty() {
+				let clear_attachment = vk::ClearAttachment::builder()
+					.aspect_mask(vk::ImageAspectFlags::COLOR)
+					.color_attachment(0)
+					.clear_value(vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } });
+
+				let clear_rects: Vec<vk::ClearRect> = letterbox_bars.iter()
+					.map(|bar| vk::ClearRect::builder().rect(*bar).base_array_layer(0).layer_count(1).build())
+					.collect();
+
+				logical_device.cmd_clear_attachments(post_process_command_buffer, &[clear_attachment.build()], &clear_rects);
+			}
+
+			logical_device.end_command_buffer(post_process_command_buffer).unwrap();
 		}
 
-		if material_counts[Material::Normal as usize] != 0 || self.mesh_resources.static_material_counts[Material::Normal as usize] != 0 {
-			secondary_command_buffers.push(normal_instance_data_resources.secondary_command_buffer);
+		// Record sprite command buffer, tilemaps are drawn under sprites and either is skipped when its texture hasn't been submitted.
+		// Tilemap chunks keep their geometry id until they're regenerated so only edited chunks are copied again.
+		let mut sprite_draws: Vec<SpriteDraw> = Vec::new();
+
+		for (entity, tilemap) in tilemap_components.iter() {
+			let texture_slot = match textures.try_borrow(tilemap.texture).and_then(|texture| self.texture_store.slot(&self.texture_table, texture)) {
+				Some(texture_slot) => texture_slot,
+				None => continue
+			};
+
+			let matrix = self.text_resources.projection_matrix * transform2d_components.borrow(entity).matrix;
+
+			sprite_draws.extend(tilemap.chunks().iter().map(|chunk| SpriteDraw {
+				matrix,
+				color: [1.0; 4],
+				texture_slot,
+				geometry_id: Some(chunk.geometry_id),
+				indices: chunk.indices(),
+				attributes: chunk.attributes()
+			}));
 		}
 
-		if material_counts[Material::Lambert as usize] != 0 || self.mesh_resources.static_material_counts[Material::Lambert as usize] != 0 {
-			secondary_command_buffers.push(lambert_instance_data_resources.secondary_command_buffer);
+		sprite_draws.extend(animated_sprite_components.iter()
+			.filter_map(|(entity, sprite)| {
+				let texture_slot = self.texture_store.slot(&self.texture_table, textures.try_borrow(sprite.texture)?)?;
+
+				Some(SpriteDraw {
+					matrix: self.text_resources.projection_matrix * transform2d_components.borrow(entity).matrix,
+					color: [1.0; 4],
+					texture_slot,
+					geometry_id: None,
+					indices: sprite.indices(),
+					attributes: sprite.attributes()
+				})
+			}));
+
+		// Selection highlights and carets go over the sprites and under the text
+		let solid_texture_slot = self.texture_store.slot(&self.texture_table, &self.solid_texture).unwrap();
+
+		for (entity, input_field) in input_field_components.iter() {
+			let matrix = self.text_resources.projection_matrix * transform2d_components.borrow(entity).matrix;
+			let quads = [(input_field.selection_quad(), input_field.selection_color), (input_field.caret_quad(), input_field.caret_color)];
+
+			sprite_draws.extend(quads.iter().filter_map(|(quad, color)| Some(SpriteDraw {
+				matrix,
+				color: [color.r, color.g, color.b, color.a],
+				texture_slot: solid_texture_slot,
+				geometry_id: None,
+				indices: &QUAD_INDICES,
+				attributes: (*quad)?
+			})));
 		}
 
+		let sprite_command_buffer = self.sprite_resources.record_command_buffer(
+			&self.context,
+			self.current_in_flight_frame_index,
+			&overlay_command_buffer_begin_info,
+			self.swapchain.extent,
+			self.texture_table.descriptor_set,
+			&sprite_draws);
+
 		// Begin text command buffer
 		unsafe {
-			logical_device.begin_command_buffer(text_instance_data_resources.secondary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_bind_pipeline(text_instance_data_resources.secondary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.text_resources.pipeline);
+			logical_device.begin_command_buffer(in_flight_frame.text_command_buffer, &overlay_command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(in_flight_frame.text_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.text_resources.pipeline);
+			logical_device.cmd_set_viewport(in_flight_frame.text_command_buffer, 0, &overlay_viewports);
+			logical_device.cmd_set_scissor(in_flight_frame.text_command_buffer, 0, &overlay_scissors);
 			logical_device.cmd_bind_descriptor_sets(
-				text_instance_data_resources.secondary_command_buffer,
+				in_flight_frame.text_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
 				self.text_resources.pipeline_layout,
 				0,
 				&[text_instance_data_resources.descriptor_set],
 				&[]);
 			logical_device.cmd_bind_descriptor_sets(
-				text_instance_data_resources.secondary_command_buffer,
+				in_flight_frame.text_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
 				self.text_resources.pipeline_layout,
 				1,
 				&[self.text_resources.sampler_descriptor_set],
 				&[]);
 			logical_device.cmd_bind_descriptor_sets(
-				text_instance_data_resources.secondary_command_buffer,
+				in_flight_frame.text_command_buffer,
 				vk::PipelineBindPoint::GRAPHICS,
 				self.text_resources.pipeline_layout,
 				2,
-				&[self.text_resources.atlases_descriptor_set],
+				&[self.texture_table.descriptor_set],
 				&[]);
 		}
 
-		// Copy text data into buffer and record draw commands
-		for (index, text_info) in text_infos.iter().enumerate() {
-			let (entity, text) = text_info.tuple;
+		// Copy text geometry which changed since this in flight frame last drew it into the text geometry cache
+		let text_geometry_cache = &mut self.text_resources.geometry_caches[self.current_in_flight_frame_index];
+		let text_geometry_entries = text_geometry_cache.update(&self.context, &texts.iter().map(|(_, text)| text).collect::<Vec<_>>());
+		let text_geometry_buffer = text_geometry_cache.buffer.handle;
+
+		// Copy text instance data into buffer and record draw commands, the scissor only changes when the clip rect does and the
+		// pipeline when the font's atlas format or whether the text glows does
+		let mut current_clip_rect = None;
+		let mut current_pipeline = self.text_resources.pipeline;
+		let mut first_instance_index = 0;
+
+		for (index, ((entity, text), geometry_entry)) in texts.iter().zip(&text_geometry_entries).enumerate() {
 			let font = fonts.borrow(text.font);
-			let submission_info = font.submission_info.as_ref().unwrap(); // error message
-			assert!(submission_info.generation == self.text_resources.submission_generation);
+			// Only text with current fonts is left
+			let submission_info = font.submission_info.as_ref().unwrap();
 
 			let instance_data_offset = text_instance_data_resources.array_offset + 4 * 16 * index;
-			let index_array_offset = index_arrays_offset + text_info.index_array_relative_offset;
-			let attribute_array_offset = attribute_arrays_offset + text_info.attribute_array_relative_offset;
-
-			let indices = text.indices();
-			let attributes = text.attributes();
 
 			let projection_matrix = &self.text_resources.projection_matrix;
-			let transform_matrix = &transform2d_components.borrow(entity).matrix;
+			let mut transform_matrix = transform2d_components.borrow(entity).matrix;
+
+			// Pixel snapped glyphs only land on whole pixels when the text does too
+			if text.positioning == TextPositioning::PixelSnapped {
+				transform_matrix.elements[0][2] = transform_matrix.elements[0][2].round();
+				transform_matrix.elements[1][2] = transform_matrix.elements[1][2].round();
+			}
+
 			let final_matrix = projection_matrix * transform_matrix;
 
 			unsafe {
@@ -796,25 +2287,77 @@ impl RenderSystem {
 
 				let atlas_index_dst_ptr = instance_data_buffer_ptr.add(instance_data_offset + 12 * 4) as *mut i32;
 				copy_nonoverlapping(&(submission_info.index as i32), atlas_index_dst_ptr, 1);
+			}
 
-				let index_array_dst_ptr = instance_data_buffer_ptr.add(index_array_offset) as *mut u16;
-				copy_nonoverlapping(indices.as_ptr(), index_array_dst_ptr, indices.len());
+			// Text with the same geometry, clip rect and glow as the text after it is drawn along with it as another instance
+			let next_text = texts.get(index + 1).map(|(_, next_text)| next_text);
 
-				let attribute_array_dst_ptr = instance_data_buffer_ptr.add(attribute_array_offset) as *mut f32;
-				copy_nonoverlapping(attributes.as_ptr(), attribute_array_dst_ptr, attributes.len());
+			if next_text.is_some_and(|next_text| next_text.geometry_id == text.geometry_id && next_text.clip_rect == text.clip_rect && next_text.glow == text.glow) {
+				continue;
+			}
 
+			let first_instance = first_instance_index as u32;
+			let instance_count = (index + 1 - first_instance_index) as u32;
+			first_instance_index = index + 1;
+
+			unsafe {
 				// Record draw commands
-				logical_device.cmd_bind_index_buffer(text_instance_data_resources.secondary_command_buffer, in_flight_frame.instance_data_buffer.handle, index_array_offset as u64, vk::IndexType::UINT16);
-				logical_device.cmd_bind_vertex_buffers(text_instance_data_resources.secondary_command_buffer, 0, &[in_flight_frame.instance_data_buffer.handle], &[attribute_array_offset as u64]);
-				logical_device.cmd_draw_indexed(text_instance_data_resources.secondary_command_buffer, indices.len() as u32, 1, 0, 0, index as u32);
+				if text.clip_rect != current_clip_rect {
+					let scissor = match text.clip_rect {
+						Some((min, max)) => clip_rect_scissor(&min, &max, self.swapchain.extent),
+						None => overlay_scissors[0]
+					};
+
+					logical_device.cmd_set_scissor(in_flight_frame.text_command_buffer, 0, &[scissor]);
+					current_clip_rect = text.clip_rect;
+				}
+
+				logical_device.cmd_bind_index_buffer(in_flight_frame.text_command_buffer, text_geometry_buffer, geometry_entry.index_array_offset as u64, vk::IndexType::UINT16);
+				logical_device.cmd_bind_vertex_buffers(in_flight_frame.text_command_buffer, 0, &[text_geometry_buffer], &[geometry_entry.attribute_array_offset as u64]);
+
+				// The glow goes under the glyphs so they stay sharp on top of it
+				if let Some(glow) = &text.glow {
+					if current_pipeline != self.text_resources.glow_pipeline {
+						logical_device.cmd_bind_pipeline(in_flight_frame.text_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.text_resources.glow_pipeline);
+						current_pipeline = self.text_resources.glow_pipeline;
+					}
+
+					let color = glow.color;
+					let push_constants = [color.r * glow.intensity, color.g * glow.intensity, color.b * glow.intensity, color.a, glow.radius]
+						.iter()
+						.flat_map(|value| value.to_ne_bytes())
+						.chain((font.color as u32).to_ne_bytes())
+						.collect::<Vec<u8>>();
+
+					logical_device.cmd_push_constants(
+						in_flight_frame.text_command_buffer,
+						self.text_resources.pipeline_layout,
+						vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+						0,
+						&push_constants);
+
+					logical_device.cmd_draw_indexed(in_flight_frame.text_command_buffer, text.indices().len() as u32, instance_count, 0, 0, first_instance);
+				}
+
+				let pipeline = if font.color { self.text_resources.color_pipeline } else { self.text_resources.pipeline };
+
+				if pipeline != current_pipeline {
+					logical_device.cmd_bind_pipeline(in_flight_frame.text_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+					current_pipeline = pipeline;
+				}
+
+				logical_device.cmd_draw_indexed(in_flight_frame.text_command_buffer, text.indices().len() as u32, instance_count, 0, 0, first_instance);
 			}
 		}
 
-		// End command buffer and add to submission list if there are texts to draw
-		unsafe { logical_device.end_command_buffer(text_instance_data_resources.secondary_command_buffer) }.unwrap();
+		// End command buffer and add to overlay submission list after the post process pass if there are texts to draw
+		unsafe { logical_device.end_command_buffer(in_flight_frame.text_command_buffer) }.unwrap();
 
-		if !text_infos.is_empty() {
-			secondary_command_buffers.push(text_instance_data_resources.secondary_command_buffer);
+		let mut overlay_secondary_command_buffers = vec![post_process_command_buffer];
+		overlay_secondary_command_buffers.extend(sprite_command_buffer);
+
+		if !texts.is_empty() {
+			overlay_secondary_command_buffers.push(in_flight_frame.text_command_buffer);
 		}
 
 		// Flush and unmap mesh buffer
@@ -845,40 +2388,175 @@ impl RenderSystem {
 		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
 			.flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
-		let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
-			.render_pass(self.render_pass)
-			.framebuffer(self.swapchain.frames[image_index as usize].framebuffer)
+		let scene_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.scene_render_pass)
+			.framebuffer(self.scene_target.framebuffer)
 			.render_area(vk::Rect2D::builder()
 				.offset(vk::Offset2D::builder().x(0).y(0).build())
-				.extent(self.swapchain.extent)
+				.extent(self.scene_target.extent)
 				.build())
 			.clear_values(&clear_colors);
-		
+
+		let scene_continuation_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.scene_continuation_render_pass)
+			.framebuffer(self.scene_target.framebuffer)
+			.render_area(vk::Rect2D::builder()
+				.offset(vk::Offset2D::builder().x(0).y(0).build())
+				.extent(self.scene_target.extent)
+				.build());
+
+		let overlay_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.overlay_render_pass)
+			.framebuffer(self.swapchain.frames[image_index as usize].framebuffer)
+			.render_area(vk::Rect2D::builder()
+				.offset(vk::Offset2D::builder().x(0).y(0).build())
+				.extent(self.swapchain.extent)
+				.build());
+
+		let breadcrumbs = &self.breadcrumbs;
+		let current_in_flight_frame_index = self.current_in_flight_frame_index;
+		let primary_command_buffer = in_flight_frame.primary_command_buffer;
+		let mark_pass_finished = |pass| if let Some(breadcrumbs) = breadcrumbs {
+			unsafe { breadcrumbs.record_pass_finished(logical_device, primary_command_buffer, current_in_flight_frame_index, pass) };
+		};
+
 		unsafe {
 			logical_device.begin_command_buffer(in_flight_frame.primary_command_buffer, &command_buffer_begin_info).unwrap();
-			logical_device.cmd_begin_render_pass(in_flight_frame.primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
-			logical_device.cmd_execute_commands(in_flight_frame.primary_command_buffer, &secondary_command_buffers);
+
+			if let Some(breadcrumbs) = breadcrumbs {
+				breadcrumbs.record_frame_start(logical_device, primary_command_buffer, current_in_flight_frame_index, frame_number);
+			}
+
+			if let Some(query_pool) = self.timestamp_query_pool {
+				let first_query = self.current_in_flight_frame_index as u32 * 2;
+				logical_device.cmd_reset_query_pool(in_flight_frame.primary_command_buffer, query_pool, first_query, 2);
+				logical_device.cmd_write_timestamp(in_flight_frame.primary_command_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, query_pool, first_query);
+			}
+
+			self.mesh_resources.geometry_cache.record_uploads(logical_device, in_flight_frame.primary_command_buffer, self.current_in_flight_frame_index);
+			self.texture_store.record_uploads(&self.context, in_flight_frame.primary_command_buffer, self.current_in_flight_frame_index);
+			mark_pass_finished(BreadcrumbPass::GeometryUploads);
+
+			// Only the overlay is drawn when the scene isn't rendered this frame
+			if render_scene {
+				self.ssao_resources.record_passes(
+					logical_device,
+					in_flight_frame.primary_command_buffer,
+					self.current_in_flight_frame_index,
+					&self.scene_target.ssao_target,
+					&camera.projection_matrix,
+					self.ssao_enabled);
+				mark_pass_finished(BreadcrumbPass::Ssao);
+
+				if self.deferred_enabled {
+					self.deferred_resources.record_geometry_pass(logical_device, in_flight_frame.primary_command_buffer, self.current_in_flight_frame_index, &self.scene_target.deferred_target);
+				}
+
+				mark_pass_finished(BreadcrumbPass::DeferredGeometry);
+
+				self.motion_vector_resources.record_pass(
+					&self.context,
+					in_flight_frame.primary_command_buffer,
+					self.current_in_flight_frame_index,
+					&self.scene_target.motion_vector_target,
+					geometry_buffer,
+					&view_projection_matrix,
+					motion_vectors_enabled);
+				mark_pass_finished(BreadcrumbPass::MotionVectors);
+
+				self.particle_resources.record_simulation(&self.context, in_flight_frame.primary_command_buffer, self.current_in_flight_frame_index);
+				mark_pass_finished(BreadcrumbPass::ParticleSimulation);
+
+				if reflection_command_buffer.is_some() {
+					self.reflection_resources.record_pass(
+						logical_device,
+						in_flight_frame.primary_command_buffer,
+						self.current_in_flight_frame_index,
+						self.scene_render_pass,
+						&self.scene_target.reflection_target);
+				}
+
+				mark_pass_finished(BreadcrumbPass::Reflection);
+
+				logical_device.cmd_begin_render_pass(in_flight_frame.primary_command_buffer, &scene_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+
+				if self.depth_prepass_enabled {
+					logical_device.cmd_execute_commands(in_flight_frame.primary_command_buffer, &[depth_prepass_command_buffer]);
+				}
+
+				logical_device.cmd_next_subpass(in_flight_frame.primary_command_buffer, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+
+				if !secondary_command_buffers.is_empty() {
+					logical_device.cmd_execute_commands(in_flight_frame.primary_command_buffer, &secondary_command_buffers);
+				}
+
+				logical_device.cmd_end_render_pass(in_flight_frame.primary_command_buffer);
+				mark_pass_finished(BreadcrumbPass::Scene);
+
+				if !refractive_command_buffers.is_empty() {
+					self.refraction_resources.record_copy(
+						logical_device,
+						in_flight_frame.primary_command_buffer,
+						self.scene_target.color_image_resources.image,
+						&self.scene_target.refraction_target);
+
+					// The depth prepass subpass is empty this time around
+					logical_device.cmd_begin_render_pass(in_flight_frame.primary_command_buffer, &scene_continuation_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+					logical_device.cmd_next_subpass(in_flight_frame.primary_command_buffer, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+					logical_device.cmd_execute_commands(in_flight_frame.primary_command_buffer, &refractive_command_buffers);
+					logical_device.cmd_end_render_pass(in_flight_frame.primary_command_buffer);
+				}
+
+				mark_pass_finished(BreadcrumbPass::Refraction);
+
+				self.taa_resources.record_pass(
+					logical_device,
+					in_flight_frame.primary_command_buffer,
+					self.current_in_flight_frame_index,
+					&mut self.scene_target.taa_target,
+					self.taa_enabled);
+				mark_pass_finished(BreadcrumbPass::Taa);
+			}
+			else {
+				mark_pass_finished(BreadcrumbPass::Taa);
+			}
+
+			logical_device.cmd_begin_render_pass(in_flight_frame.primary_command_buffer, &overlay_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(in_flight_frame.primary_command_buffer, &overlay_secondary_command_buffers);
+
 			logical_device.cmd_end_render_pass(in_flight_frame.primary_command_buffer);
+			mark_pass_finished(BreadcrumbPass::Overlay);
+
+			let swapchain_image = self.swapchain.frames[image_index as usize].image;
+			self.frame_capturer.record(&self.context, in_flight_frame.primary_command_buffer, swapchain_image, self.swapchain.extent);
+			mark_pass_finished(BreadcrumbPass::FrameCapture);
+
+			if let Some(query_pool) = self.timestamp_query_pool {
+				let first_query = self.current_in_flight_frame_index as u32 * 2;
+				logical_device.cmd_write_timestamp(in_flight_frame.primary_command_buffer, vk::PipelineStageFlags::BOTTOM_OF_PIPE, query_pool, first_query + 1);
+			}
+
 			logical_device.end_command_buffer(in_flight_frame.primary_command_buffer).unwrap();
 		}
 
-		// Wait for image to be available then submit primary command buffer
-		let image_available_semaphores = [in_flight_frame.image_available];
-		let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-		let command_buffers = [in_flight_frame.primary_command_buffer];
-		let render_finished_semaphores = [in_flight_frame.render_finished];
-		let submit_info = vk::SubmitInfo::builder()
-			.wait_semaphores(&image_available_semaphores)
-			.wait_dst_stage_mask(&wait_stages)
-			.command_buffers(&command_buffers)
-			.signal_semaphores(&render_finished_semaphores);
+		// Wait for image to be available then submit primary command buffer, the image is first written to by the post process pass
+		let submit_start = Instant::now();
+		let record = submit_start.duration_since(render_start) - present_wait;
 
-		unsafe {
-			logical_device.reset_fences(&[in_flight_frame.fence]).unwrap();
-			logical_device.queue_submit(self.context.graphics_queue, &[submit_info.build()], in_flight_frame.fence).unwrap();
+		let result = self.frame_sync.submit(
+			&self.context,
+			frame_number,
+			in_flight_frame.image_available,
+			vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+			in_flight_frame.primary_command_buffer,
+			in_flight_frame.render_finished);
+
+		if let Err(e) = result {
+			device_failed(&self.breadcrumbs, "submit the frame", e);
 		}
 
 		// Wait for render to finish then present swapchain image
+		let render_finished_semaphores = [in_flight_frame.render_finished];
 		let swapchains = [self.swapchain.handle];
 		let image_indices = [image_index];
 		let present_info = vk::PresentInfoKHR::builder()
@@ -886,15 +2564,23 @@ impl RenderSystem {
 			.swapchains(&swapchains)
 			.image_indices(&image_indices);
 		
-		let result = unsafe { self.swapchain.extension.queue_present(self.context.graphics_queue, &present_info) };
+		// The present queue is the graphics queue unless the graphics family can't present, the lock covers it either way
+		let queue_lock = self.context.lock_queue();
+		let result = unsafe { self.swapchain.extension.queue_present(self.context.present_queue, &present_info) };
+		drop(queue_lock);
 
 		let surface_changed = match result {
 			Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
-			Err(e) => panic!("Could not present swapchain image: {}", e),
+			Err(e) => device_failed(&self.breadcrumbs, "present swapchain image", e),
 			_ => false
 		};
 
-		self.current_in_flight_frame_index = (self.current_in_flight_frame_index + 1) % IN_FLIGHT_FRAMES_COUNT;
+		self.cpu_timings = [(FramePhase::Record, record), (FramePhase::Submit, submit_start.elapsed()), (FramePhase::PresentWait, present_wait)];
+		// Frames which only draw the overlay would make the GPU frame time and dynamic resolution think the scene is cheap
+		self.in_flight_frames[self.current_in_flight_frame_index].timestamps_written = self.timestamp_query_pool.is_some() && render_scene;
+		self.scene_rendered = render_scene;
+		self.current_in_flight_frame_index = (self.current_in_flight_frame_index + 1) % self.in_flight_frames.len();
+		self.submitted_frame_count += 1;
 
 		surface_changed
 	}
@@ -904,18 +2590,39 @@ impl Drop for RenderSystem {
 	fn drop(&mut self) {
 		let logical_device = &self.context.logical_device;
 
-		unsafe { logical_device.device_wait_idle() }.unwrap();
+		// The device may have been lost, in which case it's idle anyway
+		if let Err(e) = unsafe { logical_device.device_wait_idle() } {
+			println!("Could not wait for the device to be idle: {}", e);
+		}
+
+		self.frame_sync.drop(logical_device);
+		self.frame_capturer.drop(logical_device);
 
+		if let Some(breadcrumbs) = &self.breadcrumbs {
+			breadcrumbs.drop(logical_device);
+		}
+		self.post_process_resources.drop(logical_device);
+		self.motion_vector_resources.drop(logical_device);
+		self.taa_resources.drop(logical_device);
+		self.deferred_resources.drop(logical_device);
+		self.refraction_resources.drop(logical_device);
+		self.reflection_resources.drop(logical_device);
+		self.particle_resources.drop(logical_device);
+		self.trail_resources.drop(logical_device);
+		self.ssao_resources.drop(logical_device);
 		self.text_resources.drop(logical_device);
+		self.sprite_resources.drop(logical_device);
+		self.texture_store.drop(logical_device);
+		self.texture_table.drop(logical_device);
 		self.mesh_resources.drop(logical_device);
 
 		unsafe {
 			for frame in &mut self.in_flight_frames {
 				logical_device.destroy_semaphore(frame.image_available, None);
 				logical_device.destroy_semaphore(frame.render_finished, None);
-				logical_device.destroy_fence(frame.fence, None);
 				frame.frame_data_buffer.drop(&self.context.logical_device);
 				frame.instance_data_buffer.drop(&self.context.logical_device);
+				frame.light_data_buffer.drop(&self.context.logical_device);
 			}
 			
 			logical_device.destroy_descriptor_set_layout(self.instance_data_descriptor_set_layout, None);
@@ -923,17 +2630,23 @@ impl Drop for RenderSystem {
 			logical_device.destroy_command_pool(self.command_pool, None);
 			logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
 
-			self.swapchain.extension.destroy_swapchain(self.swapchain.handle, None);
-			logical_device.destroy_image(self.swapchain.depth_image_resources.image, None);
-			logical_device.destroy_image_view(self.swapchain.depth_image_resources.image_view, None);
-			logical_device.free_memory(self.swapchain.depth_image_resources.memory, None);
+			if let Some(query_pool) = self.timestamp_query_pool {
+				logical_device.destroy_query_pool(query_pool, None);
+			}
+
+			for retired_swapchain in &self.retired_swapchains {
+				retired_swapchain.resource.drop(logical_device);
+			}
 
-			for frame in &self.swapchain.frames {
-				logical_device.destroy_image_view(frame.image_view, None);
-				logical_device.destroy_framebuffer(frame.framebuffer, None);
+			for retired_scene_target in &self.retired_scene_targets {
+				retired_scene_target.resource.drop(logical_device);
 			}
 
-			logical_device.destroy_render_pass(self.render_pass, None);
+			self.scene_target.drop(logical_device);
+			self.swapchain.drop(logical_device);
+			logical_device.destroy_render_pass(self.overlay_render_pass, None);
+			logical_device.destroy_render_pass(self.scene_render_pass, None);
+			logical_device.destroy_render_pass(self.scene_continuation_render_pass, None);
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,254 @@
+use std::ffi::CString;
+use ash::vk;
+use crate::{vulkan::Context, Vfs};
+use super::{super::{create_shader_module, creation::{create_image_resources, create_transient_image_resources}}, MotionVectorTarget, VELOCITY_FORMAT};
+
+pub fn create_render_pass(context: &Context) -> vk::RenderPass {
+	let velocity_attachment_description = vk::AttachmentDescription::builder()
+		.format(VELOCITY_FORMAT)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+	// The depth is only used to resolve which surface's motion ends up in each pixel
+	let depth_attachment_description = vk::AttachmentDescription::builder()
+		.format(context.depth_format.format)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+	let attachment_descriptions = [velocity_attachment_description.build(), depth_attachment_description.build()];
+
+	let velocity_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(0)
+		.layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+	let velocity_attachment_refs = [velocity_attachment_ref.build()];
+
+	let depth_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(1)
+		.layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+	let subpass_description = vk::SubpassDescription::builder()
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+		.color_attachments(&velocity_attachment_refs)
+		.depth_stencil_attachment(&depth_attachment_ref);
+	let subpass_descriptions = [subpass_description.build()];
+
+	// Wait for the previous frame's post process pass to finish sampling the velocity before writing to it, then make the
+	// writes visible to this frame's post process pass
+	let begin_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(vk::SUBPASS_EXTERNAL)
+		.dst_subpass(0)
+		.src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.src_access_mask(vk::AccessFlags::empty())
+		.dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+		.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+	let end_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(0)
+		.dst_subpass(vk::SUBPASS_EXTERNAL)
+		.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+		.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+	let subpass_dependencies = [begin_subpass_dependency.build(), end_subpass_dependency.build()];
+
+	let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+		.attachments(&attachment_descriptions)
+		.subpasses(&subpass_descriptions)
+		.dependencies(&subpass_dependencies);
+
+	unsafe { context.logical_device.create_render_pass(&render_pass_create_info, None) }.unwrap()
+}
+
+pub fn create_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::VERTEX);
+	let layout_bindings = [layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+// The current and previous view projection matrices are push constants
+pub fn create_pipeline_layout(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout) -> vk::PipelineLayout {
+	let descriptor_set_layouts = [descriptor_set_layout];
+
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::VERTEX)
+		.offset(0)
+		.size(2 * 16 * 4);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+// Only the position is read, quantized geometry is turned back into geometry space by the instance matrices
+pub fn create_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass, quantized: bool) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	let vert_module = create_shader_module(logical_device, vfs, "motion_vector.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr);
+
+	let frag_module = create_shader_module(logical_device, vfs, "motion_vector.frag.spv");
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr);
+
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	// Same vertex layouts as the other triangle materials
+	let (stride, position_format) = if quantized {
+		(12, vk::Format::R16G16B16A16_SNORM)
+	}
+	else {
+		(24, vk::Format::R32G32B32_SFLOAT)
+	};
+
+	let input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(0)
+		.stride(stride)
+		.input_rate(vk::VertexInputRate::VERTEX);
+	let input_binding_descriptions = [input_binding_description.build()];
+
+	let input_attribute_description_position = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(0)
+		.format(position_format)
+		.offset(0);
+	let input_attribute_descriptions = [input_attribute_description_position.build()];
+
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&input_binding_descriptions)
+		.vertex_attribute_descriptions(&input_attribute_descriptions);
+
+	let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+		.primitive_restart_enable(false);
+
+	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+		.viewport_count(1)
+		.scissor_count(1);
+
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
+
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::BACK)
+		.front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+		.sample_shading_enable(false)
+		.rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(true)
+		.depth_write_enable(true)
+		.depth_compare_op(vk::CompareOp::LESS)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
+		.color_write_mask(vk::ColorComponentFlags::R | vk::ColorComponentFlags::G)
+		.blend_enable(false);
+	let color_blend_attachment_states = [color_blend_attachment_state.build()];
+
+	let color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false)
+		.attachments(&color_blend_attachment_states);
+
+	let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&stage_create_infos)
+		.vertex_input_state(&vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let pipeline = unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0];
+
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub(in super::super) fn create_target(context: &Context, extent: vk::Extent2D, render_pass: vk::RenderPass) -> MotionVectorTarget {
+	let velocity_image_resources = create_image_resources(
+		context,
+		extent,
+		VELOCITY_FORMAT,
+		vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+		vk::ImageAspectFlags::COLOR);
+
+	let depth_image_resources = create_transient_image_resources(
+		context,
+		extent,
+		context.depth_format.format,
+		vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+		context.depth_format.aspect_mask());
+
+	let attachments = [velocity_image_resources.image_view, depth_image_resources.image_view];
+
+	let create_info = vk::FramebufferCreateInfo::builder()
+		.render_pass(render_pass)
+		.attachments(&attachments)
+		.width(extent.width)
+		.height(extent.height)
+		.layers(1);
+
+	let framebuffer = unsafe { context.logical_device.create_framebuffer(&create_info, None) }.unwrap();
+
+	MotionVectorTarget {
+		extent,
+		velocity_image_resources,
+		depth_image_resources,
+		framebuffer
+	}
+}
\ No newline at end of file
@@ -0,0 +1,300 @@
+use std::{mem::{size_of, size_of_val}, ptr::copy_nonoverlapping, slice};
+use ash::vk;
+use crate::{Entity, component::Transform3DComponentList, math::Matrix4, vulkan::{Context, Buffer, MemoryCategory}, Vfs};
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+const VELOCITY_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+
+// Renders how far each pixel moved on screen since the previous frame from the previous frame's instance and camera
+// matrices. The velocity is in texture coordinates and points from where the pixel was to where it is now.
+pub struct MotionVectorRenderSystem {
+	render_pass: vk::RenderPass,
+	descriptor_set_layout: vk::DescriptorSetLayout,
+	pipeline_layout: vk::PipelineLayout,
+	pipeline: vk::Pipeline,
+	quantized_pipeline: vk::Pipeline,
+	descriptor_sets: Vec<vk::DescriptorSet>,
+	instance_data_buffers: Vec<Buffer>,
+	// The global matrices of the entities drawn last frame and this frame, indexed by the entity's index
+	previous_global_matrices: Vec<Option<(Entity, Matrix4)>>,
+	global_matrices: Vec<Option<(Entity, Matrix4)>>,
+	previous_view_projection_matrix: Option<Matrix4>,
+	// The current and previous matrix of every instance to draw this frame, in the order of the draws
+	instance_matrices: Vec<[[[f32; 4]; 4]; 2]>,
+	draws: Vec<Draw>
+}
+
+struct Draw {
+	quantized: bool,
+	index_array_offset: usize,
+	attribute_array_offset: usize,
+	index_count: usize,
+	first_instance: usize,
+	instance_count: usize
+}
+
+pub struct MotionVectorTarget {
+	extent: vk::Extent2D,
+	velocity_image_resources: ImageResources,
+	depth_image_resources: ImageResources,
+	framebuffer: vk::Framebuffer
+}
+
+impl MotionVectorTarget {
+	pub fn velocity_image_view(&self) -> vk::ImageView {
+		self.velocity_image_resources.image_view
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_framebuffer(self.framebuffer, None);
+			self.velocity_image_resources.drop(logical_device);
+			self.depth_image_resources.drop(logical_device);
+		}
+	}
+}
+
+impl MotionVectorRenderSystem {
+	pub fn new(context: &Context, vfs: &Vfs, descriptor_pool: vk::DescriptorPool, in_flight_frames_count: usize) -> Self {
+		let logical_device = &context.logical_device;
+		let render_pass = create_render_pass(context);
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+		let pipeline_layout = create_pipeline_layout(logical_device, descriptor_set_layout);
+
+		let instance_data_buffers = (0..in_flight_frames_count)
+			.map(|_| Buffer::null(vk::BufferUsageFlags::STORAGE_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::InstanceData))
+			.collect();
+
+		Self {
+			render_pass,
+			descriptor_set_layout,
+			pipeline_layout,
+			pipeline: create_pipeline(logical_device, vfs, pipeline_layout, render_pass, false),
+			quantized_pipeline: create_pipeline(logical_device, vfs, pipeline_layout, render_pass, true),
+			descriptor_sets: create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			instance_data_buffers,
+			previous_global_matrices: vec![],
+			global_matrices: vec![],
+			previous_view_projection_matrix: None,
+			instance_matrices: vec![],
+			draws: vec![]
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D) -> MotionVectorTarget {
+		create_target(context, extent, self.render_pass)
+	}
+
+	// Adds an instance group to draw this frame. Instances which weren't drawn last frame haven't moved as far as the
+	// velocity is concerned.
+	pub fn add_draw(
+		&mut self,
+		instances: &[Entity],
+		transform3d_components: &Transform3DComponentList,
+		dequantization_matrix: Option<&Matrix4>,
+		index_array_offset: usize,
+		attribute_array_offset: usize,
+		index_count: usize)
+	{
+		let first_instance = self.instance_matrices.len();
+
+		for instance in instances {
+			let global_matrix = transform3d_components.borrow(instance).global_matrix;
+
+			let previous_global_matrix = match self.previous_global_matrices.get(instance.index) {
+				Some(Some((entity, matrix))) if entity == instance => *matrix,
+				_ => global_matrix
+			};
+
+			if self.global_matrices.len() <= instance.index {
+				self.global_matrices.resize(instance.index + 1, None);
+			}
+
+			self.global_matrices[instance.index] = Some((*instance, global_matrix));
+
+			let matrices = match dequantization_matrix {
+				Some(dequantization_matrix) => [(global_matrix * dequantization_matrix).elements, (previous_global_matrix * dequantization_matrix).elements],
+				None => [global_matrix.elements, previous_global_matrix.elements]
+			};
+
+			self.instance_matrices.push(matrices);
+		}
+
+		self.draws.push(Draw {
+			quantized: dequantization_matrix.is_some(),
+			index_array_offset,
+			attribute_array_offset,
+			index_count,
+			first_instance,
+			instance_count: instances.len()
+		});
+	}
+
+	// Records the pass into the primary command buffer and makes this frame's matrices the previous ones. When disabled the
+	// pass only clears the velocity to zero and the history is forgotten so nothing smears when it's enabled again.
+	#[allow(clippy::too_many_arguments)]
+	pub fn record_pass(
+		&mut self,
+		context: &Context,
+		primary_command_buffer: vk::CommandBuffer,
+		in_flight_frame_index: usize,
+		target: &MotionVectorTarget,
+		geometry_buffer: vk::Buffer,
+		view_projection_matrix: &Matrix4,
+		enabled: bool)
+	{
+		let logical_device = &context.logical_device;
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(target.extent)
+			.build();
+
+		let clear_values = [
+			vk::ClearValue {
+				color: vk::ClearColorValue {
+					float32: [0.0, 0.0, 0.0, 0.0]
+				}
+			},
+			vk::ClearValue {
+				depth_stencil: vk::ClearDepthStencilValue {
+					depth: 1.0,
+					stencil: 0
+				}
+			}
+		];
+
+		let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.render_pass)
+			.framebuffer(target.framebuffer)
+			.render_area(render_area)
+			.clear_values(&clear_values);
+
+		if !enabled {
+			unsafe {
+				logical_device.cmd_begin_render_pass(primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+				logical_device.cmd_end_render_pass(primary_command_buffer);
+			}
+
+			self.previous_global_matrices.clear();
+			self.global_matrices.clear();
+			self.previous_view_projection_matrix = None;
+			self.instance_matrices.clear();
+			self.draws.clear();
+			return;
+		}
+
+		// Copy the instance matrices into this in flight frame's buffer, allocating a larger one if necessary
+		let instance_data_buffer = &mut self.instance_data_buffers[in_flight_frame_index];
+		let instance_data_size = (self.instance_matrices.len() * size_of::<[[[f32; 4]; 4]; 2]>()) as u64;
+
+		if instance_data_size > instance_data_buffer.capacity {
+			instance_data_buffer.reallocate(context, instance_data_size);
+
+			let descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
+				.buffer(instance_data_buffer.handle)
+				.offset(0)
+				.range(vk::WHOLE_SIZE);
+			let descriptor_buffer_infos = [descriptor_buffer_info.build()];
+
+			let write_descriptor_set = vk::WriteDescriptorSet::builder()
+				.dst_set(self.descriptor_sets[in_flight_frame_index])
+				.dst_binding(0)
+				.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+				.buffer_info(&descriptor_buffer_infos);
+
+			unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set.build()], &[]) };
+			println!("In flight frame {} motion vector instance data buffer reallocated", in_flight_frame_index);
+		}
+
+		if instance_data_size > 0 {
+			let range = vk::MappedMemoryRange::builder()
+				.memory(instance_data_buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				let instance_data_buffer_ptr = logical_device.map_memory(instance_data_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+				copy_nonoverlapping(self.instance_matrices.as_ptr(), instance_data_buffer_ptr as *mut [[[f32; 4]; 4]; 2], self.instance_matrices.len());
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(instance_data_buffer.memory);
+			}
+		}
+
+		// The camera hasn't moved on the first frame
+		let previous_view_projection_matrix = self.previous_view_projection_matrix.unwrap_or(*view_projection_matrix);
+		let push_constants = [view_projection_matrix.elements, previous_view_projection_matrix.elements];
+		let push_constants_bytes = unsafe { slice::from_raw_parts(push_constants.as_ptr() as *const u8, size_of_val(&push_constants)) };
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(target.extent.width as f32)
+			.height(target.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		unsafe {
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+
+			if !self.draws.is_empty() {
+				logical_device.cmd_set_viewport(primary_command_buffer, 0, &[viewport.build()]);
+				logical_device.cmd_set_scissor(primary_command_buffer, 0, &[render_area]);
+				logical_device.cmd_bind_descriptor_sets(
+					primary_command_buffer,
+					vk::PipelineBindPoint::GRAPHICS,
+					self.pipeline_layout,
+					0,
+					&[self.descriptor_sets[in_flight_frame_index]],
+					&[]);
+				logical_device.cmd_push_constants(primary_command_buffer, self.pipeline_layout, vk::ShaderStageFlags::VERTEX, 0, push_constants_bytes);
+			}
+
+			let mut current_pipeline = None;
+			let mut current_geometry = None;
+
+			for draw in &self.draws {
+				let pipeline = if draw.quantized { self.quantized_pipeline } else { self.pipeline };
+
+				if current_pipeline != Some(pipeline) {
+					logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+					current_pipeline = Some(pipeline);
+				}
+
+				if current_geometry != Some(draw.index_array_offset) {
+					logical_device.cmd_bind_index_buffer(primary_command_buffer, geometry_buffer, draw.index_array_offset as u64, vk::IndexType::UINT16);
+					logical_device.cmd_bind_vertex_buffers(primary_command_buffer, 0, &[geometry_buffer], &[draw.attribute_array_offset as u64]);
+					current_geometry = Some(draw.index_array_offset);
+				}
+
+				logical_device.cmd_draw_indexed(primary_command_buffer, draw.index_count as u32, draw.instance_count as u32, 0, 0, draw.first_instance as u32);
+			}
+
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+		}
+
+		// Entities which weren't drawn this frame are forgotten
+		std::mem::swap(&mut self.previous_global_matrices, &mut self.global_matrices);
+		self.global_matrices.clear();
+		self.previous_view_projection_matrix = Some(*view_projection_matrix);
+		self.instance_matrices.clear();
+		self.draws.clear();
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			for instance_data_buffer in &self.instance_data_buffers {
+				instance_data_buffer.drop(logical_device);
+			}
+
+			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline(self.quantized_pipeline, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+			logical_device.destroy_render_pass(self.render_pass, None);
+		}
+	}
+}
\ No newline at end of file
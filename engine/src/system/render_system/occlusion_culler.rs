@@ -0,0 +1,153 @@
+use crate::math::{Box3, Matrix4, Vector3, Vector4, matrix4};
+
+pub const OCCLUSION_BUFFER_WIDTH: usize = 256;
+pub const OCCLUSION_BUFFER_HEIGHT: usize = 128;
+
+// Anything this close to the camera plane or behind it is never culled and never occludes
+const MIN_W: f32 = 1e-4;
+
+// Rasterizes the occluder meshes into a small depth buffer on the CPU. An instance whose bounding box is behind that depth
+// everywhere it covers on screen is hidden and left out of the instance data. Good for dense indoor scenes where walls hide
+// most of what's around the camera, occluders should be big and simple since every one of their triangles is rasterized each
+// frame.
+pub struct OcclusionCuller {
+	width: usize,
+	height: usize,
+	depths: Vec<f32>,
+	view_projection_matrix: Matrix4
+}
+
+impl OcclusionCuller {
+	pub fn new(width: usize, height: usize) -> Self {
+		Self {
+			width,
+			height,
+			depths: vec![1.0; width * height],
+			view_projection_matrix: matrix4::IDENTITY
+		}
+	}
+
+	// Call it before rasterizing each frame's occluders
+	pub fn clear(&mut self, view_projection_matrix: &Matrix4) {
+		self.depths.fill(1.0);
+		self.view_projection_matrix = *view_projection_matrix;
+	}
+
+	// Triangles crossing the near plane are skipped rather than clipped, they just hide less. Both sides are rasterized.
+	pub fn rasterize_triangle(&mut self, triangle: &[Vector3; 3]) {
+		let Some([a, b, c]) = self.project(triangle) else {
+			return;
+		};
+
+		let area = edge(&a, &b, &c);
+
+		if area == 0.0 {
+			return;
+		}
+
+		let min_x = a.x.min(b.x).min(c.x).max(0.0) as usize;
+		let min_y = a.y.min(b.y).min(c.y).max(0.0) as usize;
+		let max_x = (a.x.max(b.x).max(c.x).ceil().max(0.0) as usize).min(self.width);
+		let max_y = (a.y.max(b.y).max(c.y).ceil().max(0.0) as usize).min(self.height);
+
+		for y in min_y..max_y {
+			for x in min_x..max_x {
+				// Sampled at the pixel's center, the weights are positive inside whichever way the triangle is wound
+				let point = Vector3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+				let wa = edge(&b, &c, &point) / area;
+				let wb = edge(&c, &a, &point) / area;
+				let wc = edge(&a, &b, &point) / area;
+
+				if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+					continue;
+				}
+
+				let depth = wa * a.z + wb * b.z + wc * c.z;
+				let texel = &mut self.depths[y * self.width + x];
+				*texel = texel.min(depth);
+			}
+		}
+	}
+
+	// Boxes partly behind the camera or off the screen count as visible
+	pub fn is_visible(&self, box3: &Box3) -> bool {
+		let Some(corners) = self.project(&box3.as_vertices()) else {
+			return true;
+		};
+
+		let mut screen_box = Box3::from_points(&corners);
+
+		if screen_box.min.x < 0.0 || screen_box.min.y < 0.0 || screen_box.max.x > self.width as f32 || screen_box.max.y > self.height as f32 {
+			return true;
+		}
+
+		screen_box.min.x = screen_box.min.x.floor();
+		screen_box.min.y = screen_box.min.y.floor();
+
+		let min_x = screen_box.min.x as usize;
+		let min_y = screen_box.min.y as usize;
+		let max_x = (screen_box.max.x.ceil() as usize).clamp(min_x + 1, self.width);
+		let max_y = (screen_box.max.y.ceil() as usize).clamp(min_y + 1, self.height);
+
+		(min_y..max_y).any(|y| self.depths[y * self.width + min_x..y * self.width + max_x].iter().any(|depth| *depth >= screen_box.min.z))
+	}
+
+	// Into pixel coordinates with the depth in z, none when any point is too close to the camera or behind it
+	fn project<const N: usize>(&self, points: &[Vector3; N]) -> Option<[Vector3; N]> {
+		let mut projected = [Vector3::default(); N];
+
+		for (point, projected) in points.iter().zip(projected.iter_mut()) {
+			let clip = self.view_projection_matrix * Vector4::new(point.x, point.y, point.z, 1.0);
+
+			if clip.w < MIN_W || clip.z < 0.0 {
+				return None;
+			}
+
+			*projected = Vector3::new(
+				(clip.x / clip.w * 0.5 + 0.5) * self.width as f32,
+				(clip.y / clip.w * 0.5 + 0.5) * self.height as f32,
+				clip.z / clip.w);
+		}
+
+		Some(projected)
+	}
+}
+
+// Twice the signed area of the triangle in screen space
+fn edge(a: &Vector3, b: &Vector3, c: &Vector3) -> f32 {
+	(b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn culler_behind_wall() -> OcclusionCuller {
+		// Looking down +z at a wall 10 units away which covers the middle of the view
+		let mut view_projection_matrix = matrix4::IDENTITY;
+		view_projection_matrix.make_perspective(2.0, 90.0, 0.1, 100.0);
+
+		let mut culler = OcclusionCuller::new(64, 32);
+		culler.clear(&view_projection_matrix);
+
+		let corners = [Vector3::new(-5.0, -5.0, 10.0), Vector3::new(5.0, -5.0, 10.0), Vector3::new(5.0, 5.0, 10.0), Vector3::new(-5.0, 5.0, 10.0)];
+		culler.rasterize_triangle(&[corners[0], corners[1], corners[2]]);
+		culler.rasterize_triangle(&[corners[2], corners[3], corners[0]]);
+		culler
+	}
+
+	#[test]
+	fn boxes_behind_occluders_are_hidden() {
+		let culler = culler_behind_wall();
+
+		assert!(!culler.is_visible(&Box3::new(Vector3::new(-1.0, -1.0, 20.0), Vector3::new(1.0, 1.0, 22.0))));
+		assert!(culler.is_visible(&Box3::new(Vector3::new(-1.0, -1.0, 5.0), Vector3::new(1.0, 1.0, 7.0))));
+
+		// Poking out from behind the side of the wall or through it
+		assert!(culler.is_visible(&Box3::new(Vector3::new(8.0, -1.0, 20.0), Vector3::new(14.0, 1.0, 22.0))));
+		assert!(culler.is_visible(&Box3::new(Vector3::new(-1.0, -1.0, 9.0), Vector3::new(1.0, 1.0, 22.0))));
+
+		// Around the camera
+		assert!(culler.is_visible(&Box3::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0))));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,206 @@
+use std::ffi::CString;
+use ash::vk;
+use crate::Vfs;
+use super::super::create_shader_module;
+
+// The particle pool, dead list, alive lists, indirect arguments and emitters
+pub fn create_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..5)
+		.map(|binding| {
+			vk::DescriptorSetLayoutBinding::builder()
+				.binding(binding)
+				.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+				.descriptor_count(1)
+				.stage_flags(vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX)
+				.build()
+		})
+		.collect();
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+pub fn create_compute_pipeline_layout(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout) -> vk::PipelineLayout {
+	let descriptor_set_layouts = [descriptor_set_layout];
+
+	// The emitter count, spawn count, time step, random seed, current alive list and pool size
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::COMPUTE)
+		.offset(0)
+		.size(6 * 4);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+pub fn create_pipeline_layout(logical_device: &ash::Device, frame_data_descriptor_set_layout: vk::DescriptorSetLayout, descriptor_set_layout: vk::DescriptorSetLayout) -> vk::PipelineLayout {
+	let descriptor_set_layouts = [frame_data_descriptor_set_layout, descriptor_set_layout];
+
+	// The alive list to draw and the pool size
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::VERTEX)
+		.offset(0)
+		.size(2 * 4);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+pub fn create_compute_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, filename: &str) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let module = create_shader_module(logical_device, vfs, filename);
+
+	let stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::COMPUTE)
+		.module(module)
+		.name(entry_point.as_c_str());
+
+	let pipeline_create_info = vk::ComputePipelineCreateInfo::builder()
+		.stage(stage_create_info.build())
+		.layout(pipeline_layout);
+
+	let pipeline = unsafe { logical_device.create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0];
+	unsafe { logical_device.destroy_shader_module(module, None) };
+	pipeline
+}
+
+// Camera facing quads generated in the vertex shader, one instance per alive particle. They're depth tested against the scene
+// without writing it and added onto what's behind them so they don't need sorting.
+pub fn create_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	let vert_module = create_shader_module(logical_device, vfs, "particle.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr);
+
+	let frag_module = create_shader_module(logical_device, vfs, "particle.frag.spv");
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr);
+
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+	let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+		.primitive_restart_enable(false);
+
+	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+		.viewport_count(1)
+		.scissor_count(1);
+
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
+
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::NONE)
+		.front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+		.sample_shading_enable(false)
+		.rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(true)
+		.depth_write_enable(false)
+		.depth_compare_op(vk::CompareOp::LESS)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
+		.blend_enable(true)
+		.src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+		.dst_color_blend_factor(vk::BlendFactor::ONE)
+		.color_blend_op(vk::BlendOp::ADD)
+		.src_alpha_blend_factor(vk::BlendFactor::ZERO)
+		.dst_alpha_blend_factor(vk::BlendFactor::ONE)
+		.alpha_blend_op(vk::BlendOp::ADD);
+	let color_blend_attachment_states = [color_blend_attachment_state.build()];
+
+	let color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false)
+		.attachments(&color_blend_attachment_states);
+
+	let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&stage_create_infos)
+		.vertex_input_state(&vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(1);
+
+	let pipeline = unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0];
+
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub fn create_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
+
+pub fn update_descriptor_set(logical_device: &ash::Device, descriptor_set: vk::DescriptorSet, buffers: [vk::Buffer; 5]) {
+	let descriptor_buffer_infos: Vec<[vk::DescriptorBufferInfo; 1]> = buffers.iter()
+		.map(|buffer| [vk::DescriptorBufferInfo::builder().buffer(*buffer).offset(0).range(vk::WHOLE_SIZE).build()])
+		.collect();
+
+	let write_descriptor_sets: Vec<vk::WriteDescriptorSet> = descriptor_buffer_infos.iter()
+		.enumerate()
+		.map(|(binding, descriptor_buffer_info)| {
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(binding as u32)
+				.descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+				.buffer_info(descriptor_buffer_info)
+				.build()
+		})
+		.collect();
+
+	unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+}
\ No newline at end of file
@@ -0,0 +1,381 @@
+use std::{mem::{size_of, size_of_val}, ptr::copy_nonoverlapping, slice};
+use ash::vk;
+use crate::{ParticleEmitter, pool::Pool, vulkan::{Context, Buffer, MemoryCategory}, Vfs};
+
+mod creation;
+use creation::*;
+
+// Must match the particle struct in the shaders
+const PARTICLE_SIZE: usize = 96;
+const WORKGROUP_SIZE: usize = 64;
+
+// Simulates and draws the particles of every emitter entirely on the GPU. Particles live in a fixed size pool, the indices of
+// the free ones are kept in a dead list and the indices of the living ones are compacted into one of two alive lists each
+// frame. Simulating reads one list and writes the survivors and the newly spawned particles into the other, which is then
+// drawn with the alive count copied straight into an indirect draw so the CPU never needs to know how many there are.
+pub struct ParticleRenderSystem {
+	max_particles: usize,
+	descriptor_set_layout: vk::DescriptorSetLayout,
+	compute_pipeline_layout: vk::PipelineLayout,
+	init_pipeline: vk::Pipeline,
+	emit_pipeline: vk::Pipeline,
+	prepare_pipeline: vk::Pipeline,
+	simulate_pipeline: vk::Pipeline,
+	pipeline_layout: vk::PipelineLayout,
+	pipeline: vk::Pipeline,
+	particle_buffer: Buffer,
+	dead_list_buffer: Buffer,
+	alive_lists_buffer: Buffer,
+	// The indirect draw arguments followed by the simulation's indirect dispatch arguments
+	indirect_buffer: Buffer,
+	emitter_buffers: Vec<Buffer>,
+	descriptor_sets: Vec<vk::DescriptorSet>,
+	command_buffers: Vec<vk::CommandBuffer>,
+	pub emitters: Pool<ParticleEmitter>,
+	emitter_data: Vec<EmitterData>,
+	delta: f32,
+	// The alive list holding the particles drawn last frame
+	current_list: usize,
+	initialized: bool,
+	seed: u32
+}
+
+// Must match the emitter struct in the shaders
+#[repr(C)]
+struct EmitterData {
+	position: [f32; 3],
+	first_spawn: u32,
+	velocity: [f32; 3],
+	spread: f32,
+	acceleration: [f32; 3],
+	drag: f32,
+	start_color: [f32; 4],
+	end_color: [f32; 4],
+	lifetime: f32,
+	start_size: f32,
+	end_size: f32,
+	spawn_count: u32
+}
+
+impl ParticleRenderSystem {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		context: &Context,
+		vfs: &Vfs,
+		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		scene_render_pass: vk::RenderPass,
+		command_pool: vk::CommandPool,
+		descriptor_pool: vk::DescriptorPool,
+		in_flight_frames_count: usize,
+		max_particles: usize)
+		-> Self
+	{
+		assert!(max_particles > 0, "The particle pool must hold at least one particle");
+
+		let logical_device = &context.logical_device;
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+		let compute_pipeline_layout = create_compute_pipeline_layout(logical_device, descriptor_set_layout);
+		let pipeline_layout = create_pipeline_layout(logical_device, frame_data_descriptor_set_layout, descriptor_set_layout);
+
+		let storage_buffer = |size: usize, usage: vk::BufferUsageFlags| {
+			Buffer::new(context, size as u64, vk::BufferUsageFlags::STORAGE_BUFFER | usage, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::Other)
+		};
+
+		let particle_buffer = storage_buffer(max_particles * PARTICLE_SIZE, vk::BufferUsageFlags::empty());
+		let dead_list_buffer = storage_buffer((1 + max_particles) * 4, vk::BufferUsageFlags::empty());
+		let alive_lists_buffer = storage_buffer((2 + 2 * max_particles) * 4, vk::BufferUsageFlags::TRANSFER_SRC);
+		let indirect_buffer = storage_buffer(8 * 4, vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::TRANSFER_DST);
+
+		let emitter_buffers: Vec<Buffer> = (0..in_flight_frames_count)
+			.map(|_| Buffer::new(context, 16 * size_of::<EmitterData>() as u64, vk::BufferUsageFlags::STORAGE_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Other))
+			.collect();
+
+		let descriptor_sets = create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count);
+
+		for (descriptor_set, emitter_buffer) in descriptor_sets.iter().zip(&emitter_buffers) {
+			update_descriptor_set(
+				logical_device,
+				*descriptor_set,
+				[particle_buffer.handle, dead_list_buffer.handle, alive_lists_buffer.handle, indirect_buffer.handle, emitter_buffer.handle]);
+		}
+
+		Self {
+			max_particles,
+			descriptor_set_layout,
+			compute_pipeline_layout,
+			init_pipeline: create_compute_pipeline(logical_device, vfs, compute_pipeline_layout, "particle_init.comp.spv"),
+			emit_pipeline: create_compute_pipeline(logical_device, vfs, compute_pipeline_layout, "particle_emit.comp.spv"),
+			prepare_pipeline: create_compute_pipeline(logical_device, vfs, compute_pipeline_layout, "particle_prepare.comp.spv"),
+			simulate_pipeline: create_compute_pipeline(logical_device, vfs, compute_pipeline_layout, "particle_simulate.comp.spv"),
+			pipeline_layout,
+			pipeline: create_pipeline(logical_device, vfs, pipeline_layout, scene_render_pass),
+			particle_buffer,
+			dead_list_buffer,
+			alive_lists_buffer,
+			indirect_buffer,
+			emitter_buffers,
+			descriptor_sets,
+			command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count),
+			emitters: Pool::new(),
+			emitter_data: vec![],
+			delta: 0.0,
+			current_list: 0,
+			initialized: false,
+			seed: 0
+		}
+	}
+
+	// Advances the emitters, the particles themselves are simulated over the accumulated time the next time a frame is rendered
+	pub fn update(&mut self, delta: f32) {
+		for emitter in self.emitters.iter_mut() {
+			emitter.update(delta);
+		}
+
+		self.delta += delta;
+	}
+
+	// Records the secondary command buffer drawing the alive particles, it's executed in the scene render pass's shading subpass.
+	// It draws the list this frame's simulation writes so it must be recorded before record_simulation.
+	pub fn record_draw_command_buffer(
+		&self,
+		logical_device: &ash::Device,
+		in_flight_frame_index: usize,
+		extent: vk::Extent2D,
+		scene_render_pass: vk::RenderPass,
+		scene_framebuffer: vk::Framebuffer,
+		frame_data_descriptor_set: vk::DescriptorSet)
+		-> vk::CommandBuffer
+	{
+		// The list the simulation writes this frame
+		let push_constants = [(self.current_list ^ 1) as u32, self.max_particles as u32];
+		let push_constants_bytes = unsafe { slice::from_raw_parts(push_constants.as_ptr() as *const u8, size_of_val(&push_constants)) };
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(extent.width as f32)
+			.height(extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		let command_buffer = self.command_buffers[in_flight_frame_index];
+
+		let command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(scene_render_pass)
+			.subpass(1)
+			.framebuffer(scene_framebuffer);
+
+		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&command_buffer_inheritance_info);
+
+		unsafe {
+			logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+			logical_device.cmd_set_viewport(command_buffer, 0, &[viewport.build()]);
+			logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+			logical_device.cmd_bind_descriptor_sets(
+				command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.pipeline_layout,
+				0,
+				&[frame_data_descriptor_set, self.descriptor_sets[in_flight_frame_index]],
+				&[]);
+			logical_device.cmd_push_constants(command_buffer, self.pipeline_layout, vk::ShaderStageFlags::VERTEX, 0, push_constants_bytes);
+			logical_device.cmd_draw_indirect(command_buffer, self.indirect_buffer.handle, 0, 1, 16);
+			logical_device.end_command_buffer(command_buffer).unwrap();
+		}
+
+		command_buffer
+	}
+
+	// Records the simulation into the primary command buffer before the scene render pass. The particles alive last frame are
+	// advanced and compacted into the other alive list, then the spawned particles are appended to it and its count is copied
+	// into the indirect draw.
+	pub fn record_simulation(&mut self, context: &Context, primary_command_buffer: vk::CommandBuffer, in_flight_frame_index: usize) {
+		let logical_device = &context.logical_device;
+
+		// Gather the spawned particles of every emitter, each one gets a contiguous range of the emit dispatch's invocations
+		self.emitter_data.clear();
+		let mut spawn_count = 0;
+
+		for emitter in self.emitters.iter_mut() {
+			let count = emitter.take_spawn_count().min(self.max_particles - spawn_count);
+
+			if count == 0 {
+				continue;
+			}
+
+			self.emitter_data.push(EmitterData {
+				position: [emitter.position.x, emitter.position.y, emitter.position.z],
+				first_spawn: spawn_count as u32,
+				velocity: [emitter.velocity.x, emitter.velocity.y, emitter.velocity.z],
+				spread: emitter.spread,
+				acceleration: [emitter.acceleration.x, emitter.acceleration.y, emitter.acceleration.z],
+				drag: emitter.drag,
+				start_color: [emitter.start_color.r, emitter.start_color.g, emitter.start_color.b, emitter.start_color.a],
+				end_color: [emitter.end_color.r, emitter.end_color.g, emitter.end_color.b, emitter.end_color.a],
+				lifetime: emitter.lifetime,
+				start_size: emitter.start_size,
+				end_size: emitter.end_size,
+				spawn_count: count as u32
+			});
+
+			spawn_count += count;
+		}
+
+		// Copy the emitters into this in flight frame's buffer, allocating a larger one if necessary
+		let emitter_buffer = &mut self.emitter_buffers[in_flight_frame_index];
+		let emitter_data_size = (self.emitter_data.len() * size_of::<EmitterData>()) as u64;
+
+		if emitter_data_size > emitter_buffer.capacity {
+			emitter_buffer.reallocate(context, emitter_data_size);
+
+			update_descriptor_set(
+				logical_device,
+				self.descriptor_sets[in_flight_frame_index],
+				[self.particle_buffer.handle, self.dead_list_buffer.handle, self.alive_lists_buffer.handle, self.indirect_buffer.handle, emitter_buffer.handle]);
+
+			println!("In flight frame {} particle emitter buffer reallocated", in_flight_frame_index);
+		}
+
+		if emitter_data_size > 0 {
+			let range = vk::MappedMemoryRange::builder()
+				.memory(emitter_buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				let emitter_buffer_ptr = logical_device.map_memory(emitter_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+				copy_nonoverlapping(self.emitter_data.as_ptr(), emitter_buffer_ptr as *mut EmitterData, self.emitter_data.len());
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(emitter_buffer.memory);
+			}
+		}
+
+		let next_list = self.current_list ^ 1;
+
+		// The emitter count, spawn count, time step, random seed, current alive list and pool size
+		let push_constants = [
+			self.emitter_data.len() as u32,
+			spawn_count as u32,
+			self.delta.to_bits(),
+			self.seed,
+			self.current_list as u32,
+			self.max_particles as u32
+		];
+		let push_constants_bytes = unsafe { slice::from_raw_parts(push_constants.as_ptr() as *const u8, size_of_val(&push_constants)) };
+
+		let barrier = |src_stage_mask: vk::PipelineStageFlags, src_access_mask: vk::AccessFlags, dst_stage_mask: vk::PipelineStageFlags, dst_access_mask: vk::AccessFlags| {
+			let memory_barrier = vk::MemoryBarrier::builder()
+				.src_access_mask(src_access_mask)
+				.dst_access_mask(dst_access_mask);
+
+			unsafe { logical_device.cmd_pipeline_barrier(primary_command_buffer, src_stage_mask, dst_stage_mask, vk::DependencyFlags::empty(), &[memory_barrier.build()], &[], &[]) };
+		};
+
+		let compute_to_compute = || barrier(
+			vk::PipelineStageFlags::COMPUTE_SHADER,
+			vk::AccessFlags::SHADER_WRITE,
+			vk::PipelineStageFlags::COMPUTE_SHADER,
+			vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+
+		let dispatch_count = |invocations: usize| invocations.div_ceil(WORKGROUP_SIZE) as u32;
+
+		unsafe {
+			// Last frame's draw must be done reading the lists and arguments before they're written
+			barrier(
+				vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+				vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ,
+				vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+				vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE);
+
+			logical_device.cmd_bind_descriptor_sets(
+				primary_command_buffer,
+				vk::PipelineBindPoint::COMPUTE,
+				self.compute_pipeline_layout,
+				0,
+				&[self.descriptor_sets[in_flight_frame_index]],
+				&[]);
+			logical_device.cmd_push_constants(primary_command_buffer, self.compute_pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, push_constants_bytes);
+
+			// Every particle starts dead
+			if !self.initialized {
+				logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::COMPUTE, self.init_pipeline);
+				logical_device.cmd_dispatch(primary_command_buffer, dispatch_count(self.max_particles), 1, 1);
+				compute_to_compute();
+				self.initialized = true;
+			}
+
+			// Size the simulation's dispatch to last frame's alive count and empty the list it writes to
+			logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::COMPUTE, self.prepare_pipeline);
+			logical_device.cmd_dispatch(primary_command_buffer, 1, 1, 1);
+			barrier(
+				vk::PipelineStageFlags::COMPUTE_SHADER,
+				vk::AccessFlags::SHADER_WRITE,
+				vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::COMPUTE_SHADER,
+				vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+
+			// Particles which die return their index to the dead list before any are spawned so they can be reused right away
+			logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::COMPUTE, self.simulate_pipeline);
+			logical_device.cmd_dispatch_indirect(primary_command_buffer, self.indirect_buffer.handle, 16);
+			compute_to_compute();
+
+			if spawn_count > 0 {
+				logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::COMPUTE, self.emit_pipeline);
+				logical_device.cmd_dispatch(primary_command_buffer, dispatch_count(spawn_count), 1, 1);
+			}
+
+			barrier(
+				vk::PipelineStageFlags::COMPUTE_SHADER,
+				vk::AccessFlags::SHADER_WRITE,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::AccessFlags::TRANSFER_READ);
+
+			// The alive count becomes the draw's instance count
+			let region = vk::BufferCopy::builder()
+				.src_offset((next_list * 4) as u64)
+				.dst_offset(4)
+				.size(4);
+
+			logical_device.cmd_copy_buffer(primary_command_buffer, self.alive_lists_buffer.handle, self.indirect_buffer.handle, &[region.build()]);
+
+			barrier(
+				vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+				vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+				vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+				vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::SHADER_READ);
+		}
+
+		self.current_list = next_list;
+		self.delta = 0.0;
+		self.seed = self.seed.wrapping_add(1);
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_pipeline(self.init_pipeline, None);
+			logical_device.destroy_pipeline(self.emit_pipeline, None);
+			logical_device.destroy_pipeline(self.prepare_pipeline, None);
+			logical_device.destroy_pipeline(self.simulate_pipeline, None);
+			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline_layout(self.compute_pipeline_layout, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+		}
+
+		self.particle_buffer.drop(logical_device);
+		self.dead_list_buffer.drop(logical_device);
+		self.alive_lists_buffer.drop(logical_device);
+		self.indirect_buffer.drop(logical_device);
+
+		for emitter_buffer in &self.emitter_buffers {
+			emitter_buffer.drop(logical_device);
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,184 @@
+use std::ffi::CString;
+use ash::vk;
+use crate::Vfs;
+use super::super::create_shader_module;
+
+pub fn create_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let scene_image_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+	let lut_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(1)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+	let next_lut_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(2)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+	let velocity_image_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(3)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+	let layout_bindings = [scene_image_layout_binding.build(), lut_layout_binding.build(), next_lut_layout_binding.build(), velocity_image_layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+pub fn create_pipeline_layout(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout) -> vk::PipelineLayout {
+	let descriptor_set_layouts = [descriptor_set_layout];
+
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT)
+		.offset(0)
+		.size(2 * 4);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+pub fn create_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass) -> vk::Pipeline {
+	// Create entry point string
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	// Create shader stage create infos
+	let vert_module = create_shader_module(logical_device, vfs, "post_process.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr);
+	
+	let frag_module = create_shader_module(logical_device, vfs, "post_process.frag.spv");
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr);
+	
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	// Create vertex input state create info, the full screen triangle is generated in the vertex shader
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder();
+	
+	// Create input assembly state create info
+	let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+		.primitive_restart_enable(false);
+	
+	// Create viewport state create info, the viewport and scissor are set dynamically so the pipeline survives swapchain recreation
+	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+		.viewport_count(1)
+		.scissor_count(1);
+	
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
+	
+	// Create rasterization state create info
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::empty())
+		.front_face(vk::FrontFace::CLOCKWISE)
+		.depth_bias_enable(false);
+	
+	// Create multisample state create info
+	let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+		.sample_shading_enable(false)
+		.rasterization_samples(vk::SampleCountFlags::TYPE_1);
+	
+	// Create depth stencil state create info
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(false)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+	
+	// Create color blend state create info
+	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
+		.blend_enable(false);
+	let color_blend_attachment_states = [color_blend_attachment_state.build()];
+
+	let color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false)
+		.attachments(&color_blend_attachment_states);
+	
+	// Create pipeline
+	let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&stage_create_infos)
+		.vertex_input_state(&vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+	
+	let pipeline = unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0];
+
+	// Destroy shader modules
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+	
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub fn create_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+	
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
+
+pub fn create_sampler(logical_device: &ash::Device) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(vk::Filter::LINEAR)
+		.min_filter(vk::Filter::LINEAR)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+	
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,323 @@
+use std::{ptr::copy_nonoverlapping, time::{Duration, Instant}};
+use ash::vk;
+use crate::{ColorGradingLut, vulkan::{Context, Buffer, MemoryCategory}, Vfs};
+use super::{ImageResources, Retired};
+
+mod creation;
+use creation::*;
+
+const IDENTITY_LUT_SIZE: usize = 2;
+
+pub struct PostProcessRenderSystem {
+	descriptor_set_layout: vk::DescriptorSetLayout,
+	pub pipeline_layout: vk::PipelineLayout,
+	pub pipeline: vk::Pipeline,
+	pub descriptor_sets: Vec<vk::DescriptorSet>,
+	pub command_buffers: Vec<vk::CommandBuffer>,
+	sampler: vk::Sampler,
+	lut: ImageResources,
+	lut_transition: Option<LutTransition>,
+	retired_luts: Vec<Retired<ImageResources>>
+}
+
+struct LutTransition {
+	lut: ImageResources,
+	start: Instant,
+	duration: Duration
+}
+
+impl PostProcessRenderSystem {
+	pub fn new(context: &Context, vfs: &Vfs, render_pass: vk::RenderPass, descriptor_pool: vk::DescriptorPool, command_pool: vk::CommandPool, in_flight_frames_count: usize) -> Self {
+		let logical_device = &context.logical_device;
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+		let pipeline_layout = create_pipeline_layout(logical_device, descriptor_set_layout);
+		let pipeline = create_pipeline(logical_device, vfs, pipeline_layout, render_pass);
+		let descriptor_sets = create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count);
+		let command_buffers = create_command_buffers(logical_device, command_pool, in_flight_frames_count);
+		let sampler = create_sampler(logical_device);
+		let lut = Self::upload_lut(context, &ColorGradingLut::identity(IDENTITY_LUT_SIZE));
+
+		Self {
+			descriptor_set_layout,
+			pipeline_layout,
+			pipeline,
+			descriptor_sets,
+			command_buffers,
+			sampler,
+			lut,
+			lut_transition: None,
+			retired_luts: vec![]
+		}
+	}
+
+	pub fn set_lut(&mut self, context: &Context, lut: &ColorGradingLut, submitted_frame_count: usize) {
+		let new_lut = Self::upload_lut(context, lut);
+		let old_lut = std::mem::replace(&mut self.lut, new_lut);
+		self.retire_lut(old_lut, submitted_frame_count);
+
+		if let Some(lut_transition) = self.lut_transition.take() {
+			self.retire_lut(lut_transition.lut, submitted_frame_count);
+		}
+	}
+
+	pub fn blend_to_lut(&mut self, context: &Context, lut: &ColorGradingLut, duration: Duration, submitted_frame_count: usize) {
+		// Finish the transition in progress so the new one starts from where the old one was heading
+		if let Some(lut_transition) = self.lut_transition.take() {
+			let old_lut = std::mem::replace(&mut self.lut, lut_transition.lut);
+			self.retire_lut(old_lut, submitted_frame_count);
+		}
+
+		self.lut_transition = Some(LutTransition {
+			lut: Self::upload_lut(context, lut),
+			start: Instant::now(),
+			duration
+		});
+	}
+
+	// Returns how far through the transition to the next LUT the frame is, finishing the transition once it's complete
+	pub fn update_lut_blend(&mut self, submitted_frame_count: usize) -> f32 {
+		let lut_transition = match &self.lut_transition {
+			Some(lut_transition) => lut_transition,
+			None => return 0.0
+		};
+
+		let elapsed = lut_transition.start.elapsed();
+
+		if elapsed < lut_transition.duration {
+			return elapsed.as_secs_f32() / lut_transition.duration.as_secs_f32();
+		}
+
+		let lut_transition = self.lut_transition.take().unwrap();
+		let old_lut = std::mem::replace(&mut self.lut, lut_transition.lut);
+		self.retire_lut(old_lut, submitted_frame_count);
+		0.0
+	}
+
+	pub fn update_descriptor_set(&self, logical_device: &ash::Device, in_flight_frame_index: usize, scene_image_view: vk::ImageView, velocity_image_view: vk::ImageView) {
+		let next_lut = self.lut_transition.as_ref().map_or(&self.lut, |lut_transition| &lut_transition.lut);
+
+		let scene_image_descriptor_image_info = vk::DescriptorImageInfo::builder()
+			.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.image_view(scene_image_view)
+			.sampler(self.sampler);
+		let scene_image_descriptor_image_infos = [scene_image_descriptor_image_info.build()];
+
+		let lut_descriptor_image_info = vk::DescriptorImageInfo::builder()
+			.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.image_view(self.lut.image_view)
+			.sampler(self.sampler);
+		let lut_descriptor_image_infos = [lut_descriptor_image_info.build()];
+
+		let next_lut_descriptor_image_info = vk::DescriptorImageInfo::builder()
+			.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.image_view(next_lut.image_view)
+			.sampler(self.sampler);
+		let next_lut_descriptor_image_infos = [next_lut_descriptor_image_info.build()];
+
+		let velocity_descriptor_image_info = vk::DescriptorImageInfo::builder()
+			.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.image_view(velocity_image_view)
+			.sampler(self.sampler);
+		let velocity_descriptor_image_infos = [velocity_descriptor_image_info.build()];
+
+		let descriptor_set = self.descriptor_sets[in_flight_frame_index];
+		let write_descriptor_sets = [
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(0)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(&scene_image_descriptor_image_infos)
+				.build(),
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(1)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(&lut_descriptor_image_infos)
+				.build(),
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(2)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(&next_lut_descriptor_image_infos)
+				.build(),
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(3)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(&velocity_descriptor_image_infos)
+				.build()
+		];
+
+		unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+	}
+
+	pub fn destroy_unused_retired_luts(&mut self, logical_device: &ash::Device, submitted_frame_count: usize, in_flight_frames_count: usize) {
+		self.retired_luts.retain(|retired_lut| {
+			if retired_lut.is_unused(submitted_frame_count, in_flight_frames_count) {
+				unsafe { retired_lut.resource.drop(logical_device) };
+				false
+			}
+			else {
+				true
+			}
+		});
+	}
+
+	fn retire_lut(&mut self, lut: ImageResources, submitted_frame_count: usize) {
+		self.retired_luts.push(Retired {
+			resource: lut,
+			retired_frame_count: submitted_frame_count
+		});
+	}
+
+	fn upload_lut(context: &Context, lut: &ColorGradingLut) -> ImageResources {
+		let logical_device = &context.logical_device;
+		let size = lut.size as u32;
+		let format = vk::Format::R8G8B8A8_UNORM;
+
+		// Create image
+		let image_create_info = vk::ImageCreateInfo::builder()
+			.image_type(vk::ImageType::TYPE_3D)
+			.extent(vk::Extent3D::builder().width(size).height(size).depth(size).build())
+			.mip_levels(1)
+			.array_layers(1)
+			.format(format)
+			.tiling(vk::ImageTiling::OPTIMAL)
+			.initial_layout(vk::ImageLayout::UNDEFINED)
+			.usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+			.sharing_mode(vk::SharingMode::EXCLUSIVE)
+			.samples(vk::SampleCountFlags::TYPE_1);
+
+		let image = unsafe { logical_device.create_image(&image_create_info, None) }.unwrap();
+
+		// Allocate device local memory and bind it to the image
+		let memory_requirements = unsafe { logical_device.get_image_memory_requirements(image) };
+		let (memory, allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::Textures);
+		unsafe { logical_device.bind_image_memory(image, memory, 0) }.unwrap();
+
+		// Create image view
+		let subresource_range = vk::ImageSubresourceRange::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build();
+
+		let image_view_create_info = vk::ImageViewCreateInfo::builder()
+			.image(image)
+			.view_type(vk::ImageViewType::TYPE_3D)
+			.format(format)
+			.subresource_range(subresource_range);
+
+		let image_view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }.unwrap();
+
+		// Copy the LUT into a staging buffer
+		let pixels = lut.to_rgba8();
+		let staging_buffer = Buffer::new(context, pixels.len() as u64, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging);
+		let staging_buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+		let range = vk::MappedMemoryRange::builder()
+			.memory(staging_buffer.memory)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		unsafe {
+			copy_nonoverlapping(pixels.as_ptr(), staging_buffer_ptr as *mut u8, pixels.len());
+			logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+			logical_device.unmap_memory(staging_buffer.memory);
+		}
+
+		// Record command buffer to copy the staging buffer into the image
+		let transfer_image_memory_barrier = vk::ImageMemoryBarrier::builder()
+			.old_layout(vk::ImageLayout::UNDEFINED)
+			.new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.image(image)
+			.subresource_range(subresource_range)
+			.src_access_mask(vk::AccessFlags::empty())
+			.dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+
+		let shader_read_image_memory_barrier = vk::ImageMemoryBarrier::builder()
+			.old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+			.new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+			.image(image)
+			.subresource_range(subresource_range)
+			.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+			.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+		let region = vk::BufferImageCopy::builder()
+			.buffer_offset(0)
+			.buffer_row_length(0)
+			.buffer_image_height(0)
+			.image_subresource(vk::ImageSubresourceLayers::builder()
+				.aspect_mask(vk::ImageAspectFlags::COLOR)
+				.mip_level(0)
+				.base_array_layer(0)
+				.layer_count(1)
+				.build())
+			.image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+			.image_extent(vk::Extent3D::builder().width(size).height(size).depth(size).build());
+
+		let command_pool = context.thread_command_pool();
+		let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+			.level(vk::CommandBufferLevel::PRIMARY)
+			.command_pool(command_pool)
+			.command_buffer_count(1);
+
+		let command_buffer = unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()[0];
+
+		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+		unsafe {
+			logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+			logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[transfer_image_memory_barrier.build()]);
+			logical_device.cmd_copy_buffer_to_image(command_buffer, staging_buffer.handle, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region.build()]);
+			logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &[shader_read_image_memory_barrier.build()]);
+			logical_device.end_command_buffer(command_buffer).unwrap();
+		}
+
+		// Submit command buffer
+		let command_buffers = [command_buffer];
+		let submit_info = vk::SubmitInfo::builder()
+			.command_buffers(&command_buffers);
+
+		unsafe {
+			context.submit_and_wait(&[submit_info.build()]);
+			logical_device.free_command_buffers(command_pool, &command_buffers);
+		}
+
+		// Destroy staging buffer
+		staging_buffer.drop(logical_device);
+
+		ImageResources {
+			image,
+			image_view,
+			memory,
+			_allocation: Some(allocation)
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			self.lut.drop(logical_device);
+
+			if let Some(lut_transition) = &self.lut_transition {
+				lut_transition.lut.drop(logical_device);
+			}
+
+			for retired_lut in &self.retired_luts {
+				retired_lut.resource.drop(logical_device);
+			}
+
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,137 @@
+use ash::vk;
+use crate::vulkan::{Buffer, Context, MemoryCategory};
+use super::{super::{creation::create_image_resources, FRAME_DATA_MEMORY_SIZE}, ReflectionTarget};
+
+pub fn create_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+	let layout_bindings = [layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+pub fn create_sampler(logical_device: &ash::Device) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(vk::Filter::LINEAR)
+		.min_filter(vk::Filter::LINEAR)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub fn update_image_descriptor_set(logical_device: &ash::Device, descriptor_set: vk::DescriptorSet, image_view: vk::ImageView, sampler: vk::Sampler) {
+	let descriptor_image_info = vk::DescriptorImageInfo::builder()
+		.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+		.image_view(image_view)
+		.sampler(sampler);
+	let descriptor_image_infos = [descriptor_image_info.build()];
+
+	let write_descriptor_set = vk::WriteDescriptorSet::builder()
+		.dst_set(descriptor_set)
+		.dst_binding(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.image_info(&descriptor_image_infos);
+
+	unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set.build()], &[]) };
+}
+
+pub fn create_frame_data_buffers(context: &Context, count: usize) -> Vec<Buffer> {
+	(0..count)
+		.map(|_| Buffer::new(context, FRAME_DATA_MEMORY_SIZE as u64, vk::BufferUsageFlags::UNIFORM_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::InstanceData))
+		.collect()
+}
+
+pub fn create_light_data_buffers(count: usize) -> Vec<Buffer> {
+	(0..count)
+		.map(|_| Buffer::null(vk::BufferUsageFlags::STORAGE_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::InstanceData))
+		.collect()
+}
+
+// The light data bindings are written every frame along with the light data
+pub fn update_frame_data_descriptor_sets(logical_device: &ash::Device, descriptor_sets: &[vk::DescriptorSet], frame_data_buffers: &[Buffer]) {
+	for (descriptor_set, frame_data_buffer) in descriptor_sets.iter().zip(frame_data_buffers) {
+		let descriptor_buffer_info = vk::DescriptorBufferInfo::builder()
+			.buffer(frame_data_buffer.handle)
+			.offset(0)
+			.range(vk::WHOLE_SIZE);
+		let descriptor_buffer_infos = [descriptor_buffer_info.build()];
+
+		let write_descriptor_set = vk::WriteDescriptorSet::builder()
+			.dst_set(*descriptor_set)
+			.dst_binding(0)
+			.dst_array_element(0)
+			.descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+			.buffer_info(&descriptor_buffer_infos);
+
+		unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set.build()], &[]) };
+	}
+}
+
+pub fn create_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
+
+// The color is sampled by reflective meshes, the depth is only used while the reflection is rendered
+pub(in super::super) fn create_target(context: &Context, extent: vk::Extent2D, scene_render_pass: vk::RenderPass) -> ReflectionTarget {
+	let color_image_resources = create_image_resources(
+		context,
+		extent,
+		context.surface.format.format,
+		vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+		vk::ImageAspectFlags::COLOR);
+
+	let depth_image_resources = create_image_resources(
+		context,
+		extent,
+		context.depth_format.format,
+		vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+		context.depth_format.aspect_mask());
+
+	let attachments = [color_image_resources.image_view, depth_image_resources.image_view];
+
+	let create_info = vk::FramebufferCreateInfo::builder()
+		.render_pass(scene_render_pass)
+		.attachments(&attachments)
+		.width(extent.width)
+		.height(extent.height)
+		.layers(1);
+
+	let framebuffer = unsafe { context.logical_device.create_framebuffer(&create_info, None) }.unwrap();
+
+	ReflectionTarget {
+		extent,
+		color_image_resources,
+		depth_image_resources,
+		framebuffer
+	}
+}
\ No newline at end of file
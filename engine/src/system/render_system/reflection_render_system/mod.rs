@@ -0,0 +1,117 @@
+use ash::vk;
+use crate::vulkan::{Buffer, Context};
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+// Reflective meshes show the scene seen by the camera mirrored across the reflection plane. The reflection is rendered into a
+// target of its own with the scene render pass before the scene is, from frame data of its own with every point light in a
+// single cluster. Only the opaque meshes are drawn into it.
+pub struct ReflectionRenderSystem {
+	pub descriptor_set_layout: vk::DescriptorSetLayout,
+	sampler: vk::Sampler,
+	pub descriptor_sets: Vec<vk::DescriptorSet>,
+	pub frame_data_descriptor_sets: Vec<vk::DescriptorSet>,
+	pub frame_data_buffers: Vec<Buffer>,
+	pub light_data_buffers: Vec<Buffer>,
+	// The ambient occlusion of the camera's view doesn't line up with the reflection so it samples a white image instead
+	pub ambient_occlusion_descriptor_set: vk::DescriptorSet,
+	pub command_buffers: Vec<vk::CommandBuffer>
+}
+
+pub struct ReflectionTarget {
+	extent: vk::Extent2D,
+	color_image_resources: ImageResources,
+	depth_image_resources: ImageResources,
+	pub framebuffer: vk::Framebuffer
+}
+
+impl ReflectionTarget {
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_framebuffer(self.framebuffer, None);
+			self.color_image_resources.drop(logical_device);
+			self.depth_image_resources.drop(logical_device);
+		}
+	}
+}
+
+impl ReflectionRenderSystem {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		context: &Context,
+		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		ambient_occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+		white_image_view: vk::ImageView,
+		descriptor_pool: vk::DescriptorPool,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
+		let logical_device = &context.logical_device;
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+		let sampler = create_sampler(logical_device);
+		let frame_data_buffers = create_frame_data_buffers(context, in_flight_frames_count);
+		let frame_data_descriptor_sets = create_descriptor_sets(logical_device, frame_data_descriptor_set_layout, descriptor_pool, in_flight_frames_count);
+		update_frame_data_descriptor_sets(logical_device, &frame_data_descriptor_sets, &frame_data_buffers);
+
+		let ambient_occlusion_descriptor_set = create_descriptor_sets(logical_device, ambient_occlusion_descriptor_set_layout, descriptor_pool, 1)[0];
+		update_image_descriptor_set(logical_device, ambient_occlusion_descriptor_set, white_image_view, sampler);
+
+		Self {
+			descriptor_set_layout,
+			sampler,
+			descriptor_sets: create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			frame_data_descriptor_sets,
+			frame_data_buffers,
+			light_data_buffers: create_light_data_buffers(in_flight_frames_count),
+			ambient_occlusion_descriptor_set,
+			command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count)
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D, scene_render_pass: vk::RenderPass) -> ReflectionTarget {
+		create_target(context, extent, scene_render_pass)
+	}
+
+	pub fn update_descriptor_set(&self, logical_device: &ash::Device, in_flight_frame_index: usize, target: &ReflectionTarget) {
+		update_image_descriptor_set(logical_device, self.descriptor_sets[in_flight_frame_index], target.color_image_resources.image_view, self.sampler);
+	}
+
+	// Records the reflection's scene render pass, its depth prepass subpass is left empty. The render pass leaves the color
+	// ready to be sampled by the scene's.
+	pub fn record_pass(&self, logical_device: &ash::Device, primary_command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, scene_render_pass: vk::RenderPass, target: &ReflectionTarget) {
+		let clear_values = [
+			vk::ClearValue { color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] } },
+			vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } }
+		];
+
+		let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(scene_render_pass)
+			.framebuffer(target.framebuffer)
+			.render_area(vk::Rect2D::builder()
+				.offset(vk::Offset2D::builder().x(0).y(0).build())
+				.extent(target.extent)
+				.build())
+			.clear_values(&clear_values);
+
+		unsafe {
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_next_subpass(primary_command_buffer, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(primary_command_buffer, &[self.command_buffers[in_flight_frame_index]]);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+		}
+
+		for buffer in self.frame_data_buffers.iter().chain(&self.light_data_buffers) {
+			buffer.drop(logical_device);
+		}
+	}
+}
\ No newline at end of file
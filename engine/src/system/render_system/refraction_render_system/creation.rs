@@ -0,0 +1,60 @@
+use ash::vk;
+use crate::vulkan::Context;
+use super::{super::creation::create_image_resources, RefractionTarget};
+
+pub fn create_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+	let layout_bindings = [layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+// Distorted lookups land between pixels so the copy is filtered
+pub fn create_sampler(logical_device: &ash::Device) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(vk::Filter::LINEAR)
+		.min_filter(vk::Filter::LINEAR)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub(in super::super) fn create_target(context: &Context, extent: vk::Extent2D) -> RefractionTarget {
+	let image_resources = create_image_resources(
+		context,
+		extent,
+		context.surface.format.format,
+		vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+		vk::ImageAspectFlags::COLOR);
+
+	RefractionTarget {
+		extent,
+		image_resources
+	}
+}
\ No newline at end of file
@@ -0,0 +1,180 @@
+use ash::vk;
+use crate::vulkan::Context;
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+// Refractive meshes are drawn after the rest of the scene is copied so they can sample what's behind them. The scene render
+// pass is ended before the first refractive mesh, the color is copied and a compatible render pass which loads the
+// attachments picks up where it left off.
+pub struct RefractionRenderSystem {
+	pub descriptor_set_layout: vk::DescriptorSetLayout,
+	sampler: vk::Sampler,
+	pub descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+pub struct RefractionTarget {
+	extent: vk::Extent2D,
+	image_resources: ImageResources
+}
+
+impl RefractionTarget {
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe { self.image_resources.drop(logical_device) };
+	}
+}
+
+impl RefractionRenderSystem {
+	pub fn new(logical_device: &ash::Device, descriptor_pool: vk::DescriptorPool, in_flight_frames_count: usize) -> Self {
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+
+		Self {
+			descriptor_set_layout,
+			sampler: create_sampler(logical_device),
+			descriptor_sets: create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count)
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D) -> RefractionTarget {
+		create_target(context, extent)
+	}
+
+	pub fn update_descriptor_set(&self, logical_device: &ash::Device, in_flight_frame_index: usize, target: &RefractionTarget) {
+		let descriptor_image_info = vk::DescriptorImageInfo::builder()
+			.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+			.image_view(target.image_resources.image_view)
+			.sampler(self.sampler);
+		let descriptor_image_infos = [descriptor_image_info.build()];
+
+		let write_descriptor_set = vk::WriteDescriptorSet::builder()
+			.dst_set(self.descriptor_sets[in_flight_frame_index])
+			.dst_binding(0)
+			.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+			.image_info(&descriptor_image_infos);
+
+		unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set.build()], &[]) };
+	}
+
+	// Records the copy of the scene color between the scene render pass and its continuation. The scene color is left in the
+	// color attachment layout the continuation starts in and the depth writes are made visible to its depth tests.
+	pub fn record_copy(&self, logical_device: &ash::Device, primary_command_buffer: vk::CommandBuffer, scene_color_image: vk::Image, target: &RefractionTarget) {
+		let subresource_range = vk::ImageSubresourceRange::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build();
+
+		// The previous frame may still be sampling the copy
+		let copy_barriers = [
+			vk::ImageMemoryBarrier::builder()
+				.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+				.dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+				.old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+				.new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+				.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.image(scene_color_image)
+				.subresource_range(subresource_range)
+				.build(),
+			vk::ImageMemoryBarrier::builder()
+				.src_access_mask(vk::AccessFlags::empty())
+				.dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+				.old_layout(vk::ImageLayout::UNDEFINED)
+				.new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+				.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.image(target.image_resources.image)
+				.subresource_range(subresource_range)
+				.build()
+		];
+
+		let subresource_layers = vk::ImageSubresourceLayers::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.mip_level(0)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build();
+
+		let region = vk::ImageCopy::builder()
+			.src_subresource(subresource_layers)
+			.src_offset(vk::Offset3D::default())
+			.dst_subresource(subresource_layers)
+			.dst_offset(vk::Offset3D::default())
+			.extent(vk::Extent3D::builder().width(target.extent.width).height(target.extent.height).depth(1).build());
+
+		let continue_barriers = [
+			vk::ImageMemoryBarrier::builder()
+				.src_access_mask(vk::AccessFlags::TRANSFER_READ)
+				.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+				.old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+				.new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+				.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.image(scene_color_image)
+				.subresource_range(subresource_range)
+				.build(),
+			vk::ImageMemoryBarrier::builder()
+				.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+				.dst_access_mask(vk::AccessFlags::SHADER_READ)
+				.old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+				.new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+				.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+				.image(target.image_resources.image)
+				.subresource_range(subresource_range)
+				.build()
+		];
+
+		// The depth stays in its attachment layout
+		let depth_barrier = vk::MemoryBarrier::builder()
+			.src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+			.dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+		unsafe {
+			logical_device.cmd_pipeline_barrier(
+				primary_command_buffer,
+				vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&copy_barriers);
+
+			logical_device.cmd_copy_image(
+				primary_command_buffer,
+				scene_color_image,
+				vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+				target.image_resources.image,
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				&[region.build()]);
+
+			logical_device.cmd_pipeline_barrier(
+				primary_command_buffer,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&continue_barriers);
+
+			logical_device.cmd_pipeline_barrier(
+				primary_command_buffer,
+				vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+				vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+				vk::DependencyFlags::empty(),
+				&[depth_barrier.build()],
+				&[],
+				&[]);
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,29 @@
+use std::fmt;
+
+// Counts of what was drawn for the camera of a frame and the commands recorded to draw its meshes, used to check how well
+// draws are batched and that a view stays within its budget
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RenderStats {
+	pub draws: usize,
+	pub pipeline_binds: usize,
+	pub descriptor_set_binds: usize,
+	pub geometry_binds: usize,
+	// Instances of the meshes the camera renders
+	pub submitted_instances: usize,
+	// Instances left out because the occlusion culler found them hidden behind the occluders
+	pub occluded_instances: usize,
+	pub drawn_instances: usize,
+	// Of the drawn instances, lines and points aren't counted
+	pub triangles: usize,
+	// Point lights uploaded for shading and the ones left out because they don't reach into the camera's view
+	pub point_lights: usize,
+	pub culled_point_lights: usize
+}
+
+impl fmt::Display for RenderStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} draws {} pipeline binds {} descriptor set binds {} geometry binds ", self.draws, self.pipeline_binds, self.descriptor_set_binds, self.geometry_binds)?;
+		write!(f, "{} of {} instances drawn {} occluded {} triangles ", self.drawn_instances, self.submitted_instances, self.occluded_instances, self.triangles)?;
+		write!(f, "{} point lights {} culled", self.point_lights, self.culled_point_lights)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+// Picks which frames render the scene when it's limited to a lower rate than the display's. The frames in between only draw
+// the overlay over the previous scene image so the UI stays at the display's rate. A scene frame is rendered on the display
+// frame nearest to when it's due so a display running at twice the rate renders every other one even though its frame times
+// jitter.
+pub struct ScenePacer {
+	// None renders the scene every frame and 0 only when it's required
+	pub max_frame_rate: Option<f32>,
+	next_frame: Option<Instant>,
+	previous_update: Option<Instant>,
+	required: bool
+}
+
+impl ScenePacer {
+	pub fn new() -> Self {
+		Self {
+			max_frame_rate: None,
+			next_frame: None,
+			previous_update: None,
+			required: true
+		}
+	}
+
+	// The next frame renders the scene whatever the rate, such as when the previous scene image can't be shown anymore
+	pub fn require_frame(&mut self) {
+		self.required = true;
+	}
+
+	// Call it once a frame, returns whether this frame renders the scene
+	pub fn update(&mut self, now: Instant) -> bool {
+		let display_frame_time = self.previous_update.map_or(Duration::ZERO, |previous_update| now.saturating_duration_since(previous_update));
+		self.previous_update = Some(now);
+
+		let interval = match self.max_frame_rate {
+			None => {
+				self.next_frame = None;
+				self.required = false;
+				return true;
+			},
+			Some(max_frame_rate) if max_frame_rate > 0.0 => Some(Duration::from_secs_f32(1.0 / max_frame_rate)),
+			Some(_) => None
+		};
+
+		let due = match (interval, self.next_frame) {
+			(Some(_), Some(next_frame)) => now + display_frame_time / 2 >= next_frame,
+			(Some(_), None) => true,
+			(None, _) => false
+		};
+
+		if !due && !self.required {
+			return false;
+		}
+
+		self.required = false;
+
+		// Keep the cadence unless the scene fell behind, then start over from now instead of catching up
+		self.next_frame = interval.map(|interval| match self.next_frame {
+			Some(next_frame) if next_frame + interval > now => next_frame + interval,
+			_ => now + interval
+		});
+
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(scene_pacer: &mut ScenePacer, frame_times_ms: &[u64]) -> Vec<bool> {
+		let mut now = Instant::now();
+
+		frame_times_ms.iter().map(|frame_time| {
+			now += Duration::from_millis(*frame_time);
+			scene_pacer.update(now)
+		})
+		.collect()
+	}
+
+	#[test]
+	fn renders_every_other_frame_at_half_rate() {
+		let mut scene_pacer = ScenePacer::new();
+		scene_pacer.max_frame_rate = Some(30.0);
+		assert_eq!(run(&mut scene_pacer, &[16, 17, 16, 16, 18, 16, 17, 15]), vec![true, false, true, false, true, false, true, false]);
+
+		// After a long stall it doesn't render several frames in a row to catch up
+		assert_eq!(run(&mut scene_pacer, &[200, 17, 16, 17]), vec![true, false, true, false]);
+	}
+
+	#[test]
+	fn renders_required_frames_when_frozen() {
+		let mut scene_pacer = ScenePacer::new();
+		scene_pacer.max_frame_rate = Some(0.0);
+		assert_eq!(run(&mut scene_pacer, &[16, 16, 16]), vec![true, false, false]);
+
+		scene_pacer.require_frame();
+		assert_eq!(run(&mut scene_pacer, &[16, 16]), vec![true, false]);
+
+		scene_pacer.max_frame_rate = None;
+		assert_eq!(run(&mut scene_pacer, &[16, 16]), vec![true, true]);
+	}
+}
\ No newline at end of file
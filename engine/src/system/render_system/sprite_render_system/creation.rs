@@ -0,0 +1,228 @@
+use std::{ffi::CString, mem::size_of};
+use ash::vk;
+use crate::Vfs;
+use super::super::create_shader_module;
+
+// The matrix as three padded rows, the tint color and the texture slot
+pub const PUSH_CONSTANTS_SIZE: u32 = 68;
+
+pub fn create_sampler_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
+	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+		.binding(0)
+		.descriptor_type(vk::DescriptorType::SAMPLER)
+		.descriptor_count(1)
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+	let layout_bindings = [layout_binding.build()];
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+pub fn create_pipeline_layout(
+	logical_device: &ash::Device,
+	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
+	texture_table_descriptor_set_layout: vk::DescriptorSetLayout)
+	-> vk::PipelineLayout
+{
+	let descriptor_set_layouts = [sampler_descriptor_set_layout, texture_table_descriptor_set_layout];
+
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+		.offset(0)
+		.size(PUSH_CONSTANTS_SIZE);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+// Quads are alpha blended over the scene in the overlay render pass
+pub fn create_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass, texture_count: usize) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	let vert_module = create_shader_module(logical_device, vfs, "sprite.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr);
+
+	let frag_module = create_shader_module(logical_device, vfs, "sprite.frag.spv");
+	// The size of the texture array is a specialization constant so it matches the texture table
+	let texture_count = texture_count as u32;
+	let specialization_map_entry = vk::SpecializationMapEntry::builder()
+		.constant_id(0)
+		.offset(0)
+		.size(size_of::<u32>());
+	let specialization_map_entries = [specialization_map_entry.build()];
+
+	let specialization_data = texture_count.to_ne_bytes();
+	let specialization_info = vk::SpecializationInfo::builder()
+		.map_entries(&specialization_map_entries)
+		.data(&specialization_data);
+
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr)
+		.specialization_info(&specialization_info);
+
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	// The position and texture position of each corner
+	let vert_input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(0)
+		.stride(16)
+		.input_rate(vk::VertexInputRate::VERTEX);
+	let vert_input_binding_descriptions = [vert_input_binding_description.build()];
+
+	let position_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(0)
+		.format(vk::Format::R32G32_SFLOAT)
+		.offset(0);
+
+	let texture_position_attribute_description = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(1)
+		.format(vk::Format::R32G32_SFLOAT)
+		.offset(8);
+
+	let vert_input_attribute_descriptions = [position_attribute_description.build(), texture_position_attribute_description.build()];
+
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&vert_input_binding_descriptions)
+		.vertex_attribute_descriptions(&vert_input_attribute_descriptions);
+
+	let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+		.primitive_restart_enable(false);
+
+	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+		.viewport_count(1)
+		.scissor_count(1);
+
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
+
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::NONE)
+		.front_face(vk::FrontFace::CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+		.sample_shading_enable(false)
+		.rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(false)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
+		.blend_enable(true)
+		.src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+		.dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+		.color_blend_op(vk::BlendOp::ADD)
+		.src_alpha_blend_factor(vk::BlendFactor::ONE)
+		.dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+		.alpha_blend_op(vk::BlendOp::ADD);
+	let color_blend_attachment_states = [color_blend_attachment_state.build()];
+
+	let color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false)
+		.attachments(&color_blend_attachment_states);
+
+	let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(&stage_create_infos)
+		.vertex_input_state(&vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(&rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(&depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	let pipeline = unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0];
+
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+pub fn create_descriptor_set(
+	logical_device: &ash::Device,
+	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
+	descriptor_pool: vk::DescriptorPool)
+	-> vk::DescriptorSet
+{
+	let descriptor_set_layouts = [sampler_descriptor_set_layout];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()[0]
+}
+
+// Nearest filtering keeps pixel art sharp and stops tiles bleeding into their neighbors in the atlas
+pub fn create_sampler(logical_device: &ash::Device) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(vk::Filter::NEAREST)
+		.min_filter(vk::Filter::NEAREST)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_TRANSPARENT_BLACK)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
+
+pub fn update_sampler(logical_device: &ash::Device, sampler: vk::Sampler, descriptor_set: vk::DescriptorSet) {
+	let descriptor_image_info = vk::DescriptorImageInfo::builder()
+		.sampler(sampler);
+	let descriptor_image_infos = [descriptor_image_info.build()];
+
+	let write_descriptor_set = vk::WriteDescriptorSet::builder()
+		.dst_set(descriptor_set)
+		.dst_binding(0)
+		.dst_array_element(0)
+		.descriptor_type(vk::DescriptorType::SAMPLER)
+		.image_info(&descriptor_image_infos)
+		.build();
+
+	unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set], &[]) };
+}
+
+pub fn create_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,108 @@
+use std::{collections::{HashMap, HashSet}, mem::size_of_val, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::vulkan::{Buffer, Context, MemoryCategory};
+use super::SpriteDraw;
+
+// Geometry with an id, such as tilemap chunks, is kept in a buffer per in flight frame and only copied in when it's been
+// regenerated since the in flight frame last drew it. Regenerated geometry is appended after what's there and the buffer is
+// compacted once it fills up.
+pub struct SpriteGeometryCache {
+	pub buffer: Buffer,
+	entries: HashMap<u64, SpriteGeometryEntry>,
+	used_size: usize
+}
+
+#[derive(Clone, Copy)]
+pub struct SpriteGeometryEntry {
+	pub index_array_offset: usize,
+	pub attribute_array_offset: usize
+}
+
+impl SpriteGeometryCache {
+	pub fn new() -> Self {
+		Self {
+			buffer: Buffer::null(vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::DynamicGeometry),
+			entries: HashMap::new(),
+			used_size: 0
+		}
+	}
+
+	// Returns where the geometry of each draw is in the buffer, the draws must all have a geometry id
+	pub fn update(&mut self, context: &Context, draws: &[&SpriteDraw]) -> Vec<SpriteGeometryEntry> {
+		let logical_device = &context.logical_device;
+
+		// Forget geometry that isn't drawn anymore
+		let mut drawn_geometry_ids = HashSet::new();
+		let unique_draws: Vec<(u64, &SpriteDraw)> = draws.iter()
+			.map(|draw| (draw.geometry_id.unwrap(), *draw))
+			.filter(|(geometry_id, _)| drawn_geometry_ids.insert(*geometry_id))
+			.collect();
+		self.entries.retain(|geometry_id, _| drawn_geometry_ids.contains(geometry_id));
+
+		let missing_size: usize = unique_draws.iter()
+			.filter(|(geometry_id, _)| !self.entries.contains_key(geometry_id))
+			.map(|(_, draw)| Self::size(draw))
+			.sum();
+
+		if missing_size > 0 {
+			// Start over from the beginning of the buffer when the new geometry doesn't fit after the old
+			if self.used_size + missing_size > self.buffer.capacity as usize {
+				self.entries.clear();
+				self.used_size = 0;
+
+				let size: usize = unique_draws.iter().map(|(_, draw)| Self::size(draw)).sum();
+
+				if size > self.buffer.capacity as usize {
+					let capacity = size.max(self.buffer.capacity as usize * 2);
+					self.buffer.reallocate(context, capacity as u64);
+					println!("Sprite geometry buffer reallocated");
+				}
+			}
+
+			let buffer_ptr = unsafe { logical_device.map_memory(self.buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+			for (geometry_id, draw) in &unique_draws {
+				if self.entries.contains_key(geometry_id) {
+					continue;
+				}
+
+				let index_array_offset = self.used_size;
+				let unaligned_attribute_array_offset = index_array_offset + size_of_val(draw.indices);
+				let attribute_array_offset = unaligned_attribute_array_offset + (4 - unaligned_attribute_array_offset % 4) % 4;
+
+				unsafe {
+					let index_array_dst_ptr = buffer_ptr.add(index_array_offset) as *mut u16;
+					copy_nonoverlapping(draw.indices.as_ptr(), index_array_dst_ptr, draw.indices.len());
+
+					let attribute_array_dst_ptr = buffer_ptr.add(attribute_array_offset) as *mut f32;
+					copy_nonoverlapping(draw.attributes.as_ptr(), attribute_array_dst_ptr, draw.attributes.len());
+				}
+
+				self.entries.insert(*geometry_id, SpriteGeometryEntry { index_array_offset, attribute_array_offset });
+				self.used_size += Self::size(draw);
+			}
+
+			let range = vk::MappedMemoryRange::builder()
+				.memory(self.buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(self.buffer.memory);
+			}
+		}
+
+		draws.iter().map(|draw| self.entries[&draw.geometry_id.unwrap()]).collect()
+	}
+
+	// The index array is padded so the attribute array after it is 4 byte aligned
+	fn size(draw: &SpriteDraw) -> usize {
+		let index_array_size = size_of_val(draw.indices);
+		index_array_size + (4 - index_array_size % 4) % 4 + size_of_val(draw.attributes)
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		self.buffer.drop(logical_device);
+	}
+}
\ No newline at end of file
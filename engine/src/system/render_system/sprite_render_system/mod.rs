@@ -0,0 +1,217 @@
+use std::{mem::size_of_val, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{math::Matrix3, vulkan::{Context, Buffer, MemoryCategory}, Vfs};
+
+mod creation;
+use creation::*;
+
+mod geometry_cache;
+use geometry_cache::SpriteGeometryCache;
+
+// Draws textured quads, such as animated sprites and tilemaps, in the overlay render pass. Each draw samples one texture from
+// the texture table. Quads without a geometry id are rebuilt every frame into a host visible buffer per in flight frame, the
+// rest are cached.
+pub struct SpriteRenderSystem {
+	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
+	pipeline_layout: vk::PipelineLayout,
+	pipeline: vk::Pipeline,
+	sampler_descriptor_set: vk::DescriptorSet,
+	sampler: vk::Sampler,
+	buffers: Vec<Buffer>,
+	geometry_caches: Vec<SpriteGeometryCache>,
+	command_buffers: Vec<vk::CommandBuffer>
+}
+
+// The indices of a single quad's corners
+pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+// Quads in pixels with their corners as position and texture position pairs
+pub struct SpriteDraw<'a> {
+	// Takes the quads from pixels into normalized device coordinates
+	pub matrix: Matrix3,
+	pub color: [f32; 4],
+	pub texture_slot: usize,
+	// Geometry which keeps its id between frames is only copied when the id changes
+	pub geometry_id: Option<u64>,
+	pub indices: &'a [u16],
+	pub attributes: &'a [f32]
+}
+
+impl SpriteRenderSystem {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		logical_device: &ash::Device,
+		vfs: &Vfs,
+		texture_table_descriptor_set_layout: vk::DescriptorSetLayout,
+		texture_count: usize,
+		render_pass: vk::RenderPass,
+		descriptor_pool: vk::DescriptorPool,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
+		let sampler_descriptor_set_layout = create_sampler_descriptor_set_layout(logical_device);
+		let pipeline_layout = create_pipeline_layout(logical_device, sampler_descriptor_set_layout, texture_table_descriptor_set_layout);
+		let sampler_descriptor_set = create_descriptor_set(logical_device, sampler_descriptor_set_layout, descriptor_pool);
+		let sampler = create_sampler(logical_device);
+		update_sampler(logical_device, sampler, sampler_descriptor_set);
+
+		let buffers = (0..in_flight_frames_count)
+			.map(|_| Buffer::null(vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::DynamicGeometry))
+			.collect();
+
+		Self {
+			sampler_descriptor_set_layout,
+			pipeline_layout,
+			pipeline: create_pipeline(logical_device, vfs, pipeline_layout, render_pass, texture_count),
+			sampler_descriptor_set,
+			sampler,
+			buffers,
+			geometry_caches: (0..in_flight_frames_count).map(|_| SpriteGeometryCache::new()).collect(),
+			command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count)
+		}
+	}
+
+	// Copies streamed quads into this in flight frame's buffer and changed cached ones into its geometry cache, then records the
+	// secondary command buffer drawing them in order, it's executed in the overlay render pass. There's nothing to execute when
+	// there's nothing to draw.
+	pub fn record_command_buffer(
+		&mut self,
+		context: &Context,
+		in_flight_frame_index: usize,
+		command_buffer_begin_info: &vk::CommandBufferBeginInfo,
+		extent: vk::Extent2D,
+		texture_table_descriptor_set: vk::DescriptorSet,
+		draws: &[SpriteDraw])
+		-> Option<vk::CommandBuffer>
+	{
+		let logical_device = &context.logical_device;
+		let draws: Vec<&SpriteDraw> = draws.iter().filter(|draw| !draw.indices.is_empty()).collect();
+
+		if draws.is_empty() {
+			return None;
+		}
+
+		let cached_draws: Vec<&SpriteDraw> = draws.iter().copied().filter(|draw| draw.geometry_id.is_some()).collect();
+		let geometry_cache = &mut self.geometry_caches[in_flight_frame_index];
+		let mut geometry_entries = geometry_cache.update(context, &cached_draws).into_iter();
+		let geometry_cache_buffer = geometry_cache.buffer.handle;
+
+		// Each streamed draw's index array is padded so the attribute array after it is 4 byte aligned
+		let mut streamed_offsets = Vec::new();
+		let mut size = 0;
+
+		for draw in draws.iter().filter(|draw| draw.geometry_id.is_none()) {
+			let index_array_size = size_of_val(draw.indices);
+			let attribute_array_offset = size + index_array_size + (4 - index_array_size % 4) % 4;
+			streamed_offsets.push((size, attribute_array_offset));
+			size = attribute_array_offset + size_of_val(draw.attributes);
+		}
+
+		let buffer = &mut self.buffers[in_flight_frame_index];
+
+		if size as u64 > buffer.capacity {
+			buffer.reallocate(context, size as u64);
+			println!("In flight frame {} sprite buffer reallocated", in_flight_frame_index);
+		}
+
+		if size > 0 {
+			let range = vk::MappedMemoryRange::builder()
+				.memory(buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				let buffer_ptr = logical_device.map_memory(buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+
+				for (draw, (index_array_offset, attribute_array_offset)) in draws.iter().filter(|draw| draw.geometry_id.is_none()).zip(&streamed_offsets) {
+					copy_nonoverlapping(draw.indices.as_ptr(), buffer_ptr.add(*index_array_offset) as *mut u16, draw.indices.len());
+					copy_nonoverlapping(draw.attributes.as_ptr(), buffer_ptr.add(*attribute_array_offset) as *mut f32, draw.attributes.len());
+				}
+
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(buffer.memory);
+			}
+		}
+
+		let streamed_buffer = buffer.handle;
+		let mut streamed_offsets = streamed_offsets.into_iter();
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(extent.width as f32)
+			.height(extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		let command_buffer = self.command_buffers[in_flight_frame_index];
+
+		unsafe {
+			logical_device.begin_command_buffer(command_buffer, command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+			logical_device.cmd_set_viewport(command_buffer, 0, &[viewport.build()]);
+			logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+			logical_device.cmd_bind_descriptor_sets(
+				command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.pipeline_layout,
+				0,
+				&[self.sampler_descriptor_set, texture_table_descriptor_set],
+				&[]);
+
+			// Draws are recorded in the order they're given whichever buffer their geometry is in
+			for draw in &draws {
+				let (buffer, index_array_offset, attribute_array_offset) = if draw.geometry_id.is_some() {
+					let entry = geometry_entries.next().unwrap();
+					(geometry_cache_buffer, entry.index_array_offset, entry.attribute_array_offset)
+				}
+				else {
+					let (index_array_offset, attribute_array_offset) = streamed_offsets.next().unwrap();
+					(streamed_buffer, index_array_offset, attribute_array_offset)
+				};
+
+				let push_constants = push_constants(draw);
+
+				logical_device.cmd_push_constants(command_buffer, self.pipeline_layout, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT, 0, &push_constants);
+				logical_device.cmd_bind_index_buffer(command_buffer, buffer, index_array_offset as u64, vk::IndexType::UINT16);
+				logical_device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer], &[attribute_array_offset as u64]);
+				logical_device.cmd_draw_indexed(command_buffer, draw.indices.len() as u32, 1, 0, 0, 0);
+			}
+
+			logical_device.end_command_buffer(command_buffer).unwrap();
+		}
+
+		Some(command_buffer)
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.sampler_descriptor_set_layout, None);
+		}
+
+		for buffer in &self.buffers {
+			buffer.drop(logical_device);
+		}
+
+		for geometry_cache in &self.geometry_caches {
+			geometry_cache.drop(logical_device);
+		}
+	}
+}
+
+fn push_constants(draw: &SpriteDraw) -> Vec<u8> {
+	let mut push_constants = Vec::with_capacity(PUSH_CONSTANTS_SIZE as usize);
+	push_constants.extend(draw.matrix.to_padded_array().iter().flatten().flat_map(|element| element.to_ne_bytes()));
+	push_constants.extend(draw.color.iter().flat_map(|component| component.to_ne_bytes()));
+	push_constants.extend_from_slice(&(draw.texture_slot as u32).to_ne_bytes());
+	push_constants
+}
\ No newline at end of file
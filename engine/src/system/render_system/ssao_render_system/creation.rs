@@ -0,0 +1,445 @@
+use std::{ffi::CString, mem::size_of};
+use ash::vk;
+use crate::{vulkan::{Context, DepthFormat}, Vfs};
+use super::{super::{create_shader_module, scale_extent, creation::{create_image_resources, create_aliased_image_resources}}, SsaoTarget, NORMAL_FORMAT, OCCLUSION_FORMAT};
+
+pub fn create_geometry_render_pass(logical_device: &ash::Device, depth_format: DepthFormat) -> vk::RenderPass {
+	let normal_attachment_description = vk::AttachmentDescription::builder()
+		.format(NORMAL_FORMAT)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+	let depth_attachment_description = vk::AttachmentDescription::builder()
+		.format(depth_format.format)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+
+	let attachment_descriptions = [normal_attachment_description.build(), depth_attachment_description.build()];
+
+	let normal_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(0)
+		.layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+	let normal_attachment_refs = [normal_attachment_ref.build()];
+
+	let depth_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(1)
+		.layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+	let subpass_description = vk::SubpassDescription::builder()
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+		.color_attachments(&normal_attachment_refs)
+		.depth_stencil_attachment(&depth_attachment_ref);
+	let subpass_descriptions = [subpass_description.build()];
+
+	// Wait for the previous frame's occlusion pass to finish sampling the images before writing to them, then make the
+	// writes visible to this frame's occlusion pass
+	let begin_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(vk::SUBPASS_EXTERNAL)
+		.dst_subpass(0)
+		.src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.src_access_mask(vk::AccessFlags::empty())
+		.dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+		.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+	let end_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(0)
+		.dst_subpass(vk::SUBPASS_EXTERNAL)
+		.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+		.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+	let subpass_dependencies = [begin_subpass_dependency.build(), end_subpass_dependency.build()];
+
+	let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+		.attachments(&attachment_descriptions)
+		.subpasses(&subpass_descriptions)
+		.dependencies(&subpass_dependencies);
+
+	unsafe { logical_device.create_render_pass(&render_pass_create_info, None) }.unwrap()
+}
+
+// Used by both the occlusion and the blur pass, each of which writes a single occlusion image that the next pass samples
+pub fn create_occlusion_render_pass(logical_device: &ash::Device) -> vk::RenderPass {
+	let occlusion_attachment_description = vk::AttachmentDescription::builder()
+		.format(OCCLUSION_FORMAT)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.load_op(vk::AttachmentLoadOp::CLEAR)
+		.store_op(vk::AttachmentStoreOp::STORE)
+		.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+		.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+	let attachment_descriptions = [occlusion_attachment_description.build()];
+
+	let occlusion_attachment_ref = vk::AttachmentReference::builder()
+		.attachment(0)
+		.layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+	let occlusion_attachment_refs = [occlusion_attachment_ref.build()];
+
+	let subpass_description = vk::SubpassDescription::builder()
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+		.color_attachments(&occlusion_attachment_refs);
+	let subpass_descriptions = [subpass_description.build()];
+
+	let begin_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(vk::SUBPASS_EXTERNAL)
+		.dst_subpass(0)
+		.src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.src_access_mask(vk::AccessFlags::empty())
+		.dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+		.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+
+	let end_subpass_dependency = vk::SubpassDependency::builder()
+		.src_subpass(0)
+		.dst_subpass(vk::SUBPASS_EXTERNAL)
+		.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+		.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+		.dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+		.dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+	let subpass_dependencies = [begin_subpass_dependency.build(), end_subpass_dependency.build()];
+
+	let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+		.attachments(&attachment_descriptions)
+		.subpasses(&subpass_descriptions)
+		.dependencies(&subpass_dependencies);
+
+	unsafe { logical_device.create_render_pass(&render_pass_create_info, None) }.unwrap()
+}
+
+pub fn create_image_descriptor_set_layout(logical_device: &ash::Device, image_count: u32) -> vk::DescriptorSetLayout {
+	let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..image_count)
+		.map(|binding| vk::DescriptorSetLayoutBinding::builder()
+			.binding(binding)
+			.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+			.descriptor_count(1)
+			.stage_flags(vk::ShaderStageFlags::FRAGMENT)
+			.build())
+		.collect();
+
+	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+		.bindings(&layout_bindings);
+
+	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
+}
+
+pub fn create_pipeline_layout(logical_device: &ash::Device, descriptor_set_layouts: &[vk::DescriptorSetLayout], push_constants_size: u32) -> vk::PipelineLayout {
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::FRAGMENT)
+		.offset(0)
+		.size(push_constants_size);
+	let push_constant_ranges = [push_constant_range.build()];
+
+	let mut pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+		.set_layouts(descriptor_set_layouts);
+
+	if push_constants_size > 0 {
+		pipeline_layout_create_info = pipeline_layout_create_info.push_constant_ranges(&push_constant_ranges);
+	}
+
+	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
+}
+
+// Quantized geometry gets its own pipeline whose vertex shader decodes the normal
+pub fn create_geometry_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass, quantized: bool) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	let specialization_map_entry = vk::SpecializationMapEntry::builder()
+		.constant_id(0)
+		.offset(0)
+		.size(size_of::<vk::Bool32>());
+	let specialization_map_entries = [specialization_map_entry.build()];
+
+	let specialization_data = if quantized { vk::TRUE } else { vk::FALSE }.to_ne_bytes();
+	let specialization_info = vk::SpecializationInfo::builder()
+		.map_entries(&specialization_map_entries)
+		.data(&specialization_data);
+
+	let vert_module = create_shader_module(logical_device, vfs, "ssao_geometry.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr)
+		.specialization_info(&specialization_info);
+
+	let frag_module = create_shader_module(logical_device, vfs, "ssao_geometry.frag.spv");
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr);
+
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	// Same vertex layouts as the normal and lambert materials
+	let (stride, position_format, normal_format, normal_offset) = if quantized {
+		(12, vk::Format::R16G16B16A16_SNORM, vk::Format::R16G16_SNORM, 8)
+	}
+	else {
+		(24, vk::Format::R32G32B32_SFLOAT, vk::Format::R32G32B32_SFLOAT, 12)
+	};
+
+	let input_binding_description = vk::VertexInputBindingDescription::builder()
+		.binding(0)
+		.stride(stride)
+		.input_rate(vk::VertexInputRate::VERTEX);
+	let input_binding_descriptions = [input_binding_description.build()];
+
+	let input_attribute_description_position = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(0)
+		.format(position_format)
+		.offset(0);
+
+	let input_attribute_description_normal = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(1)
+		.format(normal_format)
+		.offset(normal_offset);
+
+	let input_attribute_descriptions = [input_attribute_description_position.build(), input_attribute_description_normal.build()];
+
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+		.vertex_binding_descriptions(&input_binding_descriptions)
+		.vertex_attribute_descriptions(&input_attribute_descriptions);
+
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(true)
+		.depth_write_enable(true)
+		.depth_compare_op(vk::CompareOp::LESS)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::BACK)
+		.front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let pipeline = create_pipeline(
+		logical_device,
+		pipeline_layout,
+		render_pass,
+		&stage_create_infos,
+		&vert_input_state_create_info,
+		&rasterization_state_create_info,
+		&depth_stencil_state_create_info);
+
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+pub fn create_fullscreen_pipeline(logical_device: &ash::Device, vfs: &Vfs, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass, frag_filename: &str) -> vk::Pipeline {
+	let entry_point = CString::new("main").unwrap();
+	let entry_point_cstr = entry_point.as_c_str();
+
+	// The full screen triangle is generated in the vertex shader
+	let vert_module = create_shader_module(logical_device, vfs, "post_process.vert.spv");
+	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::VERTEX)
+		.module(vert_module)
+		.name(entry_point_cstr);
+
+	let frag_module = create_shader_module(logical_device, vfs, frag_filename);
+	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+		.stage(vk::ShaderStageFlags::FRAGMENT)
+		.module(frag_module)
+		.name(entry_point_cstr);
+
+	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
+
+	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder();
+
+	let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+		.depth_test_enable(false)
+		.depth_bounds_test_enable(false)
+		.stencil_test_enable(false);
+
+	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
+		.depth_clamp_enable(false)
+		.rasterizer_discard_enable(false)
+		.polygon_mode(vk::PolygonMode::FILL)
+		.line_width(1.0)
+		.cull_mode(vk::CullModeFlags::empty())
+		.front_face(vk::FrontFace::CLOCKWISE)
+		.depth_bias_enable(false);
+
+	let pipeline = create_pipeline(
+		logical_device,
+		pipeline_layout,
+		render_pass,
+		&stage_create_infos,
+		&vert_input_state_create_info,
+		&rasterization_state_create_info,
+		&depth_stencil_state_create_info);
+
+	unsafe {
+		logical_device.destroy_shader_module(vert_module, None);
+		logical_device.destroy_shader_module(frag_module, None);
+	}
+
+	pipeline
+}
+
+fn create_pipeline(
+	logical_device: &ash::Device,
+	pipeline_layout: vk::PipelineLayout,
+	render_pass: vk::RenderPass,
+	stage_create_infos: &[vk::PipelineShaderStageCreateInfo],
+	vert_input_state_create_info: &vk::PipelineVertexInputStateCreateInfo,
+	rasterization_state_create_info: &vk::PipelineRasterizationStateCreateInfo,
+	depth_stencil_state_create_info: &vk::PipelineDepthStencilStateCreateInfo)
+	-> vk::Pipeline
+{
+	let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+		.primitive_restart_enable(false);
+
+	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
+		.viewport_count(1)
+		.scissor_count(1);
+
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
+
+	let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+		.sample_shading_enable(false)
+		.rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
+		.blend_enable(false);
+	let color_blend_attachment_states = [color_blend_attachment_state.build()];
+
+	let color_blend_state_create_info = vk::PipelineColorBlendStateCreateInfo::builder()
+		.logic_op_enable(false)
+		.attachments(&color_blend_attachment_states);
+
+	let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+		.stages(stage_create_infos)
+		.vertex_input_state(vert_input_state_create_info)
+		.input_assembly_state(&input_assembly_state_create_info)
+		.viewport_state(&viewport_state_create_info)
+		.rasterization_state(rasterization_state_create_info)
+		.multisample_state(&multisample_state_create_info)
+		.depth_stencil_state(depth_stencil_state_create_info)
+		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
+		.layout(pipeline_layout)
+		.render_pass(render_pass)
+		.subpass(0);
+
+	unsafe { logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info.build()], None) }.unwrap()[0]
+}
+
+pub fn create_sampler(logical_device: &ash::Device, filter: vk::Filter) -> vk::Sampler {
+	let sampler_create_info = vk::SamplerCreateInfo::builder()
+		.mag_filter(filter)
+		.min_filter(filter)
+		.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+		.anisotropy_enable(false)
+		.border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+		.unnormalized_coordinates(false)
+		.compare_enable(false)
+		.mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+		.mip_lod_bias(0.0)
+		.min_lod(0.0)
+		.max_lod(0.0);
+
+	unsafe { logical_device.create_sampler(&sampler_create_info, None) }.unwrap()
+}
+
+pub fn create_descriptor_sets(logical_device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout, descriptor_pool: vk::DescriptorPool, count: usize) -> Vec<vk::DescriptorSet> {
+	let descriptor_set_layouts = vec![descriptor_set_layout; count];
+	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+		.descriptor_pool(descriptor_pool)
+		.set_layouts(&descriptor_set_layouts);
+
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+}
+
+pub fn create_command_buffers(logical_device: &ash::Device, command_pool: vk::CommandPool, count: usize) -> Vec<vk::CommandBuffer> {
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.command_pool(command_pool)
+		.level(vk::CommandBufferLevel::SECONDARY)
+		.command_buffer_count(count as u32);
+
+	unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()
+}
+
+pub(in super::super) fn create_target(context: &Context, extent: vk::Extent2D, depth_format: DepthFormat, geometry_render_pass: vk::RenderPass, occlusion_render_pass: vk::RenderPass) -> SsaoTarget {
+	let depth_image_resources = create_image_resources(
+		context,
+		extent,
+		depth_format.format,
+		vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+		vk::ImageAspectFlags::DEPTH);
+
+	// Occlusion is computed and blurred at half resolution
+	let occlusion_extent = scale_extent(extent, 0.5);
+
+	let occlusion_image_resources = create_image_resources(
+		context,
+		occlusion_extent,
+		OCCLUSION_FORMAT,
+		vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+		vk::ImageAspectFlags::COLOR);
+
+	// The normals are last read by the occlusion pass so the blur pass after it can write the blurred occlusion into the
+	// same memory, the render passes wait for fragment shader reads to finish before writing to their attachments
+	let mut aliased_image_resources = create_aliased_image_resources(context, &[
+		(extent, NORMAL_FORMAT, vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED, vk::ImageAspectFlags::COLOR),
+		(occlusion_extent, OCCLUSION_FORMAT, vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED, vk::ImageAspectFlags::COLOR)
+	]).into_iter();
+
+	let normal_image_resources = aliased_image_resources.next().unwrap();
+	let blurred_occlusion_image_resources = aliased_image_resources.next().unwrap();
+
+	let geometry_framebuffer = create_framebuffer(&context.logical_device, geometry_render_pass, &[normal_image_resources.image_view, depth_image_resources.image_view], extent);
+	let occlusion_framebuffer = create_framebuffer(&context.logical_device, occlusion_render_pass, &[occlusion_image_resources.image_view], occlusion_extent);
+	let blurred_occlusion_framebuffer = create_framebuffer(&context.logical_device, occlusion_render_pass, &[blurred_occlusion_image_resources.image_view], occlusion_extent);
+
+	SsaoTarget {
+		extent,
+		occlusion_extent,
+		normal_image_resources,
+		depth_image_resources,
+		occlusion_image_resources,
+		blurred_occlusion_image_resources,
+		geometry_framebuffer,
+		occlusion_framebuffer,
+		blurred_occlusion_framebuffer
+	}
+}
+
+fn create_framebuffer(logical_device: &ash::Device, render_pass: vk::RenderPass, attachments: &[vk::ImageView], extent: vk::Extent2D) -> vk::Framebuffer {
+	let create_info = vk::FramebufferCreateInfo::builder()
+		.render_pass(render_pass)
+		.attachments(attachments)
+		.width(extent.width)
+		.height(extent.height)
+		.layers(1);
+
+	unsafe { logical_device.create_framebuffer(&create_info, None) }.unwrap()
+}
\ No newline at end of file
@@ -0,0 +1,319 @@
+use std::{mem::size_of_val, slice};
+use ash::vk;
+use crate::{math::Matrix4, vulkan::{Context, DepthFormat}, Vfs};
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+const NORMAL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const OCCLUSION_FORMAT: vk::Format = vk::Format::R8_UNORM;
+
+pub struct SsaoRenderSystem {
+	depth_format: DepthFormat,
+	pub geometry_render_pass: vk::RenderPass,
+	occlusion_render_pass: vk::RenderPass,
+	occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+	blur_descriptor_set_layout: vk::DescriptorSetLayout,
+	pub ambient_occlusion_descriptor_set_layout: vk::DescriptorSetLayout,
+	pub geometry_pipeline_layout: vk::PipelineLayout,
+	pub geometry_pipeline: vk::Pipeline,
+	pub quantized_geometry_pipeline: vk::Pipeline,
+	occlusion_pipeline_layout: vk::PipelineLayout,
+	occlusion_pipeline: vk::Pipeline,
+	blur_pipeline_layout: vk::PipelineLayout,
+	blur_pipeline: vk::Pipeline,
+	nearest_sampler: vk::Sampler,
+	linear_sampler: vk::Sampler,
+	occlusion_descriptor_sets: Vec<vk::DescriptorSet>,
+	blur_descriptor_sets: Vec<vk::DescriptorSet>,
+	pub ambient_occlusion_descriptor_sets: Vec<vk::DescriptorSet>,
+	pub geometry_command_buffers: Vec<vk::CommandBuffer>,
+	occlusion_command_buffers: Vec<vk::CommandBuffer>,
+	blur_command_buffers: Vec<vk::CommandBuffer>
+}
+
+pub struct SsaoTarget {
+	extent: vk::Extent2D,
+	occlusion_extent: vk::Extent2D,
+	normal_image_resources: ImageResources,
+	depth_image_resources: ImageResources,
+	occlusion_image_resources: ImageResources,
+	blurred_occlusion_image_resources: ImageResources,
+	pub geometry_framebuffer: vk::Framebuffer,
+	occlusion_framebuffer: vk::Framebuffer,
+	blurred_occlusion_framebuffer: vk::Framebuffer
+}
+
+impl SsaoTarget {
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_framebuffer(self.geometry_framebuffer, None);
+			logical_device.destroy_framebuffer(self.occlusion_framebuffer, None);
+			logical_device.destroy_framebuffer(self.blurred_occlusion_framebuffer, None);
+			self.normal_image_resources.drop(logical_device);
+			self.depth_image_resources.drop(logical_device);
+			self.occlusion_image_resources.drop(logical_device);
+			self.blurred_occlusion_image_resources.drop(logical_device);
+		}
+	}
+}
+
+impl SsaoRenderSystem {
+	pub fn new(
+		context: &Context,
+		vfs: &Vfs,
+		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		descriptor_pool: vk::DescriptorPool,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
+		let logical_device = &context.logical_device;
+		let depth_format = context.sampled_depth_format;
+		let geometry_render_pass = create_geometry_render_pass(logical_device, depth_format);
+		let occlusion_render_pass = create_occlusion_render_pass(logical_device);
+
+		let occlusion_descriptor_set_layout = create_image_descriptor_set_layout(logical_device, 2);
+		let blur_descriptor_set_layout = create_image_descriptor_set_layout(logical_device, 1);
+		let ambient_occlusion_descriptor_set_layout = create_image_descriptor_set_layout(logical_device, 1);
+
+		let geometry_pipeline_layout = create_pipeline_layout(logical_device, &[frame_data_descriptor_set_layout, instance_data_descriptor_set_layout], 0);
+		let geometry_pipeline = create_geometry_pipeline(logical_device, vfs, geometry_pipeline_layout, geometry_render_pass, false);
+		let quantized_geometry_pipeline = create_geometry_pipeline(logical_device, vfs, geometry_pipeline_layout, geometry_render_pass, true);
+
+		// The occlusion pass gets the projection matrix and its inverse as push constants
+		let occlusion_pipeline_layout = create_pipeline_layout(logical_device, &[occlusion_descriptor_set_layout], 2 * 16 * 4);
+		let occlusion_pipeline = create_fullscreen_pipeline(logical_device, vfs, occlusion_pipeline_layout, occlusion_render_pass, "ssao.frag.spv");
+
+		let blur_pipeline_layout = create_pipeline_layout(logical_device, &[blur_descriptor_set_layout], 0);
+		let blur_pipeline = create_fullscreen_pipeline(logical_device, vfs, blur_pipeline_layout, occlusion_render_pass, "ssao_blur.frag.spv");
+
+		Self {
+			depth_format,
+			geometry_render_pass,
+			occlusion_render_pass,
+			occlusion_descriptor_set_layout,
+			blur_descriptor_set_layout,
+			ambient_occlusion_descriptor_set_layout,
+			geometry_pipeline_layout,
+			geometry_pipeline,
+			quantized_geometry_pipeline,
+			occlusion_pipeline_layout,
+			occlusion_pipeline,
+			blur_pipeline_layout,
+			blur_pipeline,
+			nearest_sampler: create_sampler(logical_device, vk::Filter::NEAREST),
+			linear_sampler: create_sampler(logical_device, vk::Filter::LINEAR),
+			occlusion_descriptor_sets: create_descriptor_sets(logical_device, occlusion_descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			blur_descriptor_sets: create_descriptor_sets(logical_device, blur_descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			ambient_occlusion_descriptor_sets: create_descriptor_sets(logical_device, ambient_occlusion_descriptor_set_layout, descriptor_pool, in_flight_frames_count),
+			geometry_command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count),
+			occlusion_command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count),
+			blur_command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count)
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D) -> SsaoTarget {
+		create_target(context, extent, self.depth_format, self.geometry_render_pass, self.occlusion_render_pass)
+	}
+
+	// The targets are recreated when the scene resolution changes so the descriptor sets are pointed at the current ones every frame
+	pub fn update_descriptor_sets(&self, logical_device: &ash::Device, in_flight_frame_index: usize, target: &SsaoTarget) {
+		let image_info = |image_view: vk::ImageView, sampler: vk::Sampler, image_layout: vk::ImageLayout| [
+			vk::DescriptorImageInfo::builder()
+				.image_layout(image_layout)
+				.image_view(image_view)
+				.sampler(sampler)
+				.build()
+		];
+
+		let normal_image_infos = image_info(target.normal_image_resources.image_view, self.nearest_sampler, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+		let depth_image_infos = image_info(target.depth_image_resources.image_view, self.nearest_sampler, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+		let occlusion_image_infos = image_info(target.occlusion_image_resources.image_view, self.nearest_sampler, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+		let blurred_occlusion_image_infos = image_info(target.blurred_occlusion_image_resources.image_view, self.linear_sampler, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+		let write_descriptor_set = |descriptor_set: vk::DescriptorSet, binding: u32, image_infos: &[vk::DescriptorImageInfo]| {
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(binding)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(image_infos)
+				.build()
+		};
+
+		let write_descriptor_sets = [
+			write_descriptor_set(self.occlusion_descriptor_sets[in_flight_frame_index], 0, &normal_image_infos),
+			write_descriptor_set(self.occlusion_descriptor_sets[in_flight_frame_index], 1, &depth_image_infos),
+			write_descriptor_set(self.blur_descriptor_sets[in_flight_frame_index], 0, &occlusion_image_infos),
+			write_descriptor_set(self.ambient_occlusion_descriptor_sets[in_flight_frame_index], 0, &blurred_occlusion_image_infos)
+		];
+
+		unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+	}
+
+	// Records the geometry, occlusion and blur passes into the primary command buffer, the geometry command buffer must already be
+	// recorded. When disabled only the blur pass is begun which clears the ambient occlusion to white.
+	pub fn record_passes(&self, logical_device: &ash::Device, primary_command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, target: &SsaoTarget, projection_matrix: &Matrix4, enabled: bool) {
+		let occlusion_command_buffer = self.occlusion_command_buffers[in_flight_frame_index];
+		let blur_command_buffer = self.blur_command_buffers[in_flight_frame_index];
+
+		let occlusion_render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(target.occlusion_extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(target.occlusion_extent.width as f32)
+			.height(target.occlusion_extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+		let viewports = [viewport.build()];
+		let scissors = [occlusion_render_area];
+
+		let occlusion_clear_values = [vk::ClearValue {
+			color: vk::ClearColorValue {
+				float32: [1.0, 1.0, 1.0, 1.0]
+			}
+		}];
+
+		let blurred_occlusion_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.occlusion_render_pass)
+			.framebuffer(target.blurred_occlusion_framebuffer)
+			.render_area(occlusion_render_area)
+			.clear_values(&occlusion_clear_values);
+
+		if !enabled {
+			unsafe {
+				logical_device.cmd_begin_render_pass(primary_command_buffer, &blurred_occlusion_render_pass_begin_info, vk::SubpassContents::INLINE);
+				logical_device.cmd_end_render_pass(primary_command_buffer);
+			}
+
+			return;
+		}
+
+		// Record occlusion command buffer
+		let mut inverse_projection_matrix = *projection_matrix;
+		inverse_projection_matrix.invert();
+		let push_constants = [projection_matrix.elements, inverse_projection_matrix.elements];
+		let push_constants_bytes = unsafe { slice::from_raw_parts(push_constants.as_ptr() as *const u8, size_of_val(&push_constants)) };
+
+		let occlusion_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.occlusion_render_pass)
+			.subpass(0)
+			.framebuffer(target.occlusion_framebuffer);
+
+		let occlusion_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&occlusion_command_buffer_inheritance_info);
+
+		unsafe {
+			logical_device.begin_command_buffer(occlusion_command_buffer, &occlusion_command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(occlusion_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.occlusion_pipeline);
+			logical_device.cmd_set_viewport(occlusion_command_buffer, 0, &viewports);
+			logical_device.cmd_set_scissor(occlusion_command_buffer, 0, &scissors);
+			logical_device.cmd_bind_descriptor_sets(
+				occlusion_command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.occlusion_pipeline_layout,
+				0,
+				&[self.occlusion_descriptor_sets[in_flight_frame_index]],
+				&[]);
+			logical_device.cmd_push_constants(occlusion_command_buffer, self.occlusion_pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, push_constants_bytes);
+			logical_device.cmd_draw(occlusion_command_buffer, 3, 1, 0, 0);
+			logical_device.end_command_buffer(occlusion_command_buffer).unwrap();
+		}
+
+		// Record blur command buffer
+		let blur_command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(self.occlusion_render_pass)
+			.subpass(0)
+			.framebuffer(target.blurred_occlusion_framebuffer);
+
+		let blur_command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&blur_command_buffer_inheritance_info);
+
+		unsafe {
+			logical_device.begin_command_buffer(blur_command_buffer, &blur_command_buffer_begin_info).unwrap();
+			logical_device.cmd_bind_pipeline(blur_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.blur_pipeline);
+			logical_device.cmd_set_viewport(blur_command_buffer, 0, &viewports);
+			logical_device.cmd_set_scissor(blur_command_buffer, 0, &scissors);
+			logical_device.cmd_bind_descriptor_sets(
+				blur_command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.blur_pipeline_layout,
+				0,
+				&[self.blur_descriptor_sets[in_flight_frame_index]],
+				&[]);
+			logical_device.cmd_draw(blur_command_buffer, 3, 1, 0, 0);
+			logical_device.end_command_buffer(blur_command_buffer).unwrap();
+		}
+
+		// Record passes
+		let geometry_clear_values = [
+			vk::ClearValue {
+				color: vk::ClearColorValue {
+					float32: [0.0, 0.0, 0.0, 0.0]
+				}
+			},
+			vk::ClearValue {
+				depth_stencil: vk::ClearDepthStencilValue {
+					depth: 1.0,
+					stencil: 0
+				}
+			}
+		];
+
+		let geometry_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.geometry_render_pass)
+			.framebuffer(target.geometry_framebuffer)
+			.render_area(vk::Rect2D::builder()
+				.offset(vk::Offset2D::builder().x(0).y(0).build())
+				.extent(target.extent)
+				.build())
+			.clear_values(&geometry_clear_values);
+
+		let occlusion_render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.occlusion_render_pass)
+			.framebuffer(target.occlusion_framebuffer)
+			.render_area(occlusion_render_area)
+			.clear_values(&occlusion_clear_values);
+
+		unsafe {
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &geometry_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(primary_command_buffer, &[self.geometry_command_buffers[in_flight_frame_index]]);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &occlusion_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(primary_command_buffer, &[occlusion_command_buffer]);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &blurred_occlusion_render_pass_begin_info, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+			logical_device.cmd_execute_commands(primary_command_buffer, &[blur_command_buffer]);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+		}
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.nearest_sampler, None);
+			logical_device.destroy_sampler(self.linear_sampler, None);
+			logical_device.destroy_pipeline(self.quantized_geometry_pipeline, None);
+			logical_device.destroy_pipeline(self.geometry_pipeline, None);
+			logical_device.destroy_pipeline(self.occlusion_pipeline, None);
+			logical_device.destroy_pipeline(self.blur_pipeline, None);
+			logical_device.destroy_pipeline_layout(self.geometry_pipeline_layout, None);
+			logical_device.destroy_pipeline_layout(self.occlusion_pipeline_layout, None);
+			logical_device.destroy_pipeline_layout(self.blur_pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.occlusion_descriptor_set_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.blur_descriptor_set_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.ambient_occlusion_descriptor_set_layout, None);
+			logical_device.destroy_render_pass(self.geometry_render_pass, None);
+			logical_device.destroy_render_pass(self.occlusion_render_pass, None);
+		}
+	}
+}
\ No newline at end of file
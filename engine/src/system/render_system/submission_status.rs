@@ -0,0 +1,32 @@
+// Whether a font or static geometry can be used with what was last submitted to the render system. Fonts and static
+// geometries remember the generation of the submission they were part of, submitting again starts a new generation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubmissionStatus {
+	Unsubmitted,
+	// Part of an earlier submission but not the last one, such as a font added to the pool after the fonts were submitted
+	// and a font submitted before that
+	Stale,
+	Current
+}
+
+impl SubmissionStatus {
+	pub(crate) fn new(generation: Option<usize>, current_generation: usize) -> Self {
+		match generation {
+			None => Self::Unsubmitted,
+			Some(generation) if generation == current_generation => Self::Current,
+			Some(_) => Self::Stale
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn compares_generations() {
+		assert_eq!(SubmissionStatus::new(None, 0), SubmissionStatus::Unsubmitted);
+		assert_eq!(SubmissionStatus::new(Some(2), 2), SubmissionStatus::Current);
+		assert_eq!(SubmissionStatus::new(Some(1), 2), SubmissionStatus::Stale);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,236 @@
+use ash::vk;
+use crate::{math::{matrix4, Matrix4}, vulkan::Context, Vfs};
+use super::ImageResources;
+
+mod creation;
+use creation::*;
+
+const HISTORY_WEIGHT: f32 = 0.9;
+const JITTER_SEQUENCE_LENGTH: usize = 8;
+
+// Temporal anti-aliasing. The projection is offset by a different fraction of a pixel every frame and each frame is blended
+// into the history of the previous ones, the history is reprojected with the motion vectors and clamped to the colors
+// around the pixel so things which moved or were uncovered don't leave trails.
+pub struct TaaRenderSystem {
+	render_pass: vk::RenderPass,
+	descriptor_set_layout: vk::DescriptorSetLayout,
+	pipeline_layout: vk::PipelineLayout,
+	pipeline: vk::Pipeline,
+	sampler: vk::Sampler,
+	descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+// Two images which take turns being the history and being resolved into
+pub struct TaaTarget {
+	extent: vk::Extent2D,
+	resolved_image_resources: [ImageResources; 2],
+	framebuffers: [vk::Framebuffer; 2],
+	resolved_count: usize
+}
+
+impl TaaTarget {
+	// The image this frame is resolved into
+	pub fn resolved_image_view(&self) -> vk::ImageView {
+		self.resolved_image_resources[self.resolved_count % 2].image_view
+	}
+
+	// The image the latest frame was resolved into, for frames which don't resolve
+	pub fn last_resolved_image_view(&self) -> vk::ImageView {
+		self.resolved_image_resources[(self.resolved_count + 1) % 2].image_view
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			for framebuffer in &self.framebuffers {
+				logical_device.destroy_framebuffer(*framebuffer, None);
+			}
+
+			for resolved_image_resources in &self.resolved_image_resources {
+				resolved_image_resources.drop(logical_device);
+			}
+		}
+	}
+}
+
+impl TaaRenderSystem {
+	pub fn new(context: &Context, vfs: &Vfs, descriptor_pool: vk::DescriptorPool, in_flight_frames_count: usize) -> Self {
+		let logical_device = &context.logical_device;
+		let render_pass = create_render_pass(context);
+		let descriptor_set_layout = create_descriptor_set_layout(logical_device);
+		let pipeline_layout = create_pipeline_layout(logical_device, descriptor_set_layout);
+
+		Self {
+			render_pass,
+			descriptor_set_layout,
+			pipeline_layout,
+			pipeline: create_pipeline(logical_device, vfs, pipeline_layout, render_pass),
+			sampler: create_sampler(logical_device),
+			descriptor_sets: create_descriptor_sets(logical_device, descriptor_set_layout, descriptor_pool, in_flight_frames_count)
+		}
+	}
+
+	pub fn create_target(&self, context: &Context, extent: vk::Extent2D) -> TaaTarget {
+		create_target(context, extent, self.render_pass)
+	}
+
+	// Points the descriptor set at this frame's images, the first frame has no history so it's blended with itself
+	pub fn update_descriptor_set(&self, logical_device: &ash::Device, in_flight_frame_index: usize, target: &TaaTarget, scene_image_view: vk::ImageView, velocity_image_view: vk::ImageView) {
+		let history_image_view = if target.resolved_count == 0 {
+			scene_image_view
+		}
+		else {
+			target.resolved_image_resources[(target.resolved_count + 1) % 2].image_view
+		};
+
+		let image_info = |image_view: vk::ImageView| [
+			vk::DescriptorImageInfo::builder()
+				.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+				.image_view(image_view)
+				.sampler(self.sampler)
+				.build()
+		];
+
+		let scene_image_infos = image_info(scene_image_view);
+		let history_image_infos = image_info(history_image_view);
+		let velocity_image_infos = image_info(velocity_image_view);
+
+		let descriptor_set = self.descriptor_sets[in_flight_frame_index];
+		let write_descriptor_set = |binding: u32, image_infos: &[vk::DescriptorImageInfo]| {
+			vk::WriteDescriptorSet::builder()
+				.dst_set(descriptor_set)
+				.dst_binding(binding)
+				.descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+				.image_info(image_infos)
+				.build()
+		};
+
+		let write_descriptor_sets = [
+			write_descriptor_set(0, &scene_image_infos),
+			write_descriptor_set(1, &history_image_infos),
+			write_descriptor_set(2, &velocity_image_infos)
+		];
+
+		unsafe { logical_device.update_descriptor_sets(&write_descriptor_sets, &[]) };
+	}
+
+	// Records the resolve into the primary command buffer. When disabled the history is forgotten so it starts over when it's
+	// enabled again.
+	pub fn record_pass(&self, logical_device: &ash::Device, primary_command_buffer: vk::CommandBuffer, in_flight_frame_index: usize, target: &mut TaaTarget, enabled: bool) {
+		if !enabled {
+			target.resolved_count = 0;
+			return;
+		}
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(target.extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(target.extent.width as f32)
+			.height(target.extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+			.render_pass(self.render_pass)
+			.framebuffer(target.framebuffers[target.resolved_count % 2])
+			.render_area(render_area);
+
+		unsafe {
+			logical_device.cmd_begin_render_pass(primary_command_buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+			logical_device.cmd_bind_pipeline(primary_command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+			logical_device.cmd_set_viewport(primary_command_buffer, 0, &[viewport.build()]);
+			logical_device.cmd_set_scissor(primary_command_buffer, 0, &[render_area]);
+			logical_device.cmd_bind_descriptor_sets(
+				primary_command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.pipeline_layout,
+				0,
+				&[self.descriptor_sets[in_flight_frame_index]],
+				&[]);
+			logical_device.cmd_push_constants(primary_command_buffer, self.pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, &HISTORY_WEIGHT.to_ne_bytes());
+			logical_device.cmd_draw(primary_command_buffer, 3, 1, 0, 0);
+			logical_device.cmd_end_render_pass(primary_command_buffer);
+		}
+
+		target.resolved_count += 1;
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_sampler(self.sampler, None);
+			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+			logical_device.destroy_render_pass(self.render_pass, None);
+		}
+	}
+}
+
+// The radical inverse of the index in the base, a low discrepancy sequence in [0, 1)
+fn halton(mut index: usize, base: usize) -> f32 {
+	let mut fraction = 1.0;
+	let mut result = 0.0;
+
+	while index > 0 {
+		fraction /= base as f32;
+		result += fraction * (index % base) as f32;
+		index /= base;
+	}
+
+	result
+}
+
+// Offsets the projection by up to half a pixel in each direction, cycling through a Halton (2, 3) sequence so the samples
+// of a pixel cover it evenly over a few frames
+pub fn jitter_projection_matrix(projection_matrix: &Matrix4, frame_count: usize, extent: vk::Extent2D) -> Matrix4 {
+	// The sequence starts at 1 since every base gives 0 for 0
+	let index = frame_count % JITTER_SEQUENCE_LENGTH + 1;
+
+	// Normalized device coordinates span 2 units across the image
+	let mut jitter_matrix = matrix4::IDENTITY;
+	jitter_matrix.elements[0][3] = (halton(index, 2) - 0.5) * 2.0 / extent.width as f32;
+	jitter_matrix.elements[1][3] = (halton(index, 3) - 0.5) * 2.0 / extent.height as f32;
+	jitter_matrix * projection_matrix
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::Vector3;
+
+	#[test]
+	fn halton_sequence() {
+		let base_2: Vec<f32> = (1..5).map(|index| halton(index, 2)).collect();
+		assert_eq!(base_2, vec![0.5, 0.25, 0.75, 0.125]);
+
+		let base_3: Vec<f32> = (1..4).map(|index| halton(index, 3)).collect();
+		assert!((base_3[0] - 1.0 / 3.0).abs() < 1e-6 && (base_3[1] - 2.0 / 3.0).abs() < 1e-6 && (base_3[2] - 1.0 / 9.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn jitter_is_within_half_a_pixel() {
+		let mut projection_matrix = matrix4::IDENTITY;
+		projection_matrix.make_perspective(16.0 / 9.0, 1.0, 0.1, 100.0);
+		let extent = vk::Extent2D::builder().width(160).height(90).build();
+		let point = Vector3::new(1.0, -2.0, -10.0).expand(1.0);
+
+		let projected = projection_matrix * point;
+		let mut offsets = vec![];
+
+		for frame_count in 0..JITTER_SEQUENCE_LENGTH {
+			let jittered = jitter_projection_matrix(&projection_matrix, frame_count, extent) * point;
+			let offset_x = (jittered.x / jittered.w - projected.x / projected.w) * extent.width as f32 / 2.0;
+			let offset_y = (jittered.y / jittered.w - projected.y / projected.w) * extent.height as f32 / 2.0;
+			assert!(offset_x.abs() <= 0.5 && offset_y.abs() <= 0.5);
+			offsets.push(offset_x);
+		}
+
+		// The sequence repeats
+		let repeated = jitter_projection_matrix(&projection_matrix, JITTER_SEQUENCE_LENGTH, extent) * point;
+		assert!(((repeated.x / repeated.w - projected.x / projected.w) * extent.width as f32 / 2.0 - offsets[0]).abs() < 1e-3);
+	}
+}
\ No newline at end of file
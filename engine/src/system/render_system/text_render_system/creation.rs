@@ -1,6 +1,10 @@
 use std::ffi::CString;
-use ash::{vk, version::DeviceV1_0};
-use super::{super::create_shader_module, MAX_FONTS};
+use ash::vk;
+use std::mem::size_of;
+use crate::Vfs;
+use super::super::create_shader_module;
+
+pub const GLOW_PUSH_CONSTANTS_SIZE: u32 = 24;
 
 pub fn create_sampler_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
 	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
@@ -16,64 +20,84 @@ pub fn create_sampler_descriptor_set_layout(logical_device: &ash::Device) -> vk:
 	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
 }
 
-pub fn create_atlases_descriptor_set_layout(logical_device: &ash::Device) -> vk::DescriptorSetLayout {
-	let layout_binding = vk::DescriptorSetLayoutBinding::builder()
-		.binding(0)
-		.descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
-		.descriptor_count(MAX_FONTS as u32)
-		.stage_flags(vk::ShaderStageFlags::FRAGMENT);
-	let layout_bindings = [layout_binding.build()];
-
-	let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
-		.bindings(&layout_bindings);
-
-	unsafe { logical_device.create_descriptor_set_layout(&create_info, None) }.unwrap()
-}
-
 pub fn create_pipeline_layout(
 	logical_device: &ash::Device,
 	instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
 	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
-	atlases_descriptor_set_layout: vk::DescriptorSetLayout)
+	texture_table_descriptor_set_layout: vk::DescriptorSetLayout)
 	-> vk::PipelineLayout
 {
 	let descriptor_set_layouts = [
 		instance_data_descriptor_set_layout,
 		sampler_descriptor_set_layout,
-		atlases_descriptor_set_layout
+		texture_table_descriptor_set_layout
 	];
 
+	// The glow color, radius and whether the atlas is a color atlas, only the glow pipeline uses them
+	let push_constant_range = vk::PushConstantRange::builder()
+		.stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+		.offset(0)
+		.size(GLOW_PUSH_CONSTANTS_SIZE);
+	let push_constant_ranges = [push_constant_range.build()];
+
 	let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
-		.set_layouts(&descriptor_set_layouts);
+		.set_layouts(&descriptor_set_layouts)
+		.push_constant_ranges(&push_constant_ranges);
 
 	unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }.unwrap()
 }
 
-
-pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipeline_layout: vk::PipelineLayout, render_pass: vk::RenderPass) -> vk::Pipeline {
+// Coverage atlases are tinted and color atlases are drawn as they are, only the fragment shader differs. The glow is added
+// onto what's behind it instead of blended.
+#[allow(clippy::too_many_arguments)]
+pub fn create_pipeline(
+	logical_device: &ash::Device,
+	vfs: &Vfs,
+	pipeline_layout: vk::PipelineLayout,
+	render_pass: vk::RenderPass,
+	atlas_count: usize,
+	vert_shader_filename: &str,
+	frag_shader_filename: &str,
+	additive: bool)
+	-> vk::Pipeline
+{
 	// Create entry point string
 	let entry_point = CString::new("main").unwrap();
 	let entry_point_cstr = entry_point.as_c_str();
 
 	// Create shader stage create infos
-	let vert_module = create_shader_module(logical_device, "text.vert.spv");
+	let vert_module = create_shader_module(logical_device, vfs, vert_shader_filename);
 	let vert_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::VERTEX)
 		.module(vert_module)
 		.name(entry_point_cstr);
 	
-	let frag_module = create_shader_module(logical_device, "text.frag.spv");
+	let frag_module = create_shader_module(logical_device, vfs, frag_shader_filename);
+	// The size of the atlas array is a specialization constant so it matches the texture table
+	let atlas_count = atlas_count as u32;
+	let specialization_map_entry = vk::SpecializationMapEntry::builder()
+		.constant_id(0)
+		.offset(0)
+		.size(size_of::<u32>());
+	let specialization_map_entries = [specialization_map_entry.build()];
+
+	let specialization_data = atlas_count.to_ne_bytes();
+	let specialization_info = vk::SpecializationInfo::builder()
+		.map_entries(&specialization_map_entries)
+		.data(&specialization_data);
+
 	let frag_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
 		.stage(vk::ShaderStageFlags::FRAGMENT)
 		.module(frag_module)
-		.name(entry_point_cstr);
+		.name(entry_point_cstr)
+		.specialization_info(&specialization_info);
 	
 	let stage_create_infos = [vert_stage_create_info.build(), frag_stage_create_info.build()];
 
 	// Create vertex input state create info
 	let input_binding_description = vk::VertexInputBindingDescription::builder()
 		.binding(0)
-		.stride(16)
+		.stride(20)
 		.input_rate(vk::VertexInputRate::VERTEX);
 	let input_binding_descriptions = [input_binding_description.build()];
 
@@ -91,7 +115,14 @@ pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipel
 		.offset(8)
 		.build();
 
-	let input_attribute_descriptions = [input_attribute_description_position, input_attribute_description_texture_position];
+	let input_attribute_description_alpha = vk::VertexInputAttributeDescription::builder()
+		.binding(0)
+		.location(2)
+		.format(vk::Format::R32_SFLOAT)
+		.offset(16)
+		.build();
+
+	let input_attribute_descriptions = [input_attribute_description_position, input_attribute_description_texture_position, input_attribute_description_alpha];
 
 	let vert_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
 		.vertex_binding_descriptions(&input_binding_descriptions)
@@ -102,24 +133,14 @@ pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipel
 		.topology(vk::PrimitiveTopology::TRIANGLE_LIST)
 		.primitive_restart_enable(false);
 	
-	// Create viewport state create info
-	let viewport = vk::Viewport::builder()
-		.x(0.0)
-		.y(0.0)
-		.width(extent.width as f32)
-		.height(extent.height as f32)
-		.min_depth(0.0)
-		.max_depth(1.0);
-	let viewports = [viewport.build()];
-
-	let scissor = vk::Rect2D::builder()
-		.offset(vk::Offset2D::builder().x(0).y(0).build())
-		.extent(extent);
-	let scissors = [scissor.build()];
-
+	// Create viewport state create info, the viewport and scissor are set dynamically so the pipeline survives swapchain recreation
 	let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::builder()
-		.viewports(&viewports)
-		.scissors(&scissors);
+		.viewport_count(1)
+		.scissor_count(1);
+	
+	let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+	let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo::builder()
+		.dynamic_states(&dynamic_states);
 	
 	// Create rasterization state create info
 	let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
@@ -143,14 +164,21 @@ pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipel
 		.stencil_test_enable(false);
 	
 	// Create color blend state create info
+	let (src_color_blend_factor, dst_color_blend_factor, src_alpha_blend_factor, dst_alpha_blend_factor) = if additive {
+		(vk::BlendFactor::ONE, vk::BlendFactor::ONE, vk::BlendFactor::ZERO, vk::BlendFactor::ONE)
+	}
+	else {
+		(vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA, vk::BlendFactor::ONE, vk::BlendFactor::ZERO)
+	};
+
 	let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
-		.color_write_mask(vk::ColorComponentFlags::all())
+		.color_write_mask(vk::ColorComponentFlags::RGBA)
 		.blend_enable(true)
-		.src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-		.dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+		.src_color_blend_factor(src_color_blend_factor)
+		.dst_color_blend_factor(dst_color_blend_factor)
 		.color_blend_op(vk::BlendOp::ADD)
-		.src_alpha_blend_factor(vk::BlendFactor::ONE)
-		.dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+		.src_alpha_blend_factor(src_alpha_blend_factor)
+		.dst_alpha_blend_factor(dst_alpha_blend_factor)
 		.alpha_blend_op(vk::BlendOp::ADD);
 	let color_blend_attachment_states = [color_blend_attachment_state.build()];
 
@@ -168,6 +196,7 @@ pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipel
 		.multisample_state(&multisample_state_create_info)
 		.depth_stencil_state(&depth_stencil_state_create_info)
 		.color_blend_state(&color_blend_state_create_info)
+		.dynamic_state(&dynamic_state_create_info)
 		.layout(pipeline_layout)
 		.render_pass(render_pass)
 		.subpass(0);
@@ -183,19 +212,18 @@ pub fn create_pipeline(logical_device: &ash::Device, extent: vk::Extent2D, pipel
 	pipeline
 }
 
-pub fn create_descriptor_sets(
+pub fn create_descriptor_set(
 	logical_device: &ash::Device,
 	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
-	atlases_descriptor_set_layout: vk::DescriptorSetLayout,
 	descriptor_pool: vk::DescriptorPool)
-	-> Vec<vk::DescriptorSet>
+	-> vk::DescriptorSet
 {
-	let descriptor_set_layouts = [sampler_descriptor_set_layout, atlases_descriptor_set_layout];
+	let descriptor_set_layouts = [sampler_descriptor_set_layout];
 	let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
 		.descriptor_pool(descriptor_pool)
 		.set_layouts(&descriptor_set_layouts);
 	
-	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()
+	unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()[0]
 }
 
 pub fn create_sampler(logical_device: &ash::Device) -> vk::Sampler {
@@ -0,0 +1,107 @@
+use std::{collections::{HashMap, HashSet}, mem::size_of_val, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{component::Text, vulkan::{Buffer, Context, MemoryCategory}};
+
+// Text geometry is kept in a buffer per in flight frame and only copied in when it's been regenerated since the in flight
+// frame last drew it. Regenerated text is appended after what's there and the buffer is compacted once it fills up. Text
+// sharing a geometry id has the same geometry so it's only copied once.
+pub struct TextGeometryCache {
+	pub buffer: Buffer,
+	entries: HashMap<u64, TextGeometryEntry>,
+	used_size: usize
+}
+
+#[derive(Clone, Copy)]
+pub struct TextGeometryEntry {
+	pub index_array_offset: usize,
+	pub attribute_array_offset: usize
+}
+
+impl TextGeometryCache {
+	pub fn new() -> Self {
+		Self {
+			buffer: Buffer::null(vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::DynamicGeometry),
+			entries: HashMap::new(),
+			used_size: 0
+		}
+	}
+
+	// Returns where the geometry of each text is in the buffer
+	pub fn update(&mut self, context: &Context, texts: &[&Text]) -> Vec<TextGeometryEntry> {
+		let logical_device = &context.logical_device;
+
+		// Forget geometry that isn't drawn anymore
+		let mut drawn_geometry_ids = HashSet::new();
+		let unique_texts: Vec<&Text> = texts.iter().copied().filter(|text| drawn_geometry_ids.insert(text.geometry_id)).collect();
+		self.entries.retain(|geometry_id, _| drawn_geometry_ids.contains(geometry_id));
+
+		let missing_size: usize = unique_texts.iter()
+			.filter(|text| !self.entries.contains_key(&text.geometry_id))
+			.map(|text| Self::size(text))
+			.sum();
+
+		if missing_size > 0 {
+			// Start over from the beginning of the buffer when the new geometry doesn't fit after the old
+			if self.used_size + missing_size > self.buffer.capacity as usize {
+				self.entries.clear();
+				self.used_size = 0;
+
+				let size: usize = unique_texts.iter().map(|text| Self::size(text)).sum();
+
+				if size > self.buffer.capacity as usize {
+					let capacity = size.max(self.buffer.capacity as usize * 2);
+					self.buffer.reallocate(context, capacity as u64);
+					println!("Text geometry buffer reallocated");
+				}
+			}
+
+			let buffer_ptr = unsafe { logical_device.map_memory(self.buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+			for text in &unique_texts {
+				if self.entries.contains_key(&text.geometry_id) {
+					continue;
+				}
+
+				let indices = text.indices();
+				let attributes = text.attributes();
+
+				let index_array_offset = self.used_size;
+				let unaligned_attribute_array_offset = index_array_offset + size_of_val(indices);
+				let attribute_array_offset = unaligned_attribute_array_offset + (4 - unaligned_attribute_array_offset % 4) % 4;
+
+				unsafe {
+					let index_array_dst_ptr = buffer_ptr.add(index_array_offset) as *mut u16;
+					copy_nonoverlapping(indices.as_ptr(), index_array_dst_ptr, indices.len());
+
+					let attribute_array_dst_ptr = buffer_ptr.add(attribute_array_offset) as *mut f32;
+					copy_nonoverlapping(attributes.as_ptr(), attribute_array_dst_ptr, attributes.len());
+				}
+
+				self.entries.insert(text.geometry_id, TextGeometryEntry { index_array_offset, attribute_array_offset });
+				self.used_size += Self::size(text);
+			}
+
+			let range = vk::MappedMemoryRange::builder()
+				.memory(self.buffer.memory)
+				.offset(0)
+				.size(vk::WHOLE_SIZE);
+
+			unsafe {
+				logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+				logical_device.unmap_memory(self.buffer.memory);
+			}
+		}
+
+		texts.iter().map(|text| self.entries[&text.geometry_id]).collect()
+	}
+
+	// The index array is padded so the attribute array after it is 4 byte aligned
+	fn size(text: &Text) -> usize {
+		let index_array_size = size_of_val(text.indices());
+		index_array_size + (4 - index_array_size % 4) % 4 + size_of_val(text.attributes())
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		self.buffer.drop(logical_device);
+	}
+}
\ No newline at end of file
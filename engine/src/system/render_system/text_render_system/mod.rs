@@ -1,41 +1,75 @@
-use std::{fs::File, io::{Read, Seek, SeekFrom}, ptr::copy_nonoverlapping, mem::size_of};
-use ash::{vk, version::DeviceV1_0};
-use crate::{pool::Pool, font::{Font, SubmissionInfo}, vulkan::{Context, Buffer}, math::Matrix3};
-use super::MAX_FONTS;
+use std::{fmt::{self, Display}, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{pool::Pool, font::{Font, FntError, SubmissionInfo}, vulkan::{Allocation, Context, Buffer, MemoryCategory}, math::Matrix3, Vfs};
+use super::TextureTable;
 
 mod creation;
 use creation::*;
 
+mod geometry_cache;
+use geometry_cache::TextGeometryCache;
+
 pub struct TextRenderSystem {
 	sampler_descriptor_set_layout: vk::DescriptorSetLayout,
-	atlases_descriptor_set_layout: vk::DescriptorSetLayout,
 	pub pipeline_layout: vk::PipelineLayout,
 	pub pipeline: vk::Pipeline,
+	pub color_pipeline: vk::Pipeline,
+	pub glow_pipeline: vk::Pipeline,
 	pub sampler_descriptor_set: vk::DescriptorSet,
-	pub atlases_descriptor_set: vk::DescriptorSet,
 	sampler: vk::Sampler,
 	memory: vk::DeviceMemory,
+	_memory_allocation: Option<Allocation>,
 	atlases: Vec<Atlas>,
 	empty_image: vk::Image,
 	empty_image_view: vk::ImageView,
 	pub submission_generation: usize,
-	pub projection_matrix: Matrix3
+	pub projection_matrix: Matrix3,
+	pub geometry_caches: Vec<TextGeometryCache>
+}
+
+#[derive(Debug)]
+pub enum FontSubmissionError {
+	TooManyFonts { count: usize, max: usize },
+	InvalidFontFile { fnt_path: String, error: FntError }
+}
+
+impl Display for FontSubmissionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::TooManyFonts { count, max } => write!(f, "Cannot submit {} fonts, at most {} are supported", count, max),
+			Self::InvalidFontFile { fnt_path, error } => write!(f, "Cannot read the atlas of font file {}: {}", fnt_path, error)
+		}
+	}
 }
 
+impl std::error::Error for FontSubmissionError {}
+
 struct Atlas {
 	image: vk::Image,
 	image_view: vk::ImageView
 }
 
 impl TextRenderSystem {
-	pub fn new(logical_device: &ash::Device, instance_data_descriptor_set_layout: vk::DescriptorSetLayout, extent: vk::Extent2D, render_pass: vk::RenderPass, descriptor_pool: vk::DescriptorPool) -> Self {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		logical_device: &ash::Device,
+		vfs: &Vfs,
+		instance_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		texture_table: &TextureTable,
+		extent: vk::Extent2D,
+		render_pass: vk::RenderPass,
+		descriptor_pool: vk::DescriptorPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
 		let sampler_descriptor_set_layout = create_sampler_descriptor_set_layout(logical_device);
-		let atlases_descriptor_set_layout = create_atlases_descriptor_set_layout(logical_device);
-		let pipeline_layout = create_pipeline_layout(logical_device, instance_data_descriptor_set_layout, sampler_descriptor_set_layout, atlases_descriptor_set_layout);
-		let pipeline = create_pipeline(logical_device, extent, pipeline_layout, render_pass);
-		let descriptor_sets = create_descriptor_sets(logical_device, sampler_descriptor_set_layout, atlases_descriptor_set_layout, descriptor_pool);
+		let pipeline_layout = create_pipeline_layout(logical_device, instance_data_descriptor_set_layout, sampler_descriptor_set_layout, texture_table.descriptor_set_layout);
+		let pipeline = create_pipeline(logical_device, vfs, pipeline_layout, render_pass, texture_table.capacity, "text.vert.spv", "text.frag.spv", false);
+		let color_pipeline = create_pipeline(logical_device, vfs, pipeline_layout, render_pass, texture_table.capacity, "text.vert.spv", "text_color.frag.spv", false);
+		let glow_pipeline = create_pipeline(logical_device, vfs, pipeline_layout, render_pass, texture_table.capacity, "text_glow.vert.spv", "text_glow.frag.spv", true);
+		let sampler_descriptor_set = create_descriptor_set(logical_device, sampler_descriptor_set_layout, descriptor_pool);
 		let sampler = create_sampler(logical_device);
-		update_sampler(logical_device, sampler, descriptor_sets[0]);
+		update_sampler(logical_device, sampler, sampler_descriptor_set);
 
 		let projection_matrix = Matrix3::new([
 			[2.0 / extent.width as f32, 0.0, -1.0],
@@ -44,37 +78,49 @@ impl TextRenderSystem {
 
 		Self {
 			sampler_descriptor_set_layout,
-			atlases_descriptor_set_layout,
 			pipeline_layout,
 			pipeline,
-			sampler_descriptor_set: descriptor_sets[0],
-			atlases_descriptor_set: descriptor_sets[1],
+			color_pipeline,
+			glow_pipeline,
+			sampler_descriptor_set,
 			sampler,
 			memory: vk::DeviceMemory::null(),
+			_memory_allocation: None,
 			atlases: vec![],
 			empty_image: vk::Image::null(),
 			empty_image_view: vk::ImageView::null(),
 			submission_generation: 0,
-			projection_matrix
+			projection_matrix,
+			geometry_caches: (0..in_flight_frames_count).map(|_| TextGeometryCache::new()).collect()
 		}
 	}
 
-	pub fn handle_swapchain_recreation(&mut self, logical_device: &ash::Device, extent: vk::Extent2D, render_pass: vk::RenderPass) {
-		unsafe { logical_device.destroy_pipeline(self.pipeline, None) };
-
-		self.pipeline = create_pipeline(logical_device, extent, self.pipeline_layout, render_pass);
-		
+	pub fn handle_swapchain_recreation(&mut self, extent: vk::Extent2D) {
 		self.projection_matrix.elements[0][0] = 2.0 / extent.width as f32;
 		self.projection_matrix.elements[1][1] = 2.0 / extent.height as f32;
 	}
 
-	pub fn submit_fonts(&mut self, context: &Context, command_pool: vk::CommandPool, fonts: &mut Pool<Font>) {
+	pub fn submit_fonts(&mut self, context: &Context, vfs: &Vfs, texture_table: &TextureTable, fonts: &mut Pool<Font>) -> Result<(), FontSubmissionError> {
 		let logical_device = &context.logical_device;
 
+		// Ensure there are not more fonts than the texture table holds before anything is destroyed
+		if fonts.occupied_record_count() > texture_table.font_capacity {
+			return Err(FontSubmissionError::TooManyFonts { count: fonts.occupied_record_count(), max: texture_table.font_capacity });
+		}
+
+		// Read the atlases up front too so a damaged font file leaves the submitted fonts in place
+		let mut atlases = Vec::with_capacity(fonts.occupied_record_count());
+
+		for font in fonts.iter() {
+			let atlas = font.load_atlas(vfs).map_err(|error| FontSubmissionError::InvalidFontFile { fnt_path: font.fnt_path.clone(), error })?;
+			atlases.push(atlas);
+		}
+
 		// Free memory and destroy resources
 		unsafe {
-			logical_device.queue_wait_idle(context.graphics_queue).unwrap();
+			context.wait_idle();
 			logical_device.free_memory(self.memory, None);
+			self._memory_allocation = None;
 			logical_device.destroy_image_view(self.empty_image_view, None);
 			logical_device.destroy_image(self.empty_image, None);
 
@@ -89,17 +135,15 @@ impl TextRenderSystem {
 
 		// Don't do anything if there are no fonts
 		if fonts.is_empty() {
-			return;
+			return Ok(());
 		}
 
-		// Ensure there are not more fonts than what's allowed
-		assert!(fonts.occupied_record_count() <= MAX_FONTS, "Cannot submit fonts, {} is more than the allowed {}", fonts.occupied_record_count(), MAX_FONTS);
-
 		// Create images and calculate buffer size
 		struct TempFontInfo<'a> {
 			font: &'a mut Font,
 			image: vk::Image,
 			image_view: vk::ImageView,
+			format: vk::Format,
 			offset: u64
 		}
 
@@ -107,12 +151,14 @@ impl TextRenderSystem {
 		let mut offset = 0;
 
 		for font in fonts.iter_mut() {
+			let format = if font.color { vk::Format::R8G8B8A8_UNORM } else { vk::Format::R8_UNORM };
+
 			let image_create_info = vk::ImageCreateInfo::builder()
 				.image_type(vk::ImageType::TYPE_2D)
 				.extent(vk::Extent3D::builder().width(font.atlas_width as u32).height(font.atlas_height as u32).depth(1).build())
 				.mip_levels(1)
 				.array_layers(1)
-				.format(vk::Format::R8_UNORM)
+				.format(format)
 				.tiling(vk::ImageTiling::OPTIMAL)
 				.initial_layout(vk::ImageLayout::UNDEFINED)
 				.usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
@@ -130,6 +176,7 @@ impl TextRenderSystem {
 				font,
 				image,
 				image_view: vk::ImageView::null(),
+				format,
 				offset
 			});
 
@@ -151,22 +198,15 @@ impl TextRenderSystem {
 		self.empty_image = unsafe { logical_device.create_image(&empty_image_create_info, None) }.unwrap();
 
 		// Create staging buffer
-		let staging_buffer = Buffer::new(context, offset, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE);
+		let staging_buffer = Buffer::new(context, offset, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging);
 
 		// Copy atlases into staging buffer
 		let staging_buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
 
-		for font_info in &font_infos {
-			let font = &font_info.font;
-
-			let mut file = File::open(&font.fnt_path).unwrap();
-			file.seek(SeekFrom::Start(2 * size_of::<u32>() as u64)).unwrap();
-			let mut atlas = vec![0u8; font.atlas_width * font.atlas_height];
-			file.read_exact(&mut atlas).unwrap();
-
+		for (font_info, atlas) in font_infos.iter().zip(&atlases) {
 			unsafe {
 				let dst_ptr = staging_buffer_ptr.add(font_info.offset as usize) as *mut u8;
-				copy_nonoverlapping(atlas.as_ptr(), dst_ptr, font.atlas_width * font.atlas_height);
+				copy_nonoverlapping(atlas.as_ptr(), dst_ptr, atlas.len());
 			}
 		}
 
@@ -183,13 +223,10 @@ impl TextRenderSystem {
 		// Create device local buffer
 		let first_image = font_infos[0].image;
 		let first_image_memory_requirements = unsafe { logical_device.get_image_memory_requirements(first_image) };
-		let memory_type_index = context.physical_device.find_memory_type_index(first_image_memory_requirements.memory_type_bits, vk::MemoryPropertyFlags::DEVICE_LOCAL);
-
-		let memory_allocate_info = vk::MemoryAllocateInfo::builder()
-			.allocation_size(offset as u64)
-			.memory_type_index(memory_type_index as u32);
-	
-		self.memory = unsafe { logical_device.allocate_memory(&memory_allocate_info, None) }.unwrap();
+		let memory_requirements = vk::MemoryRequirements { size: offset, ..first_image_memory_requirements };
+		let (memory, memory_allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::Fonts);
+		self.memory = memory;
+		self._memory_allocation = Some(memory_allocation);
 
 		// Bind images to device local buffer and create image view
 		for font_info in &mut font_infos {
@@ -198,7 +235,7 @@ impl TextRenderSystem {
 			let image_view_create_info = vk::ImageViewCreateInfo::builder()
 				.image(font_info.image)
 				.view_type(vk::ImageViewType::TYPE_2D)
-				.format(vk::Format::R8_UNORM)
+				.format(font_info.format)
 				.subresource_range(vk::ImageSubresourceRange::builder()
 					.aspect_mask(vk::ImageAspectFlags::COLOR)
 					.base_mip_level(0)
@@ -285,6 +322,7 @@ impl TextRenderSystem {
 		
 		shader_read_image_memory_barriers.push(empty_image_memory_barrier.build());
 
+		let command_pool = context.thread_command_pool();
 		let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
 			.level(vk::CommandBufferLevel::PRIMARY)
 			.command_pool(command_pool)
@@ -328,43 +366,16 @@ impl TextRenderSystem {
 			.command_buffers(&command_buffers);
 		
 		unsafe {
-			logical_device.queue_submit(context.graphics_queue, &[submit_info.build()], vk::Fence::null()).unwrap();
-			logical_device.queue_wait_idle(context.graphics_queue).unwrap();
+			context.submit_and_wait(&[submit_info.build()]);
 			logical_device.free_command_buffers(command_pool, &command_buffers);
 		}
 
 		// Destroy staging buffer
 		staging_buffer.drop(logical_device);
 
-		// Update descriptor sets
-		let mut descriptor_image_infos: Vec<vk::DescriptorImageInfo> = Vec::with_capacity(MAX_FONTS);
-		for font_info in &font_infos {
-			let descriptor_image_info = vk::DescriptorImageInfo::builder()
-				.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-				.image_view(font_info.image_view)
-				.build();
-			
-			descriptor_image_infos.push(descriptor_image_info);
-		}
-
-		for _ in 0..(MAX_FONTS - font_infos.len()) {
-			let descriptor_image_info = vk::DescriptorImageInfo::builder()
-				.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-				.image_view(self.empty_image_view)
-				.build();
-			
-			descriptor_image_infos.push(descriptor_image_info);
-		}
-
-		let write_descriptor_set = vk::WriteDescriptorSet::builder()
-			.dst_set(self.atlases_descriptor_set)
-			.dst_binding(0)
-			.dst_array_element(0)
-			.descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
-			.image_info(&descriptor_image_infos)
-			.build();
-
-		unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set], &[]) };
+		// Point the texture table at the atlases
+		let atlas_image_views: Vec<vk::ImageView> = font_infos.iter().map(|font_info| font_info.image_view).collect();
+		texture_table.update_fonts(logical_device, &atlas_image_views, self.empty_image_view);
 		
 		// Save submission info, images and image views
 		for (index, font_info) in font_infos.iter_mut().enumerate() {
@@ -378,6 +389,8 @@ impl TextRenderSystem {
 				image_view: font_info.image_view
 			});
 		}
+
+		Ok(())
 	}
 
 	pub fn drop(&self, logical_device: &ash::Device) {
@@ -393,10 +406,15 @@ impl TextRenderSystem {
 				logical_device.destroy_image(atlas.image, None);
 			}
 
+			for geometry_cache in &self.geometry_caches {
+				geometry_cache.drop(logical_device);
+			}
+
 			logical_device.destroy_sampler(self.sampler, None);
 			logical_device.destroy_pipeline(self.pipeline, None);
+			logical_device.destroy_pipeline(self.color_pipeline, None);
+			logical_device.destroy_pipeline(self.glow_pipeline, None);
 			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
-			logical_device.destroy_descriptor_set_layout(self.atlases_descriptor_set_layout, None);
 			logical_device.destroy_descriptor_set_layout(self.sampler_descriptor_set_layout, None);
 		}
 	}
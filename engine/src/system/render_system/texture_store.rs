@@ -0,0 +1,354 @@
+use std::{fmt::{self, Display}, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{Texture, vulkan::{Buffer, Context, MemoryCategory}};
+use super::{ImageResources, Retired, TextureTable};
+
+// The images of the submitted textures, each one in a texture slot of the texture table after the fonts. Updated pixels are
+// copied into the in flight frame's staging buffer and from there into the image at the start of the frame, the copy waits
+// for earlier frames to finish sampling the image.
+pub struct TextureStore {
+	images: Vec<Option<TextureImage>>,
+	padding: ImageResources,
+	staging_buffers: Vec<Buffer>,
+	pending_updates: Vec<PendingUpdate>,
+	retired_images: Vec<Retired<ImageResources>>
+}
+
+struct TextureImage {
+	resources: ImageResources,
+	width: u32,
+	height: u32
+}
+
+struct PendingUpdate {
+	index: usize,
+	pixels: Vec<u8>
+}
+
+#[derive(Debug)]
+pub enum TextureSubmissionError {
+	TooManyTextures { max: usize }
+}
+
+impl Display for TextureSubmissionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::TooManyTextures { max } => write!(f, "Cannot submit another texture, at most {} are supported", max)
+		}
+	}
+}
+
+impl std::error::Error for TextureSubmissionError {}
+
+impl TextureStore {
+	pub fn new(context: &Context, texture_table: &TextureTable, in_flight_frames_count: usize) -> Self {
+		let padding = upload_image(context, 1, 1, &[0; 4]);
+		texture_table.pad_textures(&context.logical_device, padding.image_view);
+
+		let staging_buffers = (0..in_flight_frames_count)
+			.map(|_| Buffer::null(vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging))
+			.collect();
+
+		Self {
+			images: vec![],
+			padding,
+			staging_buffers,
+			pending_updates: vec![],
+			retired_images: vec![]
+		}
+	}
+
+	// The texture table slot the texture is sampled from, None if it isn't submitted
+	pub fn slot(&self, texture_table: &TextureTable, texture: &Texture) -> Option<usize> {
+		texture.submission_index.map(|index| texture_table.texture_slot(index))
+	}
+
+	// The image of a submitted texture for descriptors outside of the texture table
+	pub fn image_view(&self, texture: &Texture) -> Option<vk::ImageView> {
+		let index = texture.submission_index?;
+		self.images[index].as_ref().map(|image| image.resources.image_view)
+	}
+
+	// Submitting a texture again updates it
+	pub fn submit(&mut self, context: &Context, texture_table: &TextureTable, texture: &mut Texture) -> Result<(), TextureSubmissionError> {
+		if texture.submission_index.is_some() {
+			self.update(texture);
+			return Ok(());
+		}
+
+		let index = match self.images.iter().position(Option::is_none) {
+			Some(index) => index,
+			None if self.images.len() < texture_table.texture_capacity() => {
+				self.images.push(None);
+				self.images.len() - 1
+			},
+			None => return Err(TextureSubmissionError::TooManyTextures { max: texture_table.texture_capacity() })
+		};
+
+		let width = texture.width() as u32;
+		let height = texture.height() as u32;
+		let resources = upload_image(context, width, height, &texture.pixels);
+		texture_table.update_texture(&context.logical_device, index, resources.image_view);
+
+		self.images[index] = Some(TextureImage { resources, width, height });
+		texture.submission_index = Some(index);
+		Ok(())
+	}
+
+	// Only the last update before a frame is copied
+	pub fn update(&mut self, texture: &Texture) {
+		let index = texture.submission_index.expect("Cannot update a texture which hasn't been submitted");
+		let image = self.images[index].as_ref().unwrap();
+		assert!(texture.width() as u32 == image.width && texture.height() as u32 == image.height, "Cannot change the size of a submitted texture");
+
+		self.pending_updates.retain(|pending_update| pending_update.index != index);
+		self.pending_updates.push(PendingUpdate { index, pixels: texture.pixels.clone() });
+	}
+
+	// The image is destroyed once the frames in flight are done with it and its slot goes back to the padding image
+	pub fn remove(&mut self, logical_device: &ash::Device, texture_table: &TextureTable, texture: &mut Texture, submitted_frame_count: usize) {
+		let index = match texture.submission_index.take() {
+			Some(index) => index,
+			None => return
+		};
+
+		self.pending_updates.retain(|pending_update| pending_update.index != index);
+		texture_table.update_texture(logical_device, index, self.padding.image_view);
+
+		let image = self.images[index].take().unwrap();
+		self.retired_images.push(Retired {
+			resource: image.resources,
+			retired_frame_count: submitted_frame_count
+		});
+	}
+
+	// Copies the pending updates into this in flight frame's staging buffer and records the copies into their images
+	pub fn record_uploads(&mut self, context: &Context, command_buffer: vk::CommandBuffer, in_flight_frame_index: usize) {
+		if self.pending_updates.is_empty() {
+			return;
+		}
+
+		let logical_device = &context.logical_device;
+		let staging_buffer = &mut self.staging_buffers[in_flight_frame_index];
+		let size: usize = self.pending_updates.iter().map(|pending_update| pending_update.pixels.len()).sum();
+
+		if size as u64 > staging_buffer.capacity {
+			staging_buffer.reallocate(context, size as u64);
+			println!("In flight frame {} texture staging buffer reallocated", in_flight_frame_index);
+		}
+
+		let range = vk::MappedMemoryRange::builder()
+			.memory(staging_buffer.memory)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		let mut transfer_image_memory_barriers = Vec::with_capacity(self.pending_updates.len());
+		let mut shader_read_image_memory_barriers = Vec::with_capacity(self.pending_updates.len());
+		let mut regions = Vec::with_capacity(self.pending_updates.len());
+		let mut offset = 0;
+
+		let staging_buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+		for pending_update in &self.pending_updates {
+			let image = self.images[pending_update.index].as_ref().unwrap();
+			unsafe { copy_nonoverlapping(pending_update.pixels.as_ptr(), (staging_buffer_ptr as *mut u8).add(offset), pending_update.pixels.len()) };
+
+			transfer_image_memory_barriers.push(image_memory_barrier(
+				image.resources.image,
+				vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				vk::AccessFlags::SHADER_READ,
+				vk::AccessFlags::TRANSFER_WRITE));
+
+			shader_read_image_memory_barriers.push(image_memory_barrier(
+				image.resources.image,
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				vk::AccessFlags::TRANSFER_WRITE,
+				vk::AccessFlags::SHADER_READ));
+
+			regions.push((image.resources.image, buffer_image_copy(offset as u64, image.width, image.height)));
+			offset += pending_update.pixels.len();
+		}
+
+		unsafe {
+			logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+			logical_device.unmap_memory(staging_buffer.memory);
+
+			logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &transfer_image_memory_barriers);
+
+			for (image, region) in &regions {
+				logical_device.cmd_copy_buffer_to_image(command_buffer, staging_buffer.handle, *image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[*region]);
+			}
+
+			logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &shader_read_image_memory_barriers);
+		}
+
+		self.pending_updates.clear();
+	}
+
+	pub fn destroy_unused_retired_images(&mut self, logical_device: &ash::Device, submitted_frame_count: usize, in_flight_frames_count: usize) {
+		self.retired_images.retain(|retired_image| {
+			if retired_image.is_unused(submitted_frame_count, in_flight_frames_count) {
+				unsafe { retired_image.resource.drop(logical_device) };
+				false
+			}
+			else {
+				true
+			}
+		});
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			self.padding.drop(logical_device);
+
+			for image in self.images.iter().flatten() {
+				image.resources.drop(logical_device);
+			}
+
+			for retired_image in &self.retired_images {
+				retired_image.resource.drop(logical_device);
+			}
+		}
+
+		for staging_buffer in &self.staging_buffers {
+			staging_buffer.drop(logical_device);
+		}
+	}
+}
+
+fn image_memory_barrier(image: vk::Image, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout, src_access_mask: vk::AccessFlags, dst_access_mask: vk::AccessFlags) -> vk::ImageMemoryBarrier {
+	vk::ImageMemoryBarrier::builder()
+		.old_layout(old_layout)
+		.new_layout(new_layout)
+		.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+		.image(image)
+		.subresource_range(vk::ImageSubresourceRange::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build())
+		.src_access_mask(src_access_mask)
+		.dst_access_mask(dst_access_mask)
+		.build()
+}
+
+fn buffer_image_copy(buffer_offset: u64, width: u32, height: u32) -> vk::BufferImageCopy {
+	vk::BufferImageCopy::builder()
+		.buffer_offset(buffer_offset)
+		.buffer_row_length(0)
+		.buffer_image_height(0)
+		.image_subresource(vk::ImageSubresourceLayers::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.mip_level(0)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build())
+		.image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+		.image_extent(vk::Extent3D::builder().width(width).height(height).depth(1).build())
+		.build()
+}
+
+// Creates a device local image with the pixels and waits for them to be copied in
+fn upload_image(context: &Context, width: u32, height: u32, pixels: &[u8]) -> ImageResources {
+	let logical_device = &context.logical_device;
+	let format = vk::Format::R8G8B8A8_SRGB;
+
+	// Create image
+	let image_create_info = vk::ImageCreateInfo::builder()
+		.image_type(vk::ImageType::TYPE_2D)
+		.extent(vk::Extent3D::builder().width(width).height(height).depth(1).build())
+		.mip_levels(1)
+		.array_layers(1)
+		.format(format)
+		.tiling(vk::ImageTiling::OPTIMAL)
+		.initial_layout(vk::ImageLayout::UNDEFINED)
+		.usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+		.sharing_mode(vk::SharingMode::EXCLUSIVE)
+		.samples(vk::SampleCountFlags::TYPE_1);
+
+	let image = unsafe { logical_device.create_image(&image_create_info, None) }.unwrap();
+
+	// Allocate device local memory and bind it to the image
+	let memory_requirements = unsafe { logical_device.get_image_memory_requirements(image) };
+	let (memory, allocation) = context.allocate_memory(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, MemoryCategory::Textures);
+	unsafe { logical_device.bind_image_memory(image, memory, 0) }.unwrap();
+
+	// Create image view
+	let image_view_create_info = vk::ImageViewCreateInfo::builder()
+		.image(image)
+		.view_type(vk::ImageViewType::TYPE_2D)
+		.format(format)
+		.subresource_range(vk::ImageSubresourceRange::builder()
+			.aspect_mask(vk::ImageAspectFlags::COLOR)
+			.base_mip_level(0)
+			.level_count(1)
+			.base_array_layer(0)
+			.layer_count(1)
+			.build());
+
+	let image_view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }.unwrap();
+
+	// Copy the pixels into a staging buffer
+	let staging_buffer = Buffer::new(context, pixels.len() as u64, vk::BufferUsageFlags::TRANSFER_SRC, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::Staging);
+	let staging_buffer_ptr = unsafe { logical_device.map_memory(staging_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) }.unwrap();
+
+	let range = vk::MappedMemoryRange::builder()
+		.memory(staging_buffer.memory)
+		.offset(0)
+		.size(vk::WHOLE_SIZE);
+
+	unsafe {
+		copy_nonoverlapping(pixels.as_ptr(), staging_buffer_ptr as *mut u8, pixels.len());
+		logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+		logical_device.unmap_memory(staging_buffer.memory);
+	}
+
+	// Record command buffer to copy the staging buffer into the image
+	let transfer_image_memory_barrier = image_memory_barrier(image, vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE);
+	let shader_read_image_memory_barrier = image_memory_barrier(image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::SHADER_READ);
+	let region = buffer_image_copy(0, width, height);
+
+	let command_pool = context.thread_command_pool();
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+		.level(vk::CommandBufferLevel::PRIMARY)
+		.command_pool(command_pool)
+		.command_buffer_count(1);
+
+	let command_buffer = unsafe { logical_device.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()[0];
+
+	let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+		.flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+	unsafe {
+		logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+		logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[transfer_image_memory_barrier]);
+		logical_device.cmd_copy_buffer_to_image(command_buffer, staging_buffer.handle, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region]);
+		logical_device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &[shader_read_image_memory_barrier]);
+		logical_device.end_command_buffer(command_buffer).unwrap();
+	}
+
+	// Submit command buffer
+	let command_buffers = [command_buffer];
+	let submit_info = vk::SubmitInfo::builder()
+		.command_buffers(&command_buffers);
+
+	unsafe {
+		context.submit_and_wait(&[submit_info.build()]);
+		logical_device.free_command_buffers(command_pool, &command_buffers);
+	}
+
+	// Destroy staging buffer
+	staging_buffer.drop(logical_device);
+
+	ImageResources {
+		image,
+		image_view,
+		memory,
+		_allocation: Some(allocation)
+	}
+}
\ No newline at end of file
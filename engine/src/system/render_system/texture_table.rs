@@ -0,0 +1,158 @@
+use ash::vk;
+use crate::vulkan::Context;
+
+const MAX_BINDLESS_TEXTURES: u32 = 1024;
+
+// A single descriptor set holding an array of sampled images which shaders index into. With descriptor indexing the array
+// is large and only partially bound, without it the array is small and every unused element must point at a padding image.
+// The font atlases take the first slots and textures the rest.
+pub struct TextureTable {
+	pub descriptor_set_layout: vk::DescriptorSetLayout,
+	pub descriptor_set: vk::DescriptorSet,
+	pub capacity: usize,
+	pub font_capacity: usize,
+	descriptor_pool: vk::DescriptorPool,
+	bindless: bool
+}
+
+impl TextureTable {
+	pub fn new(context: &Context, fallback_font_capacity: usize, fallback_texture_capacity: usize) -> Self {
+		let logical_device = &context.logical_device;
+		let bindless = context.physical_device.descriptor_indexing_supported;
+
+		let (capacity, font_capacity) = if bindless {
+			let capacity = context.physical_device.max_update_after_bind_sampled_images.min(MAX_BINDLESS_TEXTURES);
+			(capacity, capacity as usize / 2)
+		}
+		else {
+			((fallback_font_capacity + fallback_texture_capacity) as u32, fallback_font_capacity)
+		};
+
+		// Create descriptor set layout
+		let layout_binding = vk::DescriptorSetLayoutBinding::builder()
+			.binding(0)
+			.descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+			.descriptor_count(capacity)
+			.stage_flags(vk::ShaderStageFlags::FRAGMENT);
+		let layout_bindings = [layout_binding.build()];
+
+		let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT];
+		let mut binding_flags_create_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+			.binding_flags(&binding_flags);
+
+		let mut descriptor_set_layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+			.bindings(&layout_bindings);
+
+		if bindless {
+			descriptor_set_layout_create_info = descriptor_set_layout_create_info
+				.flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+				.push_next(&mut binding_flags_create_info);
+		}
+
+		let descriptor_set_layout = unsafe { logical_device.create_descriptor_set_layout(&descriptor_set_layout_create_info, None) }.unwrap();
+
+		// Create descriptor pool, update after bind sets need a pool of their own
+		let pool_size = vk::DescriptorPoolSize::builder()
+			.ty(vk::DescriptorType::SAMPLED_IMAGE)
+			.descriptor_count(capacity);
+		let pool_sizes = [pool_size.build()];
+
+		let mut descriptor_pool_create_info = vk::DescriptorPoolCreateInfo::builder()
+			.pool_sizes(&pool_sizes)
+			.max_sets(1);
+
+		if bindless {
+			descriptor_pool_create_info = descriptor_pool_create_info.flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND);
+		}
+
+		let descriptor_pool = unsafe { logical_device.create_descriptor_pool(&descriptor_pool_create_info, None) }.unwrap();
+
+		// Allocate descriptor set
+		let descriptor_counts = [capacity];
+		let mut variable_descriptor_count_allocate_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+			.descriptor_counts(&descriptor_counts);
+
+		let descriptor_set_layouts = [descriptor_set_layout];
+		let mut descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+			.descriptor_pool(descriptor_pool)
+			.set_layouts(&descriptor_set_layouts);
+
+		if bindless {
+			descriptor_set_allocate_info = descriptor_set_allocate_info.push_next(&mut variable_descriptor_count_allocate_info);
+		}
+
+		let descriptor_set = unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()[0];
+
+		println!("Texture table created with {} slots{}", capacity, if bindless { " using descriptor indexing" } else { "" });
+
+		Self {
+			descriptor_set_layout,
+			descriptor_set,
+			capacity: capacity as usize,
+			font_capacity,
+			descriptor_pool,
+			bindless
+		}
+	}
+
+	pub fn texture_capacity(&self) -> usize {
+		self.capacity - self.font_capacity
+	}
+
+	// The slot shaders sample a texture from
+	pub fn texture_slot(&self, texture_index: usize) -> usize {
+		self.font_capacity + texture_index
+	}
+
+	// Points the first font slots at the atlas image views, the rest point at the padding image view unless they can be left
+	// unbound
+	pub fn update_fonts(&self, logical_device: &ash::Device, image_views: &[vk::ImageView], padding_image_view: vk::ImageView) {
+		assert!(image_views.len() <= self.font_capacity, "Cannot put {} fonts in a texture table with {} font slots", image_views.len(), self.font_capacity);
+		let padding_count = if self.bindless { 0 } else { self.font_capacity - image_views.len() };
+		self.write(logical_device, 0, image_views, padding_image_view, padding_count);
+	}
+
+	pub fn update_texture(&self, logical_device: &ash::Device, texture_index: usize, image_view: vk::ImageView) {
+		assert!(texture_index < self.texture_capacity(), "Texture {} is outside of the {} texture slots", texture_index, self.texture_capacity());
+		self.write(logical_device, self.texture_slot(texture_index), &[image_view], image_view, 0);
+	}
+
+	// Every texture slot must point at an image without descriptor indexing, even the ones that aren't sampled
+	pub fn pad_textures(&self, logical_device: &ash::Device, padding_image_view: vk::ImageView) {
+		if !self.bindless {
+			self.write(logical_device, self.font_capacity, &[], padding_image_view, self.texture_capacity());
+		}
+	}
+
+	fn write(&self, logical_device: &ash::Device, first_slot: usize, image_views: &[vk::ImageView], padding_image_view: vk::ImageView, padding_count: usize) {
+
+		let descriptor_image_infos: Vec<vk::DescriptorImageInfo> = image_views.iter()
+			.chain(std::iter::repeat_n(&padding_image_view, padding_count))
+			.map(|image_view| vk::DescriptorImageInfo::builder()
+				.image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+				.image_view(*image_view)
+				.build())
+			.collect();
+
+		if descriptor_image_infos.is_empty() {
+			return;
+		}
+
+		let write_descriptor_set = vk::WriteDescriptorSet::builder()
+			.dst_set(self.descriptor_set)
+			.dst_binding(0)
+			.dst_array_element(first_slot as u32)
+			.descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+			.image_info(&descriptor_image_infos)
+			.build();
+
+		unsafe { logical_device.update_descriptor_sets(&[write_descriptor_set], &[]) };
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_descriptor_pool(self.descriptor_pool, None);
+			logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+		}
+	}
+}
\ No newline at end of file
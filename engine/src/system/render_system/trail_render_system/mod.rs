@@ -0,0 +1,164 @@
+use std::{mem::size_of, ptr::copy_nonoverlapping};
+use ash::vk;
+use crate::{component::{BlobShadow, ComponentList, Trail, TrailBlend, ColorVertex}, math::Vector3, vulkan::{Context, Buffer, MemoryCategory}, Vfs};
+
+mod creation;
+use creation::*;
+
+// Draws the ribbons of the trail components and the blob shadow decals. They're rebuilt every frame into a host visible vertex
+// buffer per in flight frame, the blob shadows and alpha blended trails are drawn before the additive trails.
+pub struct TrailRenderSystem {
+	pipeline_layout: vk::PipelineLayout,
+	additive_pipeline: vk::Pipeline,
+	alpha_pipeline: vk::Pipeline,
+	vertex_buffers: Vec<Buffer>,
+	command_buffers: Vec<vk::CommandBuffer>,
+	vertices: Vec<ColorVertex>
+}
+
+impl TrailRenderSystem {
+	pub fn new(
+		logical_device: &ash::Device,
+		vfs: &Vfs,
+		frame_data_descriptor_set_layout: vk::DescriptorSetLayout,
+		scene_render_pass: vk::RenderPass,
+		command_pool: vk::CommandPool,
+		in_flight_frames_count: usize)
+		-> Self
+	{
+		let pipeline_layout = create_pipeline_layout(logical_device, frame_data_descriptor_set_layout);
+
+		let vertex_buffers = (0..in_flight_frames_count)
+			.map(|_| Buffer::null(vk::BufferUsageFlags::VERTEX_BUFFER, vk::MemoryPropertyFlags::HOST_VISIBLE, MemoryCategory::DynamicGeometry))
+			.collect();
+
+		Self {
+			pipeline_layout,
+			additive_pipeline: create_pipeline(logical_device, vfs, pipeline_layout, scene_render_pass, TrailBlend::Additive),
+			alpha_pipeline: create_pipeline(logical_device, vfs, pipeline_layout, scene_render_pass, TrailBlend::Alpha),
+			vertex_buffers,
+			command_buffers: create_command_buffers(logical_device, command_pool, in_flight_frames_count),
+			vertices: vec![]
+		}
+	}
+
+	// Builds the ribbons and decals and records the secondary command buffer drawing them, it's executed in the scene render
+	// pass's shading subpass. There's nothing to execute when there's nothing to draw.
+	#[allow(clippy::too_many_arguments)]
+	pub fn record_command_buffer(
+		&mut self,
+		context: &Context,
+		in_flight_frame_index: usize,
+		extent: vk::Extent2D,
+		scene_render_pass: vk::RenderPass,
+		scene_framebuffer: vk::Framebuffer,
+		frame_data_descriptor_set: vk::DescriptorSet,
+		trail_components: &ComponentList<Trail>,
+		blob_shadow_components: &ComponentList<BlobShadow>,
+		camera_position: &Vector3)
+		-> Option<vk::CommandBuffer>
+	{
+		let logical_device = &context.logical_device;
+
+		self.vertices.clear();
+
+		for (_, blob_shadow) in blob_shadow_components.iter() {
+			blob_shadow.decal(&mut self.vertices);
+		}
+
+		for (_, trail) in trail_components.iter().filter(|(_, trail)| trail.blend == TrailBlend::Alpha) {
+			trail.ribbon(camera_position, &mut self.vertices);
+		}
+
+		let alpha_vertex_count = self.vertices.len();
+
+		for (_, trail) in trail_components.iter().filter(|(_, trail)| trail.blend == TrailBlend::Additive) {
+			trail.ribbon(camera_position, &mut self.vertices);
+		}
+
+		let additive_vertex_count = self.vertices.len() - alpha_vertex_count;
+
+		if self.vertices.is_empty() {
+			return None;
+		}
+
+		// Copy the vertices into this in flight frame's buffer, allocating a larger one if necessary
+		let vertex_buffer = &mut self.vertex_buffers[in_flight_frame_index];
+		let vertex_data_size = (self.vertices.len() * size_of::<ColorVertex>()) as u64;
+
+		if vertex_data_size > vertex_buffer.capacity {
+			vertex_buffer.reallocate(context, vertex_data_size);
+			println!("In flight frame {} trail vertex buffer reallocated", in_flight_frame_index);
+		}
+
+		let range = vk::MappedMemoryRange::builder()
+			.memory(vertex_buffer.memory)
+			.offset(0)
+			.size(vk::WHOLE_SIZE);
+
+		unsafe {
+			let vertex_buffer_ptr = logical_device.map_memory(vertex_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()).unwrap();
+			copy_nonoverlapping(self.vertices.as_ptr(), vertex_buffer_ptr as *mut ColorVertex, self.vertices.len());
+			logical_device.flush_mapped_memory_ranges(&[range.build()]).unwrap();
+			logical_device.unmap_memory(vertex_buffer.memory);
+		}
+
+		let render_area = vk::Rect2D::builder()
+			.offset(vk::Offset2D::builder().x(0).y(0).build())
+			.extent(extent)
+			.build();
+
+		let viewport = vk::Viewport::builder()
+			.x(0.0)
+			.y(0.0)
+			.width(extent.width as f32)
+			.height(extent.height as f32)
+			.min_depth(0.0)
+			.max_depth(1.0);
+
+		let command_buffer = self.command_buffers[in_flight_frame_index];
+
+		let command_buffer_inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+			.render_pass(scene_render_pass)
+			.subpass(1)
+			.framebuffer(scene_framebuffer);
+
+		let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+			.inheritance_info(&command_buffer_inheritance_info);
+
+		unsafe {
+			logical_device.begin_command_buffer(command_buffer, &command_buffer_begin_info).unwrap();
+			logical_device.cmd_set_viewport(command_buffer, 0, &[viewport.build()]);
+			logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+			logical_device.cmd_bind_descriptor_sets(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout, 0, &[frame_data_descriptor_set], &[]);
+			logical_device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.handle], &[0]);
+
+			if alpha_vertex_count > 0 {
+				logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.alpha_pipeline);
+				logical_device.cmd_draw(command_buffer, alpha_vertex_count as u32, 1, 0, 0);
+			}
+
+			if additive_vertex_count > 0 {
+				logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.additive_pipeline);
+				logical_device.cmd_draw(command_buffer, additive_vertex_count as u32, 1, alpha_vertex_count as u32, 0);
+			}
+
+			logical_device.end_command_buffer(command_buffer).unwrap();
+		}
+
+		Some(command_buffer)
+	}
+
+	pub fn drop(&self, logical_device: &ash::Device) {
+		unsafe {
+			logical_device.destroy_pipeline(self.additive_pipeline, None);
+			logical_device.destroy_pipeline(self.alpha_pipeline, None);
+			logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+		}
+
+		for vertex_buffer in &self.vertex_buffers {
+			vertex_buffer.drop(logical_device);
+		}
+	}
+}
\ No newline at end of file
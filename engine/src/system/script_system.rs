@@ -0,0 +1,378 @@
+use std::{cell::RefCell, error::Error, fmt, fs, io, mem, path::{Path, PathBuf}, rc::Rc, time::Duration};
+use glfw::{Action, Key};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, ParseError, Scope};
+use crate::{Entity, EntityManager, component::{ComponentList, Script, Transform3D, Transform3DComponentList}, math::Vector3};
+
+// The names scripts pass to key_down
+const KEY_NAMES: [(&str, Key); 36] = [
+	("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F), ("G", Key::G), ("H", Key::H),
+	("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L), ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P),
+	("Q", Key::Q), ("R", Key::R), ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+	("Y", Key::Y), ("Z", Key::Z), ("Space", Key::Space), ("Enter", Key::Enter), ("Tab", Key::Tab), ("Shift", Key::LeftShift),
+	("Control", Key::LeftControl), ("Alt", Key::LeftAlt), ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right)
+];
+
+#[derive(Debug)]
+pub enum ScriptError {
+	Io { path: PathBuf, error: io::Error },
+	Compile { path: PathBuf, error: ParseError }
+}
+
+impl fmt::Display for ScriptError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read script {}: {}", path.display(), error),
+			Self::Compile { path, error } => write!(f, "Cannot compile script {}: {}", path.display(), error)
+		}
+	}
+}
+
+impl Error for ScriptError {}
+
+// The keys held down when the scripts run
+pub struct ScriptInput {
+	pressed_keys: Vec<Key>
+}
+
+impl ScriptInput {
+	pub fn new() -> Self {
+		Self {
+			pressed_keys: Vec::new()
+		}
+	}
+
+	pub fn from_window(window: &glfw::Window) -> Self {
+		let pressed_keys = KEY_NAMES.iter()
+			.map(|(_, key)| *key)
+			.filter(|key| window.get_key(*key) == Action::Press)
+			.collect();
+
+		Self {
+			pressed_keys
+		}
+	}
+
+	pub fn press(&mut self, key: Key) {
+		self.pressed_keys.push(key);
+	}
+}
+
+// What the bindings work with, the entity manager and transforms are moved in while the scripts run
+struct World {
+	entity_manager: EntityManager,
+	transform3d_components: Transform3DComponentList,
+	input: ScriptInput,
+	spawned_entities: Vec<Entity>,
+	destroyed_entities: Vec<Entity>
+}
+
+type SharedWorld = Rc<RefCell<Option<World>>>;
+type BindingResult<T> = Result<T, Box<EvalAltResult>>;
+
+// Runs the scripts attached to entities. Scripts can move entities, read the keyboard, find entities by name and spawn
+// entities with a transform. Destroying an entity only queues it because the script system doesn't know about the other
+// components it may have, the game takes the queued entities with take_destroyed_entities and removes them. The spawned
+// entities are handed over the same way so the game can give them meshes and such.
+pub struct ScriptSystem {
+	engine: Engine,
+	world: SharedWorld,
+	spawned_entities: Vec<Entity>,
+	destroyed_entities: Vec<Entity>
+}
+
+impl ScriptSystem {
+	pub fn new() -> Self {
+		let world: SharedWorld = Rc::new(RefCell::new(None));
+		let mut engine = Engine::new();
+
+		engine.register_type_with_name::<Entity>("Entity")
+			.register_fn("to_string", |entity: &mut Entity| entity.to_string())
+			.register_fn("==", |a: Entity, b: Entity| a == b);
+
+		engine.register_type_with_name::<Vector3>("Vector3")
+			.register_fn("vec3", Vector3::new)
+			.register_get_set("x", |v: &mut Vector3| v.x, |v: &mut Vector3, x: f32| v.x = x)
+			.register_get_set("y", |v: &mut Vector3| v.y, |v: &mut Vector3, y: f32| v.y = y)
+			.register_get_set("z", |v: &mut Vector3| v.z, |v: &mut Vector3, z: f32| v.z = z)
+			.register_fn("+", |a: Vector3, b: Vector3| a + b)
+			.register_fn("-", |a: Vector3, b: Vector3| a - b)
+			.register_fn("*", |v: Vector3, scalar: f32| v * scalar)
+			.register_fn("length", |v: &mut Vector3| v.length())
+			.register_fn("normalize", |v: &mut Vector3| v.normalize())
+			.register_fn("to_string", |v: &mut Vector3| v.to_string());
+
+		let shared = world.clone();
+		engine.register_fn("position", move |entity: Entity| -> BindingResult<Vector3> {
+			with_world(&shared, |world| Ok(transform(world, entity)?.position))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("set_position", move |entity: Entity, position: Vector3| -> BindingResult<()> {
+			modify_transform(&shared, entity, |transform| transform.position = position)
+		});
+
+		let shared = world.clone();
+		engine.register_fn("translate", move |entity: Entity, translation: Vector3| -> BindingResult<()> {
+			modify_transform(&shared, entity, |transform| transform.position += translation)
+		});
+
+		let shared = world.clone();
+		engine.register_fn("rotate_x", move |entity: Entity, angle: f32| -> BindingResult<()> {
+			modify_transform(&shared, entity, |transform| transform.rotate_x(angle))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("rotate_y", move |entity: Entity, angle: f32| -> BindingResult<()> {
+			modify_transform(&shared, entity, |transform| transform.rotate_y(angle))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("rotate_z", move |entity: Entity, angle: f32| -> BindingResult<()> {
+			modify_transform(&shared, entity, |transform| transform.rotate_z(angle))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("key_down", move |name: &str| -> BindingResult<bool> {
+			let key = KEY_NAMES.iter().find(|(key_name, _)| *key_name == name).map(|(_, key)| *key);
+			let key = key.ok_or_else(|| format!("There is no key named {}", name))?;
+			with_world(&shared, |world| Ok(world.input.pressed_keys.contains(&key)))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("find", move |name: &str| -> BindingResult<Dynamic> {
+			with_world(&shared, |world| Ok(world.entity_manager.find_by_name(name).map_or(Dynamic::UNIT, Dynamic::from)))
+		});
+
+		let shared = world.clone();
+		engine.register_fn("spawn_entity", move |name: &str, position: Vector3| -> BindingResult<Entity> {
+			with_world(&shared, |world| {
+				let entity = world.entity_manager.create();
+				world.entity_manager.set_name(entity, name);
+				let mut transform = Transform3D::new();
+				transform.position = position;
+				world.transform3d_components.add(&mut world.entity_manager, entity, transform);
+				world.spawned_entities.push(entity);
+				Ok(entity)
+			})
+		});
+
+		let shared = world.clone();
+		engine.register_fn("destroy", move |entity: Entity| -> BindingResult<()> {
+			with_world(&shared, |world| {
+				world.destroyed_entities.push(entity);
+				Ok(())
+			})
+		});
+
+		Self {
+			engine,
+			world,
+			spawned_entities: Vec::new(),
+			destroyed_entities: Vec::new()
+		}
+	}
+
+	pub fn load(&self, path: impl AsRef<Path>) -> Result<Script, ScriptError> {
+		let path = path.as_ref().to_path_buf();
+		let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+		let source = fs::read_to_string(&path).map_err(|error| ScriptError::Io { path: path.clone(), error })?;
+		let ast = self.engine.compile(source).map_err(|error| ScriptError::Compile { path: path.clone(), error })?;
+		Ok(Script::new(path, ast, modified))
+	}
+
+	// A script which isn't loaded from a file so it's never reloaded, the name is only used in messages
+	pub fn load_source(&self, name: &str, source: &str) -> Result<Script, ScriptError> {
+		let path = PathBuf::from(name);
+		let ast = self.engine.compile(source).map_err(|error| ScriptError::Compile { path: path.clone(), error })?;
+		Ok(Script::new(path, ast, None))
+	}
+
+	// Recompiles the scripts whose files changed since they were loaded, the state in `this` is kept. A script which
+	// doesn't compile keeps running the old version.
+	pub fn reload_changed(&self, script_components: &mut ComponentList<Script>) {
+		for (_, script) in script_components.iter_mut() {
+			let modified = match script.modified {
+				Some(modified) => modified,
+				None => continue
+			};
+
+			let current_modified = fs::metadata(&script.path).and_then(|metadata| metadata.modified()).ok();
+
+			if current_modified.is_none_or(|current_modified| current_modified <= modified) {
+				continue;
+			}
+
+			match self.load(&script.path) {
+				Ok(reloaded) => {
+					script.ast = reloaded.ast;
+					script.modified = reloaded.modified;
+					script.failed = false;
+					println!("Reloaded script {}", script.path.display());
+				},
+				Err(error) => {
+					script.modified = current_modified;
+					println!("{}", error);
+				}
+			}
+		}
+	}
+
+	pub fn update(
+		&mut self,
+		entity_manager: &mut EntityManager,
+		script_components: &mut ComponentList<Script>,
+		transform3d_components: &mut Transform3DComponentList,
+		input: ScriptInput,
+		delta_time: &Duration)
+	{
+		*self.world.borrow_mut() = Some(World {
+			entity_manager: mem::replace(entity_manager, EntityManager::new()),
+			transform3d_components: mem::replace(transform3d_components, Transform3DComponentList::new()),
+			input,
+			spawned_entities: Vec::new(),
+			destroyed_entities: Vec::new()
+		});
+
+		let delta_time = delta_time.as_secs_f32();
+
+		for (entity, script) in script_components.iter_mut() {
+			if script.failed {
+				continue;
+			}
+
+			let mut result = Ok(Dynamic::UNIT);
+
+			if !script.initialized {
+				script.initialized = true;
+
+				if script.defines("init") {
+					result = self.call(script, "init", (*entity,));
+				}
+			}
+
+			if result.is_ok() {
+				result = self.call(script, "update", (*entity, delta_time));
+			}
+
+			if let Err(error) = result {
+				script.failed = true;
+				println!("Script {} attached to entity {} failed and won't run until it's reloaded: {}", script.path.display(), entity, error);
+			}
+		}
+
+		let world = self.world.borrow_mut().take().unwrap();
+		*entity_manager = world.entity_manager;
+		*transform3d_components = world.transform3d_components;
+		self.spawned_entities.extend(world.spawned_entities);
+		self.destroyed_entities.extend(world.destroyed_entities);
+	}
+
+	fn call(&self, script: &mut Script, function_name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, Box<EvalAltResult>> {
+		let options = CallFnOptions::new()
+			.eval_ast(false)
+			.bind_this_ptr(&mut script.state);
+
+		self.engine.call_fn_with_options(options, &mut Scope::new(), &script.ast, function_name, args)
+	}
+
+	pub fn take_spawned_entities(&mut self) -> Vec<Entity> {
+		mem::take(&mut self.spawned_entities)
+	}
+
+	pub fn take_destroyed_entities(&mut self) -> Vec<Entity> {
+		mem::take(&mut self.destroyed_entities)
+	}
+}
+
+fn with_world<T>(shared: &SharedWorld, f: impl FnOnce(&mut World) -> BindingResult<T>) -> BindingResult<T> {
+	let mut world = shared.borrow_mut();
+	let world = world.as_mut().ok_or("Scripts can only use the world while the script system is updating")?;
+	f(world)
+}
+
+fn transform(world: &World, entity: Entity) -> BindingResult<&Transform3D> {
+	world.transform3d_components.try_borrow(&entity).ok_or_else(|| format!("Entity {} has no transform", entity).into())
+}
+
+fn modify_transform(shared: &SharedWorld, entity: Entity, modify: impl FnOnce(&mut Transform3D)) -> BindingResult<()> {
+	with_world(shared, |world| {
+		let transform = world.transform3d_components.try_borrow_mut(&entity).ok_or_else(|| format!("Entity {} has no transform", entity))?;
+		modify(transform);
+		world.transform3d_components.update(entity);
+		Ok(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn setup(source: &str) -> (ScriptSystem, EntityManager, ComponentList<Script>, Transform3DComponentList, Entity) {
+		let script_system = ScriptSystem::new();
+		let mut entity_manager = EntityManager::new();
+		let mut script_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+
+		let entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, entity, Transform3D::new());
+		script_components.add(&mut entity_manager, entity, script_system.load_source("test", source).unwrap());
+
+		(script_system, entity_manager, script_components, transform3d_components, entity)
+	}
+
+	#[test]
+	fn update_moves_the_entity_with_state() {
+		let source = "
+			fn init(entity) { this.speed = 2.0; }
+			fn update(entity, delta_time) { translate(entity, vec3(this.speed * delta_time, 0.0, 0.0)); }";
+
+		let (mut script_system, mut entity_manager, mut script_components, mut transform3d_components, entity) = setup(source);
+
+		for _ in 0..2 {
+			script_system.update(&mut entity_manager, &mut script_components, &mut transform3d_components, ScriptInput::new(), &Duration::from_millis(500));
+		}
+
+		assert_eq!(transform3d_components.borrow(&entity).position, Vector3::new(2.0, 0.0, 0.0));
+		transform3d_components.check_for_dirties();
+	}
+
+	#[test]
+	fn input_spawn_and_destroy() {
+		let source = "
+			fn update(entity, delta_time) {
+				if key_down(\"Space\") {
+					let spawned = spawn_entity(\"bullet\", position(entity) + vec3(0.0, 1.0, 0.0));
+					destroy(entity);
+				}
+			}";
+
+		let (mut script_system, mut entity_manager, mut script_components, mut transform3d_components, entity) = setup(source);
+		script_system.update(&mut entity_manager, &mut script_components, &mut transform3d_components, ScriptInput::new(), &Duration::ZERO);
+		assert!(script_system.take_spawned_entities().is_empty());
+
+		let mut input = ScriptInput::new();
+		input.press(Key::Space);
+		script_system.update(&mut entity_manager, &mut script_components, &mut transform3d_components, input, &Duration::ZERO);
+
+		let spawned_entities = script_system.take_spawned_entities();
+		assert_eq!(spawned_entities.len(), 1);
+		assert!(entity_manager.find_by_name("bullet") == Some(spawned_entities[0]));
+		assert_eq!(transform3d_components.borrow(&spawned_entities[0]).position, Vector3::new(0.0, 1.0, 0.0));
+		assert!(script_system.take_destroyed_entities() == [entity]);
+	}
+
+	#[test]
+	fn a_failed_script_stops_running() {
+		let source = "fn update(entity, delta_time) { this.count = (this.count ?? 0) + 1; key_down(\"Nope\"); }";
+		let (mut script_system, mut entity_manager, mut script_components, mut transform3d_components, entity) = setup(source);
+
+		for _ in 0..2 {
+			script_system.update(&mut entity_manager, &mut script_components, &mut transform3d_components, ScriptInput::new(), &Duration::ZERO);
+		}
+
+		let script = script_components.borrow(&entity);
+		assert!(script.failed());
+		assert_eq!(script.state.clone_cast::<rhai::Map>()["count"].as_int(), Ok(1));
+		assert!(script_system.load_source("broken", "fn update(").is_err());
+	}
+}
\ No newline at end of file
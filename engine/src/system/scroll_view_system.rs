@@ -0,0 +1,99 @@
+use crate::{component::{ComponentList, HitArea2D, ScrollView, TextComponentList, Transform2DComponentList}, math::{Vector2, Vector3}};
+
+// Places the content of scroll views and clips it to them. The content follows the position, orientation and scale of the
+// view's transform but the clip rect is the screen space box around the view since scissors can't be rotated.
+pub struct ScrollViewSystem;
+
+impl ScrollViewSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn update(
+		&self,
+		scroll_view_components: &ComponentList<ScrollView>,
+		transform2d_components: &mut Transform2DComponentList,
+		text_components: &mut TextComponentList,
+		hit_area_components: &mut ComponentList<HitArea2D>)
+	{
+		for (entity, scroll_view) in scroll_view_components.iter() {
+			let matrix = transform2d_components.borrow(entity).matrix;
+			let to_screen = |point: Vector2| {
+				let screen_point = matrix * Vector3::new(point.x, point.y, 1.0);
+				Vector2::new(screen_point.x, screen_point.y)
+			};
+
+			let corners = [
+				to_screen(Vector2::new(0.0, 0.0)),
+				to_screen(Vector2::new(scroll_view.size.x, 0.0)),
+				to_screen(Vector2::new(0.0, scroll_view.size.y)),
+				to_screen(scroll_view.size)
+			];
+
+			let mut min = corners[0];
+			let mut max = corners[0];
+
+			for corner in &corners[1..] {
+				min.set(min.x.min(corner.x), min.y.min(corner.y));
+				max.set(max.x.max(corner.x), max.y.max(corner.y));
+			}
+
+			let clip_rect = Some((min, max));
+
+			for (content_entity, position) in &scroll_view.content {
+				let transform = transform2d_components.borrow_mut(content_entity);
+				transform.position = to_screen(position - scroll_view.scroll_offset);
+				transform2d_components.update(content_entity);
+
+				if text_components.try_borrow(content_entity).is_some() {
+					text_components.borrow_mut(*content_entity).clip_rect = clip_rect;
+				}
+
+				if let Some(hit_area) = hit_area_components.try_borrow_mut(content_entity) {
+					hit_area.clip_rect = clip_rect;
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EntityManager, component::Transform2D, math::{assert_approx_eq, vector2}};
+
+	#[test]
+	fn scrolls_and_clips_content() {
+		let mut entity_manager = EntityManager::new();
+		let mut scroll_view_components = ComponentList::new();
+		let mut transform2d_components = Transform2DComponentList::new();
+		let mut text_components = TextComponentList::new();
+		let mut hit_area_components = ComponentList::new();
+		let system = ScrollViewSystem::new();
+
+		let item = entity_manager.create();
+		transform2d_components.add(&mut entity_manager, item, Transform2D::new());
+		hit_area_components.add(&mut entity_manager, item, HitArea2D::new(vector2::ZERO, Vector2::new(100.0, 20.0)));
+
+		let view = entity_manager.create();
+		let mut transform = Transform2D::new();
+		transform.position.set(50.0, 100.0);
+		transform.scale.set(2.0, 2.0);
+		transform2d_components.add(&mut entity_manager, view, transform);
+
+		let mut scroll_view = ScrollView::new(Vector2::new(100.0, 50.0), Vector2::new(100.0, 200.0));
+		scroll_view.content.push((item, Vector2::new(0.0, 60.0)));
+		scroll_view.scroll(&Vector2::new(10.0, 500.0));
+		assert_approx_eq(&scroll_view.scroll_offset, &Vector2::new(0.0, 150.0), 1e-6);
+		scroll_view.scroll(&Vector2::new(0.0, -100.0));
+		scroll_view_components.add(&mut entity_manager, view, scroll_view);
+
+		system.update(&scroll_view_components, &mut transform2d_components, &mut text_components, &mut hit_area_components);
+		assert_approx_eq(&transform2d_components.borrow(&item).position, &Vector2::new(50.0, 120.0), 1e-4);
+
+		let (min, max) = hit_area_components.borrow(&item).clip_rect.unwrap();
+		assert_approx_eq(&min, &Vector2::new(50.0, 100.0), 1e-4);
+		assert_approx_eq(&max, &Vector2::new(250.0, 200.0), 1e-4);
+		transform2d_components.check_for_dirties();
+	}
+}
\ No newline at end of file
@@ -0,0 +1,135 @@
+use crate::{Entity, component::{Attachment, ComponentList, Socket, Sockets, Transform3DComponentList}, math::{Matrix4, Vector3, matrix4}};
+
+// Run it after the animation and anything else which moves joints, such as IK. An entity attached to an attached entity sees
+// where that one was put if its attachment was added after it. Attachments to a socket the entity doesn't have are left where
+// they are since the model and what's attached to it are made separately.
+pub struct SocketSystem {
+	warned: bool
+}
+
+impl SocketSystem {
+	pub fn new() -> Self {
+		Self {
+			warned: false
+		}
+	}
+
+	pub fn update(&mut self, sockets_components: &ComponentList<Sockets>, attachment_components: &ComponentList<Attachment>, transform3d_components: &mut Transform3DComponentList) {
+		for (entity, attachment) in attachment_components.iter() {
+			let Some(sockets) = sockets_components.try_borrow(&attachment.entity) else {
+				continue;
+			};
+
+			let Some(socket) = sockets.find(&attachment.socket) else {
+				if !self.warned {
+					println!("Entity {} has no socket named {}, attachments to missing sockets are skipped", attachment.entity, attachment.socket);
+					self.warned = true;
+				}
+
+				continue;
+			};
+
+			let Some(global_matrix) = Self::socket_global_matrix(transform3d_components, attachment.entity, socket) else {
+				continue;
+			};
+
+			let (mut position, mut orientation, _) = global_matrix.decompose();
+
+			// Into the attached entity's parent's space
+			if let Some(parent_entity) = transform3d_components.borrow(entity).parent_entity {
+				let parent_global_matrix = transform3d_components.borrow(&parent_entity).global_matrix;
+				let mut inverse_parent_global_matrix = parent_global_matrix;
+				inverse_parent_global_matrix.invert();
+				position.apply_matrix4(&inverse_parent_global_matrix);
+
+				let (_, mut parent_orientation, _) = parent_global_matrix.decompose();
+				parent_orientation.conjigate();
+				orientation = parent_orientation * orientation;
+			}
+
+			let transform = transform3d_components.borrow_mut(entity);
+			transform.position = position;
+			transform.orientation = orientation;
+			transform3d_components.update(*entity);
+		}
+	}
+
+	// None when the entity with the socket or its joint is gone
+	fn socket_global_matrix(transform3d_components: &Transform3DComponentList, entity: Entity, socket: &Socket) -> Option<Matrix4> {
+		let base_transform = transform3d_components.try_borrow(&socket.joint.unwrap_or(entity))?;
+
+		let mut offset_matrix = matrix4::IDENTITY;
+		offset_matrix.compose(&socket.position, &socket.orientation, &Vector3::from_scalar(1.0));
+		Some(base_transform.global_matrix * offset_matrix)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::FRAC_PI_2;
+	use crate::{EntityManager, component::Transform3D, math::{assert_approx_eq, vector3}};
+
+	#[test]
+	fn attaches_to_joints() {
+		let mut entity_manager = EntityManager::new();
+		let mut sockets_components = ComponentList::new();
+		let mut attachment_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut system = SocketSystem::new();
+
+		// A character with a hand joint held out to its side
+		let character_entity = entity_manager.create();
+		let mut character_transform = Transform3D::new();
+		character_transform.position = Vector3::new(5.0, 0.0, 0.0);
+		transform3d_components.add(&mut entity_manager, character_entity, character_transform);
+
+		let hand_entity = entity_manager.create();
+		let mut hand_transform = Transform3D::new();
+		hand_transform.position = Vector3::new(1.0, 1.0, 0.0);
+		hand_transform.rotate_y(FRAC_PI_2);
+		transform3d_components.add_child(&mut entity_manager, character_entity, hand_entity, hand_transform);
+
+		let mut grip = Socket::new("grip", Some(hand_entity));
+		grip.position = Vector3::new(0.0, 0.0, 0.5);
+		sockets_components.add(&mut entity_manager, character_entity, Sockets::new(vec![grip, Socket::new("origin", None)]));
+
+		let sword_entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, sword_entity, Transform3D::new());
+		attachment_components.add(&mut entity_manager, sword_entity, Attachment::new(character_entity, "grip"));
+
+		system.update(&sockets_components, &attachment_components, &mut transform3d_components);
+
+		// The grip is half a unit along the hand's z which points along x
+		let sword_transform = transform3d_components.borrow(&sword_entity);
+		assert_approx_eq(&sword_transform.position, &Vector3::new(6.5, 1.0, 0.0), 1e-5);
+
+		let mut forward = vector3::UNIT_Z;
+		forward.apply_quaternion(&sword_transform.orientation);
+		assert_approx_eq(&forward, &vector3::UNIT_X, 1e-5);
+		transform3d_components.check_for_dirties();
+	}
+
+	#[test]
+	fn skips_missing_sockets() {
+		let mut entity_manager = EntityManager::new();
+		let mut sockets_components = ComponentList::new();
+		let mut attachment_components = ComponentList::new();
+		let mut transform3d_components = Transform3DComponentList::new();
+		let mut system = SocketSystem::new();
+
+		let character_entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, character_entity, Transform3D::new());
+		sockets_components.add(&mut entity_manager, character_entity, Sockets::new(vec![Socket::new("grip", None)]));
+
+		let hat_entity = entity_manager.create();
+		let mut hat_transform = Transform3D::new();
+		hat_transform.position = Vector3::new(0.0, 2.0, 0.0);
+		transform3d_components.add(&mut entity_manager, hat_entity, hat_transform);
+		attachment_components.add(&mut entity_manager, hat_entity, Attachment::new(character_entity, "head"));
+
+		system.update(&sockets_components, &attachment_components, &mut transform3d_components);
+		system.update(&sockets_components, &attachment_components, &mut transform3d_components);
+		assert_approx_eq(&transform3d_components.borrow(&hat_entity).position, &Vector3::new(0.0, 2.0, 0.0), 1e-5);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::time::Duration;
+use crate::component::{AnimatedSprite, ComponentList};
+
+pub struct SpriteAnimationSystem;
+
+impl SpriteAnimationSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn update(&self, delta_time: &Duration, animated_sprite_components: &mut ComponentList<AnimatedSprite>) {
+		for (_, sprite) in animated_sprite_components.iter_mut() {
+			if !sprite.playing || sprite.frames_per_second <= 0.0 {
+				continue;
+			}
+
+			let seconds_per_frame = Duration::from_secs_f32(1.0 / sprite.frames_per_second);
+			let frame_count = sprite.frames.len();
+			let previous_frame_index = sprite.frame_index;
+
+			sprite.frame_duration += *delta_time;
+
+			// Step forward as many frames as have elapsed, the delta time may span more than one
+			while sprite.frame_duration >= seconds_per_frame {
+				sprite.frame_duration -= seconds_per_frame;
+
+				if sprite.frame_index + 1 < frame_count {
+					sprite.frame_index += 1;
+				}
+				else if sprite.looping {
+					sprite.frame_index = 0;
+				}
+				else {
+					sprite.playing = false;
+					sprite.frame_duration = Duration::new(0, 0);
+					break;
+				}
+			}
+
+			if sprite.frame_index != previous_frame_index {
+				sprite.generate_attributes();
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Entity, EntityManager, pool::Pool, Texture};
+
+	fn update(sprites: &mut ComponentList<AnimatedSprite>, seconds: f32) {
+		SpriteAnimationSystem::new().update(&Duration::from_secs_f32(seconds), sprites);
+	}
+
+	fn sprite(looping: bool) -> (Entity, ComponentList<AnimatedSprite>) {
+		let mut entity_manager = EntityManager::new();
+		let entity = entity_manager.create();
+		let texture = Pool::new().add(Texture::from_color([255; 4]));
+
+		// Four frames a second along the top of a 3x1 atlas
+		let mut sprite = AnimatedSprite::from_grid(texture, 48.0, 16.0, 3, 1, 3, 4.0);
+		sprite.looping = looping;
+
+		let mut sprites = ComponentList::new();
+		sprites.add(&mut entity_manager, entity, sprite);
+		(entity, sprites)
+	}
+
+	#[test]
+	fn advances_frames() {
+		let (entity, mut sprites) = sprite(true);
+
+		update(&mut sprites, 0.2);
+		assert_eq!(sprites.borrow(&entity).frame_index(), 0);
+
+		// The time left over from the last update carries into this one
+		update(&mut sprites, 0.1);
+		let sprite = sprites.borrow(&entity);
+		assert_eq!(sprite.frame_index(), 1);
+		assert_eq!(sprite.attributes()[2], 1.0 / 3.0);
+
+		// A long update steps over more than one frame
+		update(&mut sprites, 0.5);
+		assert_eq!(sprites.borrow(&entity).frame_index(), 0);
+
+		sprites.borrow_mut(&entity).playing = false;
+		update(&mut sprites, 1.0);
+		assert_eq!(sprites.borrow(&entity).frame_index(), 0);
+	}
+
+	#[test]
+	fn stops_on_the_last_frame_without_looping() {
+		let (entity, mut sprites) = sprite(false);
+
+		update(&mut sprites, 2.0);
+		let sprite = sprites.borrow(&entity);
+		assert_eq!(sprite.frame_index(), 2);
+		assert!(!sprite.playing);
+		assert_eq!(sprite.uv_rect(), (2.0 / 3.0, 0.0, 1.0, 1.0));
+	}
+}
\ No newline at end of file
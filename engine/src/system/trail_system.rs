@@ -0,0 +1,18 @@
+use std::time::Duration;
+use crate::component::{ComponentList, Trail, Transform3DComponentList};
+
+pub struct TrailSystem;
+
+impl TrailSystem {
+	pub fn new() -> Self {
+		Self
+	}
+
+	// Records where each entity with a trail is in world space, run it after the transforms have been updated for the frame
+	pub fn update(&self, delta_time: &Duration, trail_components: &mut ComponentList<Trail>, transform3d_components: &Transform3DComponentList) {
+		for (entity, trail) in trail_components.iter_mut() {
+			let position = transform3d_components.borrow(entity).global_matrix().extract_position();
+			trail.update(position, delta_time.as_secs_f32());
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+// An image sprites, tilemaps and textured meshes sample from the render system's texture table. It's copied to the GPU when
+// it's submitted and again when it's updated, which is how video frames are streamed into it. The pixels are sRGB 8 bit
+// RGBA, row by row from the top.
+pub struct Texture {
+	width: usize,
+	height: usize,
+	pub pixels: Vec<u8>,
+	// The texture's index in the render system's texture store once it's submitted
+	pub(crate) submission_index: Option<usize>
+}
+
+impl Texture {
+	pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+		assert!(width > 0 && height > 0, "A texture must be at least 1x1");
+		assert_eq!(pixels.len(), width * height * 4, "The pixels of a {}x{} texture must be {} bytes", width, height, width * height * 4);
+
+		Self {
+			width,
+			height,
+			pixels,
+			submission_index: None
+		}
+	}
+
+	// A texture of a single color, such as for drawing solid rectangles
+	pub fn from_color(color: [u8; 4]) -> Self {
+		Self::new(1, 1, color.to_vec())
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn submitted(&self) -> bool {
+		self.submission_index.is_some()
+	}
+}
\ No newline at end of file
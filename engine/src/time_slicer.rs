@@ -0,0 +1,143 @@
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TaskStatus {
+	Pending,
+	Done
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TaskId(u64);
+
+struct Task<C> {
+	id: TaskId,
+	step: Box<dyn FnMut(&mut C) -> TaskStatus>
+}
+
+// Spreads expensive work like BVH refits and rebuilding data over frames so it doesn't cause a spike. Each task is a step
+// function called over and over until it returns done, it should do a small piece of the work each call since a step isn't
+// interrupted when the budget runs out. Tasks take turns, one step each, and the next frame carries on with the task after
+// the last one that ran. The context is whatever the tasks work on, such as the game's components.
+pub struct TimeSlicer<C> {
+	tasks: VecDeque<Task<C>>,
+	next_id: u64
+}
+
+impl<C> TimeSlicer<C> {
+	pub fn new() -> Self {
+		Self {
+			tasks: VecDeque::new(),
+			next_id: 0
+		}
+	}
+
+	pub fn add(&mut self, step: impl FnMut(&mut C) -> TaskStatus + 'static) -> TaskId {
+		let id = TaskId(self.next_id);
+		self.next_id += 1;
+		self.tasks.push_back(Task { id, step: Box::new(step) });
+		id
+	}
+
+	// Returns whether the task was still pending
+	pub fn cancel(&mut self, id: TaskId) -> bool {
+		let len = self.tasks.len();
+		self.tasks.retain(|task| task.id != id);
+		self.tasks.len() != len
+	}
+
+	pub fn is_pending(&self, id: TaskId) -> bool {
+		self.tasks.iter().any(|task| task.id == id)
+	}
+
+	pub fn len(&self) -> usize {
+		self.tasks.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tasks.is_empty()
+	}
+
+	// Runs steps until the budget is used up or every task is done, returns how many ran. At least one step runs so the work
+	// always moves forward even when the budget is too small for any.
+	pub fn run_for(&mut self, budget: Duration, context: &mut C) -> usize {
+		self.run(budget, context, Instant::now)
+	}
+
+	fn run(&mut self, budget: Duration, context: &mut C, mut now: impl FnMut() -> Instant) -> usize {
+		let start = now();
+		let mut step_count = 0;
+
+		while let Some(mut task) = self.tasks.pop_front() {
+			step_count += 1;
+
+			if (task.step)(context) == TaskStatus::Pending {
+				self.tasks.push_back(task);
+			}
+
+			if now().duration_since(start) >= budget {
+				break;
+			}
+		}
+
+		step_count
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Counts down to 0 one step at a time, the context records which task each step was from
+	fn countdown(name: char, mut remaining: u32) -> impl FnMut(&mut Vec<char>) -> TaskStatus {
+		move |steps: &mut Vec<char>| {
+			steps.push(name);
+			remaining -= 1;
+			if remaining == 0 { TaskStatus::Done } else { TaskStatus::Pending }
+		}
+	}
+
+	// Each step takes a millisecond
+	fn clock() -> impl FnMut() -> Instant {
+		let start = Instant::now();
+		let mut elapsed = Duration::ZERO;
+
+		move || {
+			let now = start + elapsed;
+			elapsed += Duration::from_millis(1);
+			now
+		}
+	}
+
+	#[test]
+	fn takes_turns_within_the_budget() {
+		let mut time_slicer = TimeSlicer::new();
+		let a = time_slicer.add(countdown('a', 2));
+		let b = time_slicer.add(countdown('b', 3));
+		let mut steps = Vec::new();
+
+		assert_eq!(time_slicer.run(Duration::from_millis(3), &mut steps, clock()), 3);
+		assert_eq!(steps, vec!['a', 'b', 'a']);
+		assert!(!time_slicer.is_pending(a));
+		assert!(time_slicer.is_pending(b));
+
+		// Too small a budget still runs a step
+		assert_eq!(time_slicer.run(Duration::ZERO, &mut steps, clock()), 1);
+		assert_eq!(time_slicer.run(Duration::from_millis(10), &mut steps, clock()), 1);
+		assert_eq!(steps, vec!['a', 'b', 'a', 'b', 'b']);
+		assert!(time_slicer.is_empty());
+		assert_eq!(time_slicer.run(Duration::from_millis(10), &mut steps, clock()), 0);
+	}
+
+	#[test]
+	fn cancels_tasks() {
+		let mut time_slicer = TimeSlicer::new();
+		let a = time_slicer.add(countdown('a', 5));
+		time_slicer.add(countdown('b', 1));
+		assert!(time_slicer.cancel(a));
+		assert!(!time_slicer.cancel(a));
+
+		let mut steps = Vec::new();
+		time_slicer.run_for(Duration::from_millis(2), &mut steps);
+		assert_eq!(steps, vec!['b']);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,440 @@
+use std::{error::Error, fmt, fs::{self, File}, io::{self, Read, Seek, SeekFrom}, path::{Path, PathBuf}};
+use crate::binary_reader::{BinaryReader, BinaryReadError};
+
+const PACK_MAGIC: &[u8; 4] = b"VGPK";
+const PACK_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+// An entry with an empty path
+const MIN_ENTRY_SIZE: usize = 20;
+const FLAG_COMPRESSED: u32 = 1;
+// Where the pack built with pack_gen is looked for
+pub const DEFAULT_PACK_PATH: &str = "data.pak";
+// Where the build puts compiled shaders and generated font files
+const DEVELOPMENT_DIRECTORY: &str = "target";
+
+#[derive(Debug)]
+pub enum VfsError {
+	Io { path: PathBuf, error: io::Error },
+	InvalidPack { path: PathBuf, reason: String },
+	NotFound { path: String },
+	Decompress { path: String },
+	CompressionUnsupported { path: String }
+}
+
+impl fmt::Display for VfsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot read {}: {}", path.display(), error),
+			Self::InvalidPack { path, reason } => write!(f, "Pack file {} is invalid: {}", path.display(), reason),
+			Self::NotFound { path } => write!(f, "{} is not in any mounted directory or pack", path),
+			Self::Decompress { path } => write!(f, "Cannot decompress {}", path),
+			Self::CompressionUnsupported { path } => write!(f, "{} is compressed but the pack_compression feature is disabled", path)
+		}
+	}
+}
+
+impl Error for VfsError {}
+
+struct PackEntry {
+	path: String,
+	offset: u32,
+	stored_size: u32,
+	size: u32,
+	flags: u32
+}
+
+// A single file holding many assets. It starts with a header of the magic, version, entry count and table size, then the
+// table where each entry is the path's length and bytes followed by the data's offset from the start of the file, its size
+// as stored, its size once decompressed and flags. The data follows the table. Everything is little endian.
+pub struct Pack {
+	path: PathBuf,
+	entries: Vec<PackEntry>
+}
+
+impl Pack {
+	// Only the table is read, the data is read from the file when it's asked for
+	pub fn open(path: impl AsRef<Path>) -> Result<Self, VfsError> {
+		let path = path.as_ref().to_path_buf();
+		let io_error = |error| VfsError::Io { path: path.clone(), error };
+		let invalid = |error: BinaryReadError| VfsError::InvalidPack { path: path.clone(), reason: error.to_string() };
+
+		let mut file = File::open(&path).map_err(io_error)?;
+		let file_size = file.metadata().map_err(io_error)?.len();
+
+		if file_size < HEADER_SIZE as u64 {
+			return Err(VfsError::InvalidPack { path, reason: String::from("the header is truncated") });
+		}
+
+		let mut header = [0; HEADER_SIZE];
+		file.read_exact(&mut header).map_err(io_error)?;
+
+		if &header[..4] != PACK_MAGIC {
+			return Err(VfsError::InvalidPack { path, reason: String::from("the magic number is wrong") });
+		}
+
+		let mut reader = BinaryReader::new(&header[4..]);
+		let version = reader.read_u32().map_err(invalid)?;
+
+		if version != PACK_VERSION {
+			return Err(VfsError::InvalidPack { path, reason: format!("version {} is not supported", version) });
+		}
+
+		let entry_count = reader.read_u32().map_err(invalid)?;
+		let table_size = reader.read_u32().map_err(invalid)?;
+
+		// Check the sizes before allocating anything from them
+		if HEADER_SIZE as u64 + table_size as u64 > file_size {
+			return Err(VfsError::InvalidPack { path, reason: format!("the {} byte table runs past the end of the file", table_size) });
+		}
+
+		let mut table = vec![0; table_size as usize];
+		file.read_exact(&mut table).map_err(io_error)?;
+
+		// Don't trust the count for the allocation, the reader catches a count larger than the table
+		let mut reader = BinaryReader::new(&table);
+		let mut entries = Vec::with_capacity((entry_count as usize).min(table.len() / MIN_ENTRY_SIZE));
+
+		for _ in 0..entry_count {
+			let path_length = reader.read_u32().map_err(invalid)?;
+			let entry_path = String::from_utf8(reader.read_bytes(path_length as usize).map_err(invalid)?.to_vec())
+				.map_err(|_| VfsError::InvalidPack { path: path.clone(), reason: String::from("a path is not UTF-8") })?;
+
+			let entry = PackEntry {
+				path: entry_path,
+				offset: reader.read_u32().map_err(invalid)?,
+				stored_size: reader.read_u32().map_err(invalid)?,
+				size: reader.read_u32().map_err(invalid)?,
+				flags: reader.read_u32().map_err(invalid)?
+			};
+
+			if entry.flags & FLAG_COMPRESSED == 0 && entry.size != entry.stored_size {
+				return Err(VfsError::InvalidPack { path, reason: format!("{} is uncompressed but its sizes differ", entry.path) });
+			}
+
+			if entry.offset as u64 + entry.stored_size as u64 > file_size {
+				return Err(VfsError::InvalidPack { path, reason: format!("{} runs past the end of the file", entry.path) });
+			}
+
+			entries.push(entry);
+		}
+
+		Ok(Self {
+			path,
+			entries
+		})
+	}
+
+	pub fn contains(&self, path: &str) -> bool {
+		self.entry(path).is_some()
+	}
+
+	pub fn paths(&self) -> impl Iterator<Item = &str> {
+		self.entries.iter().map(|entry| entry.path.as_str())
+	}
+
+	pub fn read(&self, path: &str) -> Result<Vec<u8>, VfsError> {
+		let entry = self.entry(path).ok_or_else(|| VfsError::NotFound { path: String::from(path) })?;
+		let io_error = |error| VfsError::Io { path: self.path.clone(), error };
+
+		let mut file = File::open(&self.path).map_err(io_error)?;
+		file.seek(SeekFrom::Start(entry.offset as u64)).map_err(io_error)?;
+		let mut bytes = vec![0; entry.stored_size as usize];
+		file.read_exact(&mut bytes).map_err(io_error)?;
+
+		if entry.flags & FLAG_COMPRESSED != 0 {
+			bytes = decompress(&bytes, entry)?;
+		}
+
+		Ok(bytes)
+	}
+
+	fn entry(&self, path: &str) -> Option<&PackEntry> {
+		let path = normalize(path);
+		self.entries.iter().find(|entry| entry.path == path)
+	}
+}
+
+#[cfg(feature = "pack_compression")]
+fn decompress(bytes: &[u8], entry: &PackEntry) -> Result<Vec<u8>, VfsError> {
+	miniz_oxide::inflate::decompress_to_vec_with_limit(bytes, entry.size as usize)
+		.ok()
+		.filter(|decompressed| decompressed.len() == entry.size as usize)
+		.ok_or_else(|| VfsError::Decompress { path: entry.path.clone() })
+}
+
+#[cfg(not(feature = "pack_compression"))]
+fn decompress(_bytes: &[u8], entry: &PackEntry) -> Result<Vec<u8>, VfsError> {
+	Err(VfsError::CompressionUnsupported { path: entry.path.clone() })
+}
+
+// Builds a pack file, usually from the directories a development build loads its assets from
+pub struct PackWriter {
+	entries: Vec<(String, Vec<u8>, u32, u32)>
+}
+
+impl PackWriter {
+	pub fn new() -> Self {
+		Self {
+			entries: Vec::new()
+		}
+	}
+
+	// A file added again replaces the earlier one
+	pub fn add(&mut self, path: &str, bytes: Vec<u8>) {
+		let size = bytes.len() as u32;
+		self.add_entry(path, bytes, size, 0);
+	}
+
+	#[cfg(feature = "pack_compression")]
+	pub fn add_compressed(&mut self, path: &str, bytes: &[u8]) {
+		let compressed = miniz_oxide::deflate::compress_to_vec(bytes, 6);
+		self.add_entry(path, compressed, bytes.len() as u32, FLAG_COMPRESSED);
+	}
+
+	fn add_entry(&mut self, path: &str, bytes: Vec<u8>, size: u32, flags: u32) {
+		let path = normalize(path);
+		self.entries.retain(|(entry_path, _, _, _)| *entry_path != path);
+		self.entries.push((path, bytes, size, flags));
+	}
+
+	// Adds every file under the directory with its path relative to the directory put after the prefix
+	pub fn add_directory(&mut self, directory: impl AsRef<Path>, prefix: &str) -> io::Result<()> {
+		for dir_entry in fs::read_dir(directory)? {
+			let dir_entry = dir_entry?;
+			let name = dir_entry.file_name().to_string_lossy().into_owned();
+			let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+
+			if dir_entry.file_type()?.is_dir() {
+				self.add_directory(dir_entry.path(), &path)?;
+			}
+			else {
+				self.add(&path, fs::read(dir_entry.path())?);
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let table_size: usize = self.entries.iter().map(|(path, _, _, _)| 4 + path.len() + 16).sum();
+		let mut offset = (HEADER_SIZE + table_size) as u32;
+		let mut bytes = Vec::with_capacity(offset as usize + self.entries.iter().map(|(_, data, _, _)| data.len()).sum::<usize>());
+
+		bytes.extend_from_slice(PACK_MAGIC);
+		bytes.extend_from_slice(&PACK_VERSION.to_le_bytes());
+		bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(&(table_size as u32).to_le_bytes());
+
+		for (path, data, size, flags) in &self.entries {
+			bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+			bytes.extend_from_slice(path.as_bytes());
+
+			for value in [offset, data.len() as u32, *size, *flags] {
+				bytes.extend_from_slice(&value.to_le_bytes());
+			}
+
+			offset += data.len() as u32;
+		}
+
+		for (_, data, _, _) in &self.entries {
+			bytes.extend_from_slice(data);
+		}
+
+		bytes
+	}
+
+	pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, self.to_bytes())
+	}
+}
+
+enum Mount {
+	Directory(PathBuf),
+	Pack(Pack)
+}
+
+// Reads assets by a path like "shaders/basic.vert.spv" from whichever mounted directory or pack has it. Mounts made later
+// take priority so a loose directory mounted after the game's pack overrides files in it during development, or a patch pack
+// overrides the original.
+pub struct Vfs {
+	mounts: Vec<Mount>
+}
+
+impl Vfs {
+	pub fn new() -> Self {
+		Self {
+			mounts: Vec::new()
+		}
+	}
+
+	// The default pack if there is one. Development builds fall back to the target directory for anything the pack doesn't
+	// have, which is everything when the pack hasn't been built.
+	pub fn with_default_mounts() -> Result<Self, VfsError> {
+		let mut vfs = Self::new();
+
+		if cfg!(debug_assertions) {
+			vfs.mount_directory(DEVELOPMENT_DIRECTORY);
+		}
+
+		if Path::new(DEFAULT_PACK_PATH).is_file() {
+			vfs.mount_pack(DEFAULT_PACK_PATH)?;
+		}
+
+		Ok(vfs)
+	}
+
+	pub fn mount_directory(&mut self, directory: impl AsRef<Path>) {
+		self.mounts.push(Mount::Directory(directory.as_ref().to_path_buf()));
+	}
+
+	pub fn mount_pack(&mut self, path: impl AsRef<Path>) -> Result<(), VfsError> {
+		self.mounts.push(Mount::Pack(Pack::open(path)?));
+		Ok(())
+	}
+
+	pub fn exists(&self, path: &str) -> bool {
+		self.mounts.iter().any(|mount| match mount {
+			Mount::Directory(directory) => directory.join(normalize(path)).is_file(),
+			Mount::Pack(pack) => pack.contains(path)
+		})
+	}
+
+	pub fn read(&self, path: &str) -> Result<Vec<u8>, VfsError> {
+		for mount in self.mounts.iter().rev() {
+			match mount {
+				Mount::Directory(directory) => {
+					let file_path = directory.join(normalize(path));
+
+					match fs::read(&file_path) {
+						Ok(bytes) => return Ok(bytes),
+						Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+						Err(error) => return Err(VfsError::Io { path: file_path, error })
+					}
+				},
+				Mount::Pack(pack) => {
+					if pack.contains(path) {
+						return pack.read(path);
+					}
+				}
+			}
+		}
+
+		Err(VfsError::NotFound { path: String::from(path) })
+	}
+
+	pub fn read_to_string(&self, path: &str) -> Result<String, VfsError> {
+		let bytes = self.read(path)?;
+		String::from_utf8(bytes).map_err(|error| VfsError::Io { path: PathBuf::from(path), error: io::Error::new(io::ErrorKind::InvalidData, error) })
+	}
+}
+
+// Forward slashes without a leading ./ or /
+fn normalize(path: &str) -> String {
+	let path = path.replace('\\', "/");
+	let path = path.trim_start_matches("./").trim_start_matches('/');
+	String::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_dir(name: &str) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!("vfs_test_{}_{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(directory.join("shaders")).unwrap();
+		directory
+	}
+
+	#[test]
+	fn pack_round_trip() {
+		let directory = temp_dir("pack");
+		fs::write(directory.join("shaders/basic.vert.spv"), [1, 2, 3, 4]).unwrap();
+		fs::write(directory.join("readme.txt"), "hello").unwrap();
+
+		let mut writer = PackWriter::new();
+		writer.add_directory(&directory, "").unwrap();
+		writer.add("fonts/arial.fnt", vec![9; 100]);
+		let pack_path = directory.join("data.pak");
+		writer.write(&pack_path).unwrap();
+
+		let pack = Pack::open(&pack_path).unwrap();
+		assert_eq!(pack.paths().count(), 3);
+		assert_eq!(pack.read("shaders/basic.vert.spv").unwrap(), vec![1, 2, 3, 4]);
+		assert_eq!(pack.read("./readme.txt").unwrap(), b"hello");
+		assert_eq!(pack.read("fonts\\arial.fnt").unwrap(), vec![9; 100]);
+		assert!(matches!(pack.read("missing"), Err(VfsError::NotFound { .. })));
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn corrupt_packs_are_invalid() {
+		let directory = temp_dir("corrupt");
+		let pack_path = directory.join("data.pak");
+		let mut writer = PackWriter::new();
+		writer.add("readme.txt", b"hello".to_vec());
+		let bytes = writer.to_bytes();
+
+		let is_invalid = |bytes: &[u8]| {
+			fs::write(&pack_path, bytes).unwrap();
+			matches!(Pack::open(&pack_path), Err(VfsError::InvalidPack { .. }))
+		};
+
+		// Truncated header, wrong magic and a table larger than the file
+		assert!(is_invalid(b"VGPK\x01\x00"));
+		assert!(is_invalid(b"nope\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"));
+		assert!(is_invalid(b"VGPK\x01\x00\x00\x00\x01\x00\x00\x00\xff\xff\xff\xff"));
+
+		// An entry count far larger than the table holds
+		let mut huge_count = bytes.clone();
+		huge_count[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(is_invalid(&huge_count));
+
+		// The data cut off at the end of the file
+		assert!(is_invalid(&bytes[..bytes.len() - 1]));
+
+		assert!(!is_invalid(&bytes));
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn later_mounts_take_priority() {
+		let directory = temp_dir("mounts");
+		let mut writer = PackWriter::new();
+		writer.add("shaders/basic.vert.spv", vec![1]);
+		writer.add("only_in_pack.txt", b"pack".to_vec());
+		let pack_path = directory.join("data.pak");
+		writer.write(&pack_path).unwrap();
+		fs::write(directory.join("shaders/basic.vert.spv"), [2]).unwrap();
+
+		let mut vfs = Vfs::new();
+		vfs.mount_pack(&pack_path).unwrap();
+		assert_eq!(vfs.read("shaders/basic.vert.spv").unwrap(), vec![1]);
+
+		vfs.mount_directory(&directory);
+		assert_eq!(vfs.read("shaders/basic.vert.spv").unwrap(), vec![2]);
+		assert_eq!(vfs.read_to_string("only_in_pack.txt").unwrap(), "pack");
+		assert!(vfs.exists("only_in_pack.txt"));
+		assert!(!vfs.exists("missing.txt"));
+		assert!(matches!(vfs.read("missing.txt"), Err(VfsError::NotFound { .. })));
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[cfg(feature = "pack_compression")]
+	#[test]
+	fn compressed_entries() {
+		let directory = temp_dir("compressed");
+		let text = "repeated text ".repeat(100);
+		let mut writer = PackWriter::new();
+		writer.add_compressed("text.txt", text.as_bytes());
+		let pack_path = directory.join("data.pak");
+		writer.write(&pack_path).unwrap();
+
+		assert!(fs::metadata(&pack_path).unwrap().len() < text.len() as u64);
+		assert_eq!(Pack::open(&pack_path).unwrap().read("text.txt").unwrap(), text.as_bytes());
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
\ No newline at end of file
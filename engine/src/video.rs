@@ -0,0 +1,499 @@
+use std::{error::Error, fmt, fs, io::{self, Cursor}, mem, ops::Range, path::{Path, PathBuf}, sync::Arc};
+use crate::texture::Texture;
+
+#[derive(Debug)]
+pub enum VideoError {
+	Io { path: PathBuf, error: io::Error },
+	Png(png::DecodingError),
+	Invalid { reason: String }
+}
+
+impl fmt::Display for VideoError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io { path, error } => write!(f, "Cannot access video {}: {}", path.display(), error),
+			Self::Png(error) => write!(f, "Cannot decode video: {}", error),
+			Self::Invalid { reason } => write!(f, "Video is invalid: {}", reason)
+		}
+	}
+}
+
+impl Error for VideoError {}
+
+impl From<png::DecodingError> for VideoError {
+	fn from(error: png::DecodingError) -> Self {
+		Self::Png(error)
+	}
+}
+
+// Produces the frames of a video one after the other as sRGB 8 bit RGBA, row by row from the top. Other codecs can be played
+// by implementing this.
+pub trait VideoDecoder {
+	fn width(&self) -> usize;
+	fn height(&self) -> usize;
+
+	// Writes the next frame into the pixels and returns how many seconds it's shown for, returns None without touching them
+	// when there are no frames left
+	fn decode_frame(&mut self, pixels: &mut [u8]) -> Result<Option<f32>, VideoError>;
+
+	// Goes back to the first frame
+	fn rewind(&mut self);
+}
+
+// Browsers show frames without a delay for 10 milliseconds, a looping video of them would otherwise never advance time
+const MIN_FRAME_DURATION: f32 = 0.01;
+
+// An animated PNG. Each frame after the first can cover just the region which changed and be blended over or replace what's
+// there, so mostly still images such as in-world screens stay small. The frames are decoded as they're played.
+pub struct ApngVideo {
+	bytes: Arc<[u8]>,
+	// None until the first frame is decoded after a rewind
+	reader: Option<png::Reader<Cursor<Arc<[u8]>>>>,
+	width: usize,
+	height: usize,
+	frame_count: usize,
+	next_frame: usize,
+	// The frame's region as it's decoded, before it's put on the canvas
+	region_pixels: Vec<u8>,
+	// The frames drawn over each other
+	canvas: Vec<u8>,
+	// What happens to the last frame's region before the next frame is drawn
+	disposal: Disposal
+}
+
+enum Disposal {
+	None,
+	Clear(png::FrameControl),
+	Restore(png::FrameControl, Vec<u8>)
+}
+
+impl ApngVideo {
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, VideoError> {
+		let bytes: Arc<[u8]> = bytes.into();
+		let mut region_pixels = vec![];
+		let reader = Self::open(&bytes, &mut region_pixels)?;
+		let info = reader.info();
+
+		let frame_count = match info.animation_control() {
+			Some(animation_control) => animation_control.num_frames as usize,
+			None => return Err(VideoError::Invalid { reason: String::from("the PNG isn't animated") })
+		};
+
+		let width = info.width as usize;
+		let height = info.height as usize;
+
+		Ok(Self {
+			bytes,
+			reader: Some(reader),
+			width,
+			height,
+			frame_count,
+			next_frame: 0,
+			region_pixels,
+			canvas: vec![0; width * height * 4],
+			disposal: Disposal::None
+		})
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, VideoError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|error| VideoError::Io { path: path.to_path_buf(), error })?;
+		Self::from_bytes(bytes)
+	}
+
+	pub fn frame_count(&self) -> usize {
+		self.frame_count
+	}
+
+	// Reads up to the first frame of the animation, the default image is skipped when it isn't part of it
+	fn open(bytes: &Arc<[u8]>, region_pixels: &mut Vec<u8>) -> Result<png::Reader<Cursor<Arc<[u8]>>>, VideoError> {
+		let mut decoder = png::Decoder::new(Cursor::new(bytes.clone()));
+		decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+		let (_, mut reader) = decoder.read_info()?;
+		region_pixels.resize(reader.output_buffer_size(), 0);
+
+		if reader.info().animation_control().is_some() && reader.info().frame_control().is_none() {
+			reader.next_frame(region_pixels)?;
+		}
+
+		Ok(reader)
+	}
+
+	fn dispose(&mut self) {
+		match mem::replace(&mut self.disposal, Disposal::None) {
+			Disposal::None => (),
+			Disposal::Clear(control) => {
+				for row in region_rows(self.width, &control) {
+					self.canvas[row].iter_mut().for_each(|byte| *byte = 0);
+				}
+			},
+			Disposal::Restore(control, pixels) => {
+				let row_size = control.width as usize * 4;
+
+				for (row, saved_row) in region_rows(self.width, &control).zip(pixels.chunks_exact(row_size)) {
+					self.canvas[row].copy_from_slice(saved_row);
+				}
+			}
+		}
+	}
+}
+
+impl VideoDecoder for ApngVideo {
+	fn width(&self) -> usize {
+		self.width
+	}
+
+	fn height(&self) -> usize {
+		self.height
+	}
+
+	fn decode_frame(&mut self, pixels: &mut [u8]) -> Result<Option<f32>, VideoError> {
+		assert_eq!(pixels.len(), self.width * self.height * 4, "The pixels must be the size of a frame");
+
+		if self.next_frame == self.frame_count {
+			return Ok(None);
+		}
+
+		if self.reader.is_none() {
+			self.reader = Some(Self::open(&self.bytes, &mut self.region_pixels)?);
+		}
+
+		let reader = self.reader.as_mut().unwrap();
+		reader.next_frame(&mut self.region_pixels)?;
+		let (color_type, _) = reader.output_color_type();
+
+		let control = match reader.info().frame_control() {
+			Some(control) => *control,
+			None => return Err(VideoError::Invalid { reason: format!("frame {} has no frame control", self.next_frame) })
+		};
+
+		if control.x_offset as usize + control.width as usize > self.width || control.y_offset as usize + control.height as usize > self.height {
+			return Err(VideoError::Invalid { reason: format!("frame {} is outside of the image", self.next_frame) });
+		}
+
+		self.dispose();
+
+		// Restoring before the first frame is the same as clearing
+		self.disposal = match control.dispose_op {
+			png::DisposeOp::None => Disposal::None,
+			png::DisposeOp::Previous if self.next_frame > 0 => {
+				let saved_pixels = region_rows(self.width, &control).flat_map(|row| self.canvas[row].to_vec()).collect();
+				Disposal::Restore(control, saved_pixels)
+			},
+			_ => Disposal::Clear(control)
+		};
+
+		let samples = color_type.samples();
+		let region_row_size = control.width as usize * samples;
+
+		for (row, region_row) in region_rows(self.width, &control).zip(self.region_pixels.chunks_exact(region_row_size)) {
+			for (dst, src) in self.canvas[row].chunks_exact_mut(4).zip(region_row.chunks_exact(samples)) {
+				let src = match color_type {
+					png::ColorType::Grayscale => [src[0], src[0], src[0], 255],
+					png::ColorType::GrayscaleAlpha => [src[0], src[0], src[0], src[1]],
+					png::ColorType::RGB => [src[0], src[1], src[2], 255],
+					_ => [src[0], src[1], src[2], src[3]]
+				};
+
+				if control.blend_op == png::BlendOp::Source {
+					dst.copy_from_slice(&src);
+				}
+				else {
+					blend_over(dst, src);
+				}
+			}
+		}
+
+		pixels.copy_from_slice(&self.canvas);
+		self.next_frame += 1;
+
+		// A denominator of 0 means hundredths of a second
+		let delay_den = if control.delay_den == 0 { 100.0 } else { control.delay_den as f32 };
+		Ok(Some((control.delay_num as f32 / delay_den).max(MIN_FRAME_DURATION)))
+	}
+
+	fn rewind(&mut self) {
+		self.reader = None;
+		self.next_frame = 0;
+		self.canvas.iter_mut().for_each(|byte| *byte = 0);
+		self.disposal = Disposal::None;
+	}
+}
+
+// The ranges of a canvas of the width covered by each row of a frame's region
+fn region_rows(width: usize, control: &png::FrameControl) -> impl Iterator<Item = Range<usize>> {
+	let x = control.x_offset as usize;
+	let row_size = control.width as usize * 4;
+	let y = control.y_offset as usize;
+
+	(y..y + control.height as usize).map(move |y| {
+		let start = (y * width + x) * 4;
+		start..start + row_size
+	})
+}
+
+// Composites a straight alpha color over another
+fn blend_over(dst: &mut [u8], src: [u8; 4]) {
+	let src_alpha = src[3] as u32;
+	let dst_alpha = dst[3] as u32 * (255 - src_alpha) / 255;
+	let alpha = src_alpha + dst_alpha;
+
+	if alpha == 0 {
+		return;
+	}
+
+	for (dst_component, src_component) in dst[..3].iter_mut().zip(&src[..3]) {
+		*dst_component = ((*src_component as u32 * src_alpha + *dst_component as u32 * dst_alpha) / alpha) as u8;
+	}
+
+	dst[3] = alpha as u8;
+}
+
+// Steps a decoder through time and decodes its frames into a texture, the texture only needs updating in the render system
+// when an update reports it changed
+pub struct VideoPlayer {
+	decoder: Box<dyn VideoDecoder>,
+	pub looping: bool,
+	pub playing: bool,
+	time: f32,
+	// When the frame being shown ends
+	frame_end_time: f32,
+	frame_index: Option<usize>,
+	finished: bool
+}
+
+impl VideoPlayer {
+	pub fn new(decoder: Box<dyn VideoDecoder>) -> Self {
+		Self {
+			decoder,
+			looping: false,
+			playing: true,
+			time: 0.0,
+			frame_end_time: 0.0,
+			frame_index: None,
+			finished: false
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.decoder.width()
+	}
+
+	pub fn height(&self) -> usize {
+		self.decoder.height()
+	}
+
+	// A transparent texture the size of the video to submit to the render system and pass to update
+	pub fn create_texture(&self) -> Texture {
+		Texture::new(self.width(), self.height(), vec![0; self.width() * self.height() * 4])
+	}
+
+	// The frame in the texture, none before the first frame is decoded
+	pub fn frame_index(&self) -> Option<usize> {
+		self.frame_index
+	}
+
+	// Whether the last frame has been shown, a looping video never finishes
+	pub fn finished(&self) -> bool {
+		self.finished
+	}
+
+	// Advances the playback and decodes every frame up to the current time into the texture since each one can build on the
+	// last. Returns true if the pixels changed, the texture then needs updating in the render system.
+	pub fn update(&mut self, delta: f32, texture: &mut Texture) -> Result<bool, VideoError> {
+		assert!(texture.width() == self.width() && texture.height() == self.height(), "The texture must be the size of the video");
+
+		if !self.playing || self.finished {
+			return Ok(false);
+		}
+
+		self.time += delta;
+		let mut changed = false;
+
+		while self.frame_index.is_none() || self.time >= self.frame_end_time {
+			if let Some(duration) = self.decoder.decode_frame(&mut texture.pixels)? {
+				self.frame_index = Some(self.frame_index.map_or(0, |frame_index| frame_index + 1));
+				self.frame_end_time += duration;
+				changed = true;
+			}
+			else if self.looping && self.frame_index.is_some() {
+				// Carry the time past the end over into the next loop
+				self.time -= self.frame_end_time;
+				self.frame_end_time = 0.0;
+				self.decoder.rewind();
+				self.frame_index = None;
+			}
+			else {
+				self.finished = true;
+				break;
+			}
+		}
+
+		Ok(changed)
+	}
+
+	// Goes back to the start without decoding anything, the texture keeps the frame being shown until the next update
+	pub fn rewind(&mut self) {
+		self.decoder.rewind();
+		self.time = 0.0;
+		self.frame_end_time = 0.0;
+		self.frame_index = None;
+		self.finished = false;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::convert::TryInto;
+	use super::*;
+
+	// A chunk's length, type, data and checksum
+	fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+		let mut chunks = vec![];
+		let mut position = 8;
+
+		while position < png.len() {
+			let length = u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+			let chunk_type = png[position + 4..position + 8].try_into().unwrap();
+			chunks.push((chunk_type, png[position + 8..position + 8 + length].to_vec()));
+			position += 12 + length;
+		}
+
+		chunks
+	}
+
+	fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+		let mut bytes = vec![];
+		let mut encoder = png::Encoder::new(&mut bytes, width, height);
+		encoder.set_color(png::ColorType::RGBA);
+		encoder.set_depth(png::BitDepth::Eight);
+		encoder.write_header().unwrap().write_image_data(pixels).unwrap();
+		bytes
+	}
+
+	fn compressed_image_data(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+		chunks(&encode_png(width, height, pixels)).into_iter().filter(|(chunk_type, _)| chunk_type == b"IDAT").flat_map(|(_, data)| data).collect()
+	}
+
+	fn frame_control(sequence_number: u32, control: &png::FrameControl) -> Vec<u8> {
+		let mut data = vec![];
+		data.extend_from_slice(&sequence_number.to_be_bytes());
+		data.extend_from_slice(&control.width.to_be_bytes());
+		data.extend_from_slice(&control.height.to_be_bytes());
+		data.extend_from_slice(&control.x_offset.to_be_bytes());
+		data.extend_from_slice(&control.y_offset.to_be_bytes());
+		data.extend_from_slice(&control.delay_num.to_be_bytes());
+		data.extend_from_slice(&control.delay_den.to_be_bytes());
+		data.push(control.dispose_op as u8);
+		data.push(control.blend_op as u8);
+		data
+	}
+
+	// Each frame is its control and the RGBA pixels of its region, the first frame is the default image
+	fn encode_apng(width: u32, height: u32, frames: &[(png::FrameControl, Vec<u8>)]) -> Vec<u8> {
+		let mut apng = vec![];
+		let mut encoder = png::Encoder::new(&mut apng, width, height);
+		encoder.set_color(png::ColorType::RGBA);
+		encoder.set_depth(png::BitDepth::Eight);
+		let mut writer = encoder.write_header().unwrap();
+
+		let mut animation_control = (frames.len() as u32).to_be_bytes().to_vec();
+		animation_control.extend_from_slice(&0u32.to_be_bytes());
+		writer.write_chunk(*b"acTL", &animation_control).unwrap();
+
+		let mut sequence_number = 0;
+
+		for (i, (control, pixels)) in frames.iter().enumerate() {
+			writer.write_chunk(*b"fcTL", &frame_control(sequence_number, control)).unwrap();
+			sequence_number += 1;
+			let image_data = compressed_image_data(control.width, control.height, pixels);
+
+			if i == 0 {
+				writer.write_chunk(*b"IDAT", &image_data).unwrap();
+			}
+			else {
+				let mut frame_data = sequence_number.to_be_bytes().to_vec();
+				frame_data.extend(image_data);
+				writer.write_chunk(*b"fdAT", &frame_data).unwrap();
+				sequence_number += 1;
+			}
+		}
+
+		drop(writer);
+		apng
+	}
+
+	fn control(width: u32, height: u32, x_offset: u32, y_offset: u32, dispose_op: png::DisposeOp, blend_op: png::BlendOp) -> png::FrameControl {
+		png::FrameControl { sequence_number: 0, width, height, x_offset, y_offset, delay_num: 1, delay_den: 10, dispose_op, blend_op }
+	}
+
+	fn test_video() -> (Vec<u8>, Vec<Vec<u8>>) {
+		// A 2x2 image, the second frame blends a half transparent white pixel over the bottom right and the third replaces the
+		// top left after the second is restored
+		let first: Vec<u8> = vec![255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 255];
+		let frames = [
+			(control(2, 2, 0, 0, png::DisposeOp::None, png::BlendOp::Source), first.clone()),
+			(control(1, 1, 1, 1, png::DisposeOp::Previous, png::BlendOp::Over), vec![255, 255, 255, 128]),
+			(control(1, 1, 0, 0, png::DisposeOp::None, png::BlendOp::Source), vec![10, 20, 30, 40])
+		];
+
+		let mut second = first.clone();
+		second[12..16].copy_from_slice(&[128, 128, 128, 255]);
+		let mut third = first.clone();
+		third[0..4].copy_from_slice(&[10, 20, 30, 40]);
+
+		(encode_apng(2, 2, &frames), vec![first, second, third])
+	}
+
+	#[test]
+	fn decodes_apng() {
+		let (bytes, frames) = test_video();
+		let mut video = ApngVideo::from_bytes(bytes.clone()).unwrap();
+		assert_eq!((video.width(), video.height(), video.frame_count()), (2, 2, 3));
+
+		let mut pixels = vec![0; 16];
+
+		for frame in &frames {
+			assert_eq!(video.decode_frame(&mut pixels).unwrap(), Some(0.1));
+			assert_eq!(&pixels, frame);
+		}
+
+		assert_eq!(video.decode_frame(&mut pixels).unwrap(), None);
+
+		video.rewind();
+		assert_eq!(video.decode_frame(&mut pixels).unwrap(), Some(0.1));
+		assert_eq!(pixels, frames[0]);
+
+		// A still image isn't a video and a cut off one fails to decode
+		assert!(ApngVideo::from_bytes(encode_png(2, 2, &frames[0])).is_err());
+		let truncated = ApngVideo::from_bytes(bytes[..bytes.len() - 20].to_vec())
+			.and_then(|mut video| (0..3).try_for_each(|_| video.decode_frame(&mut pixels).map(|_| ())));
+		assert!(truncated.is_err());
+	}
+
+	#[test]
+	fn player_follows_time() {
+		let (bytes, frames) = test_video();
+		let mut player = VideoPlayer::new(Box::new(ApngVideo::from_bytes(bytes).unwrap()));
+		let mut texture = player.create_texture();
+
+		assert!(player.update(0.0, &mut texture).unwrap());
+		assert_eq!(player.frame_index(), Some(0));
+		assert!(!player.update(0.05, &mut texture).unwrap());
+
+		// Skipping ahead decodes the frames in between
+		assert!(player.update(0.2, &mut texture).unwrap());
+		assert_eq!(player.frame_index(), Some(2));
+		assert_eq!(texture.pixels, frames[2]);
+
+		assert!(!player.update(0.1, &mut texture).unwrap());
+		assert!(player.finished());
+
+		player.rewind();
+		player.looping = true;
+		player.update(0.35, &mut texture).unwrap();
+		assert_eq!(player.frame_index(), Some(0));
+		assert_eq!(texture.pixels, frames[0]);
+		assert!(!player.finished());
+	}
+}
\ No newline at end of file
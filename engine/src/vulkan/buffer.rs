@@ -1,33 +1,40 @@
-use ash::{vk, version::DeviceV1_0};
-use crate::vulkan::Context;
+use ash::vk;
+use crate::vulkan::{Allocation, Context, MemoryCategory};
 
 pub struct Buffer {
 	pub handle: vk::Buffer,
 	pub memory: vk::DeviceMemory,
 	usage: vk::BufferUsageFlags,
 	properties: vk::MemoryPropertyFlags,
+	category: MemoryCategory,
+	// Counts the memory toward the budget until the buffer is reallocated or dropped
+	allocation: Option<Allocation>,
 	pub capacity: vk::DeviceSize
 }
 
 impl Buffer {
-	pub fn new(context: &Context, capacity: vk::DeviceSize, usage: vk::BufferUsageFlags, properties: vk::MemoryPropertyFlags) -> Self {
-		let (handle, memory) = Self::allocate(context, capacity, usage, properties);
+	pub fn new(context: &Context, capacity: vk::DeviceSize, usage: vk::BufferUsageFlags, properties: vk::MemoryPropertyFlags, category: MemoryCategory) -> Self {
+		let (handle, memory, allocation) = Self::allocate(context, capacity, usage, properties, category);
 
 		Self {
 			handle,
 			memory,
 			usage,
 			properties,
+			category,
+			allocation: Some(allocation),
 			capacity
 		}
 	}
 
-	pub fn null(usage: vk::BufferUsageFlags, properties: vk::MemoryPropertyFlags) -> Self {
+	pub fn null(usage: vk::BufferUsageFlags, properties: vk::MemoryPropertyFlags, category: MemoryCategory) -> Self {
 		Self {
 			handle: vk::Buffer::null(),
 			memory: vk::DeviceMemory::null(),
 			usage,
 			properties,
+			category,
+			allocation: None,
 			capacity: 0
 		}
 	}
@@ -38,10 +45,13 @@ impl Buffer {
 			context.logical_device.destroy_buffer(self.handle, None);
 		}
 
-		let (handle, memory) = Self::allocate(context, capacity, self.usage, self.properties);
+		// The old allocation is released first so the budget doesn't count both
+		self.allocation = None;
+		let (handle, memory, allocation) = Self::allocate(context, capacity, self.usage, self.properties, self.category);
 
 		self.handle = handle;
 		self.memory = memory;
+		self.allocation = Some(allocation);
 		self.capacity = capacity;
 	}
 
@@ -49,7 +59,8 @@ impl Buffer {
 		context: &Context,
 		capacity: vk::DeviceSize,
 		usage: vk::BufferUsageFlags,
-		properties: vk::MemoryPropertyFlags) -> (vk::Buffer, vk::DeviceMemory)
+		properties: vk::MemoryPropertyFlags,
+		category: MemoryCategory) -> (vk::Buffer, vk::DeviceMemory, Allocation)
 	{
 		let create_info = vk::BufferCreateInfo::builder()
 			.size(capacity)
@@ -58,16 +69,10 @@ impl Buffer {
 		
 		let handle = unsafe { context.logical_device.create_buffer(&create_info, None).unwrap() };
 		let memory_requirements = unsafe { context.logical_device.get_buffer_memory_requirements(handle) };
-		let memory_type_index = context.physical_device.find_memory_type_index(memory_requirements.memory_type_bits, properties);
-
-		let allocate_info = vk::MemoryAllocateInfo::builder()
-			.allocation_size(memory_requirements.size)
-			.memory_type_index(memory_type_index as u32);
-	
-		let memory = unsafe { context.logical_device.allocate_memory(&allocate_info, None).unwrap() };
+		let (memory, allocation) = context.allocate_memory(&memory_requirements, properties, category);
 		unsafe { context.logical_device.bind_buffer_memory(handle, memory, 0).unwrap() };
 
-		(handle, memory)
+		(handle, memory, allocation)
 	}
 
 	pub fn drop(&self, logical_device: &ash::Device) {
@@ -1,6 +1,6 @@
-use std::{ffi::{CString, CStr}, os::raw::{c_void, c_char}};
-use ash::{vk, version::EntryV1_0, version::InstanceV1_0, version::DeviceV1_0, extensions::ext, extensions::khr, vk::Handle};
-use super::PhysicalDevice;
+use std::{ffi::{CString, CStr}, os::raw::{c_void, c_char}, sync::{Arc, Mutex, MutexGuard}, thread::{self, ThreadId}};
+use ash::{vk, extensions::ext, extensions::khr, prelude::VkResult, vk::Handle};
+use super::{PhysicalDevice, DepthFormat, Allocation, MemoryBudget, MemoryCategory};
 
 pub struct Context {
 	pub instance: ash::Instance,
@@ -9,7 +9,17 @@ pub struct Context {
 	pub surface: Surface,
 	pub logical_device: ash::Device,
 	pub graphics_queue: vk::Queue,
-	pub present_queue: vk::Queue
+	pub present_queue: vk::Queue,
+	// Used by the scene depth attachment, has a stencil aspect if one was required
+	pub depth_format: DepthFormat,
+	// Used by depth images which are sampled, never has a stencil aspect
+	pub sampled_depth_format: DepthFormat,
+	pub memory_budget: Arc<MemoryBudget>,
+	// Queues have to be externally synchronized, anything which submits to, waits on or presents with the graphics or present
+	// queue holds this so resources can be created and uploaded from worker threads
+	queue_mutex: Mutex<()>,
+	// Command pools can only be used by one thread at a time so each thread recording one time commands gets its own
+	thread_command_pools: Mutex<Vec<(ThreadId, vk::CommandPool)>>
 }
 
 pub struct DebugUtils {
@@ -24,9 +34,9 @@ pub struct Surface {
 }
 
 impl Context {
-	pub fn new(glfw: &glfw::Glfw, window: &glfw::Window) -> Self {
+	pub fn new(glfw: &glfw::Glfw, window: &glfw::Window, stencil_required: bool) -> Self {
 		// Create entry
-		let entry = unsafe { ash::Entry::new() }.unwrap();
+		let entry = unsafe { ash::Entry::load() }.expect("Could not load the Vulkan library");
 
 		// Create layer and extension lists
 		let validation_layer = CString::new("VK_LAYER_KHRONOS_validation").unwrap();
@@ -46,7 +56,7 @@ impl Context {
 				.unwrap_or_else(|| panic!("Required layer {} not supported", required_layer.to_str().unwrap()));
 		}
 
-		let available_instance_extensions = entry.enumerate_instance_extension_properties().unwrap();
+		let available_instance_extensions = entry.enumerate_instance_extension_properties(None).unwrap();
 		for required_instance_extension in &required_instance_extensions {
 			available_instance_extensions.iter()
 				.find(|available_instance_extension| unsafe { CStr::from_ptr(available_instance_extension.extension_name.as_ptr()) } == *required_instance_extension)
@@ -57,8 +67,8 @@ impl Context {
 		let engine_name = CString::new("Vulkan Engine").unwrap();
 		let app_info = vk::ApplicationInfo::builder()
 			.engine_name(engine_name.as_c_str())
-			.engine_version(vk::make_version(1, 0, 0))
-			.api_version(vk::make_version(1, 2, 0));
+			.engine_version(vk::make_api_version(0, 1, 0, 0))
+			.api_version(vk::make_api_version(0, 1, 2, 0));
 		
 		let layers: Vec<*const c_char> = required_layers.iter().map(|layer| layer.as_ptr()).collect();
 		let instance_extensions: Vec<*const c_char> = required_instance_extensions.iter().map(|extension| extension.as_ptr()).collect();
@@ -104,6 +114,21 @@ impl Context {
 		let surface_format_option = surface_formats.iter().find(|f| f.format == vk::Format::B8G8R8A8_SRGB && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR);
 		let surface_format = *surface_format_option.unwrap_or_else(|| &surface_formats[0]);
 
+		// Choose depth formats
+		let (instance_ref, physical_device_handle) = (&instance, physical_device.handle);
+		let format_supports = |features: vk::FormatFeatureFlags| move |format| {
+			let properties = unsafe { instance_ref.get_physical_device_format_properties(physical_device_handle, format) };
+			properties.optimal_tiling_features.contains(features)
+		};
+
+		let depth_format = DepthFormat::select(stencil_required, format_supports(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT))
+			.unwrap_or_else(|| panic!("No supported depth format{}", if stencil_required { " with a stencil aspect" } else { "" }));
+
+		let sampled_depth_format = DepthFormat::select_sampled(format_supports(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE))
+			.expect("No supported depth format which can be sampled");
+
+		println!("Using depth format {:?}", depth_format.format);
+
 		// Create logical device and queues
 		let graphics_queue_family = physical_device.graphics_queue_family;
 		let present_queue_family = physical_device.present_queue_family;
@@ -123,16 +148,32 @@ impl Context {
 		let features = vk::PhysicalDeviceFeatures::builder();
 		let device_extensions: Vec<*const c_char> = required_device_extensions.iter().map(|extension| extension.as_ptr()).collect();
 
-		let device_create_info = vk::DeviceCreateInfo::builder()
+		// Enable the optional Vulkan 1.2 features the physical device supports
+		let descriptor_indexing = physical_device.descriptor_indexing_supported;
+
+		let mut vulkan_1_2_features = vk::PhysicalDeviceVulkan12Features::builder()
+			.timeline_semaphore(physical_device.timeline_semaphores_supported)
+			.descriptor_binding_partially_bound(descriptor_indexing)
+			.descriptor_binding_variable_descriptor_count(descriptor_indexing)
+			.descriptor_binding_sampled_image_update_after_bind(descriptor_indexing)
+			.separate_depth_stencil_layouts(physical_device.separate_depth_stencil_layouts_supported)
+			.buffer_device_address(physical_device.buffer_device_address_supported);
+
+		let mut device_create_info = vk::DeviceCreateInfo::builder()
 			.queue_create_infos(&device_queue_create_infos)
 			.enabled_features(&features)
-			.enabled_layer_names(&layers)
 			.enabled_extension_names(&device_extensions);
+
+		if physical_device.vulkan_1_2_supported {
+			device_create_info = device_create_info.push_next(&mut vulkan_1_2_features);
+		}
 		
 		let logical_device = unsafe { instance.create_device(physical_device.handle, &device_create_info, None).unwrap() };
 		let graphics_queue = unsafe { logical_device.get_device_queue(graphics_queue_family, 0) };
 		let present_queue = unsafe { logical_device.get_device_queue(present_queue_family, 0) };
 
+		let memory_budget = Arc::new(MemoryBudget::new(&physical_device.memory_properties));
+
 		Self {
 			instance,
 			debug_utils,
@@ -144,9 +185,71 @@ impl Context {
 			},
 			logical_device,
 			graphics_queue,
-			present_queue
+			present_queue,
+			depth_format,
+			sampled_depth_format,
+			memory_budget,
+			queue_mutex: Mutex::new(()),
+			thread_command_pools: Mutex::new(vec![])
+		}
+	}
+
+	pub fn submit(&self, submit_infos: &[vk::SubmitInfo], fence: vk::Fence) -> VkResult<()> {
+		let _queue_lock = self.lock_queue();
+		unsafe { self.logical_device.queue_submit(self.graphics_queue, submit_infos, fence) }
+	}
+
+	pub fn wait_idle(&self) {
+		let _queue_lock = self.lock_queue();
+		unsafe { self.logical_device.queue_wait_idle(self.graphics_queue) }.unwrap();
+	}
+
+	// For one time commands such as uploads, waits for everything else submitted to the queue too
+	pub fn submit_and_wait(&self, submit_infos: &[vk::SubmitInfo]) {
+		let _queue_lock = self.lock_queue();
+
+		unsafe {
+			self.logical_device.queue_submit(self.graphics_queue, submit_infos, vk::Fence::null()).unwrap();
+			self.logical_device.queue_wait_idle(self.graphics_queue).unwrap();
 		}
 	}
+
+	// Device memory counted toward the budget until the returned allocation is dropped, which should be when the memory is freed
+	pub fn allocate_memory(&self, requirements: &vk::MemoryRequirements, properties: vk::MemoryPropertyFlags, category: MemoryCategory) -> (vk::DeviceMemory, Allocation) {
+		let memory_type_index = self.physical_device.find_memory_type_index(requirements.memory_type_bits, properties);
+
+		let allocate_info = vk::MemoryAllocateInfo::builder()
+			.allocation_size(requirements.size)
+			.memory_type_index(memory_type_index as u32);
+
+		let memory = unsafe { self.logical_device.allocate_memory(&allocate_info, None) }.unwrap();
+		let allocation = self.memory_budget.track(category, memory_type_index, requirements.size);
+		(memory, allocation)
+	}
+
+	// Held while using the graphics or present queue directly, such as to present
+	pub fn lock_queue(&self) -> MutexGuard<'_, ()> {
+		self.queue_mutex.lock().unwrap()
+	}
+
+	// The calling thread's command pool for one time commands, created the first time the thread asks for it. Command buffers
+	// from it must only be recorded and freed on the same thread.
+	pub fn thread_command_pool(&self) -> vk::CommandPool {
+		let thread_id = thread::current().id();
+		let mut thread_command_pools = self.thread_command_pools.lock().unwrap();
+
+		if let Some((_, command_pool)) = thread_command_pools.iter().find(|(id, _)| *id == thread_id) {
+			return *command_pool;
+		}
+
+		let create_info = vk::CommandPoolCreateInfo::builder()
+			.queue_family_index(self.physical_device.graphics_queue_family)
+			.flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER | vk::CommandPoolCreateFlags::TRANSIENT);
+
+		let command_pool = unsafe { self.logical_device.create_command_pool(&create_info, None) }.unwrap();
+		thread_command_pools.push((thread_id, command_pool));
+		command_pool
+	}
 	
 	unsafe extern "system" fn debug_message_callback(
 		_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
@@ -163,10 +266,27 @@ impl Context {
 impl Drop for Context {
 	fn drop(&mut self) {
 		unsafe {
+			for (_, command_pool) in self.thread_command_pools.get_mut().unwrap().drain(..) {
+				self.logical_device.destroy_command_pool(command_pool, None);
+			}
+
 			self.logical_device.destroy_device(None);
 			self.surface.extension.destroy_surface(self.surface.handle, None);
 			self.debug_utils.extension.destroy_debug_utils_messenger(self.debug_utils.messenger_handle, None);
 			self.instance.destroy_instance(None);
 		}
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_send_and_sync<T: Send + Sync>() {}
+
+	// Worker threads share the context by reference to create resources
+	#[test]
+	fn context_can_be_shared_between_threads() {
+		assert_send_and_sync::<Context>();
+	}
 }
\ No newline at end of file
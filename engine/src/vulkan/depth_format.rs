@@ -0,0 +1,77 @@
+use ash::vk;
+
+// In order of preference
+const DEPTH_FORMATS: [vk::Format; 3] = [vk::Format::D32_SFLOAT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D16_UNORM];
+const DEPTH_STENCIL_FORMATS: [vk::Format; 3] = [vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D16_UNORM_S8_UINT];
+
+// A depth image which is also sampled is simpler to use without a stencil aspect, D16_UNORM is always supported
+const SAMPLED_DEPTH_FORMATS: [vk::Format; 2] = [vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DepthFormat {
+	pub format: vk::Format,
+	pub has_stencil: bool
+}
+
+impl DepthFormat {
+	// Picks the first format in the preference list which supports the features, when stencil isn't required a format
+	// with a stencil aspect may still be picked if it's the only one supported
+	pub fn select(stencil_required: bool, is_supported: impl Fn(vk::Format) -> bool) -> Option<Self> {
+		let candidates: &[vk::Format] = if stencil_required { &DEPTH_STENCIL_FORMATS } else { &DEPTH_FORMATS };
+		Self::select_from(candidates, is_supported)
+	}
+
+	pub fn select_sampled(is_supported: impl Fn(vk::Format) -> bool) -> Option<Self> {
+		Self::select_from(&SAMPLED_DEPTH_FORMATS, is_supported)
+	}
+
+	fn select_from(candidates: &[vk::Format], is_supported: impl Fn(vk::Format) -> bool) -> Option<Self> {
+		candidates.iter()
+			.copied()
+			.find(|format| is_supported(*format))
+			.map(|format| Self {
+				format,
+				has_stencil: format != vk::Format::D32_SFLOAT && format != vk::Format::D16_UNORM
+			})
+	}
+
+	// Views used as depth stencil attachments must include every aspect of the format
+	pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+		if self.has_stencil {
+			vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+		}
+		else {
+			vk::ImageAspectFlags::DEPTH
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn select() {
+		let all = |_| true;
+		assert_eq!(DepthFormat::select(false, all), Some(DepthFormat { format: vk::Format::D32_SFLOAT, has_stencil: false }));
+		assert_eq!(DepthFormat::select(true, all), Some(DepthFormat { format: vk::Format::D24_UNORM_S8_UINT, has_stencil: true }));
+
+		let no_d32 = |format| format != vk::Format::D32_SFLOAT;
+		assert_eq!(DepthFormat::select(false, no_d32).unwrap().format, vk::Format::D24_UNORM_S8_UINT);
+		assert!(DepthFormat::select(false, no_d32).unwrap().has_stencil);
+
+		let only_d16 = |format| format == vk::Format::D16_UNORM;
+		assert_eq!(DepthFormat::select(false, only_d16).unwrap().format, vk::Format::D16_UNORM);
+		assert_eq!(DepthFormat::select(true, only_d16), None);
+		assert_eq!(DepthFormat::select_sampled(no_d32), Some(DepthFormat { format: vk::Format::D16_UNORM, has_stencil: false }));
+	}
+
+	#[test]
+	fn aspect_mask() {
+		let depth = DepthFormat { format: vk::Format::D32_SFLOAT, has_stencil: false };
+		let depth_stencil = DepthFormat { format: vk::Format::D24_UNORM_S8_UINT, has_stencil: true };
+
+		assert_eq!(depth.aspect_mask(), vk::ImageAspectFlags::DEPTH);
+		assert_eq!(depth_stencil.aspect_mask(), vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,250 @@
+use std::{fmt, sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}}};
+use ash::vk;
+
+// What device memory was allocated for, so the totals point at the system using it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemoryCategory {
+	StaticGeometry,
+	// Geometry cached for meshes and the vertices of text and trails
+	DynamicGeometry,
+	// Per frame, instance and light data
+	InstanceData,
+	Textures,
+	Fonts,
+	RenderTargets,
+	// Buffers which only live while their contents are uploaded
+	Staging,
+	Other
+}
+
+impl MemoryCategory {
+	pub const ALL: [Self; 8] = [
+		Self::StaticGeometry,
+		Self::DynamicGeometry,
+		Self::InstanceData,
+		Self::Textures,
+		Self::Fonts,
+		Self::RenderTargets,
+		Self::Staging,
+		Self::Other
+	];
+
+	fn index(self) -> usize {
+		self as usize
+	}
+}
+
+// A heap is reported once when it passes the warning fraction and again only after dropping below the reset fraction so a
+// heap hovering around the limit doesn't flood the log
+const WARNING_FRACTION: f64 = 0.9;
+const RESET_FRACTION: f64 = 0.8;
+
+struct HeapBudget {
+	size: u64,
+	device_local: bool,
+	used: AtomicU64,
+	warned: AtomicBool
+}
+
+// Counts the device memory allocated by the renderer by category and by heap, the heap sizes come from the physical device.
+// Shared by everything holding an allocation so it can be updated from worker threads.
+pub(crate) struct MemoryBudget {
+	heaps: Vec<HeapBudget>,
+	memory_type_heaps: Vec<usize>,
+	categories: [AtomicU64; MemoryCategory::ALL.len()]
+}
+
+impl MemoryBudget {
+	pub fn new(memory_properties: &vk::PhysicalDeviceMemoryProperties) -> Self {
+		let heaps = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize].iter()
+			.map(|heap| HeapBudget {
+				size: heap.size,
+				device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+				used: AtomicU64::new(0),
+				warned: AtomicBool::new(false)
+			})
+			.collect();
+
+		let memory_type_heaps = memory_properties.memory_types[..memory_properties.memory_type_count as usize].iter()
+			.map(|memory_type| memory_type.heap_index as usize)
+			.collect();
+
+		Self {
+			heaps,
+			memory_type_heaps,
+			categories: Default::default()
+		}
+	}
+
+	// Counts memory allocated from the memory type until the returned allocation is dropped
+	pub fn track(self: &Arc<Self>, category: MemoryCategory, memory_type_index: usize, size: u64) -> Allocation {
+		let heap_index = self.memory_type_heaps[memory_type_index];
+		let heap = &self.heaps[heap_index];
+		self.categories[category.index()].fetch_add(size, Ordering::Relaxed);
+		let used = heap.used.fetch_add(size, Ordering::Relaxed) + size;
+
+		if used as f64 > heap.size as f64 * WARNING_FRACTION && !heap.warned.swap(true, Ordering::Relaxed) {
+			println!(
+				"Memory heap {} is {:.0}% full, {} of {} MB used by {}",
+				heap_index,
+				used as f64 / heap.size as f64 * 100.0,
+				megabytes(used),
+				megabytes(heap.size),
+				self.usage()
+			);
+		}
+
+		Allocation {
+			budget: self.clone(),
+			category,
+			heap_index,
+			size
+		}
+	}
+
+	fn release(&self, category: MemoryCategory, heap_index: usize, size: u64) {
+		let heap = &self.heaps[heap_index];
+		self.categories[category.index()].fetch_sub(size, Ordering::Relaxed);
+		let used = heap.used.fetch_sub(size, Ordering::Relaxed) - size;
+
+		if (used as f64) < heap.size as f64 * RESET_FRACTION {
+			heap.warned.store(false, Ordering::Relaxed);
+		}
+	}
+
+	pub fn usage(&self) -> MemoryUsage {
+		MemoryUsage {
+			categories: MemoryCategory::ALL.map(|category| (category, self.categories[category.index()].load(Ordering::Relaxed))),
+			heaps: self.heaps.iter()
+				.map(|heap| HeapUsage {
+					size: heap.size,
+					used: heap.used.load(Ordering::Relaxed),
+					device_local: heap.device_local
+				})
+				.collect()
+		}
+	}
+}
+
+// Held next to the device memory it counts and dropped when the memory is freed
+pub(crate) struct Allocation {
+	budget: Arc<MemoryBudget>,
+	category: MemoryCategory,
+	heap_index: usize,
+	size: u64
+}
+
+impl Drop for Allocation {
+	fn drop(&mut self) {
+		self.budget.release(self.category, self.heap_index, self.size);
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HeapUsage {
+	pub size: u64,
+	pub used: u64,
+	pub device_local: bool
+}
+
+// How much device memory the renderer has allocated, in bytes
+#[derive(Clone, Debug)]
+pub struct MemoryUsage {
+	pub categories: [(MemoryCategory, u64); MemoryCategory::ALL.len()],
+	pub heaps: Vec<HeapUsage>
+}
+
+impl MemoryUsage {
+	pub fn category(&self, category: MemoryCategory) -> u64 {
+		self.categories[category.index()].1
+	}
+
+	pub fn device_local(&self) -> u64 {
+		self.heaps.iter().filter(|heap| heap.device_local).map(|heap| heap.used).sum()
+	}
+
+	// Heaps the host can map, on integrated GPUs these are device local too
+	pub fn host(&self) -> u64 {
+		self.heaps.iter().filter(|heap| !heap.device_local).map(|heap| heap.used).sum()
+	}
+}
+
+impl fmt::Display for MemoryUsage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (category, size) in self.categories.iter().filter(|(_, size)| *size > 0) {
+			write!(f, "{:?} {} MB ", category, megabytes(*size))?;
+		}
+
+		write!(f, "device local {} MB host {} MB", megabytes(self.device_local()), megabytes(self.host()))
+	}
+}
+
+fn megabytes(size: u64) -> String {
+	format!("{:.1}", size as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A discrete GPU with one device local heap and one host heap
+	fn budget() -> Arc<MemoryBudget> {
+		let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+			memory_type_count: 2,
+			memory_heap_count: 2,
+			..Default::default()
+		};
+
+		memory_properties.memory_heaps[0] = vk::MemoryHeap { size: 1000, flags: vk::MemoryHeapFlags::DEVICE_LOCAL };
+		memory_properties.memory_heaps[1] = vk::MemoryHeap { size: 4000, flags: vk::MemoryHeapFlags::empty() };
+		memory_properties.memory_types[0] = vk::MemoryType { property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL, heap_index: 0 };
+		memory_properties.memory_types[1] = vk::MemoryType { property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE, heap_index: 1 };
+		Arc::new(MemoryBudget::new(&memory_properties))
+	}
+
+	#[test]
+	fn counts_allocations_until_dropped() {
+		let budget = budget();
+		let geometry = budget.track(MemoryCategory::StaticGeometry, 0, 300);
+		let font = budget.track(MemoryCategory::Fonts, 0, 200);
+		let staging = budget.track(MemoryCategory::Staging, 1, 500);
+
+		let usage = budget.usage();
+		assert_eq!(usage.category(MemoryCategory::StaticGeometry), 300);
+		assert_eq!(usage.category(MemoryCategory::Fonts), 200);
+		assert_eq!(usage.device_local(), 500);
+		assert_eq!(usage.host(), 500);
+
+		// Submitting fonts again has to free the old ones or the total keeps growing
+		drop(font);
+		let _font = budget.track(MemoryCategory::Fonts, 0, 200);
+		assert_eq!(budget.usage().category(MemoryCategory::Fonts), 200);
+
+		drop(geometry);
+		drop(staging);
+		let usage = budget.usage();
+		assert_eq!(usage.category(MemoryCategory::StaticGeometry), 0);
+		assert_eq!(usage.device_local(), 200);
+		assert_eq!(usage.host(), 0);
+	}
+
+	#[test]
+	fn warns_once_near_the_heap_size() {
+		let budget = budget();
+		let warned = || budget.heaps[0].warned.load(Ordering::Relaxed);
+
+		let first = budget.track(MemoryCategory::Textures, 0, 850);
+		assert!(!warned());
+
+		let second = budget.track(MemoryCategory::Textures, 0, 100);
+		assert!(warned());
+
+		// Still above the reset fraction
+		drop(second);
+		assert!(warned());
+
+		drop(first);
+		assert!(!warned());
+		assert!(!budget.heaps[1].warned.load(Ordering::Relaxed));
+	}
+}
\ No newline at end of file
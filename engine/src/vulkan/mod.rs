@@ -4,5 +4,11 @@ pub(crate) use context::Context;
 pub(crate) mod physical_device;
 pub(crate) use physical_device::PhysicalDevice;
 
+pub(crate) mod depth_format;
+pub(crate) use depth_format::DepthFormat;
+
 pub(crate) mod buffer;
-pub(crate) use buffer::Buffer;
\ No newline at end of file
+pub(crate) use buffer::Buffer;
+
+pub(crate) mod memory_budget;
+pub(crate) use memory_budget::{Allocation, MemoryBudget, MemoryCategory};
\ No newline at end of file
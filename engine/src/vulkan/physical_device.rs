@@ -1,4 +1,4 @@
-use ash::{vk, version::InstanceV1_0, extensions::khr};
+use ash::{vk, extensions::khr};
 use std::ffi::{CString, CStr};
 
 pub struct PhysicalDevice {
@@ -7,7 +7,15 @@ pub struct PhysicalDevice {
 	pub present_queue_family: u32,
 	pub memory_properties: vk::PhysicalDeviceMemoryProperties,
 	pub min_uniform_buffer_offset_alignment: u64,
-	pub min_storage_buffer_offset_alignment: u64
+	pub min_storage_buffer_offset_alignment: u64,
+	pub timestamp_period: f32,
+	pub timestamps_supported: bool,
+	pub vulkan_1_2_supported: bool,
+	pub timeline_semaphores_supported: bool,
+	pub descriptor_indexing_supported: bool,
+	pub max_update_after_bind_sampled_images: u32,
+	pub separate_depth_stencil_layouts_supported: bool,
+	pub buffer_device_address_supported: bool
 }
 
 impl PhysicalDevice {
@@ -29,7 +37,8 @@ impl PhysicalDevice {
 			let mut graphics_queue_family = None;
 			let mut present_queue_family = None;
 			for (i, property) in queue_family_properties.iter().enumerate() {
-				if property.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
+				// Particles are simulated with compute shaders on the graphics queue
+				if property.queue_flags.contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE) {
 					graphics_queue_family = Some(i);
 				}
 
@@ -60,13 +69,49 @@ impl PhysicalDevice {
 				continue;
 			}
 
+			// The Vulkan 1.2 features are core but still optional so each one is only used if it's supported
+			let vulkan_1_2_supported = properties.api_version >= vk::make_api_version(0, 1, 2, 0);
+			let mut vulkan_1_2_features = vk::PhysicalDeviceVulkan12Features::default();
+			let mut features2 = vk::PhysicalDeviceFeatures2::builder().push_next(&mut vulkan_1_2_features);
+
+			if vulkan_1_2_supported {
+				unsafe { instance.get_physical_device_features2(device, &mut features2) };
+			}
+
+			let timeline_semaphores_supported = vulkan_1_2_features.timeline_semaphore == vk::TRUE;
+
+			let descriptor_indexing_supported = vulkan_1_2_features.descriptor_binding_partially_bound == vk::TRUE
+				&& vulkan_1_2_features.descriptor_binding_variable_descriptor_count == vk::TRUE
+				&& vulkan_1_2_features.descriptor_binding_sampled_image_update_after_bind == vk::TRUE;
+
+			let separate_depth_stencil_layouts_supported = vulkan_1_2_features.separate_depth_stencil_layouts == vk::TRUE;
+			let buffer_device_address_supported = vulkan_1_2_features.buffer_device_address == vk::TRUE;
+
+			let mut descriptor_indexing_properties = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+			let mut properties2 = vk::PhysicalDeviceProperties2::builder().push_next(&mut descriptor_indexing_properties);
+
+			if vulkan_1_2_supported {
+				unsafe { instance.get_physical_device_properties2(device, &mut properties2) };
+			}
+
+			let max_update_after_bind_sampled_images = descriptor_indexing_properties.max_per_stage_descriptor_update_after_bind_sampled_images
+				.min(descriptor_indexing_properties.max_descriptor_set_update_after_bind_sampled_images);
+
 			return Self {
 				handle: device,
 				graphics_queue_family: graphics_queue_family.unwrap() as u32,
 				present_queue_family: present_queue_family.unwrap() as u32,
 				memory_properties: unsafe { instance.get_physical_device_memory_properties(device) },
 				min_uniform_buffer_offset_alignment: properties.limits.min_uniform_buffer_offset_alignment,
-				min_storage_buffer_offset_alignment: properties.limits.min_storage_buffer_offset_alignment
+				min_storage_buffer_offset_alignment: properties.limits.min_storage_buffer_offset_alignment,
+				timestamp_period: properties.limits.timestamp_period,
+				timestamps_supported: queue_family_properties[graphics_queue_family.unwrap()].timestamp_valid_bits > 0,
+				vulkan_1_2_supported,
+				timeline_semaphores_supported,
+				descriptor_indexing_supported,
+				max_update_after_bind_sampled_images,
+				separate_depth_stencil_layouts_supported,
+				buffer_device_address_supported
 			}
 		}
 
@@ -74,10 +119,13 @@ impl PhysicalDevice {
 	}
 
 	pub fn find_memory_type_index(&self, r#type: u32, properties: vk::MemoryPropertyFlags) -> usize {
+		self.try_find_memory_type_index(r#type, properties).expect("Could not find suitable memory type")
+	}
+
+	pub fn try_find_memory_type_index(&self, r#type: u32, properties: vk::MemoryPropertyFlags) -> Option<usize> {
 		let available_types = self.memory_properties.memory_types;
 
 		(0..available_types.len())
 			.find(|&i| r#type & (1 << i) != 0 && available_types[i].property_flags.contains(properties))
-			.expect("Could not find suitable memory type")
 	}
 }
\ No newline at end of file
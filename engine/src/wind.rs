@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+use crate::math::Vector3;
+
+// The global wind which sways foliage meshes. The vertex shader displaces each vertex by the masks in its color, red is how
+// much it bends along with its branch, green how much it flutters along its normal and blue offsets its phase so leaves don't
+// all move together. Nothing is done on the CPU besides copying these into the frame data.
+pub struct Wind {
+	// The way the wind blows, doesn't need to be normalized
+	pub direction: Vector3,
+	// How far a vertex with a full branch mask leans at the peak of a gust
+	pub strength: f32,
+	// Gusts per second
+	pub frequency: f32,
+	// The distance between gusts as they travel across the world along the direction
+	pub wavelength: f32,
+	// How far a vertex with a full edge mask moves along its normal
+	pub flutter_strength: f32,
+	pub flutter_frequency: f32,
+	time: f32
+}
+
+impl Wind {
+	pub fn new() -> Self {
+		Self {
+			direction: Vector3::new(1.0, 0.0, 0.0),
+			strength: 0.1,
+			frequency: 0.25,
+			wavelength: 20.0,
+			flutter_strength: 0.02,
+			flutter_frequency: 2.0,
+			time: 0.0
+		}
+	}
+
+	// Advances the animation, a calm wind can be stopped by not updating it
+	pub fn update(&mut self, delta: f32) {
+		self.time += delta;
+	}
+
+	pub fn time(&self) -> f32 {
+		self.time
+	}
+
+	pub fn normalized_direction(&self) -> Vector3 {
+		let mut direction = self.direction;
+		direction.normalize();
+		direction
+	}
+
+	// How far the vertex at the world space position and normal is moved, the same as the foliage vertex shader so the CPU can
+	// match it for things like picking or placing particles on leaves
+	pub fn displacement(&self, position: &Vector3, normal: &Vector3, color: [f32; 4]) -> Vector3 {
+		let direction = self.normalized_direction();
+		let phase = color[2] * 2.0 * PI;
+
+		// Gusts travel along the direction, the vertex leans between its rest position and the full strength
+		let gust_phase = (self.time * self.frequency - position.dot(&direction) / self.wavelength.max(0.0001)) * 2.0 * PI;
+		let bend = color[0] * self.strength * (0.5 + 0.5 * (gust_phase + phase).sin());
+
+		let flutter_phase = self.time * self.flutter_frequency * 2.0 * PI + position.x + position.y + position.z;
+		let flutter = color[1] * self.flutter_strength * (flutter_phase + phase).sin();
+
+		direction * bend + *normal * flutter
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn displacement_follows_masks() {
+		let mut wind = Wind::new();
+		wind.direction = Vector3::new(0.0, 0.0, 3.0);
+		let position = Vector3::new(1.0, 2.0, 3.0);
+		let normal = Vector3::new(0.0, 1.0, 0.0);
+
+		// Unmasked vertices such as a trunk's base never move
+		for _ in 0..10 {
+			wind.update(0.37);
+			assert_eq!(wind.displacement(&position, &normal, [0.0, 0.0, 0.7, 1.0]), Vector3::new(0.0, 0.0, 0.0));
+		}
+
+		// Branches lean along the wind and never against it
+		for _ in 0..10 {
+			wind.update(0.37);
+			let displacement = wind.displacement(&position, &normal, [1.0, 0.0, 0.0, 1.0]);
+			assert_eq!((displacement.x, displacement.y), (0.0, 0.0));
+			assert!(displacement.z >= 0.0 && displacement.z <= wind.strength + 1e-6);
+		}
+
+		// Edges flutter along their normals both ways
+		let flutters: Vec<f32> = (0..8)
+			.map(|_| {
+				wind.update(0.1);
+				let displacement = wind.displacement(&position, &normal, [0.0, 1.0, 0.0, 1.0]);
+				assert_eq!((displacement.x, displacement.z), (0.0, 0.0));
+				displacement.y
+			})
+			.collect();
+
+		assert!(flutters.iter().any(|flutter| *flutter > 0.0) && flutters.iter().any(|flutter| *flutter < 0.0));
+		assert!(flutters.iter().all(|flutter| flutter.abs() <= wind.flutter_strength + 1e-6));
+	}
+}
\ No newline at end of file
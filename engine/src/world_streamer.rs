@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use crate::{AssetStreamer, Geometry3D, GeometryData, math::Vector3, pool::{Handle, Pool}};
+
+// A cell's x and z, the cell covers from them times the cell size up to the next cell
+pub type CellCoordinates = (i32, i32);
+
+pub type CellGeometryLoader = Box<dyn FnOnce() -> Result<GeometryData, String> + Send>;
+
+struct StreamedCell {
+	handles: Vec<Handle>,
+	loading_count: usize
+}
+
+// The cells which finished loading and the ones which were unloaded during an update, along with their geometries
+#[derive(Default, Debug)]
+pub struct WorldStreamerChanges {
+	pub loaded: Vec<(CellCoordinates, Vec<Handle>)>,
+	pub unloaded: Vec<(CellCoordinates, Vec<Handle>)>
+}
+
+impl WorldStreamerChanges {
+	pub fn is_empty(&self) -> bool {
+		self.loaded.is_empty() && self.unloaded.is_empty()
+	}
+
+	// Pass these to the render system's update static geometries so only the cells which changed are uploaded
+	pub fn loaded_handles(&self) -> Vec<Handle> {
+		self.loaded.iter().flat_map(|(_, handles)| handles.iter().copied()).collect()
+	}
+
+	pub fn unloaded_handles(&self) -> Vec<Handle> {
+		self.unloaded.iter().flat_map(|(_, handles)| handles.iter().copied()).collect()
+	}
+}
+
+// Divides the world into square cells on the xz plane and streams them in and out around the camera. A cell starts loading
+// once the camera is within the load radius of it and is unloaded once the camera is further than the unload radius, the gap
+// between them stops cells on the border from loading and unloading over and over. The cell loader is called on the main
+// thread with the cell to load and returns a loader for each of its geometries, such as ones reading the cell's file through a
+// Vfs, they're run by the asset streamer with the nearest cells first.
+pub struct WorldStreamer {
+	pub cell_size: f32,
+	pub load_radius: f32,
+	pub unload_radius: f32,
+	cell_loader: Box<dyn FnMut(CellCoordinates) -> Vec<CellGeometryLoader>>,
+	cells: HashMap<CellCoordinates, StreamedCell>
+}
+
+impl WorldStreamer {
+	pub fn new(cell_size: f32, load_radius: f32, unload_radius: f32, cell_loader: impl FnMut(CellCoordinates) -> Vec<CellGeometryLoader> + 'static) -> Self {
+		assert!(cell_size > 0.0, "World streamer cells must have a size");
+		assert!(unload_radius >= load_radius, "The unload radius of a world streamer cannot be less than its load radius");
+
+		Self {
+			cell_size,
+			load_radius,
+			unload_radius,
+			cell_loader: Box::new(cell_loader),
+			cells: HashMap::new()
+		}
+	}
+
+	// The cells which are loading or loaded
+	pub fn cells(&self) -> impl Iterator<Item = &CellCoordinates> {
+		self.cells.keys()
+	}
+
+	pub fn is_cell_loaded(&self, cell: CellCoordinates) -> bool {
+		self.cells.get(&cell).is_some_and(|cell| cell.loading_count == 0)
+	}
+
+	// Call it once a frame with the handles the asset streamer's update returned. Unloaded cells have their geometries
+	// removed from the pool so anything using them has to be removed too.
+	pub fn update(&mut self, camera_position: &Vector3, geometries: &mut Pool<Geometry3D>, asset_streamer: &mut AssetStreamer, finished: &[Handle]) -> WorldStreamerChanges {
+		let mut changes = WorldStreamerChanges::default();
+
+		for handle in finished {
+			if let Some((cell, streamed_cell)) = self.cells.iter_mut().find(|(_, streamed_cell)| streamed_cell.handles.contains(handle)) {
+				streamed_cell.loading_count -= 1;
+
+				if streamed_cell.loading_count == 0 {
+					changes.loaded.push((*cell, streamed_cell.handles.clone()));
+				}
+			}
+		}
+
+		let unloaded: Vec<CellCoordinates> = self.cells.keys()
+			.filter(|cell| cell_distance(**cell, self.cell_size, camera_position) > self.unload_radius)
+			.copied()
+			.collect();
+
+		for cell in unloaded {
+			let streamed_cell = self.cells.remove(&cell).unwrap();
+
+			for handle in &streamed_cell.handles {
+				asset_streamer.cancel(*handle);
+				geometries.remove(*handle);
+			}
+
+			// It may have finished loading in this same update
+			changes.loaded.retain(|(loaded_cell, _)| *loaded_cell != cell);
+			changes.unloaded.push((cell, streamed_cell.handles));
+		}
+
+		for (cell, streamed_cell) in self.cells.iter().filter(|(_, streamed_cell)| streamed_cell.loading_count > 0) {
+			let priority = cell_priority(*cell, self.cell_size, camera_position);
+
+			for handle in &streamed_cell.handles {
+				asset_streamer.set_priority(*handle, priority);
+			}
+		}
+
+		for cell in cells_in_range(self.cell_size, camera_position, self.load_radius) {
+			if self.cells.contains_key(&cell) {
+				continue;
+			}
+
+			let priority = cell_priority(cell, self.cell_size, camera_position);
+			let handles: Vec<Handle> = (self.cell_loader)(cell).into_iter()
+				.map(|load| asset_streamer.request_geometry(geometries, priority, load))
+				.collect();
+
+			// An empty cell is loaded straight away
+			if handles.is_empty() {
+				changes.loaded.push((cell, vec![]));
+			}
+
+			self.cells.insert(cell, StreamedCell { loading_count: handles.len(), handles });
+		}
+
+		changes
+	}
+
+	// Removes every cell, such as when changing levels
+	pub fn clear(&mut self, geometries: &mut Pool<Geometry3D>, asset_streamer: &mut AssetStreamer) -> WorldStreamerChanges {
+		let mut changes = WorldStreamerChanges::default();
+
+		for (cell, streamed_cell) in self.cells.drain() {
+			for handle in &streamed_cell.handles {
+				asset_streamer.cancel(*handle);
+				geometries.remove(*handle);
+			}
+
+			changes.unloaded.push((cell, streamed_cell.handles));
+		}
+
+		changes
+	}
+}
+
+// How far the position is from the nearest point of the cell on the xz plane, 0 when it's inside
+fn cell_distance(cell: CellCoordinates, cell_size: f32, position: &Vector3) -> f32 {
+	let min_x = cell.0 as f32 * cell_size;
+	let min_z = cell.1 as f32 * cell_size;
+	let x = (min_x - position.x).max(position.x - min_x - cell_size).max(0.0);
+	let z = (min_z - position.z).max(position.z - min_z - cell_size).max(0.0);
+	(x * x + z * z).sqrt()
+}
+
+// Nearer cells get a higher priority
+fn cell_priority(cell: CellCoordinates, cell_size: f32, position: &Vector3) -> i32 {
+	-(cell_distance(cell, cell_size, position) as i32)
+}
+
+fn cells_in_range(cell_size: f32, position: &Vector3, radius: f32) -> Vec<CellCoordinates> {
+	let min_x = ((position.x - radius) / cell_size).floor() as i32;
+	let max_x = ((position.x + radius) / cell_size).floor() as i32;
+	let min_z = ((position.z - radius) / cell_size).floor() as i32;
+	let max_z = ((position.z + radius) / cell_size).floor() as i32;
+
+	(min_x..=max_x)
+		.flat_map(|x| (min_z..=max_z).map(move |z| (x, z)))
+		.filter(|cell| cell_distance(*cell, cell_size, position) <= radius)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{thread, time::{Duration, Instant}};
+	use crate::geometry3d::Topology;
+
+	#[test]
+	fn selects_cells_within_radius() {
+		let mut cells = cells_in_range(10.0, &Vector3::new(5.0, 100.0, 5.0), 4.0);
+		assert_eq!(cells, vec![(0, 0)]);
+
+		cells = cells_in_range(10.0, &Vector3::new(9.0, 0.0, 9.0), 2.0);
+		cells.sort();
+		assert_eq!(cells, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+		// The diagonal cell's corner is further than the radius
+		cells = cells_in_range(10.0, &Vector3::new(-2.0, 0.0, -2.0), 2.5);
+		cells.sort();
+		assert_eq!(cells, vec![(-1, -1), (-1, 0), (0, -1)]);
+	}
+
+	#[test]
+	fn streams_cells_with_hysteresis() {
+		let mut asset_streamer = AssetStreamer::new(1);
+		let mut geometries = Pool::new();
+		let mut world_streamer = WorldStreamer::new(10.0, 1.0, 8.0, |cell: CellCoordinates| {
+			// Only the cells along the x axis have anything in them
+			if cell.1 != 0 {
+				return vec![];
+			}
+
+			let loader: CellGeometryLoader = Box::new(|| Ok(GeometryData::new(vec![0, 1, 2], vec![0.0; 18], Topology::Triangle)));
+			vec![loader]
+		});
+
+		let mut update = |position: Vector3, geometries: &mut Pool<Geometry3D>| {
+			let start = Instant::now();
+			let mut changes = world_streamer.update(&position, geometries, &mut asset_streamer, &[]);
+
+			while asset_streamer.is_loading() {
+				assert!(start.elapsed() < Duration::from_secs(10), "Loading took too long");
+				thread::sleep(Duration::from_millis(1));
+				let finished = asset_streamer.update(geometries);
+				let more_changes = world_streamer.update(&position, geometries, &mut asset_streamer, &finished);
+				changes.loaded.extend(more_changes.loaded);
+				changes.unloaded.extend(more_changes.unloaded);
+			}
+
+			changes.loaded.sort();
+			changes.unloaded.sort();
+			(changes, world_streamer.cells().copied().collect::<Vec<_>>())
+		};
+
+		let (changes, cells) = update(Vector3::new(5.0, 0.0, 5.0), &mut geometries);
+		assert_eq!(changes.loaded.len(), 1);
+		assert_eq!(changes.loaded[0].0, (0, 0));
+		assert_eq!(geometries.borrow(changes.loaded[0].1[0]).indices(), &[0, 1, 2]);
+		assert_eq!(cells, vec![(0, 0)]);
+
+		// Close to the next cell it loads, the first stays loaded until the camera is past the unload radius
+		let (changes, mut cells) = update(Vector3::new(11.0, 0.0, 5.0), &mut geometries);
+		cells.sort();
+		assert_eq!(changes.loaded.iter().map(|(cell, _)| *cell).collect::<Vec<_>>(), vec![(1, 0)]);
+		assert!(changes.unloaded.is_empty());
+		assert_eq!(cells, vec![(0, 0), (1, 0)]);
+
+		let first_handle = changes.loaded[0].1[0];
+		let (changes, mut cells) = update(Vector3::new(18.5, 0.0, 5.0), &mut geometries);
+		cells.sort();
+		assert_eq!(changes.unloaded.len(), 1);
+		assert_eq!(changes.unloaded[0].0, (0, 0));
+		assert!(!geometries.valid_handle(changes.unloaded[0].1[0]));
+		assert!(geometries.valid_handle(first_handle));
+		assert_eq!(cells, vec![(1, 0)]);
+
+		// Empty cells are loaded without anything to stream
+		let (changes, _) = update(Vector3::new(15.0, 0.0, 9.0), &mut geometries);
+		assert_eq!(changes.loaded, vec![((1, 1), vec![])]);
+	}
+}
\ No newline at end of file
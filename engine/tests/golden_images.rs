@@ -0,0 +1,294 @@
+// Renders reference scenes to a hidden window and compares them against the golden images in engine/tests/golden. Rendering
+// needs a Vulkan device and a display so these are ignored by default, run them with `cargo test -p engine -- --ignored`.
+// A missing golden image is written instead of compared against, set UPDATE_GOLDEN_IMAGES to rewrite all of them after an
+// intended change. The images are binary PPM files so they can be read and written without any dependencies.
+
+use std::{env, fs, path::{Path, PathBuf}};
+use engine::{
+	Camera,
+	EntityManager,
+	Font,
+	Geometry3D,
+	Texture,
+	component::{AnimatedSprite, BlobShadow, ComponentList, InputField, MultiComponentList, Light, light::{AmbientLight, Falloff, PointLight}, Mesh, Text, TextComponentList, Tilemap, Trail, Transform2D, Transform2DComponentList, Transform3D, Transform3DComponentList, mesh::Material},
+	glfw,
+	math::{Color, Vector3, color},
+	pool::Pool,
+	system::{FrameCapture, RenderScene, RenderSystem}
+};
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+
+// A pixel counts as different if any channel differs by more than this
+const CHANNEL_TOLERANCE: u8 = 8;
+
+// An image matches if at most this fraction of its pixels are different
+const PIXEL_TOLERANCE: f32 = 0.001;
+
+// Sets up a scene, renders it and returns the capture of the last frame
+type SceneFn = fn(&mut RenderSystem) -> FrameCapture;
+
+struct Scene {
+	camera: Camera,
+	entity_manager: EntityManager,
+	geometries: Pool<Geometry3D>,
+	fonts: Pool<Font>,
+	light_components: ComponentList<Light>,
+	mesh_components: MultiComponentList<Mesh>,
+	transform3d_components: Transform3DComponentList,
+	trail_components: ComponentList<Trail>,
+	blob_shadow_components: ComponentList<BlobShadow>,
+	text_components: TextComponentList,
+	transform2d_components: Transform2DComponentList,
+	textures: Pool<Texture>,
+	animated_sprite_components: ComponentList<AnimatedSprite>,
+	tilemap_components: ComponentList<Tilemap>,
+	input_field_components: ComponentList<InputField>
+}
+
+impl Scene {
+	fn new() -> Self {
+		let mut camera = Camera::new(WIDTH as f32 / HEIGHT as f32, 75.0, 0.1, 50.0);
+		camera.transform.position.set(0.0, 2.0, -6.0);
+		camera.transform.rotate_x(-0.3);
+		camera.update();
+
+		Self {
+			camera,
+			entity_manager: EntityManager::new(),
+			geometries: Pool::new(),
+			fonts: Pool::new(),
+			light_components: ComponentList::new(),
+			mesh_components: MultiComponentList::new(),
+			transform3d_components: Transform3DComponentList::new(),
+			trail_components: ComponentList::new(),
+			blob_shadow_components: ComponentList::new(),
+			text_components: TextComponentList::new(),
+			transform2d_components: Transform2DComponentList::new(),
+			textures: Pool::new(),
+			animated_sprite_components: ComponentList::new(),
+			tilemap_components: ComponentList::new(),
+			input_field_components: ComponentList::new()
+		}
+	}
+
+	fn add_mesh(&mut self, geometry: Geometry3D, material: Material, position: Vector3) {
+		let entity = self.entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position = position;
+		transform.rotate_y(0.6);
+		self.transform3d_components.add(&mut self.entity_manager, entity, transform);
+		let geometry_handle = self.geometries.add(geometry);
+		let index = self.mesh_components.add(Mesh::new(geometry_handle, material));
+		self.mesh_components.assign(&mut self.entity_manager, entity, index);
+	}
+
+	fn add_light(&mut self, light: Light, position: Vector3) {
+		let entity = self.entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position = position;
+		self.transform3d_components.add(&mut self.entity_manager, entity, transform);
+		self.light_components.add(&mut self.entity_manager, entity, light);
+	}
+
+	fn add_text(&mut self, render_system: &mut RenderSystem, size: u32, string: &str, x: f32, y: f32) {
+		let font_handle = self.fonts.add(Font::new(render_system.vfs(), "game/res/roboto.ttf", size));
+		render_system.submit_fonts(&mut self.fonts).unwrap();
+
+		let entity = self.entity_manager.create();
+		self.text_components.add(&mut self.entity_manager, entity, Text::new(font_handle, String::from(string)));
+		let mut transform = Transform2D::new();
+		transform.position.set(x, y);
+		self.transform2d_components.add(&mut self.entity_manager, entity, transform);
+	}
+
+	// Renders enough frames for every in flight frame to have uploaded the scene then captures the next one
+	fn capture(&mut self, render_system: &mut RenderSystem) -> FrameCapture {
+		self.text_components.generate_dirties(&self.fonts);
+
+		for (_, tilemap) in self.tilemap_components.iter_mut() {
+			tilemap.generate_dirty_chunks();
+		}
+
+		for frame in 0..4 {
+			if frame == 3 {
+				render_system.request_frame_capture();
+			}
+
+			let surface_changed = render_system.render(&RenderScene {
+				camera: &self.camera,
+				light_components: &self.light_components,
+				geometries: &self.geometries,
+				mesh_components: &self.mesh_components,
+				transform3d_components: &self.transform3d_components,
+				trail_components: &self.trail_components,
+				blob_shadow_components: &self.blob_shadow_components,
+				fonts: &self.fonts,
+				text_components: &self.text_components,
+				transform2d_components: &self.transform2d_components,
+				textures: &self.textures,
+				animated_sprite_components: &self.animated_sprite_components,
+				tilemap_components: &self.tilemap_components,
+				input_field_components: &self.input_field_components
+			});
+
+			assert!(!surface_changed, "The hidden window's surface changed while rendering");
+		}
+
+		render_system.take_frame_capture().unwrap()
+	}
+}
+
+fn materials_scene(render_system: &mut RenderSystem) -> FrameCapture {
+	let mut scene = Scene::new();
+	let box3 = Geometry3D::create_box();
+	scene.add_mesh(Geometry3D::create_box_helper(box3.bounding_box()), Material::Line, Vector3::new(-3.0, 0.0, 0.0));
+	scene.add_mesh(Geometry3D::create_box(), Material::Basic, Vector3::new(-1.0, 0.0, 0.0));
+	scene.add_mesh(Geometry3D::create_box(), Material::Normal, Vector3::new(1.0, 0.0, 0.0));
+	scene.add_mesh(box3, Material::Lambert, Vector3::new(3.0, 0.0, 0.0));
+
+	scene.add_light(Light::AmbientLight(AmbientLight { color: color::WHITE, intensity: 0.2 }), Vector3::from_scalar(0.0));
+
+	let mut point_light = PointLight::new(Color::rgb(1.0, 0.9, 0.8), 1.0);
+	point_light.range = 10.0;
+	point_light.falloff = Falloff::Linear;
+	scene.add_light(Light::PointLight(point_light), Vector3::new(2.0, 3.0, -2.0));
+
+	scene.capture(render_system)
+}
+
+fn text_scene(render_system: &mut RenderSystem) -> FrameCapture {
+	let mut scene = Scene::new();
+	scene.add_text(render_system, 14, "The quick brown fox jumps over the lazy dog", 10.0, 20.0);
+	scene.add_text(render_system, 32, "0123456789 !?&%", 10.0, 80.0);
+	scene.capture(render_system)
+}
+
+fn golden_image_path(name: &str) -> PathBuf {
+	Path::new("engine/tests/golden").join(format!("{}.ppm", name))
+}
+
+fn write_ppm(path: &Path, capture: &FrameCapture) {
+	let mut data = format!("P6\n{} {}\n255\n", capture.width, capture.height).into_bytes();
+
+	for pixel in capture.pixels.chunks_exact(4) {
+		data.extend_from_slice(&pixel[..3]);
+	}
+
+	fs::create_dir_all(path.parent().unwrap()).unwrap();
+	fs::write(path, data).unwrap();
+}
+
+// Only reads the files written by write_ppm, the header has no comments and the maximum value is 255
+fn read_ppm(path: &Path) -> FrameCapture {
+	let data = fs::read(path).unwrap();
+	let mut header_fields = Vec::new();
+	let mut position = 0;
+
+	while header_fields.len() < 4 {
+		let start = position;
+
+		while !data[position].is_ascii_whitespace() {
+			position += 1;
+		}
+
+		header_fields.push(String::from_utf8(data[start..position].to_vec()).unwrap());
+		position += 1;
+	}
+
+	assert_eq!(header_fields[0], "P6", "Golden image {} is not a binary PPM file", path.display());
+	let width: u32 = header_fields[1].parse().unwrap();
+	let height: u32 = header_fields[2].parse().unwrap();
+
+	let pixels = data[position..].chunks_exact(3)
+		.flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+		.collect();
+
+	FrameCapture {
+		width,
+		height,
+		pixels
+	}
+}
+
+// Returns the fraction of pixels which differ by more than the channel tolerance
+fn difference(a: &FrameCapture, b: &FrameCapture) -> f32 {
+	assert_eq!((a.width, a.height), (b.width, b.height), "Cannot compare images of different sizes");
+
+	let different_pixel_count = a.pixels.chunks_exact(4).zip(b.pixels.chunks_exact(4))
+		.filter(|(a, b)| a.iter().zip(b.iter()).take(3).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+		.count();
+
+	different_pixel_count as f32 / (a.width * a.height) as f32
+}
+
+fn check_golden_image(name: &str, capture: &FrameCapture) -> Result<(), String> {
+	let path = golden_image_path(name);
+
+	if env::var_os("UPDATE_GOLDEN_IMAGES").is_some() || !path.exists() {
+		write_ppm(&path, capture);
+		println!("Wrote golden image {}", path.display());
+		return Ok(());
+	}
+
+	let difference = difference(&read_ppm(&path), capture);
+
+	if difference <= PIXEL_TOLERANCE {
+		return Ok(());
+	}
+
+	// Keep what was rendered so it can be looked at next to the golden image
+	let failure_path = Path::new("target/golden_failures").join(format!("{}.ppm", name));
+	write_ppm(&failure_path, capture);
+	Err(format!("{}: {:.2}% of pixels differ from the golden image, the render was written to {}", name, difference * 100.0, failure_path.display()))
+}
+
+#[test]
+#[ignore]
+fn golden_images() {
+	// Shaders and resources are loaded relative to the workspace root
+	env::set_current_dir("..").unwrap();
+
+	let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+	glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
+	glfw.window_hint(glfw::WindowHint::Visible(false));
+	glfw.window_hint(glfw::WindowHint::Resizable(false));
+	let (window, _events) = glfw.create_window(WIDTH, HEIGHT, "Golden images", glfw::WindowMode::Windowed).unwrap();
+
+	let scenes: [(&str, SceneFn); 2] = [
+		("materials", materials_scene),
+		("text", text_scene)
+	];
+
+	let failures: Vec<String> = scenes.iter()
+		.filter_map(|(name, scene)| {
+			let mut render_system = RenderSystem::new(&glfw, &window);
+			let capture = scene(&mut render_system);
+			check_golden_image(name, &capture).err()
+		})
+		.collect();
+
+	assert!(failures.is_empty(), "Golden image mismatches:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn difference_tolerates_small_changes() {
+	let image = |pixels: Vec<u8>| FrameCapture { width: 2, height: 1, pixels };
+	let golden = image(vec![10, 20, 30, 255, 200, 200, 200, 255]);
+
+	assert_eq!(difference(&golden, &image(vec![12, 18, 30, 0, 200, 205, 195, 255])), 0.0);
+	assert_eq!(difference(&golden, &image(vec![10, 20, 30, 255, 0, 200, 200, 255])), 0.5);
+}
+
+#[test]
+fn ppm_round_trip() {
+	let path = env::temp_dir().join("engine_golden_image_round_trip.ppm");
+	let capture = FrameCapture { width: 2, height: 2, pixels: (0..16).map(|i| if i % 4 == 3 { 255 } else { i * 10 }).collect() };
+	write_ppm(&path, &capture);
+
+	let read = read_ppm(&path);
+	assert_eq!((read.width, read.height), (2, 2));
+	assert_eq!(read.pixels, capture.pixels);
+	fs::remove_file(path).unwrap();
+}
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::time::Duration;
-use engine::{Camera, glfw, math::{vector3, Euler, Order}};
+use engine::{Camera, Settings, glfw, math::{vector3, Euler, Order}};
 
 const TRANSLATION_SPEED: f32 = 2.5;
 const ROTATION_SPEED: f32 = 0.003;
@@ -8,17 +8,21 @@ const MAX_VERTICAL_ROTATION_ANGLE: f32 = 1.57;
 pub struct CameraController {
 	prev_mouse_pos_x: f32,
 	prev_mouse_pos_y: f32,
-	euler: Euler
+	euler: Euler,
+	// Forward, backward, left, right, up and down from the settings' key bindings
+	keys: [glfw::Key; 6]
 }
 
 impl CameraController {
-	pub fn new(window: &glfw::Window) -> Self {
+	pub fn new(window: &glfw::Window, settings: &Settings) -> Self {
 		let (mouse_pos_x, mouse_pos_y) = window.get_cursor_pos();
+		let key = |action: &str| settings.key(action).unwrap_or_else(|| panic!("Nothing is bound to {}", action));
 
 		Self {
 			prev_mouse_pos_x: mouse_pos_x as f32,
 			prev_mouse_pos_y: mouse_pos_y as f32,
-			euler: Euler::new(0.0, 0.0, 0.0, Order::Yxz)
+			euler: Euler::new(0.0, 0.0, 0.0, Order::Yxz),
+			keys: ["move_forward", "move_backward", "move_left", "move_right", "move_up", "move_down"].map(key)
 		}
 	}
 
@@ -32,27 +36,27 @@ impl CameraController {
 	pub fn update(&mut self, window: &glfw::Window, camera: &mut Camera, delta_time: &Duration) {
 		let mut translation_direction = vector3::ZERO;
 
-		if window.get_key(glfw::Key::W) == glfw::Action::Press {
+		if window.get_key(self.keys[0]) == glfw::Action::Press {
 			translation_direction.z = 1.0;
 		}
 
-		if window.get_key(glfw::Key::S) == glfw::Action::Press {
+		if window.get_key(self.keys[1]) == glfw::Action::Press {
 			translation_direction.z = -1.0;
 		}
 
-		if window.get_key(glfw::Key::A) == glfw::Action::Press {
+		if window.get_key(self.keys[2]) == glfw::Action::Press {
 			translation_direction.x = 1.0;
 		}
 
-		if window.get_key(glfw::Key::D) == glfw::Action::Press {
+		if window.get_key(self.keys[3]) == glfw::Action::Press {
 			translation_direction.x = -1.0;
 		}
 
-		if window.get_key(glfw::Key::E) == glfw::Action::Press {
+		if window.get_key(self.keys[4]) == glfw::Action::Press {
 			translation_direction.y = 1.0;
 		}
 
-		if window.get_key(glfw::Key::Q) == glfw::Action::Press {
+		if window.get_key(self.keys[5]) == glfw::Action::Press {
 			translation_direction.y = -1.0;
 		}
 
@@ -1,39 +1,115 @@
 use std::time::Duration;
 use engine::{
+	ApngVideo,
 	Camera,
+	ColorGradingLut,
+	Entity,
 	EntityManager,
 	Font,
+	FramePhase,
+	FrameTimings,
 	Geometry3D,
-	component::{ComponentList, MultiComponentList, Light, Mesh, MeshBoundsHelper, Text, TextComponentList, Transform2D, Transform2DComponentList, Transform3D, Transform3DComponentList, mesh::Material},
+	ParticleEmitter,
+	Settings,
+	Texture,
+	Vfs,
+	VideoPlayer,
+	component::{AnimatedSprite, BlobShadow, ComponentList, Draggable, DropTarget, MultiComponentList, HitArea2D, InputField, Light, light::{AmbientLight, Falloff, PointLight}, LightHelper, Mesh, MeshBoundsHelper, RenderLayer, ScrollView, Text, TextReveal, TextComponentList, Tilemap, Trail, Transform2D, Transform2DComponentList, Transform3D, Transform3DComponentList, mesh::Material},
 	glfw::{self, Glfw},
-	math::{Vector3, box3, vector3},
-	pool::Pool,
-	system::{MeshBoundsHelperSystem, RenderSystem}
+	math::{Color, Plane, Vector2, Vector3, box3, color, vector2, vector3},
+	pool::{Handle, Pool},
+	system::{BlobShadowSystem, DebugHelperSystem, DragDropSystem, Drop2D, HitTestSystem, InputFieldSystem, MeshBoundsHelperSystem, RenderScene, RenderSystem, RenderSystemSettings, ScrollViewSystem, SpriteAnimationSystem, TrailSystem}
 };
 use crate::{CameraController, component::RigidBody, system::{FrameMetricsSystem, PhysicsSystem}};
 
+const FRAME_TIMINGS_HISTORY: usize = 300;
+const SPRITE_FRAME_SIZE: usize = 16;
+const SPRITE_FRAME_COUNT: usize = 4;
+const CINEMATIC_ASPECT_RATIO: f32 = 21.0 / 9.0;
+const INVENTORY_ITEM_COUNT: usize = 12;
+const INVENTORY_ROW_HEIGHT: f32 = 30.0;
+const INVENTORY_DRAG_GROUP: u32 = 1;
+const TUTORIAL_CHARS_PER_SECOND: f32 = 30.0;
+const SETTINGS_PATH: &str = "game/settings.toml";
+
+// The defaults with whatever the player changed on top
+pub fn load_settings() -> Settings {
+	let mut settings = Settings::new();
+	settings.bind_key("move_forward", glfw::Key::W);
+	settings.bind_key("move_backward", glfw::Key::S);
+	settings.bind_key("move_left", glfw::Key::A);
+	settings.bind_key("move_right", glfw::Key::D);
+	settings.bind_key("move_up", glfw::Key::E);
+	settings.bind_key("move_down", glfw::Key::Q);
+
+	if let Err(error) = settings.load(SETTINGS_PATH) {
+		println!("{}, using the defaults", error);
+	}
+
+	settings
+}
+
 pub struct Game {
+	settings: Settings,
 	camera: Camera,
 	camera_controller: CameraController,
 	camera_controller_enabled: bool,
+	color_grading_enabled: bool,
 	geometries: Pool<Geometry3D>,
 	fonts: Pool<Font>,
+	textures: Pool<Texture>,
 	render_system: RenderSystem,
 	frame_metrics_system: FrameMetricsSystem,
+	frame_timings: FrameTimings,
 	physics_system: PhysicsSystem,
 	mesh_bounds_helper_system: MeshBoundsHelperSystem,
+	mesh_helper_batch_entity: Entity,
+	debug_helper_system: DebugHelperSystem,
+	grid_helper_entity: Entity,
+	input_field_system: InputFieldSystem,
+	sprite_animation_system: SpriteAnimationSystem,
+	input_field_entity: Entity,
+	hit_test_system: HitTestSystem,
+	scroll_view_system: ScrollViewSystem,
+	drag_drop_system: DragDropSystem,
+	trail_system: TrailSystem,
+	blob_shadow_system: BlobShadowSystem,
+	video_player: VideoPlayer,
+	video_texture: Handle,
+	inventory_entity: Entity,
+	inventory_slot_entity: Entity,
+	tutorial_entity: Entity,
 	text_components: TextComponentList,
 	transform2d_components: Transform2DComponentList,
 	light_components: ComponentList<Light>,
 	mesh_components: MultiComponentList<Mesh>,
 	transform3d_components: Transform3DComponentList,
 	rigid_body_components: ComponentList<RigidBody>,
-	mesh_bounds_helper_components: ComponentList<MeshBoundsHelper>
+	mesh_bounds_helper_components: ComponentList<MeshBoundsHelper>,
+	light_helper_components: ComponentList<LightHelper>,
+	input_field_components: ComponentList<InputField>,
+	hit_area2d_components: ComponentList<HitArea2D>,
+	scroll_view_components: ComponentList<ScrollView>,
+	draggable_components: ComponentList<Draggable>,
+	drop_target_components: ComponentList<DropTarget>,
+	trail_components: ComponentList<Trail>,
+	blob_shadow_components: ComponentList<BlobShadow>,
+	animated_sprite_components: ComponentList<AnimatedSprite>,
+	tilemap_components: ComponentList<Tilemap>
 }
 
 impl Game {
-	pub fn new(glfw: &Glfw, window: &glfw::Window) -> Self {
-		let mut render_system = RenderSystem::new(glfw, window);
+	pub fn new(glfw: &Glfw, window: &glfw::Window, settings: Settings) -> Self {
+		// Development builds read the game's assets straight from its directory instead of the pack
+		let mut vfs = Vfs::with_default_mounts().unwrap();
+
+		if cfg!(debug_assertions) {
+			vfs.mount_directory("game");
+		}
+
+		let render_system_settings = RenderSystemSettings { vsync: settings.vsync, vfs, ..RenderSystemSettings::default() };
+		let mut render_system = RenderSystem::with_settings(glfw, window, render_system_settings);
+		render_system.set_render_scale(settings.render_scale.clamp(0.5, 2.0));
 		let (extent_width, extent_height) = render_system.get_swapchain_extent();
 		let mut camera = Camera::new(extent_width as f32 / extent_height as f32, 75.0, 0.1, 50.0);
 		camera.transform.position.set(-5.0, 3.0, -5.0);
@@ -42,34 +118,153 @@ impl Game {
 
 		let mut geometries = Pool::<Geometry3D>::new();
 		let mut fonts = Pool::<Font>::new();
+		let mut textures = Pool::<Texture>::new();
 		let mut entity_manager = EntityManager::new();
 
 		let mut text_components = TextComponentList::new();
 		let mut transform2d_components = Transform2DComponentList::new();
-		let light_components = ComponentList::<Light>::new();
+		let mut light_components = ComponentList::<Light>::new();
 		let mut mesh_components = MultiComponentList::<Mesh>::new();
 		let mut transform3d_components = Transform3DComponentList::new();
 		let mut rigid_body_components = ComponentList::<RigidBody>::new();
 		let mut mesh_bounds_helper_components = ComponentList::<MeshBoundsHelper>::new();
+		let mut input_field_components = ComponentList::<InputField>::new();
+		let mut hit_area2d_components = ComponentList::<HitArea2D>::new();
+		let mut scroll_view_components = ComponentList::<ScrollView>::new();
+		let mut draggable_components = ComponentList::<Draggable>::new();
+		let mut drop_target_components = ComponentList::<DropTarget>::new();
+		let mut trail_components = ComponentList::<Trail>::new();
+		let mut blob_shadow_components = ComponentList::<BlobShadow>::new();
+		let mut animated_sprite_components = ComponentList::<AnimatedSprite>::new();
+		let mut tilemap_components = ComponentList::<Tilemap>::new();
 
 		let mut physics_system = PhysicsSystem::new();
-		let mut mesh_bounds_helper_system = MeshBoundsHelperSystem::new();
+		let mesh_bounds_helper_system = MeshBoundsHelperSystem::new();
+		let mut debug_helper_system = DebugHelperSystem::new();
+		let mut light_helper_components = ComponentList::<LightHelper>::new();
 
 		let label_entity = entity_manager.create();
-		let font_handle = fonts.add(Font::new("game/res/roboto.ttf", 14));
-		render_system.submit_fonts(&mut fonts);
+		let font_handle = fonts.add(Font::new(render_system.vfs(), "game/res/roboto.ttf", 14));
+		render_system.submit_fonts(&mut fonts).unwrap();
 		text_components.add(&mut entity_manager, label_entity, Text::new(font_handle, String::from("...")));
 		let mut transform = Transform2D::new();
 		transform.position.set(10.0, 20.0);
 		transform2d_components.add(&mut entity_manager, label_entity, transform);
 
-		let frame_metrics_system = FrameMetricsSystem::new(label_entity);
+		let render_stats_label_entity = entity_manager.create();
+		text_components.add(&mut entity_manager, render_stats_label_entity, Text::new(font_handle, String::from("...")));
+		let mut transform = Transform2D::new();
+		transform.position.set(10.0, 40.0);
+		transform2d_components.add(&mut entity_manager, render_stats_label_entity, transform);
+
+		let frame_timings_label_entity = entity_manager.create();
+		text_components.add(&mut entity_manager, frame_timings_label_entity, Text::new(font_handle, String::new()));
+		let mut transform = Transform2D::new();
+		transform.position.set(10.0, 80.0);
+		transform2d_components.add(&mut entity_manager, frame_timings_label_entity, transform);
+
+		let frame_metrics_system = FrameMetricsSystem::new(label_entity, render_stats_label_entity, frame_timings_label_entity);
+
+		let input_field_entity = entity_manager.create();
+		text_components.add(&mut entity_manager, input_field_entity, Text::new(font_handle, String::new()));
+		let mut transform = Transform2D::new();
+		transform.position.set(10.0, 50.0);
+		transform2d_components.add(&mut entity_manager, input_field_entity, transform);
+		input_field_components.add(&mut entity_manager, input_field_entity, InputField::new(color::WHITE, Color::from_srgb(0.2, 0.4, 0.9, 0.5)));
+		hit_area2d_components.add(&mut entity_manager, input_field_entity, HitArea2D::new(Vector2::new(0.0, -30.0), Vector2::new(300.0, 10.0)));
+
+		// An inventory which scrolls and whose items can be dragged into the slot below it
+		let inventory_entity = entity_manager.create();
+		let mut transform = Transform2D::new();
+		transform.position.set(900.0, 100.0);
+		transform2d_components.add(&mut entity_manager, inventory_entity, transform);
+		let mut inventory = ScrollView::new(Vector2::new(300.0, 150.0), Vector2::new(300.0, (INVENTORY_ITEM_COUNT + 1) as f32 * INVENTORY_ROW_HEIGHT));
+		hit_area2d_components.add(&mut entity_manager, inventory_entity, HitArea2D::new(vector2::ZERO, inventory.size));
+
+		for i in 0..INVENTORY_ITEM_COUNT {
+			let item_entity = entity_manager.create();
+			let item_name = format!("Item {}", i + 1);
+
+			// The hit area is sized to the label
+			let font = fonts.borrow(font_handle);
+			let (width, height, baseline) = font.measure(&item_name, font.size as f32);
+			let mut hit_area = HitArea2D::new(Vector2::new(0.0, -baseline), Vector2::new(width, height - baseline));
+			hit_area.order = 1;
+
+			text_components.add(&mut entity_manager, item_entity, Text::new(font_handle, item_name));
+			transform2d_components.add(&mut entity_manager, item_entity, Transform2D::new());
+			hit_area2d_components.add(&mut entity_manager, item_entity, hit_area);
+			draggable_components.add(&mut entity_manager, item_entity, Draggable::new(INVENTORY_DRAG_GROUP));
+			inventory.content.push((item_entity, Vector2::new(0.0, (i + 1) as f32 * INVENTORY_ROW_HEIGHT)));
+		}
+
+		scroll_view_components.add(&mut entity_manager, inventory_entity, inventory);
+
+		let inventory_slot_entity = entity_manager.create();
+		text_components.add(&mut entity_manager, inventory_slot_entity, Text::new(font_handle, String::from("Slot:")));
+		let mut transform = Transform2D::new();
+		transform.position.set(900.0, 300.0);
+		transform2d_components.add(&mut entity_manager, inventory_slot_entity, transform);
+		hit_area2d_components.add(&mut entity_manager, inventory_slot_entity, HitArea2D::new(Vector2::new(0.0, -INVENTORY_ROW_HEIGHT), Vector2::new(300.0, 0.0)));
+		drop_target_components.add(&mut entity_manager, inventory_slot_entity, DropTarget::new(INVENTORY_DRAG_GROUP));
+
+		// Typed out a character at a time with each one rising into place
+		let tutorial_entity = entity_manager.create();
+		let mut tutorial_text = Text::new(font_handle, String::from("Scroll the inventory and drag its items into the slot"));
+		let mut reveal = TextReveal::new();
+		reveal.fade_length = 5.0;
+		reveal.offset.set(0.0, 10.0);
+		tutorial_text.reveal = Some(reveal);
+		text_components.add(&mut entity_manager, tutorial_entity, tutorial_text);
+		let mut transform = Transform2D::new();
+		transform.position.set(900.0, 350.0);
+		transform2d_components.add(&mut entity_manager, tutorial_entity, transform);
+
+		// A square which fills up from the bottom a quarter at a time and starts over
+		let sprite_entity = entity_manager.create();
+		let mut sprite_texture = Texture::new(SPRITE_FRAME_SIZE * SPRITE_FRAME_COUNT, SPRITE_FRAME_SIZE, sprite_atlas_pixels());
+		render_system.submit_texture(&mut sprite_texture).unwrap();
+		let sprite_texture = textures.add(sprite_texture);
+		let atlas_width = (SPRITE_FRAME_SIZE * SPRITE_FRAME_COUNT) as f32;
+		let sprite = AnimatedSprite::from_grid(sprite_texture, atlas_width, SPRITE_FRAME_SIZE as f32, SPRITE_FRAME_COUNT, 1, SPRITE_FRAME_COUNT, 4.0);
+		animated_sprite_components.add(&mut entity_manager, sprite_entity, sprite);
+		let mut transform = Transform2D::new();
+		transform.position.set(10.0, 120.0);
+		transform.scale.set(4.0, 4.0);
+		transform2d_components.add(&mut entity_manager, sprite_entity, transform);
+
+		// A floor of tiles from the same atlas under the sprite
+		let tilemap_entity = entity_manager.create();
+		let mut tilemap = Tilemap::new(sprite_texture, 8, 2, SPRITE_FRAME_SIZE as f32, SPRITE_FRAME_SIZE as f32, atlas_width, SPRITE_FRAME_SIZE as f32);
+		for x in 0..tilemap.width() {
+			tilemap.set_tile(x, 0, Some(1));
+			tilemap.set_tile(x, 1, Some(SPRITE_FRAME_COUNT as u16 - 1));
+		}
+		tilemap_components.add(&mut entity_manager, tilemap_entity, tilemap);
+		let mut transform = Transform2D::new();
+		transform.position.set(10.0, 190.0);
+		transform2d_components.add(&mut entity_manager, tilemap_entity, transform);
+
+		// A screen looping a video next to the sprite, each frame is streamed into its texture
+		let mut video_player = VideoPlayer::new(Box::new(ApngVideo::from_bytes(render_system.vfs().read("res/screen.png").unwrap()).unwrap()));
+		video_player.looping = true;
+		let mut video_texture = video_player.create_texture();
+		render_system.submit_texture(&mut video_texture).unwrap();
+		let video_texture = textures.add(video_texture);
+		let (video_width, video_height) = (video_player.width() as f32, video_player.height() as f32);
+		let screen_entity = entity_manager.create();
+		let screen = AnimatedSprite::from_grid(video_texture, video_width, video_height, 1, 1, 1, 1.0);
+		animated_sprite_components.add(&mut entity_manager, screen_entity, screen);
+		let mut transform = Transform2D::new();
+		transform.position.set(90.0, 120.0);
+		transform.scale.set(2.0, 2.0);
+		transform2d_components.add(&mut entity_manager, screen_entity, transform);
 
-		let box_1_bounds_helper = entity_manager.create();
-		transform3d_components.add(&mut entity_manager, box_1_bounds_helper, Transform3D::new());
+		let mesh_helper_batch_entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, mesh_helper_batch_entity, Transform3D::new());
 		let geometry_handle = geometries.add(Geometry3D::create_box_helper(&box3::DEFAULT_SQUARE));
-		let index = mesh_components.add(Mesh { geometry_handle, material: Material::Line });
-		mesh_components.assign(&mut entity_manager, box_1_bounds_helper, index);
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Line));
+		mesh_components.assign(&mut entity_manager, mesh_helper_batch_entity, index);
 
 		let box_1 = entity_manager.create();
 		let mut transform = Transform3D::new();
@@ -79,43 +274,248 @@ impl Game {
 		transform.scale.set_from_scalar(0.5);
 		transform3d_components.add(&mut entity_manager, box_1, transform);
 		let geometry_handle = geometries.add(Geometry3D::create_box());
-		let index = mesh_components.add(Mesh { geometry_handle, material: Material::Normal });
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Normal));
 		mesh_components.assign(&mut entity_manager, box_1, index);
 		rigid_body_components.add(&mut entity_manager, box_1, RigidBody { velocity: vector3::ZERO, acceleration: Vector3::new(0.0, -0.00001, 0.0) });
-		mesh_bounds_helper_components.add(&mut entity_manager, box_1, MeshBoundsHelper { bounds_entity: box_1_bounds_helper });
+		mesh_bounds_helper_components.add(&mut entity_manager, box_1, MeshBoundsHelper::new());
 		physics_system.entities.push(box_1);
-		mesh_bounds_helper_system.entities.push(box_1);
+		trail_components.add(&mut entity_manager, box_1, Trail::new());
+		blob_shadow_components.add(&mut entity_manager, box_1, BlobShadow::new(0.6));
 
 		let plane = entity_manager.create();
 		let mut transform = Transform3D::new();
 		transform.scale.set_from_scalar(10.0);
 		transform3d_components.add(&mut entity_manager, plane, transform);
 		let geometry_handle = geometries.add(Geometry3D::create_plane());
-		let index = mesh_components.add(Mesh { geometry_handle, material: Material::Normal });
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Lambert));
 		mesh_components.assign(&mut entity_manager, plane, index);
 
+		let mut blob_shadow_system = BlobShadowSystem::new();
+		blob_shadow_system.add_ground(geometries.borrow(geometry_handle), transform3d_components.borrow(&plane).global_matrix());
+
+		let glass_sphere = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(-2.0, 1.0, 0.0);
+		transform3d_components.add(&mut entity_manager, glass_sphere, transform);
+		let geometry_handle = geometries.add(Geometry3D::create_sphere(32, 16));
+		let mut mesh = Mesh::new(geometry_handle, Material::Refractive);
+		mesh.layer = RenderLayer::Transparent;
+		let index = mesh_components.add(mesh);
+		mesh_components.assign(&mut entity_manager, glass_sphere, index);
+
+		// A pool of still water just above the ground, the reflection plane lies on its surface
+		let water = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(3.0, 0.01, -2.0);
+		transform.scale.set_from_scalar(1.5);
+		transform3d_components.add(&mut entity_manager, water, transform);
+		let geometry_handle = geometries.add(Geometry3D::create_plane());
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Reflective));
+		mesh_components.assign(&mut entity_manager, water, index);
+		render_system.set_reflection_plane(Some(Plane::new(Vector3::new(0.0, 1.0, 0.0), -0.01)));
+
+		let ambient_light = entity_manager.create();
+		light_components.add(&mut entity_manager, ambient_light, Light::AmbientLight(AmbientLight { color: color::WHITE, intensity: 0.2 }));
+
+		let point_light_helper = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, point_light_helper, Transform3D::new());
+		let geometry_handle = geometries.add(Geometry3D::create_sphere_helper());
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Line));
+		mesh_components.assign(&mut entity_manager, point_light_helper, index);
+
+		let point_light = entity_manager.create();
+		let mut transform = Transform3D::new();
+		transform.position.set(2.0, 2.0, 2.0);
+		transform3d_components.add(&mut entity_manager, point_light, transform);
+		let mut light = PointLight::new(Color::rgb(1.0, 0.9, 0.7), 1.0);
+		light.range = 6.0;
+		light.falloff = Falloff::Linear;
+		light_components.add(&mut entity_manager, point_light, Light::PointLight(light));
+		light_helper_components.add(&mut entity_manager, point_light, LightHelper { helper_entity: point_light_helper });
+		debug_helper_system.light_entities.push(point_light);
+
+		let grid_helper_entity = entity_manager.create();
+		transform3d_components.add(&mut entity_manager, grid_helper_entity, Transform3D::new());
+		let geometry_handle = geometries.add(Geometry3D::create_grid_helper(0.0, 0.0, debug_helper_system.grid_half_size, debug_helper_system.grid_spacing, debug_helper_system.grid_major_every));
+		let index = mesh_components.add(Mesh::new(geometry_handle, Material::Line));
+		mesh_components.assign(&mut entity_manager, grid_helper_entity, index);
+
+		// A fountain of sparks which fall back down and fade out
+		let mut fountain = ParticleEmitter::new(Vector3::new(2.0, 0.0, -2.0));
+		fountain.velocity.set(0.0, 4.0, 0.0);
+		fountain.spread = 1.0;
+		fountain.acceleration.set(0.0, -9.8, 0.0);
+		fountain.start_color = Color::new(1.0, 0.6, 0.2, 1.0);
+		fountain.end_color = Color::new(1.0, 0.1, 0.0, 0.0);
+		fountain.start_size = 0.05;
+		fountain.end_size = 0.02;
+		fountain.rate = 2000.0;
+		render_system.particle_emitters_mut().add(fountain);
+
 		Self {
+			camera_controller: CameraController::new(window, &settings),
+			settings,
 			camera,
-			camera_controller: CameraController::new(window),
 			camera_controller_enabled: false,
+			color_grading_enabled: false,
 			geometries,
 			fonts,
+			textures,
 			render_system,
 			frame_metrics_system,
+			frame_timings: FrameTimings::new(FRAME_TIMINGS_HISTORY),
 			physics_system,
 			mesh_bounds_helper_system,
+			mesh_helper_batch_entity,
+			debug_helper_system,
+			grid_helper_entity,
+			input_field_system: InputFieldSystem::new(),
+			sprite_animation_system: SpriteAnimationSystem::new(),
+			input_field_entity,
+			hit_test_system: HitTestSystem::new(),
+			scroll_view_system: ScrollViewSystem::new(),
+			drag_drop_system: DragDropSystem::new(),
+			trail_system: TrailSystem::new(),
+			blob_shadow_system,
+			video_player,
+			video_texture,
+			inventory_entity,
+			inventory_slot_entity,
+			tutorial_entity,
 			text_components,
 			transform2d_components,
 			light_components,
 			mesh_components,
 			transform3d_components,
 			rigid_body_components,
-			mesh_bounds_helper_components
+			mesh_bounds_helper_components,
+			light_helper_components,
+			input_field_components,
+			hit_area2d_components,
+			scroll_view_components,
+			draggable_components,
+			drop_target_components,
+			trail_components,
+			blob_shadow_components,
+			animated_sprite_components,
+			tilemap_components
 		}
 	}
 
 	pub fn handle_event(&mut self, event: &glfw::WindowEvent, window: &mut glfw::Window) {
+		if self.input_field_system.handle_event(event, window, &mut self.input_field_components, &mut self.text_components, &self.fonts) {
+			return;
+		}
+
 		match event {
+			glfw::WindowEvent::Key(glfw::Key::Minus, _, glfw::Action::Press, _) => {
+				let render_scale = (self.render_system.render_scale() - 0.25).max(0.5);
+				self.render_system.set_render_scale(render_scale);
+				self.settings.render_scale = render_scale;
+				self.save_settings();
+			},
+			glfw::WindowEvent::Key(glfw::Key::Equal, _, glfw::Action::Press, _) => {
+				let render_scale = (self.render_system.render_scale() + 0.25).min(2.0);
+				self.render_system.set_render_scale(render_scale);
+				self.settings.render_scale = render_scale;
+				self.save_settings();
+			},
+			glfw::WindowEvent::Key(glfw::Key::V, _, glfw::Action::Press, _) => {
+				self.settings.vsync = !self.settings.vsync;
+				self.render_system.set_vsync(self.settings.vsync);
+				self.save_settings();
+			},
+			glfw::WindowEvent::Key(glfw::Key::O, _, glfw::Action::Press, _) => {
+				let ssao_enabled = !self.render_system.ssao_enabled();
+				self.render_system.set_ssao_enabled(ssao_enabled);
+			},
+			glfw::WindowEvent::Key(glfw::Key::P, _, glfw::Action::Press, _) => {
+				let depth_prepass_enabled = !self.render_system.depth_prepass_enabled();
+				self.render_system.set_depth_prepass_enabled(depth_prepass_enabled);
+			},
+			glfw::WindowEvent::Key(glfw::Key::B, _, glfw::Action::Press, _) => {
+				let motion_blur_strength = if self.render_system.motion_blur_strength() > 0.0 { 0.0 } else { 0.5 };
+				self.render_system.set_motion_blur_strength(motion_blur_strength);
+			},
+			glfw::WindowEvent::Key(glfw::Key::Y, _, glfw::Action::Press, _) => {
+				let taa_enabled = !self.render_system.taa_enabled();
+				self.render_system.set_taa_enabled(taa_enabled);
+			},
+			glfw::WindowEvent::Key(glfw::Key::F, _, glfw::Action::Press, _) => {
+				let deferred_enabled = !self.render_system.deferred_enabled();
+				self.render_system.set_deferred_enabled(deferred_enabled);
+			},
+			glfw::WindowEvent::Key(glfw::Key::L, _, glfw::Action::Press, _) => {
+				self.camera.fixed_aspect_ratio = match self.camera.fixed_aspect_ratio {
+					Some(_) => None,
+					None => Some(CINEMATIC_ASPECT_RATIO)
+				};
+
+				let (extent_width, extent_height) = self.render_system.get_swapchain_extent();
+				self.camera.projection_matrix.make_perspective(self.camera.aspect_ratio(extent_width, extent_height), 75.0, 0.1, 50.0);
+			},
+			glfw::WindowEvent::Key(glfw::Key::G, _, glfw::Action::Press, _) => {
+				self.color_grading_enabled = !self.color_grading_enabled;
+
+				// Fade to a warm grade or back to the identity
+				let mut lut = ColorGradingLut::identity(16);
+
+				if self.color_grading_enabled {
+					for entry in &mut lut.data {
+						entry[0] = (entry[0] * 1.1).min(1.0);
+						entry[2] *= 0.8;
+					}
+				}
+
+				self.render_system.blend_to_color_grading_lut(&lut, Duration::from_secs(2));
+			},
+			glfw::WindowEvent::Key(glfw::Key::T, _, glfw::Action::Press, _) => {
+				self.frame_metrics_system.frame_timings_enabled = !self.frame_metrics_system.frame_timings_enabled;
+			},
+			glfw::WindowEvent::Key(glfw::Key::H, _, glfw::Action::Press, _) => {
+				self.debug_helper_system.enabled = !self.debug_helper_system.enabled;
+			},
+			glfw::WindowEvent::Key(glfw::Key::Enter, _, glfw::Action::Press, _) => {
+				self.input_field_system.focus(self.input_field_entity);
+			},
+			glfw::WindowEvent::MouseButton(glfw::MouseButton::Button1, glfw::Action::Press, _) if !self.camera_controller_enabled => {
+				let cursor_position = cursor_position(window);
+
+				if self.drag_drop_system.begin(&cursor_position, &self.hit_test_system, &self.hit_area2d_components, &self.draggable_components, &self.transform2d_components) {
+					return;
+				}
+
+				self.hit_test_system.update(&cursor_position, &self.hit_area2d_components, &self.transform2d_components);
+
+				if self.hit_test_system.hovered_entity() == Some(self.input_field_entity) {
+					self.input_field_system.focus(self.input_field_entity);
+				}
+			},
+			glfw::WindowEvent::MouseButton(glfw::MouseButton::Button1, glfw::Action::Release, _) => {
+				let drop = self.drag_drop_system.end(
+					&cursor_position(window),
+					&self.hit_test_system,
+					&self.hit_area2d_components,
+					&self.draggable_components,
+					&self.drop_target_components,
+					&mut self.transform2d_components);
+
+				// Items dropped in the slot are taken out of the inventory and placed after the slot's label
+				if let Some(Drop2D { entity, target: Some(_) }) = drop {
+					self.scroll_view_components.borrow_mut(&self.inventory_entity).content.retain(|(content_entity, _)| *content_entity != entity);
+					let mut slot_position = self.transform2d_components.borrow(&self.inventory_slot_entity).position;
+					slot_position.x += 60.0;
+					self.transform2d_components.borrow_mut(&entity).position = slot_position;
+					self.transform2d_components.update(&entity);
+				}
+			},
+			glfw::WindowEvent::Scroll(_, y_offset) => {
+				self.hit_test_system.update(&cursor_position(window), &self.hit_area2d_components, &self.transform2d_components);
+
+				if let Some(scroll_view) = self.hit_test_system.hovered_entity().and_then(|entity| self.scroll_view_components.try_borrow_mut(&entity)) {
+					scroll_view.scroll(&Vector2::new(0.0, -*y_offset as f32 * INVENTORY_ROW_HEIGHT));
+				}
+			},
 			glfw::WindowEvent::Key(glfw::Key::Tab, _, glfw::Action::Press, _) => {
 				self.camera_controller_enabled = !self.camera_controller_enabled;
 
@@ -131,27 +531,121 @@ impl Game {
 		}
 	}
 
+	// Stands in for an options menu, every setting changed in game is saved straight away
+	fn save_settings(&self) {
+		if let Err(error) = self.settings.save(SETTINGS_PATH) {
+			println!("{}", error);
+		}
+	}
+
 	pub fn handle_resize(&mut self, width: i32, height: i32) {
 		let (extent_width, extent_height) = self.render_system.recreate_swapchain(width, height);
-		self.camera.projection_matrix.make_perspective(extent_width as f32 / extent_height as f32, 75.0, 0.1, 50.0);
+		self.camera.projection_matrix.make_perspective(self.camera.aspect_ratio(extent_width, extent_height), 75.0, 0.1, 50.0);
 	}
 
 	pub fn update(&mut self, window: &glfw::Window, delta_time: &Duration) {
-		self.frame_metrics_system.update(&mut self.text_components, delta_time);
+		self.frame_metrics_system.update(&mut self.text_components, delta_time, self.render_system.stats(), &self.frame_timings);
 
 		if self.camera_controller_enabled {
 			self.camera_controller.update(window, &mut self.camera, delta_time);
 		}
 
 		self.physics_system.update(&mut self.transform3d_components, &mut self.rigid_body_components);
-		self.mesh_bounds_helper_system.update(&mut self.transform3d_components, &self.mesh_components, &mut self.geometries, &self.mesh_bounds_helper_components);
+		self.trail_system.update(delta_time, &mut self.trail_components, &self.transform3d_components);
+		self.blob_shadow_system.update(&mut self.blob_shadow_components, &self.transform3d_components);
+		self.mesh_bounds_helper_system.update(self.mesh_helper_batch_entity, &self.transform3d_components, &mut self.mesh_components, &mut self.geometries, &self.mesh_bounds_helper_components);
+		self.debug_helper_system.update_lights(&mut self.transform3d_components, &self.light_components, &mut self.mesh_components, &self.light_helper_components);
+		self.debug_helper_system.update_grid(&self.camera, self.grid_helper_entity, &mut self.mesh_components, &mut self.geometries);
+		self.input_field_system.update(delta_time, &mut self.input_field_components, &self.text_components, &self.fonts);
+		let tutorial_text = self.text_components.borrow(&self.tutorial_entity);
+
+		if let Some(reveal) = tutorial_text.reveal.filter(|reveal| !reveal.finished(&tutorial_text.string)) {
+			let progress = reveal.progress + delta_time.as_secs_f32() * TUTORIAL_CHARS_PER_SECOND;
+			self.text_components.borrow_mut(self.tutorial_entity).reveal.as_mut().unwrap().progress = progress;
+		}
+
+		self.sprite_animation_system.update(delta_time, &mut self.animated_sprite_components);
+		let video_texture = self.textures.borrow_mut(self.video_texture);
+
+		match self.video_player.update(delta_time.as_secs_f32(), video_texture) {
+			Ok(true) => self.render_system.update_texture(video_texture),
+			Ok(false) => (),
+			Err(error) => {
+				println!("{}", error);
+				self.video_player.playing = false;
+			}
+		}
+
+		self.render_system.wind_mut().update(delta_time.as_secs_f32());
+		self.render_system.update_particles(delta_time.as_secs_f32());
+		self.scroll_view_system.update(&self.scroll_view_components, &mut self.transform2d_components, &mut self.text_components, &mut self.hit_area2d_components);
+		self.drag_drop_system.update(&cursor_position(window), &mut self.transform2d_components, &mut self.text_components, &mut self.hit_area2d_components);
 		
 		self.text_components.generate_dirties(&self.fonts);
+
+		for (_, tilemap) in self.tilemap_components.iter_mut() {
+			tilemap.generate_dirty_chunks();
+		}
+
 		self.transform2d_components.check_for_dirties();
 		self.transform3d_components.check_for_dirties();
 	}
 
 	pub fn render(&mut self) -> bool {
-		self.render_system.render(&self.camera, &self.light_components, &self.geometries, &self.mesh_components, &self.transform3d_components, &self.fonts, &self.text_components, &self.transform2d_components)
+		self.render_system.render(&RenderScene {
+			camera: &self.camera,
+			light_components: &self.light_components,
+			geometries: &self.geometries,
+			mesh_components: &self.mesh_components,
+			transform3d_components: &self.transform3d_components,
+			trail_components: &self.trail_components,
+			blob_shadow_components: &self.blob_shadow_components,
+			fonts: &self.fonts,
+			text_components: &self.text_components,
+			transform2d_components: &self.transform2d_components,
+			textures: &self.textures,
+			animated_sprite_components: &self.animated_sprite_components,
+			tilemap_components: &self.tilemap_components,
+			input_field_components: &self.input_field_components
+		})
 	}
+
+	// Collects the CPU time spent in each phase of the frame which just finished
+	pub fn end_frame(&mut self, event_poll_time: Duration, update_time: Duration) {
+		self.frame_timings.add(FramePhase::EventPoll, event_poll_time);
+		self.frame_timings.add(FramePhase::Update, update_time);
+
+		for (phase, duration) in self.render_system.cpu_timings() {
+			self.frame_timings.add(phase, duration);
+		}
+
+		self.frame_timings.end_frame();
+	}
+}
+
+// The frames of the demo sprite side by side, each one filled a quarter further from the bottom
+fn sprite_atlas_pixels() -> Vec<u8> {
+	let width = SPRITE_FRAME_SIZE * SPRITE_FRAME_COUNT;
+	let mut pixels = Vec::with_capacity(width * SPRITE_FRAME_SIZE * 4);
+
+	for y in 0..SPRITE_FRAME_SIZE {
+		for x in 0..width {
+			let frame = x / SPRITE_FRAME_SIZE;
+			let filled = SPRITE_FRAME_SIZE - y <= (frame + 1) * SPRITE_FRAME_SIZE / SPRITE_FRAME_COUNT;
+			pixels.extend_from_slice(if filled { &[80, 200, 120, 255] } else { &[40, 40, 40, 160] });
+		}
+	}
+
+	pixels
+}
+
+// The cursor is in screen coordinates which differ from the framebuffer's pixels on high DPI displays
+fn cursor_position(window: &glfw::Window) -> Vector2 {
+	let (cursor_x, cursor_y) = window.get_cursor_pos();
+	let (window_width, window_height) = window.get_size();
+	let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+
+	Vector2::new(
+		(cursor_x * framebuffer_width as f64 / window_width as f64) as f32,
+		(cursor_y * framebuffer_height as f64 / window_height as f64) as f32)
 }
\ No newline at end of file
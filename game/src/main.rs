@@ -14,13 +14,25 @@ const MAX_FRAME_TIME: f64 = 1.0 / 10.0;
 const MAX_UPDATES_PER_FRAME: u32 = 5;
 
 fn main() {
+	let settings = game::load_settings();
+
 	let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
 	glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
-	let (mut window, events) = glfw.create_window(1280, 720, "Vulkan", glfw::WindowMode::Windowed).unwrap();
+	let (mut window, events) = glfw.with_primary_monitor(|glfw, monitor| {
+		let window_mode = match monitor {
+			Some(monitor) if settings.fullscreen => glfw::WindowMode::FullScreen(monitor),
+			_ => glfw::WindowMode::Windowed
+		};
+
+		glfw.create_window(settings.window_width, settings.window_height, "Vulkan", window_mode)
+	}).unwrap();
 	window.set_framebuffer_size_polling(true);
 	window.set_key_polling(true);
+	window.set_char_polling(true);
+	window.set_mouse_button_polling(true);
+	window.set_scroll_polling(true);
 
-	let mut game = Game::new(&glfw, &window);
+	let mut game = Game::new(&glfw, &window, settings);
 
 	let duration_zero = Duration::new(0, 0);
 	let max_duration = Duration::from_secs_f64(MAX_FRAME_TIME);
@@ -34,6 +46,7 @@ fn main() {
 
 	while !window.should_close() {
 		resized = false;
+		let event_poll_start = Instant::now();
 		glfw.poll_events();
 
 		for (_, event) in glfw::flush_messages(&events) {
@@ -59,6 +72,8 @@ fn main() {
 			game.handle_event(&event, &mut window);
 		}
 
+		let event_poll_time = event_poll_start.elapsed();
+
 		if minimized {
 			glfw.wait_events();
 			continue;
@@ -72,6 +87,7 @@ fn main() {
 		let mut duration = frame_end.duration_since(frame_start);
 		frame_start = frame_end;
 		let mut updates = 0;
+		let update_start = Instant::now();
 
 		while duration > duration_zero && updates <= MAX_UPDATES_PER_FRAME {
 			let duration_capped = duration.min(max_duration);
@@ -82,6 +98,8 @@ fn main() {
 			updates += 1;
 		}
 
+		let update_time = update_start.elapsed();
 		surface_changed = game.render();
+		game.end_frame(event_poll_time, update_time);
 	}
 }
\ No newline at end of file
@@ -1,12 +1,15 @@
 use std::time::Duration;
 
-use engine::{Entity, component::TextComponentList};
+use engine::{Entity, FrameTimings, component::TextComponentList, system::RenderStats};
 
 const UPDATE_INTERVAL_SECONDS: f32 = 0.5;
 const MAX_SAMPLED_FRAMES: usize = 100;
 
 pub struct FrameMetricsSystem {
 	label_entity: Entity,
+	render_stats_label_entity: Entity,
+	frame_timings_label_entity: Entity,
+	pub frame_timings_enabled: bool,
 	update_interval: Duration,
 	duration: Duration,
 	fps_sampled_frames: usize,
@@ -16,9 +19,12 @@ pub struct FrameMetricsSystem {
 }
 
 impl FrameMetricsSystem {
-	pub fn new(label_entity: Entity) -> Self {
+	pub fn new(label_entity: Entity, render_stats_label_entity: Entity, frame_timings_label_entity: Entity) -> Self {
 		Self {
 			label_entity,
+			render_stats_label_entity,
+			frame_timings_label_entity,
+			frame_timings_enabled: false,
 			update_interval: Duration::from_secs_f32(UPDATE_INTERVAL_SECONDS),
 			duration: Duration::new(0, 0),
 			fps_sampled_frames: 0,
@@ -28,7 +34,7 @@ impl FrameMetricsSystem {
 		}
 	}
 
-	pub fn update(&mut self, text_component_list: &mut TextComponentList, delta_time: &Duration) {
+	pub fn update(&mut self, text_component_list: &mut TextComponentList, delta_time: &Duration, render_stats: RenderStats, frame_timings: &FrameTimings) {
 		self.fps_sampled_frames += 1;
 
 		self.frame_times[self.current_frame] = delta_time.as_micros() as u32;
@@ -55,6 +61,8 @@ impl FrameMetricsSystem {
 
 			let string = format!("{:.1}fps {:.1}ms avg {:.1}ms max", fps, average, max);
 			text_component_list.borrow_mut(self.label_entity).string = string;
+			text_component_list.borrow_mut(self.render_stats_label_entity).string = render_stats.to_string();
+			text_component_list.borrow_mut(self.frame_timings_label_entity).string = if self.frame_timings_enabled { frame_timings.summary() } else { String::new() };
 			
 			self.duration = Duration::new(0, 0);
 			self.fps_sampled_frames = 0;